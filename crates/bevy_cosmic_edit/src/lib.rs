@@ -2,11 +2,15 @@ use std::{cmp, path::PathBuf};
 
 use bevy::{
     prelude::*,
-    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    window::{PrimaryWindow, WindowScaleFactorChanged},
+    render::{
+        camera::RenderTarget,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    window::{PrimaryWindow, WindowRef, WindowScaleFactorChanged},
 };
 use cosmic_text::{
-    Action, Affinity, Attrs, Buffer, Cursor, Edit, Editor, FontSystem, Metrics, SwashCache,
+    Action, Affinity, Attrs, AttrsList, Buffer, BufferLine, Cursor, Edit, Editor, FontSystem,
+    Metrics, SwashCache,
 };
 use image::{ImageBuffer, RgbaImage};
 
@@ -18,9 +22,16 @@ pub struct CosmicEditMeta<'a> {
     pub initial_background: Option<UiImage>,
     pub font_size: f32,
     pub line_height: f32,
+    /// Font face and color of the whole text.
+    pub attrs: Attrs<'a>,
     pub scale_factor: f32,
     pub font_system: &'a mut FontSystem,
     pub is_visible: bool,
+    /// Pastes clipboard text at every caret on Cmd/Ctrl+V (native only). Leave it off when the
+    /// app handles paste itself.
+    pub paste: bool,
+    /// Renders every character as `mask_char`, e.g. for password inputs.
+    pub mask_char: Option<char>,
 }
 
 /// Enum representing the position of the cosmic text.
@@ -34,10 +45,60 @@ pub enum CosmicTextPos {
 pub struct CosmicEditImage {
     pub editor: Editor,
     pub text_pos: CosmicTextPos,
+    /// Additional carets placed with Alt+Click or Cmd/Ctrl+D, the primary caret lives in `editor`.
+    pub extra_cursors: Vec<ExtraCursor>,
+    /// Whether Cmd/Ctrl+V pastes clipboard text at every caret.
+    pub paste: bool,
+    /// Character rendered in place of the actual text, `get_cosmic_text` still returns the real text.
+    pub mask_char: Option<char>,
+    /// Masked copy of `editor` laid out for rendering and hit tests, kept while it's up to date.
+    masked_editor: Option<(MaskedState, Editor)>,
     font_size: f32,
     font_line_height: f32,
 }
 
+/// What a masked editor was built from, it's rebuilt when any of it changes. Only the number of
+/// characters of each line is kept, not the masked text itself.
+#[derive(PartialEq)]
+struct MaskedState {
+    line_chars: Vec<usize>,
+    cursor: Cursor,
+    select_opt: Option<Cursor>,
+    size: (f32, f32),
+    metrics: (f32, f32),
+}
+
+impl MaskedState {
+    fn of(editor: &Editor) -> Self {
+        let buffer = editor.buffer();
+        Self {
+            line_chars: buffer
+                .lines
+                .iter()
+                .map(|line| line.text().chars().count())
+                .collect(),
+            cursor: editor.cursor(),
+            select_opt: editor.select_opt(),
+            size: buffer.size(),
+            metrics: (buffer.metrics().font_size, buffer.metrics().line_height),
+        }
+    }
+}
+
+/// Cosmic edit shown by a sprite in the world instead of a UI node. Text is laid out and
+/// rendered at `size` (logical pixels), the `custom_size` of the sprite decides how big it shows.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct CosmicEditSprite {
+    pub size: Vec2,
+}
+
+/// Additional caret with an optional selection anchor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExtraCursor {
+    pub cursor: Cursor,
+    pub select_opt: Option<Cursor>,
+}
+
 /// Plugin struct that adds systems and initializes resources related to cosmic edit functionality.
 pub struct CosmicEditPlugin;
 
@@ -49,6 +110,7 @@ impl Plugin for CosmicEditPlugin {
                 cosmic_edit_redraw_buffer,
                 active_editor_changed,
                 scale_factor_changed,
+                cosmic_edit_sprite_resized.before(cosmic_edit_redraw_buffer),
             ))
             .init_resource::<FontSystemState>()
             .init_resource::<SwashCacheState>()
@@ -83,6 +145,8 @@ pub struct CosmicFontConfig {
     pub sans_serif_family: Option<String>,
     pub serif_family: Option<String>,
     pub custom_font_data: Option<CustomCosmicFont>,
+    /// Other faces of the custom font family (bold, italic), selected with `Attrs`.
+    pub additional_font_data: Vec<&'static [u8]>,
 }
 
 #[derive(Resource, Default)]
@@ -117,6 +181,9 @@ fn init(
             fonts.set_untracked(TextStyle::default().font, font);
         }
     }
+    for data in cosmic_font.additional_font_data.iter() {
+        db.load_font_data(data.to_vec());
+    }
     if cosmic_font.load_system_fonts {
         db.load_system_fonts();
     }
@@ -128,14 +195,24 @@ fn init(
 fn scale_factor_changed(
     mut scale_factor_changed: EventReader<WindowScaleFactorChanged>,
     windows: Query<&Window, With<PrimaryWindow>>,
-    mut cosmic_edit_query: Query<(&mut CosmicEditImage, &Node), With<CosmicEditImage>>,
+    mut cosmic_edit_query: Query<
+        (
+            &mut CosmicEditImage,
+            Option<&Node>,
+            Option<&CosmicEditSprite>,
+        ),
+        With<CosmicEditImage>,
+    >,
     mut font_system_state: ResMut<FontSystemState>,
 ) {
     let factor_changed = scale_factor_changed.iter().last().is_some();
     let window = windows.single();
     if factor_changed {
         let font_system = font_system_state.font_system.as_mut().unwrap();
-        for (mut cosmic_edit, node) in &mut cosmic_edit_query.iter_mut() {
+        for (mut cosmic_edit, node, sprite) in &mut cosmic_edit_query.iter_mut() {
+            let Some(size) = render_size(node, sprite) else {
+                continue;
+            };
             let scale_factor = window.scale_factor() as f32;
             let metrics = Metrics::new(cosmic_edit.font_size, cosmic_edit.font_line_height)
                 .scale(scale_factor);
@@ -145,14 +222,23 @@ fn scale_factor_changed(
                 .set_metrics(font_system, metrics);
             cosmic_edit.editor.buffer_mut().set_size(
                 font_system,
-                node.size().x * scale_factor,
-                node.size().y * scale_factor,
+                size.x * scale_factor,
+                size.y * scale_factor,
             );
             cosmic_edit.editor.buffer_mut().set_redraw(true);
         }
     }
 }
 
+/// Resized sprite cosmic edits are laid out and rendered again at their new size.
+fn cosmic_edit_sprite_resized(
+    mut cosmic_edit_query: Query<&mut CosmicEditImage, Changed<CosmicEditSprite>>,
+) {
+    for mut cosmic_edit in cosmic_edit_query.iter_mut() {
+        cosmic_edit.editor.buffer_mut().set_redraw(true);
+    }
+}
+
 fn active_editor_changed(
     active_editor: ResMut<ActiveEditor>,
     mut previous_editor: Local<Option<Entity>>,
@@ -172,6 +258,11 @@ fn active_editor_changed(
     }
 }
 
+/// Logical size cosmic edit is laid out and rendered at.
+fn render_size(node: Option<&Node>, sprite: Option<&CosmicEditSprite>) -> Option<Vec2> {
+    sprite.map(|sprite| sprite.size).or(node.map(Node::size))
+}
+
 fn get_node_cursor_pos(
     window: &Window,
     node_transform: &GlobalTransform,
@@ -190,6 +281,47 @@ fn get_node_cursor_pos(
     })
 }
 
+/// Cursor position in a sprite cosmic edit, in logical pixels of its text from the top left
+/// corner. The cursor is mapped to the world through the camera of the primary window.
+fn get_sprite_cursor_pos(
+    window: &Window,
+    camera: Option<(&Camera, &GlobalTransform)>,
+    sprite_transform: &GlobalTransform,
+    sprite: &Sprite,
+    size: Vec2,
+) -> Option<(f32, f32)> {
+    let (camera, camera_transform) = camera?;
+    let world = camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)?;
+    let local = sprite_transform
+        .affine()
+        .inverse()
+        .transform_point3(world.extend(0.))
+        .truncate();
+    sprite_text_pos(local, sprite.custom_size?, size)
+}
+
+/// Maps a point of the sprite (from its center, y up) to its text laid out at `size`.
+fn sprite_text_pos(local: Vec2, sprite_size: Vec2, size: Vec2) -> Option<(f32, f32)> {
+    // from the top left corner, in fractions of the sprite
+    let fraction = Vec2::new(local.x / sprite_size.x + 0.5, 0.5 - local.y / sprite_size.y);
+    if fraction.cmple(Vec2::ZERO).any() || fraction.cmpge(Vec2::ONE).any() {
+        return None;
+    }
+    Some((fraction.x * size.x, fraction.y * size.y))
+}
+
+/// Active camera rendering to the primary window, sprite cosmic edits are clicked through it.
+fn primary_camera<'a>(
+    cameras: &'a Query<(&Camera, &GlobalTransform)>,
+) -> Option<(&'a Camera, &'a GlobalTransform)> {
+    cameras
+        .iter()
+        .filter(|(camera, _)| {
+            camera.is_active && matches!(camera.target, RenderTarget::Window(WindowRef::Primary))
+        })
+        .min_by_key(|(camera, _)| camera.order)
+}
+
 /// Retrieves the cosmic text content from an editor.
 ///
 /// # Arguments
@@ -247,6 +379,255 @@ fn get_x_offset(editor: &Editor) -> i32 {
         / 2.0) as i32
 }
 
+fn cursor_to_offset(editor: &Editor, cursor: Cursor) -> usize {
+    let mut offset = 0;
+    for line in editor.buffer().lines.iter().take(cursor.line) {
+        offset += line.text().len() + 1;
+    }
+    offset + cursor.index
+}
+
+fn offset_to_cursor(editor: &Editor, mut offset: usize) -> Cursor {
+    let line_count = editor.buffer().lines.len();
+    for (i, line) in editor.buffer().lines.iter().enumerate() {
+        let len = line.text().len();
+        if offset <= len || i == line_count - 1 {
+            return Cursor {
+                line: i,
+                index: cmp::min(offset, len),
+                affinity: Affinity::Before,
+            };
+        }
+        offset -= len + 1;
+    }
+    Cursor {
+        line: 0,
+        index: 0,
+        affinity: Affinity::Before,
+    }
+}
+
+fn set_editor_cursor(editor: &mut Editor, font_system: &mut FontSystem, cursor: Cursor) {
+    editor.set_select_opt(None);
+    editor.action(font_system, Action::BufferStart);
+    if cursor.line == 0 && cursor.index == 0 {
+        return;
+    }
+    // cosmic-text 0.8 can't place the cursor directly, but inserting text leaves it at the end of
+    // the insertion. The insertion goes to a placeholder swapped in for the lines of the buffer,
+    // so the text isn't touched or reshaped, and it costs one edit instead of a step per character.
+    let lines = std::mem::take(&mut editor.buffer_mut().lines);
+    let column = lines.get(cursor.line).map_or("", |line| {
+        line.text().get(..cursor.index).unwrap_or(line.text())
+    });
+    let placeholder = format!("{}{}", "\n".repeat(cursor.line), column);
+    editor.buffer_mut().lines = vec![BufferLine::new("", AttrsList::new(Attrs::new()))];
+    editor.insert_string(placeholder.as_str(), None);
+    editor.buffer_mut().lines = lines;
+}
+
+fn caret_start(caret: &ExtraCursor) -> (usize, usize) {
+    let cursor = (caret.cursor.line, caret.cursor.index);
+    match caret.select_opt {
+        Some(select) => cmp::min(cursor, (select.line, select.index)),
+        None => cursor,
+    }
+}
+
+/// Applies an edit at the primary caret and at every extra caret.
+///
+/// Carets are processed bottom-up so positions of the carets above stay valid,
+/// already edited carets are shifted by the length change of each following edit.
+fn edit_at_every_cursor(
+    cosmic_edit: &mut CosmicEditImage,
+    font_system: &mut FontSystem,
+    edit: impl Fn(&mut Editor, &mut FontSystem),
+) {
+    if cosmic_edit.extra_cursors.is_empty() {
+        edit(&mut cosmic_edit.editor, font_system);
+        return;
+    }
+    let editor = &mut cosmic_edit.editor;
+    let mut carets = vec![(
+        true,
+        ExtraCursor {
+            cursor: editor.cursor(),
+            select_opt: editor.select_opt(),
+        },
+    )];
+    carets.extend(cosmic_edit.extra_cursors.drain(..).map(|c| (false, c)));
+    carets.sort_by_key(|(_, caret)| cmp::Reverse(caret_start(caret)));
+
+    let mut offsets: Vec<(bool, usize)> = Vec::new();
+    for (is_primary, caret) in carets {
+        let len_before = get_cosmic_text(editor).len() as isize;
+        set_editor_cursor(editor, font_system, caret.cursor);
+        editor.set_select_opt(caret.select_opt);
+        edit(editor, font_system);
+        let delta = get_cosmic_text(editor).len() as isize - len_before;
+        for (_, offset) in offsets.iter_mut() {
+            *offset = (*offset as isize + delta) as usize;
+        }
+        offsets.push((is_primary, cursor_to_offset(editor, editor.cursor())));
+    }
+
+    let mut primary = None;
+    for (is_primary, offset) in offsets {
+        let cursor = offset_to_cursor(editor, offset);
+        if is_primary {
+            primary = Some(cursor);
+        } else if !cosmic_edit.extra_cursors.iter().any(|c| c.cursor == cursor) {
+            cosmic_edit.extra_cursors.push(ExtraCursor {
+                cursor,
+                select_opt: None,
+            });
+        }
+    }
+    if let Some(primary) = primary {
+        cosmic_edit.extra_cursors.retain(|c| c.cursor != primary);
+        set_editor_cursor(editor, font_system, primary);
+    }
+    editor.buffer_mut().set_redraw(true);
+}
+
+/// Adds an extra caret selecting the next occurrence of the current selection.
+fn add_next_occurrence_cursor(cosmic_edit: &mut CosmicEditImage) {
+    let editor = &mut cosmic_edit.editor;
+    let selection = match editor.copy_selection() {
+        Some(selection) if !selection.is_empty() && !selection.contains('\n') => selection,
+        _ => return,
+    };
+    let text = get_cosmic_text(editor);
+    let mut search_from = cursor_to_offset(editor, editor.cursor());
+    if let Some(select) = editor.select_opt() {
+        search_from = cmp::max(search_from, cursor_to_offset(editor, select));
+    }
+    for extra in cosmic_edit.extra_cursors.iter() {
+        search_from = cmp::max(search_from, cursor_to_offset(editor, extra.cursor));
+    }
+    let found = text[search_from..]
+        .find(selection.as_str())
+        .map(|i| i + search_from)
+        .or_else(|| text.find(selection.as_str()));
+    if let Some(start) = found {
+        let cursor = offset_to_cursor(editor, start + selection.len());
+        let is_taken = editor.cursor() == cursor
+            || editor.select_opt() == Some(cursor)
+            || cosmic_edit.extra_cursors.iter().any(|c| c.cursor == cursor);
+        if !is_taken {
+            cosmic_edit.extra_cursors.push(ExtraCursor {
+                cursor,
+                select_opt: Some(offset_to_cursor(editor, start)),
+            });
+            editor.buffer_mut().set_redraw(true);
+        }
+    }
+}
+
+fn clear_extra_cursors(cosmic_edit: &mut CosmicEditImage) {
+    if !cosmic_edit.extra_cursors.is_empty() {
+        cosmic_edit.extra_cursors.clear();
+        cosmic_edit.editor.buffer_mut().set_redraw(true);
+    }
+}
+
+fn mask_cursor(editor: &Editor, cursor: Cursor, mask_char: char) -> Cursor {
+    let text = editor.buffer().lines[cursor.line].text();
+    let chars = text[..cmp::min(cursor.index, text.len())].chars().count();
+    Cursor {
+        line: cursor.line,
+        index: chars * mask_char.len_utf8(),
+        affinity: cursor.affinity,
+    }
+}
+
+fn unmask_cursor(editor: &Editor, masked_cursor: Cursor, mask_char: char) -> Cursor {
+    let text = editor.buffer().lines[masked_cursor.line].text();
+    let chars = masked_cursor.index / mask_char.len_utf8();
+    Cursor {
+        line: masked_cursor.line,
+        index: text
+            .char_indices()
+            .nth(chars)
+            .map(|(i, _)| i)
+            .unwrap_or(text.len()),
+        affinity: masked_cursor.affinity,
+    }
+}
+
+/// Builds an editor with every character of `editor` replaced by `mask_char`,
+/// cursor and selection are mapped onto the masked text.
+fn get_masked_editor(editor: &Editor, mask_char: char, font_system: &mut FontSystem) -> Editor {
+    let masked_text: String = get_cosmic_text(editor)
+        .chars()
+        .map(|c| if c == '\n' { c } else { mask_char })
+        .collect();
+    let mut masked_editor = Editor::new(Buffer::new(font_system, editor.buffer().metrics()));
+    let (width, height) = editor.buffer().size();
+    masked_editor
+        .buffer_mut()
+        .set_size(font_system, width, height);
+    masked_editor
+        .buffer_mut()
+        .set_text(font_system, masked_text.as_str(), Attrs::new());
+    set_editor_cursor(
+        &mut masked_editor,
+        font_system,
+        mask_cursor(editor, editor.cursor(), mask_char),
+    );
+    masked_editor.set_select_opt(
+        editor
+            .select_opt()
+            .map(|select| mask_cursor(editor, select, mask_char)),
+    );
+    masked_editor.shape_as_needed(font_system);
+    masked_editor
+}
+
+/// Rebuilds the masked editor of a masked cosmic edit when its text, carets, size or metrics
+/// changed since it was last built, and returns it.
+fn update_masked_editor<'a>(
+    cosmic_edit: &'a mut CosmicEditImage,
+    font_system: &mut FontSystem,
+) -> Option<&'a mut Editor> {
+    let Some(mask_char) = cosmic_edit.mask_char else {
+        cosmic_edit.masked_editor = None;
+        return None;
+    };
+    let state = MaskedState::of(&cosmic_edit.editor);
+    if !matches!(&cosmic_edit.masked_editor, Some((built_from, _)) if *built_from == state) {
+        let masked_editor = get_masked_editor(&cosmic_edit.editor, mask_char, font_system);
+        cosmic_edit.masked_editor = Some((state, masked_editor));
+    }
+    cosmic_edit
+        .masked_editor
+        .as_mut()
+        .map(|(_, masked_editor)| masked_editor)
+}
+
+/// Runs a pointer action on the masked layout and maps the result back to the real text.
+fn masked_action(cosmic_edit: &mut CosmicEditImage, font_system: &mut FontSystem, action: Action) {
+    let (Some(mask_char), Some(masked_editor)) = (
+        cosmic_edit.mask_char,
+        update_masked_editor(cosmic_edit, font_system),
+    ) else {
+        return;
+    };
+    masked_editor.action(font_system, action);
+    masked_editor.shape_as_needed(font_system);
+    let (masked_cursor, masked_select_opt) = (masked_editor.cursor(), masked_editor.select_opt());
+    let editor = &mut cosmic_edit.editor;
+    let cursor = unmask_cursor(editor, masked_cursor, mask_char);
+    let select_opt = masked_select_opt.map(|select| unmask_cursor(editor, select, mask_char));
+    set_editor_cursor(editor, font_system, cursor);
+    editor.set_select_opt(select_opt);
+    // the masked editor already shows the new carets, no need to rebuild it for them
+    if let Some((built_from, _)) = cosmic_edit.masked_editor.as_mut() {
+        built_from.cursor = cosmic_edit.editor.cursor();
+        built_from.select_opt = cosmic_edit.editor.select_opt();
+    }
+}
+
 fn cosmic_edit_bevy_events(
     windows: Query<&Window, With<PrimaryWindow>>,
     mut font_system_state: ResMut<FontSystemState>,
@@ -255,17 +636,39 @@ fn cosmic_edit_bevy_events(
     mut char_evr: EventReader<ReceivedCharacter>,
     buttons: Res<Input<MouseButton>>,
     mut cosmic_edit_query: Query<
-        (&mut CosmicEditImage, &GlobalTransform, &Node, Entity),
+        (
+            &mut CosmicEditImage,
+            &GlobalTransform,
+            Option<&Node>,
+            Option<(&CosmicEditSprite, &Sprite)>,
+            Entity,
+        ),
         With<CosmicEditImage>,
     >,
+    cameras: Query<(&Camera, &GlobalTransform)>,
     mut is_deleting: Local<bool>,
 ) {
     let window = windows.single();
     let font_system = font_system_state.font_system.as_mut().unwrap();
-    for (mut cosmic_edit, node_transform, node, entity) in &mut cosmic_edit_query.iter_mut() {
+    for (mut cosmic_edit, transform, node, sprite, entity) in &mut cosmic_edit_query.iter_mut() {
         if active_editor.entity == Some(entity) {
+            let cursor_pos = || match (sprite, node) {
+                (Some((cosmic_sprite, sprite)), _) => get_sprite_cursor_pos(
+                    window,
+                    primary_camera(&cameras),
+                    transform,
+                    sprite,
+                    cosmic_sprite.size,
+                ),
+                (None, Some(node)) => get_node_cursor_pos(window, transform, node),
+                (None, None) => None,
+            };
             let command = keys.any_pressed([KeyCode::RWin, KeyCode::LWin]);
+            let control = keys.any_pressed([KeyCode::RControl, KeyCode::LControl]);
             let option = keys.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+            if keys.any_just_pressed([KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down]) {
+                clear_extra_cursors(&mut cosmic_edit);
+            }
             if keys.just_pressed(KeyCode::Left) {
                 cosmic_edit.editor.action(font_system, Action::Left);
             }
@@ -281,7 +684,9 @@ fn cosmic_edit_bevy_events(
             if keys.just_pressed(KeyCode::Back) {
                 // there is ReceivedCharacter event for backspace on wasm
                 #[cfg(target_arch = "wasm32")]
-                cosmic_edit.editor.action(font_system, Action::Backspace);
+                edit_at_every_cursor(&mut cosmic_edit, font_system, |editor, font_system| {
+                    editor.action(font_system, Action::Backspace)
+                });
                 *is_deleting = true;
             }
             if keys.just_released(KeyCode::Back) {
@@ -292,14 +697,18 @@ fn cosmic_edit_bevy_events(
             }
             if keys.just_pressed(KeyCode::Return) {
                 // to have new line on wasm rather than E
-                cosmic_edit.editor.action(font_system, Action::Insert('\n'));
+                edit_at_every_cursor(&mut cosmic_edit, font_system, |editor, font_system| {
+                    editor.action(font_system, Action::Insert('\n'))
+                });
                 // RETURN
                 return;
             }
             if keys.just_pressed(KeyCode::Escape) {
+                clear_extra_cursors(&mut cosmic_edit);
                 cosmic_edit.editor.action(font_system, Action::Escape);
             }
             if command && keys.just_pressed(KeyCode::A) {
+                clear_extra_cursors(&mut cosmic_edit);
                 cosmic_edit.editor.action(font_system, Action::BufferEnd);
                 cosmic_edit.editor.set_select_opt(Some(Cursor {
                     line: 0,
@@ -309,6 +718,23 @@ fn cosmic_edit_bevy_events(
                 // RETURN
                 return;
             }
+            if (command || control) && keys.just_pressed(KeyCode::D) {
+                add_next_occurrence_cursor(&mut cosmic_edit);
+                // RETURN
+                return;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if (command || control) && keys.just_pressed(KeyCode::V) {
+                if cosmic_edit.paste {
+                    if let Ok(text) = arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+                        edit_at_every_cursor(&mut cosmic_edit, font_system, |editor, _| {
+                            editor.insert_string(text.as_str(), None)
+                        });
+                    }
+                }
+                // RETURN
+                return;
+            }
             if command && option && keys.just_pressed(KeyCode::Left) {
                 cosmic_edit.editor.action(font_system, Action::PreviousWord);
                 // RETURN
@@ -319,51 +745,102 @@ fn cosmic_edit_bevy_events(
                 // RETURN
                 return;
             }
+            let is_masked = cosmic_edit.mask_char.is_some();
             let (offset_y, offset_x) = match cosmic_edit.text_pos {
-                CosmicTextPos::Center => (
-                    get_y_offset(&cosmic_edit.editor),
-                    get_x_offset(&cosmic_edit.editor),
-                ),
+                CosmicTextPos::Center => {
+                    match update_masked_editor(&mut cosmic_edit, font_system) {
+                        Some(masked_editor) => {
+                            (get_y_offset(masked_editor), get_x_offset(masked_editor))
+                        }
+                        None => (
+                            get_y_offset(&cosmic_edit.editor),
+                            get_x_offset(&cosmic_edit.editor),
+                        ),
+                    }
+                }
                 CosmicTextPos::TopLeft => (0, 0),
             };
             if buttons.just_pressed(MouseButton::Left) {
-                if let Some(node_cursor_pos) = get_node_cursor_pos(window, node_transform, node) {
-                    cosmic_edit.editor.action(
-                        font_system,
-                        Action::Click {
-                            x: (node_cursor_pos.0 * window.scale_factor() as f32) as i32 - offset_x,
-                            y: (node_cursor_pos.1 * window.scale_factor() as f32) as i32 - offset_y,
-                        },
-                    );
+                if let Some(node_cursor_pos) = cursor_pos() {
+                    if option {
+                        // keep the current caret and make the clicked position the primary one
+                        let cursor = cosmic_edit.editor.cursor();
+                        cosmic_edit.extra_cursors.push(ExtraCursor {
+                            cursor,
+                            select_opt: None,
+                        });
+                    } else {
+                        clear_extra_cursors(&mut cosmic_edit);
+                    }
+                    let action = Action::Click {
+                        x: (node_cursor_pos.0 * window.scale_factor() as f32) as i32 - offset_x,
+                        y: (node_cursor_pos.1 * window.scale_factor() as f32) as i32 - offset_y,
+                    };
+                    if is_masked {
+                        masked_action(&mut cosmic_edit, font_system, action);
+                    } else {
+                        cosmic_edit.editor.action(font_system, action);
+                    }
+                    let primary = cosmic_edit.editor.cursor();
+                    cosmic_edit.extra_cursors.retain(|c| c.cursor != primary);
                 }
                 // RETURN
                 return;
             }
             // TODO: implement proper hold check
-            if buttons.pressed(MouseButton::Left) {
-                if let Some(node_cursor_pos) = get_node_cursor_pos(window, node_transform, node) {
-                    cosmic_edit.editor.action(
-                        font_system,
-                        Action::Drag {
-                            x: (node_cursor_pos.0 * window.scale_factor() as f32) as i32 - offset_x,
-                            y: (node_cursor_pos.1 * window.scale_factor() as f32) as i32 - offset_y,
-                        },
-                    );
+            if buttons.pressed(MouseButton::Left) && !option {
+                if let Some(node_cursor_pos) = cursor_pos() {
+                    let action = Action::Drag {
+                        x: (node_cursor_pos.0 * window.scale_factor() as f32) as i32 - offset_x,
+                        y: (node_cursor_pos.1 * window.scale_factor() as f32) as i32 - offset_y,
+                    };
+                    if is_masked {
+                        masked_action(&mut cosmic_edit, font_system, action);
+                    } else {
+                        cosmic_edit.editor.action(font_system, action);
+                    }
                 }
                 // RETURN
                 return;
             }
             for char_ev in char_evr.iter() {
                 if *is_deleting {
-                    cosmic_edit.editor.action(font_system, Action::Backspace);
+                    edit_at_every_cursor(&mut cosmic_edit, font_system, |editor, font_system| {
+                        editor.action(font_system, Action::Backspace)
+                    });
                 } else {
-                    cosmic_edit
-                        .editor
-                        .action(font_system, Action::Insert(char_ev.char));
+                    let char = char_ev.char;
+                    edit_at_every_cursor(&mut cosmic_edit, font_system, |editor, font_system| {
+                        editor.action(font_system, Action::Insert(char))
+                    });
                 }
             }
         }
     }
+    // drop characters typed while no editor was active,
+    // otherwise they end up in the next activated editor
+    char_evr.clear();
+}
+
+fn get_cursor_pixel_pos(editor: &Editor, cursor: Cursor) -> Option<(i32, i32)> {
+    let font_size = editor.buffer().metrics().font_size as i32;
+    let mut line_end = None;
+    for run in editor.buffer().layout_runs() {
+        if run.line_i != cursor.line {
+            continue;
+        }
+        let line_top = run.line_y as i32 - font_size;
+        for glyph in run.glyphs.iter() {
+            if glyph.start <= cursor.index && cursor.index < glyph.end {
+                return Some((glyph.x as i32, line_top));
+            }
+        }
+        line_end = Some(match run.glyphs.last() {
+            Some(glyph) => ((glyph.x + glyph.w) as i32, line_top),
+            None => (0, line_top),
+        });
+    }
+    line_end
 }
 
 fn cosmic_edit_redraw_buffer(
@@ -372,47 +849,98 @@ fn cosmic_edit_redraw_buffer(
     mut font_system_state: ResMut<FontSystemState>,
     mut swash_cache_state: ResMut<SwashCacheState>,
     mut cosmic_edit_query: Query<
-        (&mut CosmicEditImage, &mut UiImage, &Node),
+        (
+            &mut CosmicEditImage,
+            Option<&mut UiImage>,
+            Option<&mut Handle<Image>>,
+            Option<&Node>,
+            Option<&CosmicEditSprite>,
+        ),
         With<CosmicEditImage>,
     >,
 ) {
     let window = windows.single();
     let font_system = font_system_state.font_system.as_mut().unwrap();
     let swash_cache = swash_cache_state.swash_cache.as_mut().unwrap();
-    for (mut cosmic_edit, mut img, node) in &mut cosmic_edit_query.iter_mut() {
+    for (mut cosmic_edit, ui_image, sprite_image, node, sprite) in &mut cosmic_edit_query.iter_mut()
+    {
         cosmic_edit.editor.shape_as_needed(font_system);
+        let Some(size) = render_size(node, sprite) else {
+            continue;
+        };
         if cosmic_edit.editor.buffer().redraw() {
-            let width = cmp::max((node.size().x * window.scale_factor() as f32) as i32, 1) as f32;
-            let height = cmp::max((node.size().y * window.scale_factor() as f32) as i32, 1) as f32;
+            let width = cmp::max((size.x * window.scale_factor() as f32) as i32, 1) as f32;
+            let height = cmp::max((size.y * window.scale_factor() as f32) as i32, 1) as f32;
             cosmic_edit
                 .editor
                 .buffer_mut()
                 .set_size(font_system, width, height);
             let font_color = cosmic_text::Color::rgb(0, 0, 0);
             let mut pixels = vec![0; width as usize * height as usize * 4];
+            let mask_char = cosmic_edit.mask_char;
+            update_masked_editor(&mut cosmic_edit, font_system);
+            let editor = match &cosmic_edit.masked_editor {
+                Some((_, masked_editor)) => masked_editor,
+                None => &cosmic_edit.editor,
+            };
             let (offset_y, offset_x) = match cosmic_edit.text_pos {
-                CosmicTextPos::Center => (
-                    get_y_offset(&cosmic_edit.editor),
-                    get_x_offset(&cosmic_edit.editor),
-                ),
+                CosmicTextPos::Center => (get_y_offset(editor), get_x_offset(editor)),
                 CosmicTextPos::TopLeft => (0, 0),
             };
-            cosmic_edit
-                .editor
-                .draw(font_system, swash_cache, font_color, |x, y, w, h, color| {
-                    for row in 0..h as i32 {
-                        for col in 0..w as i32 {
-                            draw_pixel(
-                                &mut pixels,
-                                width as i32,
-                                height as i32,
-                                x + col + offset_x,
-                                y + row + offset_y,
-                                color,
-                            );
+            editor.draw(font_system, swash_cache, font_color, |x, y, w, h, color| {
+                for row in 0..h as i32 {
+                    for col in 0..w as i32 {
+                        draw_pixel(
+                            &mut pixels,
+                            width as i32,
+                            height as i32,
+                            x + col + offset_x,
+                            y + row + offset_y,
+                            color,
+                        );
+                    }
+                }
+            });
+            let line_height = editor.buffer().metrics().line_height as i32;
+            let selection_color = cosmic_text::Color::rgba(0, 0, 0, 0x33);
+            let to_layout_cursor = |cursor: Cursor| match mask_char {
+                Some(mask_char) => mask_cursor(&cosmic_edit.editor, cursor, mask_char),
+                None => cursor,
+            };
+            for extra in cosmic_edit.extra_cursors.iter() {
+                let cursor_pos = get_cursor_pixel_pos(editor, to_layout_cursor(extra.cursor));
+                if let Some((x, y)) = cursor_pos {
+                    for row in 0..line_height {
+                        draw_pixel(
+                            &mut pixels,
+                            width as i32,
+                            height as i32,
+                            x + offset_x,
+                            y + row + offset_y,
+                            font_color,
+                        );
+                    }
+                }
+                let select_pos = extra
+                    .select_opt
+                    .and_then(|select| get_cursor_pixel_pos(editor, to_layout_cursor(select)));
+                if let (Some((x, y)), Some((select_x, select_y))) = (cursor_pos, select_pos) {
+                    if y == select_y {
+                        for row in 0..line_height {
+                            for col in cmp::min(x, select_x)..cmp::max(x, select_x) {
+                                draw_pixel(
+                                    &mut pixels,
+                                    width as i32,
+                                    height as i32,
+                                    col + offset_x,
+                                    y + row + offset_y,
+                                    selection_color,
+                                );
+                            }
                         }
                     }
-                });
+                }
+            }
 
             cosmic_edit.editor.buffer_mut().set_redraw(false);
             let image: RgbaImage =
@@ -429,14 +957,38 @@ fn cosmic_edit_redraw_buffer(
                 TextureFormat::Rgba8UnormSrgb,
             );
             let image = images.add(image);
-            *img = UiImage {
-                texture: image.clone(),
-                ..default()
-            };
+            if let Some(mut sprite_image) = sprite_image {
+                *sprite_image = image;
+            } else if let Some(mut ui_image) = ui_image {
+                *ui_image = UiImage {
+                    texture: image,
+                    ..default()
+                };
+            }
         }
     }
 }
 
+/// Applies new font size and attributes to the whole text of cosmic edit.
+pub fn set_cosmic_text_style(
+    cosmic_edit: &mut CosmicEditImage,
+    font_system: &mut FontSystem,
+    font_size: f32,
+    line_height: f32,
+    scale_factor: f32,
+    attrs: Attrs,
+) {
+    cosmic_edit.font_size = font_size;
+    cosmic_edit.font_line_height = line_height;
+    let metrics = Metrics::new(font_size, line_height).scale(scale_factor);
+    let text = get_cosmic_text(&cosmic_edit.editor);
+    let buffer = cosmic_edit.editor.buffer_mut();
+    buffer.set_metrics(font_system, metrics);
+    buffer.set_text(font_system, text.as_str(), attrs);
+    // text is unchanged, so carets stay valid
+    buffer.set_redraw(true);
+}
+
 /// Spawns a cosmic edit entity with the provided configuration.
 ///
 /// # Arguments
@@ -448,21 +1000,6 @@ fn cosmic_edit_redraw_buffer(
 ///
 /// The `Entity` identifier of the spawned cosmic edit entity.
 pub fn spawn_cosmic_edit(commands: &mut Commands, cosmic_edit_meta: CosmicEditMeta) -> Entity {
-    let font_system = cosmic_edit_meta.font_system;
-    let metrics = Metrics::new(cosmic_edit_meta.font_size, cosmic_edit_meta.line_height)
-        .scale(cosmic_edit_meta.scale_factor);
-    let buffer = Buffer::new(font_system, metrics);
-    let mut editor = Editor::new(buffer);
-    editor.buffer_mut().lines.clear();
-    let attrs = Attrs::new();
-    editor
-        .buffer_mut()
-        .set_text(font_system, cosmic_edit_meta.text.as_str(), attrs);
-    if let Some(initial_size) = cosmic_edit_meta.initial_size {
-        editor
-            .buffer_mut()
-            .set_size(font_system, initial_size.0, initial_size.1);
-    }
     let mut style = Style {
         size: Size {
             width: Val::Percent(100.),
@@ -478,21 +1015,74 @@ pub fn spawn_cosmic_edit(commands: &mut Commands, cosmic_edit_meta: CosmicEditMe
         style,
         ..default()
     };
-    if let Some(initial_background) = cosmic_edit_meta.initial_background {
+    if let Some(initial_background) = cosmic_edit_meta.initial_background.clone() {
         image_bundle.image = initial_background;
     }
-    let cosmic_edit = commands
+    commands
+        .spawn((image_bundle, cosmic_edit_image(cosmic_edit_meta)))
+        .id()
+}
+
+/// Spawns a cosmic edit shown by a sprite of `world_size`, its text laid out at `size`.
+///
+/// Unlike UI cosmic edits it's placed with its `Transform`, its visibility is that of the sprite.
+pub fn spawn_cosmic_edit_sprite(
+    commands: &mut Commands,
+    cosmic_edit_meta: CosmicEditMeta,
+    size: Vec2,
+    world_size: Vec2,
+) -> Entity {
+    let mut sprite_bundle = SpriteBundle {
+        sprite: Sprite {
+            custom_size: Some(world_size),
+            ..default()
+        },
+        visibility: if cosmic_edit_meta.is_visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        },
+        ..default()
+    };
+    if let Some(initial_background) = cosmic_edit_meta.initial_background.as_ref() {
+        sprite_bundle.texture = initial_background.texture.clone();
+    }
+    commands
         .spawn((
-            image_bundle,
-            CosmicEditImage {
-                editor,
-                text_pos: cosmic_edit_meta.text_pos,
-                font_line_height: cosmic_edit_meta.line_height,
-                font_size: cosmic_edit_meta.font_size,
-            },
+            sprite_bundle,
+            CosmicEditSprite { size },
+            cosmic_edit_image(cosmic_edit_meta),
         ))
-        .id();
-    cosmic_edit
+        .id()
+}
+
+fn cosmic_edit_image(cosmic_edit_meta: CosmicEditMeta) -> CosmicEditImage {
+    let font_system = cosmic_edit_meta.font_system;
+    let metrics = Metrics::new(cosmic_edit_meta.font_size, cosmic_edit_meta.line_height)
+        .scale(cosmic_edit_meta.scale_factor);
+    let buffer = Buffer::new(font_system, metrics);
+    let mut editor = Editor::new(buffer);
+    editor.buffer_mut().lines.clear();
+    editor.buffer_mut().set_text(
+        font_system,
+        cosmic_edit_meta.text.as_str(),
+        cosmic_edit_meta.attrs,
+    );
+    if let Some(initial_size) = cosmic_edit_meta.initial_size {
+        editor
+            .buffer_mut()
+            .set_size(font_system, initial_size.0, initial_size.1);
+    }
+    CosmicEditImage {
+        editor,
+        text_pos: cosmic_edit_meta.text_pos,
+        extra_cursors: Vec::new(),
+        paste: cosmic_edit_meta.paste,
+        mask_char: cosmic_edit_meta.mask_char,
+        masked_editor: None,
+        font_line_height: cosmic_edit_meta.line_height,
+        font_size: cosmic_edit_meta.font_size,
+    }
 }
 
 fn draw_pixel(
@@ -555,9 +1145,12 @@ mod tests {
             text: "Blah".to_string(),
             font_size: 18.,
             line_height: 20.,
+            attrs: Attrs::new(),
             scale_factor: 1.,
             font_system: &mut FontSystem::new(),
             is_visible: true,
+            paste: false,
+            mask_char: None,
             initial_background: None,
             text_pos: CosmicTextPos::Center,
             initial_size: None,
@@ -593,4 +1186,130 @@ mod tests {
                 .collect::<Vec<_>>());
         }
     }
+
+    #[test]
+    fn test_edit_at_every_cursor() {
+        let mut font_system = FontSystem::new();
+        let buffer = Buffer::new(&mut font_system, Metrics::new(18., 20.));
+        let mut editor = Editor::new(buffer);
+        editor
+            .buffer_mut()
+            .set_text(&mut font_system, "ab\ncd", Attrs::new());
+        editor.action(&mut font_system, Action::BufferEnd);
+        let mut cosmic_edit = CosmicEditImage {
+            editor,
+            text_pos: CosmicTextPos::Center,
+            extra_cursors: vec![ExtraCursor {
+                cursor: Cursor {
+                    line: 0,
+                    index: 1,
+                    affinity: Affinity::Before,
+                },
+                select_opt: None,
+            }],
+            paste: false,
+            mask_char: None,
+            masked_editor: None,
+            font_size: 18.,
+            font_line_height: 20.,
+        };
+
+        edit_at_every_cursor(&mut cosmic_edit, &mut font_system, |editor, font_system| {
+            editor.action(font_system, Action::Insert('x'))
+        });
+
+        assert_eq!(get_cosmic_text(&cosmic_edit.editor), "axb\ncdx");
+        assert_eq!(cosmic_edit.editor.cursor().line, 1);
+        assert_eq!(cosmic_edit.editor.cursor().index, 3);
+        assert_eq!(cosmic_edit.extra_cursors.len(), 1);
+        assert_eq!(cosmic_edit.extra_cursors[0].cursor.line, 0);
+        assert_eq!(cosmic_edit.extra_cursors[0].cursor.index, 2);
+    }
+
+    #[test]
+    fn test_set_editor_cursor() {
+        let mut font_system = FontSystem::new();
+        let buffer = Buffer::new(&mut font_system, Metrics::new(18., 20.));
+        let mut editor = Editor::new(buffer);
+        editor
+            .buffer_mut()
+            .set_text(&mut font_system, "ab\ncdé", Attrs::new());
+        let cursor = Cursor {
+            line: 1,
+            index: 2,
+            affinity: Affinity::Before,
+        };
+
+        set_editor_cursor(&mut editor, &mut font_system, cursor);
+
+        assert_eq!(get_cosmic_text(&editor), "ab\ncdé");
+        assert_eq!((editor.cursor().line, editor.cursor().index), (1, 2));
+        editor.action(&mut font_system, Action::Insert('x'));
+        assert_eq!(get_cosmic_text(&editor), "ab\ncdxé");
+    }
+
+    #[test]
+    fn test_masked_editor() {
+        let mut font_system = FontSystem::new();
+        let buffer = Buffer::new(&mut font_system, Metrics::new(18., 20.));
+        let mut editor = Editor::new(buffer);
+        editor
+            .buffer_mut()
+            .set_text(&mut font_system, "pä\nx", Attrs::new());
+        let after_umlaut = Cursor {
+            line: 0,
+            index: 3,
+            affinity: Affinity::Before,
+        };
+        set_editor_cursor(&mut editor, &mut font_system, after_umlaut);
+        let mut cosmic_edit = CosmicEditImage {
+            editor,
+            text_pos: CosmicTextPos::Center,
+            extra_cursors: vec![],
+            paste: false,
+            mask_char: Some('•'),
+            masked_editor: None,
+            font_size: 18.,
+            font_line_height: 20.,
+        };
+
+        let masked_editor = update_masked_editor(&mut cosmic_edit, &mut font_system).unwrap();
+        assert_eq!(get_cosmic_text(masked_editor), "••\n•");
+        // two bullets of three bytes each
+        let masked_cursor = masked_editor.cursor();
+        assert_eq!((masked_cursor.line, masked_cursor.index), (0, 6));
+        let cursor = unmask_cursor(&cosmic_edit.editor, masked_cursor, '•');
+        assert_eq!((cursor.line, cursor.index), (0, 3));
+        assert_eq!(get_cosmic_text(&cosmic_edit.editor), "pä\nx");
+
+        // rebuilt once the text changes
+        cosmic_edit
+            .editor
+            .action(&mut font_system, Action::Insert('y'));
+        let masked_editor = update_masked_editor(&mut cosmic_edit, &mut font_system).unwrap();
+        assert_eq!(get_cosmic_text(masked_editor), "•••\n•");
+        assert_eq!(masked_editor.cursor().index, 9);
+    }
+
+    #[test]
+    fn test_sprite_text_pos() {
+        // sprite of a node 200x100 in the world, zoomed in twice
+        let (sprite_size, size) = (Vec2::new(200., 100.), Vec2::new(400., 200.));
+        assert_eq!(
+            sprite_text_pos(
+                Vec2::new(-100., 50.) + Vec2::new(1., -1.),
+                sprite_size,
+                size
+            ),
+            Some((2., 2.))
+        );
+        assert_eq!(
+            sprite_text_pos(Vec2::ZERO, sprite_size, size),
+            Some((200., 100.))
+        );
+        assert_eq!(
+            sprite_text_pos(Vec2::new(120., 0.), sprite_size, size),
+            None
+        );
+    }
 }
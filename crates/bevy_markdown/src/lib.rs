@@ -1,4 +1,10 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    sprite::Anchor,
+    text::{update_text2d_layout, Text2dBounds, TextLayoutInfo},
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
 use serde::{Deserialize, Serialize};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{FontStyle, ThemeSet};
@@ -6,6 +12,10 @@ use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
 use uuid::Uuid;
 
+mod math;
+
+use math::typeset_tex;
+
 #[derive(Default)]
 pub struct BevyMarkdown {
     pub text: String,
@@ -15,7 +25,22 @@ pub struct BevyMarkdown {
     pub semi_bold_italic_font: Option<Handle<Font>>,
     pub extra_bold_font: Option<Handle<Font>>,
     pub code_font: Option<Handle<Font>>,
-    pub size: Option<(Val, Val)>,
+    /// Width the text wraps at, padding of the view included
+    pub width: Option<f32>,
+    pub font_size: Option<f32>,
+    pub color: Option<Color>,
+}
+
+const DEFAULT_FONT_SIZE: f32 = 18.0;
+
+impl BevyMarkdown {
+    pub fn font_size(&self) -> f32 {
+        self.font_size.unwrap_or(DEFAULT_FONT_SIZE)
+    }
+
+    pub fn color(&self) -> Color {
+        self.color.unwrap_or(Color::BLACK)
+    }
 }
 
 #[repr(u8)]
@@ -69,6 +94,25 @@ pub enum BevyMarkdownError {
 pub struct BevyMarkdownNode {
     pub id: Uuid,
     pub link_sections: Vec<Option<String>>,
+    pub task_items: Vec<TaskItem>,
+}
+
+/// Checkbox of a task list item (`- [ ]` / `- [x]`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TaskItem {
+    /// Index of the text section showing the checkbox.
+    pub section_index: usize,
+    /// Byte offset of `[` of the checkbox in the markdown text.
+    pub offset: usize,
+    pub checked: bool,
+}
+
+pub fn get_task_checkbox(checked: bool) -> &'static str {
+    if checked {
+        " ☑ "
+    } else {
+        " ☐ "
+    }
 }
 
 pub fn get_resultant_style(
@@ -107,8 +151,8 @@ pub fn handle_block_styling(
                     value: "\n".to_string(),
                     style: TextStyle {
                         font: bevy_markdown.regular_font.clone().unwrap(),
-                        font_size: 18.0,
-                        color: Color::BLACK,
+                        font_size: bevy_markdown.font_size(),
+                        color: bevy_markdown.color(),
                     },
                 },
                 None,
@@ -121,7 +165,10 @@ pub fn handle_block_styling(
                     errors,
                     InlineStyleType::Strong as u8,
                     None,
-                    Some(get_header_font_size(header.depth)),
+                    Some(
+                        get_header_font_size(header.depth) * bevy_markdown.font_size()
+                            / DEFAULT_FONT_SIZE,
+                    ),
                     &None,
                 );
             });
@@ -132,8 +179,8 @@ pub fn handle_block_styling(
                     value: "\n".to_string(),
                     style: TextStyle {
                         font: bevy_markdown.regular_font.clone().unwrap(),
-                        font_size: 18.0,
-                        color: Color::BLACK,
+                        font_size: bevy_markdown.font_size(),
+                        color: bevy_markdown.color(),
                     },
                 },
                 None,
@@ -145,8 +192,8 @@ pub fn handle_block_styling(
                             value: "\n".to_string(),
                             style: TextStyle {
                                 font: bevy_markdown.regular_font.clone().unwrap(),
-                                font_size: 18.0,
-                                color: Color::BLACK,
+                                font_size: bevy_markdown.font_size(),
+                                color: bevy_markdown.color(),
                             },
                         },
                         None,
@@ -157,8 +204,8 @@ pub fn handle_block_styling(
                         value: text.value.clone(),
                         style: TextStyle {
                             font: bevy_markdown.regular_font.clone().unwrap(),
-                            font_size: 18.0,
-                            color: Color::BLACK,
+                            font_size: bevy_markdown.font_size(),
+                            color: bevy_markdown.color(),
                         },
                     };
                     text_sections.push((text_section, None));
@@ -167,6 +214,7 @@ pub fn handle_block_styling(
                 | markdown::mdast::Node::Emphasis(_)
                 | markdown::mdast::Node::InlineCode(_)
                 | markdown::mdast::Node::Delete(_)
+                | markdown::mdast::Node::InlineMath(_)
                 | markdown::mdast::Node::Link(_) => {
                     let _ = handle_inline_styling(
                         child,
@@ -210,7 +258,7 @@ pub fn handle_inline_styling(
                     font_size: if let Some(size) = force_size {
                         size
                     } else {
-                        18.0
+                        bevy_markdown.font_size()
                     },
                     color: if let Some(color) = force_color {
                         color
@@ -221,6 +269,25 @@ pub fn handle_inline_styling(
             };
             text_sections.push((text_section, force_data.clone()));
         }
+        markdown::mdast::Node::InlineMath(math) => {
+            let text_section = TextSection {
+                value: typeset_tex(&math.value),
+                style: TextStyle {
+                    font: math_font(bevy_markdown),
+                    font_size: if let Some(size) = force_size {
+                        size
+                    } else {
+                        bevy_markdown.font_size()
+                    },
+                    color: if let Some(color) = force_color {
+                        color
+                    } else {
+                        bevy_markdown.color()
+                    },
+                },
+            };
+            text_sections.push((text_section, force_data.clone()));
+        }
         markdown::mdast::Node::Emphasis(emphasis) => emphasis.children.iter().for_each(|child| {
             let _ = handle_inline_styling(
                 child,
@@ -253,12 +320,12 @@ pub fn handle_inline_styling(
                     font_size: if let Some(size) = force_size {
                         size
                     } else {
-                        18.0
+                        bevy_markdown.font_size()
                     },
                     color: if let Some(color) = force_color {
                         color
                     } else {
-                        Color::BLACK
+                        bevy_markdown.color()
                     },
                 },
             };
@@ -290,6 +357,7 @@ fn handle_list_recursive(
     bevy_markdown: &BevyMarkdown,
     text_sections: &mut Vec<(TextSection, Option<String>)>,
     errors: &mut Vec<BevyMarkdownError>,
+    task_items: &mut Vec<TaskItem>,
     indentation_level: u8,
 ) -> Result<(), Vec<BevyMarkdownError>> {
     text_sections.push((
@@ -297,8 +365,8 @@ fn handle_list_recursive(
             value: "\n".to_string(),
             style: TextStyle {
                 font: bevy_markdown.regular_font.clone().unwrap(),
-                font_size: 18.0,
-                color: Color::BLACK,
+                font_size: bevy_markdown.font_size(),
+                color: bevy_markdown.color(),
             },
         },
         None,
@@ -320,25 +388,56 @@ fn handle_list_recursive(
                     ));
                 }
 
+                // checkbox position is looked up in the source to be able to toggle it
+                let task = item.checked.and_then(|checked| {
+                    let start = item.position.as_ref()?.start.offset;
+                    let offset = start + bevy_markdown.text.get(start..)?.find('[')?;
+                    Some((checked, offset))
+                });
+
                 let indent_char = if list.ordered {
                     let index = list_index.unwrap();
                     list_index = Some(index + 1);
-                    format!(" {}. ", index)
+                    Some(format!(" {}. ", index))
+                } else if task.is_some() {
+                    // checkbox replaces the bullet
+                    None
                 } else {
-                    get_bullet_for_indentation_level(indentation_level).to_string()
+                    Some(get_bullet_for_indentation_level(indentation_level).to_string())
                 };
 
-                text_sections.push((
-                    TextSection {
-                        value: indent_char,
-                        style: TextStyle {
-                            font: bevy_markdown.regular_font.clone().unwrap(),
-                            font_size: 18.0,
-                            color: Color::BLACK,
+                if let Some(indent_char) = indent_char {
+                    text_sections.push((
+                        TextSection {
+                            value: indent_char,
+                            style: TextStyle {
+                                font: bevy_markdown.regular_font.clone().unwrap(),
+                                font_size: bevy_markdown.font_size(),
+                                color: bevy_markdown.color(),
+                            },
                         },
-                    },
-                    None,
-                ));
+                        None,
+                    ));
+                }
+
+                if let Some((checked, offset)) = task {
+                    task_items.push(TaskItem {
+                        section_index: text_sections.len(),
+                        offset,
+                        checked,
+                    });
+                    text_sections.push((
+                        TextSection {
+                            value: get_task_checkbox(checked).to_string(),
+                            style: TextStyle {
+                                font: bevy_markdown.regular_font.clone().unwrap(),
+                                font_size: bevy_markdown.font_size(),
+                                color: bevy_markdown.color(),
+                            },
+                        },
+                        None,
+                    ));
+                }
 
                 item.children.into_iter().for_each(|child| match child {
                     markdown::mdast::Node::Paragraph(paragraph) => {
@@ -361,6 +460,7 @@ fn handle_list_recursive(
                             bevy_markdown,
                             text_sections,
                             errors,
+                            task_items,
                             indentation_level + 1,
                         );
                     }
@@ -374,8 +474,8 @@ fn handle_list_recursive(
                         value: "\n".to_string(),
                         style: TextStyle {
                             font: bevy_markdown.regular_font.clone().unwrap(),
-                            font_size: 18.0,
-                            color: Color::BLACK,
+                            font_size: bevy_markdown.font_size(),
+                            color: bevy_markdown.color(),
                         },
                     },
                     None,
@@ -390,11 +490,250 @@ fn handle_list_recursive(
     Ok(())
 }
 
+enum MarkdownBlock {
+    Text(Vec<(TextSection, Option<String>)>, Vec<TaskItem>),
+    Table(MarkdownTable),
+    Math(TextSection),
+}
+
+// Display math is set larger than surrounding text
+const DISPLAY_MATH_SCALE: f32 = 1.2;
+// Space between the edge of the view and its content
+const VIEW_PADDING: f32 = 10.;
+// Space above and below tables and display math
+const BLOCK_MARGIN: f32 = 5.;
+
+/// Math is set in the code font, which covers greek letters and math symbols.
+fn math_font(bevy_markdown: &BevyMarkdown) -> Handle<Font> {
+    bevy_markdown
+        .code_font
+        .clone()
+        .or_else(|| bevy_markdown.regular_font.clone())
+        .unwrap()
+}
+
+fn handle_display_math(math: &markdown::mdast::Math, bevy_markdown: &BevyMarkdown) -> TextSection {
+    // every line of multiline math (separated with \\) is typeset on its own
+    let value = math
+        .value
+        .split("\\\\")
+        .map(|line| typeset_tex(line.trim()))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    TextSection {
+        value,
+        style: TextStyle {
+            font: math_font(bevy_markdown),
+            font_size: bevy_markdown.font_size() * DISPLAY_MATH_SCALE,
+            color: bevy_markdown.color(),
+        },
+    }
+}
+
+struct MarkdownTable {
+    // rows of cells, the first row is the header
+    rows: Vec<Vec<Vec<(TextSection, Option<String>)>>>,
+    align: Vec<markdown::mdast::AlignKind>,
+}
+
+fn handle_table(
+    table: &markdown::mdast::Table,
+    bevy_markdown: &BevyMarkdown,
+    errors: &mut Vec<BevyMarkdownError>,
+) -> MarkdownTable {
+    let mut rows = Vec::new();
+    for (row_index, row) in table.children.iter().enumerate() {
+        let markdown::mdast::Node::TableRow(row) = row else {
+            errors.push(BevyMarkdownError::Transform {
+                info: "invalid table children".to_string(),
+            });
+            continue;
+        };
+        let style = if row_index == 0 {
+            InlineStyleType::Strong
+        } else {
+            InlineStyleType::None
+        };
+        let mut cells = Vec::new();
+        for cell in row.children.iter() {
+            let mut cell_sections = Vec::new();
+            if let markdown::mdast::Node::TableCell(cell) = cell {
+                cell.children.iter().for_each(|child| {
+                    let _ = handle_inline_styling(
+                        child,
+                        bevy_markdown,
+                        &mut cell_sections,
+                        errors,
+                        style.clone() as u8,
+                        None,
+                        None,
+                        &None,
+                    );
+                });
+            }
+            cells.push(cell_sections);
+        }
+        rows.push(cells);
+    }
+    MarkdownTable {
+        rows,
+        align: table.align.clone(),
+    }
+}
+
+/// Top of a markdown view, its blocks are stacked down from its top left corner by
+/// `layout_bevy_markdown`. Text wraps at `width` (padding included) when it's set, `size` is
+/// the size of the laid out view (padding included).
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct BevyMarkdownRoot {
+    pub width: Option<f32>,
+    pub size: Vec2,
+}
+
+/// Systems laying out markdown views, the size of their roots is known after them.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BevyMarkdownLayout;
+
+/// Block of a markdown view, laid out by `layout_bevy_markdown`.
+#[derive(Component)]
+enum BlockLayout {
+    Text,
+    Math,
+    Table {
+        // share of the table width taken by each column
+        shares: Vec<f32>,
+        align: Vec<markdown::mdast::AlignKind>,
+    },
+}
+
+#[derive(Component)]
+struct TableRow;
+
+/// Line below a table row, of the given thickness.
+#[derive(Component)]
+struct TableLine(f32);
+
+fn spawn_text_block(
+    commands: &mut Commands,
+    text_sections: Vec<(TextSection, Option<String>)>,
+    task_items: Vec<TaskItem>,
+    alignment: TextAlignment,
+    anchor: Anchor,
+) -> Entity {
+    let mut sections = Vec::new();
+    let mut links = Vec::new();
+    for (section, link) in text_sections {
+        sections.push(section);
+        links.push(link);
+    }
+    commands
+        .spawn((
+            Text2dBundle {
+                text: Text {
+                    sections,
+                    alignment,
+                    ..default()
+                },
+                text_anchor: anchor,
+                ..default()
+            },
+            BevyMarkdownNode {
+                id: Uuid::new_v4(),
+                link_sections: links,
+                task_items,
+            },
+        ))
+        .id()
+}
+
+const TABLE_CELL_PADDING: f32 = 4.;
+// Columns never get less than this share of the table width
+const TABLE_MIN_COLUMN_SHARE: f32 = 0.1;
+
+fn cell_anchor(align: Option<&markdown::mdast::AlignKind>) -> Anchor {
+    match align {
+        Some(markdown::mdast::AlignKind::Center) => Anchor::TopCenter,
+        Some(markdown::mdast::AlignKind::Right) => Anchor::TopRight,
+        _ => Anchor::TopLeft,
+    }
+}
+
+fn spawn_table(
+    commands: &mut Commands,
+    table: MarkdownTable,
+    bevy_markdown: &BevyMarkdown,
+) -> Entity {
+    let columns = table.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    // columns are sized by the longest text in them
+    let mut column_lengths = vec![1.; columns];
+    for row in table.rows.iter() {
+        for (column, cell) in row.iter().enumerate() {
+            let length: usize = cell
+                .iter()
+                .map(|(section, _)| section.value.chars().count())
+                .sum();
+            column_lengths[column] = f32::max(column_lengths[column], length as f32);
+        }
+    }
+    let total: f32 = column_lengths.iter().sum();
+    let mut shares: Vec<f32> = column_lengths
+        .iter()
+        .map(|length| (length / total).max(TABLE_MIN_COLUMN_SHARE))
+        .collect();
+    let total_share: f32 = shares.iter().sum();
+    shares.iter_mut().for_each(|share| *share /= total_share);
+    let line_color = bevy_markdown.color().with_a(0.3);
+
+    let table_entity = commands
+        .spawn((
+            SpatialBundle::default(),
+            BlockLayout::Table {
+                shares,
+                align: table.align.clone(),
+            },
+        ))
+        .id();
+    for (row_index, row) in table.rows.into_iter().enumerate() {
+        let row_entity = commands.spawn((SpatialBundle::default(), TableRow)).id();
+        for (column, cell) in row.into_iter().enumerate() {
+            let anchor = cell_anchor(table.align.get(column));
+            let text = spawn_text_block(commands, cell, vec![], TextAlignment::Left, anchor);
+            commands.entity(row_entity).add_child(text);
+        }
+        // header is separated with a thicker line
+        let line = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: line_color,
+                        anchor: Anchor::TopLeft,
+                        ..default()
+                    },
+                    ..default()
+                },
+                TableLine(if row_index == 0 { 2. } else { 1. }),
+            ))
+            .id();
+        commands.entity(table_entity).add_child(row_entity);
+        commands.entity(table_entity).add_child(line);
+    }
+    table_entity
+}
+
 pub fn spawn_bevy_markdown(
     commands: &mut Commands,
     bevy_markdown: BevyMarkdown,
 ) -> Result<Entity, Vec<BevyMarkdownError>> {
-    let node = markdown::to_mdast(bevy_markdown.text.as_str(), &markdown::ParseOptions::gfm());
+    let options = markdown::ParseOptions {
+        constructs: markdown::Constructs {
+            math_flow: true,
+            math_text: true,
+            ..markdown::Constructs::gfm()
+        },
+        ..markdown::ParseOptions::gfm()
+    };
+    let node = markdown::to_mdast(bevy_markdown.text.as_str(), &options);
     // Load these once at the start of your program
     let ps = SyntaxSet::load_defaults_newlines();
     let ts = ThemeSet::load_defaults();
@@ -403,6 +742,8 @@ pub fn spawn_bevy_markdown(
         Ok(node) => {
             let mut text_sections = Vec::new();
             let mut errors = Vec::new();
+            let mut task_items = Vec::new();
+            let mut blocks = Vec::new();
             match node {
                 markdown::mdast::Node::Root(root) => {
                     root.children.iter().for_each(|child| match child {
@@ -424,7 +765,7 @@ pub fn spawn_bevy_markdown(
                                     value: "\n\n".to_string(),
                                     style: TextStyle {
                                         font: bevy_markdown.regular_font.clone().unwrap(),
-                                        font_size: 18.0,
+                                        font_size: bevy_markdown.font_size(),
                                         color: Color::Rgba {
                                             red: 0.,
                                             green: 0.,
@@ -457,7 +798,7 @@ pub fn spawn_bevy_markdown(
                                         value: text.to_string(),
                                         style: TextStyle {
                                             font,
-                                            font_size: 18.0,
+                                            font_size: bevy_markdown.font_size(),
                                             color: Color::Rgba {
                                                 red: color.r as f32 / 255.,
                                                 green: color.g as f32 / 255.,
@@ -474,7 +815,7 @@ pub fn spawn_bevy_markdown(
                                     value: "\n".to_string(),
                                     style: TextStyle {
                                         font: bevy_markdown.regular_font.clone().unwrap(),
-                                        font_size: 18.0,
+                                        font_size: bevy_markdown.font_size(),
                                         color: Color::Rgba {
                                             red: 0.,
                                             green: 0.,
@@ -500,9 +841,37 @@ pub fn spawn_bevy_markdown(
                                 &bevy_markdown,
                                 &mut text_sections,
                                 &mut errors,
+                                &mut task_items,
                                 0,
                             );
                         }
+                        markdown::mdast::Node::Table(table) => {
+                            // tables are laid out separately from surrounding text
+                            if !text_sections.is_empty() {
+                                blocks.push(MarkdownBlock::Text(
+                                    std::mem::take(&mut text_sections),
+                                    std::mem::take(&mut task_items),
+                                ));
+                            }
+                            blocks.push(MarkdownBlock::Table(handle_table(
+                                table,
+                                &bevy_markdown,
+                                &mut errors,
+                            )));
+                        }
+                        markdown::mdast::Node::Math(math) => {
+                            // display math is centered on its own line
+                            if !text_sections.is_empty() {
+                                blocks.push(MarkdownBlock::Text(
+                                    std::mem::take(&mut text_sections),
+                                    std::mem::take(&mut task_items),
+                                ));
+                            }
+                            blocks.push(MarkdownBlock::Math(handle_display_math(
+                                math,
+                                &bevy_markdown,
+                            )));
+                        }
                         node => errors.push(BevyMarkdownError::Transform {
                             info: format!("{:?} node is not implemented for root", node),
                         }),
@@ -512,50 +881,49 @@ pub fn spawn_bevy_markdown(
                     info: format!("unexpected node: {:?}", node),
                 }),
             }
+            if !text_sections.is_empty() || blocks.is_empty() {
+                blocks.push(MarkdownBlock::Text(text_sections, task_items));
+            }
             if !errors.is_empty() {
                 Err(errors)
             } else {
-                let mut sections = Vec::new();
-                let mut links = Vec::new();
-                for (section, link) in text_sections {
-                    sections.push(section);
-                    links.push(link);
-                }
-                let text_bundle_id = Uuid::new_v4();
-                let top_style = Style {
-                    padding: UiRect::all(Val::Px(10.)),
-                    ..default()
-                };
-                let mut text_bundle_style = Style::default();
-                // Main branch of bevy doesn't need setting max_size for wrapping to work
-                // bevy_markdown will spawn multiple text bundles with more markdown features supported
-                // this is temp solution make wrapping to work
-                if let Some((x, y)) = bevy_markdown.size {
-                    text_bundle_style.max_size = Size::new(x, y);
-                }
                 let top = commands
-                    .spawn(NodeBundle {
-                        style: top_style,
-                        ..default()
-                    })
-                    .id();
-                let text_bundle = commands
                     .spawn((
-                        TextBundle {
-                            text: Text {
-                                sections,
-                                ..default()
-                            },
-                            style: text_bundle_style,
-                            ..default()
-                        },
-                        BevyMarkdownNode {
-                            id: text_bundle_id,
-                            link_sections: links,
+                        SpatialBundle::default(),
+                        BevyMarkdownRoot {
+                            width: bevy_markdown.width,
+                            size: Vec2::ZERO,
                         },
                     ))
                     .id();
-                commands.entity(top).add_child(text_bundle);
+                for block in blocks {
+                    let block = match block {
+                        MarkdownBlock::Text(text_sections, task_items) => {
+                            let text = spawn_text_block(
+                                commands,
+                                text_sections,
+                                task_items,
+                                TextAlignment::Left,
+                                Anchor::TopLeft,
+                            );
+                            commands.entity(text).insert(BlockLayout::Text);
+                            text
+                        }
+                        MarkdownBlock::Table(table) => spawn_table(commands, table, &bevy_markdown),
+                        MarkdownBlock::Math(section) => {
+                            let math = spawn_text_block(
+                                commands,
+                                vec![(section, None)],
+                                vec![],
+                                TextAlignment::Center,
+                                Anchor::TopCenter,
+                            );
+                            commands.entity(math).insert(BlockLayout::Math);
+                            math
+                        }
+                    };
+                    commands.entity(top).add_child(block);
+                }
                 Ok(top)
             }
         }
@@ -563,6 +931,150 @@ pub fn spawn_bevy_markdown(
     }
 }
 
+/// Lays out markdown views once their text is laid out: blocks are stacked from the top left
+/// corner of the view down, text wraps at the width of the view and table columns share it.
+fn layout_bevy_markdown(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut roots: Query<(&mut BevyMarkdownRoot, &Children)>,
+    blocks: Query<(&BlockLayout, Option<&Children>)>,
+    rows: Query<&Children, With<TableRow>>,
+    lines: Query<&TableLine>,
+    mut texts: Query<(&TextLayoutInfo, &mut Text2dBounds, &mut Text)>,
+    mut sprites: Query<&mut Sprite, With<TableLine>>,
+    mut transforms: Query<&mut Transform>,
+) {
+    // text layout is in physical pixels
+    let scale_factor = windows
+        .get_single()
+        .map_or(1., |window| window.scale_factor() as f32);
+    let mut place = |entity: Entity, position: Vec2| {
+        if let Ok(mut transform) = transforms.get_mut(entity) {
+            if transform.translation.truncate() != position {
+                transform.translation.x = position.x;
+                transform.translation.y = position.y;
+            }
+        }
+    };
+    let mut text_size = |entity: Entity, wrap_width: Option<f32>| {
+        let Ok((layout, mut bounds, mut text)) = texts.get_mut(entity) else {
+            return Vec2::ZERO;
+        };
+        let bounds_size = Vec2::new(wrap_width.unwrap_or(f32::MAX).max(1.), f32::MAX);
+        if bounds.size != bounds_size {
+            bounds.size = bounds_size;
+            // laid out again with the new bounds
+            text.set_changed();
+        }
+        layout.size / scale_factor
+    };
+    for (mut root, children) in roots.iter_mut() {
+        let content_width = root.width.map(|width| (width - 2. * VIEW_PADDING).max(0.));
+        let mut y = -VIEW_PADDING;
+        // widest block
+        let mut width: f32 = 0.;
+        for block in children.iter() {
+            let Ok((layout, block_children)) = blocks.get(*block) else {
+                continue;
+            };
+            match layout {
+                BlockLayout::Text => {
+                    let size = text_size(*block, content_width);
+                    place(*block, Vec2::new(VIEW_PADDING, y));
+                    y -= size.y;
+                    width = width.max(size.x);
+                }
+                BlockLayout::Math => {
+                    y -= BLOCK_MARGIN;
+                    let size = text_size(*block, content_width);
+                    let math_width = content_width.unwrap_or(0.).max(size.x);
+                    place(*block, Vec2::new(VIEW_PADDING + math_width / 2., y));
+                    y -= size.y + BLOCK_MARGIN;
+                    width = width.max(math_width);
+                }
+                BlockLayout::Table { shares, align } => {
+                    y -= BLOCK_MARGIN;
+                    place(*block, Vec2::new(VIEW_PADDING, y));
+                    let table_children: Vec<Entity> = block_children
+                        .map(|children| children.iter().copied().collect())
+                        .unwrap_or_default();
+                    let column_widths: Vec<f32> = match content_width {
+                        Some(width) => shares.iter().map(|share| width * share).collect(),
+                        // without a width columns are as wide as their widest cell
+                        None => {
+                            let mut widths = vec![0.; shares.len()];
+                            for row in rows.iter_many(&table_children) {
+                                for (column, cell) in row.iter().enumerate() {
+                                    let size = text_size(*cell, None);
+                                    widths[column] =
+                                        f32::max(widths[column], size.x + 2. * TABLE_CELL_PADDING);
+                                }
+                            }
+                            widths
+                        }
+                    };
+                    let table_width: f32 = column_widths.iter().sum();
+                    let mut row_y = 0.;
+                    for child in table_children {
+                        if let Ok(TableLine(thickness)) = lines.get(child) {
+                            if let Ok(mut sprite) = sprites.get_mut(child) {
+                                let size = Vec2::new(table_width, *thickness);
+                                if sprite.custom_size != Some(size) {
+                                    sprite.custom_size = Some(size);
+                                }
+                            }
+                            place(child, Vec2::new(0., row_y));
+                            row_y -= thickness;
+                            continue;
+                        }
+                        let Ok(cells) = rows.get(child) else {
+                            continue;
+                        };
+                        let mut row_height: f32 = 0.;
+                        let mut cell_x = 0.;
+                        for (column, cell) in cells.iter().enumerate() {
+                            let cell_width = column_widths.get(column).copied().unwrap_or(0.);
+                            let wrap_width = content_width
+                                .map(|_| (cell_width - 2. * TABLE_CELL_PADDING).max(1.));
+                            let size = text_size(*cell, wrap_width);
+                            row_height = row_height.max(size.y);
+                            let x = match cell_anchor(align.get(column)) {
+                                Anchor::TopCenter => cell_x + cell_width / 2.,
+                                Anchor::TopRight => cell_x + cell_width - TABLE_CELL_PADDING,
+                                _ => cell_x + TABLE_CELL_PADDING,
+                            };
+                            place(*cell, Vec2::new(x, -TABLE_CELL_PADDING));
+                            cell_x += cell_width;
+                        }
+                        place(child, Vec2::new(0., row_y));
+                        row_y -= row_height + 2. * TABLE_CELL_PADDING;
+                    }
+                    y += row_y - BLOCK_MARGIN;
+                    width = width.max(table_width);
+                }
+            }
+        }
+        let size = Vec2::new(width, -y) + VIEW_PADDING * Vec2::new(2., 1.);
+        if root.size != size {
+            root.size = size;
+        }
+    }
+}
+
+/// Lays out the markdown views spawned with `spawn_bevy_markdown`.
+pub struct BevyMarkdownPlugin;
+
+impl Plugin for BevyMarkdownPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            layout_bevy_markdown
+                .in_base_set(CoreSet::PostUpdate)
+                .in_set(BevyMarkdownLayout)
+                .after(update_text2d_layout)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bevy::prelude::*;
@@ -580,7 +1092,9 @@ mod tests {
                     semi_bold_italic_font: Some(font.clone()),
                     extra_bold_font: Some(font.clone()),
                     code_font: Some(font.clone()),
-                    size: None,
+                    width: None,
+                    font_size: None,
+                    color: None,
                     text: input.clone(),
                 };
                 spawn_bevy_markdown(&mut commands, bevy_markdown).unwrap();
@@ -732,4 +1246,186 @@ hello world
         .to_string();
         test_bevymarkdown(input, "test_render_nested_ordered_list".to_string())
     }
+
+    #[test]
+    pub fn test_render_task_list() {
+        let input = "- [ ] todo\n- [x] done\n- plain\n".to_string();
+        let text = input.clone();
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default());
+        app.add_plugin(AssetPlugin::default());
+        app.add_system(
+            move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                let font: Handle<Font> = asset_server.load("fonts/SourceCodePro-Regular.ttf");
+                let bevy_markdown = BevyMarkdown {
+                    regular_font: Some(font.clone()),
+                    text: input.clone(),
+                    ..default()
+                };
+                spawn_bevy_markdown(&mut commands, bevy_markdown).unwrap();
+            },
+        );
+
+        app.update();
+
+        let mut query = app.world.query::<(&Text, &BevyMarkdownNode)>();
+        let (text_node, markdown_node) = query.single(&app.world);
+        let tasks = &markdown_node.task_items;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(&text[tasks[0].offset..tasks[0].offset + 3], "[ ]");
+        assert_eq!(&text[tasks[1].offset..tasks[1].offset + 3], "[x]");
+        assert!(!tasks[0].checked);
+        assert!(tasks[1].checked);
+        assert_eq!(
+            text_node.sections[tasks[1].section_index].value,
+            get_task_checkbox(true)
+        );
+    }
+
+    #[test]
+    pub fn test_render_table() {
+        let input = "Before
+
+| Name | Value |
+| :--- | ----: |
+| a | 1 |
+| b | **2** |
+
+After
+"
+        .to_string();
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default());
+        app.add_plugin(AssetPlugin::default());
+        app.add_system(
+            move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                let font: Handle<Font> = asset_server.load("fonts/SourceCodePro-Regular.ttf");
+                let bevy_markdown = BevyMarkdown {
+                    regular_font: Some(font.clone()),
+                    bold_font: Some(font.clone()),
+                    width: Some(300.),
+                    text: input.clone(),
+                    ..default()
+                };
+                spawn_bevy_markdown(&mut commands, bevy_markdown).unwrap();
+            },
+        );
+
+        app.update();
+
+        let mut query = app.world.query::<&Text>();
+        let texts: Vec<String> = query
+            .iter(&app.world)
+            .map(|text| {
+                text.sections
+                    .iter()
+                    .map(|section| section.value.as_str())
+                    .collect()
+            })
+            .collect();
+        // text before and after the table and one text per cell
+        assert_eq!(texts.len(), 8);
+        for cell in ["Name", "Value", "a", "1", "b", "2"] {
+            assert!(texts.iter().any(|text| text == cell));
+        }
+    }
+
+    #[test]
+    pub fn test_render_math() {
+        let input = r"Energy $E = mc^2$
+
+$$
+\frac{a}{b} \leq \alpha
+$$
+"
+        .to_string();
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default());
+        app.add_plugin(AssetPlugin::default());
+        app.add_system(
+            move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                let font: Handle<Font> = asset_server.load("fonts/SourceCodePro-Regular.ttf");
+                let bevy_markdown = BevyMarkdown {
+                    regular_font: Some(font.clone()),
+                    code_font: Some(font.clone()),
+                    text: input.clone(),
+                    ..default()
+                };
+                spawn_bevy_markdown(&mut commands, bevy_markdown).unwrap();
+            },
+        );
+
+        app.update();
+
+        let mut query = app.world.query::<&Text>();
+        let texts: Vec<String> = query
+            .iter(&app.world)
+            .map(|text| {
+                text.sections
+                    .iter()
+                    .map(|section| section.value.as_str())
+                    .collect()
+            })
+            .collect();
+        // display math is a text of its own
+        assert_eq!(texts.len(), 2);
+        assert!(texts.iter().any(|text| text.contains("Energy E = mc²")));
+        assert!(texts.iter().any(|text| text == "a/b ≤ α"));
+    }
+
+    #[test]
+    pub fn test_layout() {
+        let input = "Before
+
+| a | b |
+| - | - |
+| 1 | 2 |
+
+After
+"
+        .to_string();
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default());
+        app.add_plugin(AssetPlugin::default());
+        app.add_startup_system(
+            move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                let font: Handle<Font> = asset_server.load("fonts/SourceCodePro-Regular.ttf");
+                let bevy_markdown = BevyMarkdown {
+                    regular_font: Some(font.clone()),
+                    bold_font: Some(font.clone()),
+                    width: Some(220.),
+                    text: input.clone(),
+                    ..default()
+                };
+                spawn_bevy_markdown(&mut commands, bevy_markdown).unwrap();
+            },
+        );
+        app.update();
+
+        // every text is laid out as 40x20
+        let mut layouts = app.world.query::<&mut TextLayoutInfo>();
+        for mut layout in layouts.iter_mut(&mut app.world) {
+            layout.size = Vec2::new(40., 20.);
+        }
+        app.add_system(layout_bevy_markdown);
+        app.update();
+
+        let mut query = app.world.query::<(&Text, &Transform, &Text2dBounds)>();
+        let mut placed = |text: &str| {
+            query
+                .iter(&app.world)
+                .find(|(t, _, _)| t.sections.iter().any(|section| section.value == text))
+                .map(|(_, transform, bounds)| (transform.translation.truncate(), bounds.size.x))
+                .unwrap()
+        };
+        assert_eq!(placed("Before"), (Vec2::new(10., -10.), 200.));
+        // cells are placed in their row, columns share the width of the table equally
+        assert_eq!(placed("a"), (Vec2::new(4., -4.), 92.));
+        assert_eq!(placed("2"), (Vec2::new(104., -4.), 92.));
+        // table below the text with a margin, two rows of 28 and lines of 2 and 1 below them
+        assert_eq!(placed("After"), (Vec2::new(10., -99.), 200.));
+        let mut roots = app.world.query::<&BevyMarkdownRoot>();
+        // as wide as the table, down to the bottom of the last text
+        assert_eq!(roots.single(&app.world).size, Vec2::new(220., 129.));
+    }
 }
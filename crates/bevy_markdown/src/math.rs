@@ -0,0 +1,441 @@
+//! Typesetting of TeX math (`$...$` and `$$...$$`) into a single line of unicode text.
+//!
+//! Only the subset of TeX commonly used in notes is supported: greek letters, operators and
+//! relations, super/subscripts, fractions, roots and text. Unknown commands are printed by name.
+
+#[derive(Clone, Copy, PartialEq)]
+enum AtomKind {
+    Ord,
+    // large operators and function names (\sum, \sin)
+    Op,
+    // binary operators (+, \times)
+    Bin,
+    // relations (=, \leq)
+    Rel,
+    Open,
+    Close,
+    Punct,
+}
+
+struct Atom {
+    kind: AtomKind,
+    text: String,
+}
+
+const SYMBOLS: &[(&str, &str, AtomKind)] = &[
+    ("alpha", "α", AtomKind::Ord),
+    ("beta", "β", AtomKind::Ord),
+    ("gamma", "γ", AtomKind::Ord),
+    ("delta", "δ", AtomKind::Ord),
+    ("epsilon", "ε", AtomKind::Ord),
+    ("varepsilon", "ε", AtomKind::Ord),
+    ("zeta", "ζ", AtomKind::Ord),
+    ("eta", "η", AtomKind::Ord),
+    ("theta", "θ", AtomKind::Ord),
+    ("vartheta", "ϑ", AtomKind::Ord),
+    ("iota", "ι", AtomKind::Ord),
+    ("kappa", "κ", AtomKind::Ord),
+    ("lambda", "λ", AtomKind::Ord),
+    ("mu", "μ", AtomKind::Ord),
+    ("nu", "ν", AtomKind::Ord),
+    ("xi", "ξ", AtomKind::Ord),
+    ("pi", "π", AtomKind::Ord),
+    ("rho", "ρ", AtomKind::Ord),
+    ("sigma", "σ", AtomKind::Ord),
+    ("tau", "τ", AtomKind::Ord),
+    ("upsilon", "υ", AtomKind::Ord),
+    ("phi", "φ", AtomKind::Ord),
+    ("varphi", "φ", AtomKind::Ord),
+    ("chi", "χ", AtomKind::Ord),
+    ("psi", "ψ", AtomKind::Ord),
+    ("omega", "ω", AtomKind::Ord),
+    ("Gamma", "Γ", AtomKind::Ord),
+    ("Delta", "Δ", AtomKind::Ord),
+    ("Theta", "Θ", AtomKind::Ord),
+    ("Lambda", "Λ", AtomKind::Ord),
+    ("Xi", "Ξ", AtomKind::Ord),
+    ("Pi", "Π", AtomKind::Ord),
+    ("Sigma", "Σ", AtomKind::Ord),
+    ("Phi", "Φ", AtomKind::Ord),
+    ("Psi", "Ψ", AtomKind::Ord),
+    ("Omega", "Ω", AtomKind::Ord),
+    ("infty", "∞", AtomKind::Ord),
+    ("partial", "∂", AtomKind::Ord),
+    ("ell", "ℓ", AtomKind::Ord),
+    ("hbar", "ħ", AtomKind::Ord),
+    ("prime", "′", AtomKind::Ord),
+    ("degree", "°", AtomKind::Ord),
+    ("forall", "∀", AtomKind::Ord),
+    ("exists", "∃", AtomKind::Ord),
+    ("neg", "¬", AtomKind::Ord),
+    ("lnot", "¬", AtomKind::Ord),
+    ("ldots", "…", AtomKind::Ord),
+    ("dots", "…", AtomKind::Ord),
+    ("cdots", "···", AtomKind::Ord),
+    ("sum", "∑", AtomKind::Op),
+    ("prod", "∏", AtomKind::Op),
+    ("int", "∫", AtomKind::Op),
+    ("pm", "±", AtomKind::Bin),
+    ("times", "×", AtomKind::Bin),
+    ("div", "÷", AtomKind::Bin),
+    ("cdot", "·", AtomKind::Bin),
+    ("cap", "∩", AtomKind::Bin),
+    ("leq", "≤", AtomKind::Rel),
+    ("le", "≤", AtomKind::Rel),
+    ("geq", "≥", AtomKind::Rel),
+    ("ge", "≥", AtomKind::Rel),
+    ("neq", "≠", AtomKind::Rel),
+    ("ne", "≠", AtomKind::Rel),
+    ("approx", "≈", AtomKind::Rel),
+    ("equiv", "≡", AtomKind::Rel),
+    ("to", "→", AtomKind::Rel),
+    ("rightarrow", "→", AtomKind::Rel),
+    ("leftarrow", "←", AtomKind::Rel),
+    ("gets", "←", AtomKind::Rel),
+    ("leftrightarrow", "↔", AtomKind::Rel),
+    ("Rightarrow", "⇒", AtomKind::Rel),
+    ("implies", "⇒", AtomKind::Rel),
+    ("Leftarrow", "⇐", AtomKind::Rel),
+    // glyphs missing in the bundled font are spelled out
+    ("in", "in", AtomKind::Rel),
+    ("notin", "not in", AtomKind::Rel),
+    ("land", "and", AtomKind::Bin),
+    ("wedge", "and", AtomKind::Bin),
+    ("lor", "or", AtomKind::Bin),
+    ("vee", "or", AtomKind::Bin),
+    ("langle", "<", AtomKind::Open),
+    ("rangle", ">", AtomKind::Close),
+    ("lbrace", "{", AtomKind::Open),
+    ("rbrace", "}", AtomKind::Close),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd", "deg", "dim", "ker",
+    "arg", "Pr",
+];
+
+// Commands with no visible output of their own
+const IGNORED: &[&str] = &[
+    "left",
+    "right",
+    "big",
+    "Big",
+    "bigg",
+    "Bigg",
+    "displaystyle",
+    "textstyle",
+    "limits",
+    "nolimits",
+];
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'),
+    ('1', '¹'),
+    ('2', '²'),
+    ('3', '³'),
+    ('4', '⁴'),
+    ('5', '⁵'),
+    ('6', '⁶'),
+    ('7', '⁷'),
+    ('8', '⁸'),
+    ('9', '⁹'),
+    ('(', '⁽'),
+    (')', '⁾'),
+    ('n', 'ⁿ'),
+    ('i', 'ⁱ'),
+    ('′', '′'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'),
+    ('1', '₁'),
+    ('2', '₂'),
+    ('3', '₃'),
+    ('4', '₄'),
+    ('5', '₅'),
+    ('6', '₆'),
+    ('7', '₇'),
+    ('8', '₈'),
+    ('9', '₉'),
+    ('(', '₍'),
+    (')', '₎'),
+];
+
+/// Typesets TeX math source into unicode text, e.g. `\alpha^2 \leq \frac{a}{b}` into `α² ≤ a/b`.
+pub fn typeset_tex(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut parser = TexParser { chars, pos: 0 };
+    let atoms = parser.parse_list(None);
+    join_atoms(&atoms)
+}
+
+struct TexParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl TexParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses atoms until `end` (closing brace or bracket) or end of input.
+    fn parse_list(&mut self, end: Option<char>) -> Vec<Atom> {
+        let mut atoms: Vec<Atom> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.peek() else {
+                break;
+            };
+            if Some(c) == end {
+                self.pos += 1;
+                break;
+            }
+            match c {
+                '^' | '_' => {
+                    self.pos += 1;
+                    let script = self.parse_argument();
+                    let text = if c == '^' {
+                        superscript(&script)
+                    } else {
+                        subscript(&script)
+                    };
+                    match atoms.last_mut() {
+                        Some(last) => last.text.push_str(&text),
+                        None => atoms.push(Atom {
+                            kind: AtomKind::Ord,
+                            text,
+                        }),
+                    }
+                }
+                '\'' => {
+                    self.pos += 1;
+                    match atoms.last_mut() {
+                        Some(last) => last.text.push('′'),
+                        None => atoms.push(Atom {
+                            kind: AtomKind::Ord,
+                            text: "′".to_string(),
+                        }),
+                    }
+                }
+                _ => {
+                    if let Some(atom) = self.parse_atom() {
+                        atoms.push(atom);
+                    }
+                }
+            }
+        }
+        atoms
+    }
+
+    /// Argument of a command or script: a braced group or a single atom.
+    fn parse_argument(&mut self) -> String {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                join_atoms(&self.parse_list(Some('}')))
+            }
+            Some(_) => self.parse_atom().map(|atom| atom.text).unwrap_or_default(),
+            None => String::new(),
+        }
+    }
+
+    /// Raw content of a braced group, used for `\text{...}`.
+    fn parse_text_argument(&mut self) -> String {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return self.parse_argument();
+        }
+        self.pos += 1;
+        let mut depth = 0;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+        }
+        text
+    }
+
+    fn parse_atom(&mut self) -> Option<Atom> {
+        let c = self.peek()?;
+        self.pos += 1;
+        let (kind, text) = match c {
+            '\\' => return self.parse_command(),
+            '{' => (AtomKind::Ord, join_atoms(&self.parse_list(Some('}')))),
+            '+' | '*' => (AtomKind::Bin, c.to_string()),
+            '-' => (AtomKind::Bin, "−".to_string()),
+            '=' | '<' | '>' => (AtomKind::Rel, c.to_string()),
+            '(' | '[' => (AtomKind::Open, c.to_string()),
+            ')' | ']' => (AtomKind::Close, c.to_string()),
+            ',' | ';' => (AtomKind::Punct, c.to_string()),
+            // alignment marks of multiline environments
+            '&' => return None,
+            _ => (AtomKind::Ord, c.to_string()),
+        };
+        Some(Atom { kind, text })
+    }
+
+    fn parse_command(&mut self) -> Option<Atom> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            // control symbol like \{ or \,
+            let c = self.peek()?;
+            self.pos += 1;
+            let (kind, text) = match c {
+                '{' => (AtomKind::Open, "{".to_string()),
+                '}' => (AtomKind::Close, "}".to_string()),
+                '|' => (AtomKind::Ord, "‖".to_string()),
+                // line breaks are handled by the caller for display math
+                ',' | ':' | ';' | ' ' | '\\' => (AtomKind::Ord, " ".to_string()),
+                '!' => return None,
+                _ => (AtomKind::Ord, c.to_string()),
+            };
+            return Some(Atom { kind, text });
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        let name = name.as_str();
+        if let Some((_, symbol, kind)) = SYMBOLS.iter().find(|(command, _, _)| *command == name) {
+            return Some(Atom {
+                kind: *kind,
+                text: symbol.to_string(),
+            });
+        }
+        if FUNCTIONS.contains(&name) {
+            return Some(Atom {
+                kind: AtomKind::Op,
+                text: name.to_string(),
+            });
+        }
+        if IGNORED.contains(&name) {
+            return None;
+        }
+        if name == "begin" || name == "end" {
+            // environment name
+            self.parse_text_argument();
+            return None;
+        }
+        let text = match name {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.parse_argument();
+                let denominator = self.parse_argument();
+                format!(
+                    "{}/{}",
+                    parenthesize(&numerator),
+                    parenthesize(&denominator)
+                )
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.peek() == Some('[') {
+                    self.pos += 1;
+                    superscript(&join_atoms(&self.parse_list(Some(']'))))
+                } else {
+                    String::new()
+                };
+                format!("{}√{}", index, parenthesize(&self.parse_argument()))
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mathrm" | "mathit" | "mathbf" | "mathsf"
+            | "mathtt" | "operatorname" => self.parse_text_argument(),
+            "quad" | "qquad" => "  ".to_string(),
+            _ => name.to_string(),
+        };
+        Some(Atom {
+            kind: AtomKind::Ord,
+            text,
+        })
+    }
+}
+
+/// Joins atoms with spacing of TeX: binary operators and relations are surrounded by spaces,
+/// punctuation and operators are followed by one.
+fn join_atoms(atoms: &[Atom]) -> String {
+    let mut result = String::new();
+    let mut previous: Option<AtomKind> = None;
+    for atom in atoms {
+        let mut kind = atom.kind;
+        // unary minus or plus
+        if kind == AtomKind::Bin
+            && matches!(
+                previous,
+                None | Some(AtomKind::Bin | AtomKind::Rel | AtomKind::Open | AtomKind::Punct)
+            )
+        {
+            kind = AtomKind::Ord;
+        }
+        let spaced = match (previous, kind) {
+            (None, _) => false,
+            (_, AtomKind::Bin | AtomKind::Rel) => true,
+            (Some(AtomKind::Bin | AtomKind::Rel | AtomKind::Punct), _) => true,
+            (Some(AtomKind::Op), AtomKind::Ord | AtomKind::Op) => true,
+            _ => false,
+        };
+        if spaced && !result.ends_with(' ') {
+            result.push(' ');
+        }
+        result.push_str(&atom.text);
+        previous = Some(kind);
+    }
+    result
+}
+
+fn parenthesize(text: &str) -> String {
+    if text.chars().count() <= 1 || text.chars().all(|c| c.is_ascii_digit()) {
+        text.to_string()
+    } else {
+        format!("({})", text)
+    }
+}
+
+fn superscript(text: &str) -> String {
+    convert_script(text, SUPERSCRIPTS).unwrap_or_else(|| format!("^{}", parenthesize(text)))
+}
+
+fn subscript(text: &str) -> String {
+    convert_script(text, SUBSCRIPTS).unwrap_or_else(|| format!("_{}", parenthesize(text)))
+}
+
+/// Converts every char to its script form, if all of them have one.
+fn convert_script(text: &str, table: &[(char, char)]) -> Option<String> {
+    text.chars()
+        .map(|c| {
+            table
+                .iter()
+                .find(|(plain, _)| *plain == c)
+                .map(|(_, script)| *script)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::typeset_tex;
+
+    #[test]
+    pub fn test_typeset_tex() {
+        assert_eq!(typeset_tex("E = mc^2"), "E = mc²");
+        assert_eq!(typeset_tex(r"\alpha+\beta\leq\gamma_{12}"), "α + β ≤ γ₁₂");
+        assert_eq!(
+            typeset_tex(r"x = \frac{-b \pm \sqrt{b^2-4ac}}{2a}"),
+            "x = (−b ± √(b² − 4ac))/(2a)"
+        );
+        assert_eq!(typeset_tex(r"\sum_{i=1}^n x_i"), "∑_(i = 1)ⁿ x_i");
+        assert_eq!(typeset_tex(r"\sin x, \text{if } x > 0"), "sin x, if x > 0");
+    }
+}
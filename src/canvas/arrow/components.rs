@@ -19,6 +19,73 @@ pub struct ArrowConnect {
     pub pos: ArrowConnectPos,
 }
 
+/// Text shown in the middle of the arrow, edited with double click on the arrow.
+#[derive(Component, Default, Clone, Debug)]
+pub struct ArrowLabel(pub String);
+
+/// Relationship types offered for arrows, any other text is a custom relationship.
+pub const ARROW_RELATIONS: [&str; 3] = ["depends on", "blocks", "relates to"];
+
+/// Type of the relationship between the connected nodes, e.g. "depends on" or "blocks". Arrows
+/// can be filtered by it in the tag list.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct ArrowRelation(pub String);
+
+/// Ends of the arrow pinned to the side of the node they were dropped on, other ends move to the
+/// side of their node closest to the other end whenever the arrow is redrawn.
+#[derive(Component, Default, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArrowPins {
+    pub start: bool,
+    pub end: bool,
+}
+
+/// Points the arrow line is routed through, in canvas coordinates (like the points of pen
+/// strokes), added by dragging the middle of the selected arrow.
+#[derive(Component, Default, Clone, Debug)]
+pub struct ArrowWaypoints(pub Vec<Vec2>);
+
+/// Points of the arrow line (world coordinates) and directions of the arrow at its ends, kept to
+/// redraw animated arrows without routing them again.
+#[derive(Component, Default, Clone, Debug)]
+pub struct ArrowRoute {
+    pub points: Vec<Vec2>,
+    pub start_direction: Vec2,
+    pub end_direction: Vec2,
+}
+
+/// Points of the arrow line (world coordinates) where its label and the handles of its ends
+/// are placed, updated when the arrow is redrawn.
+#[derive(Component, Default, Copy, Clone, Debug, PartialEq)]
+pub struct ArrowAnchors {
+    pub midpoint: Vec2,
+    pub start_handle: Vec2,
+    pub end_handle: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArrowEnd {
+    Start,
+    End,
+}
+
+/// Handle of an end of the selected arrow, dragging it re-attaches that end.
+#[derive(Component)]
+pub struct ArrowEndHandle {
+    pub end: ArrowEnd,
+}
+
+/// Dot moving along an arrow with the `MovingDot` animation.
+#[derive(Component)]
+pub struct ArrowFlowDot {
+    pub arrow: Entity,
+}
+
+/// Handle of a waypoint of the selected arrow, dragging it moves the waypoint.
+#[derive(Component)]
+pub struct ArrowWaypointHandle {
+    pub index: usize,
+}
+
 #[derive(Component)]
 pub struct ArrowMode {
     pub arrow_type: ArrowType,
@@ -42,3 +109,121 @@ pub enum ArrowType {
     ParallelArrow,
     ParallelDoubleArrow,
 }
+
+/// Shape drawn at an end of an arrow.
+#[derive(Serialize, Deserialize, Default, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArrowHead {
+    #[default]
+    None,
+    Filled,
+    Open,
+    Diamond,
+    Circle,
+}
+
+#[derive(Serialize, Deserialize, Default, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArrowDash {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// Route of an arrow line: `Straight` follows the `ArrowType` (direct or parallel line), `Curved`
+/// is a smooth bezier and `Elbow` an orthogonal line going around nodes.
+#[derive(Serialize, Deserialize, Default, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArrowRouting {
+    #[default]
+    Straight,
+    Curved,
+    Elbow,
+}
+
+/// Animation showing the direction of an arrow, it stands still with animations turned off.
+#[derive(Serialize, Deserialize, Default, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArrowAnimation {
+    #[default]
+    None,
+    MarchingDashes,
+    MovingDot,
+}
+
+/// Look of an arrow.
+#[derive(Component, Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct ArrowStyle {
+    pub start_head: ArrowHead,
+    pub end_head: ArrowHead,
+    pub thickness: f32,
+    pub dash: ArrowDash,
+    pub color: Color,
+    #[serde(default)]
+    pub routing: ArrowRouting,
+    #[serde(default)]
+    pub animation: ArrowAnimation,
+}
+
+impl Default for ArrowStyle {
+    fn default() -> Self {
+        Self {
+            start_head: ArrowHead::None,
+            end_head: ArrowHead::Open,
+            thickness: 1.5,
+            dash: ArrowDash::Solid,
+            color: Color::rgb(63.0 / 255.0, 81.0 / 255.0, 181.0 / 255.0),
+            routing: ArrowRouting::Straight,
+            animation: ArrowAnimation::None,
+        }
+    }
+}
+
+impl ArrowStyle {
+    /// Default style with the heads of the arrow type, e.g. for arrows saved without a style.
+    pub fn for_type(arrow_type: ArrowType) -> Self {
+        Self::default().with_type_heads(arrow_type)
+    }
+
+    /// Style saved with the arrow under `style`, older documents don't have it.
+    pub fn from_json(arrow: &serde_json::Value, arrow_type: ArrowType) -> Self {
+        serde_json::from_value(arrow["style"].clone())
+            .unwrap_or_else(|_| Self::for_type(arrow_type))
+    }
+
+    pub fn with_type_heads(self, arrow_type: ArrowType) -> Self {
+        let (start_head, end_head) = match arrow_type {
+            ArrowType::Line | ArrowType::ParallelLine => (ArrowHead::None, ArrowHead::None),
+            ArrowType::Arrow | ArrowType::ParallelArrow => (ArrowHead::None, ArrowHead::Open),
+            ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => {
+                (ArrowHead::Open, ArrowHead::Open)
+            }
+        };
+        Self {
+            start_head,
+            end_head,
+            ..self
+        }
+    }
+
+    /// Default look of arrows of the relationship type, custom relationships keep the style.
+    pub fn with_relation(self, relation: &str) -> Self {
+        match relation {
+            "depends on" => Self {
+                dash: ArrowDash::Dashed,
+                end_head: ArrowHead::Open,
+                ..self
+            },
+            "blocks" => Self {
+                end_head: ArrowHead::Filled,
+                thickness: 2.5,
+                color: Color::rgb(229.0 / 255.0, 57.0 / 255.0, 53.0 / 255.0),
+                ..self
+            },
+            "relates to" => Self {
+                start_head: ArrowHead::None,
+                end_head: ArrowHead::None,
+                dash: ArrowDash::Dotted,
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
@@ -1,11 +1,19 @@
-use super::components::{ArrowConnect, ArrowType};
+use bevy::prelude::Vec2;
+
+use super::components::{ArrowConnect, ArrowPins, ArrowStyle, ArrowType};
 use crate::utils::ReflectableUuid;
 pub struct RedrawArrowEvent {
     pub id: ReflectableUuid,
 }
-#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct CreateArrowEvent {
     pub arrow_type: ArrowType,
     pub start: ArrowConnect,
     pub end: ArrowConnect,
+    pub style: ArrowStyle,
+    pub label: Option<String>,
+    pub relation: Option<String>,
+    pub pins: ArrowPins,
+    /// Canvas coordinates
+    pub waypoints: Vec<Vec2>,
 }
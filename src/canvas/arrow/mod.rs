@@ -1,18 +1,32 @@
 pub mod components;
 pub mod events;
+pub mod resources;
 mod systems;
-mod utils;
-use bevy::app::{App, Plugin};
+pub mod utils;
+use bevy::{
+    app::{App, Plugin},
+    prelude::{apply_system_buffers, IntoSystemConfigs},
+};
 use bevy_prototype_lyon::prelude::ShapePlugin;
+use resources::ArrowIndex;
 use systems::*;
 pub struct ArrowPlugin;
 
 impl Plugin for ArrowPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(ShapePlugin).add_systems((
-            create_arrow_start,
-            create_arrow_end,
-            redraw_arrows,
-        ));
+        // created arrows are spawned and indexed before the redraw spreading them from parallel
+        // ones
+        app.add_plugin(ShapePlugin)
+            .init_resource::<ArrowIndex>()
+            .add_systems(
+                (
+                    create_arrow_start,
+                    create_arrow_end,
+                    apply_system_buffers,
+                    update_arrow_index,
+                    redraw_arrows,
+                )
+                    .chain(),
+            );
     }
 }
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::utils::ReflectableUuid;
+
+/// Arrows attached to every node, so moving a node redraws only its arrows. It follows spawned,
+/// re-attached and despawned arrows.
+#[derive(Resource, Default)]
+pub struct ArrowIndex {
+    by_node: HashMap<ReflectableUuid, Vec<Entity>>,
+    ends: HashMap<Entity, (ReflectableUuid, ReflectableUuid)>,
+}
+
+impl ArrowIndex {
+    pub fn insert(&mut self, arrow: Entity, start: ReflectableUuid, end: ReflectableUuid) {
+        if self.ends.get(&arrow) == Some(&(start, end)) {
+            return;
+        }
+        self.remove(arrow);
+        self.ends.insert(arrow, (start, end));
+        self.by_node.entry(start).or_default().push(arrow);
+        // loops are attached to their node once
+        if end != start {
+            self.by_node.entry(end).or_default().push(arrow);
+        }
+    }
+
+    pub fn remove(&mut self, arrow: Entity) {
+        let Some((start, end)) = self.ends.remove(&arrow) else {
+            return;
+        };
+        for id in [start, end] {
+            if let Some(arrows) = self.by_node.get_mut(&id) {
+                arrows.retain(|entity| *entity != arrow);
+                if arrows.is_empty() {
+                    self.by_node.remove(&id);
+                }
+            }
+        }
+    }
+
+    pub fn arrows_of(&self, id: &ReflectableUuid) -> &[Entity] {
+        self.by_node.get(id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrow_index() {
+        let (a, b, c) = (
+            ReflectableUuid(uuid::Uuid::from_u128(1)),
+            ReflectableUuid(uuid::Uuid::from_u128(2)),
+            ReflectableUuid(uuid::Uuid::from_u128(3)),
+        );
+        let (arrow1, arrow2) = (Entity::from_raw(1), Entity::from_raw(2));
+        let mut index = ArrowIndex::default();
+        index.insert(arrow1, a, b);
+        index.insert(arrow2, b, b);
+        assert_eq!(index.arrows_of(&b), &[arrow1, arrow2]);
+        // re-attached end
+        index.insert(arrow1, a, c);
+        assert_eq!(index.arrows_of(&b), &[arrow2]);
+        assert_eq!(index.arrows_of(&c), &[arrow1]);
+        index.remove(arrow2);
+        assert!(index.arrows_of(&b).is_empty());
+    }
+}
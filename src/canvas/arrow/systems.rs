@@ -1,14 +1,88 @@
+use std::collections::{HashMap, HashSet};
+
 use bevy::{prelude::*, window::PrimaryWindow};
 
 // use super::utils::{build_arrow, create_arrow};
-use super::components::{ArrowConnect, ArrowMeta};
+use super::components::{
+    ArrowAnchors, ArrowConnect, ArrowConnectPos, ArrowLabel, ArrowMeta, ArrowPins, ArrowRelation,
+    ArrowRoute, ArrowRouting, ArrowStyle, ArrowWaypoints,
+};
 // use crate::states::{AppState, MainCamera, RedrawArrow};
 use super::events::{CreateArrowEvent, RedrawArrowEvent};
-use super::utils::{build_arrow, create_arrow, get_pos};
-use crate::components::MainCamera;
-use crate::ui_plugin::UiState;
+use super::resources::ArrowIndex;
+use super::utils::{build_arrow, connection_cost, create_arrow, parallel_offsets};
+use crate::ui_plugin::{NodeIndex, NodeRect, Theme, UiState, VeloNodeContainer};
+use crate::utils::ReflectableUuid;
 use bevy_prototype_lyon::prelude::Path;
 
+// Nodes this far from the nodes an elbow arrow connects are not in its way
+const OBSTACLE_SEARCH_MARGIN: f32 = 200.;
+
+/// Positions of the connection markers of the nodes, looked up in one pass over the markers.
+fn marker_positions(
+    arrow_markers: &Query<(&ArrowConnect, &GlobalTransform), With<ArrowConnect>>,
+    nodes: &HashSet<ReflectableUuid>,
+) -> HashMap<ArrowConnect, Vec2> {
+    arrow_markers
+        .iter()
+        .filter(|(marker, _)| nodes.contains(&marker.id))
+        .map(|(marker, gt)| (*marker, gt.translation().truncate()))
+        .collect()
+}
+
+/// Part of the canvas around the connected nodes and the waypoints of an arrow, `None` until both nodes are in the index.
+fn obstacle_area(
+    node_index: &NodeIndex,
+    start: &ReflectableUuid,
+    end: &ReflectableUuid,
+    waypoints: &[Vec2],
+) -> Option<Rect> {
+    let start = node_index.rect_of(start)?;
+    let end = node_index.rect_of(end)?;
+    let area = [end.min, end.max]
+        .into_iter()
+        .chain(waypoints.iter().copied())
+        .fold(start, |area, point| {
+            Rect::from_corners(area.min.min(point), area.max.max(point))
+        });
+    let margin = Vec2::splat(OBSTACLE_SEARCH_MARGIN);
+    Some(Rect::from_corners(area.min - margin, area.max + margin))
+}
+
+/// Bounding boxes of the visible nodes in `area` (all of them without it), elbow arrows go
+/// around them.
+fn node_rects(
+    nodes: &Query<(&NodeRect, &ComputedVisibility), With<VeloNodeContainer>>,
+    node_index: &NodeIndex,
+    area: Option<Rect>,
+) -> Vec<Rect> {
+    let rect = |(rect, visibility): (&NodeRect, &ComputedVisibility)| {
+        visibility.is_visible().then(|| rect.rect())
+    };
+    match area {
+        Some(area) => node_index
+            .intersecting(area)
+            .into_iter()
+            .filter_map(|(entity, _)| nodes.get(entity).ok())
+            .filter_map(rect)
+            .collect(),
+        None => nodes.iter().filter_map(rect).collect(),
+    }
+}
+
+/// Side of a node a loop connecting it to itself ends on, loops between opposite sides would
+/// cross the node so those end on the next side clockwise instead.
+fn loop_end(start: ArrowConnectPos, end: ArrowConnectPos) -> ArrowConnectPos {
+    use ArrowConnectPos::*;
+    match (start, end) {
+        (Top, Bottom) => Right,
+        (Right, Left) => Bottom,
+        (Bottom, Top) => Left,
+        (Left, Right) => Top,
+        (_, end) => end,
+    }
+}
+
 pub fn create_arrow_start(
     mut interaction_query: Query<
         (&Interaction, &ArrowConnect),
@@ -16,6 +90,7 @@ pub fn create_arrow_start(
     >,
     mut state: ResMut<UiState>,
     mut create_arrow: EventWriter<CreateArrowEvent>,
+    theme: Res<Theme>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     let mut primary_window = windows.single_mut();
@@ -23,14 +98,23 @@ pub fn create_arrow_start(
         match interaction {
             Interaction::Clicked => match state.arrow_to_draw_start {
                 Some(start_arrow) => {
-                    if start_arrow.id == arrow_connect.id {
+                    if start_arrow == *arrow_connect {
                         continue;
                     }
                     state.arrow_to_draw_start = None;
+                    let mut end = *arrow_connect;
+                    if end.id == start_arrow.id {
+                        end.pos = loop_end(start_arrow.pos, end.pos);
+                    }
                     create_arrow.send(CreateArrowEvent {
                         start: start_arrow,
-                        end: *arrow_connect,
+                        end,
                         arrow_type: state.arrow_type,
+                        style: theme.arrow_style(state.arrow_style),
+                        label: None,
+                        relation: state.arrow_relation.clone(),
+                        pins: ArrowPins::default(),
+                        waypoints: vec![],
                     });
                 }
                 None => {
@@ -50,74 +134,193 @@ pub fn create_arrow_start(
 pub fn create_arrow_end(
     mut commands: Commands,
     mut events: EventReader<CreateArrowEvent>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     arrow_markers: Query<(&ArrowConnect, &GlobalTransform), With<ArrowConnect>>,
-    windows: Query<&Window, With<PrimaryWindow>>,
+    nodes: Query<(&NodeRect, &ComputedVisibility), With<VeloNodeContainer>>,
+    arrows: Query<&ArrowMeta>,
+    node_index: Res<NodeIndex>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
 ) {
-    let primary_window = windows.single();
-    let (camera, camera_transform) = camera_q.single();
-    for event in events.iter() {
-        let mut start = None;
-        let mut end = None;
-        for (arrow_connect, global_transform) in &mut arrow_markers.iter() {
-            if *arrow_connect == event.start {
-                start = get_pos(global_transform, primary_window, camera, camera_transform);
-            }
-            if *arrow_connect == event.end {
-                end = get_pos(global_transform, primary_window, camera, camera_transform);
-            }
-            if let (Some(start), Some(end)) = (start, end) {
-                create_arrow(
-                    &mut commands,
-                    start,
-                    end,
-                    ArrowMeta {
-                        start: event.start,
-                        end: event.end,
-                        arrow_type: event.arrow_type,
-                    },
-                );
-                break;
-            }
+    if events.is_empty() {
+        return;
+    }
+    let mut connected: Vec<_> = arrows
+        .iter()
+        .map(|arrow| (arrow.start.id, arrow.end.id))
+        .collect();
+    let events: Vec<_> = events.iter().collect();
+    let connected_nodes: HashSet<_> = events
+        .iter()
+        .flat_map(|event| [event.start.id, event.end.id])
+        .collect();
+    let markers = marker_positions(&arrow_markers, &connected_nodes);
+    for event in events {
+        let area = obstacle_area(
+            &node_index,
+            &event.start.id,
+            &event.end.id,
+            &event.waypoints,
+        );
+        let obstacles = node_rects(&nodes, &node_index, area);
+        let (Some(start), Some(end)) = (markers.get(&event.start), markers.get(&event.end)) else {
+            continue;
+        };
+        let arrow = create_arrow(
+            &mut commands,
+            *start,
+            *end,
+            ArrowMeta {
+                start: event.start,
+                end: event.end,
+                arrow_type: event.arrow_type,
+            },
+            event.style,
+            &event.waypoints,
+            &obstacles,
+        );
+        if let Some(label) = event.label.clone() {
+            commands.entity(arrow).insert(ArrowLabel(label));
+        }
+        if let Some(relation) = event.relation.clone() {
+            commands.entity(arrow).insert(ArrowRelation(relation));
+        }
+        // arrows connecting the same nodes get spread apart
+        let (start_id, end_id) = (event.start.id, event.end.id);
+        if connected
+            .iter()
+            .any(|pair| *pair == (start_id, end_id) || *pair == (end_id, start_id))
+        {
+            redraw_arrow.send(RedrawArrowEvent { id: start_id });
         }
+        connected.push((start_id, end_id));
+        commands
+            .entity(arrow)
+            .insert((event.pins, ArrowWaypoints(event.waypoints.clone())));
     }
 }
 pub fn redraw_arrows(
     mut redraw_arrow: EventReader<RedrawArrowEvent>,
-    mut arrow_query: Query<(&mut Path, &mut ArrowMeta), With<ArrowMeta>>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut arrow_query: Query<
+        (
+            Entity,
+            &mut Path,
+            &mut ArrowMeta,
+            &mut ArrowAnchors,
+            &mut ArrowRoute,
+            &ArrowStyle,
+            &ArrowWaypoints,
+            &ArrowPins,
+            &Visibility,
+        ),
+        With<ArrowMeta>,
+    >,
     arrow_markers: Query<(&ArrowConnect, &GlobalTransform), With<ArrowConnect>>,
-    windows: Query<&Window, With<PrimaryWindow>>,
+    nodes: Query<(&NodeRect, &ComputedVisibility), With<VeloNodeContainer>>,
+    arrow_index: Res<ArrowIndex>,
+    node_index: Res<NodeIndex>,
 ) {
-    let primary_window = windows.single();
-    let (camera, camera_transform) = camera_q.single();
-    for event in redraw_arrow.iter() {
-        for (mut path, mut arrow) in arrow_query.iter_mut() {
-            if arrow.start.id == event.id || arrow.end.id == event.id {
-                let (arrow_hold_vec, arrow_move_vec): (Vec<_>, Vec<_>) = arrow_markers
-                    .iter()
-                    .filter(|(x, _)| x.id == arrow.end.id || x.id == arrow.start.id)
-                    .filter_map(|(ac, gt)| {
-                        Some((ac, get_pos(gt, primary_window, camera, camera_transform)?))
-                    })
-                    .partition(|(x, _)| x.id == arrow.end.id);
-                let arrow_pos = arrow_hold_vec
-                    .iter()
-                    .flat_map(move |x| std::iter::repeat(*x).zip(arrow_move_vec.clone()))
-                    .min_by_key(|(arrow_hold, arrow_move)| {
-                        arrow_hold.1.distance(arrow_move.1) as u32
-                    });
-                if let Some((start_pos, end_pos)) = arrow_pos {
-                    let ((start_pos, start), (end_pos, end)) = if start_pos.0.id == arrow.start.id {
-                        (start_pos, end_pos)
-                    } else {
-                        (end_pos, start_pos)
-                    };
-                    arrow.start = *start_pos;
-                    arrow.end = *end_pos;
-                    *path = build_arrow(start, end, *arrow);
-                }
+    if redraw_arrow.is_empty() {
+        return;
+    }
+    // a node moved several times in a frame is redrawn once
+    let moved: HashSet<_> = redraw_arrow.iter().map(|event| event.id).collect();
+    let redrawn: HashSet<_> = moved
+        .iter()
+        .flat_map(|id| arrow_index.arrows_of(id))
+        .copied()
+        .collect();
+    if redrawn.is_empty() {
+        return;
+    }
+    // arrows parallel to a redrawn one share its start node, deleted arrows are only hidden on
+    // wasm
+    let siblings: HashSet<_> = redrawn
+        .iter()
+        .filter_map(|entity| arrow_query.get(*entity).ok())
+        .flat_map(|(_, _, arrow, ..)| arrow_index.arrows_of(&arrow.start.id))
+        .copied()
+        .collect();
+    let visible_arrows: Vec<_> = siblings
+        .into_iter()
+        .filter_map(|entity| arrow_query.get(entity).ok())
+        .filter(|(.., visibility)| **visibility != Visibility::Hidden)
+        .map(|(entity, _, arrow, ..)| (entity, *arrow))
+        .collect();
+    let offsets = parallel_offsets(&visible_arrows);
+    let connected_nodes: HashSet<_> = redrawn
+        .iter()
+        .filter_map(|entity| arrow_query.get(*entity).ok())
+        .flat_map(|(_, _, arrow, ..)| [arrow.start.id, arrow.end.id])
+        .collect();
+    let markers = marker_positions(&arrow_markers, &connected_nodes);
+    let marker_pos = |connect: ArrowConnect| markers.get(&connect).copied();
+    for entity in redrawn {
+        let Ok((_, mut path, mut arrow, mut anchors, mut route, style, waypoints, pins, _)) =
+            arrow_query.get_mut(entity)
+        else {
+            continue;
+        };
+        let offset = offsets.get(&entity).copied().unwrap_or_default();
+        let obstacles = if style.routing == ArrowRouting::Elbow {
+            let area = obstacle_area(&node_index, &arrow.start.id, &arrow.end.id, &waypoints.0);
+            node_rects(&nodes, &node_index, area)
+        } else {
+            vec![]
+        };
+        let waypoints = &waypoints.0;
+        // loops keep the sides they were drawn between
+        if arrow.start.id == arrow.end.id {
+            if let (Some(start), Some(end)) = (marker_pos(arrow.start), marker_pos(arrow.end)) {
+                (*path, *anchors, *route) =
+                    build_arrow(start, end, *arrow, *style, waypoints, offset, &obstacles);
             }
+            continue;
+        }
+        // unpinned ends are attached to the side giving the cheapest connection
+        let candidates = |connect: ArrowConnect, pinned: bool| -> Vec<(ArrowConnect, Vec2)> {
+            [
+                // in the order markers are spawned, which wins among equally cheap connections
+                ArrowConnectPos::Top,
+                ArrowConnectPos::Left,
+                ArrowConnectPos::Bottom,
+                ArrowConnectPos::Right,
+            ]
+            .into_iter()
+            .filter(|pos| !pinned || *pos == connect.pos)
+            .map(|pos| ArrowConnect {
+                id: connect.id,
+                pos,
+            })
+            .filter_map(|marker| markers.get(&marker).map(|position| (marker, *position)))
+            .collect()
+        };
+        let ends = candidates(arrow.end, pins.end);
+        let connection = candidates(arrow.start, pins.start)
+            .into_iter()
+            .flat_map(|start| ends.iter().map(move |end| (start, *end)))
+            .min_by(|(start_a, end_a), (start_b, end_b)| {
+                connection_cost(start_a.1, start_a.0.pos, end_a.1, end_a.0.pos).total_cmp(
+                    &connection_cost(start_b.1, start_b.0.pos, end_b.1, end_b.0.pos),
+                )
+            });
+        if let Some(((start_connect, start), (end_connect, end))) = connection {
+            arrow.start = start_connect;
+            arrow.end = end_connect;
+            (*path, *anchors, *route) =
+                build_arrow(start, end, *arrow, *style, waypoints, offset, &obstacles);
         }
     }
 }
+
+/// Keeps the arrows attached to every node up to date for the redraw.
+pub fn update_arrow_index(
+    mut arrow_index: ResMut<ArrowIndex>,
+    arrows: Query<(Entity, &ArrowMeta), Changed<ArrowMeta>>,
+    mut removed: RemovedComponents<ArrowMeta>,
+) {
+    for entity in removed.iter() {
+        arrow_index.remove(entity);
+    }
+    for (entity, arrow) in arrows.iter() {
+        arrow_index.insert(entity, arrow.start.id, arrow.end.id);
+    }
+}
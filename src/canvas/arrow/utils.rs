@@ -1,24 +1,65 @@
+use std::collections::HashMap;
 use std::f32::consts::PI;
 
 use bevy::prelude::*;
 use bevy_prototype_lyon::{
-    prelude::{GeometryBuilder, Path, ShapeBundle, Stroke},
+    prelude::{Fill, GeometryBuilder, Path, ShapeBundle, Stroke},
     shapes,
 };
 
 // use crate::chart_plugin::ui_helpers::ArrowConnectPos;
 
-use super::components::{ArrowConnectPos, ArrowMeta, ArrowType};
-pub fn create_arrow(commands: &mut Commands, start: Vec2, end: Vec2, arrow_meta: ArrowMeta) {
-    let arrow_path = build_arrow(start, end, arrow_meta);
-    commands.spawn((
-        ShapeBundle {
-            path: arrow_path,
-            ..default()
-        },
-        arrow_meta,
-        Stroke::new(Color::rgb(63.0 / 255.0, 81.0 / 255.0, 181.0 / 255.0), 1.5),
-    ));
+use super::components::{
+    ArrowAnchors, ArrowAnimation, ArrowConnectPos, ArrowDash, ArrowHead, ArrowMeta, ArrowRoute,
+    ArrowRouting, ArrowStyle, ArrowType,
+};
+
+// Head length of arrows of the default thickness, heads of thicker arrows are longer
+const HEAD_LENGTH: f32 = 10.0;
+// Line segments a curved arrow is flattened into
+const CURVE_SEGMENTS: usize = 24;
+// Distance kept by elbow arrows from the sides of nodes
+const ELBOW_MARGIN: f32 = 15.0;
+// Route crossing a node is only taken when every tried route crosses one
+const CROSSING_COST: f32 = 10000.0;
+const BEND_COST: f32 = 10.0;
+// Distance of the end handles of a selected arrow from its ends
+const HANDLE_DISTANCE: f32 = 12.0;
+// Reach of the loop of an arrow connecting a node to itself, further loops are wider
+const LOOP_SIZE: f32 = 40.0;
+const LOOP_SPACING: f32 = 15.0;
+// Distance between arrows connecting the same pair of nodes
+const PARALLEL_SPACING: f32 = 12.0;
+// Extra cost of attaching an arrow to a side of a node facing away from its other end
+const BACKWARD_SIDE_COST: f32 = 100.0;
+
+pub fn create_arrow(
+    commands: &mut Commands,
+    start: Vec2,
+    end: Vec2,
+    arrow_meta: ArrowMeta,
+    style: ArrowStyle,
+    waypoints: &[Vec2],
+    obstacles: &[Rect],
+) -> Entity {
+    // offset from arrows connecting the same nodes is applied when those get redrawn
+    let (arrow_path, anchors, route) =
+        build_arrow(start, end, arrow_meta, style, waypoints, 0., obstacles);
+    commands
+        .spawn((
+            ShapeBundle {
+                path: arrow_path,
+                ..default()
+            },
+            arrow_meta,
+            style,
+            anchors,
+            route,
+            Stroke::new(style.color, style.thickness),
+            // fills closed heads, lines have no area to fill
+            Fill::color(style.color),
+        ))
+        .id()
 }
 fn parallel_arrow_mid(start: Vec2, end: Vec2, arrow_meta: ArrowMeta) -> (Vec2, Vec2) {
     let mid = (start + end) / 2.0;
@@ -35,8 +76,8 @@ fn parallel_arrow_mid(start: Vec2, end: Vec2, arrow_meta: ArrowMeta) -> (Vec2, V
         (_, _) => (mid, mid),
     }
 }
-fn arrow_head(point: Vec2, pos: ArrowConnectPos) -> shapes::Polygon {
-    let headlen: f32 = 10.0;
+/// Direction pointing into the node at the connection point.
+fn connect_direction(pos: ArrowConnectPos) -> Vec2 {
     use ArrowConnectPos::*;
     let angle = match pos {
         Top => PI / 2.,
@@ -44,104 +85,523 @@ fn arrow_head(point: Vec2, pos: ArrowConnectPos) -> shapes::Polygon {
         Right => 0.,
         Left => PI,
     };
-    let points = vec![
-        point + Vec2::from_angle(angle - PI / 6.) * headlen,
-        point,
-        point + Vec2::from_angle(angle + PI / 6.) * headlen,
-    ];
-    shapes::Polygon {
-        points,
-        closed: false,
+    -Vec2::from_angle(angle)
+}
+/// Cost of connecting the points on the given sides of their nodes: their distance, with sides
+/// facing away from the other end penalized.
+pub fn connection_cost(
+    start: Vec2,
+    start_pos: ArrowConnectPos,
+    end: Vec2,
+    end_pos: ArrowConnectPos,
+) -> f32 {
+    let facing_away =
+        |from: Vec2, pos: ArrowConnectPos, to: Vec2| (to - from).dot(-connect_direction(pos)) < 0.;
+    let backward_sides =
+        facing_away(start, start_pos, end) as u8 + facing_away(end, end_pos, start) as u8;
+    start.distance(end) + backward_sides as f32 * BACKWARD_SIDE_COST
+}
+/// Cubic bezier leaving and entering the nodes perpendicular to their sides. It's flattened
+/// into points, a curved path would be filled like the closed heads.
+pub fn curved_route(start: Vec2, end: Vec2, start_out: Vec2, end_out: Vec2) -> Vec<Vec2> {
+    let reach = (start.distance(end) * 0.4).max(20.);
+    bezier_route(start, end, start + start_out * reach, end + end_out * reach)
+}
+fn bezier_route(start: Vec2, end: Vec2, control1: Vec2, control2: Vec2) -> Vec<Vec2> {
+    (0..=CURVE_SEGMENTS)
+        .map(|i| {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            let u = 1. - t;
+            start * u * u * u
+                + control1 * 3. * u * u * t
+                + control2 * 3. * u * t * t
+                + end * t * t * t
+        })
+        .collect()
+}
+fn crosses(from: Vec2, to: Vec2, rect: &Rect) -> bool {
+    from.x.min(to.x) < rect.max.x
+        && from.x.max(to.x) > rect.min.x
+        && from.y.min(to.y) < rect.max.y
+        && from.y.max(to.y) > rect.min.y
+}
+/// Drops repeated points and points in the middle of a straight run.
+fn simplify_route(points: Vec<Vec2>) -> Vec<Vec2> {
+    let mut simplified: Vec<Vec2> = vec![];
+    for point in points {
+        if simplified
+            .last()
+            .map_or(false, |last| last.distance(point) < 0.01)
+        {
+            continue;
+        }
+        if let [.., before, last] = simplified[..] {
+            let (run, next) = (last - before, point - last);
+            if run.perp_dot(next).abs() < 0.01 && run.dot(next) > 0. {
+                simplified.pop();
+            }
+        }
+        simplified.push(point);
+    }
+    simplified
+}
+fn route_cost(points: &[Vec2], obstacles: &[Rect]) -> f32 {
+    let length: f32 = points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum();
+    let crossings = points
+        .windows(2)
+        .map(|pair| {
+            obstacles
+                .iter()
+                .filter(|rect| crosses(pair[0], pair[1], rect))
+                .count()
+        })
+        .sum::<usize>();
+    let bends = points.len().saturating_sub(2);
+    length + crossings as f32 * CROSSING_COST + bends as f32 * BEND_COST
+}
+/// Orthogonal route leaving and entering the nodes perpendicular to their sides. Routes with one
+/// vertical or horizontal run through the middle, along the arrow ends or along the sides of
+/// `obstacles` (bounding boxes of nodes) are tried, the shortest one not crossing a node wins.
+pub fn elbow_route(
+    start: Vec2,
+    end: Vec2,
+    start_out: Vec2,
+    end_out: Vec2,
+    obstacles: &[Rect],
+) -> Vec<Vec2> {
+    let from = start + start_out * ELBOW_MARGIN;
+    let to = end + end_out * ELBOW_MARGIN;
+    // run through the middle is preferred over equally long ones
+    let mut xs = vec![(from.x + to.x) / 2., from.x, to.x];
+    let mut ys = vec![(from.y + to.y) / 2., from.y, to.y];
+    for rect in obstacles {
+        xs.extend([rect.min.x - ELBOW_MARGIN, rect.max.x + ELBOW_MARGIN]);
+        ys.extend([rect.min.y - ELBOW_MARGIN, rect.max.y + ELBOW_MARGIN]);
     }
+    let vertical_runs = xs.iter().map(|x| {
+        vec![
+            start,
+            from,
+            Vec2::new(*x, from.y),
+            Vec2::new(*x, to.y),
+            to,
+            end,
+        ]
+    });
+    let horizontal_runs = ys.iter().map(|y| {
+        vec![
+            start,
+            from,
+            Vec2::new(from.x, *y),
+            Vec2::new(to.x, *y),
+            to,
+            end,
+        ]
+    });
+    vertical_runs
+        .chain(horizontal_runs)
+        .map(simplify_route)
+        .map(|route| (route_cost(&route, obstacles), route))
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, route)| route)
+        .unwrap_or(vec![start, end])
 }
-pub fn build_arrow(start: Vec2, end: Vec2, arrow_meta: ArrowMeta) -> Path {
-    match arrow_meta.arrow_type {
-        ArrowType::Line => {
-            let main = shapes::Line(start, end);
-            GeometryBuilder::build_as(&main)
+/// Offsets of arrows from the line between the nodes they connect, arrows connecting the same
+/// pair of nodes (in either direction) are spread around it and loops of the same node get wider.
+pub fn parallel_offsets(arrows: &[(Entity, ArrowMeta)]) -> HashMap<Entity, f32> {
+    let mut pairs: HashMap<_, Vec<_>> = HashMap::new();
+    for (entity, arrow_meta) in arrows {
+        let (start, end) = (arrow_meta.start.id.0, arrow_meta.end.id.0);
+        pairs
+            .entry((start.min(end), start.max(end)))
+            .or_default()
+            .push((*entity, start > end));
+    }
+    let mut offsets = HashMap::new();
+    for ((start, end), mut arrows) in pairs {
+        arrows.sort();
+        let count = arrows.len() as f32;
+        for (index, (entity, reversed)) in arrows.into_iter().enumerate() {
+            let offset = if start == end {
+                index as f32 * LOOP_SPACING
+            } else {
+                (index as f32 - (count - 1.) / 2.) * PARALLEL_SPACING
+            };
+            // offsets are measured to the left of the arrow, reversed arrows have it on the
+            // other side
+            offsets.insert(entity, if reversed { -offset } else { offset });
         }
-        ArrowType::Arrow => {
-            let dt = end.x - start.x;
-            let dy = end.y - start.y;
-            let angle = dy.atan2(dt);
-            let headlen = 10.0;
-            GeometryBuilder::new()
-                .add(&shapes::Line(start, end))
-                .add(&shapes::Line(
-                    end,
-                    end - headlen * Vec2::from_angle(angle + PI / 6.),
-                ))
-                .add(&shapes::Line(
-                    end,
-                    end - headlen * Vec2::from_angle(angle - PI / 6.),
-                ))
-                .build()
+    }
+    offsets
+}
+/// Points of the arrow line and directions of the arrow at its start and end, pointing out of
+/// the line (into the connected nodes). Arrows with waypoints go straight through them whatever
+/// their routing, arrows connecting a node to itself are drawn as a loop.
+fn arrow_route(
+    start: Vec2,
+    end: Vec2,
+    arrow_meta: ArrowMeta,
+    routing: ArrowRouting,
+    waypoints: &[Vec2],
+    offset: f32,
+    obstacles: &[Rect],
+) -> (Vec<Vec2>, Vec2, Vec2) {
+    if waypoints.is_empty() && arrow_meta.start.id == arrow_meta.end.id {
+        let start_direction = connect_direction(arrow_meta.start.pos);
+        let end_direction = connect_direction(arrow_meta.end.pos);
+        let reach = LOOP_SIZE + offset;
+        let control1 = start - start_direction * reach;
+        let control2 = end - end_direction * reach;
+        return (
+            bezier_route(start, end, control1, control2),
+            start_direction,
+            end_direction,
+        );
+    }
+    let (mut points, start_direction, end_direction) =
+        unshifted_route(start, end, arrow_meta, routing, waypoints, obstacles);
+    // parallel arrows are shifted along the sides of the nodes, waypoints are kept in place
+    if waypoints.is_empty() {
+        let shift = (end - start).normalize_or_zero().perp() * offset;
+        for point in points.iter_mut() {
+            *point += shift;
         }
-        ArrowType::DoubleArrow => {
-            let headlen = 10.0;
-            let dt = end.x - start.x;
-            let dy = end.y - start.y;
-            let angle = dy.atan2(dt);
-            GeometryBuilder::new()
-                .add(&shapes::Line(
-                    start,
-                    start + headlen * Vec2::from_angle(angle + PI / 6.),
-                ))
-                .add(&shapes::Line(
-                    start,
-                    start + headlen * Vec2::from_angle(angle - PI / 6.),
-                ))
-                .add(&shapes::Line(start, end))
-                .add(&shapes::Line(
-                    end,
-                    end - headlen * Vec2::from_angle(angle + PI / 6.),
-                ))
-                .add(&shapes::Line(
-                    end,
-                    end - headlen * Vec2::from_angle(angle - PI / 6.),
-                ))
-                .build()
+    }
+    (points, start_direction, end_direction)
+}
+fn unshifted_route(
+    start: Vec2,
+    end: Vec2,
+    arrow_meta: ArrowMeta,
+    routing: ArrowRouting,
+    waypoints: &[Vec2],
+    obstacles: &[Rect],
+) -> (Vec<Vec2>, Vec2, Vec2) {
+    if let (Some(first), Some(last)) = (waypoints.first(), waypoints.last()) {
+        let mut points = vec![start];
+        points.extend_from_slice(waypoints);
+        points.push(end);
+        return (
+            points,
+            (start - *first).normalize_or_zero(),
+            (end - *last).normalize_or_zero(),
+        );
+    }
+    let start_direction = connect_direction(arrow_meta.start.pos);
+    let end_direction = connect_direction(arrow_meta.end.pos);
+    match (routing, arrow_meta.arrow_type) {
+        (ArrowRouting::Curved, _) => (
+            curved_route(start, end, -start_direction, -end_direction),
+            start_direction,
+            end_direction,
+        ),
+        (ArrowRouting::Elbow, _) => (
+            elbow_route(start, end, -start_direction, -end_direction, obstacles),
+            start_direction,
+            end_direction,
+        ),
+        (ArrowRouting::Straight, ArrowType::Line | ArrowType::Arrow | ArrowType::DoubleArrow) => {
+            let direction = (end - start).normalize_or_zero();
+            (vec![start, end], -direction, direction)
         }
-        ArrowType::ParallelLine => {
+        (
+            ArrowRouting::Straight,
+            ArrowType::ParallelLine | ArrowType::ParallelArrow | ArrowType::ParallelDoubleArrow,
+        ) => {
             let mid_point = parallel_arrow_mid(start, end, arrow_meta);
-            GeometryBuilder::new()
-                .add(&shapes::Line(start, mid_point.0))
-                .add(&shapes::Line(mid_point.0, mid_point.1))
-                .add(&shapes::Line(mid_point.1, end))
-                .build()
+            (
+                vec![start, mid_point.0, mid_point.1, end],
+                start_direction,
+                end_direction,
+            )
         }
-        ArrowType::ParallelArrow => {
-            let head_pos = arrow_meta.end.pos;
-            let mid_point = parallel_arrow_mid(start, end, arrow_meta);
-            GeometryBuilder::new()
-                .add(&shapes::Line(start, mid_point.0))
-                .add(&shapes::Line(mid_point.0, mid_point.1))
-                .add(&shapes::Line(mid_point.1, end))
-                .add(&arrow_head(end, head_pos))
-                .build()
+    }
+}
+/// Splits the polyline into dashes of `dash` length separated by `gap`, the pattern continues
+/// around corners.
+pub fn dashed_segments(points: &[Vec2], dash: f32, gap: f32, phase: f32) -> Vec<(Vec2, Vec2)> {
+    let mut segments = vec![];
+    // the pattern is moved `phase` forward along the line
+    let start = (dash + gap - phase.rem_euclid(dash + gap)) % (dash + gap);
+    let (mut on, mut remaining) = if start < dash {
+        (true, dash - start)
+    } else {
+        (false, dash + gap - start)
+    };
+    for pair in points.windows(2) {
+        let (mut from, to) = (pair[0], pair[1]);
+        let mut length = from.distance(to);
+        if length == 0. {
+            continue;
         }
-        ArrowType::ParallelDoubleArrow => {
-            let head_pos = arrow_meta.end.pos;
-            let tail_pos = arrow_meta.start.pos;
-            let mid_point = parallel_arrow_mid(start, end, arrow_meta);
-            GeometryBuilder::new()
-                .add(&arrow_head(start, tail_pos))
-                .add(&shapes::Line(start, mid_point.0))
-                .add(&shapes::Line(mid_point.0, mid_point.1))
-                .add(&shapes::Line(mid_point.1, end))
-                .add(&arrow_head(end, head_pos))
-                .build()
+        let direction = (to - from) / length;
+        while length > 0. {
+            let step = remaining.min(length);
+            let next = from + direction * step;
+            if on {
+                segments.push((from, next));
+            }
+            from = next;
+            length -= step;
+            remaining -= step;
+            if remaining <= 0. {
+                on = !on;
+                remaining = if on { dash } else { gap };
+            }
+        }
+    }
+    segments
+}
+fn add_head(
+    builder: GeometryBuilder,
+    tip: Vec2,
+    direction: Vec2,
+    head: ArrowHead,
+    thickness: f32,
+) -> GeometryBuilder {
+    let length = HEAD_LENGTH * (thickness / ArrowStyle::default().thickness).max(1.);
+    let back = -direction;
+    let left = tip + Vec2::from_angle(PI / 6.).rotate(back) * length;
+    let right = tip + Vec2::from_angle(-PI / 6.).rotate(back) * length;
+    match head {
+        ArrowHead::None => builder,
+        ArrowHead::Open => builder
+            .add(&shapes::Line(tip, left))
+            .add(&shapes::Line(tip, right)),
+        ArrowHead::Filled => builder.add(&shapes::Polygon {
+            points: vec![tip, left, right],
+            closed: true,
+        }),
+        ArrowHead::Diamond => {
+            let side = back.perp() * length * 0.3;
+            let mid = tip + back * length / 2.;
+            builder.add(&shapes::Polygon {
+                points: vec![tip, mid + side, tip + back * length, mid - side],
+                closed: true,
+            })
+        }
+        ArrowHead::Circle => builder.add(&shapes::Circle {
+            radius: length * 0.3,
+            center: tip + back * length * 0.3,
+        }),
+    }
+}
+/// Point `distance` along the polyline.
+pub fn point_along(points: &[Vec2], distance: f32) -> Vec2 {
+    let mut remaining = distance;
+    for pair in points.windows(2) {
+        let segment = pair[0].distance(pair[1]);
+        if segment >= remaining && segment > 0. {
+            return pair[0].lerp(pair[1], remaining / segment);
         }
+        remaining -= segment;
+    }
+    points.last().copied().unwrap_or_default()
+}
+fn route_anchors(points: &[Vec2]) -> ArrowAnchors {
+    let length: f32 = points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum();
+    // handles stay off the connection points, those start new arrows when clicked
+    let handle_distance = HANDLE_DISTANCE.min(length / 3.);
+    ArrowAnchors {
+        midpoint: point_along(points, length / 2.),
+        start_handle: point_along(points, handle_distance),
+        end_handle: point_along(points, length - handle_distance),
+    }
+}
+/// Path of the arrow and the anchors along its line, `obstacles` are the bounding boxes of nodes
+/// avoided by elbow arrows.
+pub fn build_arrow(
+    start: Vec2,
+    end: Vec2,
+    arrow_meta: ArrowMeta,
+    style: ArrowStyle,
+    waypoints: &[Vec2],
+    offset: f32,
+    obstacles: &[Rect],
+) -> (Path, ArrowAnchors, ArrowRoute) {
+    let (points, start_direction, end_direction) = arrow_route(
+        start,
+        end,
+        arrow_meta,
+        style.routing,
+        waypoints,
+        offset,
+        obstacles,
+    );
+    let route = ArrowRoute {
+        points,
+        start_direction,
+        end_direction,
+    };
+    (
+        arrow_path(&route, style, 0.),
+        route_anchors(&route.points),
+        route,
+    )
+}
+
+/// Path of the line and heads of the arrow, dashes are moved `phase` along the line. Arrows
+/// with marching dashes are dashed even with a solid line style.
+pub fn arrow_path(route: &ArrowRoute, style: ArrowStyle, phase: f32) -> Path {
+    let points = &route.points;
+    let unit = style.thickness.max(1.);
+    let dash = match (style.dash, style.animation) {
+        (ArrowDash::Solid, ArrowAnimation::MarchingDashes) => ArrowDash::Dashed,
+        (dash, _) => dash,
+    };
+    let segments = match dash {
+        ArrowDash::Solid => points.windows(2).map(|pair| (pair[0], pair[1])).collect(),
+        ArrowDash::Dashed => dashed_segments(points, 4. * unit, 3. * unit, phase),
+        ArrowDash::Dotted => dashed_segments(points, unit, 2. * unit, phase),
+    };
+    let mut builder = GeometryBuilder::new();
+    for (from, to) in segments {
+        builder = builder.add(&shapes::Line(from, to));
     }
+    if let (Some(start), Some(end)) = (points.first(), points.last()) {
+        builder = add_head(
+            builder,
+            *start,
+            route.start_direction,
+            style.start_head,
+            style.thickness,
+        );
+        builder = add_head(
+            builder,
+            *end,
+            route.end_direction,
+            style.end_head,
+            style.thickness,
+        );
+    }
+    builder.build()
 }
 
-pub fn get_pos(
-    global_transform: &GlobalTransform,
-    primary_window: &Window,
-    camera: &Camera,
-    camera_transform: &GlobalTransform,
-) -> Option<Vec2> {
-    let world_position = global_transform.affine().translation;
-    let point = Vec2::new(world_position.x, primary_window.height() - world_position.y);
-    camera.viewport_to_world_2d(camera_transform, point)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashed_segments() {
+        assert_eq!(
+            dashed_segments(&[Vec2::ZERO, Vec2::new(10., 0.)], 3., 2., 0.),
+            vec![
+                (Vec2::ZERO, Vec2::new(3., 0.)),
+                (Vec2::new(5., 0.), Vec2::new(8., 0.))
+            ]
+        );
+        // the pattern continues around the corner
+        assert_eq!(
+            dashed_segments(
+                &[Vec2::ZERO, Vec2::new(2., 0.), Vec2::new(2., 4.)],
+                3.,
+                1.,
+                0.
+            ),
+            vec![
+                (Vec2::ZERO, Vec2::new(2., 0.)),
+                (Vec2::new(2., 0.), Vec2::new(2., 1.)),
+                (Vec2::new(2., 2.), Vec2::new(2., 4.))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_connection_cost() {
+        use ArrowConnectPos::*;
+        let start = Vec2::ZERO;
+        let end = Vec2::new(100., 0.);
+        assert_eq!(connection_cost(start, Right, end, Left), 100.);
+        assert_eq!(
+            connection_cost(start, Left, end, Left),
+            100. + BACKWARD_SIDE_COST
+        );
+        assert_eq!(
+            connection_cost(start, Left, end, Right),
+            100. + 2. * BACKWARD_SIDE_COST
+        );
+    }
+
+    #[test]
+    fn test_parallel_offsets() {
+        use super::super::components::ArrowConnect;
+        use crate::utils::ReflectableUuid;
+        let meta = |start: u128, end: u128| ArrowMeta {
+            start: ArrowConnect {
+                id: ReflectableUuid(uuid::Uuid::from_u128(start)),
+                pos: ArrowConnectPos::Right,
+            },
+            end: ArrowConnect {
+                id: ReflectableUuid(uuid::Uuid::from_u128(end)),
+                pos: ArrowConnectPos::Left,
+            },
+            ..default()
+        };
+        let offsets = parallel_offsets(&[
+            (Entity::from_raw(0), meta(1, 2)),
+            (Entity::from_raw(1), meta(2, 1)),
+            (Entity::from_raw(2), meta(1, 3)),
+            (Entity::from_raw(3), meta(3, 3)),
+            (Entity::from_raw(4), meta(3, 3)),
+        ]);
+        assert_eq!(offsets[&Entity::from_raw(0)], -PARALLEL_SPACING / 2.);
+        // left of the reversed arrow is the other side of the line
+        assert_eq!(offsets[&Entity::from_raw(1)], -PARALLEL_SPACING / 2.);
+        assert_eq!(offsets[&Entity::from_raw(2)], 0.);
+        assert_eq!(offsets[&Entity::from_raw(3)], 0.);
+        assert_eq!(offsets[&Entity::from_raw(4)], LOOP_SPACING);
+    }
+
+    #[test]
+    fn test_route_anchors() {
+        assert_eq!(
+            route_anchors(&[Vec2::ZERO, Vec2::new(16., 0.), Vec2::new(16., 64.)]),
+            ArrowAnchors {
+                midpoint: Vec2::new(16., 24.),
+                start_handle: Vec2::new(12., 0.),
+                end_handle: Vec2::new(16., 52.),
+            }
+        );
+        // handles of short arrows are a third of the length away from the ends
+        assert_eq!(
+            route_anchors(&[Vec2::ZERO, Vec2::new(24., 0.)]),
+            ArrowAnchors {
+                midpoint: Vec2::new(12., 0.),
+                start_handle: Vec2::new(8., 0.),
+                end_handle: Vec2::new(16., 0.),
+            }
+        );
+    }
+
+    #[test]
+    fn test_curved_route() {
+        let route = curved_route(Vec2::ZERO, Vec2::new(100., 0.), Vec2::X, -Vec2::X);
+        assert_eq!(route.len(), CURVE_SEGMENTS + 1);
+        assert_eq!(route[0], Vec2::ZERO);
+        assert_eq!(route[CURVE_SEGMENTS], Vec2::new(100., 0.));
+    }
+
+    #[test]
+    fn test_elbow_route() {
+        let (start, end) = (Vec2::ZERO, Vec2::new(200., 100.));
+        assert_eq!(
+            elbow_route(start, end, Vec2::X, -Vec2::X, &[]),
+            vec![start, Vec2::new(100., 0.), Vec2::new(100., 100.), end]
+        );
+        // node in the way of the middle run
+        let obstacle = Rect::from_center_size(Vec2::new(100., 50.), Vec2::new(40., 200.));
+        let route = elbow_route(start, end, Vec2::X, -Vec2::X, &[obstacle]);
+        assert_eq!(route.first(), Some(&start));
+        assert_eq!(route.last(), Some(&end));
+        for pair in route.windows(2) {
+            assert!(pair[0].x == pair[1].x || pair[0].y == pair[1].y);
+            assert!(!crosses(pair[0], pair[1], &obstacle));
+        }
+    }
 }
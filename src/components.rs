@@ -1,19 +1,53 @@
+use crate::ui_plugin::{deserialize_checkpoint, serialize_checkpoint};
 use crate::utils::ReflectableUuid;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::HashMap;
 #[derive(Component)]
 pub struct MainCamera;
 
 #[derive(Component)]
 pub struct EffectsCamera;
 
+/// Background image of the canvas, tinted by the theme.
+#[derive(Component)]
+pub struct CanvasBackground;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Tab {
     pub is_active: bool,
     pub id: ReflectableUuid,
     pub name: String,
-    pub checkpoints: VecDeque<String>,
+    /// Saved nodes, arrows, ... of the tab as JSON, none for a tab that was never saved. Edits
+    /// are undone with the `History` of the session, only the last state is kept.
+    #[serde(
+        default,
+        alias = "checkpoints",
+        serialize_with = "serialize_checkpoint",
+        deserialize_with = "deserialize_checkpoint"
+    )]
+    pub checkpoint: Option<String>,
+    #[serde(default = "default_zoom")]
+    pub zoom: f32,
+    /// Background of the tab in the tab bar.
+    #[serde(default)]
+    pub color: Option<Color>,
+    /// Name of the icon shown before the tab name, one of `TAB_ICONS`.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Pinned tabs are kept leftmost and can't be deleted.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Panned offset of the canvas (`CanvasOffset`) when the tab was last shown.
+    #[serde(default)]
+    pub offset: [f32; 2],
+    /// Nodes selected when the tab was last shown.
+    #[serde(default)]
+    pub selected: Vec<ReflectableUuid>,
+}
+
+fn default_zoom() -> f32 {
+    1.
 }
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
@@ -22,4 +56,10 @@ pub struct Doc {
     pub id: ReflectableUuid,
     pub name: String,
     pub tags: Vec<String>,
+    /// Files embedded in the document by content hash, base64 encoded.
+    #[serde(default)]
+    pub assets: HashMap<String, String>,
+    /// Revision of the copy on the sync remote this document was last pushed to or pulled from.
+    #[serde(default)]
+    pub revision: u64,
 }
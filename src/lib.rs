@@ -4,19 +4,31 @@ mod resources;
 mod systems;
 mod ui_plugin;
 mod utils;
-use bevy::{prelude::*, window::PresentMode};
+use bevy::{
+    prelude::*,
+    window::{ExitCondition, PresentMode},
+};
 use bevy_cosmic_edit::{CosmicEditPlugin, CosmicFontConfig, CustomCosmicFont};
 use bevy_embedded_assets::EmbeddedAssetPlugin;
 #[cfg(not(target_arch = "wasm32"))]
 use bevy_hanabi::HanabiPlugin;
+use bevy_markdown::BevyMarkdownPlugin;
 use bevy_pkv::PkvStore;
 use bevy_ui_borders::BordersPlugin;
 use canvas::CanvasPlugin;
 use systems::*;
 use ui_plugin::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use ui_plugin::{run_export, ExportFormat, ExportJob, EXPORT_USAGE};
 
 pub static ORG_NAME: &str = "";
 pub static APP_NAME: &str = "velo";
+/// Faces of Source Code Pro used for all texts: regular, bold and italic.
+pub static FONTS: [&[u8]; 3] = [
+    include_bytes!("../assets/fonts/SourceCodePro-Regular.ttf"),
+    include_bytes!("../assets/fonts/SourceCodePro-Bold.ttf"),
+    include_bytes!("../assets/fonts/SourceCodePro-Italic.ttf"),
+];
 
 pub struct VeloPlugin;
 impl Plugin for VeloPlugin {
@@ -35,12 +47,15 @@ impl Plugin for VeloPlugin {
                             prevent_default_event_handling: false,
                             ..default()
                         }),
+                        // windows showing other tabs are closed along with the main one
+                        exit_condition: ExitCondition::OnPrimaryClosed,
                         ..default()
                     })
                     .build()
                     .add_before::<bevy::asset::AssetPlugin, _>(EmbeddedAssetPlugin),
             )
             .add_plugin(CosmicEditPlugin)
+            .add_plugin(BevyMarkdownPlugin)
             .add_plugin(CanvasPlugin)
             .add_plugin(UiPlugin)
             .add_plugin(BordersPlugin)
@@ -52,9 +67,10 @@ impl Plugin for VeloPlugin {
                 sans_serif_family: Some("Source Code Pro".to_string()),
                 serif_family: Some("Source Code Pro".to_string()),
                 custom_font_data: Some(CustomCosmicFont {
-                    data: include_bytes!("../assets/fonts/SourceCodePro-Regular.ttf"),
+                    data: FONTS[0],
                     override_bevy_font: true,
                 }),
+                additional_font_data: vec![FONTS[1], FONTS[2]],
             });
 
         #[cfg(not(target_arch = "wasm32"))]
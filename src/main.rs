@@ -5,6 +5,29 @@ fn main() {
     #[cfg(target_arch = "wasm32")]
     console_error_panic_hook::set_once();
     #[cfg(not(target_arch = "wasm32"))]
-    std::env::set_var("RUST_LOG", "warn,velo=info,tantivy=warn");
+    {
+        std::env::set_var("RUST_LOG", "warn,velo=info,tantivy=warn");
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.first().map(String::as_str) == Some("export") {
+            export(&args[1..]);
+            return;
+        }
+    }
     App::new().add_plugin(VeloPlugin).run();
 }
+
+/// `velo export <doc.json> --format png|svg|pdf|md`, exits with an error code if it fails.
+#[cfg(not(target_arch = "wasm32"))]
+fn export(args: &[String]) {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", velo::EXPORT_USAGE);
+        return;
+    }
+    match velo::ExportJob::from_args(args).and_then(velo::run_export) {
+        Ok(path) => println!("Exported {}", path.display()),
+        Err(e) => {
+            eprintln!("velo export: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -5,6 +5,7 @@ use crate::utils::ReflectableUuid;
 use bevy::prelude::*;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 #[derive(Resource, Default)]
 pub struct AppState {
@@ -14,6 +15,26 @@ pub struct AppState {
     #[cfg(not(target_arch = "wasm32"))]
     pub search_index: Option<SearchIndexState>,
     pub doc_list_ui: HashSet<ReflectableUuid>,
+    // Documents encrypted when saved to a file, passphrases are never stored
+    pub passphrases: HashMap<ReflectableUuid, String>,
+    // Files documents were opened from or last saved to, watched for changes by other programs
+    pub files: HashMap<ReflectableUuid, DocFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DocFile {
+    pub path: PathBuf,
+    /// Modification time of the file when velo last read or wrote it.
+    pub modified: Option<SystemTime>,
+}
+
+impl DocFile {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Self { path, modified }
+    }
 }
 
 #[derive(Resource, Debug)]
@@ -37,5 +58,4 @@ pub struct LoadDocRequest {
 pub struct LoadTabRequest {
     pub doc_id: ReflectableUuid,
     pub tab_id: ReflectableUuid,
-    pub drop_last_checkpoint: bool, // Useful for undo functionality
 }
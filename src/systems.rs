@@ -1,4 +1,5 @@
-use crate::components::{EffectsCamera, MainCamera};
+use crate::components::{CanvasBackground, EffectsCamera, MainCamera};
+use crate::ui_plugin::{BACKGROUND_Z, CANVAS_FAR};
 use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
     prelude::*,
@@ -7,14 +8,19 @@ use bevy::{
 
 pub fn setup_background(mut commands: Commands, asset_server: Res<AssetServer>) {
     let background_image = asset_server.load("bg.png");
-    commands.spawn(SpriteBundle {
-        texture: background_image,
-        ..Default::default()
-    });
+    commands.spawn((
+        SpriteBundle {
+            texture: background_image,
+            transform: Transform::from_xyz(0., 0., BACKGROUND_Z),
+            ..Default::default()
+        },
+        CanvasBackground,
+    ));
 }
 
 pub fn setup_camera(mut commands: Commands) {
-    let main_camera = Camera2dBundle::default();
+    // nodes are stacked along z, see `NODE_BASE_Z`
+    let main_camera = Camera2dBundle::new_with_far(CANVAS_FAR);
     commands.spawn((main_camera, MainCamera));
     let mut effects_camera = Camera2dBundle {
         camera: Camera {
@@ -3,9 +3,12 @@ use bevy::{prelude::*, text::BreakLineOn};
 
 use serde::{Deserialize, Serialize};
 
+use crate::components::Doc;
 use crate::resources::AppState;
 
-use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowType};
+use crate::canvas::arrow::components::{
+    ArrowConnect, ArrowConnectPos, ArrowEnd, ArrowStyle, ArrowType,
+};
 use crate::canvas::arrow::events::{CreateArrowEvent, RedrawArrowEvent};
 use crate::utils::ReflectableUuid;
 use std::path::PathBuf;
@@ -19,8 +22,65 @@ use save_systems::*;
 #[path = "systems/load.rs"]
 mod load_systems;
 use load_systems::*;
+#[path = "systems/export.rs"]
+mod export;
+use export::*;
+#[path = "systems/compression.rs"]
+mod compression;
+use compression::*;
+#[path = "systems/serialization.rs"]
+mod serialization;
+pub use serialization::*;
+#[path = "systems/storage.rs"]
+mod storage;
+use storage::*;
+#[cfg(target_arch = "wasm32")]
+#[path = "systems/web_storage.rs"]
+mod web_storage;
+#[cfg(target_arch = "wasm32")]
+use web_storage::*;
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+#[path = "systems/file_dialogs.rs"]
+mod file_dialogs;
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+use file_dialogs::*;
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+#[path = "systems/merge_docs.rs"]
+mod merge_docs;
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+use merge_docs::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/tab_previews.rs"]
+mod tab_previews;
+#[cfg(not(target_arch = "wasm32"))]
+use tab_previews::*;
+#[path = "systems/file_io.rs"]
+mod file_io;
+use file_io::*;
+#[path = "systems/encryption.rs"]
+mod encryption;
+use encryption::*;
+#[path = "systems/dot.rs"]
+mod dot;
+#[path = "systems/excalidraw.rs"]
+mod excalidraw;
+#[path = "systems/export_markdown.rs"]
+mod export_markdown;
+#[path = "systems/export_pdf.rs"]
+mod export_pdf;
+#[path = "systems/export_png.rs"]
+mod export_png;
+#[path = "systems/export_svg.rs"]
+mod export_svg;
+#[path = "systems/import.rs"]
+mod import;
+use import::*;
+#[path = "systems/json_canvas.rs"]
+mod json_canvas;
 #[path = "systems/keyboard.rs"]
 mod keyboard_systems;
+#[path = "systems/mermaid.rs"]
+mod mermaid;
 use keyboard_systems::*;
 #[path = "systems/modal.rs"]
 mod modal;
@@ -46,6 +106,9 @@ use doc_list::*;
 #[path = "systems/clickable_links.rs"]
 mod clickable_links;
 use clickable_links::*;
+#[path = "systems/task_lists.rs"]
+mod task_lists;
+use task_lists::*;
 #[path = "systems/entity_to_edit_changed.rs"]
 mod entity_to_edit_changed;
 use entity_to_edit_changed::*;
@@ -64,15 +127,287 @@ use create_new_node::*;
 mod search;
 #[cfg(not(target_arch = "wasm32"))]
 pub use search::*;
+#[path = "systems/theme.rs"]
+mod theme;
+pub use theme::*;
+#[path = "systems/locale.rs"]
+mod locale;
+use locale::*;
+#[path = "systems/settings.rs"]
+mod settings;
+use settings::*;
+#[path = "systems/keybindings.rs"]
+mod keybindings;
+use keybindings::*;
+#[path = "systems/canvas_search.rs"]
+mod canvas_search;
+use canvas_search::*;
+#[path = "systems/command_palette.rs"]
+mod command_palette;
+use command_palette::*;
+#[path = "systems/notifications.rs"]
+mod notifications;
+use notifications::*;
+#[path = "systems/status_bar.rs"]
+mod status_bar;
+use status_bar::*;
+#[path = "systems/panels.rs"]
+mod panels;
+use panels::*;
+#[path = "systems/window_title.rs"]
+mod window_title;
+use window_title::*;
+#[path = "systems/arrow_selection.rs"]
+mod arrow_selection;
+use arrow_selection::*;
+#[path = "systems/arrow_labels.rs"]
+mod arrow_labels;
+use arrow_labels::*;
+#[path = "systems/arrow_hover.rs"]
+mod arrow_hover;
+use arrow_hover::*;
+#[path = "systems/arrow_animation.rs"]
+mod arrow_animation;
+use arrow_animation::*;
 #[path = "systems/canvas_click.rs"]
 mod canvas_click;
 use canvas_click::*;
+#[path = "systems/selection.rs"]
+mod selection;
+use selection::*;
+#[path = "systems/canvas_camera.rs"]
+mod canvas_camera;
+pub use canvas_camera::*;
+#[path = "systems/canvas_picking.rs"]
+mod canvas_picking;
+use canvas_picking::*;
+#[path = "systems/node_layout.rs"]
+mod node_layout;
+use node_layout::*;
+#[path = "systems/node_index.rs"]
+mod node_index;
+pub use node_index::*;
+#[path = "systems/culling.rs"]
+mod culling;
+use culling::*;
+#[path = "systems/groups.rs"]
+mod groups;
+use groups::*;
+#[path = "systems/grid.rs"]
+mod grid;
+use grid::*;
+#[path = "systems/keyboard_navigation.rs"]
+mod keyboard_navigation;
+use keyboard_navigation::*;
+#[path = "systems/rotate_node.rs"]
+mod rotate_node;
+use rotate_node::*;
+#[path = "systems/align_nodes.rs"]
+mod align_nodes;
+use align_nodes::*;
+#[path = "systems/lock_nodes.rs"]
+mod lock_nodes;
+use lock_nodes::*;
+#[path = "systems/sticky_note.rs"]
+mod sticky_note;
+use sticky_note::*;
+#[path = "systems/frames.rs"]
+mod frames;
+use frames::*;
+#[path = "systems/branches.rs"]
+mod branches;
+use branches::*;
+#[path = "systems/auto_layout.rs"]
+mod auto_layout;
+use auto_layout::*;
+#[path = "systems/recent_docs.rs"]
+mod recent_docs;
+use recent_docs::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/cli_export.rs"]
+mod cli_export;
+#[cfg(not(target_arch = "wasm32"))]
+pub use cli_export::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/recovery.rs"]
+mod recovery;
+#[cfg(not(target_arch = "wasm32"))]
+use recovery::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/file_watch.rs"]
+mod file_watch;
+#[cfg(not(target_arch = "wasm32"))]
+use file_watch::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/theme_file.rs"]
+mod theme_file;
+#[cfg(not(target_arch = "wasm32"))]
+use theme_file::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/cloud_sync.rs"]
+mod cloud_sync;
+#[cfg(not(target_arch = "wasm32"))]
+use cloud_sync::*;
+#[path = "systems/collab.rs"]
+mod collab;
+#[path = "systems/collab_socket.rs"]
+mod collab_socket;
+#[path = "systems/collab_text.rs"]
+mod collab_text;
+use collab::*;
+#[path = "systems/pen.rs"]
+mod pen;
+use pen::*;
+#[path = "systems/rect_tool.rs"]
+mod rect_tool;
+use rect_tool::*;
+#[path = "systems/tools.rs"]
+mod tools;
+use tools::*;
+#[path = "systems/arrow_to_new_node.rs"]
+mod arrow_to_new_node;
+use arrow_to_new_node::*;
+#[path = "systems/assets.rs"]
+mod assets;
+use assets::*;
+#[path = "systems/attachments.rs"]
+mod attachments;
+use attachments::*;
+#[path = "systems/internal_links.rs"]
+mod internal_links;
+use internal_links::*;
+#[path = "systems/presentation.rs"]
+mod presentation;
+use presentation::*;
+#[path = "systems/image_node.rs"]
+mod image_node;
+use image_node::*;
+#[path = "systems/arrow_style.rs"]
+mod arrow_style;
+use arrow_style::*;
+#[path = "systems/node_border.rs"]
+mod node_border;
+use node_border::*;
+#[path = "systems/node_effects.rs"]
+mod node_effects;
+use node_effects::*;
+#[path = "systems/text_style.rs"]
+mod text_style;
+use text_style::*;
+#[path = "systems/zoom.rs"]
+mod zoom;
+use zoom::*;
+#[path = "systems/pan.rs"]
+mod pan;
+use pan::*;
+#[path = "systems/gestures.rs"]
+mod gestures;
+use gestures::*;
+#[path = "systems/high_contrast.rs"]
+mod high_contrast;
+use high_contrast::*;
+#[path = "systems/history.rs"]
+mod history;
+use history::*;
+#[path = "systems/compare.rs"]
+mod compare;
+use compare::*;
+#[path = "systems/tab_transfer.rs"]
+mod tab_transfer;
+use tab_transfer::*;
+#[path = "systems/templates.rs"]
+mod templates;
+use templates::*;
+#[path = "systems/snippets.rs"]
+mod snippets;
+use snippets::*;
+#[path = "systems/minimap.rs"]
+mod minimap;
+use minimap::*;
+#[path = "systems/context_menu.rs"]
+mod context_menu;
+use context_menu::*;
+#[path = "systems/inspector.rs"]
+mod inspector;
+use inspector::*;
+#[path = "systems/color_picker.rs"]
+mod color_picker;
+use color_picker::*;
+#[path = "systems/tags.rs"]
+mod tags;
+use tags::*;
+#[path = "systems/duplicate_node.rs"]
+mod duplicate_node;
+use duplicate_node::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/drop_files.rs"]
+mod drop_files;
+#[cfg(not(target_arch = "wasm32"))]
+use drop_files::*;
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/clipboard.rs"]
+mod clipboard;
+#[cfg(not(target_arch = "wasm32"))]
+use clipboard::*;
 
 pub struct UiPlugin;
 
+/// Spawns `node` (in world coordinates) and edits it.
 pub struct AddRectEvent {
     pub node: JsonNode,
-    pub image: Option<UiImage>,
+    pub image: Option<Handle<Image>>,
+}
+
+/// Pans the canvas by `delta` (logical pixels, y axis pointing up).
+pub struct PanCanvasEvent {
+    pub delta: Vec2,
+}
+
+/// Sets canvas zoom to `zoom`, canvas point `from` ends up at `to` (both from the left bottom
+/// corner of the canvas).
+#[derive(Clone, Copy)]
+pub struct ZoomCanvasEvent {
+    pub zoom: f32,
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+/// Switches to the tab if needed, centers the canvas on the node (if any) and selects it.
+pub struct JumpToNodeEvent {
+    pub tab_id: ReflectableUuid,
+    pub node_id: Option<ReflectableUuid>,
+}
+
+/// Sent when an internal link (to a tab or a node) is clicked.
+pub struct OpenInternalLinkEvent {
+    pub link: InternalLink,
+}
+
+/// Sent when an entry of the context menu is chosen, `node` is the right-clicked node and
+/// `position` the right-clicked point of the canvas (from its left bottom corner).
+pub struct ContextMenuEvent {
+    pub action: ContextMenuAction,
+    pub node: Option<ReflectableUuid>,
+    pub position: Vec2,
+}
+
+/// Changes a property of the node by `step`: pixels (unscaled by zoom) for position and size,
+/// layers for z-index and palette entries for color, any step toggles text position.
+pub struct ChangeNodePropertyEvent {
+    pub id: ReflectableUuid,
+    pub property: NodeProperty,
+    pub step: f32,
+}
+
+/// Sets the relationship type of new arrows and of arrows between selected nodes, `None` (or an
+/// empty type) removes it.
+pub struct SetArrowRelationEvent {
+    pub relation: Option<String>,
+}
+
+pub struct SetNodeTagsEvent {
+    pub id: ReflectableUuid,
+    pub tags: Vec<String>,
 }
 
 pub struct SaveStoreEvent {
@@ -82,10 +417,57 @@ pub struct SaveStoreEvent {
 
 pub struct UpdateDeleteDocBtnEvent;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotificationLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+/// Shown as a toast and logged, failures which don't stop velo are reported with it. `details`
+/// (e.g. the error of the OS) are shown when the toast is clicked.
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: Localized,
+    pub details: Option<String>,
+}
+
+impl Notification {
+    pub fn info(message: Localized) -> Self {
+        Self {
+            level: NotificationLevel::Info,
+            message,
+            details: None,
+        }
+    }
+
+    pub fn warn(message: Localized) -> Self {
+        Self {
+            level: NotificationLevel::Warn,
+            message,
+            details: None,
+        }
+    }
+
+    pub fn error(message: Localized) -> Self {
+        Self {
+            level: NotificationLevel::Error,
+            message,
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: impl ToString) -> Self {
+        self.details = Some(details.to_string());
+        self
+    }
+}
+
 #[derive(Resource, Clone)]
 pub struct CommChannels {
-    pub tx: Sender<String>,
-    pub rx: Receiver<String>,
+    pub tx: Sender<Doc>,
+    pub rx: Receiver<Doc>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Reflect, Default, Debug)]
@@ -93,21 +475,96 @@ pub enum NodeType {
     #[default]
     Rect,
     Circle,
+    Ellipse,
+    Diamond,
+    Hexagon,
+    Parallelogram,
+    Cylinder,
+    Sticky,
+    Image,
+    Frame,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Where the text of a node is placed, set on its `VeloNode`.
+#[derive(Serialize, Deserialize, Component, Clone, PartialEq, Eq, Debug)]
 pub enum TextPos {
     Center,
     TopLeft,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub enum BorderStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// Border of box-shaped nodes (rectangles, sticky notes, images and frames).
+#[derive(Serialize, Deserialize, Component, Clone, PartialEq, Debug)]
+pub struct NodeBorder {
+    pub width: f32,
+    pub color: Color,
+    pub style: BorderStyle,
+    pub radius: f32,
+}
+
+impl Default for NodeBorder {
+    fn default() -> Self {
+        Self {
+            width: 1.,
+            color: Color::rgb(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0),
+            style: BorderStyle::Solid,
+            radius: 0.,
+        }
+    }
+}
+
+/// Drop shadow of a node, offset is in pixels to the right and down.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct NodeShadow {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub blur: f32,
+    pub color: Color,
+}
+
+/// Fonts bundled with the app, all faces of Source Code Pro.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub enum NodeFont {
+    #[default]
+    Regular,
+    Bold,
+    Italic,
+}
+
+/// Text style of a node, shared by the markdown view and the editor.
+#[derive(Serialize, Deserialize, Component, Clone, PartialEq, Debug)]
+pub struct NodeTextStyle {
+    pub font_size: f32,
+    pub font: NodeFont,
+    pub color: Color,
+}
+
+impl Default for NodeTextStyle {
+    fn default() -> Self {
+        Self {
+            font_size: 18.,
+            font: NodeFont::Regular,
+            color: Color::BLACK,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct JsonNodeText {
     pub text: String,
     pub pos: TextPos,
+    #[serde(default)]
+    pub style: NodeTextStyle,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct JsonNode {
     pub id: Uuid,
     pub node_type: NodeType,
@@ -118,22 +575,401 @@ pub struct JsonNode {
     pub text: JsonNodeText,
     pub bg_color: Color,
     pub z_index: i32,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub border: NodeBorder,
+    #[serde(default)]
+    pub shadow: Option<NodeShadow>,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// File paths or URLs attached to the node.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+/// Frame node contents saved in the tab under its id.
+#[derive(Serialize, Deserialize)]
+pub struct JsonFrame {
+    pub members: Vec<Uuid>,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+/// Pen stroke saved in the tab, points and width are unscaled by canvas zoom.
+#[derive(Serialize, Deserialize)]
+pub struct JsonStroke {
+    pub id: Uuid,
+    pub points: Vec<[f32; 2]>,
+    pub color: Color,
+    pub width: f32,
+}
+
+fn default_opacity() -> f32 {
+    1.
 }
 
-pub const MAX_CHECKPOINTS: i32 = 7;
+// Nodes can't be resized to less than that
+pub const MIN_NODE_SIZE: f32 = 10.;
 pub const MAX_SAVED_DOCS_IN_MEMORY: i32 = 7;
 
 #[derive(Resource, Default)]
 pub struct UiState {
     pub modal_id: Option<ReflectableUuid>,
-    pub entity_to_edit: Option<ReflectableUuid>,
+    /// The text of the primary selected node is edited
+    pub editing_node: bool,
     pub tab_to_edit: Option<ReflectableUuid>,
     pub doc_to_edit: Option<ReflectableUuid>,
     pub search_box_to_edit: Option<ReflectableUuid>,
     pub arrow_type: ArrowType,
-    pub hold_entity: Option<ReflectableUuid>,
+    /// Style of new arrows
+    pub arrow_style: ArrowStyle,
+    /// Relationship type of new arrows
+    pub arrow_relation: Option<String>,
+    /// The primary selected node is dragged with the mouse
+    pub holding_node: bool,
     pub entity_to_resize: Option<(ReflectableUuid, ResizeMarker)>,
     pub arrow_to_draw_start: Option<ArrowConnect>,
+    pub color_hex_to_edit: bool,
+    pub node_tags_to_edit: Option<ReflectableUuid>,
+    pub canvas_search_to_edit: bool,
+    pub entity_to_rotate: Option<ReflectableUuid>,
+    /// Arrow whose label is edited
+    pub arrow_label_to_edit: Option<Entity>,
+    pub command_palette_to_edit: bool,
+    /// A window previewing a tab has the keyboard focus
+    pub tab_preview_focused: bool,
+}
+
+impl UiState {
+    /// True when no text input (node, modal, tab, doc, search box, color hex, tags field,
+    /// canvas search, arrow label or command palette) nor tab preview has focus.
+    pub fn is_canvas_focused(&self) -> bool {
+        !self.editing_node
+            && self.modal_id.is_none()
+            && self.tab_to_edit.is_none()
+            && self.doc_to_edit.is_none()
+            && self.search_box_to_edit.is_none()
+            && !self.color_hex_to_edit
+            && self.node_tags_to_edit.is_none()
+            && !self.canvas_search_to_edit
+            && self.arrow_label_to_edit.is_none()
+            && !self.command_palette_to_edit
+            && !self.tab_preview_focused
+    }
+}
+
+/// Nodes selected with a click, rubber band or Shift+Click, move/delete/color apply to all of
+/// them. The first one is the primary node, the one clicked last: its text is edited while
+/// `UiState::editing_node` is set and it follows the mouse while `UiState::holding_node` is set.
+#[derive(Resource, Default)]
+pub struct SelectedNodes(pub Vec<ReflectableUuid>);
+
+impl SelectedNodes {
+    pub fn primary(&self) -> Option<ReflectableUuid> {
+        self.0.first().copied()
+    }
+
+    /// Makes `id` the primary node, selecting it if it isn't yet.
+    pub fn set_primary(&mut self, id: ReflectableUuid) {
+        self.0.retain(|selected| *selected != id);
+        self.0.insert(0, id);
+    }
+
+    /// Selects `id` alone and starts editing its text.
+    pub fn edit(&mut self, ui_state: &mut UiState, id: ReflectableUuid) {
+        self.0 = vec![id];
+        ui_state.editing_node = true;
+    }
+
+    /// The node whose text is edited.
+    pub fn edited(&self, ui_state: &UiState) -> Option<ReflectableUuid> {
+        self.primary().filter(|_| ui_state.editing_node)
+    }
+
+    /// The node dragged with the mouse, the rest of the selection moves along.
+    pub fn held(&self, ui_state: &UiState) -> Option<ReflectableUuid> {
+        self.primary().filter(|_| ui_state.holding_node)
+    }
+}
+
+/// Arrow selected by clicking it, Delete/Backspace removes it and dragging one of its end
+/// handles re-attaches that end to another node. Dragging its line adds a waypoint, waypoints
+/// are dragged by their handles.
+#[derive(Resource, Default)]
+pub struct SelectedArrow {
+    pub entity: Option<Entity>,
+    pub dragged_end: Option<ArrowEnd>,
+    pub dragged_waypoint: Option<usize>,
+    /// World position where the line of the selected arrow was pressed, a waypoint is added
+    /// there once the cursor moves away
+    pub line_pressed_at: Option<Vec2>,
+}
+
+impl SelectedArrow {
+    pub fn is_dragging(&self) -> bool {
+        self.dragged_end.is_some()
+            || self.dragged_waypoint.is_some()
+            || self.line_pressed_at.is_some()
+    }
+}
+
+/// Reduced motion stops arrow animations, the caret blink, touch flings and particles and makes
+/// presentation transitions and layout animations instant, it's toggled with Cmd/Ctrl+Shift+M,
+/// in the settings panel or set in the config file.
+#[derive(Resource, Default)]
+pub struct MotionSettings {
+    pub reduced_motion: bool,
+}
+
+#[derive(Resource)]
+pub struct GridSettings {
+    pub enabled: bool,
+    pub size: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 20.,
+        }
+    }
+}
+
+impl GridSettings {
+    /// Rounds value to the closest grid line, returns it unchanged if grid is disabled.
+    pub fn snap(&self, value: f32) -> f32 {
+        if self.enabled && self.size > 0. {
+            (value / self.size).round() * self.size
+        } else {
+            value
+        }
+    }
+}
+
+/// Zoom of the canvas of the active tab. The main camera shows the world scaled by it, text is
+/// rendered in the scaled size.
+#[derive(Resource)]
+pub struct CanvasZoom(pub f32);
+
+impl Default for CanvasZoom {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Canvas point (from the left bottom corner of the main panel) the world origin is shown at,
+/// changed by panning and zooming. Node positions are in world coordinates and don't depend on
+/// it.
+#[derive(Resource, Default)]
+pub struct CanvasOffset(pub Vec2);
+
+/// Tool the canvas is used with, dragging on the canvas selects, pans, draws a node, connects
+/// nodes, draws strokes or erases them depending on it.
+#[derive(Resource, Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub enum Tool {
+    #[default]
+    Select,
+    Pan,
+    Node,
+    Arrow,
+    Pen,
+    Eraser,
+}
+
+/// Color and width of the strokes drawn with the pen tool.
+#[derive(Resource)]
+pub struct PenTool {
+    pub color: Color,
+    /// Stroke width unscaled by canvas zoom
+    pub width: f32,
+}
+
+impl Default for PenTool {
+    fn default() -> Self {
+        Self {
+            color: Color::rgb(33.0 / 255.0, 33.0 / 255.0, 33.0 / 255.0),
+            width: 2.,
+        }
+    }
+}
+
+/// Presentation of the active tab, walks through `path` of nodes with editor panels hidden.
+#[derive(Resource, Default)]
+pub struct Presentation {
+    pub active: bool,
+    pub path: Vec<ReflectableUuid>,
+    pub current: usize,
+}
+
+/// The open document has edits which aren't saved yet, shown in the window title.
+#[derive(Resource, Default)]
+pub struct UnsavedChanges(pub bool);
+
+/// Highlighting of the differences of the active tab from its last saved state.
+#[derive(Resource, Default)]
+pub struct CompareMode {
+    pub active: bool,
+}
+
+/// Pen strokes selected by clicking them, Delete/Backspace removes them.
+#[derive(Resource, Default)]
+pub struct SelectedStrokes(pub Vec<ReflectableUuid>);
+
+/// Colors of the node background color palette.
+pub const NODE_COLORS: [Color; 5] = [
+    Color::rgb(1., 225.0 / 255.0, 130.0 / 255.0),
+    Color::rgb(215.0 / 255.0, 204.0 / 255.0, 200.0 / 255.0),
+    Color::rgb(173.0 / 255.0, 216.0 / 255.0, 230.0 / 255.0),
+    Color::rgb(207.0 / 255.0, 226.0 / 255.0, 243.0 / 255.0),
+    Color::rgb(245.0 / 255.0, 222.0 / 255.0, 179.0 / 255.0),
+];
+
+/// Node properties shown in the inspector panel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeProperty {
+    X,
+    Y,
+    Width,
+    Height,
+    ZIndex,
+    Rotation,
+    Color,
+    TextPos,
+    NodeType,
+    Tags,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum TagFilterMode {
+    #[default]
+    Dim,
+    Hide,
+}
+
+/// Tags selected in the tag list, nodes having none of them are dimmed or hidden. Arrows of none
+/// of the selected relationship types are dimmed.
+#[derive(Resource, Default)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+    pub relations: Vec<String>,
+    pub mode: TagFilterMode,
+}
+
+pub const MAX_RECENT_COLORS: usize = 8;
+
+/// Colors applied with the color picker, most recent first, persisted across sessions.
+#[derive(Resource, Default)]
+pub struct RecentColors(pub Vec<Color>);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContextMenuAction {
+    Edit,
+    Duplicate,
+    Delete,
+    Lock,
+    BringToFront,
+    ChangeColor,
+    CopyLink,
+    NewNode,
+    Paste,
+    SelectAll,
+    /// Entry added by another system, which handles `ContextMenuEvent` with it.
+    Custom(&'static str),
+    /// Entry of the "Move to tab…" menu, moves the selected nodes to the tab.
+    MoveToTab(ReflectableUuid),
+    /// Entries of the menu of a tab in the tab bar.
+    PinTab(ReflectableUuid),
+    CycleTabColor(ReflectableUuid),
+    CycleTabIcon(ReflectableUuid),
+    /// Entry of the recent documents menu.
+    OpenDoc(ReflectableUuid),
+    /// Entry of the menu of a tab, shows a preview of the tab in a window of its own.
+    OpenTabPreview(ReflectableUuid),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContextMenuTarget {
+    Node,
+    Canvas,
+}
+
+pub struct ContextMenuEntry {
+    pub label: String,
+    pub target: ContextMenuTarget,
+    pub action: ContextMenuAction,
+}
+
+/// Entries of the right-click menu in display order, other systems can add their own.
+#[derive(Resource)]
+pub struct ContextMenuEntries(pub Vec<ContextMenuEntry>);
+
+impl ContextMenuEntries {
+    pub fn register(
+        &mut self,
+        label: impl Into<String>,
+        target: ContextMenuTarget,
+        action: ContextMenuAction,
+    ) {
+        self.0.push(ContextMenuEntry {
+            label: label.into(),
+            target,
+            action,
+        });
+    }
+}
+
+impl Default for ContextMenuEntries {
+    fn default() -> Self {
+        let mut entries = Self(vec![]);
+        entries.register("Edit", ContextMenuTarget::Node, ContextMenuAction::Edit);
+        entries.register(
+            "Duplicate",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Duplicate,
+        );
+        entries.register("Delete", ContextMenuTarget::Node, ContextMenuAction::Delete);
+        entries.register(
+            "Lock/Unlock",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Lock,
+        );
+        entries.register(
+            "Bring to front",
+            ContextMenuTarget::Node,
+            ContextMenuAction::BringToFront,
+        );
+        entries.register(
+            "Change color",
+            ContextMenuTarget::Node,
+            ContextMenuAction::ChangeColor,
+        );
+        // clipboard is available on native target only
+        #[cfg(not(target_arch = "wasm32"))]
+        entries.register(
+            "Copy link",
+            ContextMenuTarget::Node,
+            ContextMenuAction::CopyLink,
+        );
+        entries.register(
+            "New node here",
+            ContextMenuTarget::Canvas,
+            ContextMenuAction::NewNode,
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        entries.register("Paste", ContextMenuTarget::Canvas, ContextMenuAction::Paste);
+        entries.register(
+            "Select all",
+            ContextMenuTarget::Canvas,
+            ContextMenuAction::SelectAll,
+        );
+        entries
+    }
 }
 
 #[derive(Resource)]
@@ -144,6 +980,70 @@ pub struct BlinkTimer {
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<UiState>();
+        app.init_resource::<SelectedNodes>();
+        app.init_resource::<GridSettings>();
+        app.init_resource::<MotionSettings>();
+        app.init_resource::<CanvasZoom>();
+        app.init_resource::<CanvasOffset>();
+        app.init_resource::<History>();
+        app.init_resource::<CollabSettings>();
+        app.init_resource::<FileIo>();
+        app.init_resource::<Theme>();
+        app.init_resource::<Locale>();
+        app.init_resource::<Settings>();
+        app.init_resource::<KeybindingRecorder>();
+        app.init_resource::<PaletteRun>();
+        #[cfg(not(target_arch = "wasm32"))]
+        app.init_resource::<CloudSync>();
+        #[cfg(not(target_arch = "wasm32"))]
+        app.init_resource::<ThemeFile>();
+        app.init_resource::<PenTool>();
+        app.init_resource::<SelectedStrokes>();
+        app.init_resource::<SelectedArrow>();
+        app.init_resource::<NodeIndex>();
+        app.init_resource::<Tool>();
+        app.init_resource::<Presentation>();
+        app.init_resource::<CompareMode>();
+        app.init_resource::<UnsavedChanges>();
+        app.init_resource::<ContextMenuEntries>();
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Collapse/Expand branch",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Custom(TOGGLE_BRANCH_ACTION),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Attach from clipboard",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Custom(ATTACH_FROM_CLIPBOARD_ACTION),
+        );
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Add to presentation",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Custom(ADD_TO_PRESENTATION_ACTION),
+        );
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Remove from presentation",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Custom(REMOVE_FROM_PRESENTATION_ACTION),
+        );
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Move to tab…",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Custom(MOVE_TO_TAB_ACTION),
+        );
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Save as snippet",
+            ContextMenuTarget::Node,
+            ContextMenuAction::Custom(SAVE_SNIPPET_ACTION),
+        );
+        app.world.resource_mut::<ContextMenuEntries>().register(
+            "Duplicate tab",
+            ContextMenuTarget::Canvas,
+            ContextMenuAction::Custom(DUPLICATE_TAB_ACTION),
+        );
+        app.init_resource::<RecentColors>();
+        app.init_resource::<TagFilter>();
         app.init_resource::<AppState>();
 
         app.register_type::<VeloNode>();
@@ -161,13 +1061,35 @@ impl Plugin for UiPlugin {
         app.add_event::<CreateArrowEvent>();
         app.add_event::<RedrawArrowEvent>();
         app.add_event::<SaveStoreEvent>();
+        app.add_event::<FileIoEvent>();
+        app.add_event::<PanCanvasEvent>();
+        app.add_event::<ZoomCanvasEvent>();
+        app.add_event::<JumpToNodeEvent>();
+        app.add_event::<OpenInternalLinkEvent>();
+        app.add_event::<ContextMenuEvent>();
+        app.add_event::<ChangeNodePropertyEvent>();
+        app.add_event::<SetNodeTagsEvent>();
+        app.add_event::<SetArrowRelationEvent>();
         app.add_event::<UpdateDeleteDocBtnEvent>();
+        app.add_event::<Notification>();
 
         #[cfg(not(target_arch = "wasm32"))]
-        app.add_startup_systems((read_native_config, init_search_index).before(init_layout));
+        app.add_startup_systems(
+            (
+                read_native_config.after(load_settings),
+                init_search_index,
+                check_recovery,
+            )
+                .before(init_layout),
+        );
         #[cfg(target_arch = "wasm32")]
         app.add_startup_system(load_from_url.before(init_layout));
         app.add_startup_system(init_layout);
+        app.add_startup_system(init_shape_images);
+        app.add_startup_system(init_border_images);
+        app.add_startup_system(load_recent_colors);
+        app.add_startup_system(load_locale.before(load_settings));
+        app.add_startup_system(load_settings.before(init_layout));
 
         app.add_systems((
             rec_button_handlers,
@@ -220,6 +1142,42 @@ impl Plugin for UiPlugin {
             keyboard_input_system,
         ));
         app.add_systems((doc_list_del_button_update, doc_list_ui_changed).chain());
+        app.add_systems((scroll_tab_bar, drag_tab).chain());
+        app.add_systems((open_tab_menu, tab_menu_actions.after(context_menu_click)));
+        app.add_system(tab_viewport.before(record_history));
+        app.add_systems((
+            remember_session,
+            open_recent_docs_menu.before(context_menu_click),
+            recent_docs_menu_actions.after(context_menu_click),
+            show_welcome_screen,
+            welcome_screen_click,
+        ));
+        app.add_systems((
+            toggle_snippet_library,
+            save_snippet.after(context_menu_click),
+            snippet_library_click,
+        ));
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems((
+            write_recovery_snapshot,
+            show_recovery_prompt,
+            recovery_prompt_click,
+            clear_recovery_on_exit,
+            watch_doc_file,
+            watch_theme_file.after(sync_settings),
+            file_changed_prompt_click,
+            sync_doc_click,
+            start_sync.after(remove_save_tab_request),
+            sync_results,
+            sync_conflict_click,
+        ));
+        app.add_systems((
+            duplicate_tab
+                .after(context_menu_click)
+                .after(remove_save_tab_request),
+            open_move_to_tab_menu.after(context_menu_click),
+            move_selection_to_tab.after(context_menu_click),
+        ));
 
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems((search_box_click, search_box_text_changed));
@@ -227,9 +1185,22 @@ impl Plugin for UiPlugin {
         app.add_systems((
             button_generic_handler,
             select_tab_handler,
+            // the path modal is used where there are no file dialogs
+            #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
             export_to_file,
+            encrypt_doc_handler,
+            request_passphrase,
+            #[cfg(not(any(
+                target_arch = "wasm32",
+                target_os = "windows",
+                target_os = "macos",
+                target_os = "linux"
+            )))]
             import_from_file,
+            #[cfg(target_arch = "wasm32")]
+            import_from_browser_file,
             import_from_url,
+            insert_image,
             load_doc_handler,
             #[cfg(target_arch = "wasm32")]
             set_window_property,
@@ -239,7 +1210,323 @@ impl Plugin for UiPlugin {
             save_to_store.after(save_tab),
             canvas_click,
         ));
-        app.add_systems((set_focused_entity, clickable_links).chain());
+        app.add_systems((file_io_results.before(load_doc_handler), file_io_status));
+        app.init_resource::<NotificationSender>();
+        app.add_systems((
+            check_fonts,
+            forward_notifications.before(show_notifications),
+            show_notifications,
+            toast_click,
+            expire_toasts,
+        ));
+        app.add_system(update_status_bar);
+        app.add_systems((
+            track_unsaved_changes
+                .after(record_history)
+                .before(save_doc)
+                .before(load_doc),
+            update_window_title.after(track_unsaved_changes),
+        ));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.init_resource::<TabPreviewRenders>();
+            app.add_systems((
+                open_tab_preview.after(context_menu_click),
+                render_tab_previews.after(open_tab_preview),
+                show_tab_preview_renders,
+                navigate_tab_previews,
+                close_tab_preview_views,
+                track_tab_preview_focus,
+            ));
+        }
+        app.add_systems((
+            resize_left_panel,
+            toggle_left_panel,
+            apply_panel_layout
+                .after(resize_left_panel)
+                .after(toggle_left_panel)
+                .after(settings_panel_click),
+        ));
+        #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+        {
+            app.init_resource::<FileDialogChannel>();
+            app.add_systems((
+                save_file_dialog,
+                open_file_dialog,
+                import_into_current_dialog,
+                file_dialog_results,
+            ));
+        }
+        app.add_systems((set_focused_entity, clickable_links, toggle_task_items).chain());
+        app.add_system(clickable_editor_links);
+        app.add_systems((rubber_band_selection, selected_nodes_changed).chain());
+        app.add_system(
+            pick_canvas_sprites
+                .in_base_set(CoreSet::PreUpdate)
+                .after(bevy::ui::UiSystem::Focus),
+        );
+        app.add_systems(
+            (
+                stack_nodes,
+                fit_to_text.before(layout_nodes),
+                layout_nodes.before(bevy_markdown::BevyMarkdownLayout),
+                place_markdown_views.after(bevy_markdown::BevyMarkdownLayout),
+                draw_canvas_outlines.after(layout_nodes),
+                sync_canvas_camera.after(bevy::ui::UiSystem::Flex),
+                (update_node_index, cull_offscreen_nodes).chain(),
+            )
+                .in_base_set(CoreSet::PostUpdate)
+                .before(bevy::transform::TransformSystem::TransformPropagate),
+        );
+        app.add_systems(
+            (
+                select_arrow.before(rubber_band_selection),
+                delete_selected_arrow,
+                drop_arrow_end,
+                drag_arrow_waypoint,
+                update_arrow_selection,
+                update_waypoint_handles,
+            )
+                .chain(),
+        );
+        app.add_systems((
+            toggle_reduced_motion,
+            animate_marching_dashes,
+            animate_flow_dots,
+        ));
+        app.add_systems((
+            highlight_arrow_markers,
+            highlight_hovered_arrow.after(update_arrow_selection),
+        ));
+        app.add_systems((group_nodes, duplicate_node, align_nodes, z_order_shortcuts));
+        app.add_systems((keyboard_navigation, nudge_selected_nodes));
+        app.add_systems(
+            (
+                rotate_node_start,
+                rotate_node,
+                apply_node_rotation,
+                update_rotation_handles,
+            )
+                .chain(),
+        );
+        app.add_systems((toggle_grid, update_grid).chain());
+        app.add_system(toggle_theme);
+        app.add_system(apply_theme.in_base_set(CoreSet::PostUpdate));
+        app.add_systems(
+            (
+                widen_outlines.before(draw_canvas_outlines),
+                scale_hit_targets.before(layout_nodes),
+            )
+                .in_base_set(CoreSet::PostUpdate),
+        );
+        app.add_system(change_language);
+        app.add_systems((
+            toggle_settings_panel,
+            settings_panel_click,
+            sync_settings
+                .after(settings_panel_click)
+                .after(toggle_theme)
+                .after(change_language),
+            autosave,
+            toggle_keybindings_panel,
+            keybindings_panel_click,
+        ));
+        app.add_system(
+            record_keybinding
+                .in_base_set(CoreSet::PreUpdate)
+                .after(bevy::input::InputSystem),
+        );
+        app.add_system(
+            apply_locale
+                .in_base_set(CoreSet::PostUpdate)
+                .before(bevy::ui::UiSystem::Flex),
+        );
+        app.add_systems((toggle_lock, update_lock_badges).chain());
+        app.add_systems((change_border, update_node_borders).chain());
+        app.add_systems(
+            (
+                change_arrow_style,
+                change_arrow_relation,
+                set_arrow_relation,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (change_node_effect, apply_node_opacity, update_node_shadows)
+                .chain()
+                .before(update_node_borders),
+        );
+        app.add_systems((change_text_style, update_text_style).chain());
+        app.add_system(canvas_zoom.before(update_text_style));
+        app.add_system(pan_canvas);
+        app.add_systems((
+            touchpad_gestures.before(canvas_zoom),
+            scroll_canvas.before(pan_canvas),
+            touch_gestures.before(canvas_zoom).before(pan_canvas),
+        ));
+        app.add_systems(
+            (
+                undo_redo,
+                history_list_click,
+                apply_history,
+                record_history.before(load_tab),
+                update_history_list,
+            )
+                .chain()
+                .after(canvas_zoom)
+                .after(pan_canvas),
+        );
+        app.add_systems(
+            (toggle_collab, sync_collab, share_cursor, show_collaborators)
+                .chain()
+                .after(record_history),
+        );
+        app.add_system(toggle_compare.before(load_tab));
+        app.add_systems((minimap_navigation.before(pan_canvas), update_minimap));
+        app.add_systems(
+            (
+                toggle_canvas_search,
+                canvas_search_input,
+                canvas_search_buttons,
+                update_canvas_search,
+                highlight_search_matches,
+                jump_to_node.before(pan_canvas),
+            )
+                .chain(),
+        );
+        app.add_system(follow_internal_links.before(jump_to_node));
+        app.add_systems(
+            (
+                toggle_command_palette,
+                command_palette_input,
+                update_command_palette,
+            )
+                .chain(),
+        );
+        app.add_system(
+            run_palette_command
+                .in_base_set(CoreSet::PreUpdate)
+                .after(bevy::input::InputSystem)
+                .after(bevy::ui::UiSystem::Focus),
+        );
+        app.add_systems((open_context_menu, context_menu_click, context_menu_actions).chain());
+        app.add_systems(
+            (
+                toggle_inspector,
+                inspector_buttons,
+                apply_node_property_change,
+                update_inspector,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                open_color_picker,
+                color_picker_areas,
+                color_picker_hex_input,
+                color_picker_eyedropper.after(set_focused_entity),
+                color_picker_buttons,
+                update_color_picker,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                edit_node_tags.before(update_inspector),
+                set_node_tags,
+                tag_filter_buttons,
+                update_tag_list,
+                apply_tag_filter,
+                apply_relation_filter.after(highlight_hovered_arrow),
+            )
+                .chain(),
+        );
+        app.add_system(add_sticky_note.before(create_new_node));
+        app.add_system(connect_to_new_node.before(create_new_node));
+        app.add_systems(
+            (
+                edit_presentation_path.after(context_menu_actions),
+                toggle_presentation,
+                apply_presentation_chrome,
+                animate_presentation.before(canvas_zoom),
+                update_presentation_badges,
+            )
+                .chain(),
+        );
+        app.add_system(create_node_on_double_click.before(create_new_node));
+        app.add_systems(
+            (
+                edit_arrow_label_on_click,
+                arrow_label_editing,
+                update_arrow_label_views,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                add_frame.before(create_new_node),
+                frame_collapse_buttons,
+                update_frame_members,
+                apply_frame_collapse.after(apply_tag_filter),
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                toggle_branch.after(context_menu_actions),
+                expand_branch_badges,
+                apply_branch_collapse.after(apply_tag_filter),
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                open_layout_menu.before(context_menu_click),
+                apply_layout.after(context_menu_click),
+                animate_layout,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                adjust_pen.after(select_tool),
+                draw_pen_strokes.before(rubber_band_selection),
+                select_pen_strokes,
+                update_pen_paths.after(pan_canvas).after(canvas_zoom),
+            )
+                .chain(),
+        );
+        app.add_systems(
+            (
+                select_tool,
+                draw_rect_node
+                    .before(create_new_node)
+                    .before(rubber_band_selection),
+            )
+                .chain(),
+        );
+        app.add_systems((
+            highlight_tool_button.after(select_tool),
+            update_tool_cursor
+                .after(select_tool)
+                .after(button_generic_handler),
+            draw_arrow_tool.after(select_tool),
+        ));
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems((copy_nodes, paste_nodes));
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems((
+            drop_files,
+            attach_from_clipboard.after(context_menu_actions),
+        ));
+        app.add_systems(
+            (
+                attachment_chip_click,
+                update_attachment_chips,
+                flow_attachment_chips,
+            )
+                .chain(),
+        );
 
         app.add_system(
             entity_to_edit_changed
@@ -254,12 +1541,28 @@ impl Plugin for UiPlugin {
 }
 
 #[cfg(target_arch = "wasm32")]
-fn load_from_url(mut commands: Commands) {
+fn load_from_url(
+    mut commands: Commands,
+    pkv: Res<bevy_pkv::PkvStore>,
+    mut collab_settings: ResMut<CollabSettings>,
+    notification_sender: Res<NotificationSender>,
+) {
     let (tx, rx) = async_channel::bounded(1);
     commands.insert_resource(CommChannels { tx: tx.clone(), rx });
+    // local storage was cleared or full, the last document is still in IndexedDB
+    if pkv
+        .get::<std::collections::HashMap<ReflectableUuid, String>>("names")
+        .is_err()
+    {
+        restore_from_indexed_db(tx.clone());
+    }
     let href = web_sys::window().unwrap().location().href().unwrap();
     let url = url::Url::parse(href.as_str()).unwrap();
     let query_pairs: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    collab_settings.relay = query_pairs.get("collab").cloned();
+    if let Some(name) = query_pairs.get("name") {
+        collab_settings.name = name.clone();
+    }
     if let Some(url) = query_pairs.get("document") {
         let pool = bevy::tasks::IoTaskPool::get();
         let mut finder = linkify::LinkFinder::new();
@@ -268,12 +1571,9 @@ fn load_from_url(mut commands: Commands) {
         if links.len() == 1 {
             let url = links.first().unwrap().as_str().to_owned();
             let cc = tx.clone();
+            let sender = notification_sender.clone();
             let task = pool.spawn(async move {
-                let request = ehttp::Request::get(url);
-                ehttp::fetch(request, move |result| {
-                    let json_string = result.unwrap().text().unwrap();
-                    cc.try_send(json_string).unwrap();
-                });
+                load_doc_from_url(url, cc, sender);
             });
             task.detach();
         }
@@ -281,11 +1581,35 @@ fn load_from_url(mut commands: Commands) {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn read_native_config(mut app_state: ResMut<AppState>) {
+fn read_native_config(
+    mut app_state: ResMut<AppState>,
+    mut settings: ResMut<Settings>,
+    mut collab_settings: ResMut<CollabSettings>,
+    mut cloud_sync: ResMut<CloudSync>,
+    mut theme_file: ResMut<ThemeFile>,
+) {
     use crate::utils::read_config_file;
 
     let config = read_config_file().unwrap_or_default();
     if let Some(github_token) = &config.github_access_token {
         app_state.github_token = Some(github_token.clone());
     }
+    // the config file takes precedence over the settings
+    if let Some(grid_size) = config.grid_size {
+        settings.grid_size = grid_size;
+    }
+    if let Some(reduced_motion) = config.reduced_motion {
+        settings.reduced_motion = reduced_motion;
+    }
+    collab_settings.relay = config.collab_relay;
+    if let Some(name) = config.collab_name {
+        collab_settings.name = name;
+    }
+    if let Some(sync) = config.sync {
+        match sync_backend(&sync) {
+            Ok(backend) => cloud_sync.backend = Some(backend),
+            Err(e) => error!("Error reading sync settings: {}", e),
+        }
+    }
+    theme_file.path = config.theme_file;
 }
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use super::{
+    ui_helpers::NodeRect, History, HistoryCommand, SelectedNodes, UiState, VeloNodeContainer,
+};
+use crate::canvas::arrow::events::RedrawArrowEvent;
+
+#[derive(Clone, Copy, PartialEq)]
+enum AlignCommand {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    CenterHorizontal,
+    CenterVertical,
+    DistributeHorizontal,
+    DistributeVertical,
+}
+
+// left, bottom, width, height
+type Rect = (f32, f32, f32, f32);
+
+fn apply_command(command: AlignCommand, rects: &mut [Rect]) {
+    let min_left = rects.iter().map(|r| r.0).fold(f32::MAX, f32::min);
+    let max_right = rects.iter().map(|r| r.0 + r.2).fold(f32::MIN, f32::max);
+    let min_bottom = rects.iter().map(|r| r.1).fold(f32::MAX, f32::min);
+    let max_top = rects.iter().map(|r| r.1 + r.3).fold(f32::MIN, f32::max);
+    match command {
+        AlignCommand::Left => rects.iter_mut().for_each(|r| r.0 = min_left),
+        AlignCommand::Right => rects.iter_mut().for_each(|r| r.0 = max_right - r.2),
+        AlignCommand::Bottom => rects.iter_mut().for_each(|r| r.1 = min_bottom),
+        AlignCommand::Top => rects.iter_mut().for_each(|r| r.1 = max_top - r.3),
+        AlignCommand::CenterHorizontal => {
+            let center = (min_left + max_right) / 2.;
+            rects.iter_mut().for_each(|r| r.0 = center - r.2 / 2.);
+        }
+        AlignCommand::CenterVertical => {
+            let center = (min_bottom + max_top) / 2.;
+            rects.iter_mut().for_each(|r| r.1 = center - r.3 / 2.);
+        }
+        AlignCommand::DistributeHorizontal => {
+            let mut order: Vec<usize> = (0..rects.len()).collect();
+            order.sort_by(|a, b| rects[*a].0.total_cmp(&rects[*b].0));
+            let total: f32 = rects.iter().map(|r| r.2).sum();
+            let gap = (max_right - min_left - total) / (rects.len() - 1) as f32;
+            let mut left = min_left;
+            for i in order {
+                rects[i].0 = left;
+                left += rects[i].2 + gap;
+            }
+        }
+        AlignCommand::DistributeVertical => {
+            let mut order: Vec<usize> = (0..rects.len()).collect();
+            order.sort_by(|a, b| rects[*a].1.total_cmp(&rects[*b].1));
+            let total: f32 = rects.iter().map(|r| r.3).sum();
+            let gap = (max_top - min_bottom - total) / (rects.len() - 1) as f32;
+            let mut bottom = min_bottom;
+            for i in order {
+                rects[i].1 = bottom;
+                bottom += rects[i].3 + gap;
+            }
+        }
+    }
+}
+
+pub fn align_nodes(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut containers: Query<(&mut NodeRect, &VeloNodeContainer)>,
+    mut events: EventWriter<RedrawArrowEvent>,
+    mut history: ResMut<History>,
+) {
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    let alt = input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if !shift || !alt || ui_state.editing_node {
+        return;
+    }
+    let align_command = if input.just_pressed(KeyCode::Left) {
+        AlignCommand::Left
+    } else if input.just_pressed(KeyCode::Right) {
+        AlignCommand::Right
+    } else if input.just_pressed(KeyCode::Up) {
+        AlignCommand::Top
+    } else if input.just_pressed(KeyCode::Down) {
+        AlignCommand::Bottom
+    } else if input.just_pressed(KeyCode::C) {
+        AlignCommand::CenterHorizontal
+    } else if input.just_pressed(KeyCode::M) {
+        AlignCommand::CenterVertical
+    } else if input.just_pressed(KeyCode::H) {
+        AlignCommand::DistributeHorizontal
+    } else if input.just_pressed(KeyCode::V) {
+        AlignCommand::DistributeVertical
+    } else {
+        return;
+    };
+    let min_nodes = match align_command {
+        AlignCommand::DistributeHorizontal | AlignCommand::DistributeVertical => 3,
+        _ => 2,
+    };
+    let mut nodes: Vec<_> = containers
+        .iter_mut()
+        .filter(|(_, container)| selected_nodes.0.contains(&container.id))
+        .collect();
+    if nodes.len() < min_nodes {
+        return;
+    }
+    let mut rects: Vec<Rect> = nodes
+        .iter()
+        .map(|(rect, _)| (rect.position.x, rect.position.y, rect.size.x, rect.size.y))
+        .collect();
+    apply_command(align_command, &mut rects);
+    for ((rect, container), (left, bottom, _, _)) in nodes.iter_mut().zip(rects) {
+        history.record(HistoryCommand::NodeMoved {
+            id: container.id.0,
+            from: rect.position,
+            to: Vec2::new(left, bottom),
+        });
+        rect.position = Vec2::new(left, bottom);
+        events.send(RedrawArrowEvent { id: container.id });
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::{
+    prelude::{Fill, GeometryBuilder, Path, ShapeBundle},
+    shapes,
+};
+
+use crate::canvas::arrow::components::{
+    ArrowAnimation, ArrowFlowDot, ArrowMeta, ArrowRoute, ArrowStyle,
+};
+use crate::canvas::arrow::utils::{arrow_path, point_along};
+
+use super::{MotionSettings, UiState};
+
+// Speeds along the arrow line in pixels per second
+const MARCHING_SPEED: f32 = 20.;
+const DOT_SPEED: f32 = 80.;
+
+pub fn toggle_reduced_motion(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    mut motion_settings: ResMut<MotionSettings>,
+) {
+    let command = input.any_pressed([
+        KeyCode::RWin,
+        KeyCode::LWin,
+        KeyCode::RControl,
+        KeyCode::LControl,
+    ]);
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    if command && shift && input.just_pressed(KeyCode::M) && !ui_state.editing_node {
+        motion_settings.reduced_motion = !motion_settings.reduced_motion;
+    }
+}
+
+/// Moves the dashes of arrows with marching dashes along their lines, with reduced motion they
+/// are drawn still.
+pub fn animate_marching_dashes(
+    time: Res<Time>,
+    motion_settings: Res<MotionSettings>,
+    mut arrows: Query<(&ArrowStyle, &ArrowRoute, &Visibility, &mut Path), With<ArrowMeta>>,
+) {
+    if motion_settings.reduced_motion && !motion_settings.is_changed() {
+        return;
+    }
+    let phase = if motion_settings.reduced_motion {
+        0.
+    } else {
+        time.elapsed_seconds() * MARCHING_SPEED
+    };
+    for (style, route, visibility, mut path) in arrows.iter_mut() {
+        if style.animation == ArrowAnimation::MarchingDashes && *visibility != Visibility::Hidden {
+            *path = arrow_path(route, *style, phase);
+        }
+    }
+}
+
+/// Spawns a dot for every arrow with the moving dot animation and moves it from the start of
+/// the arrow to its end over and over, dots are hidden with reduced motion.
+pub fn animate_flow_dots(
+    mut commands: Commands,
+    time: Res<Time>,
+    motion_settings: Res<MotionSettings>,
+    arrows: Query<(Entity, &ArrowStyle, &ArrowRoute, &Visibility), With<ArrowMeta>>,
+    mut dots: Query<
+        (
+            Entity,
+            &ArrowFlowDot,
+            &mut Transform,
+            &mut Visibility,
+            &mut Fill,
+        ),
+        Without<ArrowMeta>,
+    >,
+) {
+    let mut arrows_with_dot = HashSet::new();
+    for (entity, dot, mut transform, mut visibility, mut fill) in dots.iter_mut() {
+        let Some((_, style, route, arrow_visibility)) = arrows
+            .get(dot.arrow)
+            .ok()
+            .filter(|(_, style, ..)| style.animation == ArrowAnimation::MovingDot)
+        else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        arrows_with_dot.insert(dot.arrow);
+        let length: f32 = route
+            .points
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum();
+        let hidden = motion_settings.reduced_motion
+            || *arrow_visibility == Visibility::Hidden
+            || length == 0.;
+        let new_visibility = if hidden {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+        if hidden {
+            continue;
+        }
+        let position = point_along(&route.points, (time.elapsed_seconds() * DOT_SPEED) % length);
+        // above the arrow
+        transform.translation = position.extend(0.5);
+        if fill.color != style.color {
+            fill.color = style.color;
+        }
+    }
+    for (arrow, style, ..) in arrows.iter() {
+        if style.animation != ArrowAnimation::MovingDot || arrows_with_dot.contains(&arrow) {
+            continue;
+        }
+        commands.spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shapes::Circle {
+                    radius: style.thickness + 1.5,
+                    center: Vec2::ZERO,
+                }),
+                // hidden until placed on the arrow
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Fill::color(style.color),
+            ArrowFlowDot { arrow },
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_prototype_lyon::prelude::{Path, Stroke};
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowMeta, ArrowStyle};
+use crate::components::MainCamera;
+
+use super::{
+    arrow_at_cursor,
+    ui_helpers::{
+        ArrowMarkerDot, MainPanel, ARROW_MARKER_COLOR, ARROW_MARKER_HOVER_SIZE, ARROW_MARKER_SIZE,
+    },
+    SelectedArrow, Theme, UiState,
+};
+
+const HOVERED_MARKER_COLOR: Color = Color::rgb(0., 0., 1.);
+const HOVERED_ARROW_COLOR: Color = Color::rgb(100.0 / 255.0, 181.0 / 255.0, 246.0 / 255.0);
+
+/// Grows and brightens the dot of the hovered connection point and of the one a new arrow is
+/// drawn from.
+pub fn highlight_arrow_markers(
+    ui_state: Res<UiState>,
+    markers: Query<(&Interaction, &ArrowConnect, &Children)>,
+    mut dots: Query<(&mut Sprite, &Children), With<ArrowMarkerDot>>,
+    mut insides: Query<&mut Sprite, Without<ArrowMarkerDot>>,
+) {
+    for (interaction, arrow_connect, children) in markers.iter() {
+        let highlighted = *interaction != Interaction::None
+            || ui_state.arrow_to_draw_start == Some(*arrow_connect);
+        let (size, color) = if highlighted {
+            (ARROW_MARKER_HOVER_SIZE, HOVERED_MARKER_COLOR)
+        } else {
+            (ARROW_MARKER_SIZE, ARROW_MARKER_COLOR)
+        };
+        for child in children.iter() {
+            let Ok((mut dot, dot_children)) = dots.get_mut(*child) else {
+                continue;
+            };
+            if dot.custom_size != Some(Vec2::splat(size)) {
+                dot.custom_size = Some(Vec2::splat(size));
+            }
+            if dot.color != color {
+                dot.color = color;
+            }
+            // the white inside leaves a ring of the dot color
+            for inside in dot_children.iter() {
+                let Ok(mut inside) = insides.get_mut(*inside) else {
+                    continue;
+                };
+                if inside.custom_size != Some(Vec2::splat(size - 2.)) {
+                    inside.custom_size = Some(Vec2::splat(size - 2.));
+                }
+            }
+        }
+    }
+}
+
+/// Highlights the arrow under the cursor, the selected arrow keeps its own highlight.
+pub fn highlight_hovered_arrow(
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    main_panel_query: Query<&Interaction, With<MainPanel>>,
+    selected_arrow: Res<SelectedArrow>,
+    arrows: Query<(Entity, &Path, &Visibility), With<ArrowMeta>>,
+    mut strokes: Query<(&ArrowStyle, &mut Stroke), With<ArrowMeta>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    theme: Res<Theme>,
+    mut hovered: Local<Option<Entity>>,
+) {
+    if cursor_moved_events.iter().count() == 0 {
+        return;
+    }
+    // nodes and panels above the arrows hide them from the cursor
+    let over_canvas = main_panel_query
+        .get_single()
+        .map_or(false, |interaction| *interaction == Interaction::Hovered);
+    let hit = if over_canvas {
+        let (camera, camera_transform) = camera_q.single();
+        arrow_at_cursor(windows.single(), camera, camera_transform, &arrows, &theme)
+            .filter(|entity| selected_arrow.entity != Some(*entity))
+    } else {
+        None
+    };
+    if *hovered == hit {
+        return;
+    }
+    if let Some(Ok((style, mut stroke))) = hovered
+        .filter(|entity| selected_arrow.entity != Some(*entity))
+        .map(|entity| strokes.get_mut(entity))
+    {
+        stroke.color = style.color;
+        stroke.options.line_width = style.thickness;
+    }
+    if let Some(Ok((style, mut stroke))) = hit.map(|entity| strokes.get_mut(entity)) {
+        stroke.color = HOVERED_ARROW_COLOR;
+        stroke.options.line_width = style.thickness + 1.;
+    }
+    *hovered = hit;
+}
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::{
+    get_cosmic_text, spawn_cosmic_edit_sprite, ActiveEditor, CosmicEditImage, CosmicEditMeta,
+    CosmicTextPos, FontSystemState,
+};
+use bevy_prototype_lyon::prelude::{tess::path::PathEvent, Path};
+use cosmic_text::Attrs;
+
+use crate::canvas::arrow::components::{ArrowAnchors, ArrowLabel, ArrowMeta};
+use crate::components::MainCamera;
+
+use super::{
+    distance_to_segment, is_double_click,
+    ui_helpers::{ArrowLabelEditor, ArrowLabelView, MainPanel},
+    Theme, UiState,
+};
+
+// Cursor closer than that to an arrow line hovers it, thin lines are hard to hit exactly
+const ARROW_HIT_DISTANCE: f32 = 10.;
+const LABEL_WIDTH: f32 = 120.;
+const LABEL_HEIGHT: f32 = 24.;
+// Labels are drawn over the arrows
+const LABEL_Z: f32 = 1.;
+
+/// Visible arrow passing closest to the cursor, if any is close enough (further with the larger
+/// hit targets of the theme).
+pub fn arrow_at_cursor(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    arrows: &Query<(Entity, &Path, &Visibility), With<ArrowMeta>>,
+    theme: &Theme,
+) -> Option<Entity> {
+    let cursor = window.cursor_position()?;
+    let position = camera.viewport_to_world_2d(camera_transform, cursor)?;
+    arrows
+        .iter()
+        .filter(|(_, _, visibility)| **visibility != Visibility::Hidden)
+        .filter_map(|(entity, path, _)| {
+            path.0
+                .iter()
+                .filter_map(|event| match event {
+                    PathEvent::Line { from, to } => Some((from, to)),
+                    PathEvent::End { last, first, close } if close => Some((last, first)),
+                    _ => None,
+                })
+                .map(|(from, to)| {
+                    distance_to_segment(position, Vec2::new(from.x, from.y), Vec2::new(to.x, to.y))
+                })
+                .min_by(|a, b| a.total_cmp(b))
+                .map(|distance| (entity, distance))
+        })
+        .filter(|(_, distance)| *distance < ARROW_HIT_DISTANCE * theme.hit_target_scale)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+/// Double click on an arrow edits its label (adding one to arrows without it), click on a label
+/// edits it and click elsewhere on the canvas ends editing.
+pub fn edit_arrow_label_on_click(
+    mut commands: Commands,
+    canvas_interactions: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    label_interactions: Query<(&Interaction, &ArrowLabelView), Changed<Interaction>>,
+    arrows: Query<(Entity, &Path, &Visibility), With<ArrowMeta>>,
+    labels: Query<&ArrowLabel>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    theme: Res<Theme>,
+    mut ui_state: ResMut<UiState>,
+    mut double_click: Local<(Duration, Option<Vec2>)>,
+) {
+    for (interaction, view) in label_interactions.iter() {
+        if *interaction == Interaction::Clicked {
+            ui_state.editing_node = false;
+            ui_state.arrow_label_to_edit = Some(view.arrow);
+        }
+    }
+    if !canvas_interactions
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    ui_state.arrow_label_to_edit = None;
+    let primary_window = windows.single();
+    let Some(cursor) = primary_window.cursor_position() else {
+        return;
+    };
+    if !is_double_click(&mut double_click, cursor) {
+        return;
+    }
+    let (camera, camera_transform) = camera_q.single();
+    let Some(arrow) = arrow_at_cursor(primary_window, camera, camera_transform, &arrows, &theme)
+    else {
+        return;
+    };
+    if labels.get(arrow).is_err() {
+        commands.entity(arrow).insert(ArrowLabel::default());
+    }
+    ui_state.arrow_label_to_edit = Some(arrow);
+}
+
+fn spawn_label_view(
+    commands: &mut Commands,
+    font_system_state: &mut FontSystemState,
+    arrow: Entity,
+    text: &str,
+    scale_factor: f32,
+) -> Entity {
+    let size = Vec2::new(LABEL_WIDTH, LABEL_HEIGHT);
+    let view = commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0., 0., 0., 0.),
+                    custom_size: Some(size),
+                    ..default()
+                },
+                ..default()
+            },
+            Interaction::default(),
+            ArrowLabelView { arrow },
+        ))
+        .id();
+    let cosmic_edit_meta = CosmicEditMeta {
+        text: text.to_string(),
+        text_pos: CosmicTextPos::Center,
+        initial_size: Some((LABEL_WIDTH, LABEL_HEIGHT)),
+        initial_background: None,
+        font_size: 14.,
+        line_height: 18.,
+        attrs: Attrs::new(),
+        scale_factor,
+        font_system: font_system_state.font_system.as_mut().unwrap(),
+        is_visible: true,
+        paste: true,
+        mask_char: None,
+    };
+    let cosmic_edit = spawn_cosmic_edit_sprite(commands, cosmic_edit_meta, size, size);
+    commands.entity(cosmic_edit).insert((
+        ArrowLabelEditor { arrow },
+        Transform::from_xyz(0., 0., 0.01),
+    ));
+    commands.entity(view).add_child(cosmic_edit);
+    view
+}
+
+/// Spawns views of new labels, keeps them centered on the middle of their arrows (and hidden
+/// with them) and despawns views of removed labels.
+pub fn update_arrow_label_views(
+    mut commands: Commands,
+    arrows: Query<(Entity, &ArrowLabel, &ArrowAnchors, &Visibility), With<ArrowMeta>>,
+    mut views: Query<
+        (Entity, &ArrowLabelView, &mut Transform, &mut Visibility),
+        Without<ArrowMeta>,
+    >,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut font_system_state: ResMut<FontSystemState>,
+) {
+    let primary_window = windows.single();
+    let mut arrows_with_view = HashSet::new();
+    for (entity, view, mut transform, mut visibility) in views.iter_mut() {
+        let Ok((_, _, anchors, arrow_visibility)) = arrows.get(view.arrow) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        arrows_with_view.insert(view.arrow);
+        if *visibility != *arrow_visibility {
+            *visibility = *arrow_visibility;
+        }
+        let translation = anchors.midpoint.extend(LABEL_Z);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+    for (arrow, label, _, _) in arrows.iter() {
+        if arrows_with_view.contains(&arrow) {
+            continue;
+        }
+        spawn_label_view(
+            &mut commands,
+            &mut font_system_state,
+            arrow,
+            &label.0,
+            primary_window.scale_factor() as f32,
+        );
+    }
+}
+
+/// Routes typing to the edited label and keeps the label of the arrow in sync with it, labels
+/// left empty are removed when editing ends.
+pub fn arrow_label_editing(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    active_editor: Res<ActiveEditor>,
+    editors: Query<(Entity, &ArrowLabelEditor, &CosmicEditImage)>,
+    mut labels: Query<&mut ArrowLabel>,
+    mut edited: Local<Option<Entity>>,
+) {
+    if ui_state.editing_node || input.just_pressed(KeyCode::Escape) {
+        ui_state.arrow_label_to_edit = None;
+    }
+    if *edited != ui_state.arrow_label_to_edit {
+        if let Some(arrow) = *edited {
+            if labels
+                .get(arrow)
+                .map_or(false, |label| label.0.trim().is_empty())
+            {
+                commands.entity(arrow).remove::<ArrowLabel>();
+            }
+            // editing a node activates its own editor
+            if !ui_state.editing_node {
+                commands.insert_resource(ActiveEditor { entity: None });
+            }
+        }
+        *edited = ui_state.arrow_label_to_edit;
+    }
+    let Some(arrow) = ui_state.arrow_label_to_edit else {
+        return;
+    };
+    for (entity, editor, cosmic_edit) in editors.iter() {
+        if editor.arrow != arrow {
+            continue;
+        }
+        if active_editor.entity != Some(entity) {
+            commands.insert_resource(ActiveEditor {
+                entity: Some(entity),
+            });
+        }
+        let text = get_cosmic_text(&cosmic_edit.editor);
+        if let Ok(mut label) = labels.get_mut(arrow) {
+            if label.0 != text {
+                label.0 = text;
+            }
+        }
+    }
+}
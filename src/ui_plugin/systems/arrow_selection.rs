@@ -0,0 +1,395 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_prototype_lyon::{
+    prelude::{Fill, GeometryBuilder, Path, ShapeBundle, Stroke},
+    shapes,
+};
+
+use crate::canvas::arrow::components::{
+    ArrowAnchors, ArrowConnect, ArrowEnd, ArrowEndHandle, ArrowMeta, ArrowPins, ArrowStyle,
+    ArrowWaypointHandle, ArrowWaypoints,
+};
+use crate::canvas::arrow::events::RedrawArrowEvent;
+use crate::components::MainCamera;
+
+use super::{
+    arrow_at_cursor, distance_to_segment, remove_shape, ui_helpers::MainPanel, CanvasZoom,
+    SelectedArrow, Theme, Tool, UiState,
+};
+
+const HANDLE_RADIUS: f32 = 5.;
+// Dropped end is attached to the closest connection point within that distance (screen pixels)
+const SNAP_DISTANCE: f32 = 24.;
+// Pressed line has to be dragged that far (screen pixels) to add a waypoint, shorter drags are
+// clicks
+const WAYPOINT_DRAG_DISTANCE: f32 = 4.;
+const SELECTED_ARROW_COLOR: Color = Color::rgb(33.0 / 255.0, 150.0 / 255.0, 243.0 / 255.0);
+
+fn cursor_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera_q: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera_q.get_single().ok()?;
+    let cursor = windows.single().cursor_position()?;
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+/// Index the waypoint added at `position` gets, so that it lands between the waypoints (or
+/// arrow ends) of the closest part of the line.
+pub fn waypoint_insert_index(start: Vec2, waypoints: &[Vec2], end: Vec2, position: Vec2) -> usize {
+    let mut points = vec![start];
+    points.extend_from_slice(waypoints);
+    points.push(end);
+    points
+        .windows(2)
+        .map(|pair| distance_to_segment(position, pair[0], pair[1]))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Click on an arrow selects it, click on an end handle of the selected arrow starts dragging
+/// that end and click on a waypoint handle starts dragging the waypoint (Alt+Click removes it).
+pub fn select_arrow(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    input: Res<Input<KeyCode>>,
+    tool: Res<Tool>,
+    arrows: Query<(Entity, &Path, &Visibility), With<ArrowMeta>>,
+    mut arrow_points: Query<(&ArrowMeta, &ArrowAnchors, &mut ArrowWaypoints)>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    theme: Res<Theme>,
+    zoom: Res<CanvasZoom>,
+    mut selected_arrow: ResMut<SelectedArrow>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
+) {
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked || *tool != Tool::Select || input.pressed(KeyCode::Space) {
+        return;
+    }
+    let Some(cursor) = cursor_position(&windows, &camera_q) else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+    if let Some((arrow, anchors, mut waypoints)) = selected_arrow
+        .entity
+        .and_then(|entity| arrow_points.get_mut(entity).ok())
+    {
+        // handles are small, clicks next to them still grab them
+        let grab_radius = HANDLE_RADIUS * 2. * theme.hit_target_scale / zoom.0;
+        let dragged_end = if cursor.distance(anchors.start_handle) <= grab_radius {
+            Some(ArrowEnd::Start)
+        } else if cursor.distance(anchors.end_handle) <= grab_radius {
+            Some(ArrowEnd::End)
+        } else {
+            None
+        };
+        if dragged_end.is_some() {
+            selected_arrow.dragged_end = dragged_end;
+            return;
+        }
+        if let Some(index) = waypoints
+            .0
+            .iter()
+            .position(|position| cursor.distance(*position) <= grab_radius)
+        {
+            if input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+                waypoints.0.remove(index);
+                redraw_arrow.send(RedrawArrowEvent { id: arrow.start.id });
+            } else {
+                selected_arrow.dragged_waypoint = Some(index);
+            }
+            return;
+        }
+    }
+    let hit = arrow_at_cursor(windows.single(), camera, camera_transform, &arrows, &theme);
+    if hit.is_some() && selected_arrow.entity == hit {
+        selected_arrow.line_pressed_at = Some(cursor);
+    }
+    if selected_arrow.entity != hit {
+        selected_arrow.entity = hit;
+    }
+}
+
+/// Dragging the pressed line of the selected arrow adds a waypoint to the closest part of the
+/// line and drags it further, the dragged waypoint follows the cursor until the button is
+/// released.
+pub fn drag_arrow_waypoint(
+    buttons: Res<Input<MouseButton>>,
+    mut selected_arrow: ResMut<SelectedArrow>,
+    mut arrows: Query<(&ArrowMeta, &ArrowAnchors, &mut ArrowWaypoints)>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    zoom: Res<CanvasZoom>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
+) {
+    if selected_arrow.dragged_waypoint.is_none() && selected_arrow.line_pressed_at.is_none() {
+        return;
+    }
+    if !buttons.pressed(MouseButton::Left) {
+        selected_arrow.dragged_waypoint = None;
+        selected_arrow.line_pressed_at = None;
+        return;
+    }
+    let Some((arrow, anchors, mut waypoints)) = selected_arrow
+        .entity
+        .and_then(|entity| arrows.get_mut(entity).ok())
+    else {
+        return;
+    };
+    let Some(position) = cursor_position(&windows, &camera_q) else {
+        return;
+    };
+    if let Some(pressed_at) = selected_arrow.line_pressed_at {
+        if pressed_at.distance(position) < WAYPOINT_DRAG_DISTANCE / zoom.0 {
+            return;
+        }
+        let index = waypoint_insert_index(
+            anchors.start_handle,
+            &waypoints.0,
+            anchors.end_handle,
+            pressed_at,
+        );
+        waypoints.0.insert(index, position);
+        selected_arrow.line_pressed_at = None;
+        selected_arrow.dragged_waypoint = Some(index);
+        redraw_arrow.send(RedrawArrowEvent { id: arrow.start.id });
+        return;
+    }
+    let Some(waypoint) = selected_arrow
+        .dragged_waypoint
+        .and_then(|index| waypoints.0.get_mut(index))
+    else {
+        return;
+    };
+    if *waypoint != position {
+        *waypoint = position;
+        redraw_arrow.send(RedrawArrowEvent { id: arrow.start.id });
+    }
+}
+
+pub fn delete_selected_arrow(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    mut arrows: Query<&mut Visibility, With<ArrowMeta>>,
+    mut selected_arrow: ResMut<SelectedArrow>,
+) {
+    if !ui_state.is_canvas_focused() || !input.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+        return;
+    }
+    let Some(entity) = selected_arrow.entity else {
+        return;
+    };
+    if let Ok(mut visibility) = arrows.get_mut(entity) {
+        remove_shape(&mut commands, entity, &mut visibility);
+    }
+    *selected_arrow = SelectedArrow::default();
+}
+
+/// Dropping the dragged end attaches it to the closest connection point of another node than
+/// the one at the other end and pins it to that side, dropping it elsewhere unpins it so the
+/// side is picked again whenever the arrow is redrawn.
+pub fn drop_arrow_end(
+    buttons: Res<Input<MouseButton>>,
+    mut selected_arrow: ResMut<SelectedArrow>,
+    mut arrows: Query<(&mut ArrowMeta, &mut ArrowPins)>,
+    markers: Query<(&ArrowConnect, &GlobalTransform, &ComputedVisibility)>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    zoom: Res<CanvasZoom>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
+) {
+    let Some(dragged_end) = selected_arrow.dragged_end else {
+        return;
+    };
+    if buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    selected_arrow.dragged_end = None;
+    let Some((mut arrow, mut pins)) = selected_arrow
+        .entity
+        .and_then(|entity| arrows.get_mut(entity).ok())
+    else {
+        return;
+    };
+    let Some(cursor) = cursor_position(&windows, &camera_q) else {
+        return;
+    };
+    let other_end = match dragged_end {
+        ArrowEnd::Start => arrow.end,
+        ArrowEnd::End => arrow.start,
+    };
+    let target = markers
+        .iter()
+        .filter(|(marker, _, visibility)| visibility.is_visible() && marker.id != other_end.id)
+        .map(|(marker, global_transform, _)| {
+            let position = global_transform.translation().truncate();
+            (*marker, position.distance(cursor))
+        })
+        .filter(|(_, distance)| *distance <= SNAP_DISTANCE / zoom.0)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(marker, _)| marker);
+    let pinned = match dragged_end {
+        ArrowEnd::Start => &mut pins.start,
+        ArrowEnd::End => &mut pins.end,
+    };
+    *pinned = target.is_some();
+    if let Some(target) = target {
+        match dragged_end {
+            ArrowEnd::Start => arrow.start = target,
+            ArrowEnd::End => arrow.end = target,
+        }
+    }
+    redraw_arrow.send(RedrawArrowEvent { id: arrow.start.id });
+}
+
+fn spawn_handle(commands: &mut Commands, handle: impl Component) {
+    commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Circle {
+                radius: HANDLE_RADIUS,
+                center: Vec2::ZERO,
+            }),
+            // hidden until placed on the arrow
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Fill::color(Color::WHITE),
+        Stroke::new(SELECTED_ARROW_COLOR, 1.5),
+        handle,
+    ));
+}
+
+/// Highlights the selected arrow and keeps its end handles on it, the dragged handle follows
+/// the cursor.
+pub fn update_arrow_selection(
+    mut commands: Commands,
+    mut selected_arrow: ResMut<SelectedArrow>,
+    mut arrows: Query<(&ArrowStyle, &ArrowAnchors, &Visibility, &mut Stroke), With<ArrowMeta>>,
+    mut handles: Query<
+        (Entity, &ArrowEndHandle, &mut Transform, &mut Visibility),
+        Without<ArrowMeta>,
+    >,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut highlighted: Local<Option<Entity>>,
+) {
+    // e.g. the arrow got deleted or hidden with a collapsed branch
+    if let Some(entity) = selected_arrow.entity {
+        if arrows.get(entity).map_or(true, |(_, _, visibility, _)| {
+            *visibility == Visibility::Hidden
+        }) {
+            *selected_arrow = SelectedArrow::default();
+        }
+    }
+    if *highlighted != selected_arrow.entity {
+        if let Some(Ok((style, _, _, mut stroke))) =
+            highlighted.map(|entity| arrows.get_mut(entity))
+        {
+            stroke.color = style.color;
+            stroke.options.line_width = style.thickness;
+        }
+        for (entity, ..) in handles.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if selected_arrow.entity.is_some() {
+            for end in [ArrowEnd::Start, ArrowEnd::End] {
+                spawn_handle(&mut commands, ArrowEndHandle { end });
+            }
+        }
+        *highlighted = selected_arrow.entity;
+    }
+    let Some(Ok((style, anchors, _, mut stroke))) =
+        selected_arrow.entity.map(|entity| arrows.get_mut(entity))
+    else {
+        return;
+    };
+    // restyling the arrow resets its stroke
+    if stroke.color != SELECTED_ARROW_COLOR {
+        stroke.color = SELECTED_ARROW_COLOR;
+        stroke.options.line_width = style.thickness + 1.;
+    }
+    let cursor = cursor_position(&windows, &camera_q);
+    for (_, handle, mut transform, mut visibility) in handles.iter_mut() {
+        let position = match (handle.end, cursor) {
+            (end, Some(cursor)) if selected_arrow.dragged_end == Some(end) => cursor,
+            (ArrowEnd::Start, _) => anchors.start_handle,
+            (ArrowEnd::End, _) => anchors.end_handle,
+        };
+        // above the arrows
+        let translation = position.extend(1.);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+        if *visibility == Visibility::Hidden {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+/// Keeps a handle on every waypoint of the selected arrow.
+pub fn update_waypoint_handles(
+    mut commands: Commands,
+    selected_arrow: Res<SelectedArrow>,
+    arrows: Query<&ArrowWaypoints>,
+    mut handles: Query<(
+        Entity,
+        &ArrowWaypointHandle,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    let positions = selected_arrow
+        .entity
+        .and_then(|entity| arrows.get(entity).ok())
+        .map_or(vec![], |waypoints| waypoints.0.clone());
+    let mut placed = vec![false; positions.len()];
+    for (entity, handle, mut transform, mut visibility) in handles.iter_mut() {
+        let Some(position) = positions.get(handle.index) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        placed[handle.index] = true;
+        // above the arrows
+        let translation = position.extend(1.);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+        if *visibility == Visibility::Hidden {
+            *visibility = Visibility::Inherited;
+        }
+    }
+    for (index, _) in placed.iter().enumerate().filter(|(_, placed)| !**placed) {
+        spawn_handle(&mut commands, ArrowWaypointHandle { index });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waypoint_insert_index() {
+        let start = Vec2::ZERO;
+        let end = Vec2::new(100., 0.);
+        assert_eq!(
+            waypoint_insert_index(start, &[], end, Vec2::new(50., 5.)),
+            0
+        );
+        let waypoints = [Vec2::new(30., 40.), Vec2::new(70., 40.)];
+        assert_eq!(
+            waypoint_insert_index(start, &waypoints, end, Vec2::new(10., 15.)),
+            0
+        );
+        assert_eq!(
+            waypoint_insert_index(start, &waypoints, end, Vec2::new(50., 42.)),
+            1
+        );
+        assert_eq!(
+            waypoint_insert_index(start, &waypoints, end, Vec2::new(90., 15.)),
+            2
+        );
+    }
+}
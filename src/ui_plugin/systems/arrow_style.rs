@@ -0,0 +1,202 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::FontSystemState;
+use bevy_prototype_lyon::prelude::{Fill, Stroke};
+
+use crate::canvas::arrow::components::{
+    ArrowAnimation, ArrowDash, ArrowHead, ArrowMeta, ArrowRelation, ArrowRouting, ArrowStyle,
+    ARROW_RELATIONS,
+};
+use crate::canvas::arrow::events::RedrawArrowEvent;
+use crate::utils::{next_in_cycle, ReflectableUuid};
+
+use super::{
+    ui_helpers::{spawn_modal, ArrowStyleChange, ChangeArrowStyle, MainPanel},
+    ModalAction, SelectedNodes, SetArrowRelationEvent, UiState,
+};
+
+const ARROW_HEADS: [ArrowHead; 5] = [
+    ArrowHead::None,
+    ArrowHead::Filled,
+    ArrowHead::Open,
+    ArrowHead::Diamond,
+    ArrowHead::Circle,
+];
+const ARROW_THICKNESSES: [f32; 4] = [1., 1.5, 2.5, 4.];
+const ARROW_DASHES: [ArrowDash; 3] = [ArrowDash::Solid, ArrowDash::Dashed, ArrowDash::Dotted];
+const ARROW_ROUTINGS: [ArrowRouting; 3] = [
+    ArrowRouting::Straight,
+    ArrowRouting::Curved,
+    ArrowRouting::Elbow,
+];
+const ARROW_ANIMATIONS: [ArrowAnimation; 3] = [
+    ArrowAnimation::None,
+    ArrowAnimation::MarchingDashes,
+    ArrowAnimation::MovingDot,
+];
+const ARROW_COLORS: [Color; 5] = [
+    Color::rgb(63.0 / 255.0, 81.0 / 255.0, 181.0 / 255.0),
+    Color::BLACK,
+    Color::GRAY,
+    Color::rgb(229.0 / 255.0, 57.0 / 255.0, 53.0 / 255.0),
+    Color::rgb(67.0 / 255.0, 160.0 / 255.0, 71.0 / 255.0),
+];
+
+/// Cycles the property of the style of new arrows, arrows between selected nodes take it too.
+pub fn change_arrow_style(
+    mut interaction_query: Query<
+        (&Interaction, &ChangeArrowStyle),
+        (Changed<Interaction>, With<ChangeArrowStyle>),
+    >,
+    mut arrows: Query<(&ArrowMeta, &mut ArrowStyle, &mut Stroke, &mut Fill)>,
+    mut state: ResMut<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
+) {
+    for (interaction, change_arrow_style) in &mut interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let change = change_arrow_style.change;
+        let style = &mut state.arrow_style;
+        match change {
+            ArrowStyleChange::StartHead => {
+                style.start_head = next_in_cycle(&ARROW_HEADS, style.start_head)
+            }
+            ArrowStyleChange::EndHead => {
+                style.end_head = next_in_cycle(&ARROW_HEADS, style.end_head)
+            }
+            ArrowStyleChange::Thickness => {
+                style.thickness = next_in_cycle(&ARROW_THICKNESSES, style.thickness)
+            }
+            ArrowStyleChange::Dash => style.dash = next_in_cycle(&ARROW_DASHES, style.dash),
+            ArrowStyleChange::Color => style.color = next_in_cycle(&ARROW_COLORS, style.color),
+            ArrowStyleChange::Routing => {
+                style.routing = next_in_cycle(&ARROW_ROUTINGS, style.routing)
+            }
+            ArrowStyleChange::Animation => {
+                style.animation = next_in_cycle(&ARROW_ANIMATIONS, style.animation)
+            }
+            // relationship types come with their own style
+            ArrowStyleChange::Relation => continue,
+        }
+        let style = state.arrow_style;
+        for (arrow_meta, mut arrow_style, mut stroke, mut fill) in arrows.iter_mut() {
+            if !selected_nodes.0.contains(&arrow_meta.start.id)
+                || !selected_nodes.0.contains(&arrow_meta.end.id)
+            {
+                continue;
+            }
+            match change {
+                ArrowStyleChange::StartHead => arrow_style.start_head = style.start_head,
+                ArrowStyleChange::EndHead => arrow_style.end_head = style.end_head,
+                ArrowStyleChange::Thickness => arrow_style.thickness = style.thickness,
+                ArrowStyleChange::Dash => arrow_style.dash = style.dash,
+                ArrowStyleChange::Color => arrow_style.color = style.color,
+                ArrowStyleChange::Routing => arrow_style.routing = style.routing,
+                ArrowStyleChange::Animation => arrow_style.animation = style.animation,
+                ArrowStyleChange::Relation => {}
+            }
+            stroke.color = arrow_style.color;
+            stroke.options.line_width = arrow_style.thickness;
+            fill.color = arrow_style.color;
+            // heads, dashes and route are part of the path
+            redraw_arrow.send(RedrawArrowEvent {
+                id: arrow_meta.start.id,
+            });
+        }
+    }
+}
+
+/// Cycles the relationship type of new arrows through no type, the offered types and custom
+/// types of arrows on the canvas. Shift+click asks for a custom type.
+pub fn change_arrow_relation(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &ChangeArrowStyle), Changed<Interaction>>,
+    input: Res<Input<KeyCode>>,
+    relations: Query<&ArrowRelation>,
+    mut state: ResMut<UiState>,
+    mut events: EventWriter<SetArrowRelationEvent>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut font_system_state: ResMut<FontSystemState>,
+) {
+    for (interaction, change_arrow_style) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked
+            || !matches!(change_arrow_style.change, ArrowStyleChange::Relation)
+        {
+            continue;
+        }
+        if input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+            let id = ReflectableUuid::generate();
+            state.editing_node = false;
+            state.modal_id = Some(id);
+            commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+            let entity = spawn_modal(
+                &mut commands,
+                &mut font_system_state,
+                windows.single(),
+                id,
+                ModalAction::ArrowRelation,
+            );
+            commands.entity(main_panel_query.single()).add_child(entity);
+            continue;
+        }
+        let mut cycle: Vec<Option<&str>> = vec![None];
+        cycle.extend(ARROW_RELATIONS.iter().map(|relation| Some(*relation)));
+        let mut custom: Vec<&str> = relations
+            .iter()
+            .map(|relation| relation.0.as_str())
+            .filter(|relation| !ARROW_RELATIONS.contains(relation))
+            .collect();
+        custom.sort();
+        custom.dedup();
+        cycle.extend(custom.into_iter().map(Some));
+        events.send(SetArrowRelationEvent {
+            relation: next_in_cycle(&cycle, state.arrow_relation.as_deref()).map(str::to_string),
+        });
+    }
+}
+
+/// Applies the relationship type (and its default style) to new arrows and to arrows between
+/// selected nodes.
+pub fn set_arrow_relation(
+    mut commands: Commands,
+    mut events: EventReader<SetArrowRelationEvent>,
+    mut arrows: Query<(Entity, &ArrowMeta, &mut ArrowStyle, &mut Stroke, &mut Fill)>,
+    mut state: ResMut<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
+) {
+    for event in events.iter() {
+        let relation = event
+            .relation
+            .as_deref()
+            .map(str::trim)
+            .filter(|relation| !relation.is_empty());
+        state.arrow_relation = relation.map(str::to_string);
+        if let Some(relation) = relation {
+            state.arrow_style = state.arrow_style.with_relation(relation);
+        }
+        for (entity, arrow_meta, mut arrow_style, mut stroke, mut fill) in arrows.iter_mut() {
+            if !selected_nodes.0.contains(&arrow_meta.start.id)
+                || !selected_nodes.0.contains(&arrow_meta.end.id)
+            {
+                continue;
+            }
+            let Some(relation) = relation else {
+                commands.entity(entity).remove::<ArrowRelation>();
+                continue;
+            };
+            commands
+                .entity(entity)
+                .insert(ArrowRelation(relation.to_string()));
+            *arrow_style = arrow_style.with_relation(relation);
+            stroke.color = arrow_style.color;
+            stroke.options.line_width = arrow_style.thickness;
+            fill.color = arrow_style.color;
+            redraw_arrow.send(RedrawArrowEvent {
+                id: arrow_meta.start.id,
+            });
+        }
+    }
+}
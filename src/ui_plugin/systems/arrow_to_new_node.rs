@@ -0,0 +1,135 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowPins};
+use crate::canvas::arrow::events::CreateArrowEvent;
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
+use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeType, TextPos};
+
+use super::{ui_helpers::MainPanel, NodeIndex, Theme, UiState, VeloNodeContainer};
+
+const NEW_NODE_SIZE: f32 = 128.;
+// Shorter drags are clicks starting an arrow to another marker
+const MIN_DRAG_DISTANCE: f32 = 10.;
+
+fn opposite(pos: ArrowConnectPos) -> ArrowConnectPos {
+    match pos {
+        ArrowConnectPos::Top => ArrowConnectPos::Bottom,
+        ArrowConnectPos::Bottom => ArrowConnectPos::Top,
+        ArrowConnectPos::Left => ArrowConnectPos::Right,
+        ArrowConnectPos::Right => ArrowConnectPos::Left,
+    }
+}
+
+/// Dragging from an arrow connection marker and releasing over empty canvas creates a node
+/// centered at the drop point, connects the arrow to it and starts editing its text.
+pub fn connect_to_new_node(
+    buttons: Res<Input<MouseButton>>,
+    mut ui_state: ResMut<UiState>,
+    markers: Query<(&Interaction, &ArrowConnect)>,
+    containers: Query<&Visibility, With<VeloNodeContainer>>,
+    node_index: Res<NodeIndex>,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut add_rect: EventWriter<AddRectEvent>,
+    mut create_arrow: EventWriter<CreateArrowEvent>,
+    theme: Res<Theme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut drag_start: Local<Option<Vec2>>,
+    mut pending_arrow: Local<Option<CreateArrowEvent>>,
+) {
+    // the arrow is created once markers of the new node are laid out
+    if let Some(event) = pending_arrow.as_ref() {
+        if markers.iter().any(|(_, marker)| *marker == event.end) {
+            create_arrow.send(event.clone());
+            *pending_arrow = None;
+        }
+    }
+    let Some(start) = ui_state.arrow_to_draw_start else {
+        *drag_start = None;
+        return;
+    };
+    let primary_window = windows.single();
+    let Some(cursor) = primary_window.cursor_position() else {
+        return;
+    };
+    let Some(drag_start_pos) = *drag_start else {
+        // the marker stays clicked while the mouse button is held
+        if markers
+            .iter()
+            .any(|(interaction, marker)| *interaction == Interaction::Clicked && *marker == start)
+        {
+            *drag_start = Some(cursor);
+        }
+        return;
+    };
+    if buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    *drag_start = None;
+    if cursor.distance(drag_start_pos) < MIN_DRAG_DISTANCE {
+        return;
+    }
+    let Ok((panel, panel_transform)) = main_panel_query.get_single() else {
+        return;
+    };
+    // ui coordinates have y axis pointing down
+    let ui_cursor = Vec2::new(cursor.x, primary_window.height() - cursor.y);
+    if !panel.logical_rect(panel_transform).contains(ui_cursor) {
+        return;
+    }
+    let (camera, camera_transform) = camera_q.single();
+    let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+    let over_node = node_index.at(point).iter().any(|(entity, _)| {
+        containers
+            .get(*entity)
+            .map_or(false, |visibility| *visibility != Visibility::Hidden)
+    });
+    if over_node {
+        return;
+    }
+    let id = Uuid::new_v4();
+    *pending_arrow = Some(CreateArrowEvent {
+        start,
+        end: ArrowConnect {
+            id: ReflectableUuid(id),
+            pos: opposite(start.pos),
+        },
+        arrow_type: ui_state.arrow_type,
+        style: theme.arrow_style(ui_state.arrow_style),
+        label: None,
+        relation: ui_state.arrow_relation.clone(),
+        pins: ArrowPins::default(),
+        waypoints: vec![],
+    });
+    ui_state.arrow_to_draw_start = None;
+    let half_size = NEW_NODE_SIZE / 2.;
+    add_rect.send(AddRectEvent {
+        node: JsonNode {
+            id,
+            node_type: NodeType::Rect,
+            left: Val::Px(point.x - half_size),
+            bottom: Val::Px(point.y - half_size),
+            width: Val::Px(NEW_NODE_SIZE),
+            height: Val::Px(NEW_NODE_SIZE),
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::Center,
+                style: theme.node_text_style(),
+            },
+            bg_color: theme.node_background,
+            z_index: 0,
+            locked: false,
+            border: theme.node_border(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: None,
+    });
+}
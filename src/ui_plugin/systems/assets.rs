@@ -0,0 +1,130 @@
+use base64::{engine::general_purpose, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::components::Doc;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resources::AppState;
+
+pub const ASSET_LINK_PREFIX: &str = "velo://asset/";
+// Bigger files stay attached by their path to keep documents small enough to load quickly
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_EMBEDDED_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Content address of embedded file data.
+pub fn asset_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Stores the file data in the document (once for identical files) and returns the link
+/// attachments use to refer to it: `velo://asset/<hash>/<file name>`.
+pub fn embed_asset(doc: &mut Doc, name: &str, bytes: &[u8]) -> String {
+    let hash = asset_hash(bytes);
+    doc.assets
+        .entry(hash.clone())
+        .or_insert_with(|| general_purpose::STANDARD.encode(bytes));
+    format!("{}{}/{}", ASSET_LINK_PREFIX, hash, name)
+}
+
+/// Hash and file name of an embedded asset link.
+pub fn parse_asset_link(link: &str) -> Option<(&str, &str)> {
+    link.strip_prefix(ASSET_LINK_PREFIX)?
+        .split_once('/')
+        .filter(|(hash, name)| !hash.is_empty() && !name.is_empty())
+}
+
+/// Drops embedded files none of the tab checkpoints refer to anymore.
+pub fn prune_assets(doc: &mut Doc) {
+    let tabs = &doc.tabs;
+    doc.assets.retain(|hash, _| {
+        tabs.iter()
+            .flat_map(|tab| tab.checkpoint.iter())
+            .any(|checkpoint| checkpoint.contains(hash.as_str()))
+    });
+}
+
+/// Embeds the attached file into the current document, URLs, missing files and big files are
+/// attached as they are.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn embed_attachment(app_state: &mut AppState, attachment: String) -> String {
+    let path = std::path::Path::new(&attachment);
+    let is_small_file = std::fs::metadata(path).map_or(false, |metadata| {
+        metadata.is_file() && metadata.len() <= MAX_EMBEDDED_SIZE
+    });
+    let (Some(name), true) = (path.file_name(), is_small_file) else {
+        return attachment;
+    };
+    let Some(doc) = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get_mut(&doc_id))
+    else {
+        return attachment;
+    };
+    match std::fs::read(path) {
+        Ok(bytes) => embed_asset(doc, &name.to_string_lossy(), &bytes),
+        Err(_) => attachment,
+    }
+}
+
+/// Writes the embedded file to the temporary directory, so it can be opened by other
+/// applications, and returns its path.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn extract_asset(doc: &Doc, hash: &str, name: &str) -> std::io::Result<std::path::PathBuf> {
+    let data = doc
+        .assets
+        .get(hash)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "missing asset"))?;
+    let dir = std::env::temp_dir().join("velo-assets").join(hash);
+    // names come from the document, they must not escape the directory
+    let path = dir.join(std::path::Path::new(name).file_name().unwrap_or_default());
+    if !path.exists() {
+        let bytes = general_purpose::STANDARD
+            .decode(data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, bytes)?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Tab;
+    use crate::utils::ReflectableUuid;
+
+    #[test]
+    fn test_embed_asset() {
+        let mut doc = Doc::default();
+        let link = embed_asset(&mut doc, "notes.txt", b"hello");
+        let same = embed_asset(&mut doc, "copy.txt", b"hello");
+        assert_eq!(doc.assets.len(), 1);
+        let (hash, name) = parse_asset_link(&link).unwrap();
+        assert_eq!(hash, asset_hash(b"hello"));
+        assert_eq!(name, "notes.txt");
+        assert_eq!(parse_asset_link(&same).unwrap().1, "copy.txt");
+        assert_eq!(parse_asset_link("velo://asset/abc"), None);
+        let path = extract_asset(&doc, hash, "../notes.txt").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+        assert_eq!(path.file_name().unwrap(), "notes.txt");
+
+        doc.tabs.push(Tab {
+            id: ReflectableUuid::generate(),
+            name: "Tab 1".to_string(),
+            checkpoint: Some(format!("{{\"attachments\":[\"{}\"]}}", link)),
+            zoom: 1.,
+            color: None,
+            icon: None,
+            pinned: false,
+            offset: [0., 0.],
+            selected: vec![],
+            is_active: true,
+        });
+        embed_asset(&mut doc, "other.txt", b"other");
+        prune_assets(&mut doc);
+        assert_eq!(doc.assets.len(), 1);
+        assert!(doc.assets.contains_key(hash));
+    }
+}
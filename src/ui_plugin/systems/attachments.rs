@@ -0,0 +1,259 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::{embed_attachment, ContextMenuAction, ContextMenuEvent};
+use super::{
+    open_link,
+    ui_helpers::{AttachmentChip, AttachmentChips, FitToText, NodeAttachments, NodePart, VeloNode},
+    CanvasZoom, Notification, OpenInternalLinkEvent,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resources::AppState;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub const ATTACH_FROM_CLIPBOARD_ACTION: &str = "attach_from_clipboard";
+// Longer chip labels are cut with an ellipsis
+const MAX_LABEL_LENGTH: usize = 20;
+// Chips are inset in the node and spaced by their margin, in screen pixels
+const CHIPS_INSET: f32 = 4.;
+const CHIP_MARGIN: f32 = 2.;
+// Chips are drawn over the content of the node, below dims of the tag filter
+const CHIPS_Z: f32 = 0.4;
+
+/// Short label of an attachment chip: file name of a path or last segment of a URL.
+pub fn attachment_label(attachment: &str) -> String {
+    let trimmed = attachment.trim_end_matches(['/', '\\']);
+    let name = trimmed
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(trimmed);
+    if name.chars().count() > MAX_LABEL_LENGTH {
+        let name: String = name.chars().take(MAX_LABEL_LENGTH - 1).collect();
+        format!("{}…", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Adds the attachment to the node unless it's attached already.
+pub fn add_attachment(attachments: &mut NodeAttachments, attachment: String) {
+    if !attachment.is_empty() && !attachments.0.contains(&attachment) {
+        attachments.0.push(attachment);
+    }
+}
+
+/// Respawns the chips of nodes whose attachments changed (including newly spawned nodes).
+pub fn update_attachment_chips(
+    mut commands: Commands,
+    nodes: Query<(Entity, &VeloNode, &NodeAttachments), Changed<NodeAttachments>>,
+    chips: Query<(Entity, &AttachmentChips)>,
+) {
+    for (entity, node, attachments) in nodes.iter() {
+        for (chips_entity, chips) in chips.iter() {
+            if chips.id == node.id {
+                commands.entity(chips_entity).despawn_recursive();
+            }
+        }
+        if attachments.0.is_empty() {
+            continue;
+        }
+        let row = commands
+            .spawn((
+                SpatialBundle::default(),
+                NodePart::handle(
+                    Vec2::new(-0.5, -0.5),
+                    Vec2::splat(CHIPS_INSET),
+                    Vec2::ZERO,
+                    CHIPS_Z,
+                ),
+                AttachmentChips { id: node.id },
+            ))
+            .with_children(|builder| {
+                for attachment in attachments.0.iter() {
+                    builder
+                        .spawn((
+                            SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0),
+                                    anchor: Anchor::BottomLeft,
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            FitToText {
+                                padding: Vec2::new(6., 2.),
+                            },
+                            Interaction::default(),
+                            AttachmentChip {
+                                id: node.id,
+                                attachment: attachment.clone(),
+                            },
+                        ))
+                        .with_children(|builder| {
+                            builder.spawn(Text2dBundle {
+                                text: Text::from_section(
+                                    attachment_label(attachment),
+                                    TextStyle {
+                                        font_size: 12.,
+                                        color: Color::rgb(33.0 / 255.0, 33.0 / 255.0, 33.0 / 255.0),
+                                        ..default()
+                                    },
+                                ),
+                                ..default()
+                            });
+                        });
+                }
+            })
+            .id();
+        commands.entity(entity).add_child(row);
+    }
+}
+
+/// Positions of chips of `sizes` laid out in rows from the left bottom corner, wrapping at
+/// `width`: each row is above the previous one.
+pub fn flow_chips(sizes: &[Vec2], width: f32) -> Vec<Vec2> {
+    let mut positions = Vec::with_capacity(sizes.len());
+    let mut cursor = Vec2::ZERO;
+    let mut row_height: f32 = 0.;
+    for size in sizes {
+        let advance = *size + 2. * CHIP_MARGIN;
+        if cursor.x > 0. && cursor.x + advance.x > width {
+            cursor = Vec2::new(0., cursor.y + row_height);
+            row_height = 0.;
+        }
+        positions.push(cursor + CHIP_MARGIN);
+        cursor.x += advance.x;
+        row_height = row_height.max(advance.y);
+    }
+    positions
+}
+
+/// Wraps the chips of nodes at the node width (chips keep their size on screen).
+pub fn flow_attachment_chips(
+    zoom: Res<CanvasZoom>,
+    rows: Query<(&Parent, &Children), With<AttachmentChips>>,
+    nodes: Query<&Sprite, With<VeloNode>>,
+    mut chips: Query<(&Sprite, &mut Transform), (With<AttachmentChip>, Without<VeloNode>)>,
+) {
+    for (parent, children) in rows.iter() {
+        let Ok(node_sprite) = nodes.get(parent.get()) else {
+            continue;
+        };
+        let width = node_sprite.custom_size.unwrap_or_default().x * zoom.0 - 2. * CHIPS_INSET;
+        let sizes: Vec<Vec2> = children
+            .iter()
+            .filter_map(|chip| chips.get(*chip).ok())
+            .map(|(sprite, _)| sprite.custom_size.unwrap_or_default())
+            .collect();
+        let positions = flow_chips(&sizes, width);
+        let mut chips_iter = chips.iter_many_mut(children);
+        let mut positions = positions.into_iter();
+        while let (Some((_, mut transform)), Some(position)) =
+            (chips_iter.fetch_next(), positions.next())
+        {
+            if transform.translation.truncate() != position {
+                transform.translation = position.extend(0.);
+            }
+        }
+    }
+}
+
+/// Click on an attachment chip opens it with the default application of the OS (URLs in the
+/// browser), Alt+Click removes the attachment.
+pub fn attachment_chip_click(
+    input: Res<Input<KeyCode>>,
+    chips: Query<(&Interaction, &AttachmentChip), Changed<Interaction>>,
+    mut nodes: Query<(&VeloNode, &mut NodeAttachments)>,
+    mut internal_links: EventWriter<OpenInternalLinkEvent>,
+    mut notifications: EventWriter<Notification>,
+) {
+    for (interaction, chip) in chips.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if !input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+            open_link(&chip.attachment, &mut internal_links, &mut notifications);
+            continue;
+        }
+        for (node, mut attachments) in nodes.iter_mut() {
+            if node.id == chip.id {
+                attachments
+                    .0
+                    .retain(|attachment| *attachment != chip.attachment);
+            }
+        }
+    }
+}
+
+/// "Attach from clipboard" of the node context menu attaches the copied URL or embeds the file
+/// of the copied path.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn attach_from_clipboard(
+    mut events: EventReader<ContextMenuEvent>,
+    mut app_state: ResMut<AppState>,
+    mut nodes: Query<(&VeloNode, &mut NodeAttachments)>,
+) {
+    for event in events.iter() {
+        if event.action != ContextMenuAction::Custom(ATTACH_FROM_CLIPBOARD_ACTION) {
+            continue;
+        }
+        let Some(id) = event.node else {
+            continue;
+        };
+        let Ok(text) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text())
+        else {
+            continue;
+        };
+        // a path or URL is a single line
+        let text = text.trim();
+        if text.lines().count() != 1 {
+            continue;
+        }
+        let attachment = embed_attachment(&mut app_state, text.to_string());
+        for (node, mut attachments) in nodes.iter_mut() {
+            if node.id == id {
+                add_attachment(&mut attachments, attachment.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_label() {
+        assert_eq!(attachment_label("/home/user/report.pdf"), "report.pdf");
+        assert_eq!(attachment_label("C:\\Users\\user\\notes.txt"), "notes.txt");
+        assert_eq!(attachment_label("https://example.com/"), "example.com");
+        assert_eq!(
+            attachment_label("a_very_long_file_name_indeed.txt"),
+            "a_very_long_file_na…"
+        );
+    }
+
+    #[test]
+    fn test_add_attachment() {
+        let mut attachments = NodeAttachments::default();
+        add_attachment(&mut attachments, "a.txt".to_string());
+        add_attachment(&mut attachments, "a.txt".to_string());
+        add_attachment(&mut attachments, "".to_string());
+        assert_eq!(attachments.0, vec!["a.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_flow_chips() {
+        let chip = Vec2::new(40., 16.);
+        assert_eq!(
+            flow_chips(&[chip, chip, chip], 100.),
+            vec![Vec2::new(2., 2.), Vec2::new(46., 2.), Vec2::new(2., 22.)]
+        );
+        // a chip wider than the node still gets its own row
+        assert_eq!(
+            flow_chips(&[Vec2::new(200., 16.)], 100.),
+            vec![Vec2::new(2., 2.)]
+        );
+    }
+}
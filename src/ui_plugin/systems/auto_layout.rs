@@ -0,0 +1,585 @@
+use bevy::prelude::*;
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::canvas::arrow::events::RedrawArrowEvent;
+use crate::utils::ReflectableUuid;
+
+use super::{
+    spawn_context_menu,
+    ui_helpers::{
+        ContextMenu, FrameMembers, LayoutAnimation, LayoutMenuButton, MainPanel, NodeRect,
+    },
+    ContextMenuAction, ContextMenuEvent, History, HistoryCommand, Locked, MotionSettings,
+    SelectedNodes, VeloNodeContainer,
+};
+
+// Labels and context menu actions of the layout menu entries
+const LAYOUT_ACTIONS: [(&str, &str); 4] = [
+    ("Tree", "layout_tree"),
+    ("Layered", "layout_layered"),
+    ("Force-directed", "layout_force"),
+    ("Grid", "layout_grid"),
+];
+// Space between laid out nodes
+pub const LAYOUT_GAP: Vec2 = Vec2::new(40., 80.);
+const LAYOUT_ANIMATION_SECONDS: f32 = 0.4;
+const FORCE_ITERATIONS: usize = 300;
+const BARYCENTER_SWEEPS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayoutAlgorithm {
+    Tree,
+    Layered,
+    ForceDirected,
+    Grid,
+}
+
+impl LayoutAlgorithm {
+    fn from_action(action: ContextMenuAction) -> Option<Self> {
+        match action {
+            ContextMenuAction::Custom("layout_tree") => Some(Self::Tree),
+            ContextMenuAction::Custom("layout_layered") => Some(Self::Layered),
+            ContextMenuAction::Custom("layout_force") => Some(Self::ForceDirected),
+            ContextMenuAction::Custom("layout_grid") => Some(Self::Grid),
+            _ => None,
+        }
+    }
+}
+
+/// Centers of nodes of `sizes` laid out with `algorithm`, y axis pointing up.
+///
+/// `edges` holds `(start, end)` node indices of the arrows, `centers` are the current ones.
+pub fn compute_layout(
+    algorithm: LayoutAlgorithm,
+    sizes: &[Vec2],
+    centers: &[Vec2],
+    edges: &[(usize, usize)],
+    gap: Vec2,
+) -> Vec<Vec2> {
+    let edges: Vec<_> = edges.iter().copied().filter(|(a, b)| a != b).collect();
+    match algorithm {
+        LayoutAlgorithm::Tree => tree_layout(sizes, &edges, gap),
+        LayoutAlgorithm::Layered => layered_layout(sizes, &edges, gap),
+        LayoutAlgorithm::ForceDirected => force_directed_layout(sizes, centers, &edges, gap),
+        LayoutAlgorithm::Grid => grid_layout(sizes, centers, gap),
+    }
+}
+
+/// Top of each layer and its height, layers are stacked downwards from 0.
+fn layer_tops(sizes: &[Vec2], layers: &[usize], gap: f32) -> Vec<(f32, f32)> {
+    let count = layers.iter().max().map_or(0, |max| max + 1);
+    let mut heights = vec![0.; count];
+    for (i, layer) in layers.iter().enumerate() {
+        heights[*layer] = f32::max(heights[*layer], sizes[i].y);
+    }
+    let mut top = 0.;
+    heights
+        .into_iter()
+        .map(|height| {
+            let result = (top, height);
+            top -= height + gap;
+            result
+        })
+        .collect()
+}
+
+/// Parents are centered above their children, roots are the nodes without incoming arrows.
+fn tree_layout(sizes: &[Vec2], edges: &[(usize, usize)], gap: Vec2) -> Vec<Vec2> {
+    let n = sizes.len();
+    let mut has_parent = vec![false; n];
+    for (_, end) in edges.iter() {
+        has_parent[*end] = true;
+    }
+    // spanning tree built breadth first, nodes in cycles without roots start their own trees
+    let mut children = vec![vec![]; n];
+    let mut depth = vec![0; n];
+    let mut visited = vec![false; n];
+    let mut roots = vec![];
+    let candidates: Vec<usize> = (0..n).filter(|i| !has_parent[*i]).chain(0..n).collect();
+    for root in candidates {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        roots.push(root);
+        let mut queue = std::collections::VecDeque::from([root]);
+        while let Some(node) = queue.pop_front() {
+            for (start, end) in edges.iter() {
+                if *start == node && !visited[*end] {
+                    visited[*end] = true;
+                    depth[*end] = depth[node] + 1;
+                    children[node].push(*end);
+                    queue.push_back(*end);
+                }
+            }
+        }
+    }
+    let mut x = vec![0.; n];
+    let mut cursor = 0.;
+    for root in roots {
+        place_subtree(root, &children, sizes, gap.x, &mut cursor, &mut x);
+    }
+    let tops = layer_tops(sizes, &depth, gap.y);
+    (0..n)
+        .map(|i| Vec2::new(x[i], tops[depth[i]].0 - tops[depth[i]].1 / 2.))
+        .collect()
+}
+
+/// Places leaves left to right starting at `cursor` and parents centered above them.
+fn place_subtree(
+    node: usize,
+    children: &[Vec<usize>],
+    sizes: &[Vec2],
+    gap: f32,
+    cursor: &mut f32,
+    x: &mut [f32],
+) {
+    let start = *cursor;
+    for child in children[node].iter() {
+        place_subtree(*child, children, sizes, gap, cursor, x);
+    }
+    let width = sizes[node].x;
+    match (children[node].first(), children[node].last()) {
+        (Some(first), Some(last)) => {
+            x[node] = (x[*first] + x[*last]) / 2.;
+            // parent wider than its children shifts the following subtrees
+            *cursor = f32::max(*cursor, x[node] + width / 2. + gap);
+            if x[node] - width / 2. < start {
+                let shift = start - (x[node] - width / 2.);
+                shift_subtree(node, children, shift, x);
+                *cursor += shift;
+            }
+        }
+        _ => {
+            x[node] = *cursor + width / 2.;
+            *cursor += width + gap;
+        }
+    }
+}
+
+fn shift_subtree(node: usize, children: &[Vec<usize>], shift: f32, x: &mut [f32]) {
+    x[node] += shift;
+    for child in children[node].iter() {
+        shift_subtree(*child, children, shift, x);
+    }
+}
+
+/// Sugiyama-style layout: cycles are broken, nodes are assigned to layers by the longest path
+/// from the sources and ordered within layers by barycenters of their neighbours.
+fn layered_layout(sizes: &[Vec2], edges: &[(usize, usize)], gap: Vec2) -> Vec<Vec2> {
+    let n = sizes.len();
+    let edges = remove_cycles(n, edges);
+    // longest path layering in topological order
+    let mut layer = vec![0; n];
+    let mut in_degree = vec![0; n];
+    for (_, end) in edges.iter() {
+        in_degree[*end] += 1;
+    }
+    let mut queue: Vec<usize> = (0..n).filter(|i| in_degree[*i] == 0).collect();
+    while let Some(node) = queue.pop() {
+        for (start, end) in edges.iter() {
+            if *start == node {
+                layer[*end] = layer[*end].max(layer[node] + 1);
+                in_degree[*end] -= 1;
+                if in_degree[*end] == 0 {
+                    queue.push(*end);
+                }
+            }
+        }
+    }
+    // edges spanning several layers go through dummy nodes
+    let mut node_layer = layer.clone();
+    let mut widths: Vec<f32> = sizes.iter().map(|size| size.x).collect();
+    let mut segments = vec![];
+    for (start, end) in edges.iter() {
+        let mut previous = *start;
+        for dummy_layer in layer[*start] + 1..layer[*end] {
+            node_layer.push(dummy_layer);
+            widths.push(0.);
+            segments.push((previous, node_layer.len() - 1));
+            previous = node_layer.len() - 1;
+        }
+        segments.push((previous, *end));
+    }
+    let layer_count = node_layer.iter().max().map_or(0, |max| max + 1);
+    let mut layers: Vec<Vec<usize>> = vec![vec![]; layer_count];
+    for (node, layer) in node_layer.iter().enumerate() {
+        layers[*layer].push(node);
+    }
+    let mut position = vec![0.; node_layer.len()];
+    for nodes in layers.iter() {
+        for (i, node) in nodes.iter().enumerate() {
+            position[*node] = i as f32;
+        }
+    }
+    for sweep in 0..BARYCENTER_SWEEPS {
+        let downwards = sweep % 2 == 0;
+        let layer_indices: Vec<usize> = if downwards {
+            (1..layer_count).collect()
+        } else {
+            (0..layer_count.saturating_sub(1)).rev().collect()
+        };
+        for l in layer_indices {
+            let mut keys: Vec<(f32, usize)> = layers[l]
+                .iter()
+                .map(|node| {
+                    let neighbours: Vec<f32> = segments
+                        .iter()
+                        .filter_map(|(start, end)| match downwards {
+                            true if end == node => Some(position[*start]),
+                            false if start == node => Some(position[*end]),
+                            _ => None,
+                        })
+                        .collect();
+                    let key = if neighbours.is_empty() {
+                        position[*node]
+                    } else {
+                        neighbours.iter().sum::<f32>() / neighbours.len() as f32
+                    };
+                    (key, *node)
+                })
+                .collect();
+            keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[l] = keys.into_iter().map(|(_, node)| node).collect();
+            for (i, node) in layers[l].iter().enumerate() {
+                position[*node] = i as f32;
+            }
+        }
+    }
+    let tops = layer_tops(sizes, &layer, gap.y);
+    let mut x = vec![0.; node_layer.len()];
+    for nodes in layers.iter() {
+        // layers are centered around the same vertical axis
+        let total: f32 = nodes.iter().map(|node| widths[*node] + gap.x).sum::<f32>() - gap.x;
+        let mut cursor = -total / 2.;
+        for node in nodes.iter() {
+            x[*node] = cursor + widths[*node] / 2.;
+            cursor += widths[*node] + gap.x;
+        }
+    }
+    (0..n)
+        .map(|i| Vec2::new(x[i], tops[layer[i]].0 - tops[layer[i]].1 / 2.))
+        .collect()
+}
+
+/// Reverses edges going back to a node on the current depth-first search path.
+fn remove_cycles(n: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // 0 - not visited, 1 - on the path, 2 - done
+    let mut state = vec![0; n];
+    let mut result = vec![];
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0)];
+        state[root] = 1;
+        while let Some((node, next_edge)) = stack.pop() {
+            let Some((index, (_, end))) = edges
+                .iter()
+                .enumerate()
+                .skip(next_edge)
+                .find(|(_, (start, _))| *start == node)
+            else {
+                state[node] = 2;
+                continue;
+            };
+            stack.push((node, index + 1));
+            match state[*end] {
+                0 => {
+                    result.push((node, *end));
+                    state[*end] = 1;
+                    stack.push((*end, 0));
+                }
+                1 => result.push((*end, node)),
+                _ => result.push((node, *end)),
+            }
+        }
+    }
+    result
+}
+
+/// Fruchterman-Reingold layout starting from the current positions, connected nodes attract
+/// each other and all nodes repel.
+fn force_directed_layout(
+    sizes: &[Vec2],
+    centers: &[Vec2],
+    edges: &[(usize, usize)],
+    gap: Vec2,
+) -> Vec<Vec2> {
+    let n = sizes.len();
+    if n == 0 {
+        return vec![];
+    }
+    let k = sizes.iter().map(|size| size.length()).sum::<f32>() / n as f32 + gap.x;
+    let mut positions = centers.to_vec();
+    // nodes in the same place would never be pushed apart
+    for i in 0..n {
+        if positions[..i].contains(&positions[i]) {
+            let angle = i as f32;
+            positions[i] += Vec2::new(angle.cos(), angle.sin()) * k / 4.;
+        }
+    }
+    let initial_temperature = k * 2.;
+    for iteration in 0..FORCE_ITERATIONS {
+        let mut displacement = vec![Vec2::ZERO; n];
+        for i in 0..n {
+            for j in i + 1..n {
+                let delta = positions[i] - positions[j];
+                let distance = delta.length().max(0.01);
+                let force = delta / distance * k * k / distance;
+                displacement[i] += force;
+                displacement[j] -= force;
+            }
+        }
+        for (start, end) in edges.iter() {
+            let delta = positions[*start] - positions[*end];
+            let distance = delta.length().max(0.01);
+            let force = delta / distance * distance * distance / k;
+            displacement[*start] -= force;
+            displacement[*end] += force;
+        }
+        let temperature = initial_temperature * (1. - iteration as f32 / FORCE_ITERATIONS as f32);
+        for (position, displacement) in positions.iter_mut().zip(displacement.iter()) {
+            let length = displacement.length();
+            if length > 0. {
+                *position += *displacement / length * length.min(temperature);
+            }
+        }
+    }
+    positions
+}
+
+/// Nodes in rows of equal cells, ordered as they are read: top to bottom, left to right.
+fn grid_layout(sizes: &[Vec2], centers: &[Vec2], gap: Vec2) -> Vec<Vec2> {
+    let n = sizes.len();
+    let columns = (n as f32).sqrt().ceil().max(1.) as usize;
+    let cell = sizes.iter().fold(Vec2::ZERO, |a, b| a.max(*b)) + Vec2::splat(gap.x);
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|a, b| {
+        centers[*b]
+            .y
+            .total_cmp(&centers[*a].y)
+            .then(centers[*a].x.total_cmp(&centers[*b].x))
+    });
+    let mut result = vec![Vec2::ZERO; n];
+    for (i, node) in order.into_iter().enumerate() {
+        let (row, column) = (i / columns, i % columns);
+        result[node] = Vec2::new((column as f32 + 0.5) * cell.x, -(row as f32 + 0.5) * cell.y);
+    }
+    result
+}
+
+/// Left top corner of the bounding box of nodes, y axis pointing up.
+fn left_top(sizes: &[Vec2], centers: &[Vec2]) -> Vec2 {
+    sizes
+        .iter()
+        .zip(centers.iter())
+        .map(|(size, center)| Vec2::new(center.x - size.x / 2., center.y + size.y / 2.))
+        .fold(Vec2::new(f32::MAX, f32::MIN), |a, b| {
+            Vec2::new(a.x.min(b.x), a.y.max(b.y))
+        })
+}
+
+/// Opens the layout menu below the menu bar, layouts are applied to the selected nodes or to
+/// the whole tab when less than two nodes are selected.
+pub fn open_layout_menu(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<LayoutMenuButton>)>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    menus: Query<Entity, With<ContextMenu>>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    // opened menu is closed by the click
+    if !menus.is_empty() {
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    let items: Vec<_> = LAYOUT_ACTIONS
+        .iter()
+        .map(|(label, action)| (label.to_string(), ContextMenuAction::Custom(*action)))
+        .collect();
+    spawn_context_menu(
+        &mut commands,
+        main_panel,
+        &items,
+        Vec2::new(8., 8.),
+        None,
+        Vec2::ZERO,
+    );
+}
+
+pub fn apply_layout(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    selected_nodes: Res<SelectedNodes>,
+    // frames keep their place, their contents are laid out
+    containers: Query<
+        (Entity, &VeloNodeContainer, &NodeRect, &Visibility),
+        (Without<Locked>, Without<FrameMembers>),
+    >,
+    arrows: Query<(&ArrowMeta, &Visibility)>,
+) {
+    let Some(algorithm) = events
+        .iter()
+        .find_map(|event| LayoutAlgorithm::from_action(event.action))
+    else {
+        return;
+    };
+    let whole_tab = selected_nodes.0.len() < 2;
+    let nodes: Vec<_> = containers
+        .iter()
+        .filter(|(_, container, _, visibility)| {
+            **visibility != Visibility::Hidden
+                && (whole_tab || selected_nodes.0.contains(&container.id))
+        })
+        .map(|(entity, container, rect, _)| (entity, container.id, rect.position, rect.size))
+        .collect();
+    if nodes.is_empty() {
+        return;
+    }
+    let ids: Vec<ReflectableUuid> = nodes.iter().map(|(_, id, _, _)| *id).collect();
+    let sizes: Vec<Vec2> = nodes.iter().map(|(_, _, _, size)| *size).collect();
+    let centers: Vec<Vec2> = nodes
+        .iter()
+        .map(|(_, _, position, size)| *position + *size / 2.)
+        .collect();
+    let edges: Vec<(usize, usize)> = arrows
+        .iter()
+        .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+        .filter_map(|(arrow, _)| {
+            let start = ids.iter().position(|id| *id == arrow.start.id)?;
+            let end = ids.iter().position(|id| *id == arrow.end.id)?;
+            Some((start, end))
+        })
+        .collect();
+    let new_centers = compute_layout(algorithm, &sizes, &centers, &edges, LAYOUT_GAP);
+    // laid out nodes stay where they were in the world
+    let offset = left_top(&sizes, &centers) - left_top(&sizes, &new_centers);
+    for (i, (entity, _, position, size)) in nodes.iter().enumerate() {
+        commands.entity(*entity).insert(LayoutAnimation {
+            from: *position,
+            to: (new_centers[i] + offset - *size / 2.).round(),
+            elapsed: 0.,
+        });
+    }
+}
+
+/// Moves laid out nodes to their new places over `LAYOUT_ANIMATION_SECONDS`, at once with
+/// reduced motion.
+pub fn animate_layout(
+    mut commands: Commands,
+    time: Res<Time>,
+    motion_settings: Res<MotionSettings>,
+    mut containers: Query<(
+        Entity,
+        &VeloNodeContainer,
+        &mut NodeRect,
+        &mut LayoutAnimation,
+    )>,
+    mut events: EventWriter<RedrawArrowEvent>,
+    mut history: ResMut<History>,
+    mut finished: Local<Vec<ReflectableUuid>>,
+) {
+    // arrows are redrawn once more after the final layout is computed
+    for id in finished.drain(..) {
+        events.send(RedrawArrowEvent { id });
+    }
+    for (entity, container, mut rect, mut animation) in containers.iter_mut() {
+        animation.elapsed += time.delta_seconds();
+        let t = if motion_settings.reduced_motion {
+            1.
+        } else {
+            (animation.elapsed / LAYOUT_ANIMATION_SECONDS).min(1.)
+        };
+        // ease out
+        let t = 1. - (1. - t) * (1. - t);
+        rect.position = animation.from.lerp(animation.to, t);
+        events.send(RedrawArrowEvent { id: container.id });
+        // the whole animation is a single move
+        if t >= 1. {
+            history.record(HistoryCommand::NodeMoved {
+                id: container.id.0,
+                from: animation.from,
+                to: animation.to,
+            });
+            commands.entity(entity).remove::<LayoutAnimation>();
+            finished.push(container.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlap(sizes: &[Vec2], centers: &[Vec2]) -> bool {
+        (0..sizes.len()).any(|i| {
+            (i + 1..sizes.len()).any(|j| {
+                let d = (centers[i] - centers[j]).abs();
+                d.x < (sizes[i].x + sizes[j].x) / 2. && d.y < (sizes[i].y + sizes[j].y) / 2.
+            })
+        })
+    }
+
+    #[test]
+    fn test_tree_layout() {
+        let sizes = [Vec2::splat(100.); 4];
+        // 0 -> 1, 0 -> 2, 2 -> 3
+        let centers = tree_layout(&sizes, &[(0, 1), (0, 2), (2, 3)], Vec2::new(20., 50.));
+        assert!(!overlap(&sizes, &centers));
+        assert_eq!(centers[0].x, (centers[1].x + centers[2].x) / 2.);
+        assert!(centers[0].y > centers[1].y);
+        assert_eq!(centers[1].y, centers[2].y);
+        assert!(centers[2].y > centers[3].y);
+    }
+
+    #[test]
+    fn test_layered_layout() {
+        let sizes = [Vec2::splat(100.); 4];
+        // 0 -> 1 -> 2 -> 0 cycle and a long edge 0 -> 3 with 1 -> 3
+        let edges = [(0, 1), (1, 2), (2, 0), (0, 3), (1, 3)];
+        let centers = layered_layout(&sizes, &edges, Vec2::new(20., 50.));
+        assert!(!overlap(&sizes, &centers));
+        assert!(centers[0].y > centers[1].y);
+        assert!(centers[1].y > centers[3].y);
+    }
+
+    #[test]
+    fn test_remove_cycles() {
+        let edges = remove_cycles(3, &[(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(edges.len(), 3);
+        assert!(edges.contains(&(0, 2)));
+    }
+
+    #[test]
+    fn test_force_directed_layout() {
+        let sizes = [Vec2::splat(50.); 3];
+        let centers = [Vec2::ZERO; 3];
+        let result = force_directed_layout(&sizes, &centers, &[(0, 1)], Vec2::new(40., 80.));
+        assert!(result.iter().all(|center| center.is_finite()));
+        assert!(!overlap(&sizes, &result));
+    }
+
+    #[test]
+    fn test_grid_layout() {
+        let sizes = [Vec2::splat(10.); 4];
+        let centers = [
+            Vec2::new(50., 0.),
+            Vec2::new(0., 0.),
+            Vec2::new(50., 100.),
+            Vec2::new(0., 100.),
+        ];
+        let result = grid_layout(&sizes, &centers, Vec2::new(10., 10.));
+        assert_eq!(result[3], Vec2::new(10., -10.));
+        assert_eq!(result[2], Vec2::new(30., -10.));
+        assert_eq!(result[1], Vec2::new(10., -30.));
+        assert_eq!(result[0], Vec2::new(30., -30.));
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::utils::ReflectableUuid;
+
+use super::{
+    ui_helpers::{
+        BranchBadge, CollapsedBranch, FitToText, HiddenByBranch, NodePart, Tooltip, VeloNode,
+    },
+    ContextMenuAction, ContextMenuEvent, SelectedNodes, VeloNodeContainer,
+};
+
+pub const TOGGLE_BRANCH_ACTION: &str = "toggle_branch";
+// Badges are drawn over the node and its handles
+const BADGE_Z: f32 = 0.75;
+
+/// Nodes reachable from `root` following arrows from their start to their end, `root` excluded.
+///
+/// `edges` holds `(start node id, end node id)` of the arrows.
+pub fn descendants(
+    root: ReflectableUuid,
+    edges: &[(ReflectableUuid, ReflectableUuid)],
+) -> Vec<ReflectableUuid> {
+    let mut result: Vec<ReflectableUuid> = vec![];
+    let mut queue = vec![root];
+    while let Some(id) = queue.pop() {
+        for (start, end) in edges.iter() {
+            if *start == id && *end != root && !result.contains(end) {
+                result.push(*end);
+                queue.push(*end);
+            }
+        }
+    }
+    result
+}
+
+/// Collapses or expands outgoing subtrees of the selected nodes from the context menu.
+pub fn toggle_branch(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    selected_nodes: Res<SelectedNodes>,
+    containers: Query<(Entity, &VeloNodeContainer, Option<&CollapsedBranch>)>,
+) {
+    for event in events.iter() {
+        if event.action != ContextMenuAction::Custom(TOGGLE_BRANCH_ACTION) {
+            continue;
+        }
+        for (entity, container, collapsed) in containers.iter() {
+            if !selected_nodes.0.contains(&container.id) {
+                continue;
+            }
+            if collapsed.is_some() {
+                commands.entity(entity).remove::<CollapsedBranch>();
+            } else {
+                commands.entity(entity).insert(CollapsedBranch);
+            }
+        }
+    }
+}
+
+pub fn expand_branch_badges(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &BranchBadge), Changed<Interaction>>,
+    containers: Query<(Entity, &VeloNodeContainer), With<CollapsedBranch>>,
+) {
+    for (interaction, badge) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        for (entity, container) in containers.iter() {
+            if container.id == badge.id {
+                commands.entity(entity).remove::<CollapsedBranch>();
+            }
+        }
+    }
+}
+
+/// Hides nodes and arrows of collapsed subtrees and keeps badges of collapsed nodes showing
+/// the hidden nodes count. Visibility of expanded nodes is restored by the tag filter.
+pub fn apply_branch_collapse(
+    mut commands: Commands,
+    collapsed_query: Query<&VeloNodeContainer, With<CollapsedBranch>>,
+    mut containers: Query<
+        (
+            Entity,
+            &VeloNodeContainer,
+            &mut Visibility,
+            Option<&HiddenByBranch>,
+        ),
+        Without<ArrowMeta>,
+    >,
+    mut arrows: Query<
+        (Entity, &ArrowMeta, &mut Visibility, Option<&HiddenByBranch>),
+        (Without<VeloNodeContainer>, Without<Tooltip>),
+    >,
+    nodes: Query<(Entity, &VeloNode)>,
+    badges: Query<(Entity, &BranchBadge, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    // arrows hidden without the marker are deleted
+    let edges: Vec<_> = arrows
+        .iter()
+        .filter(|(_, _, visibility, hidden)| **visibility != Visibility::Hidden || hidden.is_some())
+        .map(|(_, arrow, _, _)| (arrow.start.id, arrow.end.id))
+        .collect();
+    let mut counts: Vec<(ReflectableUuid, usize)> = vec![];
+    let mut hidden: Vec<ReflectableUuid> = vec![];
+    for container in collapsed_query.iter() {
+        let subtree = descendants(container.id, &edges);
+        counts.push((container.id, subtree.len()));
+        for id in subtree {
+            if !hidden.contains(&id) {
+                hidden.push(id);
+            }
+        }
+    }
+    for (entity, container, mut visibility, hidden_by_branch) in containers.iter_mut() {
+        if hidden.contains(&container.id) {
+            if hidden_by_branch.is_none() {
+                commands.entity(entity).insert(HiddenByBranch);
+            }
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+        } else if hidden_by_branch.is_some() {
+            commands.entity(entity).remove::<HiddenByBranch>();
+        }
+    }
+    for (entity, arrow, mut visibility, hidden_by_branch) in arrows.iter_mut() {
+        if hidden.contains(&arrow.start.id) || hidden.contains(&arrow.end.id) {
+            if hidden_by_branch.is_none() && *visibility != Visibility::Hidden {
+                commands.entity(entity).insert(HiddenByBranch);
+                *visibility = Visibility::Hidden;
+            }
+        } else if hidden_by_branch.is_some() {
+            commands.entity(entity).remove::<HiddenByBranch>();
+            *visibility = Visibility::Inherited;
+        }
+    }
+
+    for (badge_entity, badge, children) in badges.iter() {
+        match counts.iter().find(|(id, _)| *id == badge.id) {
+            Some((_, count)) => {
+                let label = format!("+{}", count);
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        if text.sections[0].value != label {
+                            text.sections[0].value = label.clone();
+                        }
+                    }
+                }
+            }
+            None => {
+                commands.entity(badge_entity).despawn_recursive();
+            }
+        }
+    }
+    for (id, count) in counts.iter() {
+        if badges.iter().any(|(_, badge, _)| badge.id == *id) {
+            continue;
+        }
+        let Some((node_entity, _)) = nodes.iter().find(|(_, node)| node.id == *id) else {
+            continue;
+        };
+        let badge = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(59.0 / 255.0, 130.0 / 255.0, 246.0 / 255.0),
+                        anchor: Anchor::BottomRight,
+                        ..default()
+                    },
+                    ..default()
+                },
+                NodePart::handle(
+                    Vec2::new(0.5, -0.5),
+                    Vec2::new(10., -10.),
+                    Vec2::ZERO,
+                    BADGE_Z,
+                ),
+                FitToText {
+                    padding: Vec2::new(4., 2.),
+                },
+                Interaction::default(),
+                BranchBadge { id: *id },
+            ))
+            .with_children(|builder| {
+                builder.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        format!("+{}", count),
+                        TextStyle {
+                            font_size: 14.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    ..default()
+                });
+            })
+            .id();
+        commands.entity(node_entity).add_child(badge);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_descendants() {
+        let [root, a, b, c, other] = [(); 5].map(|_| ReflectableUuid(Uuid::new_v4()));
+        // b points back to the root, other points into the subtree
+        let edges = [(root, a), (a, b), (b, root), (root, c), (other, a)];
+        let mut result = descendants(root, &edges);
+        result.sort_by(|x, y| x.0.cmp(&y.0));
+        let mut expected = vec![a, b, c];
+        expected.sort_by(|x, y| x.0.cmp(&y.0));
+        assert_eq!(result, expected);
+        assert!(descendants(c, &edges).is_empty());
+    }
+}
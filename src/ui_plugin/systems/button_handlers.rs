@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::{collections::VecDeque, time::Duration};
+use std::time::Duration;
 
 use bevy::render::view::RenderLayers;
 use bevy::{prelude::*, window::PrimaryWindow};
@@ -11,121 +11,270 @@ use serde::Serialize;
 use serde_json::{json, Value};
 use uuid::Uuid;
 
-use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeType, UiState};
+use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeType, SelectedNodes, TextPos, UiState};
 
 use super::ui_helpers::{
-    pos_to_style, spawn_modal, ButtonAction, ChangeColor, DeleteDoc, DocListItemButton,
-    GenericButton, NewDoc, ParticlesEffect, RawText, SaveDoc, TextPosMode, Tooltip, VeloNode,
+    spawn_modal, ButtonAction, ButtonTypes, ChangeColor, DeleteDoc, DocListItemButton,
+    GenericButton, Localized, NewDoc, NodeEffects, NodeZIndex, ParticlesEffect, RawText, SaveDoc,
+    TextPosMode, Tooltip, VeloNode,
+};
+use super::{
+    canvas_to_world, Action, CanvasOffset, CanvasZoom, EncryptDoc, History, ImportFromUrl,
+    MainPanel, MotionSettings, Notification, NotificationSender, Settings, ShareDoc, Theme,
+    VeloNodeContainer,
 };
-use super::{ExportToFile, ImportFromFile, ImportFromUrl, MainPanel, ShareDoc, VeloNodeContainer};
 use crate::canvas::arrow::components::{ArrowMeta, ArrowMode};
 use crate::components::{Doc, EffectsCamera, Tab};
 use crate::resources::{AppState, LoadDocRequest, SaveDocRequest};
 use crate::utils::{get_timestamp, load_doc_to_memory, to_cosmic_text_pos, ReflectableUuid};
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum ZOrder {
+    Forward,
+    Backward,
+    ToFront,
+    ToBack,
+}
+
+pub fn change_z_order(
+    ids: &[ReflectableUuid],
+    z_order: ZOrder,
+    nodes: &mut Query<(Entity, &VeloNodeContainer, &mut NodeZIndex), With<VeloNodeContainer>>,
+) {
+    let others: Vec<i32> = nodes
+        .iter()
+        .filter(|(_, node, _)| !ids.contains(&node.id))
+        .map(|(_, _, z_index)| z_index.0)
+        .collect();
+    let max_other = others.iter().max().copied();
+    let min_other = others.iter().min().copied();
+    let mut selected: Vec<_> = nodes
+        .iter_mut()
+        .filter(|(_, node, _)| ids.contains(&node.id))
+        .map(|(_, _, z_index)| z_index)
+        .collect();
+    // keep the relative order of the moved nodes
+    selected.sort_by_key(|z_index| z_index.0);
+    let count = selected.len() as i32;
+    for (rank, mut z_index) in selected.into_iter().enumerate() {
+        let current = z_index.0;
+        let rank = rank as i32;
+        *z_index = NodeZIndex(match z_order {
+            ZOrder::Forward => current + 1,
+            ZOrder::Backward => current - 1,
+            ZOrder::ToFront => max_other.map_or(current, |max| max + 1 + rank),
+            ZOrder::ToBack => min_other.map_or(current, |min| min - count + rank),
+        });
+    }
+}
+
+pub fn z_order_shortcuts(
+    input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    selected_nodes: Res<SelectedNodes>,
+    mut nodes: Query<(Entity, &VeloNodeContainer, &mut NodeZIndex), With<VeloNodeContainer>>,
+) {
+    let shortcuts = [
+        (Action::BringForward, ZOrder::Forward),
+        (Action::BringToFront, ZOrder::ToFront),
+        (Action::SendBackward, ZOrder::Backward),
+        (Action::SendToBack, ZOrder::ToBack),
+    ];
+    let Some(z_order) = shortcuts
+        .into_iter()
+        .find(|(action, _)| settings.keybindings.just_pressed(*action, &input))
+        .map(|(_, z_order)| z_order)
+    else {
+        return;
+    };
+    change_z_order(&selected_nodes.0, z_order, &mut nodes);
+}
+
+/// Removes nodes together with their arrows and stops editing, the deletion is recorded in the
+/// history.
+pub fn delete_nodes(
+    ids: &[ReflectableUuid],
+    commands: &mut Commands,
+    state: &mut UiState,
+    selected_nodes: &mut SelectedNodes,
+    history: &mut History,
+    nodes: &Query<(Entity, &VeloNodeContainer, &mut NodeZIndex), With<VeloNodeContainer>>,
+    arrows: &mut Query<(Entity, &ArrowMeta, &mut Visibility), (With<ArrowMeta>, Without<Tooltip>)>,
+) {
+    if ids.is_empty() {
+        return;
+    }
+    history.record_deleted(ids);
+    commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+    *state = UiState::default();
+    selected_nodes.0.clear();
+    for (entity, node, _) in nodes.iter() {
+        if ids.contains(&node.id) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    #[allow(unused)]
+    for (entity, arrow, mut visibility) in arrows.iter_mut() {
+        if ids.contains(&arrow.start.id) || ids.contains(&arrow.end.id) {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                commands.entity(entity).despawn_recursive();
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                *visibility = Visibility::Hidden;
+            }
+        }
+    }
+}
+
 pub fn rec_button_handlers(
     mut commands: Commands,
     mut events: EventWriter<AddRectEvent>,
-    mut interaction_query: Query<
+    interaction_query: Query<
         (&Interaction, &ButtonAction),
         (Changed<Interaction>, With<ButtonAction>),
     >,
-    mut nodes: Query<(Entity, &VeloNodeContainer, &mut ZIndex), With<VeloNodeContainer>>,
+    mut nodes: Query<(Entity, &VeloNodeContainer, &mut NodeZIndex), With<VeloNodeContainer>>,
     mut arrows: Query<(Entity, &ArrowMeta, &mut Visibility), (With<ArrowMeta>, Without<Tooltip>)>,
     mut state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut history: ResMut<History>,
+    theme: Res<Theme>,
     windows: Query<&Window, With<PrimaryWindow>>,
+    input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
 ) {
     let window = windows.single();
-    for (interaction, button_action) in &mut interaction_query {
-        match *interaction {
-            Interaction::Clicked => match button_action.button_type {
-                super::ui_helpers::ButtonTypes::AddRec => {
-                    events.send(AddRectEvent {
-                        node: JsonNode {
-                            id: Uuid::new_v4(),
-                            node_type: NodeType::Rect,
-                            left: Val::Px(window.width() / 2. - 200.),
-                            bottom: Val::Px(window.height() / 2.),
-                            width: Val::Px(128.0),
-                            height: Val::Px(128.0),
-                            text: JsonNodeText {
-                                text: "".to_string(),
-                                pos: crate::TextPos::Center,
-                            },
-                            bg_color: Color::rgb(1.0, 1.0, 1.0),
-                            z_index: 0,
+    // new nodes are placed left of the middle of the view
+    let position = canvas_to_world(
+        Vec2::new(window.width() / 2. - 200., window.height() / 2.),
+        &offset,
+        &zoom,
+    );
+    // the new node and delete shortcuts do what the buttons do
+    let shortcut = if !state.is_canvas_focused() {
+        None
+    } else if settings.keybindings.just_pressed(Action::NewNode, &input) {
+        Some(ButtonTypes::AddRec)
+    } else if settings.keybindings.just_pressed(Action::Delete, &input) {
+        Some(ButtonTypes::Del)
+    } else {
+        None
+    };
+    let clicked = interaction_query
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button_action)| &button_action.button_type);
+    for button_type in clicked.chain(shortcut.as_ref()) {
+        let ids = selected_nodes.0.clone();
+        match *button_type {
+            super::ui_helpers::ButtonTypes::AddRec => {
+                events.send(AddRectEvent {
+                    node: JsonNode {
+                        id: Uuid::new_v4(),
+                        node_type: NodeType::Rect,
+                        left: Val::Px(position.x),
+                        bottom: Val::Px(position.y),
+                        width: Val::Px(128.0),
+                        height: Val::Px(128.0),
+                        text: JsonNodeText {
+                            text: "".to_string(),
+                            pos: crate::TextPos::Center,
+                            style: theme.node_text_style(),
                         },
-                        image: None,
-                    });
-                }
-                super::ui_helpers::ButtonTypes::AddCircle => {
-                    events.send(AddRectEvent {
-                        node: JsonNode {
-                            id: Uuid::new_v4(),
-                            node_type: NodeType::Circle,
-                            left: Val::Px(window.width() / 2. - 200.),
-                            bottom: Val::Px(window.height() / 2.),
-                            width: Val::Px(128.0),
-                            height: Val::Px(128.0),
-                            text: JsonNodeText {
-                                text: "".to_string(),
-                                pos: crate::TextPos::Center,
-                            },
-                            bg_color: Color::rgb(1.0, 1.0, 1.0),
-                            z_index: 0,
+                        bg_color: theme.node_background,
+                        z_index: 0,
+                        locked: false,
+                        border: theme.node_border(),
+                        shadow: None,
+                        opacity: 1.,
+                        tags: vec![],
+                        attachments: vec![],
+                        rotation: 0.,
+                    },
+                    image: None,
+                });
+            }
+            super::ui_helpers::ButtonTypes::AddCircle => {
+                events.send(AddRectEvent {
+                    node: JsonNode {
+                        id: Uuid::new_v4(),
+                        node_type: NodeType::Circle,
+                        left: Val::Px(position.x),
+                        bottom: Val::Px(position.y),
+                        width: Val::Px(128.0),
+                        height: Val::Px(128.0),
+                        text: JsonNodeText {
+                            text: "".to_string(),
+                            pos: crate::TextPos::Center,
+                            style: theme.node_text_style(),
                         },
-                        image: None,
-                    });
-                }
-                super::ui_helpers::ButtonTypes::Del => {
-                    if let Some(id) = state.entity_to_edit {
-                        commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
-                        *state = UiState::default();
-                        for (entity, node, _) in nodes.iter() {
-                            if node.id == id {
-                                commands.entity(entity).despawn_recursive();
-                            }
-                        }
-                        #[allow(unused)]
-                        for (entity, arrow, mut visibility) in &mut arrows.iter_mut() {
-                            if arrow.start.id == id || arrow.end.id == id {
-                                #[cfg(not(target_arch = "wasm32"))]
-                                {
-                                    commands.entity(entity).despawn_recursive();
-                                }
-                                #[cfg(target_arch = "wasm32")]
-                                {
-                                    *visibility = Visibility::Hidden;
-                                }
-                            }
-                        }
-                    }
-                }
-                super::ui_helpers::ButtonTypes::Front => {
-                    if let Some(id) = state.entity_to_edit {
-                        for (_, node, mut z_index) in nodes.iter_mut() {
-                            if node.id == id {
-                                if let ZIndex::Local(i) = *z_index {
-                                    *z_index = ZIndex::Local(i + 1);
-                                }
-                            }
-                        }
-                    }
-                }
-                super::ui_helpers::ButtonTypes::Back => {
-                    if let Some(id) = state.entity_to_edit {
-                        for (_, node, mut z_index) in nodes.iter_mut() {
-                            if node.id == id {
-                                if let ZIndex::Local(i) = *z_index {
-                                    *z_index = ZIndex::Local(i - 1);
-                                }
-                            }
-                        }
-                    }
-                }
-            },
-            Interaction::Hovered => {}
-            Interaction::None => {}
+                        bg_color: theme.node_background,
+                        z_index: 0,
+                        locked: false,
+                        border: theme.node_border(),
+                        shadow: None,
+                        opacity: 1.,
+                        tags: vec![],
+                        attachments: vec![],
+                        rotation: 0.,
+                    },
+                    image: None,
+                });
+            }
+            super::ui_helpers::ButtonTypes::AddShape(ref node_type) => {
+                events.send(AddRectEvent {
+                    node: JsonNode {
+                        id: Uuid::new_v4(),
+                        node_type: node_type.clone(),
+                        left: Val::Px(position.x),
+                        bottom: Val::Px(position.y),
+                        width: Val::Px(128.0),
+                        height: Val::Px(128.0),
+                        text: JsonNodeText {
+                            text: "".to_string(),
+                            pos: crate::TextPos::Center,
+                            style: theme.node_text_style(),
+                        },
+                        bg_color: theme.node_background,
+                        z_index: 0,
+                        locked: false,
+                        border: theme.node_border(),
+                        shadow: None,
+                        opacity: 1.,
+                        tags: vec![],
+                        attachments: vec![],
+                        rotation: 0.,
+                    },
+                    image: None,
+                });
+            }
+            super::ui_helpers::ButtonTypes::Del => {
+                delete_nodes(
+                    &ids,
+                    &mut commands,
+                    &mut state,
+                    &mut selected_nodes,
+                    &mut history,
+                    &nodes,
+                    &mut arrows,
+                );
+            }
+            super::ui_helpers::ButtonTypes::Front => {
+                change_z_order(&ids, ZOrder::Forward, &mut nodes);
+            }
+            super::ui_helpers::ButtonTypes::Back => {
+                change_z_order(&ids, ZOrder::Backward, &mut nodes);
+            }
+            super::ui_helpers::ButtonTypes::ToFront => {
+                change_z_order(&ids, ZOrder::ToFront, &mut nodes);
+            }
+            super::ui_helpers::ButtonTypes::ToBack => {
+                change_z_order(&ids, ZOrder::ToBack, &mut nodes);
+            }
         }
     }
 }
@@ -135,18 +284,16 @@ pub fn change_color_pallete(
         (&Interaction, &ChangeColor),
         (Changed<Interaction>, With<ChangeColor>, Without<VeloNode>),
     >,
-    mut nodes: Query<(&mut BackgroundColor, &VeloNode), With<VeloNode>>,
-    state: Res<UiState>,
+    mut nodes: Query<(&mut NodeEffects, &VeloNode), With<VeloNode>>,
+    selected_nodes: Res<SelectedNodes>,
 ) {
     for (interaction, change_color) in &mut interaction_query {
         match *interaction {
             Interaction::Clicked => {
                 let color = change_color.color;
-                if state.entity_to_edit.is_some() {
-                    for (mut bg_color, node) in nodes.iter_mut() {
-                        if node.id == state.entity_to_edit.unwrap() {
-                            bg_color.0 = color;
-                        }
+                for (mut effects, node) in nodes.iter_mut() {
+                    if selected_nodes.0.contains(&node.id) {
+                        effects.color = color;
                     }
                 }
             }
@@ -161,20 +308,18 @@ pub fn change_text_pos(
         (&Interaction, &TextPosMode),
         (Changed<Interaction>, With<TextPosMode>),
     >,
-    mut nodes: Query<(&mut Style, &VeloNode), With<VeloNode>>,
+    mut nodes: Query<(&mut TextPos, &VeloNode), With<VeloNode>>,
     state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
     mut raw_text_node_query: Query<(&RawText, &mut CosmicEditImage), With<RawText>>,
 ) {
     for (interaction, text_pos_mode) in &mut interaction_query {
         match *interaction {
             Interaction::Clicked => {
-                if state.entity_to_edit.is_some() {
-                    for (mut style, node) in nodes.iter_mut() {
-                        if node.id == state.entity_to_edit.unwrap() {
-                            let (justify_content, align_items) =
-                                pos_to_style(text_pos_mode.text_pos.clone());
-                            style.justify_content = justify_content;
-                            style.align_items = align_items;
+                if let Some(id) = selected_nodes.edited(&state) {
+                    for (mut text_pos, node) in nodes.iter_mut() {
+                        if node.id == id {
+                            *text_pos = text_pos_mode.text_pos.clone();
                             for (raw_text, mut cosmic_editor) in &mut raw_text_node_query.iter_mut()
                             {
                                 if raw_text.id == node.id {
@@ -204,6 +349,7 @@ pub fn change_arrow_type(
         match *interaction {
             Interaction::Clicked => {
                 state.arrow_type = arrow_mode.arrow_type;
+                state.arrow_style = state.arrow_style.with_type_heads(arrow_mode.arrow_type);
             }
             Interaction::Hovered => {}
             Interaction::None => {}
@@ -222,19 +368,22 @@ pub fn new_doc_handler(
                 let doc_id = ReflectableUuid::generate();
                 let name = "Untitled".to_string();
                 let tab_id = ReflectableUuid::generate();
-                let mut checkpoints = VecDeque::new();
-                checkpoints.push_back(
-                    json!({
-                        "nodes": [],
-                        "arrows": [],
-                        "images": {},
-                    })
-                    .to_string(),
-                );
+                let checkpoint = json!({
+                    "nodes": [],
+                    "arrows": [],
+                    "images": {},
+                })
+                .to_string();
                 let tabs = vec![Tab {
                     id: tab_id,
                     name: "Tab 1".to_string(),
-                    checkpoints,
+                    checkpoint: Some(checkpoint),
+                    zoom: 1.,
+                    color: None,
+                    icon: None,
+                    pinned: false,
+                    offset: [0., 0.],
+                    selected: vec![],
                     is_active: true,
                 }];
                 app_state.docs.insert(
@@ -244,6 +393,8 @@ pub fn new_doc_handler(
                         name: name.clone(),
                         tabs,
                         tags: vec![],
+                        assets: HashMap::new(),
+                        revision: 0,
                     },
                 );
                 commands.insert_resource(SaveDocRequest {
@@ -361,9 +512,10 @@ pub fn save_doc_handler(
     }
 }
 
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
 pub fn export_to_file(
     mut commands: Commands,
-    mut query: Query<&Interaction, (Changed<Interaction>, With<ExportToFile>)>,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<super::ExportToFile>)>,
     mut ui_state: ResMut<UiState>,
     main_panel_query: Query<Entity, With<MainPanel>>,
     windows: Query<&Window, With<PrimaryWindow>>,
@@ -392,6 +544,37 @@ pub fn export_to_file(
     }
 }
 
+pub fn encrypt_doc_handler(
+    mut commands: Commands,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<EncryptDoc>)>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut font_system_state: ResMut<FontSystemState>,
+) {
+    let window = windows.single();
+    for interaction in &mut query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                let id = ReflectableUuid::generate();
+                *ui_state = UiState::default();
+                commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+                ui_state.modal_id = Some(id);
+                let entity = spawn_modal(
+                    &mut commands,
+                    &mut font_system_state,
+                    window,
+                    id,
+                    super::ModalAction::SetPassphrase,
+                );
+                commands.entity(main_panel_query.single()).add_child(entity);
+            }
+            Interaction::Hovered => {}
+            Interaction::None => {}
+        }
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 pub fn set_window_property(mut app_state: ResMut<AppState>, mut pkv: ResMut<PkvStore>) {
     if let Some(doc_id) = app_state.current_document {
@@ -421,6 +604,7 @@ pub fn shared_doc_handler(
     mut app_state: ResMut<AppState>,
     mut query: Query<&Interaction, (Changed<Interaction>, With<ShareDoc>)>,
     mut pkv: ResMut<PkvStore>,
+    notification_sender: Res<NotificationSender>,
 ) {
     for interaction in &mut query.iter_mut() {
         match *interaction {
@@ -454,25 +638,35 @@ pub fn shared_doc_handler(
                         "Authorization".to_string(),
                         format!("token {}", app_state.github_token.as_ref().unwrap()),
                     );
-                    #[cfg(not(target_arch = "wasm32"))]
-                    let mut clipboard = arboard::Clipboard::new().unwrap();
+                    let notification_sender = notification_sender.clone();
                     ehttp::fetch(request, move |result| {
-                        let response = result.unwrap();
-                        if response.ok {
-                            let res_json: Value =
-                                serde_json::from_str(response.text().unwrap().as_str()).unwrap();
-                            let files: Value = res_json["files"].clone();
-                            let velo = files["velo.json"].clone();
-                            #[cfg(not(target_arch = "wasm32"))]
-                            clipboard
-                                .set_text(format!(
-                                    "https://staffengineer.github.io/velo?document={}",
-                                    velo["raw_url"].to_string().replace('\"', "")
-                                ))
-                                .unwrap();
-                        } else {
-                            error!("Error sharing document: {}", response.status_text);
-                        }
+                        let error = match result {
+                            Ok(response) if response.ok => {
+                                let res_json: Value =
+                                    serde_json::from_str(response.text().unwrap().as_str())
+                                        .unwrap();
+                                let files: Value = res_json["files"].clone();
+                                let velo = files["velo.json"].clone();
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if let Err(e) =
+                                    arboard::Clipboard::new().and_then(|mut clipboard| {
+                                        clipboard.set_text(format!(
+                                            "https://staffengineer.github.io/velo?document={}",
+                                            velo["raw_url"].to_string().replace('\"', "")
+                                        ))
+                                    })
+                                {
+                                    let message = Localized::new("Couldn't copy to the clipboard");
+                                    notification_sender
+                                        .send(Notification::error(message).with_details(e));
+                                }
+                                return;
+                            }
+                            Ok(response) => response.status_text,
+                            Err(e) => e,
+                        };
+                        let message = Localized::new("Couldn't share the document");
+                        notification_sender.send(Notification::error(message).with_details(error));
                     });
                 }
             }
@@ -482,9 +676,15 @@ pub fn shared_doc_handler(
     }
 }
 
+#[cfg(not(any(
+    target_arch = "wasm32",
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux"
+)))]
 pub fn import_from_file(
     mut commands: Commands,
-    mut query: Query<&Interaction, (Changed<Interaction>, With<ImportFromFile>)>,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<super::ImportFromFile>)>,
     mut ui_state: ResMut<UiState>,
     main_panel_query: Query<Entity, With<MainPanel>>,
     windows: Query<&Window, With<PrimaryWindow>>,
@@ -586,6 +786,7 @@ pub fn particles_effect(
     mut effects: ResMut<Assets<bevy_hanabi::EffectAsset>>,
     mut effects_camera: Query<&mut Camera, With<EffectsCamera>>,
     mut effects_query: Query<(&Name, Entity)>,
+    motion_settings: Res<MotionSettings>,
 ) {
     use bevy_hanabi::prelude::*;
     use rand::Rng;
@@ -600,7 +801,7 @@ pub fn particles_effect(
                             commands.entity(entity).despawn_recursive();
                         }
                     }
-                } else {
+                } else if !motion_settings.reduced_motion {
                     effects_camera.single_mut().is_active = true;
                     let mut gradient = Gradient::new();
                     let mut rng = rand::thread_rng();
@@ -680,13 +881,15 @@ fn test_change_color_pallete() {
     let entity_id = crate::utils::ReflectableUuid::generate();
 
     app.insert_resource(UiState {
-        entity_to_edit: Some(entity_id),
+        editing_node: true,
         ..default()
     });
+    app.insert_resource(SelectedNodes(vec![entity_id]));
     app.add_event::<Interaction>();
     app.add_event::<ChangeColor>();
 
     app.add_system(change_color_pallete);
+    app.add_system(super::apply_node_opacity.after(change_color_pallete));
 
     app.world
         .spawn(NodeBundle {
@@ -704,15 +907,10 @@ fn test_change_color_pallete() {
         .insert(Interaction::Clicked)
         .insert(ChangeColor { color: Color::RED });
     app.world
-        .spawn(NodeBundle {
-            background_color: BackgroundColor(Color::BLUE),
-            style: Style {
-                size: Size::new(Val::Px(100.0), Val::Px(100.0)),
-                position: UiRect {
-                    left: Val::Px(0.0),
-                    bottom: Val::Px(0.0),
-                    ..Default::default()
-                },
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: Color::BLUE,
+                custom_size: Some(Vec2::new(100.0, 100.0)),
                 ..Default::default()
             },
             ..Default::default()
@@ -720,7 +918,8 @@ fn test_change_color_pallete() {
         .insert(VeloNode {
             id: entity_id,
             node_type: default(),
-        });
+        })
+        .insert(NodeEffects::new(None, 0.5, Color::BLUE));
 
     app.world
         .resource_mut::<Events<Interaction>>()
@@ -731,11 +930,47 @@ fn test_change_color_pallete() {
 
     app.update();
 
-    let (bg_color, _) = app
+    let (sprite, effects) = app
         .world
-        .query::<(&BackgroundColor, With<VeloNode>)>()
-        .iter_mut(&mut app.world)
+        .query_filtered::<(&Sprite, &NodeEffects), With<VeloNode>>()
+        .iter(&app.world)
         .last()
         .unwrap();
-    assert_eq!(bg_color.0, Color::RED);
+    assert_eq!(effects.color, Color::RED);
+    // the sprite is drawn with the opacity of the node
+    assert_eq!(sprite.color, Color::RED.with_a(0.5));
+}
+
+#[test]
+fn test_z_order_shortcuts() {
+    let mut app = App::new();
+    let ids: Vec<ReflectableUuid> = (0..3).map(|_| ReflectableUuid::generate()).collect();
+
+    let mut input = Input::<KeyCode>::default();
+    input.press(KeyCode::LControl);
+    input.press(KeyCode::LShift);
+    input.press(KeyCode::BracketRight);
+    app.insert_resource(input);
+    app.init_resource::<UiState>();
+    app.init_resource::<Settings>();
+    app.insert_resource(SelectedNodes(vec![ids[0]]));
+
+    app.add_system(z_order_shortcuts);
+
+    for (i, id) in ids.iter().enumerate() {
+        app.world
+            .spawn((NodeZIndex(i as i32), VeloNodeContainer { id: *id }));
+    }
+
+    app.update();
+
+    for (node, z_index) in app
+        .world
+        .query::<(&VeloNodeContainer, &NodeZIndex)>()
+        .iter(&app.world)
+    {
+        if node.id == ids[0] {
+            assert_eq!(*z_index, NodeZIndex(3));
+        }
+    }
 }
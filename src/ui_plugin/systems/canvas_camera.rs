@@ -0,0 +1,97 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::components::{CanvasBackground, MainCamera};
+
+use super::ui_helpers::MainPanel;
+use super::{CanvasOffset, CanvasZoom};
+
+// Depth of the canvas drawn by the main camera: arrows lie on the background, nodes are stacked
+// above them and overlays (rubber band, highlights, collaborators) above all nodes
+pub const CANVAS_FAR: f32 = 100_000.;
+pub const BACKGROUND_Z: f32 = -0.05;
+pub const NODE_BASE_Z: f32 = 10.;
+pub const OVERLAY_Z: f32 = 90_000.;
+
+/// Canvas point (from the left bottom corner of the main panel, y axis pointing up) where a
+/// world point is shown.
+pub fn world_to_canvas(point: Vec2, offset: &CanvasOffset, zoom: &CanvasZoom) -> Vec2 {
+    point * zoom.0 + offset.0
+}
+
+/// World point shown at a canvas point.
+pub fn canvas_to_world(point: Vec2, offset: &CanvasOffset, zoom: &CanvasZoom) -> Vec2 {
+    (point - offset.0) / zoom.0
+}
+
+/// Rect of the main panel in window coordinates (from the left bottom corner of the window, y
+/// axis pointing up).
+pub fn panel_rect(node: &Node, transform: &GlobalTransform, window: &Window) -> Rect {
+    let center = Vec2::new(
+        transform.translation().x,
+        window.height() - transform.translation().y,
+    );
+    Rect::from_center_size(center, node.size())
+}
+
+/// World rect shown in the main panel of `size`.
+pub fn view_rect(size: Vec2, offset: &CanvasOffset, zoom: &CanvasZoom) -> Rect {
+    Rect::from_corners(
+        canvas_to_world(Vec2::ZERO, offset, zoom),
+        canvas_to_world(size, offset, zoom),
+    )
+}
+
+/// Points the main camera at the canvas, so the world is drawn in the main panel panned by
+/// `CanvasOffset` and scaled by `CanvasZoom`. The background follows the camera.
+pub fn sync_canvas_camera(
+    zoom: Res<CanvasZoom>,
+    offset: Res<CanvasOffset>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    mut backgrounds: Query<&mut Transform, (With<CanvasBackground>, Without<MainCamera>)>,
+) {
+    let (Ok(window), Ok((panel, panel_transform))) =
+        (windows.get_single(), main_panel_query.get_single())
+    else {
+        return;
+    };
+    let panel = panel_rect(panel, panel_transform, window);
+    let window_center = Vec2::new(window.width(), window.height()) / 2.;
+    let center = canvas_to_world(window_center - panel.min, &offset, &zoom);
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        if transform.translation.truncate() != center {
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+        if projection.scale != 1. / zoom.0 {
+            projection.scale = 1. / zoom.0;
+        }
+    }
+    for mut transform in backgrounds.iter_mut() {
+        let background = Transform::from_translation(center.extend(BACKGROUND_Z))
+            .with_scale(Vec3::splat(1. / zoom.0));
+        if *transform != background {
+            *transform = background;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_mapping() {
+        let offset = CanvasOffset(Vec2::new(100., -50.));
+        let zoom = CanvasZoom(2.);
+        let world = Vec2::new(10., 20.);
+        let canvas = world_to_canvas(world, &offset, &zoom);
+        assert_eq!(canvas, Vec2::new(120., -10.));
+        assert_eq!(canvas_to_world(canvas, &offset, &zoom), world);
+        assert_eq!(
+            view_rect(Vec2::new(400., 300.), &offset, &zoom),
+            Rect::new(-50., 25., 150., 175.)
+        );
+    }
+}
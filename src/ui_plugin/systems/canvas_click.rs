@@ -1,6 +1,34 @@
-use bevy::prelude::*;
+use std::time::Duration;
 
-use super::{ui_helpers::MainPanel, UiState};
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_prototype_lyon::prelude::Path;
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::components::MainCamera;
+use crate::utils::get_timestamp;
+use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeType, TextPos};
+
+use super::{arrow_at_cursor, ui_helpers::MainPanel, Theme, Tool, UiState};
+
+const DOUBLE_CLICK_NODE_SIZE: f32 = 128.;
+// Second click further away than that starts a new double click
+const DOUBLE_CLICK_DISTANCE: f32 = 5.;
+
+/// Records the click and tells whether it completes a double click.
+pub fn is_double_click(last_click: &mut (Duration, Option<Vec2>), cursor: Vec2) -> bool {
+    let now = Duration::from_millis(get_timestamp() as u64);
+    let is_double_click = last_click.1.map_or(false, |last| {
+        last.distance(cursor) < DOUBLE_CLICK_DISTANCE
+            && now - last_click.0 < Duration::from_millis(500)
+    });
+    *last_click = if is_double_click {
+        (Duration::from_secs(0), None)
+    } else {
+        (now, Some(cursor))
+    };
+    is_double_click
+}
 
 pub fn canvas_click(
     interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
@@ -8,7 +36,69 @@ pub fn canvas_click(
 ) {
     for interaction in interaction_query.iter() {
         if *interaction == Interaction::Clicked {
-            ui_state.entity_to_edit = None;
+            ui_state.editing_node = false;
         }
     }
 }
+
+/// Double click on empty canvas creates a node centered at the click and starts editing it.
+pub fn create_node_on_double_click(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    input: Res<Input<KeyCode>>,
+    tool: Res<Tool>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    arrows: Query<(Entity, &Path, &Visibility), With<ArrowMeta>>,
+    theme: Res<Theme>,
+    mut events: EventWriter<AddRectEvent>,
+    mut double_click: Local<(Duration, Option<Vec2>)>,
+) {
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    // other tools and Space+drag use clicks on the canvas
+    if !clicked || *tool != Tool::Select || input.pressed(KeyCode::Space) {
+        return;
+    }
+    let primary_window = windows.single();
+    let Some(cursor) = primary_window.cursor_position() else {
+        return;
+    };
+    if !is_double_click(&mut double_click, cursor) {
+        return;
+    }
+    // double click on an arrow edits its label
+    let (camera, camera_transform) = camera_q.single();
+    if arrow_at_cursor(primary_window, camera, camera_transform, &arrows, &theme).is_some() {
+        return;
+    }
+    let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+    let half_size = DOUBLE_CLICK_NODE_SIZE / 2.;
+    events.send(AddRectEvent {
+        node: JsonNode {
+            id: Uuid::new_v4(),
+            node_type: NodeType::Rect,
+            left: Val::Px(point.x - half_size),
+            bottom: Val::Px(point.y - half_size),
+            width: Val::Px(DOUBLE_CLICK_NODE_SIZE),
+            height: Val::Px(DOUBLE_CLICK_NODE_SIZE),
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::Center,
+                style: theme.node_text_style(),
+            },
+            bg_color: theme.node_background,
+            z_index: 0,
+            locked: false,
+            border: theme.node_border(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: None,
+    });
+}
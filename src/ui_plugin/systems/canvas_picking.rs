@@ -0,0 +1,168 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::components::MainCamera;
+
+use super::ui_helpers::{ArrowLabelView, MainPanel};
+use super::NodeIndex;
+
+// Screen pixels by which handles and badges of a node may stick out of it
+const PICK_MARGIN: f32 = 40.;
+
+/// Whether `point` (world) lies in the sprite, its size is its `custom_size`.
+pub fn sprite_contains(sprite: &Sprite, transform: &GlobalTransform, point: Vec2) -> bool {
+    let Some(size) = sprite.custom_size else {
+        return false;
+    };
+    let local = transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(0.))
+        .truncate();
+    // the anchor is the point of the sprite at its translation
+    let centered = local + sprite.anchor.as_vec() * size;
+    centered.abs().cmple(size / 2.).all()
+}
+
+/// Sets the `Interaction` of canvas sprites (nodes, their handles, badges and chips) the way
+/// `bevy_ui` does for UI nodes: the topmost sprite under the cursor is hovered or clicked, a
+/// clicked sprite stays clicked until the button is released. Sprites are only picked where the
+/// main panel is interacted with (not under panels and menus over the canvas) and the main panel
+/// isn't interacted with where a sprite is picked.
+///
+/// Only the sprites of the nodes near the cursor in the `NodeIndex` and arrow labels are hit
+/// tested, the focus, resize and arrow connection systems react to the changed `Interaction`.
+pub fn pick_canvas_sprites(
+    mouse_buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    node_index: Res<NodeIndex>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut main_panel_query: Query<&mut Interaction, With<MainPanel>>,
+    children_query: Query<&Children>,
+    labels: Query<Entity, With<ArrowLabelView>>,
+    mut sprites: Query<
+        (
+            &Sprite,
+            &GlobalTransform,
+            &ComputedVisibility,
+            &mut Interaction,
+        ),
+        Without<MainPanel>,
+    >,
+    mut interacted: Local<Vec<Entity>>,
+) {
+    let (Ok(window), Ok((camera, camera_transform)), Ok(mut panel_interaction)) = (
+        windows.get_single(),
+        camera_q.get_single(),
+        main_panel_query.get_single_mut(),
+    ) else {
+        return;
+    };
+    if mouse_buttons.just_released(MouseButton::Left) || touches.any_just_released() {
+        for entity in interacted.iter() {
+            if let Ok((_, _, _, mut interaction)) = sprites.get_mut(*entity) {
+                if *interaction == Interaction::Clicked {
+                    *interaction = Interaction::None;
+                }
+            }
+        }
+    }
+    let pressed = mouse_buttons.just_pressed(MouseButton::Left) || touches.any_just_pressed();
+    // touch positions are from the top left corner of the window
+    let cursor = window.cursor_position().or_else(|| {
+        touches
+            .first_pressed_position()
+            .map(|position| Vec2::new(position.x, window.height() - position.y))
+    });
+    let topmost = cursor
+        .filter(|_| *panel_interaction != Interaction::None)
+        .and_then(|cursor| {
+            let point = camera.viewport_to_world_2d(camera_transform, cursor)?;
+            // handles and badges stick out of their node
+            let margin = camera
+                .viewport_to_world_2d(camera_transform, cursor + Vec2::splat(PICK_MARGIN))?
+                - point;
+            node_index
+                .intersecting(Rect::from_center_half_size(point, margin.abs()))
+                .into_iter()
+                .flat_map(|(container, _)| children_query.iter_descendants(container))
+                .chain(labels.iter())
+                .filter_map(|entity| {
+                    let (sprite, transform, visibility, _) = sprites.get(entity).ok()?;
+                    (visibility.is_visible() && sprite_contains(sprite, transform, point))
+                        .then_some((entity, transform.translation().z))
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(entity, _)| entity)
+        });
+    if let Some(entity) = topmost.filter(|entity| !interacted.contains(entity)) {
+        interacted.push(entity);
+    }
+    interacted.retain(|entity| {
+        let Ok((_, _, _, mut interaction)) = sprites.get_mut(*entity) else {
+            return false;
+        };
+        if Some(*entity) == topmost {
+            if pressed {
+                *interaction = Interaction::Clicked;
+            } else if *interaction == Interaction::None {
+                *interaction = Interaction::Hovered;
+            }
+        } else if *interaction == Interaction::Hovered {
+            *interaction = Interaction::None;
+        }
+        *interaction != Interaction::None
+    });
+    // the main panel is below the picked sprite
+    if topmost.is_some()
+        && (pressed || *panel_interaction == Interaction::Hovered)
+        && *panel_interaction != Interaction::None
+    {
+        *panel_interaction = Interaction::None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::sprite::Anchor;
+
+    use super::*;
+
+    #[test]
+    fn test_sprite_contains() {
+        let sprite = Sprite {
+            custom_size: Some(Vec2::new(100., 50.)),
+            ..default()
+        };
+        let transform = GlobalTransform::from_translation(Vec3::new(200., 100., 0.));
+        assert!(sprite_contains(&sprite, &transform, Vec2::new(150., 75.)));
+        assert!(sprite_contains(&sprite, &transform, Vec2::new(249., 124.)));
+        assert!(!sprite_contains(&sprite, &transform, Vec2::new(251., 100.)));
+        // rotated a quarter turn, it's taller than wide
+        let rotated = GlobalTransform::from(
+            Transform::from_xyz(200., 100., 0.)
+                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+        );
+        assert!(sprite_contains(&sprite, &rotated, Vec2::new(200., 145.)));
+        assert!(!sprite_contains(&sprite, &rotated, Vec2::new(245., 100.)));
+        let anchored = Sprite {
+            anchor: Anchor::BottomLeft,
+            ..sprite
+        };
+        assert!(sprite_contains(
+            &anchored,
+            &transform,
+            Vec2::new(290., 140.)
+        ));
+        assert!(!sprite_contains(
+            &anchored,
+            &transform,
+            Vec2::new(190., 100.)
+        ));
+        assert!(!sprite_contains(
+            &Sprite::default(),
+            &transform,
+            Vec2::new(200., 100.)
+        ));
+    }
+}
@@ -0,0 +1,613 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_cosmic_edit::{get_cosmic_text, CosmicEditImage};
+use bevy_ui_borders::BorderColor;
+
+use crate::resources::{AppState, LoadTabRequest, SaveTabRequest};
+use crate::utils::ReflectableUuid;
+use crate::JsonNode;
+
+use super::{
+    ui_helpers::{
+        CanvasOutline, CanvasSearch, CanvasSearchClose, CanvasSearchInput, CanvasSearchModeButton,
+        CanvasSearchResult, CanvasSearchResultButton, CanvasSearchResultList, CanvasSearchText,
+        GenericButton, MainPanel, NodePart, NodeRect, SearchHighlight,
+    },
+    world_to_canvas, Action, CanvasOffset, CanvasZoom, JumpToNodeEvent, PanCanvasEvent, RawText,
+    SelectedNodes, Settings, UiState, VeloNodeContainer,
+};
+
+const SEARCH_WIDTH: f32 = 320.;
+const ROW_HEIGHT: f32 = 24.;
+const FONT_SIZE: f32 = 14.;
+const MAX_RESULTS: usize = 20;
+// Characters of the preview shown before the match
+const PREVIEW_BEFORE: usize = 15;
+const PREVIEW_LENGTH: usize = 45;
+// Frames to wait for the nodes of a loaded tab before giving up the jump
+const JUMP_FRAMES: u8 = 10;
+// Highlights are drawn over the content of matching nodes, below their outline and handles
+const HIGHLIGHT_Z: f32 = 0.44;
+const SELECTED_RESULT_COLOR: Color = Color::rgb(191.0 / 255.0, 219.0 / 255.0, 254.0 / 255.0);
+
+/// Char range of the best match of `query` in `text`, case insensitive. Plain search looks for
+/// the query as is, fuzzy search for its characters in order, preferring the shortest span.
+pub fn find_match(text: &str, query: &str, fuzzy: bool) -> Option<(usize, usize)> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() || query.len() > text.len() {
+        return None;
+    }
+    if !fuzzy {
+        return text
+            .windows(query.len())
+            .position(|window| window == query.as_slice())
+            .map(|start| (start, start + query.len()));
+    }
+    let mut best: Option<(usize, usize)> = None;
+    for start in (0..text.len()).filter(|i| text[*i] == query[0]) {
+        let mut matched = 1;
+        let mut end = start + 1;
+        while matched < query.len() && end < text.len() {
+            if text[end] == query[matched] {
+                matched += 1;
+            }
+            end += 1;
+        }
+        if matched < query.len() {
+            break;
+        }
+        if best.map_or(true, |(s, e)| end - start < e - s) {
+            best = Some((start, end));
+        }
+    }
+    best
+}
+
+/// Single line excerpt of `text` around the match starting at char `start`.
+pub fn match_preview(text: &str, start: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let start = start.min(chars.len());
+    let line_start = chars[..start]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let line_end = chars[start..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |i| start + i);
+    let from = line_start.max(start.saturating_sub(PREVIEW_BEFORE));
+    let to = line_end.min(from + PREVIEW_LENGTH);
+    let mut preview: String = chars[from..to].iter().collect();
+    if from > line_start {
+        preview.insert(0, '…');
+    }
+    if to < line_end {
+        preview.push('…');
+    }
+    preview.trim().to_string()
+}
+
+/// Cmd/Ctrl+F, or the chord bound to "Find", opens the search bar or focuses it, Escape closes
+/// it.
+pub fn toggle_canvas_search(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    searches: Query<Entity, With<CanvasSearch>>,
+    input_query: Query<&Interaction, (Changed<Interaction>, With<CanvasSearchInput>)>,
+    close_query: Query<&Interaction, (Changed<Interaction>, With<CanvasSearchClose>)>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    settings: Res<Settings>,
+) {
+    let clicked = input_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let search = searches.get_single().ok();
+    if let Some(search) = search {
+        let closed = close_query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Clicked);
+        if closed || (ui_state.canvas_search_to_edit && input.just_pressed(KeyCode::Escape)) {
+            ui_state.canvas_search_to_edit = false;
+            commands.entity(search).despawn_recursive();
+            return;
+        }
+    }
+    let shortcut = settings.keybindings.just_pressed(Action::Find, &input);
+    if !clicked && !shortcut || ui_state.modal_id.is_some() {
+        return;
+    }
+    // typed text would go to the edited node otherwise
+    *ui_state = UiState::default();
+    ui_state.canvas_search_to_edit = true;
+    if search.is_none() {
+        let search = spawn_canvas_search(&mut commands);
+        commands.entity(main_panel_query.single()).add_child(search);
+    }
+}
+
+fn text_style(color: Color) -> TextStyle {
+    TextStyle {
+        font_size: FONT_SIZE,
+        color,
+        ..default()
+    }
+}
+
+fn spawn_canvas_search(commands: &mut Commands) -> Entity {
+    let border_color = Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0);
+    let button_color = Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0);
+    let button_style = Style {
+        size: Size::height(Val::Px(20.)),
+        padding: UiRect::horizontal(Val::Px(6.)),
+        margin: UiRect::left(Val::Px(4.)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(50.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    margin: UiRect::left(Val::Px(-SEARCH_WIDTH / 2.)),
+                    size: Size::width(Val::Px(SEARCH_WIDTH)),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                // clicks on the search bar don't reach the canvas
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Local(i32::MAX),
+                ..default()
+            },
+            BorderColor(border_color),
+            CanvasSearch::default(),
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|builder| {
+                    builder
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    flex_grow: 1.,
+                                    size: Size::height(Val::Px(ROW_HEIGHT)),
+                                    align_items: AlignItems::Center,
+                                    padding: UiRect::horizontal(Val::Px(4.)),
+                                    border: UiRect::all(Val::Px(1.)),
+                                    overflow: Overflow::Hidden,
+                                    ..default()
+                                },
+                                background_color: Color::WHITE.into(),
+                                ..default()
+                            },
+                            BorderColor(border_color),
+                            CanvasSearchInput,
+                        ))
+                        .with_children(|builder| {
+                            builder.spawn((
+                                TextBundle::from_section("|", text_style(Color::BLACK)),
+                                CanvasSearchText,
+                            ));
+                        });
+                    builder
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: button_color.into(),
+                                ..default()
+                            },
+                            CanvasSearchModeButton,
+                            GenericButton,
+                        ))
+                        .with_children(|builder| {
+                            builder
+                                .spawn(TextBundle::from_section("plain", text_style(Color::BLACK)));
+                        });
+                    builder
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style,
+                                background_color: button_color.into(),
+                                ..default()
+                            },
+                            CanvasSearchClose,
+                            GenericButton,
+                        ))
+                        .with_children(|builder| {
+                            builder.spawn(TextBundle::from_section("x", text_style(Color::BLACK)));
+                        });
+                });
+            builder.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                },
+                CanvasSearchResultList,
+            ));
+        })
+        .id()
+}
+
+/// Typing edits the query, Up/Down choose a result and Enter jumps to it.
+pub fn canvas_search_input(
+    mut char_events: EventReader<ReceivedCharacter>,
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    mut searches: Query<&mut CanvasSearch>,
+    mut events: EventWriter<JumpToNodeEvent>,
+) {
+    let Ok(mut search) = searches.get_single_mut() else {
+        char_events.clear();
+        return;
+    };
+    if !ui_state.canvas_search_to_edit {
+        char_events.clear();
+        return;
+    }
+    let command = input.any_pressed([
+        KeyCode::RWin,
+        KeyCode::LWin,
+        KeyCode::RControl,
+        KeyCode::LControl,
+    ]);
+    let mut query = search.query.clone();
+    for event in char_events.iter() {
+        // e.g. F of the shortcut opening the search
+        if !command && !event.char.is_control() {
+            query.push(event.char);
+        }
+    }
+    if input.just_pressed(KeyCode::Back) {
+        query.pop();
+    }
+    if query != search.query {
+        search.query = query;
+    }
+    if search.results.is_empty() {
+        return;
+    }
+    if input.just_pressed(KeyCode::Down) {
+        search.selected = (search.selected + 1) % search.results.len();
+    }
+    if input.just_pressed(KeyCode::Up) {
+        search.selected = search
+            .selected
+            .checked_sub(1)
+            .unwrap_or(search.results.len() - 1);
+    }
+    if input.just_pressed(KeyCode::Return) {
+        let result = &search.results[search.selected];
+        events.send(JumpToNodeEvent {
+            tab_id: result.tab_id,
+            node_id: Some(result.node_id),
+        });
+    }
+}
+
+pub fn canvas_search_buttons(
+    mode_buttons: Query<
+        (&Interaction, &Children),
+        (Changed<Interaction>, With<CanvasSearchModeButton>),
+    >,
+    result_buttons: Query<(&Interaction, &CanvasSearchResultButton), Changed<Interaction>>,
+    mut text_query: Query<&mut Text>,
+    mut searches: Query<&mut CanvasSearch>,
+    mut events: EventWriter<JumpToNodeEvent>,
+) {
+    let Ok(mut search) = searches.get_single_mut() else {
+        return;
+    };
+    for (interaction, children) in mode_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        search.fuzzy = !search.fuzzy;
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value = if search.fuzzy { "fuzzy" } else { "plain" }.to_string();
+            }
+        }
+    }
+    for (interaction, button) in result_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Some(result) = search.results.get(button.index) {
+            events.send(JumpToNodeEvent {
+                tab_id: result.tab_id,
+                node_id: Some(result.node_id),
+            });
+            search.selected = button.index;
+        }
+    }
+}
+
+/// Searches text of the nodes of all tabs when the query changes: the active tab is searched
+/// as shown, other tabs as of their last checkpoint. Redraws the query and the results.
+pub fn update_canvas_search(
+    mut commands: Commands,
+    app_state: Res<AppState>,
+    ui_state: Res<UiState>,
+    mut searches: Query<&mut CanvasSearch>,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    mut search_text_query: Query<&mut Text, With<CanvasSearchText>>,
+    list_query: Query<Entity, With<CanvasSearchResultList>>,
+    mut searched: Local<(String, bool)>,
+) {
+    let Ok(mut search) = searches.get_single_mut() else {
+        *searched = (String::new(), false);
+        return;
+    };
+    let mut changed = false;
+    if *searched != (search.query.clone(), search.fuzzy) {
+        *searched = (search.query.clone(), search.fuzzy);
+        let results = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get(&doc_id))
+            .map_or(vec![], |doc| {
+                let mut results = vec![];
+                for tab in doc.tabs.iter() {
+                    let texts: Vec<(ReflectableUuid, String)> = if tab.is_active {
+                        text_query
+                            .iter()
+                            .map(|(raw_text, cosmic_edit)| {
+                                (raw_text.id, get_cosmic_text(&cosmic_edit.editor))
+                            })
+                            .collect()
+                    } else {
+                        tab.checkpoint.as_ref().map_or(vec![], |checkpoint| {
+                            checkpoint_texts(checkpoint).unwrap_or_default()
+                        })
+                    };
+                    let mut matches: Vec<_> = texts
+                        .into_iter()
+                        .filter_map(|(node_id, text)| {
+                            let (start, end) = find_match(&text, &search.query, search.fuzzy)?;
+                            Some((end - start, start, node_id, match_preview(&text, start)))
+                        })
+                        .collect();
+                    // the tightest matches go first
+                    matches.sort_by_key(|(span, start, ..)| (*span, *start));
+                    results.extend(matches.into_iter().map(|(_, _, node_id, preview)| {
+                        CanvasSearchResult {
+                            tab_id: tab.id,
+                            node_id,
+                            tab_name: tab.name.clone(),
+                            preview,
+                        }
+                    }));
+                }
+                results.truncate(MAX_RESULTS);
+                results
+            });
+        search.results = results;
+        search.selected = 0;
+        changed = true;
+    }
+    if changed || search.is_changed() || ui_state.is_changed() {
+        for mut text in search_text_query.iter_mut() {
+            let (value, color) = if ui_state.canvas_search_to_edit {
+                (format!("{}|", search.query), Color::BLACK)
+            } else if search.query.is_empty() {
+                ("Search nodes (Cmd/Ctrl+F)".to_string(), Color::GRAY)
+            } else {
+                (search.query.clone(), Color::BLACK)
+            };
+            text.sections[0].value = value;
+            text.sections[0].style.color = color;
+        }
+    }
+    if !changed && !search.is_changed() {
+        return;
+    }
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|builder| {
+        if !search.query.is_empty() && search.results.is_empty() {
+            builder.spawn(TextBundle::from_section(
+                "no matches",
+                text_style(Color::GRAY),
+            ));
+        }
+        for (index, result) in search.results.iter().enumerate() {
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                            align_items: AlignItems::Center,
+                            padding: UiRect::horizontal(Val::Px(4.)),
+                            margin: UiRect::top(Val::Px(2.)),
+                            overflow: Overflow::Hidden,
+                            ..default()
+                        },
+                        background_color: if index == search.selected {
+                            SELECTED_RESULT_COLOR.into()
+                        } else {
+                            Color::WHITE.into()
+                        },
+                        ..default()
+                    },
+                    CanvasSearchResultButton { index },
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    builder.spawn(TextBundle::from_sections([
+                        TextSection::new(format!("{}: ", result.tab_name), text_style(Color::GRAY)),
+                        TextSection::new(result.preview.clone(), text_style(Color::BLACK)),
+                    ]));
+                });
+        }
+    });
+}
+
+/// Ids and texts of the nodes saved in a checkpoint of a tab.
+pub fn checkpoint_texts(checkpoint: &str) -> Option<Vec<(ReflectableUuid, String)>> {
+    let json: serde_json::Value = serde_json::from_str(checkpoint).ok()?;
+    let nodes = json["nodes"].as_array()?;
+    Some(
+        nodes
+            .iter()
+            .filter_map(|node| serde_json::from_value::<JsonNode>(node.clone()).ok())
+            .map(|node| (ReflectableUuid(node.id), node.text.text))
+            .collect(),
+    )
+}
+
+/// Highlights nodes of the active tab matching the search.
+pub fn highlight_search_matches(
+    mut commands: Commands,
+    searches: Query<&CanvasSearch>,
+    containers: Query<(Entity, &VeloNodeContainer)>,
+    highlights: Query<(Entity, &Parent), With<SearchHighlight>>,
+) {
+    let search = searches.get_single().ok();
+    for (entity, container) in containers.iter() {
+        let matches = search.map_or(false, |search| {
+            search
+                .results
+                .iter()
+                .any(|result| result.node_id == container.id)
+        });
+        let highlight = highlights
+            .iter()
+            .find(|(_, parent)| parent.get() == entity)
+            .map(|(highlight, _)| highlight);
+        match (matches, highlight) {
+            (true, None) => {
+                let highlight = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::YELLOW.with_a(0.15),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        // highlighted nodes can still be selected and edited, the highlight
+                        // isn't picked
+                        NodePart::fill(HIGHLIGHT_Z),
+                        CanvasOutline::new(Color::ORANGE, 3.),
+                        SearchHighlight,
+                    ))
+                    .id();
+                commands.entity(entity).add_child(highlight);
+            }
+            (false, Some(highlight)) => {
+                commands.entity(highlight).despawn_recursive();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Loads the tab if it isn't active, then centers the canvas on the node (if any) once it's
+/// spawned and selects it.
+pub fn jump_to_node(
+    mut commands: Commands,
+    mut events: EventReader<JumpToNodeEvent>,
+    mut app_state: ResMut<AppState>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    containers: Query<(&NodeRect, &VeloNodeContainer)>,
+    main_panel_query: Query<&Node, With<MainPanel>>,
+    zoom: Res<CanvasZoom>,
+    offset: Res<CanvasOffset>,
+    mut pan_events: EventWriter<PanCanvasEvent>,
+    mut pending: Local<Option<(ReflectableUuid, u8)>>,
+) {
+    for event in events.iter() {
+        *pending = event.node_id.map(|node_id| (node_id, JUMP_FRAMES));
+        let Some(doc_id) = app_state.current_document else {
+            continue;
+        };
+        let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+            continue;
+        };
+        let Some(active_tab) = doc.tabs.iter().find(|tab| tab.is_active).map(|tab| tab.id) else {
+            continue;
+        };
+        if active_tab == event.tab_id || !doc.tabs.iter().any(|tab| tab.id == event.tab_id) {
+            continue;
+        }
+        commands.insert_resource(SaveTabRequest {
+            doc_id,
+            tab_id: active_tab,
+        });
+        for tab in doc.tabs.iter_mut() {
+            tab.is_active = tab.id == event.tab_id;
+        }
+        commands.insert_resource(LoadTabRequest {
+            doc_id,
+            tab_id: event.tab_id,
+        });
+    }
+    let Some((node_id, frames)) = *pending else {
+        return;
+    };
+    let Some((rect, _)) = containers
+        .iter()
+        .find(|(_, container)| container.id == node_id)
+    else {
+        *pending = frames.checked_sub(1).map(|frames| (node_id, frames));
+        return;
+    };
+    *pending = None;
+    let Ok(panel) = main_panel_query.get_single() else {
+        return;
+    };
+    let center = world_to_canvas(rect.center(), &offset, &zoom);
+    pan_events.send(PanCanvasEvent {
+        delta: panel.size() / 2. - center,
+    });
+    ui_state.editing_node = false;
+    selected_nodes.0 = vec![node_id];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_match() {
+        assert_eq!(find_match("Hello World", "world", false), Some((6, 11)));
+        assert_eq!(find_match("Hello World", "wrld", false), None);
+        assert_eq!(find_match("Hello World", "wrld", true), Some((6, 11)));
+        // the shortest span wins
+        assert_eq!(find_match("a-b----ab", "ab", true), Some((7, 9)));
+        assert_eq!(find_match("Hello", "", true), None);
+        assert_eq!(find_match("Hi", "Hello", true), None);
+    }
+
+    #[test]
+    fn test_match_preview() {
+        assert_eq!(match_preview("first line\nsecond line", 11), "second line");
+        let text = format!("{}match{}", "a".repeat(30), "b".repeat(60));
+        let preview = match_preview(&text, 30);
+        assert!(preview.starts_with('…'));
+        assert!(preview.ends_with('…'));
+        assert!(preview.contains("match"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use super::{decrypt, document_bytes, is_encrypted, read_doc};
+use crate::components::Doc;
+
+pub const EXPORT_USAGE: &str = "usage: velo export <doc.json> --format png|svg|pdf|md [--tab <name or number>] [--output <file>] [--passphrase <passphrase>]";
+
+/// Formats of `velo export`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Png,
+    Svg,
+    Pdf,
+    Md,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "svg" => Some(Self::Svg),
+            "pdf" => Some(Self::Pdf),
+            "md" | "markdown" => Some(Self::Md),
+            _ => None,
+        }
+    }
+
+    /// Extension of the exported file, which `document_bytes` picks the format from.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+            Self::Md => "md",
+        }
+    }
+}
+
+/// Document export run by `velo export` without opening a window.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ExportJob {
+    pub input: PathBuf,
+    /// Name or number (from 1) of the exported tab, the active one by default. PDF exports all
+    /// tabs unless one is given.
+    pub tab: Option<String>,
+    pub format: ExportFormat,
+    /// Input file with the extension of the format by default.
+    pub output: Option<PathBuf>,
+    pub passphrase: Option<String>,
+}
+
+impl ExportJob {
+    /// Reads the job from the arguments following `velo export`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut input = None;
+        let mut tab = None;
+        let mut format = None;
+        let mut output = None;
+        let mut passphrase = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value of {}\n{}", arg, EXPORT_USAGE))
+            };
+            match arg.as_str() {
+                "--tab" | "-t" => tab = Some(value()?),
+                "--format" | "-f" => {
+                    let name = value()?;
+                    format =
+                        Some(ExportFormat::from_name(&name).ok_or_else(|| {
+                            format!("unknown format \"{}\"\n{}", name, EXPORT_USAGE)
+                        })?);
+                }
+                "--output" | "-o" => output = Some(PathBuf::from(value()?)),
+                "--passphrase" => passphrase = Some(value()?),
+                _ if arg.starts_with('-') => {
+                    return Err(format!("unknown option {}\n{}", arg, EXPORT_USAGE))
+                }
+                _ if input.is_none() => input = Some(PathBuf::from(arg)),
+                _ => return Err(format!("unexpected argument {}\n{}", arg, EXPORT_USAGE)),
+            }
+        }
+        // the format can be left out when the output file has an extension of one
+        let format = format
+            .or_else(|| {
+                let extension = output.as_ref()?.extension()?.to_str()?;
+                ExportFormat::from_name(extension)
+            })
+            .ok_or_else(|| format!("missing --format\n{}", EXPORT_USAGE))?;
+        Ok(Self {
+            input: input.ok_or_else(|| format!("missing document\n{}", EXPORT_USAGE))?,
+            tab,
+            format,
+            output,
+            passphrase,
+        })
+    }
+
+    pub fn output_path(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| self.input.with_extension(self.format.extension()))
+    }
+}
+
+/// Result of the export job, the path of the written file.
+#[derive(Resource)]
+struct ExportOutcome(Result<PathBuf, String>);
+
+fn read_input(path: &Path, passphrase: Option<&str>) -> Result<Doc, String> {
+    let data = std::fs::read(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let data = if is_encrypted(&data) {
+        let passphrase = passphrase
+            .ok_or_else(|| format!("{} is encrypted, use --passphrase", path.display()))?;
+        decrypt(&data, passphrase).map_err(|e| e.to_string())?
+    } else {
+        data
+    };
+    read_doc(data.as_slice()).map_err(|e| format!("can't read {}: {}", path.display(), e))
+}
+
+/// Keeps the tab with the name, or the number counted from 1, as the only and active tab.
+pub fn select_tab(doc: &mut Doc, tab: &str) -> Result<(), String> {
+    let index = doc
+        .tabs
+        .iter()
+        .position(|doc_tab| doc_tab.name == tab)
+        .or_else(|| {
+            tab.parse::<usize>()
+                .ok()
+                .filter(|number| (1..=doc.tabs.len()).contains(number))
+                .map(|number| number - 1)
+        })
+        .ok_or_else(|| {
+            let names: Vec<_> = doc
+                .tabs
+                .iter()
+                .map(|doc_tab| format!("\"{}\"", doc_tab.name))
+                .collect();
+            format!("no tab \"{}\", tabs are {}", tab, names.join(", "))
+        })?;
+    let mut tab = doc.tabs.swap_remove(index);
+    tab.is_active = true;
+    doc.tabs = vec![tab];
+    Ok(())
+}
+
+fn export(job: &ExportJob) -> Result<PathBuf, String> {
+    let mut doc = read_input(&job.input, job.passphrase.as_deref())?;
+    if let Some(tab) = job.tab.as_ref() {
+        select_tab(&mut doc, tab)?;
+    }
+    let output = job.output_path();
+    // the output extension may not be the one of the format
+    let bytes = document_bytes(
+        &PathBuf::from("export").with_extension(job.format.extension()),
+        &doc,
+        None,
+    )
+    .map_err(|e| e.to_string())?;
+    std::fs::write(&output, bytes)
+        .map_err(|e| format!("can't write {}: {}", output.display(), e))?;
+    Ok(output)
+}
+
+fn run_export_job(mut commands: Commands, job: Res<ExportJob>) {
+    commands.insert_resource(ExportOutcome(export(&job)));
+}
+
+/// Runs the export in an app without window or rendering, for scripts and CI pipelines.
+pub fn run_export(job: ExportJob) -> Result<PathBuf, String> {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(job)
+        .add_system(run_export_job);
+    app.update();
+    app.world
+        .remove_resource::<ExportOutcome>()
+        .map_or_else(|| Err("export didn't run".to_string()), |outcome| outcome.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Tab;
+    use crate::utils::ReflectableUuid;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn tab(name: &str, is_active: bool) -> Tab {
+        Tab {
+            id: ReflectableUuid::generate(),
+            name: name.to_string(),
+            checkpoint: Some("{\"nodes\":[]}".to_string()),
+            zoom: 1.,
+            color: None,
+            icon: None,
+            pinned: false,
+            offset: [0., 0.],
+            selected: vec![],
+            is_active,
+        }
+    }
+
+    #[test]
+    fn test_export_job_from_args() {
+        let job =
+            ExportJob::from_args(&args(&["doc.json", "--tab", "Plan", "--format", "svg"])).unwrap();
+        assert_eq!(job.input, PathBuf::from("doc.json"));
+        assert_eq!(job.tab.as_deref(), Some("Plan"));
+        assert_eq!(job.format, ExportFormat::Svg);
+        assert_eq!(job.output_path(), PathBuf::from("doc.svg"));
+
+        let job = ExportJob::from_args(&args(&["-o", "out/plan.PDF", "doc.json"])).unwrap();
+        assert_eq!(job.format, ExportFormat::Pdf);
+        assert_eq!(job.output_path(), PathBuf::from("out/plan.PDF"));
+
+        assert!(ExportJob::from_args(&args(&["doc.json"])).is_err());
+        assert!(ExportJob::from_args(&args(&["doc.json", "--format", "gif"])).is_err());
+        assert!(ExportJob::from_args(&args(&["--format", "md"])).is_err());
+        assert!(ExportJob::from_args(&args(&["doc.json", "--format"])).is_err());
+    }
+
+    #[test]
+    fn test_select_tab() {
+        let doc = Doc {
+            id: ReflectableUuid::generate(),
+            name: "Doc".to_string(),
+            tabs: vec![tab("Plan", true), tab("2", false), tab("Notes", false)],
+            tags: vec![],
+            assets: Default::default(),
+            revision: 0,
+        };
+        let mut by_name = doc.clone();
+        select_tab(&mut by_name, "Notes").unwrap();
+        assert_eq!(by_name.tabs.len(), 1);
+        assert_eq!(by_name.tabs[0].name, "Notes");
+        assert!(by_name.tabs[0].is_active);
+        // names are matched before numbers
+        let mut by_number = doc.clone();
+        select_tab(&mut by_number, "2").unwrap();
+        assert_eq!(by_number.tabs[0].name, "2");
+        let mut by_number = doc.clone();
+        select_tab(&mut by_number, "3").unwrap();
+        assert_eq!(by_number.tabs[0].name, "Notes");
+        assert!(select_tab(&mut doc.clone(), "4").is_err());
+    }
+}
@@ -1,79 +1,150 @@
-use bevy::{
-    prelude::*,
-    text::{PositionedGlyph, TextLayoutInfo},
-    window::PrimaryWindow,
-};
+use bevy::{prelude::*, sprite::Anchor, text::TextLayoutInfo, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicEditImage;
 use bevy_markdown::BevyMarkdownNode;
+use cosmic_text::Edit;
+use linkify::{LinkFinder, LinkKind};
+
+use super::ui_helpers::Localized;
+use super::{
+    parse_internal_link, Notification, OpenInternalLinkEvent, RawText, SelectedNodes, UiState,
+    VeloNode,
+};
+use crate::components::MainCamera;
 
-use super::{UiState, VeloNode};
+/// Section of the laid out text under the world `point`.
+pub fn section_at(
+    point: Vec2,
+    transform: &GlobalTransform,
+    layout: &TextLayoutInfo,
+    anchor: &Anchor,
+    scale_factor: f32,
+) -> Option<usize> {
+    // glyphs are positioned in physical pixels from the left bottom corner of the text
+    let local = transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(0.))
+        .truncate();
+    let position = local * scale_factor + layout.size * (anchor.as_vec() + 0.5);
+    layout
+        .glyphs
+        .iter()
+        .find(|glyph| Rect::from_center_size(glyph.position, glyph.size).contains(position))
+        .map(|glyph| glyph.section_index)
+}
 
 pub fn clickable_links(
     mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
-    mut markdown_text_query: Query<
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    markdown_text_query: Query<
         (
-            &Node,
             &GlobalTransform,
-            &mut Text,
             &TextLayoutInfo,
+            &Anchor,
             &BevyMarkdownNode,
         ),
         With<BevyMarkdownNode>,
     >,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<VeloNode>)>,
     mut ui_state: ResMut<UiState>,
+    mut internal_links: EventWriter<OpenInternalLinkEvent>,
+    mut notifications: EventWriter<Notification>,
 ) {
-    if ui_state.hold_entity.is_some() {
+    if ui_state.holding_node {
         return;
     }
     let mut primary_window = primary_window.iter_mut().next().unwrap();
     let scale_factor = primary_window.scale_factor() as f32;
 
-    if let Some(cursor_position) = primary_window.cursor_position() {
-        let window_height = primary_window.height();
-        let pos = Vec2::new(cursor_position.x, window_height - cursor_position.y);
-        for (node, transform, text, text_layout_info, markdown_text) in
-            markdown_text_query.iter_mut()
-        {
-            let mut str = "".to_string();
-            let mut text_copy = text.clone();
-            for section in text_copy.sections.iter_mut() {
-                str = format!("{}{}", str, section.value.clone());
-            }
-            let link_sections = markdown_text.link_sections.clone();
-
-            let offset = transform.translation().truncate() - 0.5 * node.size();
-            for PositionedGlyph {
-                position,
-                section_index,
-                size,
-                ..
-            } in &text_layout_info.glyphs
-            {
-                let rect = bevy::math::Rect::from_center_size(
-                    offset + *position / scale_factor,
-                    *size / scale_factor,
-                );
-                if rect.contains(pos) {
-                    if let Some(link) = link_sections[*section_index].clone() {
-                        primary_window.cursor.icon = CursorIcon::Hand;
-                        for interaction in &mut interaction_query {
-                            if *interaction == Interaction::Clicked {
-                                #[cfg(not(target_arch = "wasm32"))]
-                                open::that(link.clone()).unwrap();
-                                #[cfg(target_arch = "wasm32")]
-                                open_url_in_new_tab(link.clone().as_str()).unwrap();
-                                ui_state.entity_to_edit = None;
-                            }
-                        }
-                    } else {
-                        primary_window.cursor.icon = CursorIcon::Text;
+    let (camera, camera_transform) = camera_q.single();
+    if let Some(point) = primary_window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    {
+        for (transform, text_layout_info, anchor, markdown_text) in markdown_text_query.iter() {
+            let Some(section_index) =
+                section_at(point, transform, text_layout_info, anchor, scale_factor)
+            else {
+                continue;
+            };
+            if let Some(link) = markdown_text.link_sections[section_index].clone() {
+                primary_window.cursor.icon = CursorIcon::Hand;
+                for interaction in &mut interaction_query {
+                    if *interaction == Interaction::Clicked {
+                        open_link(link.as_str(), &mut internal_links, &mut notifications);
+                        ui_state.editing_node = false;
                     }
                 }
+            } else {
+                primary_window.cursor.icon = CursorIcon::Text;
             }
         }
     }
 }
 
+/// Cmd/Ctrl+Click opens link under the caret of the edited node, plain click only moves the caret.
+pub fn clickable_editor_links(
+    input: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    raw_text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    mut internal_links: EventWriter<OpenInternalLinkEvent>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let command = input.any_pressed([
+        KeyCode::RWin,
+        KeyCode::LWin,
+        KeyCode::RControl,
+        KeyCode::LControl,
+    ]);
+    // caret is already moved to the clicked position when the button is released
+    if !command || !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(entity_to_edit) = selected_nodes.edited(&ui_state) else {
+        return;
+    };
+    for (raw_text, cosmic_edit) in raw_text_query.iter() {
+        if raw_text.id != entity_to_edit {
+            continue;
+        }
+        let cursor = cosmic_edit.editor.cursor();
+        let Some(line) = cosmic_edit.editor.buffer().lines.get(cursor.line) else {
+            continue;
+        };
+        let mut finder = LinkFinder::new();
+        finder.kinds(&[LinkKind::Url]);
+        if let Some(link) = finder
+            .links(line.text())
+            .find(|link| link.start() <= cursor.index && cursor.index <= link.end())
+        {
+            open_link(link.as_str(), &mut internal_links, &mut notifications);
+        }
+    }
+}
+
+/// Internal links are followed inside the app, other links are opened by the OS/browser.
+pub fn open_link(
+    link: &str,
+    internal_links: &mut EventWriter<OpenInternalLinkEvent>,
+    notifications: &mut EventWriter<Notification>,
+) {
+    if let Some(link) = parse_internal_link(link) {
+        internal_links.send(OpenInternalLinkEvent { link });
+        return;
+    }
+    // e.g. attached file was moved
+    #[cfg(not(target_arch = "wasm32"))]
+    let result = open::that(link).map_err(|err| err.to_string());
+    #[cfg(target_arch = "wasm32")]
+    let result = open_url_in_new_tab(link).map_err(|err| format!("{:?}", err));
+    if let Err(err) = result {
+        let message = Localized::new("Couldn't open {}").with_args([link]);
+        notifications.send(Notification::warn(message).with_details(err));
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 pub fn open_url_in_new_tab(url: &str) -> Result<(), wasm_bindgen::prelude::JsValue> {
     use wasm_bindgen::prelude::*;
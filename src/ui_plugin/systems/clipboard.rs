@@ -0,0 +1,188 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::{CosmicEditImage, FontSystemState};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::{
+    ArrowLabel, ArrowMeta, ArrowPins, ArrowRelation, ArrowStyle,
+};
+use crate::canvas::arrow::events::CreateArrowEvent;
+use crate::utils::{decode_image_base64, ReflectableUuid};
+use crate::JsonNode;
+
+use super::ui_helpers::Localized;
+use super::{
+    json_images, json_node_meta, json_nodes, notify, spawn_node, Action, CanvasZoom,
+    ContextMenuAction, ContextMenuEvent, History, NodeContainerQuery, NodeMeta, NodeQuery,
+    Notification, RawText, SelectedNodes, Settings, UiState,
+};
+
+// Marks clipboard text as velo nodes payload
+const CLIPBOARD_PAYLOAD_KIND: &str = "velo/nodes";
+// Offset of pasted nodes from the copied ones
+const PASTE_OFFSET: f32 = 20.;
+
+pub fn copy_nodes(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    images: Res<Assets<Image>>,
+    node_container_query: NodeContainerQuery,
+    node_query: NodeQuery,
+    arrows: Query<
+        (
+            &ArrowMeta,
+            &ArrowStyle,
+            Option<&ArrowLabel>,
+            Option<&ArrowRelation>,
+            &ArrowPins,
+            &Visibility,
+        ),
+        With<ArrowMeta>,
+    >,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    settings: Res<Settings>,
+    mut notifications: EventWriter<Notification>,
+) {
+    if !settings.keybindings.just_pressed(Action::Copy, &input)
+        || !ui_state.is_canvas_focused()
+        || selected_nodes.0.is_empty()
+    {
+        return;
+    }
+    // copied geometry is in world coordinates, the same as in saved tabs
+    let nodes: Vec<_> = json_nodes(&node_query, &node_container_query, &text_query)
+        .into_iter()
+        .filter(|(node, _)| selected_nodes.0.contains(&ReflectableUuid(node.id)))
+        .collect();
+    let mut json = json!({
+        "kind": CLIPBOARD_PAYLOAD_KIND,
+        "images": json_images(&nodes, &images),
+        "nodes": nodes.into_iter().map(|(node, _)| node).collect::<Vec<_>>(),
+        "arrows": [],
+    });
+    // only arrows connecting copied nodes are copied
+    let json_arrows = json["arrows"].as_array_mut().unwrap();
+    for (arrow_meta, style, label, relation, pins, visibility) in arrows.iter() {
+        if visibility != Visibility::Hidden
+            && selected_nodes.0.contains(&arrow_meta.start.id)
+            && selected_nodes.0.contains(&arrow_meta.end.id)
+        {
+            let mut json_arrow = json!(arrow_meta);
+            json_arrow["style"] = json!(style);
+            if let Some(label) = label.filter(|label| !label.0.is_empty()) {
+                json_arrow["label"] = json!(label.0);
+            }
+            if let Some(relation) = relation {
+                json_arrow["relation"] = json!(relation.0);
+            }
+            if *pins != ArrowPins::default() {
+                json_arrow["pins"] = json!(pins);
+            }
+            json_arrows.push(json_arrow);
+        }
+    }
+    if let Err(e) =
+        arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(json.to_string()))
+    {
+        let message = Localized::new("Couldn't copy to the clipboard");
+        notifications.send(Notification::error(message).with_details(e));
+    }
+}
+
+pub fn paste_nodes(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut history: ResMut<History>,
+    asset_server: Res<AssetServer>,
+    mut res_images: ResMut<Assets<Image>>,
+    mut font_system_state: ResMut<FontSystemState>,
+    mut create_arrow: EventWriter<CreateArrowEvent>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    zoom: Res<CanvasZoom>,
+    mut context_menu_events: EventReader<ContextMenuEvent>,
+    settings: Res<Settings>,
+) {
+    let from_menu = context_menu_events
+        .iter()
+        .any(|event| event.action == ContextMenuAction::Paste);
+    if !from_menu
+        && (!settings.keybindings.just_pressed(Action::Paste, &input)
+            || !ui_state.is_canvas_focused())
+    {
+        return;
+    }
+    let mut clipboard = match arboard::Clipboard::new() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            // pasting with the keyboard is reported by `insert_from_clipboard`
+            if from_menu {
+                let message = Localized::new("Couldn't paste from the clipboard");
+                notify(&mut commands, Notification::error(message).with_details(e));
+            }
+            return;
+        }
+    };
+    let json: Value = match clipboard
+        .get_text()
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+    {
+        Some(json) => json,
+        None => return,
+    };
+    if json["kind"] != CLIPBOARD_PAYLOAD_KIND {
+        return;
+    }
+    let window = windows.single();
+    let images = json["images"].as_object().unwrap();
+    let nodes = json["nodes"].as_array().unwrap();
+    let mut new_ids: HashMap<Uuid, ReflectableUuid> = HashMap::new();
+    for node in nodes.iter() {
+        let json_node: JsonNode = serde_json::from_value(node.clone()).unwrap();
+        let image = images
+            .get(&json_node.id.to_string())
+            .map(|image| res_images.add(decode_image_base64(image.as_str().unwrap())));
+        let id = ReflectableUuid::generate();
+        new_ids.insert(json_node.id, id);
+        history.record_created(id);
+        let meta = json_node_meta(json_node, image, zoom.0, window.scale_factor() as f32);
+        spawn_node(
+            &mut commands,
+            &asset_server,
+            &mut font_system_state,
+            NodeMeta {
+                id,
+                position: meta.position + Vec2::new(PASTE_OFFSET, -PASTE_OFFSET),
+                ..meta
+            },
+        );
+    }
+    for arrow in json["arrows"].as_array().unwrap().iter() {
+        let mut arrow_meta: ArrowMeta = serde_json::from_value(arrow.clone()).unwrap();
+        if let (Some(start), Some(end)) = (
+            new_ids.get(&arrow_meta.start.id.0),
+            new_ids.get(&arrow_meta.end.id.0),
+        ) {
+            arrow_meta.start.id = *start;
+            arrow_meta.end.id = *end;
+            create_arrow.send(CreateArrowEvent {
+                start: arrow_meta.start,
+                end: arrow_meta.end,
+                arrow_type: arrow_meta.arrow_type,
+                style: ArrowStyle::from_json(arrow, arrow_meta.arrow_type),
+                label: arrow["label"].as_str().map(|label| label.to_string()),
+                relation: arrow["relation"]
+                    .as_str()
+                    .map(|relation| relation.to_string()),
+                pins: serde_json::from_value(arrow["pins"].clone()).unwrap_or_default(),
+                // waypoints belong to the layout of the copied nodes, not the pasted ones
+                waypoints: vec![],
+            });
+        }
+    }
+    selected_nodes.0 = new_ids.into_values().collect();
+}
@@ -0,0 +1,1149 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bevy::{
+    prelude::*, render::texture::DEFAULT_IMAGE_HANDLE, sprite::Anchor, window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::collab_socket::{connect, CollabSocket, SocketEvent};
+use super::collab_text::{seed_site, typed_chars, TextSequence};
+use super::ui_helpers::{
+    CanvasOutline, CollabButton, CollaboratorMarker, FitToText, Localized, MainPanel, NodeRect,
+};
+use super::{
+    diff_snapshots, json_images, panel_rect, properties, CanvasZoom, History, HistoryCommand,
+    HistoryNode, HistorySnapshot, Notification, SelectedNodes, VeloNodeContainer, OVERLAY_Z,
+};
+use crate::components::MainCamera;
+use crate::resources::AppState;
+use crate::utils::{convert_from_val_px, decode_image_base64, ReflectableUuid};
+use crate::JsonNode;
+
+// Seconds between two updates of the cursor sent to the others
+const CURSOR_INTERVAL: f32 = 0.05;
+const COLLABORATOR_COLORS: [Color; 6] = [
+    Color::rgb(0.9, 0.3, 0.2),
+    Color::rgb(0.2, 0.6, 0.9),
+    Color::rgb(0.3, 0.7, 0.3),
+    Color::rgb(0.8, 0.5, 0.1),
+    Color::rgb(0.6, 0.3, 0.8),
+    Color::rgb(0.9, 0.3, 0.6),
+];
+
+/// Relay the open document is shared through and the name shown to the others, from
+/// `collab_relay` and `collab_name` of ~/.velo.toml (`collab` and `name` URL parameters in the
+/// browser).
+#[derive(Resource, Clone, Debug)]
+pub struct CollabSettings {
+    pub relay: Option<String>,
+    pub name: String,
+}
+
+impl Default for CollabSettings {
+    fn default() -> Self {
+        Self {
+            relay: None,
+            name: "Anonymous".to_string(),
+        }
+    }
+}
+
+/// Lamport timestamp of a register write, the site that wrote it breaks ties so every site
+/// orders concurrent writes the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Stamp {
+    pub counter: u64,
+    pub site: Uuid,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum CollabOp {
+    /// Write of a register.
+    Set {
+        key: String,
+        value: Value,
+        stamp: Stamp,
+    },
+    /// Text typed in a node after a character (at the start when none), its characters are
+    /// stamped with consecutive counters from `stamp`.
+    Insert {
+        node: Uuid,
+        after: Option<Stamp>,
+        text: String,
+        stamp: Stamp,
+    },
+    /// Characters removed from the text of a node.
+    Remove { node: Uuid, chars: Vec<Stamp> },
+}
+
+impl CollabOp {
+    /// Greatest counter the op was stamped with.
+    fn counter(&self) -> u64 {
+        match self {
+            CollabOp::Set { stamp, .. } => stamp.counter,
+            CollabOp::Insert { text, stamp, .. } => {
+                stamp.counter + text.chars().count().saturating_sub(1) as u64
+            }
+            CollabOp::Remove { .. } => 0,
+        }
+    }
+}
+
+/// Edit made here, stamped when it's written: a register or the text of a node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollabWrite {
+    Register(String, Value),
+    Text(Uuid, String),
+}
+
+/// Tab as last-writer-wins registers: the position, size, other properties, image and existence
+/// of each node, and each arrow (null once deleted), with the text of each node as a
+/// `TextSequence`. Merging keeps the write with the greatest stamp, so sites that received the
+/// same writes in any order, any number of times, end up with the same tab and concurrent edits
+/// of different properties, or of the same text, are all kept.
+#[derive(Clone, Debug, Default)]
+pub struct TabRegisters {
+    registers: BTreeMap<String, (Value, Stamp)>,
+    texts: BTreeMap<Uuid, TextSequence>,
+}
+
+/// Nodes (with their encoded image) and arrows of a tab as relayed between collaborators,
+/// in world coordinates like in the history.
+#[derive(Clone, Default)]
+pub struct CollabState {
+    pub nodes: BTreeMap<Uuid, (JsonNode, Option<String>)>,
+    pub arrows: Vec<Value>,
+}
+
+fn node_key(id: Uuid, field: &str) -> String {
+    format!("node/{}/{}", id, field)
+}
+
+/// Arrows have no ids, there is one arrow between two connectors.
+fn arrow_key(arrow: &Value) -> String {
+    let connector = |end: &str| {
+        format!(
+            "{}:{}",
+            arrow[end]["id"].as_str().unwrap_or_default(),
+            arrow[end]["pos"].as_str().unwrap_or_default()
+        )
+    };
+    format!("arrow/{}->{}", connector("start"), connector("end"))
+}
+
+fn node_from_fields(fields: &HashMap<&str, &Value>, text: String) -> Option<JsonNode> {
+    let mut node = (*fields.get("properties")?).clone();
+    let position = fields.get("position")?;
+    let size = fields.get("size")?;
+    let px = |value: &Value| Some(json!(Val::Px(value.as_f64()? as f32)));
+    node["left"] = px(&position[0])?;
+    node["bottom"] = px(&position[1])?;
+    node["width"] = px(&size[0])?;
+    node["height"] = px(&size[1])?;
+    node["text"]["text"] = json!(text);
+    serde_json::from_value(node).ok()
+}
+
+impl TabRegisters {
+    /// Applies the op unless it was merged already (or a later write of the register was), true
+    /// if it changed the tab.
+    pub fn merge(&mut self, op: &CollabOp) -> bool {
+        match op {
+            CollabOp::Set { key, value, stamp } => match self.registers.get(key) {
+                Some((_, merged)) if merged >= stamp => false,
+                _ => {
+                    self.registers.insert(key.clone(), (value.clone(), *stamp));
+                    true
+                }
+            },
+            CollabOp::Insert {
+                node,
+                after,
+                text,
+                stamp,
+            } => {
+                let sequence = self.texts.entry(*node).or_default();
+                typed_chars(text, *after, *stamp)
+                    .into_iter()
+                    .fold(false, |changed, char| sequence.insert(char) || changed)
+            }
+            CollabOp::Remove { node, chars } => self.texts.entry(*node).or_default().remove(chars),
+        }
+    }
+
+    /// Stamps the writes made here by `site` with the next counters of `clock` and merges them.
+    pub fn write(
+        &mut self,
+        site: Uuid,
+        clock: &mut u64,
+        writes: Vec<CollabWrite>,
+    ) -> Vec<CollabOp> {
+        let mut ops = vec![];
+        for write in writes {
+            let written = ops.len();
+            match write {
+                CollabWrite::Register(key, value) => {
+                    *clock += 1;
+                    ops.push(CollabOp::Set {
+                        key,
+                        value,
+                        stamp: Stamp {
+                            counter: *clock,
+                            site,
+                        },
+                    });
+                }
+                CollabWrite::Text(node, text) => {
+                    let edit = self.texts.entry(node).or_default().edit(&text);
+                    if !edit.removed.is_empty() {
+                        ops.push(CollabOp::Remove {
+                            node,
+                            chars: edit.removed,
+                        });
+                    }
+                    if !edit.inserted.is_empty() {
+                        let stamp = Stamp {
+                            counter: *clock + 1,
+                            site,
+                        };
+                        *clock += edit.inserted.chars().count() as u64;
+                        ops.push(CollabOp::Insert {
+                            node,
+                            after: edit.after,
+                            text: edit.inserted,
+                            stamp,
+                        });
+                    }
+                }
+            }
+            // the text edit of a later write is made on this one
+            for op in ops[written..].iter() {
+                self.merge(op);
+            }
+        }
+        ops
+    }
+
+    /// Ops that rebuild the tab when merged, consecutive characters typed together are sent as
+    /// one insertion.
+    pub fn ops(&self) -> Vec<CollabOp> {
+        let mut ops: Vec<_> = self
+            .registers
+            .iter()
+            .map(|(key, (value, stamp))| CollabOp::Set {
+                key: key.clone(),
+                value: value.clone(),
+                stamp: *stamp,
+            })
+            .collect();
+        for (node, sequence) in self.texts.iter() {
+            let mut last: Option<Stamp> = None;
+            for char in sequence.chars() {
+                let typed_after_last = last.is_some()
+                    && char.after == last
+                    && last.map(|last| (last.counter + 1, last.site))
+                        == Some((char.stamp.counter, char.stamp.site));
+                match ops.last_mut() {
+                    Some(CollabOp::Insert { text, .. }) if typed_after_last => {
+                        text.push(char.value)
+                    }
+                    _ => ops.push(CollabOp::Insert {
+                        node: *node,
+                        after: char.after,
+                        text: char.value.to_string(),
+                        stamp: char.stamp,
+                    }),
+                }
+                last = Some(char.stamp);
+            }
+            let removed: Vec<_> = sequence.removed().copied().collect();
+            if !removed.is_empty() {
+                ops.push(CollabOp::Remove {
+                    node: *node,
+                    chars: removed,
+                });
+            }
+        }
+        ops
+    }
+
+    pub fn state(&self) -> CollabState {
+        let mut state = CollabState::default();
+        let mut node_fields: BTreeMap<Uuid, HashMap<&str, &Value>> = BTreeMap::new();
+        for (key, (value, _)) in self.registers.iter() {
+            if let Some((id, field)) = key
+                .strip_prefix("node/")
+                .and_then(|key| key.split_once('/'))
+            {
+                if let Ok(id) = Uuid::parse_str(id) {
+                    node_fields.entry(id).or_default().insert(field, value);
+                }
+            } else if key.starts_with("arrow/") && !value.is_null() {
+                state.arrows.push(value.clone());
+            }
+        }
+        for (id, fields) in node_fields {
+            if fields.get("exists").and_then(|exists| exists.as_bool()) != Some(true) {
+                continue;
+            }
+            let text = self
+                .texts
+                .get(&id)
+                .map(TextSequence::text)
+                .unwrap_or_default();
+            if let Some(node) = node_from_fields(&fields, text) {
+                let image = fields
+                    .get("image")
+                    .and_then(|image| image.as_str())
+                    .map(str::to_string);
+                state.nodes.insert(id, (node, image));
+            }
+        }
+        state
+    }
+}
+
+impl CollabState {
+    /// State of the canvas the history recorded, images aren't compared with it.
+    pub fn from_snapshot(snapshot: &HistorySnapshot) -> Self {
+        Self {
+            nodes: snapshot
+                .nodes
+                .iter()
+                .map(|(id, node)| (*id, (node.node.clone(), None)))
+                .collect(),
+            arrows: snapshot.arrows.clone(),
+        }
+    }
+
+    fn snapshot(&self) -> HistorySnapshot {
+        HistorySnapshot {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(id, (node, _))| (*id, history_node(node)))
+                .collect(),
+            arrows: self.arrows.clone(),
+        }
+    }
+}
+
+fn history_node(node: &JsonNode) -> HistoryNode {
+    HistoryNode {
+        node: node.clone(),
+        image: DEFAULT_IMAGE_HANDLE.typed(),
+    }
+}
+
+fn node_writes(node: &HistoryNode, image: Option<String>) -> Vec<CollabWrite> {
+    let id = node.node.id;
+    vec![
+        CollabWrite::Register(
+            node_key(id, "position"),
+            json!([
+                convert_from_val_px(node.node.left),
+                convert_from_val_px(node.node.bottom)
+            ]),
+        ),
+        CollabWrite::Register(
+            node_key(id, "size"),
+            json!([
+                convert_from_val_px(node.node.width),
+                convert_from_val_px(node.node.height)
+            ]),
+        ),
+        CollabWrite::Text(id, node.node.text.text.clone()),
+        CollabWrite::Register(node_key(id, "properties"), properties(&node.node)),
+        CollabWrite::Register(
+            node_key(id, "image"),
+            image.map_or(Value::Null, Value::String),
+        ),
+    ]
+}
+
+/// Writes of an edit made here, `image` encodes the image of a node if it has one.
+pub fn command_writes(
+    command: &HistoryCommand,
+    image: &impl Fn(&HistoryNode) -> Option<String>,
+) -> Vec<CollabWrite> {
+    match command {
+        HistoryCommand::NodeMoved { id, to, .. } => {
+            vec![CollabWrite::Register(
+                node_key(*id, "position"),
+                json!([to.x, to.y]),
+            )]
+        }
+        HistoryCommand::NodeResized { id, to, .. } => {
+            vec![CollabWrite::Register(
+                node_key(*id, "size"),
+                json!([to.x, to.y]),
+            )]
+        }
+        HistoryCommand::TextEdited { id, to, .. } => vec![CollabWrite::Text(*id, to.clone())],
+        HistoryCommand::NodeChanged { to, .. } => node_writes(to, image(to)),
+        HistoryCommand::NodeCreated(node) => {
+            let mut writes = vec![CollabWrite::Register(
+                node_key(node.node.id, "exists"),
+                json!(true),
+            )];
+            writes.extend(node_writes(node, image(node)));
+            writes
+        }
+        HistoryCommand::NodeDeleted(node) => {
+            vec![CollabWrite::Register(
+                node_key(node.node.id, "exists"),
+                json!(false),
+            )]
+        }
+        HistoryCommand::ArrowCreated(arrow) => {
+            vec![CollabWrite::Register(arrow_key(arrow), arrow.clone())]
+        }
+        HistoryCommand::ArrowDeleted(arrow) => {
+            vec![CollabWrite::Register(arrow_key(arrow), Value::Null)]
+        }
+    }
+}
+
+/// Ops writing the tab when it's shared the first time, with the lowest stamps so any edit made
+/// elsewhere wins over them. Texts are written by a site that depends on them only, so the same
+/// saved text shared by several sites is merged once.
+fn seed_ops(site: Uuid, writes: Vec<CollabWrite>) -> Vec<CollabOp> {
+    writes
+        .into_iter()
+        .filter_map(|write| match write {
+            CollabWrite::Register(key, value) => Some(CollabOp::Set {
+                key,
+                value,
+                stamp: Stamp { counter: 0, site },
+            }),
+            CollabWrite::Text(node, text) if !text.is_empty() => Some(CollabOp::Insert {
+                node,
+                after: None,
+                stamp: Stamp {
+                    counter: 0,
+                    site: seed_site(node, &text),
+                },
+                text,
+            }),
+            CollabWrite::Text(..) => None,
+        })
+        .collect()
+}
+
+/// Commands turning the canvas showing `old` into `new`. Nodes whose image changed are spawned
+/// again, the images themselves are set by `set_images`.
+pub fn state_commands(old: &CollabState, new: &CollabState) -> Vec<HistoryCommand> {
+    let mut commands = diff_snapshots(&old.snapshot(), &new.snapshot());
+    let respawned: HashSet<Uuid> = commands
+        .iter()
+        .filter_map(|command| match command {
+            HistoryCommand::NodeCreated(node) | HistoryCommand::NodeChanged { to: node, .. } => {
+                Some(node.node.id)
+            }
+            _ => None,
+        })
+        .collect();
+    let image_changes: Vec<_> = new
+        .nodes
+        .iter()
+        .filter(|(id, _)| !respawned.contains(id))
+        .filter_map(|(id, (node, image))| {
+            let (old_node, old_image) = old.nodes.get(id)?;
+            // images of the canvas aren't known
+            (old_image.is_some() && old_image != image).then(|| HistoryCommand::NodeChanged {
+                from: Box::new(history_node(old_node)),
+                to: Box::new(history_node(node)),
+            })
+        })
+        .collect();
+    let created = commands
+        .iter()
+        .filter(|command| matches!(command, HistoryCommand::NodeCreated(_)))
+        .count();
+    commands.splice(created..created, image_changes);
+    commands
+}
+
+fn node_image(node: &HistoryNode, images: &Assets<Image>) -> Option<String> {
+    json_images(&[(node.node.clone(), node.image.clone())], images)
+        .remove(&node.node.id.to_string())
+        .and_then(|image| image.as_str().map(str::to_string))
+}
+
+fn set_images(commands: &mut [HistoryCommand], state: &CollabState, images: &mut Assets<Image>) {
+    for command in commands.iter_mut() {
+        if let HistoryCommand::NodeCreated(node) | HistoryCommand::NodeChanged { to: node, .. } =
+            command
+        {
+            if let Some((_, Some(image))) = state.nodes.get(&node.node.id) {
+                node.image = images.add(decode_image_base64(image));
+            }
+        }
+    }
+}
+
+/// Messages relayed to everyone else collaborating on the document.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CollabMessage {
+    /// Sent when joining, the others reply with their tabs and cursors.
+    Hello {
+        site: Uuid,
+        name: String,
+    },
+    Ops {
+        site: Uuid,
+        tab_id: ReflectableUuid,
+        ops: Vec<CollabOp>,
+    },
+    Cursor {
+        site: Uuid,
+        name: String,
+        #[serde(flatten)]
+        cursor: CollabCursor,
+    },
+    Leave {
+        site: Uuid,
+    },
+}
+
+/// Where a collaborator points on which tab, and the nodes they selected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollabCursor {
+    pub tab_id: Option<ReflectableUuid>,
+    /// In world coordinates, none outside of the canvas.
+    pub position: Option<[f32; 2]>,
+    pub selected: Vec<ReflectableUuid>,
+}
+
+pub struct Collaborator {
+    pub name: String,
+    pub color: Color,
+    pub cursor: CollabCursor,
+}
+
+/// Collaboration on the open document, started and stopped with the "Collaborate" menu button.
+#[derive(Resource)]
+pub struct CollabSession {
+    site: Uuid,
+    name: String,
+    doc_id: ReflectableUuid,
+    socket: CollabSocket,
+    clock: u64,
+    tabs: HashMap<ReflectableUuid, TabRegisters>,
+    /// Tabs whose nodes and arrows were written by this site when it joined.
+    seeded: HashSet<ReflectableUuid>,
+    /// Active tab whose canvas shows its registers, remote writes are applied to it right away.
+    synced_tab: Option<ReflectableUuid>,
+    sent_cursor: Option<CollabCursor>,
+    pub collaborators: BTreeMap<Uuid, Collaborator>,
+}
+
+impl CollabSession {
+    fn new(settings: &CollabSettings, relay: &str, doc_id: ReflectableUuid) -> Self {
+        Self {
+            site: Uuid::new_v4(),
+            name: settings.name.clone(),
+            doc_id,
+            // each document is a room of the relay
+            socket: connect(format!("{}/{}", relay.trim_end_matches('/'), doc_id.0)),
+            clock: 0,
+            tabs: HashMap::new(),
+            seeded: HashSet::new(),
+            synced_tab: None,
+            sent_cursor: None,
+            collaborators: BTreeMap::new(),
+        }
+    }
+
+    fn send(&self, message: &CollabMessage) {
+        if let Ok(text) = serde_json::to_string(message) {
+            let _ = self.socket.tx.try_send(text);
+        }
+    }
+
+    /// Stamps and merges writes made here.
+    fn write(&mut self, tab_id: ReflectableUuid, writes: Vec<CollabWrite>) -> Vec<CollabOp> {
+        self.tabs
+            .entry(tab_id)
+            .or_default()
+            .write(self.site, &mut self.clock, writes)
+    }
+}
+
+fn color_of(site: Uuid) -> Color {
+    COLLABORATOR_COLORS[(site.as_u128() % COLLABORATOR_COLORS.len() as u128) as usize]
+}
+
+fn active_tab(app_state: &AppState) -> Option<ReflectableUuid> {
+    let doc = app_state.docs.get(&app_state.current_document?)?;
+    doc.tabs.iter().find(|tab| tab.is_active).map(|tab| tab.id)
+}
+
+fn stop_collab(commands: &mut Commands, session: &CollabSession, history: &mut History) {
+    session.send(&CollabMessage::Leave { site: session.site });
+    commands.remove_resource::<CollabSession>();
+    history.share_edits(false);
+}
+
+/// The "Collaborate" button joins the room of the open document on the relay or leaves it,
+/// opening another document leaves it too.
+pub fn toggle_collab(
+    mut commands: Commands,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<CollabButton>)>,
+    settings: Res<CollabSettings>,
+    app_state: Res<AppState>,
+    session: Option<Res<CollabSession>>,
+    mut history: ResMut<History>,
+) {
+    let clicked = buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    match session {
+        Some(session) => {
+            if clicked || app_state.current_document != Some(session.doc_id) {
+                info!("Stopped collaborating on the document");
+                stop_collab(&mut commands, &session, &mut history);
+            }
+        }
+        None => {
+            let (Some(relay), Some(doc_id)) = (settings.relay.as_ref(), app_state.current_document)
+            else {
+                return;
+            };
+            if clicked {
+                info!("Collaborating on the document through {}", relay);
+                commands.insert_resource(CollabSession::new(&settings, relay, doc_id));
+                history.share_edits(true);
+            }
+        }
+    }
+}
+
+/// Sends the edits made here and applies the ones of the others. A tab is written as a whole
+/// with the lowest stamps the first time it's open, so any edit made elsewhere wins over it,
+/// and its canvas catches up with the registers whenever it's loaded.
+pub fn sync_collab(
+    mut commands: Commands,
+    session: Option<ResMut<CollabSession>>,
+    mut history: ResMut<History>,
+    mut images: ResMut<Assets<Image>>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    for (tab_id, entry) in history.take_shared_edits() {
+        let writes = entry
+            .iter()
+            .flat_map(|command| command_writes(command, &|node| node_image(node, &images)))
+            .collect();
+        let ops = session.write(tab_id, writes);
+        session.send(&CollabMessage::Ops {
+            site: session.site,
+            tab_id,
+            ops,
+        });
+    }
+    if history.snapshot().is_none() && session.synced_tab.is_some() {
+        session.synced_tab = None;
+    }
+    while let Ok(event) = session.socket.rx.try_recv() {
+        let message = match event {
+            SocketEvent::Opened => {
+                session.send(&CollabMessage::Hello {
+                    site: session.site,
+                    name: session.name.clone(),
+                });
+                continue;
+            }
+            SocketEvent::Message(text) => match serde_json::from_str(&text) {
+                Ok(message) => message,
+                Err(e) => {
+                    info!("Unknown message from the relay: {:?}", e);
+                    continue;
+                }
+            },
+            SocketEvent::Closed(reason) => {
+                let message = Localized::new("Stopped collaborating on the document");
+                notifications.send(Notification::error(message).with_details(reason));
+                stop_collab(&mut commands, &session, &mut history);
+                return;
+            }
+        };
+        match message {
+            CollabMessage::Hello { site, name } if site != session.site => {
+                for (tab_id, registers) in session.tabs.iter() {
+                    session.send(&CollabMessage::Ops {
+                        site: session.site,
+                        tab_id: *tab_id,
+                        ops: registers.ops(),
+                    });
+                }
+                session.sent_cursor = None;
+                session.collaborators.insert(
+                    site,
+                    Collaborator {
+                        name,
+                        color: color_of(site),
+                        cursor: CollabCursor::default(),
+                    },
+                );
+            }
+            CollabMessage::Ops { site, tab_id, ops } if site != session.site => {
+                let synced = session.synced_tab == Some(tab_id);
+                let registers = session.tabs.entry(tab_id).or_default();
+                let old = synced.then(|| registers.state());
+                let mut changed = false;
+                for op in ops.iter() {
+                    changed |= registers.merge(op);
+                }
+                let clock = ops.iter().map(CollabOp::counter).max().unwrap_or(0);
+                session.clock = session.clock.max(clock);
+                if let (Some(old), true) = (old, changed) {
+                    let new = session.tabs[&tab_id].state();
+                    let mut remote = state_commands(&old, &new);
+                    set_images(&mut remote, &new, &mut images);
+                    history.apply_remote(remote);
+                }
+            }
+            CollabMessage::Cursor { site, name, cursor } if site != session.site => {
+                session.collaborators.insert(
+                    site,
+                    Collaborator {
+                        name,
+                        color: color_of(site),
+                        cursor,
+                    },
+                );
+            }
+            CollabMessage::Leave { site } => {
+                session.collaborators.remove(&site);
+            }
+            // the relay may send messages back to their sender
+            _ => {}
+        }
+    }
+    let Some((tab_id, snapshot)) = history.snapshot() else {
+        return;
+    };
+    if session.synced_tab == Some(tab_id) {
+        return;
+    }
+    let canvas = CollabState::from_snapshot(snapshot);
+    if session.seeded.insert(tab_id) {
+        let site = session.site;
+        let image = |node: &HistoryNode| node_image(node, &images);
+        let nodes = snapshot
+            .nodes
+            .values()
+            .map(|node| HistoryCommand::NodeCreated(Box::new(node.clone())));
+        let arrows = snapshot
+            .arrows
+            .iter()
+            .cloned()
+            .map(HistoryCommand::ArrowCreated);
+        let writes: Vec<_> = nodes
+            .chain(arrows)
+            .flat_map(|command| command_writes(&command, &image))
+            .collect();
+        let ops = seed_ops(site, writes);
+        let registers = session.tabs.entry(tab_id).or_default();
+        for op in ops.iter() {
+            registers.merge(op);
+        }
+        let clock = ops.iter().map(CollabOp::counter).max().unwrap_or(0);
+        session.clock = session.clock.max(clock);
+        session.send(&CollabMessage::Ops { site, tab_id, ops });
+    }
+    let new = session.tabs.entry(tab_id).or_default().state();
+    let mut remote = state_commands(&canvas, &new);
+    set_images(&mut remote, &new, &mut images);
+    history.apply_remote(remote);
+    session.synced_tab = Some(tab_id);
+}
+
+/// Sends where the cursor is on the canvas and the selected nodes when they change.
+pub fn share_cursor(
+    session: Option<ResMut<CollabSession>>,
+    app_state: Res<AppState>,
+    selected_nodes: Res<SelectedNodes>,
+    time: Res<Time>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    mut elapsed: Local<f32>,
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    *elapsed += time.delta_seconds();
+    if *elapsed < CURSOR_INTERVAL {
+        return;
+    }
+    *elapsed = 0.;
+    let (Ok(window), Ok((camera, camera_transform)), Ok((panel, panel_transform))) = (
+        windows.get_single(),
+        camera_q.get_single(),
+        main_panel_query.get_single(),
+    ) else {
+        return;
+    };
+    let panel = panel_rect(panel, panel_transform, window);
+    let position = window
+        .cursor_position()
+        .filter(|cursor| panel.contains(*cursor))
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .map(|position| [position.x, position.y]);
+    let cursor = CollabCursor {
+        tab_id: active_tab(&app_state),
+        position,
+        selected: selected_nodes.0.clone(),
+    };
+    if session.sent_cursor.as_ref() == Some(&cursor) {
+        return;
+    }
+    session.send(&CollabMessage::Cursor {
+        site: session.site,
+        name: session.name.clone(),
+        cursor: cursor.clone(),
+    });
+    session.sent_cursor = Some(cursor);
+}
+
+/// Cursor of a collaborator at `position` (world) with their name, it keeps its size on screen.
+fn spawn_cursor(commands: &mut Commands, collaborator: &Collaborator, position: Vec2, zoom: f32) {
+    commands
+        .spawn((
+            SpatialBundle::from_transform(
+                Transform::from_translation(position.extend(OVERLAY_Z)).with_scale(Vec3::new(
+                    1. / zoom,
+                    1. / zoom,
+                    1.,
+                )),
+            ),
+            CollaboratorMarker,
+        ))
+        .with_children(|builder| {
+            // the top left corner of the dot is the cursor
+            builder.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: collaborator.color,
+                    custom_size: Some(Vec2::splat(8.)),
+                    anchor: Anchor::TopLeft,
+                    ..default()
+                },
+                ..default()
+            });
+            builder
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: collaborator.color,
+                            anchor: Anchor::TopLeft,
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(10., 0., 0.),
+                        ..default()
+                    },
+                    FitToText {
+                        padding: Vec2::new(4., 0.),
+                    },
+                ))
+                .with_children(|builder| {
+                    builder.spawn(Text2dBundle {
+                        text: Text::from_section(
+                            collaborator.name.clone(),
+                            TextStyle {
+                                font_size: 14.,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        ..default()
+                    });
+                });
+        });
+}
+
+/// Shows the cursors of the others on the active tab with their names, and outlines the nodes
+/// they selected in their color.
+pub fn show_collaborators(
+    mut commands: Commands,
+    session: Option<Res<CollabSession>>,
+    app_state: Res<AppState>,
+    zoom: Res<CanvasZoom>,
+    markers: Query<Entity, With<CollaboratorMarker>>,
+    containers: Query<(&VeloNodeContainer, &NodeRect)>,
+    moved: Query<(), (Changed<NodeRect>, With<VeloNodeContainer>)>,
+) {
+    let Some(session) = session else {
+        for entity in markers.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+    if !session.is_changed() && !zoom.is_changed() && moved.is_empty() {
+        return;
+    }
+    for entity in markers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let tab_id = active_tab(&app_state);
+    for collaborator in session.collaborators.values() {
+        if collaborator.cursor.tab_id != tab_id || tab_id.is_none() {
+            continue;
+        }
+        if let Some([x, y]) = collaborator.cursor.position {
+            spawn_cursor(&mut commands, collaborator, Vec2::new(x, y), zoom.0);
+        }
+        for (container, rect) in containers.iter() {
+            if !collaborator.cursor.selected.contains(&container.id) {
+                continue;
+            }
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::NONE,
+                        // 3 pixels around the node on screen
+                        custom_size: Some(rect.size + Vec2::splat(6. / zoom.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(rect.center().extend(OVERLAY_Z)),
+                    ..default()
+                },
+                CanvasOutline::new(collaborator.color, 2.),
+                CollaboratorMarker,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos};
+
+    fn node(id: Uuid, text: &str) -> HistoryNode {
+        history_node(&JsonNode {
+            id,
+            node_type: NodeType::Rect,
+            left: Val::Px(10.),
+            bottom: Val::Px(20.),
+            width: Val::Px(100.),
+            height: Val::Px(50.),
+            text: JsonNodeText {
+                text: text.to_string(),
+                pos: TextPos::Center,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::WHITE,
+            z_index: 0,
+            locked: false,
+            border: NodeBorder::default(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        })
+    }
+
+    /// Writes the command made by `site` on the tab with counters from `counter`.
+    fn merge(registers: &mut TabRegisters, site: Uuid, counter: u64, command: HistoryCommand) {
+        let mut clock = counter - 1;
+        registers.write(site, &mut clock, command_writes(&command, &|_| None));
+    }
+
+    /// Ops of the command made by `site` on a new tab.
+    fn ops(site: Uuid, counter: u64, command: HistoryCommand) -> Vec<CollabOp> {
+        let mut clock = counter - 1;
+        TabRegisters::default().write(site, &mut clock, command_writes(&command, &|_| None))
+    }
+
+    #[test]
+    fn test_concurrent_edits_converge() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let id = Uuid::new_v4();
+        let created = ops(
+            a,
+            1,
+            HistoryCommand::NodeCreated(Box::new(node(id, "idea"))),
+        );
+        let mut a_tab = TabRegisters::default();
+        let mut b_tab = TabRegisters::default();
+        for op in created.iter() {
+            a_tab.merge(op);
+            b_tab.merge(op);
+        }
+        let (mut a_clock, mut b_clock) = (20, 20);
+        // a moves the node and b resizes it while both edit its text
+        let moved = ops(
+            a,
+            21,
+            HistoryCommand::NodeMoved {
+                id,
+                from: Vec2::new(10., 20.),
+                to: Vec2::new(50., 60.),
+            },
+        );
+        let resized = ops(
+            b,
+            21,
+            HistoryCommand::NodeResized {
+                id,
+                from: Vec2::new(100., 50.),
+                to: Vec2::new(120., 50.),
+            },
+        );
+        let a_text = a_tab.write(
+            a,
+            &mut a_clock,
+            vec![CollabWrite::Text(id, "my idea".into())],
+        );
+        let b_text = b_tab.write(b, &mut b_clock, vec![CollabWrite::Text(id, "ideas".into())]);
+        let orders = [
+            [&created, &moved, &resized, &a_text, &b_text],
+            [&b_text, &a_text, &resized, &moved, &created],
+            [&a_text, &created, &b_text, &moved, &resized],
+        ];
+        let states: Vec<_> = orders
+            .iter()
+            .map(|order| {
+                let mut registers = TabRegisters::default();
+                for ops in order.iter().chain(order.iter()) {
+                    for op in ops.iter() {
+                        registers.merge(op);
+                    }
+                }
+                registers.state()
+            })
+            .collect();
+        for state in states.iter() {
+            let (node, image) = &state.nodes[&id];
+            assert_eq!(node.left, Val::Px(50.));
+            assert_eq!(node.bottom, Val::Px(60.));
+            assert_eq!(node.width, Val::Px(120.));
+            assert_eq!(node.text.text, "my ideas");
+            assert!(image.is_none());
+        }
+
+        // the same stamps for the same register, the greater site wins
+        let moved_by = |site| {
+            ops(
+                site,
+                30,
+                HistoryCommand::NodeMoved {
+                    id,
+                    from: Vec2::new(50., 60.),
+                    to: Vec2::new(if site == a { 0. } else { 1. }, 0.),
+                },
+            )
+        };
+        let mut registers = TabRegisters::default();
+        for op in created
+            .iter()
+            .chain(moved_by(a).iter())
+            .chain(moved_by(b).iter())
+        {
+            registers.merge(op);
+        }
+        assert_eq!(
+            registers.state().nodes[&id].0.left,
+            Val::Px(if a > b { 0. } else { 1. })
+        );
+
+        let deleted = ops(a, 40, HistoryCommand::NodeDeleted(Box::new(node(id, ""))));
+        assert!(registers.merge(&deleted[0]));
+        assert!(!registers.merge(&deleted[0]));
+        assert!(registers.state().nodes.is_empty());
+    }
+
+    #[test]
+    fn test_seed_ops() {
+        let (a, b, id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let seed = |site| {
+            let created = HistoryCommand::NodeCreated(Box::new(node(id, "idea")));
+            seed_ops(site, command_writes(&created, &|_| None))
+        };
+        let mut registers = TabRegisters::default();
+        // both sites shared the saved text
+        for op in seed(a).iter().chain(seed(b).iter()) {
+            registers.merge(op);
+        }
+        assert_eq!(registers.state().nodes[&id].0.text.text, "idea");
+        // edits made anywhere win over the tab as shared
+        let mut clock = 0;
+        let ops = registers.write(
+            b,
+            &mut clock,
+            vec![
+                CollabWrite::Register(node_key(id, "position"), json!([1., 2.])),
+                CollabWrite::Text(id, "ideas".into()),
+            ],
+        );
+        assert_eq!(ops.len(), 2);
+        let state = registers.state();
+        let (node, _) = &state.nodes[&id];
+        assert_eq!(node.left, Val::Px(1.));
+        assert_eq!(node.text.text, "ideas");
+        // a tab rebuilt from its ops has the same text
+        let mut rebuilt = TabRegisters::default();
+        for op in registers.ops() {
+            rebuilt.merge(&op);
+        }
+        assert_eq!(rebuilt.state().nodes[&id].0.text.text, "ideas");
+        assert!(matches!(
+            &rebuilt.ops()[..],
+            [.., CollabOp::Insert { text, .. }, CollabOp::Insert { .. }] if text == "idea"
+        ));
+    }
+
+    #[test]
+    fn test_state_commands() {
+        let (site, id, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let arrow = json!({"arrow_type": "Line", "start": {"id": id, "pos": "Top"}, "end": {"id": other, "pos": "Bottom"}});
+        let mut registers = TabRegisters::default();
+        let node_created = |id, text| HistoryCommand::NodeCreated(Box::new(node(id, text)));
+        merge(&mut registers, site, 1, node_created(id, "a"));
+        merge(&mut registers, site, 2, node_created(other, "b"));
+        let old = registers.state();
+        merge(
+            &mut registers,
+            site,
+            3,
+            HistoryCommand::ArrowCreated(arrow.clone()),
+        );
+        merge(
+            &mut registers,
+            site,
+            4,
+            HistoryCommand::NodeResized {
+                id,
+                from: Vec2::new(100., 50.),
+                to: Vec2::new(120., 50.),
+            },
+        );
+        let new = registers.state();
+        let commands = state_commands(&old, &new);
+        assert!(matches!(
+            commands[0],
+            HistoryCommand::NodeResized { id: resized, to, .. }
+                if resized == id && to == Vec2::new(120., 50.)
+        ));
+        assert!(matches!(&commands[1], HistoryCommand::ArrowCreated(value) if *value == arrow));
+        assert_eq!(commands.len(), 2);
+
+        // the canvas catches up with the registers
+        let commands = state_commands(&CollabState::default(), &new);
+        assert_eq!(commands.len(), 3);
+        assert!(state_commands(&new, &new).is_empty());
+        let mut canvas = new.snapshot();
+        canvas.apply(&HistoryCommand::ArrowDeleted(arrow.clone()));
+        let commands = state_commands(&CollabState::from_snapshot(&canvas), &new);
+        assert!(matches!(&commands[..], [HistoryCommand::ArrowCreated(value)] if *value == arrow));
+    }
+}
@@ -0,0 +1,136 @@
+use async_channel::{Receiver, Sender};
+
+pub enum SocketEvent {
+    Opened,
+    Message(String),
+    /// The connection failed or was closed, with the reason.
+    Closed(String),
+}
+
+/// WebSocket connection to the collaboration relay. Messages sent before it's open are sent
+/// once it is, dropping it closes the connection.
+pub struct CollabSocket {
+    pub tx: Sender<String>,
+    pub rx: Receiver<SocketEvent>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    use async_channel::{Receiver, Sender, TryRecvError};
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::Message;
+
+    use super::SocketEvent;
+
+    // Outgoing messages wait at most that long for the socket to be read
+    const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+    pub fn run(
+        url: &str,
+        outgoing: &Receiver<String>,
+        events: &Sender<SocketEvent>,
+    ) -> tungstenite::Result<()> {
+        let (mut socket, _) = tungstenite::connect(url)?;
+        match socket.get_mut() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(READ_TIMEOUT))?,
+            MaybeTlsStream::Rustls(stream) => {
+                stream.get_mut().set_read_timeout(Some(READ_TIMEOUT))?
+            }
+            _ => {}
+        }
+        let _ = events.try_send(SocketEvent::Opened);
+        loop {
+            loop {
+                match outgoing.try_recv() {
+                    Ok(text) => socket.write_message(Message::Text(text))?,
+                    Err(TryRecvError::Empty) => break,
+                    // the session was stopped
+                    Err(TryRecvError::Closed) => {
+                        socket.close(None)?;
+                        return Ok(());
+                    }
+                }
+            }
+            match socket.read_message() {
+                Ok(Message::Text(text)) => {
+                    if events.try_send(SocketEvent::Message(text)).is_err() {
+                        return Ok(());
+                    }
+                }
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Connects to the relay on a thread of its own.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn connect(url: String) -> CollabSocket {
+    let (tx, outgoing) = async_channel::unbounded();
+    let (events, rx) = async_channel::unbounded();
+    std::thread::spawn(move || {
+        let reason = match native::run(&url, &outgoing, &events) {
+            Ok(()) => "disconnected".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let _ = events.try_send(SocketEvent::Closed(reason));
+    });
+    CollabSocket { tx, rx }
+}
+
+/// Connects to the relay with the WebSocket of the browser.
+#[cfg(target_arch = "wasm32")]
+pub fn connect(url: String) -> CollabSocket {
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{MessageEvent, WebSocket};
+
+    let (tx, outgoing) = async_channel::unbounded::<String>();
+    let (events, rx) = async_channel::unbounded();
+    let socket = match WebSocket::new(&url) {
+        Ok(socket) => socket,
+        Err(e) => {
+            let _ = events.try_send(SocketEvent::Closed(format!("{:?}", e)));
+            return CollabSocket { tx, rx };
+        }
+    };
+    let (open_tx, open_rx) = async_channel::bounded::<()>(1);
+    let opened = events.clone();
+    let on_open = Closure::<dyn FnMut()>::new(move || {
+        let _ = opened.try_send(SocketEvent::Opened);
+        let _ = open_tx.try_send(());
+    });
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    on_open.forget();
+    let received = events.clone();
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        if let Some(text) = event.data().as_string() {
+            let _ = received.try_send(SocketEvent::Message(text));
+        }
+    });
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    let on_close = Closure::<dyn FnMut()>::new(move || {
+        let _ = events.try_send(SocketEvent::Closed("disconnected".to_string()));
+    });
+    socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    on_close.forget();
+    wasm_bindgen_futures::spawn_local(async move {
+        if open_rx.recv().await.is_err() {
+            return;
+        }
+        while let Ok(text) = outgoing.recv().await {
+            if let Err(e) = socket.send_with_str(&text) {
+                bevy::log::error!("Error sending to the relay: {:?}", e);
+            }
+        }
+        let _ = socket.close();
+    });
+    CollabSocket { tx, rx }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeSet;
+
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::collab::Stamp;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextChar {
+    pub stamp: Stamp,
+    /// Character it was typed after, none at the start of the text.
+    pub after: Option<Stamp>,
+    pub value: char,
+}
+
+/// Text of a node as an RGA (replicated growable array): each character is stamped when it's
+/// typed and placed right after the character it was typed after, characters typed after the
+/// same one are ordered by their stamps, latest first. Removed characters stay as tombstones, so
+/// characters typed after them still find their place. Sites that merged the same insertions and
+/// removals in any order, any number of times, have the same text and concurrent edits of a text
+/// are all kept.
+#[derive(Clone, Debug, Default)]
+pub struct TextSequence {
+    chars: Vec<TextChar>,
+    removed: BTreeSet<Stamp>,
+    /// Characters typed after ones that weren't merged yet.
+    pending: Vec<TextChar>,
+}
+
+/// Edit turning a text into another: the characters to remove and the text to type after a
+/// character (at the start when none).
+#[derive(Debug, PartialEq)]
+pub struct TextEdit {
+    pub removed: Vec<Stamp>,
+    pub after: Option<Stamp>,
+    pub inserted: String,
+}
+
+impl TextSequence {
+    fn contains(&self, stamp: Stamp) -> bool {
+        self.chars.iter().any(|c| c.stamp == stamp) || self.pending.iter().any(|c| c.stamp == stamp)
+    }
+
+    /// Merges the character, true if it's new.
+    pub fn insert(&mut self, char: TextChar) -> bool {
+        if self.contains(char.stamp) {
+            return false;
+        }
+        if !self.integrate(char.clone()) {
+            self.pending.push(char);
+            return true;
+        }
+        // characters typed after this one may be waiting for it
+        while let Some(index) = self
+            .pending
+            .iter()
+            .position(|pending| self.position_after(pending.after).is_some())
+        {
+            let pending = self.pending.remove(index);
+            self.integrate(pending);
+        }
+        true
+    }
+
+    /// Index right after the character, none if it wasn't merged yet.
+    fn position_after(&self, after: Option<Stamp>) -> Option<usize> {
+        match after {
+            None => Some(0),
+            Some(after) => self
+                .chars
+                .iter()
+                .position(|c| c.stamp == after)
+                .map(|index| index + 1),
+        }
+    }
+
+    fn integrate(&mut self, char: TextChar) -> bool {
+        let Some(mut index) = self.position_after(char.after) else {
+            return false;
+        };
+        // characters typed later after the same one come first, so do the ones typed after them
+        // which are stamped later still
+        while index < self.chars.len() && self.chars[index].stamp > char.stamp {
+            index += 1;
+        }
+        self.chars.insert(index, char);
+        true
+    }
+
+    /// Merges the removal of the characters, true if any of them wasn't removed yet.
+    pub fn remove(&mut self, stamps: &[Stamp]) -> bool {
+        stamps.iter().fold(false, |changed, stamp| {
+            self.removed.insert(*stamp) || changed
+        })
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &TextChar> {
+        self.chars
+            .iter()
+            .filter(|c| !self.removed.contains(&c.stamp))
+    }
+
+    pub fn text(&self) -> String {
+        self.visible().map(|c| c.value).collect()
+    }
+
+    /// Edit of the text between their common start and end.
+    pub fn edit(&self, text: &str) -> TextEdit {
+        let old: Vec<&TextChar> = self.visible().collect();
+        let new: Vec<char> = text.chars().collect();
+        let prefix = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(old, new)| old.value == **new)
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(old, new)| old.value == **new)
+            .count();
+        TextEdit {
+            removed: old[prefix..old.len() - suffix]
+                .iter()
+                .map(|c| c.stamp)
+                .collect(),
+            after: prefix.checked_sub(1).map(|index| old[index].stamp),
+            inserted: new[prefix..new.len() - suffix].iter().collect(),
+        }
+    }
+
+    /// Characters with the removed ones, in text order (waiting ones last).
+    pub fn chars(&self) -> impl Iterator<Item = &TextChar> {
+        self.chars.iter().chain(self.pending.iter())
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &Stamp> {
+        self.removed.iter()
+    }
+}
+
+/// Characters of `text` typed one after the other after `after`, stamped with consecutive
+/// counters from `stamp`.
+pub fn typed_chars(text: &str, after: Option<Stamp>, stamp: Stamp) -> Vec<TextChar> {
+    let mut after = after;
+    text.chars()
+        .enumerate()
+        .map(|(index, value)| {
+            let char = TextChar {
+                stamp: Stamp {
+                    counter: stamp.counter + index as u64,
+                    site: stamp.site,
+                },
+                after,
+                value,
+            };
+            after = Some(char.stamp);
+            char
+        })
+        .collect()
+}
+
+/// Site the text of a node is written by when a tab is shared the first time. It depends on the
+/// text only, so sites sharing the same saved text write the same characters.
+pub fn seed_site(node: Uuid, text: &str) -> Uuid {
+    let digest = Sha256::new()
+        .chain_update(node.as_bytes())
+        .chain_update(text.as_bytes())
+        .finalize();
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Uuid::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(sequence: &mut TextSequence, text: &str, stamp: Stamp) -> Vec<TextChar> {
+        let edit = sequence.edit(text);
+        sequence.remove(&edit.removed);
+        let chars = typed_chars(&edit.inserted, edit.after, stamp);
+        for char in chars.iter() {
+            sequence.insert(char.clone());
+        }
+        chars
+    }
+
+    #[test]
+    fn test_text_sequence() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let stamp = |counter, site| Stamp { counter, site };
+        let mut sequence = TextSequence::default();
+        let idea = type_text(&mut sequence, "idea", stamp(1, a));
+        assert_eq!(sequence.text(), "idea");
+
+        // "idea" turns into "my idea!" on a and into "deas" on b at the same time
+        let edit = sequence.edit("my idea");
+        assert_eq!(edit.after, None);
+        assert_eq!(edit.inserted, "my ");
+        let my = typed_chars(&edit.inserted, edit.after, stamp(5, a));
+        let exclamation = typed_chars("!", Some(idea[3].stamp), stamp(8, a));
+        let s = typed_chars("s", Some(idea[3].stamp), stamp(5, b));
+        let removed = vec![idea[0].stamp];
+
+        let orders = [
+            [&idea, &my, &exclamation, &s],
+            [&s, &exclamation, &my, &idea],
+            [&exclamation, &idea, &s, &my],
+        ];
+        for order in orders {
+            let mut sequence = TextSequence::default();
+            sequence.remove(&removed);
+            // merged twice
+            for chars in order.iter().chain(order.iter()) {
+                for char in chars.iter() {
+                    sequence.insert(char.clone());
+                }
+            }
+            assert!(!sequence.remove(&removed));
+            assert_eq!(sequence.text(), "my dea!s");
+        }
+    }
+
+    #[test]
+    fn test_text_edit() {
+        let site = Uuid::new_v4();
+        let mut sequence = TextSequence::default();
+        let chars = type_text(&mut sequence, "brainstorm", Stamp { counter: 1, site });
+        let edit = sequence.edit("brainstorming");
+        assert!(edit.removed.is_empty());
+        assert_eq!(edit.after, Some(chars[9].stamp));
+        assert_eq!(edit.inserted, "ing");
+        let edit = sequence.edit("brain storm");
+        assert!(edit.removed.is_empty());
+        assert_eq!(edit.after, Some(chars[4].stamp));
+        assert_eq!(edit.inserted, " ");
+        let edit = sequence.edit("rainstorm");
+        assert_eq!(edit.removed, vec![chars[0].stamp]);
+        assert_eq!(edit.after, None);
+        assert!(edit.inserted.is_empty());
+        type_text(&mut sequence, "storm", Stamp { counter: 20, site });
+        assert_eq!(sequence.text(), "storm");
+        assert_eq!(
+            sequence.edit("storm"),
+            TextEdit {
+                removed: vec![],
+                after: Some(chars[9].stamp),
+                inserted: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_seed_site() {
+        let node = Uuid::new_v4();
+        assert_eq!(seed_site(node, "idea"), seed_site(node, "idea"));
+        assert_ne!(seed_site(node, "idea"), seed_site(node, "ideas"));
+        assert_ne!(seed_site(node, "idea"), seed_site(Uuid::new_v4(), "idea"));
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_pkv::PkvStore;
+
+use super::{
+    color_to_hex, color_to_hsv, hsv_to_color, hue_image, saturation_value_image,
+    spawn_color_picker,
+    ui_helpers::{
+        ColorPicker, ColorPickerArea, ColorPickerButton, ColorPickerHex, ColorPickerPreview,
+        MainPanel, OpenColorPicker, RecentColorSwatch,
+    },
+    NodeEffects, RecentColors, SelectedNodes, UiState, VeloNode, MAX_RECENT_COLORS,
+};
+
+const RECENT_COLORS_KEY: &str = "recent_colors";
+
+pub fn load_recent_colors(pkv: Res<PkvStore>, mut recent_colors: ResMut<RecentColors>) {
+    if let Ok(colors) = pkv.get::<Vec<Color>>(RECENT_COLORS_KEY) {
+        recent_colors.0 = colors;
+    }
+}
+
+/// Opens color picker for the selected nodes, closes it if it's already open.
+pub fn open_color_picker(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<OpenColorPicker>)>,
+    pickers: Query<Entity, With<ColorPicker>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    nodes: Query<(&VeloNode, &Sprite, Option<&NodeEffects>)>,
+    mut ui_state: ResMut<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    recent_colors: Res<RecentColors>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    if let Ok(picker) = pickers.get_single() {
+        commands.entity(picker).despawn_recursive();
+        ui_state.color_hex_to_edit = false;
+        return;
+    }
+    let targets = selected_nodes.0.clone();
+    // picker starts with the color of the first target
+    let color = nodes
+        .iter()
+        .find(|(node, _, _)| targets.first() == Some(&node.id))
+        .map_or(Color::WHITE, |(_, sprite, effects)| {
+            effects.map_or(sprite.color, |e| e.color)
+        });
+    let (hue, saturation, value) = color_to_hsv(color);
+    let picker = ColorPicker {
+        hue,
+        saturation,
+        value,
+        targets,
+        saturation_value_image: images.add(saturation_value_image(hue, saturation, value)),
+        hue_image: images.add(hue_image(hue)),
+        eyedropper: false,
+        hex_input: None,
+    };
+    let icon_font = asset_server.load("fonts/MaterialIcons-Regular.ttf");
+    let picker = spawn_color_picker(&mut commands, &icon_font, picker, &recent_colors.0);
+    commands.entity(main_panel_query.single()).add_child(picker);
+}
+
+/// Picks saturation/value or hue while the mouse button is held over their area.
+pub fn color_picker_areas(
+    areas: Query<(&Interaction, &ColorPickerArea, &Node, &GlobalTransform)>,
+    mut pickers: Query<&mut ColorPicker>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut picker) = pickers.get_single_mut() else {
+        return;
+    };
+    let window = windows.single();
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    for (interaction, area, node, transform) in areas.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        // ui coordinates have y axis pointing down
+        let rect = node.logical_rect(transform);
+        let cursor = Vec2::new(cursor.x, window.height() - cursor.y);
+        let fraction = ((cursor - rect.min) / rect.size()).clamp(Vec2::ZERO, Vec2::ONE);
+        match area {
+            ColorPickerArea::SaturationValue => {
+                picker.saturation = fraction.x;
+                picker.value = 1. - fraction.y;
+            }
+            ColorPickerArea::Hue => {
+                picker.hue = fraction.x * 360.;
+            }
+        }
+    }
+}
+
+/// Edits the hex field: typed hex digits replace the color once all 6 are entered.
+pub fn color_picker_hex_input(
+    mut pickers: Query<&mut ColorPicker>,
+    mut char_events: EventReader<ReceivedCharacter>,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+) {
+    let Ok(mut picker) = pickers.get_single_mut() else {
+        char_events.clear();
+        return;
+    };
+    if !ui_state.color_hex_to_edit {
+        char_events.clear();
+        if picker.hex_input.is_some() {
+            picker.hex_input = None;
+        }
+        return;
+    }
+    let mut hex = picker.hex_input.clone().unwrap_or_default();
+    for event in char_events.iter() {
+        if event.char.is_ascii_hexdigit() && hex.len() < 6 {
+            hex.push(event.char.to_ascii_uppercase());
+        }
+    }
+    if input.just_pressed(KeyCode::Back) {
+        hex.pop();
+    }
+    if input.any_just_pressed([KeyCode::Return, KeyCode::Escape]) {
+        ui_state.color_hex_to_edit = false;
+        picker.hex_input = None;
+        return;
+    }
+    if hex.len() == 6 {
+        if let Ok(color) = Color::hex(&hex) {
+            (picker.hue, picker.saturation, picker.value) = color_to_hsv(color);
+        }
+        ui_state.color_hex_to_edit = false;
+        picker.hex_input = None;
+    } else if picker.hex_input.as_ref() != Some(&hex) {
+        picker.hex_input = Some(hex);
+    }
+}
+
+/// While eyedropper is active, clicking a node takes its color.
+pub fn color_picker_eyedropper(
+    mut pickers: Query<&mut ColorPicker>,
+    interaction_query: Query<
+        (&Interaction, &Sprite, Option<&NodeEffects>),
+        (Changed<Interaction>, With<VeloNode>),
+    >,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut picker) = pickers.get_single_mut() else {
+        return;
+    };
+    if !picker.eyedropper {
+        return;
+    }
+    let mut primary_window = windows.single_mut();
+    primary_window.cursor.icon = CursorIcon::Crosshair;
+    for (interaction, sprite, effects) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            let color = effects.map_or(sprite.color, |e| e.color);
+            (picker.hue, picker.saturation, picker.value) = color_to_hsv(color);
+            picker.eyedropper = false;
+            primary_window.cursor.icon = CursorIcon::Default;
+        }
+    }
+}
+
+pub fn color_picker_buttons(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &ColorPickerButton), Changed<Interaction>>,
+    swatches_query: Query<(&Interaction, &RecentColorSwatch), Changed<Interaction>>,
+    mut pickers: Query<(Entity, &mut ColorPicker)>,
+    mut nodes: Query<(&VeloNode, &mut NodeEffects)>,
+    mut ui_state: ResMut<UiState>,
+    mut recent_colors: ResMut<RecentColors>,
+    mut pkv: ResMut<PkvStore>,
+) {
+    let Ok((picker_entity, mut picker)) = pickers.get_single_mut() else {
+        return;
+    };
+    for (interaction, swatch) in swatches_query.iter() {
+        if *interaction == Interaction::Clicked {
+            (picker.hue, picker.saturation, picker.value) = color_to_hsv(swatch.color);
+        }
+    }
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            ColorPickerButton::Hex => {
+                // typed digits would go to the edited node otherwise
+                ui_state.editing_node = false;
+                ui_state.color_hex_to_edit = true;
+                picker.hex_input = Some(String::new());
+            }
+            ColorPickerButton::Eyedropper => {
+                picker.eyedropper = true;
+            }
+            ColorPickerButton::Apply => {
+                let color = hsv_to_color(picker.hue, picker.saturation, picker.value);
+                for (node, mut effects) in nodes.iter_mut() {
+                    if picker.targets.contains(&node.id) {
+                        effects.color = color;
+                    }
+                }
+                recent_colors.0.retain(|recent| *recent != color);
+                recent_colors.0.insert(0, color);
+                recent_colors.0.truncate(MAX_RECENT_COLORS);
+                pkv.set(RECENT_COLORS_KEY, &recent_colors.0).unwrap();
+                ui_state.color_hex_to_edit = false;
+                commands.entity(picker_entity).despawn_recursive();
+            }
+            ColorPickerButton::Close => {
+                ui_state.color_hex_to_edit = false;
+                commands.entity(picker_entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Redraws picker images, preview and hex field when the picked color changes.
+pub fn update_color_picker(
+    pickers: Query<&ColorPicker, Changed<ColorPicker>>,
+    mut images: ResMut<Assets<Image>>,
+    mut preview_query: Query<&mut BackgroundColor, With<ColorPickerPreview>>,
+    mut hex_query: Query<&mut Text, With<ColorPickerHex>>,
+) {
+    let Ok(picker) = pickers.get_single() else {
+        return;
+    };
+    let color = hsv_to_color(picker.hue, picker.saturation, picker.value);
+    if let Some(image) = images.get_mut(&picker.saturation_value_image) {
+        *image = saturation_value_image(picker.hue, picker.saturation, picker.value);
+    }
+    if let Some(image) = images.get_mut(&picker.hue_image) {
+        *image = hue_image(picker.hue);
+    }
+    for mut bg_color in preview_query.iter_mut() {
+        bg_color.0 = color;
+    }
+    for mut text in hex_query.iter_mut() {
+        text.sections[0].value = match &picker.hex_input {
+            Some(hex) => format!("#{}|", hex),
+            None => color_to_hex(color),
+        };
+    }
+}
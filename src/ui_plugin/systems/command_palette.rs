@@ -0,0 +1,480 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ui_borders::BorderColor;
+
+use super::ui_helpers::{
+    CommandPalette, CommandPaletteInput, CommandPaletteResultButton, CommandPaletteResultList,
+    CommandPaletteText, GenericButton, Localized, MainPanel, TabButton, ThemedBackground,
+    ThemedBorder, Tooltip,
+};
+use super::{find_match, Action, Locale, Settings, Theme, ThemeColor, UiState};
+use crate::resources::AppState;
+
+const PALETTE_WIDTH: f32 = 420.;
+const ROW_HEIGHT: f32 = 24.;
+const FONT_SIZE: f32 = 14.;
+// Rows shown at once, the list scrolls with the selection
+const VISIBLE_RESULTS: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteCommand {
+    /// Runs as if the first chord bound to the action was pressed
+    Action(Action),
+    /// Clicks a button of the menu, the left panel or the tab bar
+    Button(Entity),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PaletteEntry {
+    /// Label in the language of the UI
+    pub label: String,
+    pub shortcut: String,
+    pub command: PaletteCommand,
+}
+
+/// Command chosen in the palette, run at the start of the next frame, and the keys pressed or
+/// the button clicked for it, released in the frame after.
+#[derive(Resource, Default)]
+pub struct PaletteRun {
+    command: Option<PaletteCommand>,
+    pressed: Vec<KeyCode>,
+    clicked: Option<Entity>,
+}
+
+/// Indices of the entries whose label matches `query` fuzzily, the tightest matches first. All
+/// entries match an empty query.
+pub fn filter_entries(entries: &[PaletteEntry], query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return (0..entries.len()).collect();
+    }
+    let mut matches: Vec<(usize, usize, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let (start, end) = find_match(&entry.label, query, true)?;
+            Some((end - start, start, index))
+        })
+        .collect();
+    matches.sort();
+    matches.into_iter().map(|(.., index)| index).collect()
+}
+
+/// Actions with their chords, every button with a tooltip and the tabs of the open document,
+/// sorted by label.
+fn palette_entries(
+    settings: &Settings,
+    locale: &Locale,
+    app_state: &AppState,
+    buttons: &Query<(Entity, &Children), With<Button>>,
+    tooltips: &Query<&Localized, With<Tooltip>>,
+    tab_buttons: &Query<(Entity, &TabButton)>,
+) -> Vec<PaletteEntry> {
+    let mut entries: Vec<PaletteEntry> = Action::ALL
+        .into_iter()
+        .filter(|action| *action != Action::CommandPalette)
+        .map(|action| PaletteEntry {
+            label: locale.translate(action.label()).to_string(),
+            shortcut: settings.keybindings.describe(action),
+            command: PaletteCommand::Action(action),
+        })
+        .collect();
+    for (entity, children) in buttons.iter() {
+        let Some(tooltip) = children.iter().find_map(|child| tooltips.get(*child).ok()) else {
+            continue;
+        };
+        let label = locale.format(tooltip);
+        // e.g. the toolbar buttons are actions with a shortcut as well
+        if entries.iter().all(|entry| entry.label != label) {
+            entries.push(PaletteEntry {
+                label,
+                shortcut: String::new(),
+                command: PaletteCommand::Button(entity),
+            });
+        }
+    }
+    let doc = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get(&doc_id));
+    if let Some(doc) = doc {
+        for (entity, tab_button) in tab_buttons.iter() {
+            if let Some(tab) = doc.tabs.iter().find(|tab| tab.id == tab_button.id) {
+                let label = Localized::new("Switch to tab \"{}\"").with_args([tab.name.clone()]);
+                entries.push(PaletteEntry {
+                    label: locale.format(&label),
+                    shortcut: String::new(),
+                    command: PaletteCommand::Button(entity),
+                });
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.label.to_lowercase().cmp(&b.label.to_lowercase()));
+    entries
+}
+
+fn text_style(color: Color) -> TextStyle {
+    TextStyle {
+        font_size: FONT_SIZE,
+        color,
+        ..default()
+    }
+}
+
+fn spawn_command_palette(
+    commands: &mut Commands,
+    palette: CommandPalette,
+    theme: &Theme,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(50.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    margin: UiRect::left(Val::Px(-PALETTE_WIDTH / 2.)),
+                    size: Size::width(Val::Px(PALETTE_WIDTH)),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: theme.surface.into(),
+                // clicks on the palette don't reach the canvas
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Global(3),
+                ..default()
+            },
+            BorderColor(theme.border),
+            ThemedBackground(ThemeColor::Surface),
+            ThemedBorder(ThemeColor::Border),
+            palette,
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                            align_items: AlignItems::Center,
+                            padding: UiRect::horizontal(Val::Px(4.)),
+                            border: UiRect::all(Val::Px(1.)),
+                            overflow: Overflow::Hidden,
+                            ..default()
+                        },
+                        background_color: theme.surface.into(),
+                        ..default()
+                    },
+                    BorderColor(theme.border),
+                    ThemedBackground(ThemeColor::Surface),
+                    ThemedBorder(ThemeColor::Border),
+                    CommandPaletteInput,
+                ))
+                .with_children(|builder| {
+                    builder.spawn((
+                        TextBundle::from_section("|", text_style(theme.text)),
+                        CommandPaletteText,
+                    ));
+                });
+            builder.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                },
+                CommandPaletteResultList,
+            ));
+        })
+        .id()
+}
+
+/// Cmd/Ctrl+Shift+P, or the chord bound to "Command palette", opens the palette or closes it,
+/// Escape closes it.
+pub fn toggle_command_palette(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    theme: Res<Theme>,
+    app_state: Res<AppState>,
+    mut ui_state: ResMut<UiState>,
+    palettes: Query<Entity, With<CommandPalette>>,
+    buttons: Query<(Entity, &Children), With<Button>>,
+    tooltips: Query<&Localized, With<Tooltip>>,
+    tab_buttons: Query<(Entity, &TabButton)>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+) {
+    let shortcut = settings
+        .keybindings
+        .just_pressed(Action::CommandPalette, &input);
+    if let Ok(palette) = palettes.get_single() {
+        let escape = ui_state.command_palette_to_edit && input.just_pressed(KeyCode::Escape);
+        if shortcut || escape {
+            ui_state.command_palette_to_edit = false;
+            commands.entity(palette).despawn_recursive();
+        }
+        return;
+    }
+    if !shortcut || ui_state.modal_id.is_some() {
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    let entries = palette_entries(
+        &settings,
+        &locale,
+        &app_state,
+        &buttons,
+        &tooltips,
+        &tab_buttons,
+    );
+    let palette = CommandPalette {
+        results: filter_entries(&entries, ""),
+        entries,
+        ..default()
+    };
+    // typed text would go to the edited node otherwise
+    *ui_state = UiState::default();
+    ui_state.command_palette_to_edit = true;
+    let palette = spawn_command_palette(&mut commands, palette, &theme);
+    commands.entity(main_panel).add_child(palette);
+}
+
+/// Typing filters the commands, Up/Down choose one and Enter, or a click, runs it.
+pub fn command_palette_input(
+    mut commands: Commands,
+    mut char_events: EventReader<ReceivedCharacter>,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    mut palettes: Query<(Entity, &mut CommandPalette)>,
+    input_buttons: Query<&Interaction, (Changed<Interaction>, With<CommandPaletteInput>)>,
+    result_buttons: Query<(&Interaction, &CommandPaletteResultButton), Changed<Interaction>>,
+    mut run: ResMut<PaletteRun>,
+) {
+    let Ok((entity, mut palette)) = palettes.get_single_mut() else {
+        char_events.clear();
+        return;
+    };
+    if input_buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        *ui_state = UiState::default();
+        ui_state.command_palette_to_edit = true;
+    }
+    let clicked = result_buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.index);
+    let mut chosen = clicked.and_then(|index| palette.results.get(index).copied());
+    if ui_state.command_palette_to_edit {
+        let command = input.any_pressed([
+            KeyCode::RWin,
+            KeyCode::LWin,
+            KeyCode::RControl,
+            KeyCode::LControl,
+        ]);
+        let mut query = palette.query.clone();
+        for event in char_events.iter() {
+            // e.g. P of the shortcut opening the palette
+            if !command && !event.char.is_control() {
+                query.push(event.char);
+            }
+        }
+        if input.just_pressed(KeyCode::Back) {
+            query.pop();
+        }
+        if query != palette.query {
+            palette.results = filter_entries(&palette.entries, &query);
+            palette.query = query;
+            palette.selected = 0;
+        }
+        let count = palette.results.len();
+        if count > 0 && input.just_pressed(KeyCode::Down) {
+            palette.selected = (palette.selected + 1) % count;
+        }
+        if count > 0 && input.just_pressed(KeyCode::Up) {
+            palette.selected = palette.selected.checked_sub(1).unwrap_or(count - 1);
+        }
+        if input.just_pressed(KeyCode::Return) {
+            chosen = palette.results.get(palette.selected).copied();
+        }
+    } else {
+        char_events.clear();
+    }
+    let Some(index) = chosen else {
+        return;
+    };
+    run.command = Some(palette.entries[index].command);
+    ui_state.command_palette_to_edit = false;
+    commands.entity(entity).despawn_recursive();
+}
+
+/// Shows the query and the commands matching it, with their shortcuts.
+pub fn update_command_palette(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    locale: Res<Locale>,
+    theme: Res<Theme>,
+    palettes: Query<Ref<CommandPalette>>,
+    mut text_query: Query<&mut Text, With<CommandPaletteText>>,
+    list_query: Query<Entity, With<CommandPaletteResultList>>,
+) {
+    let Ok(palette) = palettes.get_single() else {
+        return;
+    };
+    if palette.is_changed() || ui_state.is_changed() {
+        for mut text in text_query.iter_mut() {
+            let (value, color) = if ui_state.command_palette_to_edit {
+                (format!("{}|", palette.query), theme.text)
+            } else if palette.query.is_empty() {
+                (locale.translate("Type a command").to_string(), Color::GRAY)
+            } else {
+                (palette.query.clone(), theme.text)
+            };
+            text.sections[0].value = value;
+            text.sections[0].style.color = color;
+        }
+    }
+    if !palette.is_changed() {
+        return;
+    }
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|builder| {
+        if palette.results.is_empty() {
+            builder.spawn((
+                TextBundle::from_section("no matches", text_style(Color::GRAY)),
+                Localized::new("no matches"),
+            ));
+        }
+        let first = palette.selected.saturating_sub(VISIBLE_RESULTS - 1);
+        for (index, entry_index) in palette
+            .results
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(VISIBLE_RESULTS)
+        {
+            let entry = &palette.entries[*entry_index];
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::horizontal(Val::Px(4.)),
+                            margin: UiRect::top(Val::Px(2.)),
+                            overflow: Overflow::Hidden,
+                            ..default()
+                        },
+                        background_color: if index == palette.selected {
+                            theme.selection.with_a(0.3).into()
+                        } else {
+                            theme.surface.into()
+                        },
+                        ..default()
+                    },
+                    CommandPaletteResultButton { index },
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    builder.spawn(TextBundle::from_section(
+                        entry.label.clone(),
+                        text_style(theme.text),
+                    ));
+                    builder.spawn(TextBundle::from_section(
+                        entry.shortcut.clone(),
+                        text_style(Color::GRAY),
+                    ));
+                });
+        }
+    });
+}
+
+/// Runs the command chosen in the palette: buttons are clicked and actions get the keys of
+/// their first chord pressed, so each shortcut system handles them as usual.
+pub fn run_palette_command(
+    mut run: ResMut<PaletteRun>,
+    mut input: ResMut<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut interactions: Query<&mut Interaction>,
+) {
+    if run.command.is_none() && run.pressed.is_empty() && run.clicked.is_none() {
+        return;
+    }
+    for key in std::mem::take(&mut run.pressed) {
+        input.release(key);
+    }
+    // the mouse isn't released over the button, so it would stay clicked
+    if let Some(entity) = run.clicked.take() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            *interaction = Interaction::None;
+        }
+    }
+    match run.command.take() {
+        Some(PaletteCommand::Action(action)) => {
+            let Some(chord) = settings.keybindings.chords(action).first().copied() else {
+                return;
+            };
+            let modifiers = [
+                (chord.command, KeyCode::LControl),
+                (chord.shift, KeyCode::LShift),
+                (chord.alt, KeyCode::LAlt),
+            ];
+            let mut keys: Vec<KeyCode> = modifiers
+                .into_iter()
+                .filter(|(held, _)| *held)
+                .map(|(_, key)| key)
+                .collect();
+            keys.push(chord.key);
+            for key in keys.iter() {
+                input.press(*key);
+            }
+            run.pressed = keys;
+        }
+        Some(PaletteCommand::Button(entity)) => {
+            if let Ok(mut interaction) = interactions.get_mut(entity) {
+                *interaction = Interaction::Clicked;
+                run.clicked = Some(entity);
+            }
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_entries() {
+        let entry = |label: &str| PaletteEntry {
+            label: label.to_string(),
+            shortcut: String::new(),
+            command: PaletteCommand::Action(Action::Undo),
+        };
+        let entries = vec![
+            entry("Export To File"),
+            entry("New tab"),
+            entry("Toggle Grid"),
+            entry("Save tab"),
+        ];
+        assert_eq!(filter_entries(&entries, ""), vec![0, 1, 2, 3]);
+        assert_eq!(filter_entries(&entries, "tab"), vec![1, 3]);
+        // characters of the query in order, not next to each other
+        assert_eq!(filter_entries(&entries, "tg"), vec![2]);
+        // the shortest span first
+        let entries = vec![entry("Save document"), entry("Save tab")];
+        assert_eq!(filter_entries(&entries, "sat"), vec![1, 0]);
+        assert_eq!(filter_entries(&entries, "EXP"), vec![0]);
+        assert!(filter_entries(&entries, "zzz").is_empty());
+    }
+}
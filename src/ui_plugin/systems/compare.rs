@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE, sprite::Anchor};
+use bevy_cosmic_edit::CosmicEditImage;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::ui_helpers::{CanvasOutline, CompareButton, CompareGhost, CompareHighlight, NodePart};
+use super::{
+    diff_snapshots, json_nodes, CompareMode, HistoryCommand, HistoryNode, HistorySnapshot,
+    NodeContainerQuery, NodeQuery, RawText, UiState, VeloNodeContainer, OVERLAY_Z,
+};
+use crate::resources::{AppState, LoadTabRequest};
+use crate::utils::convert_from_val_px;
+use crate::JsonNode;
+
+const ADDED_COLOR: Color = Color::rgb(0.13, 0.77, 0.37);
+const DELETED_COLOR: Color = Color::rgb(0.94, 0.27, 0.27);
+const MODIFIED_COLOR: Color = Color::rgb(0.98, 0.8, 0.08);
+// Highlights are drawn over the content of nodes, below their outline and handles
+const HIGHLIGHT_Z: f32 = 0.44;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareStatus {
+    Added,
+    Modified,
+}
+
+/// Status of the nodes that differ from the saved state and the deleted nodes (saved ones that
+/// are not on the canvas anymore).
+pub fn compare_snapshots(
+    saved: &HistorySnapshot,
+    current: &HistorySnapshot,
+) -> (HashMap<Uuid, CompareStatus>, Vec<JsonNode>) {
+    let mut statuses = HashMap::new();
+    let mut deleted = vec![];
+    for command in diff_snapshots(saved, current) {
+        match command {
+            HistoryCommand::NodeCreated(node) => {
+                statuses.insert(node.node.id, CompareStatus::Added);
+            }
+            HistoryCommand::NodeDeleted(node) => deleted.push(node.node),
+            HistoryCommand::NodeMoved { id, .. }
+            | HistoryCommand::NodeResized { id, .. }
+            | HistoryCommand::TextEdited { id, .. } => {
+                statuses.insert(id, CompareStatus::Modified);
+            }
+            HistoryCommand::NodeChanged { to, .. } => {
+                statuses.insert(to.node.id, CompareStatus::Modified);
+            }
+            HistoryCommand::ArrowCreated(_) | HistoryCommand::ArrowDeleted(_) => {}
+        }
+    }
+    (statuses, deleted)
+}
+
+/// Nodes of the saved tab.
+fn saved_snapshot(checkpoint: &str, current: &HistorySnapshot) -> HistorySnapshot {
+    let json: Value = serde_json::from_str(checkpoint).unwrap_or_default();
+    let nodes = json["nodes"].as_array().cloned().unwrap_or_default();
+    HistorySnapshot {
+        nodes: nodes
+            .into_iter()
+            .filter_map(|node| serde_json::from_value::<JsonNode>(node).ok())
+            .map(|node| {
+                // images aren't compared, the saved ones are only encoded
+                let image = current
+                    .nodes
+                    .get(&node.id)
+                    .map_or(DEFAULT_IMAGE_HANDLE.typed(), |current| {
+                        current.image.clone()
+                    });
+                (node.id, HistoryNode { node, image })
+            })
+            .collect(),
+        arrows: vec![],
+    }
+}
+
+/// Highlight of `size` (world), highlighted nodes can still be selected and edited as it isn't
+/// picked.
+fn highlight(color: Color, size: Option<Vec2>) -> (SpriteBundle, CanvasOutline, CompareHighlight) {
+    (
+        SpriteBundle {
+            sprite: Sprite {
+                color: color.with_a(0.2),
+                custom_size: size,
+                ..default()
+            },
+            ..default()
+        },
+        CanvasOutline::new(color, 3.),
+        CompareHighlight,
+    )
+}
+
+/// Compare mode, toggled by the compare button of the top menu: nodes added since the tab was
+/// last saved are highlighted in green, modified ones (text, position, size or style) in yellow
+/// and deleted ones are shown in red where they were. Escape or switching tabs ends it.
+pub fn toggle_compare(
+    mut commands: Commands,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<CompareButton>)>,
+    input: Res<Input<KeyCode>>,
+    load_request: Option<Res<LoadTabRequest>>,
+    ui_state: Res<UiState>,
+    mut compare: ResMut<CompareMode>,
+    app_state: Res<AppState>,
+    nodes: NodeQuery,
+    containers: NodeContainerQuery,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    container_entities: Query<(Entity, &VeloNodeContainer)>,
+    highlights: Query<Entity, With<CompareHighlight>>,
+) {
+    let clicked = buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let escape =
+        compare.active && ui_state.is_canvas_focused() && input.just_pressed(KeyCode::Escape);
+    // other tabs aren't compared
+    let reloaded = compare.active && load_request.is_some();
+    if !clicked && !escape && !reloaded {
+        return;
+    }
+    for entity in highlights.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    compare.active = !compare.active && clicked;
+    if !compare.active {
+        return;
+    }
+    let Some(checkpoint) = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get(&doc_id))
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active))
+        .and_then(|tab| tab.checkpoint.as_ref())
+    else {
+        return;
+    };
+    let current = HistorySnapshot {
+        nodes: json_nodes(&nodes, &containers, &text_query)
+            .into_iter()
+            .map(|(node, image)| (node.id, HistoryNode { node, image }))
+            .collect(),
+        arrows: vec![],
+    };
+    let (statuses, deleted) = compare_snapshots(&saved_snapshot(checkpoint, &current), &current);
+    for (entity, container) in container_entities.iter() {
+        let color = match statuses.get(&container.id.0) {
+            Some(CompareStatus::Added) => ADDED_COLOR,
+            Some(CompareStatus::Modified) => MODIFIED_COLOR,
+            None => continue,
+        };
+        let highlight = commands
+            .spawn((highlight(color, None), NodePart::fill(HIGHLIGHT_Z)))
+            .id();
+        commands.entity(entity).add_child(highlight);
+    }
+    for node in deleted {
+        let position = Vec2::new(
+            convert_from_val_px(node.left),
+            convert_from_val_px(node.bottom),
+        );
+        let size = Vec2::new(
+            convert_from_val_px(node.width),
+            convert_from_val_px(node.height),
+        );
+        commands
+            .spawn(highlight(DELETED_COLOR, Some(size)))
+            .insert((
+                Transform::from_translation((position + size / 2.).extend(OVERLAY_Z)),
+                CompareGhost,
+            ))
+            .with_children(|builder| {
+                builder.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        node.text.text,
+                        TextStyle {
+                            font_size: 14.,
+                            color: DELETED_COLOR,
+                            ..default()
+                        },
+                    ),
+                    text_anchor: Anchor::TopLeft,
+                    transform: Transform::from_translation(
+                        (size / 2. * Vec2::new(-1., 1.)).extend(0.01),
+                    ),
+                    ..default()
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos};
+
+    fn saved_node(id: Uuid, left: f32, text: &str) -> serde_json::Value {
+        serde_json::json!(JsonNode {
+            id,
+            node_type: NodeType::Rect,
+            left: Val::Px(left),
+            bottom: Val::Px(0.),
+            width: Val::Px(100.),
+            height: Val::Px(100.),
+            text: JsonNodeText {
+                text: text.to_string(),
+                pos: TextPos::Center,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::WHITE,
+            z_index: 0,
+            locked: false,
+            border: NodeBorder::default(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        })
+    }
+
+    #[test]
+    fn test_compare_snapshots() {
+        let ids: Vec<_> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let checkpoint = serde_json::json!({
+            "nodes": [
+                saved_node(ids[0], 50., "same"),
+                saved_node(ids[1], 50., "old"),
+                saved_node(ids[2], 50., "deleted"),
+            ]
+        });
+        let current = saved_snapshot(
+            &serde_json::json!({
+                "nodes": [
+                    saved_node(ids[0], 50., "same"),
+                    saved_node(ids[1], 50., "new"),
+                    saved_node(ids[3], 50., "added"),
+                ]
+            })
+            .to_string(),
+            &HistorySnapshot::default(),
+        );
+        let saved = saved_snapshot(&checkpoint.to_string(), &current);
+        let (statuses, deleted) = compare_snapshots(&saved, &current);
+        assert_eq!(
+            statuses,
+            HashMap::from([
+                (ids[1], CompareStatus::Modified),
+                (ids[3], CompareStatus::Added)
+            ])
+        );
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].id, ids[2]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use flate2::{read::GzDecoder, write::GzEncoder};
+
+use crate::components::Doc;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+#[cfg(not(target_arch = "wasm32"))]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+#[cfg(not(target_arch = "wasm32"))]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression of a saved document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    #[cfg(not(target_arch = "wasm32"))]
+    Zstd,
+}
+
+impl Compression {
+    /// Compression given by the extension of the file: `.gz` or `.zst`.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("gz") => Compression::Gzip,
+            #[cfg(not(target_arch = "wasm32"))]
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Compression of the data starting with `header`.
+    fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&GZIP_MAGIC) {
+            return Compression::Gzip;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if header.starts_with(&ZSTD_MAGIC) {
+            return Compression::Zstd;
+        }
+        Compression::None
+    }
+}
+
+/// Serializes the document into the (compressed) writer, saved tabs are embedded by
+/// `serialize_checkpoint`. Plain documents are pretty printed to stay readable and diff well.
+pub fn write_doc(writer: impl Write, doc: &Doc, compression: Compression) -> std::io::Result<()> {
+    match compression {
+        Compression::None => serde_json::to_writer_pretty(writer, doc)?,
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+            serde_json::to_writer(&mut encoder, doc)?;
+            encoder.finish()?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        Compression::Zstd => {
+            let mut encoder = zstd::Encoder::new(writer, ZSTD_LEVEL)?;
+            serde_json::to_writer(&mut encoder, doc)?;
+            encoder.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Deserializes a plain or compressed document from the reader, the compression is detected
+/// from the data.
+pub fn read_doc(reader: impl Read) -> std::io::Result<Doc> {
+    let mut reader = BufReader::new(reader);
+    let compression = Compression::detect(reader.fill_buf()?);
+    let doc = match compression {
+        Compression::None => serde_json::from_reader(reader)?,
+        Compression::Gzip => serde_json::from_reader(BufReader::new(GzDecoder::new(reader)))?,
+        #[cfg(not(target_arch = "wasm32"))]
+        Compression::Zstd => {
+            serde_json::from_reader(BufReader::new(zstd::Decoder::with_buffer(reader)?))?
+        }
+    };
+    Ok(doc)
+}
+
+/// Reads the document from a plain or compressed file.
+pub fn read_doc_file(path: &Path) -> std::io::Result<Doc> {
+    read_doc(std::fs::File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Tab;
+    use crate::utils::ReflectableUuid;
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let doc = Doc {
+            id: ReflectableUuid::generate(),
+            name: "Doc".to_string(),
+            tabs: vec![Tab {
+                id: ReflectableUuid::generate(),
+                name: "Tab 1".to_string(),
+                checkpoint: Some("{\"nodes\":[]}".to_string()),
+                zoom: 1.,
+                color: None,
+                icon: None,
+                pinned: false,
+                offset: [0., 0.],
+                selected: vec![],
+                is_active: true,
+            }],
+            tags: vec![],
+            assets: Default::default(),
+            revision: 0,
+        };
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let mut bytes = vec![];
+            write_doc(&mut bytes, &doc, compression).unwrap();
+            assert_eq!(Compression::detect(&bytes), compression);
+            let read = read_doc(bytes.as_slice()).unwrap();
+            assert_eq!(read.id, doc.id);
+            assert_eq!(read.tabs[0].checkpoint, doc.tabs[0].checkpoint);
+        }
+        assert_eq!(
+            Compression::from_path(Path::new("doc.velo.json.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("doc.json")),
+            Compression::None
+        );
+    }
+}
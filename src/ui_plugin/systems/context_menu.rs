@@ -0,0 +1,339 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ui_borders::BorderColor;
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::utils::{next_in_cycle, ReflectableUuid};
+use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeType, TextPos};
+
+use super::{
+    canvas_to_world, change_z_order, delete_nodes,
+    ui_helpers::{
+        ContextMenu, ContextMenuItem, Localized, MainPanel, NodeEffects, NodeZIndex, Tooltip,
+    },
+    with_group_members, CanvasOffset, CanvasZoom, ContextMenuAction, ContextMenuEntries,
+    ContextMenuEvent, ContextMenuTarget, History, Locked, NodeGroup, SelectedNodes, Theme, UiState,
+    VeloNode, VeloNodeContainer, ZOrder, NODE_COLORS,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{notify, Notification};
+
+pub const MENU_WIDTH: f32 = 160.;
+const ITEM_HEIGHT: f32 = 26.;
+const ITEM_HOVER_COLOR: Color = Color::rgb(230.0 / 255.0, 230.0 / 255.0, 230.0 / 255.0);
+// Size of the node created with "New node here"
+const NEW_NODE_SIZE: f32 = 128.;
+
+/// Opens the context menu on right click, with node entries over a node and canvas entries
+/// over empty canvas. Right-clicked node gets selected.
+pub fn open_context_menu(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    entries: Res<ContextMenuEntries>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    nodes: Query<(&Interaction, &VeloNode)>,
+    main_panel_query: Query<(Entity, &Interaction, &Node, &GlobalTransform), With<MainPanel>>,
+    menus: Query<Entity, With<ContextMenu>>,
+    groups_query: Query<(&VeloNodeContainer, &NodeGroup)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    if !buttons.just_pressed(MouseButton::Right) {
+        return;
+    }
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+    let Ok((main_panel, panel_interaction, panel, panel_transform)) = main_panel_query.get_single()
+    else {
+        return;
+    };
+    let window = windows.single();
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let node = nodes
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Hovered)
+        .map(|(_, node)| node.id);
+    // e.g. cursor is over the minimap
+    if node.is_none() && *panel_interaction != Interaction::Hovered {
+        return;
+    }
+    let target = match node {
+        Some(id) => {
+            let edited = selected_nodes.edited(&ui_state) == Some(id);
+            if !selected_nodes.0.contains(&id) {
+                let groups: Vec<_> = groups_query
+                    .iter()
+                    .map(|(container, group)| (container.id, group.id))
+                    .collect();
+                selected_nodes.0 = with_group_members(&[id], &groups);
+            }
+            if !edited {
+                ui_state.editing_node = false;
+            }
+            ContextMenuTarget::Node
+        }
+        None => ContextMenuTarget::Canvas,
+    };
+    let items: Vec<_> = entries
+        .0
+        .iter()
+        .filter(|entry| entry.target == target)
+        .collect();
+    if items.is_empty() {
+        return;
+    }
+
+    // ui coordinates have y axis pointing down, canvas ones point up
+    let rect = panel.logical_rect(panel_transform);
+    let top = window.height() - cursor.y - rect.min.y;
+    let position = Vec2::new(cursor.x - rect.min.x, rect.height() - top);
+    // menu is kept inside the canvas
+    let menu_height = context_menu_height(items.len());
+    let left = (cursor.x - rect.min.x)
+        .min(rect.width() - MENU_WIDTH)
+        .max(0.);
+    let top = top.min(rect.height() - menu_height).max(0.);
+    let items: Vec<_> = items
+        .into_iter()
+        .map(|entry| (entry.label.clone(), entry.action))
+        .collect();
+    spawn_context_menu(
+        &mut commands,
+        main_panel,
+        &items,
+        Vec2::new(left, top),
+        node,
+        position,
+    );
+}
+
+pub fn context_menu_height(items: usize) -> f32 {
+    items as f32 * ITEM_HEIGHT + 2.
+}
+
+/// Spawns the menu at `left_top` of the canvas (y axis pointing down), clicking an item sends
+/// `ContextMenuEvent` with its action, `node` and `position`.
+pub fn spawn_context_menu(
+    commands: &mut Commands,
+    main_panel: Entity,
+    items: &[(String, ContextMenuAction)],
+    left_top: Vec2,
+    node: Option<ReflectableUuid>,
+    position: Vec2,
+) {
+    let menu = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(left_top.x),
+                        top: Val::Px(left_top.y),
+                        ..default()
+                    },
+                    size: Size::new(
+                        Val::Px(MENU_WIDTH),
+                        Val::Px(context_menu_height(items.len())),
+                    ),
+                    flex_direction: FlexDirection::Column,
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                // above nodes, minimap and tooltips
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+            ContextMenu { node, position },
+        ))
+        .with_children(|builder| {
+            for (label, action) in items.iter() {
+                builder
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Px(ITEM_HEIGHT)),
+                                align_items: AlignItems::Center,
+                                padding: UiRect::left(Val::Px(8.)),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                        ContextMenuItem { action: *action },
+                    ))
+                    .with_children(|builder| {
+                        builder.spawn((
+                            TextBundle::from_section(
+                                label.clone(),
+                                TextStyle {
+                                    font_size: 16.,
+                                    color: Color::BLACK,
+                                    ..default()
+                                },
+                            ),
+                            Localized::new(label.clone()),
+                        ));
+                    });
+            }
+        })
+        .id();
+    commands.entity(main_panel).add_child(menu);
+}
+
+/// Sends `ContextMenuEvent` for the clicked entry, any other click or Escape closes the menu.
+pub fn context_menu_click(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    mut items: Query<(&Interaction, &ContextMenuItem, &mut BackgroundColor), Changed<Interaction>>,
+    menus: Query<(Entity, &ContextMenu)>,
+    mut events: EventWriter<ContextMenuEvent>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok((menu_entity, menu)) = menus.get_single() else {
+        return;
+    };
+    let mut primary_window = windows.single_mut();
+    for (interaction, item, mut bg_color) in items.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                events.send(ContextMenuEvent {
+                    action: item.action,
+                    node: menu.node,
+                    position: menu.position,
+                });
+                primary_window.cursor.icon = CursorIcon::Default;
+                commands.entity(menu_entity).despawn_recursive();
+                return;
+            }
+            Interaction::Hovered => {
+                primary_window.cursor.icon = CursorIcon::Hand;
+                bg_color.0 = ITEM_HOVER_COLOR;
+            }
+            Interaction::None => {
+                primary_window.cursor.icon = CursorIcon::Default;
+                bg_color.0 = Color::WHITE;
+            }
+        }
+    }
+    if buttons.any_just_pressed([MouseButton::Left, MouseButton::Middle])
+        || input.just_pressed(KeyCode::Escape)
+    {
+        commands.entity(menu_entity).despawn_recursive();
+    }
+}
+
+/// Handles built-in context menu actions, duplicate, lock and paste are handled by the systems
+/// of their shortcuts.
+pub fn context_menu_actions(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    mut add_rect_events: EventWriter<AddRectEvent>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut history: ResMut<History>,
+    mut nodes: Query<(Entity, &VeloNodeContainer, &mut NodeZIndex), With<VeloNodeContainer>>,
+    mut arrows: Query<(Entity, &ArrowMeta, &mut Visibility), (With<ArrowMeta>, Without<Tooltip>)>,
+    mut bg_colors: Query<(&mut NodeEffects, &VeloNode), With<VeloNode>>,
+    locked_query: Query<&VeloNodeContainer, With<Locked>>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
+    theme: Res<Theme>,
+) {
+    for event in events.iter() {
+        let ids = selected_nodes.0.clone();
+        match event.action {
+            ContextMenuAction::Edit => {
+                let Some(id) = event.node else {
+                    continue;
+                };
+                // locked nodes can't be edited
+                if locked_query.iter().any(|container| container.id == id) {
+                    continue;
+                }
+                *ui_state = UiState::default();
+                selected_nodes.edit(&mut ui_state, id);
+            }
+            ContextMenuAction::Delete => {
+                delete_nodes(
+                    &ids,
+                    &mut commands,
+                    &mut ui_state,
+                    &mut selected_nodes,
+                    &mut history,
+                    &nodes,
+                    &mut arrows,
+                );
+            }
+            ContextMenuAction::BringToFront => {
+                change_z_order(&ids, ZOrder::ToFront, &mut nodes);
+            }
+            ContextMenuAction::ChangeColor => {
+                // all nodes get the palette color following the one of the right-clicked node
+                let current = bg_colors
+                    .iter()
+                    .find(|(_, node)| Some(node.id) == event.node)
+                    .map_or(NODE_COLORS[NODE_COLORS.len() - 1], |(effects, _)| {
+                        effects.color
+                    });
+                let color = next_in_cycle(&NODE_COLORS, current);
+                for (mut effects, node) in bg_colors.iter_mut() {
+                    if ids.contains(&node.id) {
+                        effects.color = color;
+                    }
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ContextMenuAction::CopyLink => {
+                if let Some(id) = event.node {
+                    let link = format!("{}{}", super::NODE_LINK_PREFIX, id.0);
+                    if let Err(e) =
+                        arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(link))
+                    {
+                        let message = Localized::new("Couldn't copy to the clipboard");
+                        notify(&mut commands, Notification::error(message).with_details(e));
+                    }
+                }
+            }
+            ContextMenuAction::NewNode => {
+                // clicked point becomes the left top corner of the node
+                let position = canvas_to_world(event.position, &offset, &zoom);
+                add_rect_events.send(AddRectEvent {
+                    node: JsonNode {
+                        id: Uuid::new_v4(),
+                        node_type: NodeType::Rect,
+                        left: Val::Px(position.x),
+                        bottom: Val::Px(position.y - NEW_NODE_SIZE),
+                        width: Val::Px(NEW_NODE_SIZE),
+                        height: Val::Px(NEW_NODE_SIZE),
+                        text: JsonNodeText {
+                            text: "".to_string(),
+                            pos: TextPos::Center,
+                            style: theme.node_text_style(),
+                        },
+                        bg_color: theme.node_background,
+                        z_index: 0,
+                        locked: false,
+                        border: theme.node_border(),
+                        shadow: None,
+                        opacity: 1.,
+                        tags: vec![],
+                        attachments: vec![],
+                        rotation: 0.,
+                    },
+                    image: None,
+                });
+            }
+            ContextMenuAction::SelectAll => {
+                ui_state.editing_node = false;
+                selected_nodes.0 = nodes.iter().map(|(_, node, _)| node.id).collect();
+            }
+            _ => {}
+        }
+    }
+}
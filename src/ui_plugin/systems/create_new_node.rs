@@ -3,39 +3,39 @@ use bevy_cosmic_edit::FontSystemState;
 
 use crate::utils::ReflectableUuid;
 
-use super::{spawn_node, AddRectEvent, MainPanel, NodeMeta, UiState};
+use super::{
+    json_node_meta, spawn_node, AddRectEvent, CanvasZoom, History, NodeMeta, SelectedNodes, UiState,
+};
 
 pub fn create_new_node(
     mut commands: Commands,
     mut events: EventReader<AddRectEvent>,
     mut ui_state: ResMut<UiState>,
-    main_panel_query: Query<Entity, With<MainPanel>>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut history: ResMut<History>,
     asset_server: Res<AssetServer>,
     mut font_system_state: ResMut<FontSystemState>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    zoom: Res<CanvasZoom>,
 ) {
     let window = windows.single_mut();
     for event in events.iter() {
         *ui_state = UiState::default();
-        ui_state.entity_to_edit = Some(ReflectableUuid(event.node.id));
-        let entity = spawn_node(
+        selected_nodes.edit(&mut ui_state, ReflectableUuid(event.node.id));
+        history.record_created(ReflectableUuid(event.node.id));
+        spawn_node(
             &mut commands,
             &asset_server,
             &mut font_system_state,
             NodeMeta {
-                size: (event.node.width, event.node.height),
-                id: ReflectableUuid(event.node.id),
-                node_type: event.node.node_type.clone(),
-                image: event.image.clone(),
-                text: event.node.text.text.clone(),
-                bg_color: event.node.bg_color,
-                position: (event.node.left, event.node.bottom),
-                text_pos: event.node.text.pos.clone(),
-                z_index: event.node.z_index,
                 is_active: true,
-                scale_factor: window.scale_factor() as f32,
+                ..json_node_meta(
+                    event.node.clone(),
+                    event.image.clone(),
+                    zoom.0,
+                    window.scale_factor() as f32,
+                )
             },
         );
-        commands.entity(main_panel_query.single()).add_child(entity);
     }
 }
@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::canvas::arrow::components::ArrowConnect;
+
+use super::ui_helpers::{Culled, CulledVisibility, MainPanel};
+use super::{
+    view_rect, CanvasOffset, CanvasZoom, NodeIndex, SelectedNodes, UiState, VeloNode,
+    VeloNodeContainer,
+};
+
+// Nodes this far (screen pixels) outside of the canvas keep their content, so panning doesn't uncover
+// empty nodes
+const CULL_MARGIN: f32 = 256.;
+
+/// Nodes outside of the canvas (plus a margin) are culled: their content (text, handles,
+/// borders, shadow) is hidden and isn't laid out, so their text isn't laid out and redrawn
+/// either. The nodes themselves and their arrow connection markers stay as placeholders, so
+/// arrows, the minimap and saving work as before. The edited node is never culled.
+pub fn cull_offscreen_nodes(
+    mut commands: Commands,
+    node_index: Res<NodeIndex>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
+    main_panel_query: Query<Ref<Node>, With<MainPanel>>,
+    containers: Query<(Entity, &VeloNodeContainer, &Children, Option<&Culled>)>,
+    buttons: Query<&Children, With<VeloNode>>,
+    markers: Query<(), With<ArrowConnect>>,
+    mut visibilities: Query<
+        (&mut Visibility, Option<&CulledVisibility>),
+        Without<VeloNodeContainer>,
+    >,
+) {
+    let Ok(panel) = main_panel_query.get_single() else {
+        return;
+    };
+    if !node_index.is_changed()
+        && !panel.is_changed()
+        && !ui_state.is_changed()
+        && !selected_nodes.is_changed()
+        && !offset.is_changed()
+        && !zoom.is_changed()
+    {
+        return;
+    }
+    let view = view_rect(panel.size(), &offset, &zoom);
+    let margin = Vec2::splat(CULL_MARGIN / zoom.0);
+    let view = Rect::from_corners(view.min - margin, view.max + margin);
+    let shown: HashSet<_> = node_index
+        .intersecting(view)
+        .into_iter()
+        .map(|(_, id)| id)
+        .chain(selected_nodes.edited(&ui_state))
+        .collect();
+    for (entity, container, children, culled) in containers.iter() {
+        let cull = !shown.contains(&container.id);
+        if cull == culled.is_some() {
+            continue;
+        }
+        if cull {
+            commands.entity(entity).insert(Culled);
+        } else {
+            commands.entity(entity).remove::<Culled>();
+        }
+        // the shadow next to the node and everything in it but the markers
+        let content: Vec<Entity> = children
+            .iter()
+            .flat_map(|child| match buttons.get(*child) {
+                Ok(node_children) => node_children.iter().copied().collect(),
+                Err(_) => vec![*child],
+            })
+            .filter(|child| !markers.contains(*child))
+            .collect();
+        for child in content {
+            let Ok((mut visibility, stashed)) = visibilities.get_mut(child) else {
+                continue;
+            };
+            if cull {
+                // hidden content (e.g. editors of nodes not edited) stays as it is
+                if *visibility != Visibility::Hidden {
+                    commands.entity(child).insert(CulledVisibility(*visibility));
+                    *visibility = Visibility::Hidden;
+                }
+            } else if let Some(CulledVisibility(stashed)) = stashed {
+                if *visibility == Visibility::Hidden {
+                    *visibility = *stashed;
+                }
+                commands.entity(child).remove::<CulledVisibility>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canvas::arrow::components::ArrowConnect;
+    use crate::utils::ReflectableUuid;
+
+    use super::*;
+
+    #[test]
+    fn test_cull_offscreen_nodes() {
+        let mut app = App::new();
+        app.init_resource::<NodeIndex>();
+        app.init_resource::<UiState>();
+        app.init_resource::<SelectedNodes>();
+        app.init_resource::<CanvasOffset>();
+        app.insert_resource(CanvasZoom(1.));
+        app.add_system(cull_offscreen_nodes);
+        app.world.spawn((NodeBundle::default(), MainPanel));
+
+        let id = ReflectableUuid::generate();
+        let text = app.world.spawn(Visibility::Inherited).id();
+        // editor of a node not edited
+        let editor = app.world.spawn(Visibility::Hidden).id();
+        let marker = app
+            .world
+            .spawn((Visibility::Inherited, ArrowConnect { id, ..default() }))
+            .id();
+        let button = app
+            .world
+            .spawn((Visibility::Visible, VeloNode { id, ..default() }))
+            .push_children(&[text, editor, marker])
+            .id();
+        let shadow = app.world.spawn(Visibility::Visible).id();
+        let container = app
+            .world
+            .spawn(VeloNodeContainer { id })
+            .push_children(&[shadow, button])
+            .id();
+        let far = Rect::new(5000., 5000., 5100., 5100.);
+        app.world
+            .resource_mut::<NodeIndex>()
+            .insert(container, id, far);
+        app.update();
+
+        let visibility = |app: &App, entity| *app.world.get::<Visibility>(entity).unwrap();
+        let stashed = |app: &App, entity| {
+            app.world
+                .get::<CulledVisibility>(entity)
+                .map(|stashed| stashed.0)
+        };
+        assert!(app.world.get::<Culled>(container).is_some());
+        for entity in [text, shadow] {
+            assert_eq!(visibility(&app, entity), Visibility::Hidden);
+        }
+        assert_eq!(stashed(&app, text), Some(Visibility::Inherited));
+        assert_eq!(stashed(&app, shadow), Some(Visibility::Visible));
+        // the node itself is a placeholder
+        assert_eq!(visibility(&app, button), Visibility::Visible);
+        assert_eq!(stashed(&app, editor), None);
+        assert_eq!(visibility(&app, marker), Visibility::Inherited);
+
+        // moved back into the view
+        let near = Rect::new(0., 0., 100., 100.);
+        app.world
+            .resource_mut::<NodeIndex>()
+            .insert(container, id, near);
+        app.update();
+
+        assert!(app.world.get::<Culled>(container).is_none());
+        assert_eq!(visibility(&app, text), Visibility::Inherited);
+        assert_eq!(visibility(&app, shadow), Visibility::Visible);
+        assert_eq!(visibility(&app, editor), Visibility::Hidden);
+        assert_eq!(visibility(&app, marker), Visibility::Inherited);
+        for entity in [text, shadow, editor] {
+            assert_eq!(stashed(&app, entity), None);
+        }
+    }
+}
@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use super::color_to_hex;
+use super::export::ExportedTab;
+use super::import::{ImportedEdge, ImportedGraph};
+use crate::canvas::arrow::components::{ArrowDash, ArrowStyle, ArrowType};
+use crate::NodeType;
+
+type Attributes = Vec<(String, String)>;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Id(String),
+    Punct(char),
+    Edge,
+}
+
+/// Tokens of a DOT file without comments. Quoted strings are unescaped and html strings lose
+/// their tags.
+fn tokenize(dot: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = dot.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // line comments and preprocessor output lines
+            '#' | '/' if (c == '#' && line_start) || chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                line_start = true;
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n' | 'l' | 'r') => text.push('\n'),
+                            // line continuation
+                            Some('\n') => {}
+                            Some(c) => text.push(c),
+                            None => {}
+                        },
+                        c => text.push(c),
+                    }
+                }
+                tokens.push(Token::Id(text));
+            }
+            '<' => {
+                let mut text = String::new();
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '<' => depth += 1,
+                        '>' if depth == 1 => break,
+                        '>' => depth -= 1,
+                        c if depth == 1 => text.push(c),
+                        _ => {}
+                    }
+                }
+                tokens.push(Token::Id(text));
+            }
+            '-' if matches!(chars.peek(), Some('-' | '>')) => {
+                chars.next();
+                tokens.push(Token::Edge);
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' => tokens.push(Token::Punct(c)),
+            c => {
+                let mut id = c.to_string();
+                while let Some(c) = chars.peek() {
+                    if !(c.is_alphanumeric() || matches!(c, '_' | '.')) {
+                        break;
+                    }
+                    id.push(*c);
+                    chars.next();
+                }
+                tokens.push(Token::Id(id));
+            }
+        }
+        line_start = false;
+    }
+    tokens
+}
+
+struct DotParser {
+    tokens: Vec<Token>,
+    position: usize,
+    directed: bool,
+    graph: ImportedGraph,
+    ids: HashMap<String, usize>,
+}
+
+impl DotParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_id(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Id(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// `[a=b, c=d][e=f]` attribute lists, empty if there are none.
+    fn attributes(&mut self) -> Attributes {
+        let mut attributes = vec![];
+        while self.eat('[') {
+            while let Some(token) = self.next() {
+                match token {
+                    Token::Punct(']') => break,
+                    Token::Id(name) => {
+                        if self.eat('=') {
+                            if let Some(Token::Id(value)) = self.next() {
+                                attributes.push((name.to_lowercase(), value));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        attributes
+    }
+
+    /// Statements up to the closing brace of the (sub)graph, returns the nodes used in them.
+    fn statements(
+        &mut self,
+        mut node_defaults: Attributes,
+        mut edge_defaults: Attributes,
+    ) -> Vec<usize> {
+        let mut nodes = vec![];
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Punct('}') => {
+                    self.position += 1;
+                    break;
+                }
+                Token::Punct('{') => {
+                    nodes.extend(self.edge_statement(&node_defaults, &edge_defaults))
+                }
+                Token::Punct(_) | Token::Edge => self.position += 1,
+                Token::Id(id) => {
+                    let keyword = id.to_lowercase();
+                    if matches!(keyword.as_str(), "graph" | "node" | "edge")
+                        && self.tokens.get(self.position + 1) == Some(&Token::Punct('['))
+                    {
+                        self.position += 1;
+                        let attributes = self.attributes();
+                        match keyword.as_str() {
+                            "node" => node_defaults.extend(attributes),
+                            "edge" => edge_defaults.extend(attributes),
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    // graph attribute
+                    if self.tokens.get(self.position + 1) == Some(&Token::Punct('='))
+                        && keyword != "subgraph"
+                    {
+                        self.position += 3;
+                        continue;
+                    }
+                    nodes.extend(self.edge_statement(&node_defaults, &edge_defaults));
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Node or subgraph, the nodes it stands for.
+    fn operand(&mut self, node_defaults: &Attributes, edge_defaults: &Attributes) -> Vec<usize> {
+        if self
+            .peek_id()
+            .map_or(false, |id| id.eq_ignore_ascii_case("subgraph"))
+        {
+            self.position += 1;
+            if self.peek_id().is_some() {
+                self.position += 1;
+            }
+        }
+        if self.eat('{') {
+            return self.statements(node_defaults.clone(), edge_defaults.clone());
+        }
+        let Some(Token::Id(id)) = self.next() else {
+            return vec![];
+        };
+        // port and compass point
+        while self.eat(':') {
+            self.position += 1;
+        }
+        let index = match self.ids.get(&id) {
+            Some(index) => *index,
+            None => {
+                let index = self.graph.add_node(id.clone(), NodeType::Ellipse);
+                self.ids.insert(id.clone(), index);
+                apply_node_attributes(&mut self.graph, index, &id, node_defaults);
+                index
+            }
+        };
+        vec![index]
+    }
+
+    /// Node statement (`a [attributes]`) or edge statement (`a -> b -> {c d} [attributes]`).
+    fn edge_statement(
+        &mut self,
+        node_defaults: &Attributes,
+        edge_defaults: &Attributes,
+    ) -> Vec<usize> {
+        let node_id = self
+            .peek_id()
+            .filter(|id| !id.eq_ignore_ascii_case("subgraph"))
+            .map(|id| id.to_string());
+        let mut operands = vec![self.operand(node_defaults, edge_defaults)];
+        while self.peek() == Some(&Token::Edge) {
+            self.position += 1;
+            operands.push(self.operand(node_defaults, edge_defaults));
+        }
+        let attributes = self.attributes();
+        if operands.len() == 1 {
+            if let (Some(id), [index]) = (node_id, operands[0].as_slice()) {
+                apply_node_attributes(&mut self.graph, *index, &id, &attributes);
+            }
+        } else {
+            let mut edge_attributes = edge_defaults.clone();
+            edge_attributes.extend(attributes);
+            let (arrow_type, style, label, reversed) = edge_style(&edge_attributes, self.directed);
+            for pair in operands.windows(2) {
+                for start in pair[0].iter() {
+                    for end in pair[1].iter() {
+                        let (start, end) = if reversed {
+                            (*end, *start)
+                        } else {
+                            (*start, *end)
+                        };
+                        self.graph.edges.push(ImportedEdge {
+                            start,
+                            end,
+                            arrow_type,
+                            style,
+                            label: label.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        operands.concat()
+    }
+}
+
+fn apply_node_attributes(
+    graph: &mut ImportedGraph,
+    index: usize,
+    id: &str,
+    attributes: &Attributes,
+) {
+    let node = &mut graph.nodes[index];
+    let mut filled = false;
+    let mut color = None;
+    for (name, value) in attributes.iter() {
+        match name.as_str() {
+            // `\N` is the node id
+            "label" => node.text = value.replace("\\N", id).trim().to_string(),
+            "shape" => node.node_type = node_type(value),
+            "style" => filled = value.contains("filled"),
+            "color" => color = parse_color(value),
+            "fillcolor" => {
+                if let Some(fill) = parse_color(value) {
+                    node.bg_color = fill;
+                }
+            }
+            _ => {}
+        }
+    }
+    if let (true, Some(color)) = (filled, color) {
+        if !attributes.iter().any(|(name, _)| name == "fillcolor") {
+            node.bg_color = color;
+        }
+    }
+}
+
+fn node_type(shape: &str) -> NodeType {
+    match shape.to_lowercase().as_str() {
+        "circle" | "doublecircle" | "point" => NodeType::Circle,
+        "ellipse" | "oval" => NodeType::Ellipse,
+        "diamond" | "mdiamond" => NodeType::Diamond,
+        "hexagon" => NodeType::Hexagon,
+        "parallelogram" => NodeType::Parallelogram,
+        "cylinder" => NodeType::Cylinder,
+        "note" => NodeType::Sticky,
+        _ => NodeType::Rect,
+    }
+}
+
+/// Arrow type, style and label of an edge and whether it points back to its start.
+fn edge_style(
+    attributes: &Attributes,
+    directed: bool,
+) -> (ArrowType, ArrowStyle, Option<String>, bool) {
+    let mut dir = if directed { "forward" } else { "none" };
+    let mut label = None;
+    let mut dash = ArrowDash::Solid;
+    let mut color = None;
+    let mut thickness = None;
+    for (name, value) in attributes.iter() {
+        match name.as_str() {
+            "dir" => dir = value.as_str(),
+            "label" | "xlabel" => {
+                label = Some(value.trim().to_string()).filter(|label| !label.is_empty())
+            }
+            "style" if value.contains("dashed") => dash = ArrowDash::Dashed,
+            "style" if value.contains("dotted") => dash = ArrowDash::Dotted,
+            "color" => color = parse_color(value),
+            "penwidth" => thickness = value.parse::<f32>().ok(),
+            _ => {}
+        }
+    }
+    let arrow_type = match dir {
+        "both" => ArrowType::DoubleArrow,
+        "none" => ArrowType::Line,
+        _ => ArrowType::Arrow,
+    };
+    let mut style = ArrowStyle::for_type(arrow_type);
+    style.dash = dash;
+    if let Some(color) = color {
+        style.color = color;
+    }
+    if let Some(thickness) = thickness {
+        style.thickness = thickness;
+    }
+    (arrow_type, style, label, dir == "back")
+}
+
+/// `#rrggbb[aa]` or a few common X11 color names.
+fn parse_color(color: &str) -> Option<Color> {
+    if let Some(hex) = color.strip_prefix('#') {
+        return Color::hex(hex).ok();
+    }
+    Some(match color.to_lowercase().as_str() {
+        "black" => Color::BLACK,
+        "white" => Color::WHITE,
+        "red" => Color::RED,
+        "green" => Color::GREEN,
+        "blue" => Color::BLUE,
+        "yellow" => Color::YELLOW,
+        "orange" => Color::ORANGE,
+        "purple" => Color::PURPLE,
+        "pink" => Color::PINK,
+        "cyan" => Color::CYAN,
+        "magenta" => Color::FUCHSIA,
+        "gray" | "grey" => Color::GRAY,
+        "lightgray" | "lightgrey" => Color::rgb(0.83, 0.83, 0.83),
+        "lightblue" => Color::rgb(0.68, 0.85, 0.9),
+        "lightgreen" => Color::rgb(0.56, 0.93, 0.56),
+        "lightyellow" => Color::rgb(1., 1., 0.88),
+        _ => return None,
+    })
+}
+
+/// Nodes and arrows of a DOT graph. Subgraphs are flattened, node labels, shapes and colors and
+/// edge labels, directions, styles, colors and widths are kept.
+pub fn dot_graph(dot: &str) -> ImportedGraph {
+    let tokens = tokenize(dot);
+    let header = tokens
+        .iter()
+        .position(|token| *token == Token::Punct('{'))
+        .unwrap_or(tokens.len());
+    let directed = tokens[..header]
+        .iter()
+        .any(|token| matches!(token, Token::Id(id) if id.eq_ignore_ascii_case("digraph")));
+    let mut parser = DotParser {
+        tokens,
+        position: header + 1,
+        directed,
+        graph: ImportedGraph::default(),
+        ids: HashMap::new(),
+    };
+    parser.statements(vec![], vec![]);
+    parser.graph
+}
+
+fn escape(text: &str) -> String {
+    text.trim()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// DOT digraph of the tab with node shapes and colors and arrow labels (or relationships),
+/// directions, dashing, colors and widths.
+pub fn tab_to_dot(tab: &ExportedTab) -> String {
+    let mut dot = format!(
+        "digraph \"{}\" {{\n    node [style=filled];\n",
+        escape(&tab.name)
+    );
+    let mut ids = HashMap::new();
+    let order = tab
+        .reading_order()
+        .into_iter()
+        .filter(|index| tab.nodes[*index].node_type != NodeType::Frame);
+    for (i, index) in order.enumerate() {
+        let node = &tab.nodes[index];
+        let id = format!("n{}", i + 1);
+        let shape = match node.node_type {
+            NodeType::Circle => "circle",
+            NodeType::Ellipse => "ellipse",
+            NodeType::Diamond => "diamond",
+            NodeType::Hexagon => "hexagon",
+            NodeType::Parallelogram => "parallelogram",
+            NodeType::Cylinder => "cylinder",
+            NodeType::Sticky => "note",
+            _ => "box",
+        };
+        let _ = writeln!(
+            dot,
+            "    {} [label=\"{}\", shape={}, fillcolor=\"{}\"];",
+            id,
+            escape(&node.text.text),
+            shape,
+            color_to_hex(node.bg_color)
+        );
+        ids.insert(node.id, id);
+    }
+    for arrow in tab.arrows.iter() {
+        let (Some(start), Some(end)) = (
+            ids.get(&arrow.meta.start.id.0),
+            ids.get(&arrow.meta.end.id.0),
+        ) else {
+            continue;
+        };
+        let mut attributes = vec![];
+        let text = arrow
+            .label
+            .as_deref()
+            .filter(|label| !label.trim().is_empty())
+            .or(arrow.relation.as_deref());
+        if let Some(text) = text {
+            attributes.push(format!("label=\"{}\"", escape(text)));
+        }
+        match arrow.meta.arrow_type {
+            ArrowType::Line | ArrowType::ParallelLine => attributes.push("dir=none".to_string()),
+            ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => {
+                attributes.push("dir=both".to_string())
+            }
+            ArrowType::Arrow | ArrowType::ParallelArrow => {}
+        }
+        match arrow.style.dash {
+            ArrowDash::Solid => {}
+            ArrowDash::Dashed => attributes.push("style=dashed".to_string()),
+            ArrowDash::Dotted => attributes.push("style=dotted".to_string()),
+        }
+        attributes.push(format!("color=\"{}\"", color_to_hex(arrow.style.color)));
+        attributes.push(format!("penwidth={}", arrow.style.thickness));
+        let _ = writeln!(dot, "    {} -> {} [{}];", start, end, attributes.join(", "));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_graph() {
+        let graph = dot_graph(
+            r##"/* services */
+digraph G {
+    rankdir=LR;
+    node [shape=box];
+    api [label="API\nserver", style=filled, fillcolor="#FF0000"];
+    db [shape=cylinder];
+    api -> db [label="reads", style=dashed];
+    // cluster members
+    subgraph cluster_web { web; cdn }
+    web -> {api cdn} [dir=back, penwidth=3];
+}"##,
+        );
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|node| (node.text.as_str(), node.node_type.clone()))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("API\nserver", NodeType::Rect),
+                ("db", NodeType::Cylinder),
+                ("web", NodeType::Rect),
+                ("cdn", NodeType::Rect),
+            ]
+        );
+        assert_eq!(graph.nodes[0].bg_color, Color::RED);
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    edge.start,
+                    edge.end,
+                    edge.arrow_type,
+                    edge.style.dash,
+                    edge.label.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, ArrowType::Arrow, ArrowDash::Dashed, Some("reads")),
+                (0, 2, ArrowType::Arrow, ArrowDash::Solid, None),
+                (3, 2, ArrowType::Arrow, ArrowDash::Solid, None),
+            ]
+        );
+        assert_eq!(graph.edges[1].style.thickness, 3.);
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use uuid::Uuid;
+
+use crate::components::MainCamera;
+use crate::resources::AppState;
+use crate::utils::ReflectableUuid;
+use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos};
+
+use super::{
+    add_attachment, add_image_node, embed_attachment, is_encrypted_file, read_image_file,
+    ui_helpers::{LeftPanel, NodeAttachments, NodeRect, NodeZIndex, VeloNode},
+    FileIo, PassphraseRequest, VeloNodeContainer,
+};
+
+const MARKDOWN_NODE_WIDTH: f32 = 300.;
+const MARKDOWN_NODE_HEIGHT: f32 = 200.;
+
+pub fn drop_files(
+    mut commands: Commands,
+    mut drop_events: EventReader<FileDragAndDrop>,
+    mut file_io: ResMut<FileIo>,
+    mut app_state: ResMut<AppState>,
+    mut images: ResMut<Assets<Image>>,
+    mut events: EventWriter<AddRectEvent>,
+    left_panel_query: Query<&Style, With<LeftPanel>>,
+    containers: Query<(&NodeRect, &NodeZIndex, &Visibility, &VeloNodeContainer)>,
+    mut attachments: Query<(&VeloNode, &mut NodeAttachments)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    let primary_window = windows.single();
+    let (camera, camera_transform) = camera_q.single();
+    let mut left_panel_width = 0.;
+    if let Val::Percent(x) = left_panel_query.single().size.width {
+        left_panel_width = (primary_window.width() * x) / 100.;
+    }
+    for event in drop_events.iter() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = event else {
+            continue;
+        };
+        // cursor position is not reported while dragging on every platform
+        let cursor = primary_window.cursor_position().unwrap_or(Vec2::new(
+            (primary_window.width() + left_panel_width) / 2.,
+            primary_window.height() / 2.,
+        ));
+        let Some(position) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+            continue;
+        };
+        // file dropped onto a node is embedded in the document and attached to it
+        if primary_window.cursor_position().is_some() {
+            if let Some(id) = node_at(position, &containers) {
+                let attachment =
+                    embed_attachment(&mut app_state, path_buf.to_string_lossy().to_string());
+                for (node, mut attachments) in attachments.iter_mut() {
+                    if node.id == id {
+                        add_attachment(&mut attachments, attachment.clone());
+                    }
+                }
+                continue;
+            }
+        }
+        let extension = path_buf
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("json") | Some("gz") | Some("zst") => {
+                open_document(path_buf, &mut commands, &mut file_io)
+            }
+            Some("md") => {
+                if let Ok(text) = std::fs::read_to_string(path_buf) {
+                    add_markdown_node(text, position, &mut events);
+                }
+            }
+            _ => {
+                if let Some(image) = read_image_file(path_buf) {
+                    add_image_node(image, position, &mut images, &mut events);
+                }
+            }
+        }
+    }
+}
+
+/// Topmost visible node at the world position.
+fn node_at(
+    position: Vec2,
+    containers: &Query<(&NodeRect, &NodeZIndex, &Visibility, &VeloNodeContainer)>,
+) -> Option<ReflectableUuid> {
+    containers
+        .iter()
+        .filter(|(rect, _, visibility, _)| {
+            **visibility != Visibility::Hidden && rect.rect().contains(position)
+        })
+        .max_by_key(|(_, z_index, _, _)| **z_index)
+        .map(|(_, _, _, container)| container.id)
+}
+
+fn open_document(path: &Path, commands: &mut Commands, file_io: &mut FileIo) {
+    if is_encrypted_file(path) {
+        if let Ok(data) = std::fs::read(path) {
+            commands.insert_resource(PassphraseRequest {
+                data,
+                wrong_passphrase: false,
+            });
+        }
+        return;
+    }
+    file_io.load(path.to_path_buf());
+}
+
+fn add_markdown_node(text: String, center: Vec2, events: &mut EventWriter<AddRectEvent>) {
+    events.send(AddRectEvent {
+        node: JsonNode {
+            id: Uuid::new_v4(),
+            node_type: NodeType::Rect,
+            left: Val::Px(center.x - MARKDOWN_NODE_WIDTH / 2.),
+            bottom: Val::Px(center.y - MARKDOWN_NODE_HEIGHT / 2.),
+            width: Val::Px(MARKDOWN_NODE_WIDTH),
+            height: Val::Px(MARKDOWN_NODE_HEIGHT),
+            text: JsonNodeText {
+                text,
+                pos: TextPos::TopLeft,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::WHITE,
+            z_index: 0,
+            locked: false,
+            border: NodeBorder::default(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: None,
+    });
+}
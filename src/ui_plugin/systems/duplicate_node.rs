@@ -0,0 +1,113 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::{get_cosmic_text, CosmicEditImage, FontSystemState};
+
+use crate::utils::ReflectableUuid;
+use crate::{NodeBorder, NodeTextStyle, TextPos};
+
+use super::{
+    has_image, spawn_node, Action, CanvasZoom, ContextMenuAction, ContextMenuEvent, History,
+    Locked, NodeAttachments, NodeEffects, NodeMeta, NodeRect, NodeRotation, NodeTags, NodeZIndex,
+    RawText, SelectedNodes, Settings, UiState, VeloNode, VeloNodeContainer,
+};
+
+// Offset of the copy from the original node
+const DUPLICATE_OFFSET: f32 = 20.;
+
+pub fn duplicate_node(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut history: ResMut<History>,
+    asset_server: Res<AssetServer>,
+    mut font_system_state: ResMut<FontSystemState>,
+    node_container_query: Query<
+        (
+            &NodeRect,
+            &NodeZIndex,
+            Option<&Locked>,
+            Option<&NodeRotation>,
+        ),
+        With<VeloNodeContainer>,
+    >,
+    node_query: Query<
+        (
+            &VeloNode,
+            &Handle<Image>,
+            &Sprite,
+            &Parent,
+            &TextPos,
+            Option<&NodeBorder>,
+            Option<&NodeEffects>,
+            Option<&NodeTextStyle>,
+            Option<&NodeTags>,
+            Option<&NodeAttachments>,
+        ),
+        (With<VeloNode>, Without<VeloNodeContainer>),
+    >,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    zoom: Res<CanvasZoom>,
+    mut context_menu_events: EventReader<ContextMenuEvent>,
+    settings: Res<Settings>,
+) {
+    let from_menu = context_menu_events
+        .iter()
+        .any(|event| event.action == ContextMenuAction::Duplicate);
+    // while editing text Cmd/Ctrl+D adds a caret in the editor instead
+    if !from_menu
+        && (!settings.keybindings.just_pressed(Action::Duplicate, &input) || ui_state.editing_node)
+    {
+        return;
+    }
+    let window = windows.single();
+    let mut copies = vec![];
+    for (node, image, sprite, parent, text_pos, border, effects, text_style, tags, attachments) in
+        node_query.iter()
+    {
+        if !selected_nodes.0.contains(&node.id) {
+            continue;
+        }
+        let (rect, z_index, locked, rotation) = node_container_query.get(parent.get()).unwrap();
+        let text = text_query
+            .iter()
+            .find(|(raw_text, _)| raw_text.id == node.id)
+            .map(|(_, cosmic_edit)| get_cosmic_text(&cosmic_edit.editor))
+            .unwrap_or_default();
+        let image = has_image(image).then(|| image.clone());
+        let id = ReflectableUuid::generate();
+        spawn_node(
+            &mut commands,
+            &asset_server,
+            &mut font_system_state,
+            NodeMeta {
+                id,
+                node_type: node.node_type.clone(),
+                size: rect.size,
+                position: rect.position + Vec2::new(DUPLICATE_OFFSET, -DUPLICATE_OFFSET),
+                text,
+                bg_color: effects.map_or(sprite.color, |e| e.color),
+                image,
+                text_pos: text_pos.clone(),
+                text_style: text_style.cloned().unwrap_or_default(),
+                z_index: z_index.0,
+                locked: locked.is_some(),
+                border: border.cloned().unwrap_or_default(),
+                shadow: effects.and_then(|e| e.shadow),
+                opacity: effects.map_or(1., |e| e.opacity),
+                tags: tags.map_or(vec![], |tags| tags.0.clone()),
+                attachments: attachments.map_or(vec![], |attachments| attachments.0.clone()),
+                rotation: rotation.map_or(0., |rotation| rotation.0),
+                is_active: false,
+                scale_factor: window.scale_factor() as f32,
+                zoom: zoom.0,
+            },
+        );
+        history.record_created(id);
+        copies.push(id);
+    }
+    if !copies.is_empty() {
+        ui_state.editing_node = false;
+        selected_nodes.0 = copies;
+    }
+}
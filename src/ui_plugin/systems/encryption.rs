@@ -0,0 +1,320 @@
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+use argon2::Argon2;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::FontSystemState;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use super::ui_helpers::Localized;
+use super::{
+    notify, read_doc, spawn_modal, write_doc, CommChannels, Compression, MainPanel, ModalAction,
+    Notification,
+};
+use crate::components::Doc;
+use crate::resources::AppState;
+use crate::utils::ReflectableUuid;
+use crate::UiState;
+
+// Encrypted documents: magic, argon2 salt, nonce and the encrypted (possibly compressed) json
+const ENCRYPTED_MAGIC: &[u8; 8] = b"VELOENC\x01";
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const HEADER_LENGTH: usize = ENCRYPTED_MAGIC.len() + SALT_LENGTH + NONCE_LENGTH;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecryptError {
+    /// The passphrase doesn't match (or the file was tampered with).
+    WrongPassphrase,
+    /// The data is not an encrypted document or is truncated.
+    InvalidData,
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecryptError::WrongPassphrase => write!(f, "wrong passphrase"),
+            DecryptError::InvalidData => write!(f, "not an encrypted document"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// Encrypted document waiting for its passphrase.
+#[derive(Resource)]
+pub struct PassphraseRequest {
+    pub data: Vec<u8>,
+    pub wrong_passphrase: bool,
+}
+
+// Keys derived for the documents encrypted or decrypted in this session. Argon2 is slow on
+// purpose, saving a document again reuses its salt and key instead of deriving a new one.
+static DERIVED_KEYS: Mutex<Vec<DerivedKey>> = Mutex::new(Vec::new());
+const MAX_DERIVED_KEYS: usize = 16;
+
+struct DerivedKey {
+    doc_id: Option<ReflectableUuid>,
+    passphrase: String,
+    salt: [u8; SALT_LENGTH],
+    key: [u8; 32],
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> std::io::Result<[u8; 32]> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    Ok(key)
+}
+
+fn remember_key(keys: &mut Vec<DerivedKey>, key: DerivedKey) {
+    if keys.len() >= MAX_DERIVED_KEYS {
+        keys.remove(0);
+    }
+    keys.push(key);
+}
+
+/// Key derived from the passphrase and the salt, taken from the cache if it was derived before.
+fn cipher(passphrase: &str, salt: &[u8]) -> std::io::Result<ChaCha20Poly1305> {
+    let mut keys = DERIVED_KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(cached) = keys
+        .iter()
+        .find(|cached| cached.passphrase == passphrase && cached.salt == salt)
+    {
+        return Ok(ChaCha20Poly1305::new(Key::from_slice(&cached.key)));
+    }
+    let key = derive_key(passphrase, salt)?;
+    let mut cached_salt = [0; SALT_LENGTH];
+    cached_salt.copy_from_slice(salt);
+    remember_key(
+        &mut keys,
+        DerivedKey {
+            doc_id: None,
+            passphrase: passphrase.to_string(),
+            salt: cached_salt,
+            key,
+        },
+    );
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Salt and key for encrypting the document, the ones used the last time it was encrypted with
+/// the same passphrase or a new random salt.
+fn document_key(
+    doc_id: ReflectableUuid,
+    passphrase: &str,
+) -> std::io::Result<([u8; SALT_LENGTH], [u8; 32])> {
+    let mut keys = DERIVED_KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(cached) = keys
+        .iter()
+        .find(|cached| cached.doc_id == Some(doc_id) && cached.passphrase == passphrase)
+    {
+        return Ok((cached.salt, cached.key));
+    }
+    let mut salt = [0; SALT_LENGTH];
+    getrandom::getrandom(&mut salt)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    let key = derive_key(passphrase, &salt)?;
+    keys.retain(|cached| cached.doc_id != Some(doc_id));
+    remember_key(
+        &mut keys,
+        DerivedKey {
+            doc_id: Some(doc_id),
+            passphrase: passphrase.to_string(),
+            salt,
+            key,
+        },
+    );
+    Ok((salt, key))
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_MAGIC)
+}
+
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0; ENCRYPTED_MAGIC.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_or(false, |_| is_encrypted(&header))
+}
+
+/// Encrypts the serialized document with a key derived from the passphrase.
+pub fn encrypt(data: &[u8], passphrase: &str, doc_id: ReflectableUuid) -> std::io::Result<Vec<u8>> {
+    let (salt, key) = document_key(doc_id, passphrase)?;
+    let mut nonce = [0; NONCE_LENGTH];
+    getrandom::getrandom(&mut nonce)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    let encrypted = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(Nonce::from_slice(&nonce), data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    Ok([
+        ENCRYPTED_MAGIC.as_slice(),
+        salt.as_slice(),
+        nonce.as_slice(),
+        encrypted.as_slice(),
+    ]
+    .concat())
+}
+
+/// Serializes the document and encrypts it, documents with a passphrase are never stored in
+/// plain text.
+pub fn encrypt_doc(doc: &Doc, passphrase: &str) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![];
+    write_doc(&mut data, doc, Compression::Gzip)?;
+    encrypt(&data, passphrase, doc.id)
+}
+
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, DecryptError> {
+    if !is_encrypted(data) || data.len() < HEADER_LENGTH {
+        return Err(DecryptError::InvalidData);
+    }
+    let (salt, rest) = data[ENCRYPTED_MAGIC.len()..].split_at(SALT_LENGTH);
+    let (nonce, encrypted) = rest.split_at(NONCE_LENGTH);
+    cipher(passphrase, salt)
+        .map_err(|_| DecryptError::InvalidData)?
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| DecryptError::WrongPassphrase)
+}
+
+/// Passphrase used when the current document is saved to a file, an empty one saves it
+/// unencrypted again.
+pub fn set_passphrase(app_state: &mut AppState, passphrase: &str) {
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    if passphrase.is_empty() {
+        app_state.passphrases.remove(&doc_id);
+    } else {
+        app_state.passphrases.insert(doc_id, passphrase.to_string());
+    }
+}
+
+/// Loads the requested document if the passphrase is right, asks again otherwise. The
+/// passphrase is kept, so the document stays encrypted when saved again.
+pub fn decrypt_requested_doc(
+    request: &PassphraseRequest,
+    passphrase: &str,
+    commands: &mut Commands,
+    app_state: &mut AppState,
+    comm_channels: &CommChannels,
+) {
+    match decrypt(&request.data, passphrase) {
+        Ok(data) => {
+            commands.remove_resource::<PassphraseRequest>();
+            match read_doc(data.as_slice()) {
+                Ok(doc) => {
+                    app_state.passphrases.insert(doc.id, passphrase.to_string());
+                    if let Err(err) = comm_channels.tx.try_send(doc) {
+                        let message = Localized::new("Couldn't open the document");
+                        notify(commands, Notification::error(message).with_details(err));
+                    }
+                }
+                Err(err) => {
+                    let message = Localized::new("Couldn't read the decrypted document");
+                    notify(commands, Notification::error(message).with_details(err));
+                }
+            }
+        }
+        Err(DecryptError::WrongPassphrase) => {
+            warn!("Wrong passphrase for encrypted document");
+            commands.insert_resource(PassphraseRequest {
+                data: request.data.clone(),
+                wrong_passphrase: true,
+            });
+        }
+        Err(err) => {
+            commands.remove_resource::<PassphraseRequest>();
+            let message = Localized::new("Couldn't decrypt the document");
+            notify(commands, Notification::error(message).with_details(err));
+        }
+    }
+}
+
+/// Asks for the passphrase of an encrypted document being opened (again after a wrong one).
+pub fn request_passphrase(
+    mut commands: Commands,
+    request: Option<Res<PassphraseRequest>>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut font_system_state: ResMut<FontSystemState>,
+) {
+    let Some(request) = request else {
+        return;
+    };
+    if !request.is_changed() {
+        return;
+    }
+    let id = ReflectableUuid::generate();
+    *ui_state = UiState::default();
+    commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+    ui_state.modal_id = Some(id);
+    let entity = spawn_modal(
+        &mut commands,
+        &mut font_system_state,
+        windows.single(),
+        id,
+        ModalAction::EnterPassphrase {
+            wrong_passphrase: request.wrong_passphrase,
+        },
+    );
+    commands.entity(main_panel_query.single()).add_child(entity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encryption() {
+        let data = b"{\"tabs\":[]}";
+        let encrypted = encrypt(data, "secret", ReflectableUuid::generate()).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted
+            .windows(data.len())
+            .any(|window| window == data.as_slice()));
+        assert_eq!(decrypt(&encrypted, "secret").unwrap(), data);
+        assert_eq!(
+            decrypt(&encrypted, "Secret"),
+            Err(DecryptError::WrongPassphrase)
+        );
+        assert_eq!(
+            decrypt(&encrypted[..HEADER_LENGTH - 1], "secret"),
+            Err(DecryptError::InvalidData)
+        );
+        assert_eq!(decrypt(data, "secret"), Err(DecryptError::InvalidData));
+    }
+
+    #[test]
+    fn test_encrypt_doc() {
+        let doc = Doc {
+            id: ReflectableUuid::generate(),
+            name: "Secret plans".to_string(),
+            tabs: vec![],
+            tags: vec!["private".to_string()],
+            assets: Default::default(),
+            revision: 0,
+        };
+        let encrypted = encrypt_doc(&doc, "secret").unwrap();
+        assert!(!encrypted
+            .windows(doc.name.len())
+            .any(|window| window == doc.name.as_bytes()));
+        let decrypted = read_doc(decrypt(&encrypted, "secret").unwrap().as_slice()).unwrap();
+        assert_eq!(decrypted.id, doc.id);
+        assert_eq!(decrypted.name, doc.name);
+        assert_eq!(decrypted.tags, doc.tags);
+
+        // saving again reuses the salt (and the derived key) but never the nonce
+        let again = encrypt_doc(&doc, "secret").unwrap();
+        assert_eq!(
+            again[..ENCRYPTED_MAGIC.len() + SALT_LENGTH],
+            encrypted[..ENCRYPTED_MAGIC.len() + SALT_LENGTH]
+        );
+        assert_ne!(again[..HEADER_LENGTH], encrypted[..HEADER_LENGTH]);
+        assert!(decrypt(&again, "secret").is_ok());
+    }
+}
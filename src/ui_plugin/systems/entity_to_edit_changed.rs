@@ -1,33 +1,61 @@
 use bevy::prelude::*;
 use bevy_cosmic_edit::{get_cosmic_text, ActiveEditor, CosmicEditImage};
-use bevy_markdown::{spawn_bevy_markdown, BevyMarkdown};
+use bevy_markdown::spawn_bevy_markdown;
 
 use crate::utils::ReflectableUuid;
-use bevy_ui_borders::Outline;
 
-use super::{BevyMarkdownView, NodeType, RawText, UiState, VeloNode};
+use super::{
+    markdown_view, node_outline, BevyMarkdownView, CanvasOutline, CanvasZoom, History,
+    HistoryCommand, NodeBorder, NodeEffects, NodeTextStyle, RawText, SelectedNodes, UiState,
+    VeloNode,
+};
 
+/// Shows the editor of the edited node and markdown views of the others. The text edit is
+/// recorded in the history once the node isn't edited anymore.
 pub fn entity_to_edit_changed(
     ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut history: ResMut<History>,
     mut last_entity_to_edit: Local<Option<ReflectableUuid>>,
-    mut velo_node_query: Query<(&mut Outline, &VeloNode, Entity), With<VeloNode>>,
-    mut raw_text_node_query: Query<
+    mut text_at_edit_start: Local<Option<String>>,
+    mut velo_node_query: Query<
         (
-            &mut Style,
-            &RawText,
-            &Parent,
+            &mut CanvasOutline,
+            &VeloNode,
             Entity,
-            &CosmicEditImage,
-            &Node,
+            Option<&NodeBorder>,
+            Option<&NodeEffects>,
+            Option<&NodeTextStyle>,
+            &Sprite,
         ),
+        With<VeloNode>,
+    >,
+    mut raw_text_node_query: Query<
+        (&mut Visibility, &RawText, &Parent, Entity, &CosmicEditImage),
         With<RawText>,
     >,
     mut markdown_text_node_query: Query<(Entity, &BevyMarkdownView), With<BevyMarkdownView>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    zoom: Res<CanvasZoom>,
 ) {
-    if ui_state.is_changed() && ui_state.entity_to_edit != *last_entity_to_edit {
-        match ui_state.entity_to_edit {
+    let entity_to_edit = selected_nodes.edited(&ui_state);
+    if (ui_state.is_changed() || selected_nodes.is_changed())
+        && entity_to_edit != *last_entity_to_edit
+    {
+        let text = |id: ReflectableUuid| {
+            raw_text_node_query
+                .iter()
+                .find(|(_, raw_text, ..)| raw_text.id == id)
+                .map(|(.., cosmic_edit)| get_cosmic_text(&cosmic_edit.editor))
+        };
+        if let (Some(id), Some(from)) = (*last_entity_to_edit, text_at_edit_start.take()) {
+            if let Some(to) = text(id).filter(|to| *to != from) {
+                history.record(HistoryCommand::TextEdited { id: id.0, from, to });
+            }
+        }
+        *text_at_edit_start = entity_to_edit.and_then(text);
+        match entity_to_edit {
             Some(entity_to_edit) => {
                 handle_entity_selection(
                     entity_to_edit,
@@ -36,6 +64,7 @@ pub fn entity_to_edit_changed(
                     &mut markdown_text_node_query,
                     &mut commands,
                     &asset_server,
+                    zoom.0,
                 );
             }
             None => {
@@ -44,83 +73,75 @@ pub fn entity_to_edit_changed(
                     &mut raw_text_node_query,
                     &mut commands,
                     &asset_server,
+                    zoom.0,
                 );
             }
         }
-        *last_entity_to_edit = ui_state.entity_to_edit;
+        *last_entity_to_edit = entity_to_edit;
     }
 }
 
 fn handle_entity_selection(
     entity_to_edit: ReflectableUuid,
-    velo_node_query: &mut Query<(&mut Outline, &VeloNode, Entity), With<VeloNode>>,
-    raw_text_node_query: &mut Query<
+    velo_node_query: &mut Query<
         (
-            &mut Style,
-            &RawText,
-            &Parent,
+            &mut CanvasOutline,
+            &VeloNode,
             Entity,
-            &CosmicEditImage,
-            &Node,
+            Option<&NodeBorder>,
+            Option<&NodeEffects>,
+            Option<&NodeTextStyle>,
+            &Sprite,
         ),
+        With<VeloNode>,
+    >,
+    raw_text_node_query: &mut Query<
+        (&mut Visibility, &RawText, &Parent, Entity, &CosmicEditImage),
         With<RawText>,
     >,
     markdown_text_node_query: &mut Query<(Entity, &BevyMarkdownView), With<BevyMarkdownView>>,
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    zoom: f32,
 ) {
     // Change border for selected node
-    for (mut outline, node, _) in velo_node_query.iter_mut() {
+    for (mut outline, node, _, border, effects, _, _) in velo_node_query.iter_mut() {
         if node.id == entity_to_edit {
             outline.color = Color::rgba(33.0 / 255.0, 150.0 / 255.0, 243.0 / 255.0, 1.0);
-            outline.thickness = UiRect::all(Val::Px(2.));
+            outline.thickness = 2.;
         } else {
-            match node.node_type {
-                NodeType::Rect => {
-                    outline.color = Color::rgb(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0);
-                }
-                NodeType::Circle => {
-                    outline.color = Color::rgba(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0, 0.);
-                }
-            }
-
-            outline.thickness = UiRect::all(Val::Px(1.));
+            *outline = node_outline(&node.node_type, border, effects.map_or(1., |e| e.opacity));
         }
     }
 
     // Hide raw text and have markdown view for all nodes (except selected)
-    for (mut style, raw_text, parent, entity, cosmic_edit, node) in raw_text_node_query.iter_mut() {
+    for (mut visibility, raw_text, parent, entity, cosmic_edit) in raw_text_node_query.iter_mut() {
         if raw_text.id == entity_to_edit {
             commands.insert_resource(ActiveEditor {
                 entity: Some(entity),
             });
-            style.display = Display::Flex;
+            *visibility = Visibility::Inherited;
             continue;
         }
-        if style.display == Display::None {
+        if *visibility == Visibility::Hidden {
             continue;
         }
-        style.display = Display::None;
+        *visibility = Visibility::Hidden;
         let str = get_cosmic_text(&cosmic_edit.editor);
-        let bevy_markdown = BevyMarkdown {
-            text: str,
-            regular_font: Some(TextStyle::default().font),
-            code_font: Some(TextStyle::default().font),
-            bold_font: Some(asset_server.load("fonts/SourceCodePro-Bold.ttf")),
-            italic_font: Some(asset_server.load("fonts/SourceCodePro-Italic.ttf")),
-            semi_bold_italic_font: Some(
-                asset_server.load("fonts/SourceCodePro-SemiBoldItalic.ttf"),
-            ),
-            extra_bold_font: Some(asset_server.load("fonts/SourceCodePro-ExtraBold.ttf")),
-            size: Some((Val::Px(node.size().x), Val::Px(node.size().y))),
-        };
+        let (_, _, entity, _, _, text_style, sprite) = velo_node_query.get(parent.get()).unwrap();
+        let bevy_markdown = markdown_view(
+            str,
+            &text_style.cloned().unwrap_or_default(),
+            sprite.custom_size.unwrap_or_default().x,
+            zoom,
+            asset_server,
+        );
         let markdown_text = spawn_bevy_markdown(commands, bevy_markdown)
             .expect("should handle markdown conversion");
         commands
             .get_entity(markdown_text)
             .unwrap()
             .insert(BevyMarkdownView { id: raw_text.id });
-        let (_, _, entity) = velo_node_query.get(parent.get()).unwrap();
         commands.entity(entity).add_child(markdown_text);
     }
 
@@ -134,59 +155,51 @@ fn handle_entity_selection(
 }
 
 fn handle_no_entity_selection(
-    velo_node_query: &mut Query<(&mut Outline, &VeloNode, Entity), With<VeloNode>>,
-    raw_text_node_query: &mut Query<
+    velo_node_query: &mut Query<
         (
-            &mut Style,
-            &RawText,
-            &Parent,
+            &mut CanvasOutline,
+            &VeloNode,
             Entity,
-            &CosmicEditImage,
-            &Node,
+            Option<&NodeBorder>,
+            Option<&NodeEffects>,
+            Option<&NodeTextStyle>,
+            &Sprite,
         ),
+        With<VeloNode>,
+    >,
+    raw_text_node_query: &mut Query<
+        (&mut Visibility, &RawText, &Parent, Entity, &CosmicEditImage),
         With<RawText>,
     >,
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    zoom: f32,
 ) {
     // Reset border colors and thickness for all nodes
-    for (mut outline, node, _) in velo_node_query.iter_mut() {
-        match node.node_type {
-            NodeType::Rect => {
-                outline.color = Color::rgb(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0);
-            }
-            NodeType::Circle => {
-                outline.color = Color::rgba(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0, 0.);
-            }
-        }
-        outline.thickness = UiRect::all(Val::Px(1.));
+    for (mut outline, node, _, border, effects, _, _) in velo_node_query.iter_mut() {
+        *outline = node_outline(&node.node_type, border, effects.map_or(1., |e| e.opacity));
     }
 
     // Hide raw text and have markdown view for all nodes
-    for (mut style, raw_text, parent, _, cosmic_edit, node) in raw_text_node_query.iter_mut() {
-        if style.display == Display::None {
+    for (mut visibility, raw_text, parent, _, cosmic_edit) in raw_text_node_query.iter_mut() {
+        if *visibility == Visibility::Hidden {
             continue;
         }
-        style.display = Display::None;
+        *visibility = Visibility::Hidden;
         let str = get_cosmic_text(&cosmic_edit.editor);
-        let bevy_markdown = BevyMarkdown {
-            text: str,
-            regular_font: Some(TextStyle::default().font),
-            code_font: Some(TextStyle::default().font),
-            bold_font: Some(asset_server.load("fonts/SourceCodePro-Bold.ttf")),
-            italic_font: Some(asset_server.load("fonts/SourceCodePro-Italic.ttf")),
-            semi_bold_italic_font: Some(
-                asset_server.load("fonts/SourceCodePro-SemiBoldItalic.ttf"),
-            ),
-            extra_bold_font: Some(asset_server.load("fonts/SourceCodePro-ExtraBold.ttf")),
-            size: Some((Val::Px(node.size().x), Val::Px(node.size().y))),
-        };
+        let (_, _, entity, _, _, text_style, sprite) = velo_node_query.get(parent.get()).unwrap();
+        let bevy_markdown = markdown_view(
+            str,
+            &text_style.cloned().unwrap_or_default(),
+            sprite.custom_size.unwrap_or_default().x,
+            zoom,
+            asset_server,
+        );
         let markdown_text = spawn_bevy_markdown(commands, bevy_markdown).unwrap();
         commands
             .get_entity(markdown_text)
             .unwrap()
             .insert(BevyMarkdownView { id: raw_text.id });
-        let (_, _, entity) = velo_node_query.get(parent.get()).unwrap();
         commands.entity(entity).add_child(markdown_text);
     }
 }
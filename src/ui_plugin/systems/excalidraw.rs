@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use super::import::{ImportedEdge, ImportedGraph};
+use crate::canvas::arrow::components::{ArrowDash, ArrowHead, ArrowRouting, ArrowStyle, ArrowType};
+use crate::NodeType;
+
+// Excalidraw stroke widths are 1, 2 and 4 for thin, bold and extra bold
+const STROKE_WIDTH_SCALE: f32 = 1.5;
+
+fn color(color: &serde_json::Value) -> Option<Color> {
+    Color::hex(color.as_str()?.strip_prefix('#')?).ok()
+}
+
+fn head(arrowhead: &serde_json::Value) -> ArrowHead {
+    match arrowhead.as_str() {
+        None => ArrowHead::None,
+        Some("triangle") => ArrowHead::Filled,
+        Some("dot" | "circle" | "circle_outline") => ArrowHead::Circle,
+        Some("diamond" | "diamond_outline") => ArrowHead::Diamond,
+        Some("bar") => ArrowHead::None,
+        Some(_) => ArrowHead::Open,
+    }
+}
+
+/// Rectangles, diamonds, ellipses and free text of an Excalidraw scene as nodes at their
+/// positions, with the text bound to them, and arrows bound to them on both ends as arrows.
+/// Other elements (lines, drawings, images, frames) are skipped.
+pub fn excalidraw_scene(excalidraw: &str) -> ImportedGraph {
+    let scene: serde_json::Value = serde_json::from_str(excalidraw).unwrap_or_default();
+    let elements: Vec<_> = scene["elements"]
+        .as_array()
+        .map(|elements| {
+            elements
+                .iter()
+                .filter(|element| !element["isDeleted"].as_bool().unwrap_or(false))
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut graph = ImportedGraph::default();
+    let mut nodes = HashMap::new();
+    for element in elements.iter() {
+        let element_type = element["type"].as_str().unwrap_or_default();
+        let width = element["width"].as_f64().unwrap_or_default() as f32;
+        let height = element["height"].as_f64().unwrap_or_default() as f32;
+        let node_type = match element_type {
+            "rectangle" => NodeType::Rect,
+            "diamond" => NodeType::Diamond,
+            "ellipse" if (width - height).abs() < 1. => NodeType::Circle,
+            "ellipse" => NodeType::Ellipse,
+            "text" if element["containerId"].is_null() => NodeType::Rect,
+            _ => continue,
+        };
+        let text = match element_type {
+            "text" => element["originalText"]
+                .as_str()
+                .or(element["text"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        };
+        let index = graph.add_node(text, node_type);
+        let node = &mut graph.nodes[index];
+        if let Some(bg_color) = color(&element["backgroundColor"]) {
+            node.bg_color = bg_color;
+        }
+        // excalidraw has y axis pointing down
+        let x = element["x"].as_f64().unwrap_or_default() as f32;
+        let y = element["y"].as_f64().unwrap_or_default() as f32;
+        node.rect = Some(Rect::new(x, -y, x + width, -y - height));
+        if let Some(id) = element["id"].as_str() {
+            nodes.insert(id, index);
+        }
+    }
+    let mut labels = HashMap::new();
+    for element in elements.iter() {
+        let Some(container) = element["containerId"].as_str() else {
+            continue;
+        };
+        if element["type"].as_str() != Some("text") {
+            continue;
+        }
+        let text = element["originalText"]
+            .as_str()
+            .or(element["text"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        match nodes.get(container) {
+            Some(index) => graph.nodes[*index].text = text,
+            None => {
+                labels.insert(container, text);
+            }
+        }
+    }
+    for element in elements.iter() {
+        if element["type"].as_str() != Some("arrow") {
+            continue;
+        }
+        let (Some(start), Some(end)) = (
+            element["startBinding"]["elementId"]
+                .as_str()
+                .and_then(|id| nodes.get(id)),
+            element["endBinding"]["elementId"]
+                .as_str()
+                .and_then(|id| nodes.get(id)),
+        ) else {
+            continue;
+        };
+        let (mut start, mut end) = (*start, *end);
+        let mut start_head = head(&element["startArrowhead"]);
+        let mut end_head = head(&element["endArrowhead"]);
+        if start_head != ArrowHead::None && end_head == ArrowHead::None {
+            (start, end) = (end, start);
+            (start_head, end_head) = (end_head, start_head);
+        }
+        let arrow_type = match (start_head, end_head) {
+            (ArrowHead::None, ArrowHead::None) => ArrowType::Line,
+            (ArrowHead::None, _) => ArrowType::Arrow,
+            _ => ArrowType::DoubleArrow,
+        };
+        let mut style = ArrowStyle::for_type(arrow_type);
+        style.start_head = start_head;
+        style.end_head = end_head;
+        style.dash = match element["strokeStyle"].as_str() {
+            Some("dashed") => ArrowDash::Dashed,
+            Some("dotted") => ArrowDash::Dotted,
+            _ => ArrowDash::Solid,
+        };
+        if let Some(stroke_color) = color(&element["strokeColor"]) {
+            style.color = stroke_color;
+        }
+        if let Some(width) = element["strokeWidth"].as_f64() {
+            style.thickness = width as f32 * STROKE_WIDTH_SCALE;
+        }
+        style.routing = if element["elbowed"].as_bool().unwrap_or(false) {
+            ArrowRouting::Elbow
+        } else if element["roundness"].is_null() {
+            ArrowRouting::Straight
+        } else {
+            ArrowRouting::Curved
+        };
+        let label = element["id"]
+            .as_str()
+            .and_then(|id| labels.get(id))
+            .filter(|label| !label.trim().is_empty())
+            .cloned();
+        graph.edges.push(ImportedEdge {
+            start,
+            end,
+            arrow_type,
+            style,
+            label,
+        });
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excalidraw_scene() {
+        let graph = excalidraw_scene(
+            r##"{
+                "type": "excalidraw",
+                "elements": [
+                    {"id": "a", "type": "rectangle", "x": 10, "y": 20, "width": 100, "height": 50,
+                     "backgroundColor": "#ffc9c9", "boundElements": [{"id": "t", "type": "text"}]},
+                    {"id": "t", "type": "text", "x": 20, "y": 30, "width": 80, "height": 20,
+                     "text": "Start", "originalText": "Start", "containerId": "a"},
+                    {"id": "b", "type": "ellipse", "x": 200, "y": 20, "width": 60, "height": 60,
+                     "backgroundColor": "transparent"},
+                    {"id": "gone", "type": "diamond", "x": 0, "y": 0, "width": 10, "height": 10,
+                     "isDeleted": true},
+                    {"id": "note", "type": "text", "x": 0, "y": 200, "width": 80, "height": 20,
+                     "text": "Note", "containerId": null},
+                    {"id": "arrow", "type": "arrow", "x": 110, "y": 45, "width": 90, "height": 0,
+                     "strokeStyle": "dashed", "strokeColor": "#1e1e1e", "strokeWidth": 2,
+                     "roundness": null, "startArrowhead": "arrow", "endArrowhead": null,
+                     "startBinding": {"elementId": "a"}, "endBinding": {"elementId": "b"}},
+                    {"id": "label", "type": "text", "x": 140, "y": 35, "width": 30, "height": 20,
+                     "text": "next", "containerId": "arrow"},
+                    {"id": "loose", "type": "arrow", "startBinding": null,
+                     "endBinding": {"elementId": "b"}}
+                ]
+            }"##,
+        );
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|node| (node.text.as_str(), node.node_type.clone(), node.rect))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (
+                    "Start",
+                    NodeType::Rect,
+                    Some(Rect::new(10., -20., 110., -70.))
+                ),
+                (
+                    "",
+                    NodeType::Circle,
+                    Some(Rect::new(200., -20., 260., -80.))
+                ),
+                (
+                    "Note",
+                    NodeType::Rect,
+                    Some(Rect::new(0., -200., 80., -220.))
+                ),
+            ]
+        );
+        assert_eq!(graph.nodes[1].bg_color, Color::WHITE);
+        assert_eq!(graph.edges.len(), 1);
+        let edge = &graph.edges[0];
+        // the arrow points to its start
+        assert_eq!((edge.start, edge.end), (1, 0));
+        assert_eq!(edge.arrow_type, ArrowType::Arrow);
+        assert_eq!(edge.style.end_head, ArrowHead::Open);
+        assert_eq!(edge.style.dash, ArrowDash::Dashed);
+        assert_eq!(edge.style.thickness, 3.);
+        assert_eq!(edge.label.as_deref(), Some("next"));
+    }
+}
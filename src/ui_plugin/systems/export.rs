@@ -0,0 +1,232 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use super::compression::{write_doc, Compression};
+use super::dot::tab_to_dot;
+use super::encryption::encrypt;
+use super::export_markdown::tab_to_markdown;
+use super::export_pdf::doc_to_pdf;
+use super::export_png::tab_to_png;
+use super::export_svg::tab_to_svg;
+use super::json_canvas::tab_to_json_canvas;
+use super::mermaid::tab_to_mermaid;
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowStyle};
+use crate::components::{Doc, Tab};
+use crate::utils::convert_from_val_px;
+use crate::{JsonNode, JsonStroke};
+
+/// Arrow of a saved tab, waypoints are in canvas coordinates.
+pub struct ExportedArrow {
+    pub meta: ArrowMeta,
+    pub style: ArrowStyle,
+    pub label: Option<String>,
+    pub relation: Option<String>,
+    pub waypoints: Vec<Vec2>,
+}
+
+impl ExportedArrow {
+    /// Text shown next to the arrow: its label followed by the relation in parentheses.
+    pub fn text(&self) -> Option<String> {
+        match (&self.label, &self.relation) {
+            (Some(label), Some(relation)) => Some(format!("{} ({})", label, relation)),
+            (Some(text), None) | (None, Some(text)) => Some(text.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Contents of the last checkpoint of a tab, as read by the exporters.
+pub struct ExportedTab {
+    pub name: String,
+    pub nodes: Vec<JsonNode>,
+    pub arrows: Vec<ExportedArrow>,
+    pub strokes: Vec<JsonStroke>,
+}
+
+impl ExportedTab {
+    pub fn from_tab(tab: &Tab) -> Self {
+        let json: serde_json::Value = tab
+            .checkpoint
+            .as_ref()
+            .and_then(|checkpoint| serde_json::from_str(checkpoint).ok())
+            .unwrap_or_default();
+        let nodes = json["nodes"]
+            .as_array()
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .filter_map(|node| serde_json::from_value(node.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let arrows = json["arrows"]
+            .as_array()
+            .map(|arrows| {
+                arrows
+                    .iter()
+                    .filter_map(|arrow| {
+                        let meta: ArrowMeta = serde_json::from_value(arrow.clone()).ok()?;
+                        Some(ExportedArrow {
+                            meta,
+                            style: ArrowStyle::from_json(arrow, meta.arrow_type),
+                            label: arrow["label"].as_str().map(|label| label.to_string()),
+                            relation: arrow["relation"]
+                                .as_str()
+                                .map(|relation| relation.to_string()),
+                            waypoints: serde_json::from_value::<Vec<[f32; 2]>>(
+                                arrow["waypoints"].clone(),
+                            )
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|[x, y]| Vec2::new(x, y))
+                            .collect(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let strokes = json["strokes"]
+            .as_array()
+            .map(|strokes| {
+                strokes
+                    .iter()
+                    .filter_map(|stroke| serde_json::from_value(stroke.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            name: tab.name.clone(),
+            nodes,
+            arrows,
+            strokes,
+        }
+    }
+
+    /// Indices of the nodes top to bottom and then left to right.
+    pub fn reading_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_by(|a, b| {
+            let (a, b) = (node_rect(&self.nodes[*a]), node_rect(&self.nodes[*b]));
+            b.max
+                .y
+                .total_cmp(&a.max.y)
+                .then(a.min.x.total_cmp(&b.min.x))
+        });
+        order
+    }
+
+    /// Node the arrow end is attached to.
+    pub fn node(&self, connect: &ArrowConnect) -> Option<&JsonNode> {
+        self.nodes.iter().find(|node| node.id == connect.id.0)
+    }
+
+    /// Canvas position of the connection point of the arrow end.
+    pub fn connection_point(&self, connect: &ArrowConnect) -> Option<Vec2> {
+        let rect = node_rect(self.node(connect)?);
+        Some(match connect.pos {
+            ArrowConnectPos::Top => Vec2::new(rect.center().x, rect.max.y),
+            ArrowConnectPos::Bottom => Vec2::new(rect.center().x, rect.min.y),
+            ArrowConnectPos::Left => Vec2::new(rect.min.x, rect.center().y),
+            ArrowConnectPos::Right => Vec2::new(rect.max.x, rect.center().y),
+        })
+    }
+
+    /// Canvas points the arrow goes through, from its start to its end.
+    pub fn arrow_points(&self, arrow: &ExportedArrow) -> Option<Vec<Vec2>> {
+        let mut points = vec![self.connection_point(&arrow.meta.start)?];
+        points.extend(arrow.waypoints.iter().copied());
+        points.push(self.connection_point(&arrow.meta.end)?);
+        Some(points)
+    }
+}
+
+/// Rectangle of the node in canvas coordinates (y axis pointing up).
+pub fn node_rect(node: &JsonNode) -> Rect {
+    let min = Vec2::new(
+        convert_from_val_px(node.left),
+        convert_from_val_px(node.bottom),
+    );
+    let size = Vec2::new(
+        convert_from_val_px(node.width),
+        convert_from_val_px(node.height),
+    );
+    Rect::from_corners(min, min + size)
+}
+
+/// Tab shown in the document, exported by the single tab formats.
+fn active_tab(doc: &Doc) -> Option<&Tab> {
+    doc.tabs
+        .iter()
+        .find(|tab| tab.is_active)
+        .or_else(|| doc.tabs.first())
+}
+
+/// False for the formats `write_document` exports the active tab to, which can't be read back as
+/// the document.
+pub fn is_document_path(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    !matches!(
+        extension.as_deref(),
+        Some("pdf" | "png" | "svg" | "md" | "mmd" | "mermaid" | "dot" | "gv" | "canvas")
+    )
+}
+
+/// Contents of the document in the format given by the extension of the file: `.pdf`, `.png`,
+/// `.svg`, `.md`, `.mmd`, `.dot` or `.canvas` (the active tab only, except for `.pdf`) or velo
+/// json for any other one, compressed if the extension is `.gz` or `.zst` and encrypted if there
+/// is a passphrase.
+pub fn document_bytes(
+    path: &Path,
+    doc: &Doc,
+    passphrase: Option<&str>,
+) -> std::io::Result<Vec<u8>> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    let exported_tab = || active_tab(doc).map(ExportedTab::from_tab);
+    let bytes = match extension.as_deref() {
+        Some("pdf") => doc_to_pdf(doc),
+        Some("png") => exported_tab()
+            .map(|tab| tab_to_png(&tab))
+            .unwrap_or_default(),
+        Some("svg") => exported_tab()
+            .map(|tab| tab_to_svg(&tab))
+            .unwrap_or_default()
+            .into_bytes(),
+        Some("md") => exported_tab()
+            .map(|tab| tab_to_markdown(&tab))
+            .unwrap_or_default()
+            .into_bytes(),
+        Some("mmd") | Some("mermaid") => exported_tab()
+            .map(|tab| tab_to_mermaid(&tab))
+            .unwrap_or_default()
+            .into_bytes(),
+        Some("dot") | Some("gv") => exported_tab()
+            .map(|tab| tab_to_dot(&tab))
+            .unwrap_or_default()
+            .into_bytes(),
+        Some("canvas") => exported_tab()
+            .map(|tab| tab_to_json_canvas(&tab))
+            .unwrap_or_default()
+            .into_bytes(),
+        _ => {
+            let mut data = vec![];
+            write_doc(&mut data, doc, Compression::from_path(path))?;
+            match passphrase {
+                Some(passphrase) => encrypt(&data, passphrase, doc.id)?,
+                None => data,
+            }
+        }
+    };
+    Ok(bytes)
+}
+
+/// Writes the document to the file, see `document_bytes` for the formats.
+pub fn write_document(path: &Path, doc: &Doc, passphrase: Option<&str>) -> std::io::Result<()> {
+    std::fs::write(path, document_bytes(path, doc, passphrase)?)
+}
@@ -0,0 +1,173 @@
+use std::fmt::Write;
+
+use super::export::ExportedTab;
+
+/// Nodes of the tab in reading order as `(node index, depth, relationship of the arrow leading
+/// to it)`. Nodes without incoming arrows start trees, top to bottom and then left to right,
+/// followed by the nodes their arrows point to. Nodes only reachable through cycles start trees
+/// too.
+pub fn outline(tab: &ExportedTab) -> Vec<(usize, usize, Option<&str>)> {
+    let order = tab.reading_order();
+    let mut rank = vec![0; tab.nodes.len()];
+    for (position, index) in order.iter().enumerate() {
+        rank[*index] = position;
+    }
+    let index_of = |id| tab.nodes.iter().position(|node| node.id == id);
+    let mut children: Vec<Vec<(usize, Option<&str>)>> = vec![vec![]; tab.nodes.len()];
+    let mut has_parent = vec![false; tab.nodes.len()];
+    for arrow in tab.arrows.iter() {
+        let (Some(start), Some(end)) = (
+            index_of(arrow.meta.start.id.0),
+            index_of(arrow.meta.end.id.0),
+        ) else {
+            continue;
+        };
+        if start != end {
+            children[start].push((end, arrow.relation.as_deref()));
+            has_parent[end] = true;
+        }
+    }
+    for list in children.iter_mut() {
+        list.sort_by_key(|(child, _)| rank[*child]);
+    }
+    let roots = order
+        .iter()
+        .filter(|index| !has_parent[**index])
+        .chain(order.iter());
+    let mut visited = vec![false; tab.nodes.len()];
+    let mut outline = vec![];
+    for root in roots {
+        let mut stack = vec![(*root, 0, None)];
+        while let Some((index, depth, relation)) = stack.pop() {
+            if visited[index] {
+                continue;
+            }
+            visited[index] = true;
+            outline.push((index, depth, relation));
+            for (child, relation) in children[index].iter().rev() {
+                stack.push((*child, depth + 1, *relation));
+            }
+        }
+    }
+    outline
+}
+
+/// Markdown with tree roots as sections and the nodes they lead to as nested bullets. Tags of
+/// the nodes are kept in the front matter.
+pub fn tab_to_markdown(tab: &ExportedTab) -> String {
+    let mut tags: Vec<&String> = tab.nodes.iter().flat_map(|node| node.tags.iter()).collect();
+    tags.sort();
+    tags.dedup();
+    // json strings and arrays are valid yaml
+    let mut markdown = format!(
+        "---\ntitle: {}\ntags: {}\n---\n",
+        serde_json::to_string(&tab.name).unwrap(),
+        serde_json::to_string(&tags).unwrap()
+    );
+    for (index, depth, relation) in outline(tab) {
+        let text = tab.nodes[index].text.text.trim();
+        let mut lines = text.lines();
+        let Some(first) = lines.next() else {
+            continue;
+        };
+        if depth == 0 {
+            let _ = writeln!(markdown, "\n## {}", first.trim_start_matches('#').trim());
+            let rest: Vec<_> = lines.collect();
+            if !rest.is_empty() {
+                let _ = writeln!(markdown, "\n{}", rest.join("\n").trim());
+            }
+            markdown.push('\n');
+            continue;
+        }
+        let indent = "  ".repeat(depth - 1);
+        let relation = relation.map_or(String::new(), |relation| format!("*{}:* ", relation));
+        let _ = writeln!(markdown, "{}- {}{}", indent, relation, first);
+        for line in lines {
+            let _ = writeln!(markdown, "{}  {}", indent, line);
+        }
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::canvas::arrow::components::{
+        ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowStyle, ArrowType,
+    };
+    use crate::ui_plugin::export::ExportedArrow;
+    use crate::utils::ReflectableUuid;
+    use crate::{JsonNode, JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos};
+
+    fn node(id: u128, text: &str, left: f32, bottom: f32) -> JsonNode {
+        JsonNode {
+            id: Uuid::from_u128(id),
+            node_type: NodeType::Rect,
+            left: Val::Px(left),
+            bottom: Val::Px(bottom),
+            width: Val::Px(100.),
+            height: Val::Px(50.),
+            text: JsonNodeText {
+                text: text.to_string(),
+                pos: TextPos::Center,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::WHITE,
+            z_index: 0,
+            locked: false,
+            border: NodeBorder::default(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        }
+    }
+
+    fn arrow(start: u128, end: u128, relation: Option<&str>) -> ExportedArrow {
+        let connect = |id| ArrowConnect {
+            id: ReflectableUuid(Uuid::from_u128(id)),
+            pos: ArrowConnectPos::Top,
+        };
+        ExportedArrow {
+            meta: ArrowMeta {
+                arrow_type: ArrowType::Arrow,
+                start: connect(start),
+                end: connect(end),
+            },
+            style: ArrowStyle::default(),
+            label: None,
+            relation: relation.map(|relation| relation.to_string()),
+            waypoints: vec![],
+        }
+    }
+
+    #[test]
+    fn test_tab_to_markdown() {
+        let mut root = node(1, "# Plan\nfirst steps", 0., 300.);
+        root.tags = vec!["idea".to_string()];
+        let tab = ExportedTab {
+            name: "Tab 1".to_string(),
+            nodes: vec![
+                node(4, "Later", 0., 0.),
+                node(3, "Ship", 200., 150.),
+                node(2, "Build\nand test", 0., 150.),
+                root,
+            ],
+            arrows: vec![
+                arrow(1, 3, Some("blocks")),
+                arrow(1, 2, None),
+                // cycles don't repeat nodes
+                arrow(3, 2, None),
+            ],
+            strokes: vec![],
+        };
+        assert_eq!(
+            tab_to_markdown(&tab),
+            "---\ntitle: \"Tab 1\"\ntags: [\"idea\"]\n---\n\n## Plan\n\nfirst steps\n\n- Build\n  and test\n- *blocks:* Ship\n\n## Later\n\n"
+        );
+    }
+}
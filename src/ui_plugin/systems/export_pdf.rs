@@ -0,0 +1,545 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use super::export::{node_rect, ExportedArrow, ExportedTab};
+use crate::canvas::arrow::components::{ArrowDash, ArrowHead};
+use crate::components::Doc;
+use crate::utils::convert_from_val_px;
+use crate::{BorderStyle, JsonNode, JsonStroke, NodeFont, NodeType, TextPos};
+
+// Tabs bigger than that are split into tiles of that size, in points (1px of the canvas)
+const MAX_PAGE_SIZE: f32 = 1440.;
+pub const PAGE_MARGIN: f32 = 20.;
+// A4 page used for empty tabs
+const EMPTY_PAGE_SIZE: Vec2 = Vec2::new(595., 842.);
+pub const TEXT_PADDING: f32 = 8.;
+pub const LINE_HEIGHT: f32 = 1.2;
+// Average width of a Helvetica character relative to the font size, used for wrapping
+pub const CHAR_WIDTH: f32 = 0.5;
+pub const HEAD_LENGTH: f32 = 10.;
+// Distance of bezier control points approximating a quarter of an ellipse
+const KAPPA: f32 = 0.5523;
+pub const IMAGE_PLACEHOLDER_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+
+/// PDF with a page per tab, tabs bigger than `MAX_PAGE_SIZE` are tiled over several pages.
+/// Nodes, arrows and pen strokes are vector graphics and node texts are selectable text in the
+/// standard Helvetica fonts, images are drawn as placeholders.
+pub fn doc_to_pdf(doc: &Doc) -> Vec<u8> {
+    // 1: catalog, 2: page tree, 3-5: fonts, tabs and pages follow
+    let mut objects: Vec<Vec<u8>> = vec![vec![], vec![]];
+    for font in ["Helvetica", "Helvetica-Bold", "Helvetica-Oblique"] {
+        objects.push(
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                font
+            )
+            .into_bytes(),
+        );
+    }
+    let mut pages = vec![];
+    for tab in doc.tabs.iter() {
+        let tab = ExportedTab::from_tab(tab);
+        let bounds = tab_bounds(&tab);
+        // the tab is drawn once and shown on every tile through the form
+        objects.push(stream(
+            &format!(
+                "/Type /XObject /Subtype /Form /BBox [{} {} {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >>",
+                bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y
+            ),
+            &tab_content(&tab),
+        ));
+        let form = objects.len();
+        for tile in tiles(bounds) {
+            objects.push(stream(
+                "",
+                &format!("q 1 0 0 1 {} {} cm /Tab Do Q", -tile.min.x, -tile.min.y),
+            ));
+            let contents = objects.len();
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Tab {} 0 R >> >> /Contents {} 0 R >>",
+                    tile.width(),
+                    tile.height(),
+                    form,
+                    contents
+                )
+                .into_bytes(),
+            );
+            pages.push(objects.len());
+        }
+    }
+    objects[0] = b"<< /Type /Catalog /Pages 2 0 R >>".to_vec();
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        pages
+            .iter()
+            .map(|page| format!("{} 0 R", page))
+            .collect::<Vec<_>>()
+            .join(" "),
+        pages.len()
+    )
+    .into_bytes();
+    write_pdf(&objects)
+}
+
+/// File with the objects numbered from 1 in order, followed by their cross-reference table.
+fn write_pdf(objects: &[Vec<u8>]) -> Vec<u8> {
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(table, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        table,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    pdf.extend(table.into_bytes());
+    pdf
+}
+
+fn stream(dictionary: &str, content: &str) -> Vec<u8> {
+    format!(
+        "<< {} /Length {} >>\nstream\n{}\nendstream",
+        dictionary,
+        content.len(),
+        content
+    )
+    .into_bytes()
+}
+
+/// Canvas area with contents of the tab and a margin around them.
+pub fn tab_bounds(tab: &ExportedTab) -> Rect {
+    let points = tab
+        .nodes
+        .iter()
+        .flat_map(|node| {
+            let rect = node_rect(node);
+            [rect.min, rect.max]
+        })
+        .chain(tab.arrows.iter().flat_map(|arrow| arrow.waypoints.clone()))
+        .chain(
+            tab.strokes
+                .iter()
+                .flat_map(|stroke| stroke.points.iter().map(|[x, y]| Vec2::new(*x, *y))),
+        );
+    let mut bounds: Option<Rect> = None;
+    for point in points {
+        bounds = Some(bounds.map_or(Rect::from_corners(point, point), |bounds| {
+            bounds.union_point(point)
+        }));
+    }
+    match bounds {
+        Some(bounds) => bounds.inset(PAGE_MARGIN),
+        None => Rect::from_corners(Vec2::ZERO, EMPTY_PAGE_SIZE),
+    }
+}
+
+/// Tiles covering the bounds row by row from the top left corner, a single one for bounds
+/// fitting a page.
+fn tiles(bounds: Rect) -> Vec<Rect> {
+    let columns = (bounds.width() / MAX_PAGE_SIZE).ceil().max(1.) as usize;
+    let rows = (bounds.height() / MAX_PAGE_SIZE).ceil().max(1.) as usize;
+    let mut tiles = vec![];
+    for row in 0..rows {
+        let top = bounds.max.y - row as f32 * MAX_PAGE_SIZE;
+        let bottom = (top - MAX_PAGE_SIZE).max(bounds.min.y);
+        for column in 0..columns {
+            let left = bounds.min.x + column as f32 * MAX_PAGE_SIZE;
+            let right = (left + MAX_PAGE_SIZE).min(bounds.max.x);
+            tiles.push(Rect::new(left, bottom, right, top));
+        }
+    }
+    tiles
+}
+
+fn rgb(color: Color) -> String {
+    let [r, g, b, _] = color.as_rgba_f32();
+    format!("{:.3} {:.3} {:.3}", r, g, b)
+}
+
+fn polyline(content: &mut String, points: &[Vec2]) {
+    for (index, point) in points.iter().enumerate() {
+        let operator = if index == 0 { "m" } else { "l" };
+        let _ = writeln!(content, "{:.2} {:.2} {}", point.x, point.y, operator);
+    }
+}
+
+/// Content stream drawing the tab: pen strokes and arrows under the nodes, like on the canvas.
+fn tab_content(tab: &ExportedTab) -> String {
+    let mut content = String::from("1 J 1 j\n");
+    for stroke in tab.strokes.iter() {
+        draw_stroke(&mut content, stroke);
+    }
+    for arrow in tab.arrows.iter() {
+        draw_arrow(&mut content, tab, arrow);
+    }
+    let mut nodes: Vec<_> = tab.nodes.iter().collect();
+    nodes.sort_by_key(|node| node.z_index);
+    for node in nodes {
+        draw_node(&mut content, node);
+    }
+    content
+}
+
+fn draw_stroke(content: &mut String, stroke: &JsonStroke) {
+    let points: Vec<_> = stroke
+        .points
+        .iter()
+        .map(|[x, y]| Vec2::new(*x, *y))
+        .collect();
+    let _ = writeln!(
+        content,
+        "{} RG {:.2} w [] 0 d",
+        rgb(stroke.color),
+        stroke.width
+    );
+    polyline(content, &points);
+    content.push_str("S\n");
+}
+
+fn draw_arrow(content: &mut String, tab: &ExportedTab, arrow: &ExportedArrow) {
+    let Some(points) = tab.arrow_points(arrow) else {
+        return;
+    };
+    let (start, end) = (points[0], points[points.len() - 1]);
+    let style = arrow.style;
+    let dash = match style.dash {
+        ArrowDash::Solid => "[]".to_string(),
+        ArrowDash::Dashed => format!("[{} {}]", style.thickness * 4., style.thickness * 3.),
+        ArrowDash::Dotted => format!("[0 {}]", style.thickness * 2.5),
+    };
+    let _ = writeln!(
+        content,
+        "{} RG {} rg {:.2} w {} 0 d",
+        rgb(style.color),
+        rgb(style.color),
+        style.thickness,
+        dash
+    );
+    polyline(content, &points);
+    content.push_str("S\n[] 0 d\n");
+    draw_head(content, points[1], start, style.start_head);
+    draw_head(content, points[points.len() - 2], end, style.end_head);
+    let Some(text) = arrow.text() else {
+        return;
+    };
+    let middle = points[points.len() / 2 - 1].lerp(points[points.len() / 2], 0.5);
+    let font_size = 12.;
+    let width = text.chars().count() as f32 * font_size * CHAR_WIDTH;
+    let _ = writeln!(
+        content,
+        "BT /F1 {} Tf {} rg {:.2} {:.2} Td {} Tj ET",
+        font_size,
+        rgb(style.color),
+        middle.x - width / 2.,
+        middle.y + 4.,
+        pdf_string(&text)
+    );
+}
+
+/// Head of the arrow at `tip` pointing away from `from`.
+fn draw_head(content: &mut String, from: Vec2, tip: Vec2, head: ArrowHead) {
+    let direction = (tip - from).normalize_or_zero();
+    if head == ArrowHead::None || direction == Vec2::ZERO {
+        return;
+    }
+    let back = tip - direction * HEAD_LENGTH;
+    let side = direction.perp() * HEAD_LENGTH / 2.;
+    match head {
+        ArrowHead::None => {}
+        ArrowHead::Filled => {
+            polyline(content, &[tip, back + side, back - side]);
+            content.push_str("h f\n");
+        }
+        ArrowHead::Open => {
+            polyline(content, &[back + side, tip, back - side]);
+            content.push_str("S\n");
+        }
+        ArrowHead::Diamond => {
+            let middle = tip - direction * HEAD_LENGTH / 2.;
+            polyline(content, &[tip, middle + side, back, middle - side]);
+            content.push_str("h f\n");
+        }
+        ArrowHead::Circle => {
+            let center = tip - direction * HEAD_LENGTH / 2.;
+            ellipse(content, center, Vec2::splat(HEAD_LENGTH / 2.));
+            content.push_str("f\n");
+        }
+    }
+}
+
+/// Closed ellipse path made of four bezier curves.
+fn ellipse(content: &mut String, center: Vec2, radius: Vec2) {
+    let (c, r, k) = (center, radius, radius * KAPPA);
+    let _ = writeln!(content, "{:.2} {:.2} m", c.x + r.x, c.y);
+    let curves = [
+        (
+            c + Vec2::new(r.x, k.y),
+            c + Vec2::new(k.x, r.y),
+            c + Vec2::new(0., r.y),
+        ),
+        (
+            c + Vec2::new(-k.x, r.y),
+            c + Vec2::new(-r.x, k.y),
+            c + Vec2::new(-r.x, 0.),
+        ),
+        (
+            c + Vec2::new(-r.x, -k.y),
+            c + Vec2::new(-k.x, -r.y),
+            c + Vec2::new(0., -r.y),
+        ),
+        (
+            c + Vec2::new(k.x, -r.y),
+            c + Vec2::new(r.x, -k.y),
+            c + Vec2::new(r.x, 0.),
+        ),
+    ];
+    for (a, b, end) in curves {
+        let _ = writeln!(
+            content,
+            "{:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c",
+            a.x, a.y, b.x, b.y, end.x, end.y
+        );
+    }
+    content.push_str("h\n");
+}
+
+fn draw_node(content: &mut String, node: &JsonNode) {
+    let rect = node_rect(node);
+    let (min, max, center) = (rect.min, rect.max, rect.center());
+    let (w, h) = (rect.width(), rect.height());
+    match node.node_type {
+        NodeType::Circle | NodeType::Ellipse => ellipse(content, center, rect.half_size()),
+        NodeType::Diamond => {
+            polyline(
+                content,
+                &[
+                    Vec2::new(center.x, min.y),
+                    Vec2::new(max.x, center.y),
+                    Vec2::new(center.x, max.y),
+                    Vec2::new(min.x, center.y),
+                ],
+            );
+            content.push_str("h\n");
+        }
+        NodeType::Hexagon => {
+            polyline(
+                content,
+                &[
+                    Vec2::new(min.x + w / 4., min.y),
+                    Vec2::new(max.x - w / 4., min.y),
+                    Vec2::new(max.x, center.y),
+                    Vec2::new(max.x - w / 4., max.y),
+                    Vec2::new(min.x + w / 4., max.y),
+                    Vec2::new(min.x, center.y),
+                ],
+            );
+            content.push_str("h\n");
+        }
+        NodeType::Parallelogram => {
+            polyline(
+                content,
+                &[
+                    Vec2::new(min.x, min.y),
+                    Vec2::new(max.x - w / 5., min.y),
+                    Vec2::new(max.x, max.y),
+                    Vec2::new(min.x + w / 5., max.y),
+                ],
+            );
+            content.push_str("h\n");
+        }
+        _ => {
+            let _ = writeln!(content, "{:.2} {:.2} {:.2} {:.2} re", min.x, min.y, w, h);
+        }
+    }
+    let border = &node.border;
+    let dash = match border.style {
+        BorderStyle::Solid => "[]".to_string(),
+        BorderStyle::Dashed => format!("[{} {}]", border.width * 4., border.width * 3.),
+        BorderStyle::Dotted => format!("[0 {}]", border.width * 2.5),
+    };
+    let _ = write!(
+        content,
+        "{} RG {:.2} w {} 0 d ",
+        rgb(border.color),
+        border.width,
+        dash
+    );
+    // frames only outline their members
+    match node.node_type {
+        NodeType::Frame => content.push_str("S\n"),
+        NodeType::Image => {
+            let _ = writeln!(content, "{} rg B", rgb(IMAGE_PLACEHOLDER_COLOR));
+        }
+        _ => {
+            let _ = writeln!(content, "{} rg B", rgb(node.bg_color));
+        }
+    }
+    draw_text(content, node);
+}
+
+fn draw_text(content: &mut String, node: &JsonNode) {
+    let style = &node.text.style;
+    let font = match style.font {
+        NodeFont::Regular => "F1",
+        NodeFont::Bold => "F2",
+        NodeFont::Italic => "F3",
+    };
+    let width = convert_from_val_px(node.width) - 2. * TEXT_PADDING;
+    let max_chars = (width / (style.font_size * CHAR_WIDTH)).floor().max(1.) as usize;
+    let lines = wrap_text(&node.text.text, max_chars);
+    if lines.is_empty() {
+        return;
+    }
+    let rect = node_rect(node);
+    let line_height = style.font_size * LINE_HEIGHT;
+    let text_height = lines.len() as f32 * line_height;
+    let top = match node.text.pos {
+        TextPos::Center => rect.center().y + text_height / 2.,
+        TextPos::TopLeft => rect.max.y - TEXT_PADDING,
+    };
+    let _ = writeln!(
+        content,
+        "BT /{} {} Tf {} rg",
+        font,
+        style.font_size,
+        rgb(style.color)
+    );
+    for (index, line) in lines.iter().enumerate() {
+        let line_width = line.chars().count() as f32 * style.font_size * CHAR_WIDTH;
+        let x = match node.text.pos {
+            TextPos::Center => rect.center().x - line_width / 2.,
+            TextPos::TopLeft => rect.min.x + TEXT_PADDING,
+        };
+        // baseline sits below the top of the line
+        let y = top - index as f32 * line_height - style.font_size;
+        let _ = writeln!(
+            content,
+            "1 0 0 1 {:.2} {:.2} Tm {} Tj",
+            x,
+            y,
+            pdf_string(line)
+        );
+    }
+    content.push_str("ET\n");
+}
+
+/// Lines of the text wrapped at word boundaries to at most `max_chars` characters, longer words
+/// are cut.
+pub fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            while word.len() > max_chars {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                lines.push(word.drain(..max_chars).collect());
+            }
+            let word: String = word.into_iter().collect();
+            if line.is_empty() {
+                line = word;
+            } else if line.chars().count() + 1 + word.chars().count() <= max_chars {
+                line.push(' ');
+                line.push_str(&word);
+            } else {
+                lines.push(std::mem::replace(&mut line, word));
+            }
+        }
+        lines.push(line);
+    }
+    // trailing empty lines don't show
+    while lines.last().map_or(false, |line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+/// PDF literal string in the WinAnsi encoding of the standard fonts, characters outside of
+/// Latin-1 are replaced with `?`.
+pub fn pdf_string(text: &str) -> String {
+    let mut string = String::from("(");
+    for char in text.chars() {
+        match char {
+            '(' | ')' | '\\' => {
+                string.push('\\');
+                string.push(char);
+            }
+            ' '..='~' => string.push(char),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(string, "\\{:03o}", char as u32);
+            }
+            _ => string.push('?'),
+        }
+    }
+    string.push(')');
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(
+            wrap_text("one two three\n\nfour", 7),
+            vec!["one two", "three", "", "four"]
+        );
+        assert_eq!(wrap_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert!(wrap_text("\n", 5).is_empty());
+    }
+
+    #[test]
+    fn test_pdf_string() {
+        assert_eq!(pdf_string("a (b) \\ é ✓"), "(a \\(b\\) \\\\ \\351 ?)");
+    }
+
+    #[test]
+    fn test_tiles() {
+        let bounds = Rect::new(0., 0., MAX_PAGE_SIZE * 1.5, 100.);
+        assert_eq!(
+            tiles(bounds),
+            vec![
+                Rect::new(0., 0., MAX_PAGE_SIZE, 100.),
+                Rect::new(MAX_PAGE_SIZE, 0., MAX_PAGE_SIZE * 1.5, 100.)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_doc_to_pdf() {
+        let doc = Doc {
+            tabs: vec![crate::components::Tab {
+                is_active: true,
+                id: crate::utils::ReflectableUuid::generate(),
+                name: "Tab 1".to_string(),
+                checkpoint: None,
+                zoom: 1.,
+                color: None,
+                icon: None,
+                pinned: false,
+                offset: [0., 0.],
+                selected: vec![],
+            }],
+            ..default()
+        };
+        let pdf = String::from_utf8(doc_to_pdf(&doc)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.contains("/Count 1"));
+        assert!(pdf.ends_with("%%EOF\n"));
+    }
+}
@@ -0,0 +1,531 @@
+use bevy::prelude::*;
+use cosmic_text::{Attrs, Buffer, Family, FontSystem, Metrics, SwashCache, Weight};
+use image::{ImageOutputFormat, Rgba, RgbaImage};
+
+use super::export::{node_rect, ExportedArrow, ExportedTab};
+use super::export_pdf::{
+    tab_bounds, HEAD_LENGTH, IMAGE_PLACEHOLDER_COLOR, LINE_HEIGHT, TEXT_PADDING,
+};
+use crate::canvas::arrow::components::{ArrowDash, ArrowHead};
+use crate::{BorderStyle, JsonNode, JsonStroke, NodeFont, NodeType, TextPos, FONTS};
+
+// Bigger tabs are scaled down to fit the image
+const MAX_IMAGE_SIZE: f32 = 8192.;
+// Segments of the polygons approximating ellipses
+const ELLIPSE_SEGMENTS: usize = 48;
+const ARROW_FONT_SIZE: f32 = 12.;
+
+/// PNG image of the tab like `tab_to_svg`, texts are rendered with the fonts bundled with the
+/// app and images are drawn as placeholders.
+pub fn tab_to_png(tab: &ExportedTab) -> Vec<u8> {
+    let bounds = tab_bounds(tab);
+    let scale = (MAX_IMAGE_SIZE / bounds.width().max(bounds.height())).min(1.);
+    let mut canvas = Canvas {
+        image: RgbaImage::from_pixel(
+            (bounds.width() * scale).ceil() as u32,
+            (bounds.height() * scale).ceil() as u32,
+            Rgba([255, 255, 255, 255]),
+        ),
+        bounds,
+        scale,
+    };
+    let mut font_system = font_system();
+    let mut swash_cache = SwashCache::new();
+    // pen strokes and arrows are under the nodes, like on the canvas
+    for stroke in tab.strokes.iter() {
+        canvas.draw_stroke(stroke);
+    }
+    for arrow in tab.arrows.iter() {
+        canvas.draw_arrow(tab, arrow);
+        canvas.draw_arrow_text(&mut font_system, &mut swash_cache, tab, arrow);
+    }
+    let mut nodes: Vec<_> = tab.nodes.iter().collect();
+    nodes.sort_by_key(|node| node.z_index);
+    for node in nodes {
+        canvas.draw_node(node);
+        canvas.draw_text(&mut font_system, &mut swash_cache, node);
+    }
+    let mut png = vec![];
+    image::DynamicImage::ImageRgba8(canvas.image)
+        .write_to(&mut std::io::Cursor::new(&mut png), ImageOutputFormat::Png)
+        .expect("Failed to encode PNG image");
+    png
+}
+
+fn font_system() -> FontSystem {
+    let mut db = cosmic_text::fontdb::Database::new();
+    db.set_monospace_family("Source Code Pro");
+    for data in FONTS {
+        db.load_font_data(data.to_vec());
+    }
+    FontSystem::new_with_locale_and_db("en-US".to_string(), db)
+}
+
+/// Image the tab is drawn to, mapping canvas coordinates (y axis pointing up) to pixels.
+struct Canvas {
+    image: RgbaImage,
+    bounds: Rect,
+    scale: f32,
+}
+
+impl Canvas {
+    fn point(&self, point: Vec2) -> Vec2 {
+        Vec2::new(point.x - self.bounds.min.x, self.bounds.max.y - point.y) * self.scale
+    }
+
+    /// Blends the color over the pixel, `coverage` is the part of the pixel covered by it.
+    fn blend(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.image.width() as i32 || y >= self.image.height() as i32 {
+            return;
+        }
+        let alpha = color.a() * coverage.clamp(0., 1.);
+        let pixel = self.image.get_pixel_mut(x as u32, y as u32);
+        let [r, g, b, _] = color.as_rgba_u8();
+        for (channel, value) in pixel.0.iter_mut().zip([r, g, b]) {
+            *channel = (*channel as f32 * (1. - alpha) + value as f32 * alpha).round() as u8;
+        }
+    }
+
+    /// Fills the polygon given in pixels, edges are smoothed with 4 samples per pixel.
+    fn fill_polygon(&mut self, points: &[Vec2], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        let (min, max) = points.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+        for y in min.y.floor() as i32..=max.y.ceil() as i32 {
+            for x in min.x.floor() as i32..=max.x.ceil() as i32 {
+                let samples = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+                    .iter()
+                    .filter(|(dx, dy)| contains(points, Vec2::new(x as f32 + dx, y as f32 + dy)))
+                    .count();
+                if samples > 0 {
+                    self.blend(x, y, color, samples as f32 / 4.);
+                }
+            }
+        }
+    }
+
+    /// Draws the line between two points given in pixels with round caps.
+    fn draw_segment(&mut self, a: Vec2, b: Vec2, width: f32, color: Color) {
+        let radius = (width / 2.).max(0.5);
+        let (min, max) = (a.min(b) - radius, a.max(b) + radius);
+        for y in min.y.floor() as i32..=max.y.ceil() as i32 {
+            for x in min.x.floor() as i32..=max.x.ceil() as i32 {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let distance = distance_to_segment(center, a, b);
+                if distance < radius + 0.5 {
+                    self.blend(x, y, color, radius + 0.5 - distance);
+                }
+            }
+        }
+    }
+
+    /// Draws the line through the points given in pixels, `dash` is the length of the dashes
+    /// and of the gaps between them.
+    fn draw_polyline(&mut self, points: &[Vec2], width: f32, color: Color, dash: Option<Vec2>) {
+        let Some(dash) = dash.filter(|dash| dash.x + dash.y > 0.) else {
+            for segment in points.windows(2) {
+                self.draw_segment(segment[0], segment[1], width, color);
+            }
+            return;
+        };
+        // distance along the dash pattern, carried over between the segments
+        let period = dash.x + dash.y;
+        let mut offset = 0.;
+        for segment in points.windows(2) {
+            let (a, b) = (segment[0], segment[1]);
+            let length = a.distance(b);
+            let mut start = 0.;
+            while start < length {
+                let phase = (offset + start) % period;
+                let (drawn, step) = if phase < dash.x {
+                    (true, dash.x - phase)
+                } else {
+                    (false, period - phase)
+                };
+                let end = (start + step.max(0.01)).min(length);
+                if drawn {
+                    let (from, to) = (a.lerp(b, start / length), a.lerp(b, end / length));
+                    self.draw_segment(from, to, width, color);
+                }
+                start = end;
+            }
+            offset += length;
+        }
+    }
+
+    fn draw_stroke(&mut self, stroke: &JsonStroke) {
+        let points: Vec<_> = stroke
+            .points
+            .iter()
+            .map(|[x, y]| self.point(Vec2::new(*x, *y)))
+            .collect();
+        self.draw_polyline(&points, stroke.width * self.scale, stroke.color, None);
+    }
+
+    fn draw_arrow(&mut self, tab: &ExportedTab, arrow: &ExportedArrow) {
+        let Some(points) = tab.arrow_points(arrow) else {
+            return;
+        };
+        let style = arrow.style;
+        let thickness = style.thickness * self.scale;
+        let dash = match style.dash {
+            ArrowDash::Solid => None,
+            ArrowDash::Dashed => Some(Vec2::new(4., 3.) * thickness),
+            // round caps turn the short dashes into dots
+            ArrowDash::Dotted => Some(Vec2::new(0.01, 2.5 * thickness)),
+        };
+        let image_points: Vec<_> = points.iter().map(|point| self.point(*point)).collect();
+        self.draw_polyline(&image_points, thickness, style.color, dash);
+        let (start, end) = (points[0], points[points.len() - 1]);
+        self.draw_head(points[1], start, style.start_head, style.color);
+        self.draw_head(points[points.len() - 2], end, style.end_head, style.color);
+    }
+
+    /// Head of the arrow at `tip` pointing away from `from`.
+    fn draw_head(&mut self, from: Vec2, tip: Vec2, head: ArrowHead, color: Color) {
+        let direction = (tip - from).normalize_or_zero();
+        if head == ArrowHead::None || direction == Vec2::ZERO {
+            return;
+        }
+        let back = tip - direction * HEAD_LENGTH;
+        let side = direction.perp() * HEAD_LENGTH / 2.;
+        let middle = tip - direction * HEAD_LENGTH / 2.;
+        let points = match head {
+            ArrowHead::None => return,
+            ArrowHead::Filled => vec![tip, back + side, back - side],
+            ArrowHead::Open => {
+                let points = [back + side, tip, back - side].map(|point| self.point(point));
+                self.draw_polyline(&points, self.scale, color, None);
+                return;
+            }
+            ArrowHead::Diamond => vec![tip, middle + side, back, middle - side],
+            ArrowHead::Circle => ellipse(middle, Vec2::splat(HEAD_LENGTH / 2.)),
+        };
+        let points: Vec<_> = points.into_iter().map(|point| self.point(point)).collect();
+        self.fill_polygon(&points, color);
+    }
+
+    fn draw_node(&mut self, node: &JsonNode) {
+        let rect = node_rect(node);
+        let (min, max, center) = (rect.min, rect.max, rect.center());
+        let w = rect.width();
+        let outline = match node.node_type {
+            NodeType::Circle | NodeType::Ellipse => ellipse(center, rect.half_size()),
+            NodeType::Diamond => vec![
+                Vec2::new(center.x, min.y),
+                Vec2::new(max.x, center.y),
+                Vec2::new(center.x, max.y),
+                Vec2::new(min.x, center.y),
+            ],
+            NodeType::Hexagon => vec![
+                Vec2::new(min.x + w / 4., min.y),
+                Vec2::new(max.x - w / 4., min.y),
+                Vec2::new(max.x, center.y),
+                Vec2::new(max.x - w / 4., max.y),
+                Vec2::new(min.x + w / 4., max.y),
+                Vec2::new(min.x, center.y),
+            ],
+            NodeType::Parallelogram => vec![
+                Vec2::new(min.x, min.y),
+                Vec2::new(max.x - w / 5., min.y),
+                Vec2::new(max.x, max.y),
+                Vec2::new(min.x + w / 5., max.y),
+            ],
+            _ => rounded_rect(rect, node.border.radius),
+        };
+        let rotation = Vec2::from_angle(node.rotation.to_radians());
+        let outline: Vec<_> = outline
+            .into_iter()
+            .map(|point| self.point(center + rotation.rotate(point - center)))
+            .collect();
+        let with_opacity = |mut color: Color| {
+            color.set_a(color.a() * node.opacity);
+            color
+        };
+        match node.node_type {
+            // frames only outline their members
+            NodeType::Frame => {}
+            NodeType::Image => self.fill_polygon(&outline, with_opacity(IMAGE_PLACEHOLDER_COLOR)),
+            _ => self.fill_polygon(&outline, with_opacity(node.bg_color)),
+        }
+        let border = &node.border;
+        let width = border.width * self.scale;
+        let dash = match border.style {
+            BorderStyle::Solid => None,
+            BorderStyle::Dashed => Some(Vec2::new(4., 3.) * width),
+            BorderStyle::Dotted => Some(Vec2::new(0.01, 2.5 * width)),
+        };
+        let mut closed = outline.clone();
+        closed.push(outline[0]);
+        self.draw_polyline(&closed, width, with_opacity(border.color), dash);
+    }
+
+    /// Draws the text of the node, rotated with the node.
+    fn draw_text(
+        &mut self,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        node: &JsonNode,
+    ) {
+        if node.text.text.trim().is_empty() {
+            return;
+        }
+        let style = &node.text.style;
+        let attrs = Attrs::new().family(Family::Monospace);
+        let attrs = match style.font {
+            NodeFont::Regular => attrs,
+            NodeFont::Bold => attrs.weight(Weight::BOLD),
+            NodeFont::Italic => attrs.style(cosmic_text::Style::Italic),
+        };
+        let rect = node_rect(node);
+        let layer = text_layer(
+            font_system,
+            swash_cache,
+            TextLayout {
+                text: &node.text.text,
+                attrs,
+                font_size: style.font_size * self.scale,
+                color: style.color,
+                size: rect.size() * self.scale,
+                padding: TEXT_PADDING * self.scale,
+                pos: node.text.pos.clone(),
+            },
+        );
+        let center = self.point(rect.center());
+        self.draw_layer(&layer, center, node.rotation, node.opacity);
+    }
+
+    /// Draws the label of the arrow above its middle.
+    fn draw_arrow_text(
+        &mut self,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        tab: &ExportedTab,
+        arrow: &ExportedArrow,
+    ) {
+        let (Some(points), Some(text)) = (tab.arrow_points(arrow), arrow.text()) else {
+            return;
+        };
+        let font_size = ARROW_FONT_SIZE * self.scale;
+        // wide enough for the monospace font, the text is centered in it
+        let size = Vec2::new(
+            text.chars().count() as f32 * font_size + font_size,
+            font_size * LINE_HEIGHT,
+        );
+        let layer = text_layer(
+            font_system,
+            swash_cache,
+            TextLayout {
+                text: &text,
+                attrs: Attrs::new().family(Family::Monospace),
+                font_size,
+                color: arrow.style.color,
+                size,
+                padding: 0.,
+                pos: TextPos::Center,
+            },
+        );
+        let middle = points[points.len() / 2 - 1].lerp(points[points.len() / 2], 0.5);
+        let center = self.point(middle) - Vec2::new(0., 4. * self.scale + size.y / 2.);
+        self.draw_layer(&layer, center, 0., 1.);
+    }
+
+    /// Blends the layer centered at `center`, rotated counterclockwise by `rotation` degrees.
+    fn draw_layer(&mut self, layer: &RgbaImage, center: Vec2, rotation: f32, opacity: f32) {
+        let size = Vec2::new(layer.width() as f32, layer.height() as f32);
+        let rotation = Vec2::from_angle(rotation.to_radians());
+        let radius = size.length() / 2.;
+        // pixels of the image are mapped back to the layer
+        for y in (center.y - radius).floor() as i32..=(center.y + radius).ceil() as i32 {
+            for x in (center.x - radius).floor() as i32..=(center.x + radius).ceil() as i32 {
+                let pixel = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+                // inverse of the rotation as seen on the image, whose y axis points down
+                let position = rotation.rotate(pixel) + size / 2.;
+                if position.x < 0.
+                    || position.y < 0.
+                    || position.x >= size.x
+                    || position.y >= size.y
+                {
+                    continue;
+                }
+                let Rgba([r, g, b, a]) = *layer.get_pixel(position.x as u32, position.y as u32);
+                if a > 0 {
+                    let color = Color::rgba_u8(r, g, b, 255);
+                    self.blend(x, y, color, a as f32 / 255. * opacity);
+                }
+            }
+        }
+    }
+}
+
+/// Text drawn to a layer of `size` pixels by `text_layer`.
+struct TextLayout<'a> {
+    text: &'a str,
+    attrs: Attrs<'a>,
+    font_size: f32,
+    color: Color,
+    size: Vec2,
+    padding: f32,
+    pos: TextPos,
+}
+
+/// Transparent image with the text wrapped in it, centered like in the nodes on the canvas.
+fn text_layer(
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+    layout: TextLayout,
+) -> RgbaImage {
+    let (size, padding) = (layout.size, layout.padding);
+    let mut buffer = Buffer::new(
+        font_system,
+        Metrics::new(layout.font_size, layout.font_size * LINE_HEIGHT),
+    );
+    buffer.set_size(
+        font_system,
+        (size.x - 2. * padding).max(1.),
+        (size.y - 2. * padding).max(1.),
+    );
+    buffer.set_text(font_system, layout.text, layout.attrs);
+    let offset = match layout.pos {
+        TextPos::Center => {
+            let (count, width) = buffer
+                .lines
+                .iter()
+                .filter_map(|line| line.layout_opt().as_ref())
+                .flatten()
+                .fold((0, 0f32), |(count, width), line| {
+                    (count + 1, width.max(line.w))
+                });
+            let text_height = count as f32 * buffer.metrics().line_height;
+            Vec2::new(
+                (size.x - width.min(size.x - 2. * padding)) / 2.,
+                (size.y - text_height) / 2.,
+            )
+        }
+        TextPos::TopLeft => Vec2::splat(padding),
+    };
+    let mut layer = RgbaImage::new(size.x.ceil().max(1.) as u32, size.y.ceil().max(1.) as u32);
+    let [r, g, b, a] = layout.color.as_rgba_u8();
+    buffer.draw(
+        font_system,
+        swash_cache,
+        cosmic_text::Color::rgba(r, g, b, a),
+        |x, y, w, h, color| {
+            for row in 0..h as i32 {
+                for column in 0..w as i32 {
+                    let (x, y) = (x + column + offset.x as i32, y + row + offset.y as i32);
+                    if x >= 0 && y >= 0 && (x as u32) < layer.width() && (y as u32) < layer.height()
+                    {
+                        layer.put_pixel(
+                            x as u32,
+                            y as u32,
+                            Rgba([color.r(), color.g(), color.b(), color.a()]),
+                        );
+                    }
+                }
+            }
+        },
+    );
+    layer
+}
+
+/// Even-odd test of the point against the polygon.
+fn contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for current in polygon.iter().copied() {
+        if (current.y > point.y) != (previous.y > point.y)
+            && point.x
+                < (previous.x - current.x) * (point.y - current.y) / (previous.y - current.y)
+                    + current.x
+        {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_squared() == 0. {
+        0.
+    } else {
+        ((point - a).dot(ab) / ab.length_squared()).clamp(0., 1.)
+    };
+    point.distance(a + ab * t)
+}
+
+fn ellipse(center: Vec2, radius: Vec2) -> Vec<Vec2> {
+    (0..ELLIPSE_SEGMENTS)
+        .map(|index| {
+            let angle = index as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+/// Outline of the rectangle with corners rounded by `radius`, counterclockwise.
+fn rounded_rect(rect: Rect, radius: f32) -> Vec<Vec2> {
+    let radius = radius.min(rect.width() / 2.).min(rect.height() / 2.);
+    if radius <= 0. {
+        return vec![
+            rect.min,
+            Vec2::new(rect.max.x, rect.min.y),
+            rect.max,
+            Vec2::new(rect.min.x, rect.max.y),
+        ];
+    }
+    let corners = [
+        Vec2::new(rect.max.x - radius, rect.min.y + radius),
+        Vec2::new(rect.max.x - radius, rect.max.y - radius),
+        Vec2::new(rect.min.x + radius, rect.max.y - radius),
+        Vec2::new(rect.min.x + radius, rect.min.y + radius),
+    ];
+    let segments = ELLIPSE_SEGMENTS / 4;
+    corners
+        .iter()
+        .enumerate()
+        .flat_map(|(corner, center)| {
+            (0..=segments).map(move |index| {
+                let angle = (corner as f32 - 1. + index as f32 / segments as f32)
+                    * std::f32::consts::FRAC_PI_2;
+                *center + Vec2::new(angle.cos(), angle.sin()) * radius
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let square = rounded_rect(Rect::new(0., 0., 10., 10.), 0.);
+        assert!(contains(&square, Vec2::new(5., 5.)));
+        assert!(!contains(&square, Vec2::new(15., 5.)));
+        let rounded = rounded_rect(Rect::new(0., 0., 10., 10.), 4.);
+        assert!(contains(&rounded, Vec2::new(5., 0.5)));
+        // the corner is cut off
+        assert!(!contains(&rounded, Vec2::new(0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_tab_to_png() {
+        let tab = ExportedTab {
+            name: "Empty".to_string(),
+            nodes: vec![],
+            arrows: vec![],
+            strokes: vec![],
+        };
+        let png = tab_to_png(&tab);
+        let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap();
+        assert_eq!(image.width(), 595);
+        assert_eq!(image.height(), 842);
+    }
+}
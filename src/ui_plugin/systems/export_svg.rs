@@ -0,0 +1,396 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use super::export::{node_rect, ExportedArrow, ExportedTab};
+use super::export_pdf::{
+    tab_bounds, wrap_text, CHAR_WIDTH, HEAD_LENGTH, IMAGE_PLACEHOLDER_COLOR, LINE_HEIGHT,
+    TEXT_PADDING,
+};
+use crate::canvas::arrow::components::{ArrowDash, ArrowHead};
+use crate::utils::convert_from_val_px;
+use crate::{BorderStyle, JsonNode, JsonStroke, NodeFont, NodeType, TextPos};
+
+const FONT_FAMILY: &str = "Source Code Pro, monospace";
+
+/// SVG image of the tab, the canvas area with contents of the tab and a margin around them.
+pub fn tab_to_svg(tab: &ExportedTab) -> String {
+    let bounds = tab_bounds(tab);
+    let svg = Svg { bounds };
+    let mut content = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.0} {:.0}\">\n",
+        bounds.width(),
+        bounds.height(),
+        bounds.width(),
+        bounds.height()
+    );
+    let _ = writeln!(content, "<title>{}</title>", escape(&tab.name));
+    content.push_str("<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n");
+    // pen strokes and arrows are under the nodes, like on the canvas
+    for stroke in tab.strokes.iter() {
+        svg.stroke(&mut content, stroke);
+    }
+    for arrow in tab.arrows.iter() {
+        svg.arrow(&mut content, tab, arrow);
+    }
+    let mut nodes: Vec<_> = tab.nodes.iter().collect();
+    nodes.sort_by_key(|node| node.z_index);
+    for node in nodes {
+        svg.node(&mut content, node);
+    }
+    content.push_str("</svg>\n");
+    content
+}
+
+/// Maps canvas coordinates (y axis pointing up) to the image ones (y axis pointing down).
+struct Svg {
+    bounds: Rect,
+}
+
+impl Svg {
+    fn point(&self, point: Vec2) -> Vec2 {
+        Vec2::new(point.x - self.bounds.min.x, self.bounds.max.y - point.y)
+    }
+
+    fn points(&self, points: &[Vec2]) -> String {
+        points
+            .iter()
+            .map(|point| {
+                let point = self.point(*point);
+                format!("{:.2},{:.2}", point.x, point.y)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn stroke(&self, content: &mut String, stroke: &JsonStroke) {
+        let points: Vec<_> = stroke
+            .points
+            .iter()
+            .map(|[x, y]| Vec2::new(*x, *y))
+            .collect();
+        let _ = writeln!(
+            content,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\"{} stroke-width=\"{:.2}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+            self.points(&points),
+            hex(stroke.color),
+            alpha("stroke-opacity", stroke.color),
+            stroke.width
+        );
+    }
+
+    fn arrow(&self, content: &mut String, tab: &ExportedTab, arrow: &ExportedArrow) {
+        let Some(points) = tab.arrow_points(arrow) else {
+            return;
+        };
+        let style = arrow.style;
+        let color = hex(style.color);
+        let dash = match style.dash {
+            ArrowDash::Solid => String::new(),
+            ArrowDash::Dashed => format!(
+                " stroke-dasharray=\"{} {}\"",
+                style.thickness * 4.,
+                style.thickness * 3.
+            ),
+            ArrowDash::Dotted => format!(" stroke-dasharray=\"0 {}\"", style.thickness * 2.5),
+        };
+        let _ = writeln!(
+            content,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{:.2}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"{}/>",
+            self.points(&points),
+            color,
+            style.thickness,
+            dash
+        );
+        let (start, end) = (points[0], points[points.len() - 1]);
+        self.head(content, points[1], start, style.start_head, &color);
+        self.head(
+            content,
+            points[points.len() - 2],
+            end,
+            style.end_head,
+            &color,
+        );
+        let Some(text) = arrow.text() else {
+            return;
+        };
+        let middle = self.point(points[points.len() / 2 - 1].lerp(points[points.len() / 2], 0.5));
+        let _ = writeln!(
+            content,
+            "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"{}\" font-size=\"12\" fill=\"{}\" text-anchor=\"middle\">{}</text>",
+            middle.x,
+            middle.y - 4.,
+            FONT_FAMILY,
+            color,
+            escape(&text)
+        );
+    }
+
+    /// Head of the arrow at `tip` pointing away from `from`.
+    fn head(&self, content: &mut String, from: Vec2, tip: Vec2, head: ArrowHead, color: &str) {
+        let direction = (tip - from).normalize_or_zero();
+        if head == ArrowHead::None || direction == Vec2::ZERO {
+            return;
+        }
+        let back = tip - direction * HEAD_LENGTH;
+        let side = direction.perp() * HEAD_LENGTH / 2.;
+        let middle = tip - direction * HEAD_LENGTH / 2.;
+        match head {
+            ArrowHead::None => {}
+            ArrowHead::Filled => {
+                let _ = writeln!(
+                    content,
+                    "<polygon points=\"{}\" fill=\"{}\"/>",
+                    self.points(&[tip, back + side, back - side]),
+                    color
+                );
+            }
+            ArrowHead::Open => {
+                let _ = writeln!(
+                    content,
+                    "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\"/>",
+                    self.points(&[back + side, tip, back - side]),
+                    color
+                );
+            }
+            ArrowHead::Diamond => {
+                let _ = writeln!(
+                    content,
+                    "<polygon points=\"{}\" fill=\"{}\"/>",
+                    self.points(&[tip, middle + side, back, middle - side]),
+                    color
+                );
+            }
+            ArrowHead::Circle => {
+                let center = self.point(middle);
+                let _ = writeln!(
+                    content,
+                    "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"{}\"/>",
+                    center.x,
+                    center.y,
+                    HEAD_LENGTH / 2.,
+                    color
+                );
+            }
+        }
+    }
+
+    fn node(&self, content: &mut String, node: &JsonNode) {
+        let rect = node_rect(node);
+        let (min, max, center) = (rect.min, rect.max, rect.center());
+        let w = rect.width();
+        let top_left = self.point(Vec2::new(min.x, max.y));
+        let image_center = self.point(center);
+        let _ = writeln!(
+            content,
+            "<g opacity=\"{:.2}\" transform=\"rotate({:.2} {:.2} {:.2})\">",
+            node.opacity, -node.rotation, image_center.x, image_center.y
+        );
+        let fill = match node.node_type {
+            // frames only outline their members
+            NodeType::Frame => "none".to_string(),
+            NodeType::Image => hex(IMAGE_PLACEHOLDER_COLOR),
+            _ => hex(node.bg_color),
+        };
+        let border = &node.border;
+        let dash = match border.style {
+            BorderStyle::Solid => String::new(),
+            BorderStyle::Dashed => format!(
+                " stroke-dasharray=\"{} {}\"",
+                border.width * 4.,
+                border.width * 3.
+            ),
+            BorderStyle::Dotted => format!(" stroke-dasharray=\"0 {}\"", border.width * 2.5),
+        };
+        let paint = format!(
+            "fill=\"{}\" stroke=\"{}\" stroke-width=\"{:.2}\" stroke-linecap=\"round\"{}",
+            fill,
+            hex(border.color),
+            border.width,
+            dash
+        );
+        let polygon = |points: &[Vec2]| format!("<polygon points=\"{}\" ", self.points(points));
+        let shape = match node.node_type {
+            NodeType::Circle | NodeType::Ellipse => format!(
+                "<ellipse cx=\"{:.2}\" cy=\"{:.2}\" rx=\"{:.2}\" ry=\"{:.2}\" ",
+                image_center.x,
+                image_center.y,
+                rect.half_size().x,
+                rect.half_size().y
+            ),
+            NodeType::Diamond => polygon(&[
+                Vec2::new(center.x, min.y),
+                Vec2::new(max.x, center.y),
+                Vec2::new(center.x, max.y),
+                Vec2::new(min.x, center.y),
+            ]),
+            NodeType::Hexagon => polygon(&[
+                Vec2::new(min.x + w / 4., min.y),
+                Vec2::new(max.x - w / 4., min.y),
+                Vec2::new(max.x, center.y),
+                Vec2::new(max.x - w / 4., max.y),
+                Vec2::new(min.x + w / 4., max.y),
+                Vec2::new(min.x, center.y),
+            ]),
+            NodeType::Parallelogram => polygon(&[
+                Vec2::new(min.x, min.y),
+                Vec2::new(max.x - w / 5., min.y),
+                Vec2::new(max.x, max.y),
+                Vec2::new(min.x + w / 5., max.y),
+            ]),
+            _ => format!(
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" rx=\"{:.2}\" ",
+                top_left.x,
+                top_left.y,
+                w,
+                rect.height(),
+                border.radius
+            ),
+        };
+        let _ = writeln!(content, "{}{}/>", shape, paint);
+        self.text(content, node);
+        content.push_str("</g>\n");
+    }
+
+    fn text(&self, content: &mut String, node: &JsonNode) {
+        let style = &node.text.style;
+        let width = convert_from_val_px(node.width) - 2. * TEXT_PADDING;
+        let max_chars = (width / (style.font_size * CHAR_WIDTH)).floor().max(1.) as usize;
+        let lines = wrap_text(&node.text.text, max_chars);
+        if lines.is_empty() {
+            return;
+        }
+        let rect = node_rect(node);
+        let line_height = style.font_size * LINE_HEIGHT;
+        let text_height = lines.len() as f32 * line_height;
+        let (x, anchor) = match node.text.pos {
+            TextPos::Center => (rect.center().x, "middle"),
+            TextPos::TopLeft => (rect.min.x + TEXT_PADDING, "start"),
+        };
+        let top = match node.text.pos {
+            TextPos::Center => rect.center().y + text_height / 2.,
+            TextPos::TopLeft => rect.max.y - TEXT_PADDING,
+        };
+        let font = match style.font {
+            NodeFont::Regular => "",
+            NodeFont::Bold => " font-weight=\"bold\"",
+            NodeFont::Italic => " font-style=\"italic\"",
+        };
+        let _ = writeln!(
+            content,
+            "<text font-family=\"{}\" font-size=\"{}\" fill=\"{}\" text-anchor=\"{}\"{}>",
+            FONT_FAMILY,
+            style.font_size,
+            hex(style.color),
+            anchor,
+            font
+        );
+        for (index, line) in lines.iter().enumerate() {
+            // baseline sits below the top of the line
+            let position = self.point(Vec2::new(
+                x,
+                top - index as f32 * line_height - style.font_size,
+            ));
+            let _ = writeln!(
+                content,
+                "<tspan x=\"{:.2}\" y=\"{:.2}\">{}</tspan>",
+                position.x,
+                position.y,
+                escape(line)
+            );
+        }
+        content.push_str("</text>\n");
+    }
+}
+
+fn hex(color: Color) -> String {
+    let [r, g, b, _] = color.as_rgba_u8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn alpha(attribute: &str, color: Color) -> String {
+    if color.a() < 1. {
+        format!(" {}=\"{:.2}\"", attribute, color.a())
+    } else {
+        String::new()
+    }
+}
+
+/// Text escaped for SVG contents and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::{
+        ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowStyle, ArrowType,
+    };
+    use crate::utils::ReflectableUuid;
+    use crate::{JsonNodeText, NodeBorder, NodeTextStyle};
+    use uuid::Uuid;
+
+    fn node(id: Uuid, left: f32, bottom: f32, text: &str) -> JsonNode {
+        JsonNode {
+            id,
+            node_type: NodeType::Rect,
+            left: Val::Px(left),
+            bottom: Val::Px(bottom),
+            width: Val::Px(100.),
+            height: Val::Px(50.),
+            text: JsonNodeText {
+                text: text.to_string(),
+                pos: TextPos::Center,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::WHITE,
+            z_index: 0,
+            locked: false,
+            border: NodeBorder::default(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        }
+    }
+
+    #[test]
+    fn test_tab_to_svg() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let tab = ExportedTab {
+            name: "Plan".to_string(),
+            nodes: vec![node(a, 0., 0., "a < b"), node(b, 200., 100., "")],
+            arrows: vec![ExportedArrow {
+                meta: ArrowMeta {
+                    arrow_type: ArrowType::Arrow,
+                    start: ArrowConnect {
+                        id: ReflectableUuid(a),
+                        pos: ArrowConnectPos::Right,
+                    },
+                    end: ArrowConnect {
+                        id: ReflectableUuid(b),
+                        pos: ArrowConnectPos::Left,
+                    },
+                },
+                style: ArrowStyle::default(),
+                label: Some("next".to_string()),
+                relation: None,
+                waypoints: vec![],
+            }],
+            strokes: vec![],
+        };
+        let svg = tab_to_svg(&tab);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"340\""));
+        assert!(svg.contains("<title>Plan</title>"));
+        assert!(svg.contains(">a &lt; b</tspan>"));
+        assert!(svg.contains(">next</text>"));
+        // y axis points down, the margin is around the contents
+        assert!(svg.contains("<rect x=\"20.00\" y=\"120.00\" width=\"100.00\" height=\"50.00\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
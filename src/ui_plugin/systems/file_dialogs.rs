@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use async_channel::{Receiver, Sender};
+use bevy::prelude::*;
+use rfd::AsyncFileDialog;
+
+use super::ui_helpers::{ExportToFile, ImportFromFile, ImportIntoCurrent};
+use super::{import_into_current, open_file, FileIo};
+use crate::resources::{AppState, SaveDocRequest};
+
+const DOCUMENT_EXTENSIONS: [&str; 3] = ["json", "gz", "zst"];
+const EXPORT_EXTENSIONS: [&str; 8] = ["pdf", "png", "svg", "md", "mmd", "dot", "gv", "canvas"];
+const IMPORT_EXTENSIONS: [&str; 6] = ["md", "mmd", "dot", "gv", "excalidraw", "canvas"];
+
+pub enum FileDialogResult {
+    SaveTo(PathBuf),
+    Open(PathBuf),
+    ImportInto(PathBuf),
+}
+
+/// Paths chosen in the OS file dialogs, which run without blocking the app.
+#[derive(Resource)]
+pub struct FileDialogChannel {
+    pub tx: Sender<FileDialogResult>,
+    pub rx: Receiver<FileDialogResult>,
+}
+
+impl Default for FileDialogChannel {
+    fn default() -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        Self { tx, rx }
+    }
+}
+
+/// "Export To File" opens the save dialog of the OS, the extension of the chosen file gives the
+/// format as with the path modal.
+pub fn save_file_dialog(
+    query: Query<&Interaction, (Changed<Interaction>, With<ExportToFile>)>,
+    channel: Res<FileDialogChannel>,
+) {
+    if !query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    let tx = channel.tx.clone();
+    bevy::tasks::IoTaskPool::get()
+        .spawn(async move {
+            let file = AsyncFileDialog::new()
+                .set_file_name("velo.json")
+                .add_filter("velo document", &DOCUMENT_EXTENSIONS)
+                .add_filter("export", &EXPORT_EXTENSIONS)
+                .save_file()
+                .await;
+            if let Some(file) = file {
+                let _ = tx
+                    .send(FileDialogResult::SaveTo(file.path().to_path_buf()))
+                    .await;
+            }
+        })
+        .detach();
+}
+
+/// "Import From File" opens the open dialog of the OS.
+pub fn open_file_dialog(
+    query: Query<&Interaction, (Changed<Interaction>, With<ImportFromFile>)>,
+    channel: Res<FileDialogChannel>,
+) {
+    if !query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    let tx = channel.tx.clone();
+    bevy::tasks::IoTaskPool::get()
+        .spawn(async move {
+            let file = AsyncFileDialog::new()
+                .add_filter("velo document", &DOCUMENT_EXTENSIONS)
+                .add_filter("import", &IMPORT_EXTENSIONS)
+                .pick_file()
+                .await;
+            if let Some(file) = file {
+                let _ = tx
+                    .send(FileDialogResult::Open(file.path().to_path_buf()))
+                    .await;
+            }
+        })
+        .detach();
+}
+
+/// "Import Document Into Current…" picks a velo document whose tabs are added to the open one.
+pub fn import_into_current_dialog(
+    query: Query<&Interaction, (Changed<Interaction>, With<ImportIntoCurrent>)>,
+    channel: Res<FileDialogChannel>,
+) {
+    if !query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    let tx = channel.tx.clone();
+    bevy::tasks::IoTaskPool::get()
+        .spawn(async move {
+            let file = AsyncFileDialog::new()
+                .add_filter("velo document", &DOCUMENT_EXTENSIONS)
+                .pick_file()
+                .await;
+            if let Some(file) = file {
+                let _ = tx
+                    .send(FileDialogResult::ImportInto(file.path().to_path_buf()))
+                    .await;
+            }
+        })
+        .detach();
+}
+
+pub fn file_dialog_results(
+    mut commands: Commands,
+    mut app_state: ResMut<AppState>,
+    channel: Res<FileDialogChannel>,
+    mut file_io: ResMut<FileIo>,
+) {
+    while let Ok(result) = channel.rx.try_recv() {
+        match result {
+            FileDialogResult::SaveTo(path) => {
+                let Some(doc_id) = app_state.current_document else {
+                    continue;
+                };
+                commands.insert_resource(SaveDocRequest {
+                    doc_id,
+                    path: Some(path),
+                });
+            }
+            FileDialogResult::Open(path) => {
+                open_file(&path, &mut commands, &mut app_state, &mut file_io);
+            }
+            FileDialogResult::ImportInto(path) => {
+                import_into_current(&mut commands, &mut app_state, &path);
+            }
+        }
+    }
+}
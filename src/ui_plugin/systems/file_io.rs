@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_channel::{Receiver, Sender};
+use bevy::prelude::*;
+
+use super::ui_helpers::{FileIoStatus, Localized, MainPanel};
+use super::{is_document_path, read_doc_file, save_document_file, CommChannels, Notification};
+use crate::components::Doc;
+use crate::resources::{AppState, DocFile};
+use crate::utils::ReflectableUuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileIoKind {
+    Save,
+    Load,
+}
+
+enum FileIoResult {
+    Saved {
+        doc_id: ReflectableUuid,
+        path: PathBuf,
+        result: Result<(), String>,
+    },
+    Loaded {
+        path: PathBuf,
+        result: Result<Doc, String>,
+    },
+}
+
+/// Sent when a background save or load of a document file is done.
+#[derive(Debug)]
+pub enum FileIoEvent {
+    Saved {
+        doc_id: ReflectableUuid,
+        path: PathBuf,
+    },
+    Loaded {
+        doc_id: ReflectableUuid,
+        path: PathBuf,
+    },
+    Failed {
+        kind: FileIoKind,
+        path: PathBuf,
+        error: String,
+    },
+}
+
+struct QueuedSave {
+    doc_id: ReflectableUuid,
+    doc: Doc,
+    passphrase: Option<String>,
+}
+
+/// Document files are serialized and written, or read and deserialized, in `IoTaskPool` tasks
+/// so big documents don't freeze the UI.
+#[derive(Resource)]
+pub struct FileIo {
+    tx: Sender<FileIoResult>,
+    rx: Receiver<FileIoResult>,
+    pending: Vec<(FileIoKind, PathBuf)>,
+    /// Latest version to write to a file which is being written, saved when that's done so two
+    /// tasks never write the same file.
+    queued: HashMap<PathBuf, QueuedSave>,
+}
+
+impl Default for FileIo {
+    fn default() -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        Self {
+            tx,
+            rx,
+            pending: vec![],
+            queued: HashMap::new(),
+        }
+    }
+}
+
+impl FileIo {
+    pub fn save(
+        &mut self,
+        doc_id: ReflectableUuid,
+        doc: Doc,
+        path: PathBuf,
+        passphrase: Option<String>,
+    ) {
+        if self.is_saving(&path) {
+            self.queued.insert(
+                path,
+                QueuedSave {
+                    doc_id,
+                    doc,
+                    passphrase,
+                },
+            );
+            return;
+        }
+        self.pending.push((FileIoKind::Save, path.clone()));
+        let tx = self.tx.clone();
+        bevy::tasks::IoTaskPool::get()
+            .spawn(async move {
+                let result = save_document_file(&path, &doc, passphrase.as_deref())
+                    .map_err(|e| e.to_string());
+                let _ = tx
+                    .send(FileIoResult::Saved {
+                        doc_id,
+                        path,
+                        result,
+                    })
+                    .await;
+            })
+            .detach();
+    }
+
+    /// Reads the velo document of the file, which is opened once it's read.
+    pub fn load(&mut self, path: PathBuf) {
+        self.pending.push((FileIoKind::Load, path.clone()));
+        let tx = self.tx.clone();
+        bevy::tasks::IoTaskPool::get()
+            .spawn(async move {
+                let result = read_doc_file(&path).map_err(|e| e.to_string());
+                let _ = tx.send(FileIoResult::Loaded { path, result }).await;
+            })
+            .detach();
+    }
+
+    pub fn is_saving(&self, path: &Path) -> bool {
+        self.pending
+            .iter()
+            .any(|(kind, pending)| *kind == FileIoKind::Save && pending == path)
+    }
+
+    fn done(&mut self, kind: FileIoKind, path: &Path) {
+        if let Some(index) = self
+            .pending
+            .iter()
+            .position(|(pending_kind, pending)| *pending_kind == kind && pending == path)
+        {
+            self.pending.remove(index);
+        }
+    }
+
+    /// Text of the status line, errors are notified.
+    pub fn status(&self) -> Option<String> {
+        let (kind, path) = self.pending.last()?;
+        let file_name = path.file_name().map_or(path.display().to_string(), |name| {
+            name.to_string_lossy().to_string()
+        });
+        let verb = match kind {
+            FileIoKind::Save => "Saving",
+            FileIoKind::Load => "Loading",
+        };
+        let others = match self.pending.len() - 1 {
+            0 => String::new(),
+            1 => " and 1 other file".to_string(),
+            n => format!(" and {} other files", n),
+        };
+        Some(format!("{} {}{}…", verb, file_name, others))
+    }
+}
+
+/// Applies the results of background saves and loads: opens loaded documents, remembers the
+/// file of saved ones and notifies errors.
+pub fn file_io_results(
+    mut file_io: ResMut<FileIo>,
+    mut app_state: ResMut<AppState>,
+    comm_channels: Res<CommChannels>,
+    mut events: EventWriter<FileIoEvent>,
+    mut notifications: EventWriter<Notification>,
+) {
+    while let Ok(result) = file_io.rx.try_recv() {
+        let (kind, path, error) = match result {
+            FileIoResult::Saved {
+                doc_id,
+                path,
+                result,
+            } => {
+                file_io.done(FileIoKind::Save, &path);
+                if let Some(queued) = file_io.queued.remove(&path) {
+                    file_io.save(queued.doc_id, queued.doc, path.clone(), queued.passphrase);
+                }
+                match result {
+                    Ok(()) => {
+                        if is_document_path(&path) {
+                            app_state.files.insert(doc_id, DocFile::new(path.clone()));
+                        }
+                        events.send(FileIoEvent::Saved { doc_id, path });
+                        continue;
+                    }
+                    Err(e) => (FileIoKind::Save, path, e),
+                }
+            }
+            FileIoResult::Loaded { path, result } => {
+                file_io.done(FileIoKind::Load, &path);
+                match result {
+                    Ok(doc) => {
+                        let doc_id = doc.id;
+                        app_state.files.insert(doc_id, DocFile::new(path.clone()));
+                        if comm_channels.tx.try_send(doc).is_err() {
+                            info!("Document {:?} read while another one is loading", path);
+                        }
+                        events.send(FileIoEvent::Loaded { doc_id, path });
+                        continue;
+                    }
+                    Err(e) => (FileIoKind::Load, path, e),
+                }
+            }
+        };
+        let message = match kind {
+            FileIoKind::Save => "Couldn't save {}",
+            FileIoKind::Load => "Couldn't open {}",
+        };
+        notifications.send(
+            Notification::error(Localized::new(message).with_args([path.display().to_string()]))
+                .with_details(&error),
+        );
+        events.send(FileIoEvent::Failed { kind, path, error });
+    }
+}
+
+/// Shows the files being saved or loaded at the bottom of the window.
+pub fn file_io_status(
+    mut commands: Commands,
+    file_io: Res<FileIo>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    mut status_query: Query<(Entity, &mut Text), With<FileIoStatus>>,
+) {
+    let Some(message) = file_io.status() else {
+        for (entity, _) in status_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+    if let Ok((_, mut text)) = status_query.get_single_mut() {
+        if text.sections[0].value != message {
+            text.sections[0].value = message;
+        }
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    let status = commands
+        .spawn((
+            TextBundle {
+                text: Text::from_section(
+                    message,
+                    TextStyle {
+                        font_size: 16.,
+                        color: Color::DARK_GRAY,
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.),
+                        bottom: Val::Px(10.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            FileIoStatus,
+        ))
+        .id();
+    commands.entity(main_panel).add_child(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let mut file_io = FileIo::default();
+        assert_eq!(file_io.status(), None);
+        file_io
+            .pending
+            .push((FileIoKind::Save, PathBuf::from("/docs/plan.json")));
+        assert_eq!(file_io.status(), Some("Saving plan.json…".to_string()));
+        assert!(file_io.is_saving(Path::new("/docs/plan.json")));
+        file_io
+            .pending
+            .push((FileIoKind::Load, PathBuf::from("notes.json.gz")));
+        assert_eq!(
+            file_io.status(),
+            Some("Loading notes.json.gz and 1 other file…".to_string())
+        );
+        file_io.done(FileIoKind::Load, Path::new("notes.json.gz"));
+        file_io.done(FileIoKind::Save, Path::new("/docs/plan.json"));
+        assert!(!file_io.is_saving(Path::new("/docs/plan.json")));
+        assert_eq!(file_io.status(), None);
+    }
+}
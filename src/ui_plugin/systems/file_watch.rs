@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use super::ui_helpers::{
+    spawn_prompt, FileChangedPrompt, FileKeep, FileReload, Localized, MainPanel,
+};
+use super::{decrypt, is_encrypted_file, read_doc, read_doc_file, FileIo};
+use crate::components::Doc;
+use crate::resources::{AppState, DocFile, LoadDocRequest};
+
+/// Seconds between two checks of the file of the open document.
+const WATCH_INTERVAL: f32 = 1.;
+
+fn read_changed_doc(path: &Path, passphrase: Option<&str>) -> Result<Doc, String> {
+    if !is_encrypted_file(path) {
+        return read_doc_file(path).map_err(|e| e.to_string());
+    }
+    let passphrase = passphrase.ok_or("the document is encrypted")?;
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let data = decrypt(&data, passphrase).map_err(|e| format!("{:?}", e))?;
+    read_doc(data.as_slice()).map_err(|e| e.to_string())
+}
+
+/// Watches the file the open document was opened from or saved to and asks whether to reload
+/// it when another program (or a sync client) changes it.
+pub fn watch_doc_file(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut app_state: ResMut<AppState>,
+    file_io: Res<FileIo>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    prompts: Query<(), With<FileChangedPrompt>>,
+    mut elapsed: Local<f32>,
+) {
+    *elapsed += time.delta_seconds();
+    if *elapsed < WATCH_INTERVAL {
+        return;
+    }
+    *elapsed = 0.;
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    let Some(file) = app_state.files.get_mut(&doc_id) else {
+        return;
+    };
+    // velo itself is writing it
+    if file_io.is_saving(&file.path) {
+        return;
+    }
+    let current = DocFile::new(file.path.clone());
+    if current.modified.is_none() || current.modified == file.modified {
+        return;
+    }
+    // asked once per change
+    file.modified = current.modified;
+    let file_name = file.path.display().to_string();
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    if !prompts.is_empty() {
+        return;
+    }
+    spawn_prompt(
+        &mut commands,
+        main_panel,
+        Localized::new("{} was changed by another program, reload it or keep the open version?")
+            .with_args([file_name]),
+        FileChangedPrompt { doc_id },
+        ("Reload", FileReload),
+        ("Keep", FileKeep),
+    );
+}
+
+/// Reloads the document from its file or keeps the open version, which overwrites the file
+/// when it's saved to it again.
+pub fn file_changed_prompt_click(
+    mut commands: Commands,
+    mut app_state: ResMut<AppState>,
+    reload: Query<&Interaction, (Changed<Interaction>, With<FileReload>)>,
+    keep: Query<&Interaction, (Changed<Interaction>, With<FileKeep>)>,
+    prompts: Query<(Entity, &FileChangedPrompt)>,
+) {
+    let Ok((entity, prompt)) = prompts.get_single() else {
+        return;
+    };
+    let reloaded = reload
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let kept = keep
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !reloaded && !kept {
+        return;
+    }
+    commands.entity(entity).despawn_recursive();
+    if kept {
+        return;
+    }
+    let doc_id = prompt.doc_id;
+    let Some(path) = app_state.files.get(&doc_id).map(|file| file.path.clone()) else {
+        return;
+    };
+    let passphrase = app_state.passphrases.get(&doc_id).map(String::as_str);
+    match read_changed_doc(&path, passphrase) {
+        Ok(mut doc) => {
+            // stays the same document for the doc list and the recent documents
+            doc.id = doc_id;
+            app_state.docs.insert(doc_id, doc);
+            if app_state.current_document == Some(doc_id) {
+                commands.insert_resource(LoadDocRequest { doc_id });
+            }
+        }
+        Err(e) => info!("Error reloading {:?}: {}", path, e),
+    }
+}
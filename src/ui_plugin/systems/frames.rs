@@ -0,0 +1,266 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use uuid::Uuid;
+
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
+use crate::{
+    AddRectEvent, BorderStyle, JsonNode, JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos,
+};
+
+use super::{
+    ui_helpers::{
+        FrameCollapseButton, FrameCollapsed, FrameMembers, HiddenByFrame, LeftPanel, NodeRect,
+        NodeZIndex,
+    },
+    Action, SelectedNodes, Settings, UiState, VeloNodeContainer,
+};
+
+const FRAME_SIZE: Vec2 = Vec2::new(400., 300.);
+// Space around the framed nodes, the title is drawn above them
+const FRAME_PADDING: f32 = 20.;
+const FRAME_TITLE_HEIGHT: f32 = 30.;
+
+/// Left, bottom, right and top of the node container.
+fn container_bounds(rect: &NodeRect) -> Vec4 {
+    let max = rect.position + rect.size;
+    Vec4::new(rect.position.x, rect.position.y, max.x, max.y)
+}
+
+fn contains(frame: Vec4, bounds: Vec4) -> bool {
+    bounds.x >= frame.x && bounds.y >= frame.y && bounds.z <= frame.z && bounds.w <= frame.w
+}
+
+/// Extends `ids` with members of the frames among them, members of nested frames included.
+pub fn with_frame_members(
+    ids: &[ReflectableUuid],
+    frames: &[(ReflectableUuid, Vec<ReflectableUuid>)],
+) -> Vec<ReflectableUuid> {
+    let mut result = ids.to_vec();
+    let mut i = 0;
+    while i < result.len() {
+        if let Some((_, members)) = frames.iter().find(|(id, _)| *id == result[i]) {
+            for member in members.iter() {
+                if !result.contains(member) {
+                    result.push(*member);
+                }
+            }
+        }
+        i += 1;
+    }
+    result
+}
+
+/// `F`, or the chord bound to "Frame", wraps the selected nodes into a new frame, or drops an
+/// empty frame under the cursor.
+pub fn add_frame(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    containers: Query<(&NodeRect, &NodeZIndex, &VeloNodeContainer)>,
+    mut events: EventWriter<AddRectEvent>,
+    left_panel_query: Query<&Style, With<LeftPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    settings: Res<Settings>,
+) {
+    if !settings.keybindings.just_pressed(Action::Frame, &input) || !ui_state.is_canvas_focused() {
+        return;
+    }
+    let selected: Vec<Vec4> = containers
+        .iter()
+        .filter(|(_, _, container)| selected_nodes.0.contains(&container.id))
+        .map(|(rect, _, _)| container_bounds(rect))
+        .collect();
+    // left, bottom, width and height in the world
+    let rect = if selected.is_empty() {
+        let primary_window = windows.single();
+        let mut left_panel_width = 0.;
+        if let Val::Percent(x) = left_panel_query.single().size.width {
+            left_panel_width = (primary_window.width() * x) / 100.;
+        }
+        let cursor = primary_window.cursor_position().unwrap_or(Vec2::new(
+            (primary_window.width() + left_panel_width) / 2.,
+            primary_window.height() / 2.,
+        ));
+        let (camera, camera_transform) = camera_q.single();
+        let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+            return;
+        };
+        Vec4::new(
+            point.x - FRAME_SIZE.x / 2.,
+            point.y - FRAME_SIZE.y / 2.,
+            FRAME_SIZE.x,
+            FRAME_SIZE.y,
+        )
+    } else {
+        let bounds = selected.iter().skip(1).fold(selected[0], |a, b| {
+            Vec4::new(a.x.min(b.x), a.y.min(b.y), a.z.max(b.z), a.w.max(b.w))
+        });
+        Vec4::new(
+            bounds.x - FRAME_PADDING,
+            bounds.y - FRAME_PADDING,
+            bounds.z - bounds.x + 2. * FRAME_PADDING,
+            bounds.w - bounds.y + 2. * FRAME_PADDING + FRAME_TITLE_HEIGHT,
+        )
+    };
+    // frames are placed behind all other nodes
+    let z_index = containers
+        .iter()
+        .map(|(_, z_index, _)| z_index.0)
+        .min()
+        .map_or(0, |z| z - 1);
+    events.send(AddRectEvent {
+        node: JsonNode {
+            id: Uuid::new_v4(),
+            node_type: NodeType::Frame,
+            left: Val::Px(rect.x),
+            bottom: Val::Px(rect.y),
+            width: Val::Px(rect.z),
+            height: Val::Px(rect.w),
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::TopLeft,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::rgb(245.0 / 255.0, 245.0 / 255.0, 245.0 / 255.0),
+            z_index,
+            locked: false,
+            border: NodeBorder {
+                color: Color::GRAY,
+                style: BorderStyle::Dashed,
+                ..default()
+            },
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: None,
+    });
+}
+
+pub fn frame_collapse_buttons(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &FrameCollapseButton), Changed<Interaction>>,
+    frames: Query<(Entity, &VeloNodeContainer, Option<&FrameCollapsed>), With<FrameMembers>>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        for (entity, container, collapsed) in frames.iter() {
+            if container.id != button.id {
+                continue;
+            }
+            if collapsed.is_some() {
+                commands.entity(entity).remove::<FrameCollapsed>();
+            } else {
+                commands.entity(entity).insert(FrameCollapsed);
+            }
+        }
+    }
+}
+
+/// Members of expanded frames follow their bounds, contents of collapsed frames stay as they
+/// were when collapsed.
+pub fn update_frame_members(
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut frames: Query<(&VeloNodeContainer, &NodeRect, &mut FrameMembers), Without<FrameCollapsed>>,
+    containers: Query<(&VeloNodeContainer, &NodeRect)>,
+) {
+    for (frame, frame_rect, mut members) in frames.iter_mut() {
+        // the dragged frame carries its members along
+        if selected_nodes.held(&ui_state) == Some(frame.id) {
+            continue;
+        }
+        let frame_bounds = container_bounds(frame_rect);
+        let new_members: Vec<ReflectableUuid> = containers
+            .iter()
+            .filter(|(container, rect)| {
+                container.id != frame.id && contains(frame_bounds, container_bounds(rect))
+            })
+            .map(|(container, _)| container.id)
+            .collect();
+        if members.0 != new_members {
+            members.0 = new_members;
+        }
+    }
+}
+
+/// Hides members of collapsed frames, visibility of the rest is restored by the tag filter.
+pub fn apply_frame_collapse(
+    mut commands: Commands,
+    frames: Query<(&VeloNodeContainer, &FrameMembers, Option<&FrameCollapsed>)>,
+    mut containers: Query<(
+        Entity,
+        &VeloNodeContainer,
+        &mut Visibility,
+        Option<&HiddenByFrame>,
+    )>,
+    buttons: Query<(&FrameCollapseButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    let hidden: Vec<ReflectableUuid> = frames
+        .iter()
+        .filter(|(_, _, collapsed)| collapsed.is_some())
+        .flat_map(|(_, members, _)| members.0.iter().copied())
+        .collect();
+    for (entity, container, mut visibility, hidden_by_frame) in containers.iter_mut() {
+        match (hidden.contains(&container.id), hidden_by_frame.is_some()) {
+            (true, _) => {
+                if hidden_by_frame.is_none() {
+                    commands.entity(entity).insert(HiddenByFrame);
+                }
+                if *visibility != Visibility::Hidden {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<HiddenByFrame>();
+            }
+            (false, false) => {}
+        }
+    }
+    for (button, children) in buttons.iter() {
+        let collapsed = frames
+            .iter()
+            .any(|(frame, _, collapsed)| frame.id == button.id && collapsed.is_some());
+        let label = if collapsed { "+" } else { "-" };
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                if text.sections[0].value != label {
+                    text.sections[0].value = label.to_string();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_frame_members() {
+        let outer = ReflectableUuid(Uuid::new_v4());
+        let inner = ReflectableUuid(Uuid::new_v4());
+        let node = ReflectableUuid(Uuid::new_v4());
+        let other = ReflectableUuid(Uuid::new_v4());
+        let frames = [(outer, vec![inner, node]), (inner, vec![node])];
+        assert_eq!(
+            with_frame_members(&[outer], &frames),
+            vec![outer, inner, node]
+        );
+        assert_eq!(with_frame_members(&[inner], &frames), vec![inner, node]);
+        assert_eq!(with_frame_members(&[other], &frames), vec![other]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let frame = Vec4::new(0., 0., 100., 100.);
+        assert!(contains(frame, Vec4::new(10., 10., 90., 90.)));
+        assert!(!contains(frame, Vec4::new(10., 10., 110., 90.)));
+    }
+}
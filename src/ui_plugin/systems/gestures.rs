@@ -0,0 +1,249 @@
+use bevy::{
+    input::{
+        mouse::{MouseScrollUnit, MouseWheel},
+        touch::Touch,
+        touchpad::{TouchpadMagnify, TouchpadRotate},
+    },
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use super::{
+    CanvasZoom, Locked, MainPanel, MotionSettings, NodeRotation, PanCanvasEvent, SelectedNodes,
+    VeloNode, VeloNodeContainer, ZoomCanvasEvent,
+};
+
+// Pixels of canvas panned by one line of mouse wheel scroll
+const PIXELS_PER_LINE: f32 = 20.;
+// Share of the measured velocity mixed into the fling velocity each frame
+const VELOCITY_SMOOTHING: f32 = 0.5;
+// Fraction of the fling velocity left after one second
+const FLING_FRICTION: f32 = 0.02;
+// Flings slower than this (pixels per second) stop
+const MIN_FLING_SPEED: f32 = 20.;
+
+/// Two finger touch gesture on the canvas and the fling it ends with.
+#[derive(Default)]
+pub struct TouchGesture {
+    active: bool,
+    /// Pixels per second, y axis pointing up
+    velocity: Vec2,
+}
+
+/// Pan (movement of the midpoint), zoom factor (change of the distance) and midpoint of two
+/// touches between their previous and current positions.
+fn pinch(previous: [Vec2; 2], current: [Vec2; 2]) -> (Vec2, f32, Vec2) {
+    let previous_mid = (previous[0] + previous[1]) / 2.;
+    let mid = (current[0] + current[1]) / 2.;
+    let previous_distance = previous[0].distance(previous[1]);
+    let factor = if previous_distance > 0. {
+        current[0].distance(current[1]) / previous_distance
+    } else {
+        1.
+    };
+    (mid - previous_mid, factor, mid)
+}
+
+/// Velocity of a fling after `seconds`, zero once it's too slow to be seen.
+fn fling_velocity(velocity: Vec2, seconds: f32) -> Vec2 {
+    let velocity = velocity * FLING_FRICTION.powf(seconds);
+    if velocity.length() < MIN_FLING_SPEED {
+        Vec2::ZERO
+    } else {
+        velocity
+    }
+}
+
+/// Window position relative to the left bottom corner of the canvas, both with y axis pointing
+/// up unlike the ui rect of the canvas.
+fn canvas_position(window: &Window, canvas: Rect, position: Vec2) -> Vec2 {
+    Vec2::new(
+        position.x - canvas.min.x,
+        position.y - (window.height() - canvas.max.y),
+    )
+}
+
+fn is_on_canvas(canvas: Rect, position: Vec2) -> bool {
+    Rect::from_corners(Vec2::ZERO, canvas.size()).contains(position)
+}
+
+/// Pinching a trackpad zooms the canvas around the cursor, rotating two fingers on it turns the
+/// selected nodes (unless locked).
+pub fn touchpad_gestures(
+    mut magnify_events: EventReader<TouchpadMagnify>,
+    mut rotate_events: EventReader<TouchpadRotate>,
+    zoom: Res<CanvasZoom>,
+    selected_nodes: Res<SelectedNodes>,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut containers: Query<(&VeloNodeContainer, &mut NodeRotation), Without<Locked>>,
+    mut zoom_events: EventWriter<ZoomCanvasEvent>,
+) {
+    let factor: f32 = magnify_events.iter().map(|event| 1. + event.0).product();
+    // counterclockwise degrees, node rotation is clockwise
+    let turn: f32 = rotate_events.iter().map(|event| event.0).sum();
+    let Ok((panel, panel_transform)) = main_panel_query.get_single() else {
+        return;
+    };
+    let window = windows.single();
+    let canvas = panel.logical_rect(panel_transform);
+    if factor != 1. {
+        let pivot = window
+            .cursor_position()
+            .map(|cursor| canvas_position(window, canvas, cursor))
+            .filter(|cursor| is_on_canvas(canvas, *cursor))
+            .unwrap_or(canvas.size() / 2.);
+        zoom_events.send(ZoomCanvasEvent {
+            zoom: zoom.0 * factor,
+            from: pivot,
+            to: pivot,
+        });
+    }
+    if turn != 0. {
+        for (container, mut rotation) in containers.iter_mut() {
+            if selected_nodes.0.contains(&container.id) {
+                rotation.0 = (rotation.0 - turn).rem_euclid(360.);
+            }
+        }
+    }
+}
+
+/// Scrolling over the canvas (two finger swipe on a trackpad) pans it, Cmd/Ctrl+scroll zooms
+/// it in `canvas_zoom` instead. Trackpads keep scrolling for a while after a swipe by
+/// themselves.
+pub fn scroll_canvas(
+    input: Res<Input<KeyCode>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    hovered: Query<&Interaction, Or<(With<MainPanel>, With<VeloNode>)>>,
+    mut pan_events: EventWriter<PanCanvasEvent>,
+) {
+    let command = input.any_pressed([
+        KeyCode::RWin,
+        KeyCode::LWin,
+        KeyCode::RControl,
+        KeyCode::LControl,
+    ]);
+    // panels above the canvas block it, the tab bar and the document list scroll themselves
+    let over_canvas = hovered
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let delta: Vec2 = mouse_wheel_events
+        .iter()
+        .map(|event| {
+            let delta = Vec2::new(event.x, event.y);
+            match event.unit {
+                MouseScrollUnit::Line => delta * PIXELS_PER_LINE,
+                MouseScrollUnit::Pixel => delta,
+            }
+        })
+        .sum();
+    if command || !over_canvas || delta == Vec2::ZERO {
+        return;
+    }
+    // positive deltas move the content right and down
+    pan_events.send(PanCanvasEvent {
+        delta: Vec2::new(delta.x, -delta.y),
+    });
+}
+
+/// Two fingers on a touchscreen pan the canvas and pinch to zoom it, lifting them while they
+/// move flings the canvas on until it slows down (unless with reduced motion). One finger works
+/// like the mouse.
+pub fn touch_gestures(
+    touches: Res<Touches>,
+    time: Res<Time>,
+    motion_settings: Res<MotionSettings>,
+    buttons: Res<Input<MouseButton>>,
+    zoom: Res<CanvasZoom>,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut pan_events: EventWriter<PanCanvasEvent>,
+    mut zoom_events: EventWriter<ZoomCanvasEvent>,
+    mut gesture: Local<TouchGesture>,
+) {
+    let seconds = time.delta_seconds();
+    let fingers: Vec<&Touch> = touches.iter().collect();
+    let Ok((panel, panel_transform)) = main_panel_query.get_single() else {
+        return;
+    };
+    let window = windows.single();
+    let canvas = panel.logical_rect(panel_transform);
+    if let [first, second] = fingers.as_slice() {
+        let previous = [first.previous_position(), second.previous_position()];
+        let current = [first.position(), second.position()];
+        let (delta, factor, mid) = pinch(previous, current);
+        // gestures start on the canvas, then follow the fingers anywhere
+        let mid = canvas_position(window, canvas, mid);
+        if !gesture.active && !is_on_canvas(canvas, mid) {
+            return;
+        }
+        if !gesture.active {
+            gesture.active = true;
+            gesture.velocity = Vec2::ZERO;
+        }
+        if seconds > 0. {
+            gesture.velocity = gesture.velocity.lerp(delta / seconds, VELOCITY_SMOOTHING);
+        }
+        if factor != 1. {
+            // zoomed around the midpoint before the move, which is panned after it
+            let from = mid - delta;
+            zoom_events.send(ZoomCanvasEvent {
+                zoom: zoom.0 * factor,
+                from,
+                to: from,
+            });
+        }
+        if delta != Vec2::ZERO {
+            pan_events.send(PanCanvasEvent { delta });
+        }
+        return;
+    }
+    if gesture.active {
+        gesture.active = false;
+        // a finger left on the screen holds the canvas
+        if !fingers.is_empty() || motion_settings.reduced_motion {
+            gesture.velocity = Vec2::ZERO;
+        }
+    }
+    if touches.iter_just_pressed().next().is_some() || buttons.get_just_pressed().next().is_some() {
+        gesture.velocity = Vec2::ZERO;
+    }
+    if gesture.velocity == Vec2::ZERO {
+        return;
+    }
+    pan_events.send(PanCanvasEvent {
+        delta: gesture.velocity * seconds,
+    });
+    gesture.velocity = fling_velocity(gesture.velocity, seconds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinch() {
+        let previous = [Vec2::new(100., 100.), Vec2::new(200., 100.)];
+        let (delta, factor, mid) = pinch(previous, previous);
+        assert_eq!(
+            (delta, factor, mid),
+            (Vec2::ZERO, 1., Vec2::new(150., 100.))
+        );
+
+        // fingers spread apart and moved up together
+        let current = [Vec2::new(50., 120.), Vec2::new(250., 120.)];
+        let (delta, factor, mid) = pinch(previous, current);
+        assert_eq!(delta, Vec2::new(0., 20.));
+        assert_eq!(factor, 2.);
+        assert_eq!(mid, Vec2::new(150., 120.));
+    }
+
+    #[test]
+    fn test_fling_velocity() {
+        let velocity = Vec2::new(1000., 0.);
+        assert_eq!(fling_velocity(velocity, 0.), velocity);
+        let slowed = fling_velocity(velocity, 0.5);
+        assert!(slowed.x < velocity.x && slowed.x > 0.);
+        assert_eq!(fling_velocity(velocity, 2.), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use super::{
+    ui_helpers::{Grid, MainPanel, ToggleGrid},
+    view_rect, CanvasOffset, CanvasZoom, GridSettings, Theme, BACKGROUND_Z,
+};
+
+// Grid lines are drawn over the background, below arrows and nodes
+const GRID_Z: f32 = BACKGROUND_Z + 0.01;
+// Lines closer than that on screen are skipped, every other line is drawn instead
+const MIN_LINE_SPACING: f32 = 4.;
+
+pub fn toggle_grid(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ToggleGrid>)>,
+    mut grid_settings: ResMut<GridSettings>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            grid_settings.enabled = !grid_settings.enabled;
+        }
+    }
+}
+
+/// World positions of the grid lines of `spacing` in `min..=max`.
+pub fn grid_lines(min: f32, max: f32, spacing: f32) -> impl Iterator<Item = f32> {
+    let first = (min / spacing).ceil() as i64;
+    let last = (max / spacing).floor() as i64;
+    (first..=last).map(move |i| i as f32 * spacing)
+}
+
+/// Draws the grid lines in view, they are one pixel wide on screen whatever the canvas zoom.
+pub fn update_grid(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    theme: Res<Theme>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
+    grid_query: Query<Entity, With<Grid>>,
+    main_panel_query: Query<Ref<Node>, With<MainPanel>>,
+) {
+    let Ok(panel) = main_panel_query.get_single() else {
+        return;
+    };
+    if !grid_settings.is_changed()
+        && !theme.is_changed()
+        && !offset.is_changed()
+        && !zoom.is_changed()
+        && !panel.is_changed()
+    {
+        return;
+    }
+    for entity in grid_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !grid_settings.enabled || grid_settings.size <= 0. {
+        return;
+    }
+    let view = view_rect(panel.size(), &offset, &zoom);
+    let mut spacing = grid_settings.size;
+    while spacing * zoom.0 < MIN_LINE_SPACING {
+        spacing *= 2.;
+    }
+    let width = 1. / zoom.0;
+    let line = |center: Vec2, size: Vec2| SpriteBundle {
+        sprite: Sprite {
+            color: theme.grid_line,
+            custom_size: Some(size),
+            ..default()
+        },
+        transform: Transform::from_translation(center.extend(0.)),
+        ..default()
+    };
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(0., 0., GRID_Z)),
+            Grid,
+        ))
+        .with_children(|builder| {
+            for x in grid_lines(view.min.x, view.max.x, spacing) {
+                builder.spawn(line(
+                    Vec2::new(x, view.center().y),
+                    Vec2::new(width, view.height()),
+                ));
+            }
+            for y in grid_lines(view.min.y, view.max.y, spacing) {
+                builder.spawn(line(
+                    Vec2::new(view.center().x, y),
+                    Vec2::new(view.width(), width),
+                ));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_lines() {
+        assert_eq!(
+            grid_lines(-15., 25., 10.).collect::<Vec<_>>(),
+            vec![-10., 0., 10., 20.]
+        );
+        assert_eq!(grid_lines(1., 9., 10.).count(), 0);
+    }
+}
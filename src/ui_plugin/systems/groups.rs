@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+
+use crate::utils::ReflectableUuid;
+
+use super::{Action, NodeGroup, SelectedNodes, Settings, VeloNodeContainer};
+
+pub fn group_nodes(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    selected_nodes: Res<SelectedNodes>,
+    containers: Query<(Entity, &VeloNodeContainer, Option<&NodeGroup>)>,
+    settings: Res<Settings>,
+) {
+    let group = settings.keybindings.just_pressed(Action::Group, &input);
+    let ungroup = settings.keybindings.just_pressed(Action::Ungroup, &input);
+    if !group && !ungroup {
+        return;
+    }
+    if ungroup {
+        let groups: Vec<ReflectableUuid> = containers
+            .iter()
+            .filter(|(_, container, _)| selected_nodes.0.contains(&container.id))
+            .filter_map(|(_, _, group)| group.map(|g| g.id))
+            .collect();
+        for (entity, _, group) in containers.iter() {
+            if let Some(group) = group {
+                if groups.contains(&group.id) {
+                    commands.entity(entity).remove::<NodeGroup>();
+                }
+            }
+        }
+    } else if selected_nodes.0.len() > 1 {
+        let id = ReflectableUuid::generate();
+        for (entity, container, _) in containers.iter() {
+            if selected_nodes.0.contains(&container.id) {
+                commands.entity(entity).insert(NodeGroup { id });
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::Stroke;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowMeta, ArrowStyle};
+
+use super::ui_helpers::{
+    CanvasOutline, NodePart, ResizeMarker, RotationHandle, ARROW_MARKER_HIT_SIZE,
+    EDGE_MARKER_THICKNESS, RESIZE_MARKER_SIZE, ROTATION_HANDLE_SIZE,
+};
+use super::{Theme, VeloNode};
+
+/// Draws node outlines and arrow lines at least as wide as the theme asks for, after all the
+/// systems setting them (selection, editing, borders, hover and arrow styles) ran.
+pub fn widen_outlines(
+    theme: Res<Theme>,
+    mut outlines: Query<&mut CanvasOutline, (With<VeloNode>, Changed<CanvasOutline>)>,
+    mut arrows: Query<(&ArrowStyle, &mut Stroke), With<ArrowMeta>>,
+) {
+    for mut outline in outlines.iter_mut() {
+        let drawn = theme.outline_width(outline.thickness);
+        if drawn != outline.thickness {
+            outline.thickness = drawn;
+        }
+    }
+    // arrows are reset to their own width when the theme changes back
+    if theme.min_outline_width <= 0. && !theme.is_changed() {
+        return;
+    }
+    for (style, mut stroke) in arrows.iter_mut() {
+        if !theme.is_changed() && !stroke.is_changed() {
+            continue;
+        }
+        // hovered and selected arrows are drawn a pixel wider in their highlight color
+        let highlight = if stroke.color == style.color { 0. } else { 1. };
+        let width = theme.outline_width(style.thickness) + highlight;
+        if stroke.options.line_width != width {
+            stroke.options.line_width = width;
+        }
+    }
+}
+
+/// Scales the hit zones of connection points, resize and rotation handles of nodes by the
+/// theme, when they are spawned and when the theme changes.
+pub fn scale_hit_targets(
+    theme: Res<Theme>,
+    mut targets: Query<
+        (
+            &mut NodePart,
+            Option<Ref<ArrowConnect>>,
+            Option<Ref<ResizeMarker>>,
+            Option<Ref<RotationHandle>>,
+        ),
+        Or<(With<ArrowConnect>, With<ResizeMarker>, With<RotationHandle>)>,
+    >,
+) {
+    let scale = theme.hit_target_scale;
+    for (mut part, connect, resize, rotation) in targets.iter_mut() {
+        let added = connect.as_ref().map_or(false, |c| c.is_added())
+            || resize.as_ref().map_or(false, |r| r.is_added())
+            || rotation.as_ref().map_or(false, |r| r.is_added());
+        if !added && !theme.is_changed() {
+            continue;
+        }
+        if connect.is_some() {
+            part.size = Vec2::splat(ARROW_MARKER_HIT_SIZE * scale);
+        } else if rotation.is_some() {
+            part.size = Vec2::splat(ROTATION_HANDLE_SIZE * scale);
+        } else if let Some(resize) = resize {
+            let thickness = EDGE_MARKER_THICKNESS * scale;
+            match *resize {
+                ResizeMarker::Top | ResizeMarker::Bottom => part.size.y = thickness,
+                ResizeMarker::Left | ResizeMarker::Right => part.size.x = thickness,
+                _ => part.size = Vec2::splat(RESIZE_MARKER_SIZE * scale),
+            }
+        }
+    }
+}
@@ -0,0 +1,1202 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE, window::PrimaryWindow};
+use bevy_cosmic_edit::{CosmicEditImage, FontSystemState};
+use cosmic_text::Edit;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::ui_helpers::{
+    CollapsedBranch, FrameCollapsed, FrameMembers, GenericButton, HistoryEntryButton, HistoryList,
+    Localized, NodeGroup, NodeZIndex, PresentationStep,
+};
+use super::{
+    cosmic_attrs, json_arrow_event, json_arrows, json_node, json_node_meta, json_nodes, notify,
+    remove_shape, spawn_node, Action, ArrowQuery, CanvasZoom, Locked, NodeAttachments,
+    NodeContainerQuery, NodeEffects, NodeQuery, NodeRect, NodeRotation, NodeTags, Notification,
+    RawText, SelectedNodes, Settings, UiState, VeloNode, VeloNodeContainer,
+};
+use crate::canvas::arrow::components::{
+    ArrowConnect, ArrowLabel, ArrowMeta, ArrowPins, ArrowRelation, ArrowStyle, ArrowWaypoints,
+};
+use crate::canvas::arrow::events::{CreateArrowEvent, RedrawArrowEvent};
+use crate::resources::{AppState, LoadTabRequest};
+use crate::utils::{convert_from_val_px, ReflectableUuid};
+use crate::{JsonNode, NodeBorder, NodeTextStyle, TextPos};
+
+// Nodes moved or resized by less than that (in world units) are unchanged
+const POSITION_EPSILON: f32 = 0.5;
+// Numbers of saved arrows closer than that are the same
+const VALUE_EPSILON: f64 = 0.001;
+// Frames to wait after loading a tab or applying entries before the canvas is read back
+const SETTLE_FRAMES: u32 = 3;
+// Frames between applying entries, so nodes and arrows spawned by one exist for the next
+const APPLY_FRAMES: u32 = 2;
+// Entries listed in the history panel
+const SHOWN_ENTRIES: usize = 12;
+const CURRENT_ENTRY_COLOR: Color = Color::rgb(191.0 / 255.0, 219.0 / 255.0, 254.0 / 255.0);
+
+/// Node as recorded in the history, in world coordinates like it's saved.
+#[derive(Clone)]
+pub struct HistoryNode {
+    pub node: JsonNode,
+    pub image: Handle<Image>,
+}
+
+/// Edit of the active tab that can be applied and inverted without reloading the tab.
+#[derive(Clone)]
+pub enum HistoryCommand {
+    NodeMoved {
+        id: Uuid,
+        from: Vec2,
+        to: Vec2,
+    },
+    NodeResized {
+        id: Uuid,
+        from: Vec2,
+        to: Vec2,
+    },
+    TextEdited {
+        id: Uuid,
+        from: String,
+        to: String,
+    },
+    /// Any other property (color, border, tags, ...), the node is spawned again.
+    NodeChanged {
+        from: Box<HistoryNode>,
+        to: Box<HistoryNode>,
+    },
+    NodeCreated(Box<HistoryNode>),
+    NodeDeleted(Box<HistoryNode>),
+    /// Arrow as saved in the tab.
+    ArrowCreated(Value),
+    ArrowDeleted(Value),
+}
+
+impl HistoryCommand {
+    pub fn inverse(&self) -> HistoryCommand {
+        match self.clone() {
+            HistoryCommand::NodeMoved { id, from, to } => HistoryCommand::NodeMoved {
+                id,
+                from: to,
+                to: from,
+            },
+            HistoryCommand::NodeResized { id, from, to } => HistoryCommand::NodeResized {
+                id,
+                from: to,
+                to: from,
+            },
+            HistoryCommand::TextEdited { id, from, to } => HistoryCommand::TextEdited {
+                id,
+                from: to,
+                to: from,
+            },
+            HistoryCommand::NodeChanged { from, to } => {
+                HistoryCommand::NodeChanged { from: to, to: from }
+            }
+            HistoryCommand::NodeCreated(node) => HistoryCommand::NodeDeleted(node),
+            HistoryCommand::NodeDeleted(node) => HistoryCommand::NodeCreated(node),
+            HistoryCommand::ArrowCreated(arrow) => HistoryCommand::ArrowDeleted(arrow),
+            HistoryCommand::ArrowDeleted(arrow) => HistoryCommand::ArrowCreated(arrow),
+        }
+    }
+
+    /// Node the command applies to, none for arrows.
+    fn node_id(&self) -> Option<Uuid> {
+        match self {
+            HistoryCommand::NodeMoved { id, .. }
+            | HistoryCommand::NodeResized { id, .. }
+            | HistoryCommand::TextEdited { id, .. } => Some(*id),
+            HistoryCommand::NodeChanged { to: node, .. }
+            | HistoryCommand::NodeCreated(node)
+            | HistoryCommand::NodeDeleted(node) => Some(node.node.id),
+            HistoryCommand::ArrowCreated(_) | HistoryCommand::ArrowDeleted(_) => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            HistoryCommand::NodeMoved { .. } => "Move node",
+            HistoryCommand::NodeResized { .. } => "Resize node",
+            HistoryCommand::TextEdited { .. } => "Edit text",
+            HistoryCommand::NodeChanged { .. } => "Change node",
+            HistoryCommand::NodeCreated(_) => "Add node",
+            HistoryCommand::NodeDeleted(_) => "Delete node",
+            HistoryCommand::ArrowCreated(_) => "Add arrow",
+            HistoryCommand::ArrowDeleted(_) => "Delete arrow",
+        }
+    }
+}
+
+/// Short description of a history entry for the history panel, e.g. "Move node ×2, Add arrow".
+pub fn describe_entry(entry: &[HistoryCommand]) -> String {
+    let mut labels: Vec<(&str, usize)> = vec![];
+    for command in entry {
+        match labels
+            .iter_mut()
+            .find(|(label, _)| *label == command.label())
+        {
+            Some((_, count)) => *count += 1,
+            None => labels.push((command.label(), 1)),
+        }
+    }
+    labels
+        .iter()
+        .map(|(label, count)| match count {
+            1 => label.to_string(),
+            _ => format!("{} ×{}", label, count),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Nodes and arrows of the active tab the history is recorded from.
+#[derive(Clone, Default)]
+pub struct HistorySnapshot {
+    pub nodes: BTreeMap<Uuid, HistoryNode>,
+    pub arrows: Vec<Value>,
+}
+
+impl HistorySnapshot {
+    /// Updates the snapshot with a command applied to the canvas without being recorded.
+    pub fn apply(&mut self, command: &HistoryCommand) {
+        match command {
+            HistoryCommand::NodeMoved { id, to, .. } => {
+                if let Some(node) = self.nodes.get_mut(id) {
+                    node.node.left = Val::Px(to.x);
+                    node.node.bottom = Val::Px(to.y);
+                }
+            }
+            HistoryCommand::NodeResized { id, to, .. } => {
+                if let Some(node) = self.nodes.get_mut(id) {
+                    node.node.width = Val::Px(to.x);
+                    node.node.height = Val::Px(to.y);
+                }
+            }
+            HistoryCommand::TextEdited { id, to, .. } => {
+                if let Some(node) = self.nodes.get_mut(id) {
+                    node.node.text.text = to.clone();
+                }
+            }
+            HistoryCommand::NodeChanged { to: node, .. } | HistoryCommand::NodeCreated(node) => {
+                self.nodes.insert(node.node.id, *node.clone());
+            }
+            HistoryCommand::NodeDeleted(node) => {
+                self.nodes.remove(&node.node.id);
+            }
+            HistoryCommand::ArrowCreated(arrow) => self.arrows.push(arrow.clone()),
+            HistoryCommand::ArrowDeleted(arrow) => {
+                if let Some(index) = self.arrows.iter().position(|a| approx_eq(a, arrow)) {
+                    self.arrows.remove(index);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TabHistory {
+    /// Edits in the order they were made, each entry is undone at once.
+    pub undo: Vec<Vec<HistoryCommand>>,
+    pub redo: Vec<Vec<HistoryCommand>>,
+}
+
+impl TabHistory {
+    /// Descriptions of the done edits followed by the undone ones, oldest first.
+    pub fn entries(&self) -> Vec<String> {
+        self.undo
+            .iter()
+            .chain(self.redo.iter().rev())
+            .map(|entry| describe_entry(entry))
+            .collect()
+    }
+}
+
+/// Edits recorded since the last history entry.
+#[derive(Default)]
+struct PendingEntry {
+    commands: Vec<HistoryCommand>,
+    /// Nodes created by the recorded edits, their commands are read from the canvas once they
+    /// are spawned.
+    created: Vec<ReflectableUuid>,
+    /// Nodes whose other properties may have changed.
+    nodes: HashSet<Entity>,
+    arrows_changed: bool,
+    recorded_this_frame: bool,
+}
+
+impl PendingEntry {
+    fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+            && self.created.is_empty()
+            && self.nodes.is_empty()
+            && !self.arrows_changed
+    }
+}
+
+/// Undo/redo stacks of the tabs opened in this session.
+#[derive(Resource, Default)]
+pub struct History {
+    pub tabs: HashMap<ReflectableUuid, TabHistory>,
+    tab_id: Option<ReflectableUuid>,
+    snapshot: Option<HistorySnapshot>,
+    pending: PendingEntry,
+    to_apply: VecDeque<Vec<HistoryCommand>>,
+    apply_frames: u32,
+    pending_arrows: Vec<CreateArrowEvent>,
+    pending_redraw: Vec<ReflectableUuid>,
+    settle_frames: u32,
+    /// Edits of other collaborators, applied like undone/redone entries but never recorded.
+    remote: VecDeque<Vec<HistoryCommand>>,
+    share_edits: bool,
+    /// Edits made or undone/redone here since the last `take_shared_edits`.
+    shared_edits: Vec<(ReflectableUuid, Vec<HistoryCommand>)>,
+}
+
+impl History {
+    pub fn active_tab(&self) -> Option<&TabHistory> {
+        self.tabs.get(&self.tab_id?)
+    }
+
+    /// Tab the recorded edits belong to, it follows the active tab once that is loaded.
+    pub fn tab_id(&self) -> Option<ReflectableUuid> {
+        self.tab_id
+    }
+
+    /// Records an edit of the active tab made by an editing system, edits recorded in
+    /// consecutive frames become one entry. Ignored while the tab is loaded.
+    pub fn record(&mut self, command: HistoryCommand) {
+        if self.snapshot.is_none() {
+            return;
+        }
+        self.pending.commands.push(command);
+        self.pending.recorded_this_frame = true;
+    }
+
+    /// Records a node spawned by an editing system, it's read from the canvas once spawned.
+    pub fn record_created(&mut self, id: ReflectableUuid) {
+        if self.snapshot.is_none() {
+            return;
+        }
+        self.pending.created.push(id);
+        self.pending.recorded_this_frame = true;
+    }
+
+    /// Records deleting the nodes together with the arrows connected to them, before they are
+    /// despawned.
+    pub fn record_deleted(&mut self, ids: &[ReflectableUuid]) {
+        let Some(snapshot) = self.snapshot.as_ref() else {
+            return;
+        };
+        let connected = |arrow: &&Value| {
+            serde_json::from_value::<ArrowMeta>((*arrow).clone()).map_or(false, |meta| {
+                ids.contains(&meta.start.id) || ids.contains(&meta.end.id)
+            })
+        };
+        let arrows = snapshot
+            .arrows
+            .iter()
+            .filter(connected)
+            .map(|arrow| HistoryCommand::ArrowDeleted(arrow.clone()));
+        let nodes = ids
+            .iter()
+            .filter_map(|id| snapshot.nodes.get(&id.0))
+            .map(|node| HistoryCommand::NodeDeleted(Box::new(node.clone())));
+        let commands: Vec<_> = arrows.chain(nodes).collect();
+        // nodes created and deleted before the entry is recorded aren't part of it
+        self.pending.created.retain(|id| !ids.contains(id));
+        for command in commands {
+            self.record(command);
+        }
+    }
+
+    /// Undoes or redoes edits of the active tab until `position` of them are done.
+    pub fn jump_to(&mut self, position: usize) {
+        let Some(tab) = self.tab_id.and_then(|tab_id| self.tabs.get_mut(&tab_id)) else {
+            return;
+        };
+        let tab_id = self.tab_id.unwrap();
+        let queued = self.to_apply.len();
+        while tab.undo.len() > position {
+            let entry = tab.undo.pop().unwrap();
+            self.to_apply
+                .push_back(entry.iter().rev().map(HistoryCommand::inverse).collect());
+            tab.redo.push(entry);
+        }
+        while tab.undo.len() < position {
+            let Some(entry) = tab.redo.pop() else {
+                break;
+            };
+            self.to_apply.push_back(entry.clone());
+            tab.undo.push(entry);
+        }
+        for entry in self.to_apply.iter().skip(queued) {
+            if let Some(snapshot) = self.snapshot.as_mut() {
+                entry.iter().for_each(|command| snapshot.apply(command));
+            }
+            if self.share_edits {
+                self.shared_edits.push((tab_id, entry.clone()));
+            }
+        }
+        if self.to_apply.len() > queued {
+            // nodes respawned by the commands are read back once the canvas settles
+            self.settle_frames = SETTLE_FRAMES;
+        }
+    }
+
+    /// Active tab and its last recorded state, none while the tab is loaded.
+    pub fn snapshot(&self) -> Option<(ReflectableUuid, &HistorySnapshot)> {
+        Some((self.tab_id?, self.snapshot.as_ref()?))
+    }
+
+    /// Creates the arrows once the markers of their nodes are spawned, they are recorded in the
+    /// same edit as the nodes.
+    pub fn create_arrows(&mut self, arrows: impl IntoIterator<Item = CreateArrowEvent>) {
+        self.pending_arrows.extend(arrows);
+    }
+
+    /// Keeps the edits of the active tab for `take_shared_edits` from now on, or stops.
+    pub fn share_edits(&mut self, share: bool) {
+        self.share_edits = share;
+        self.shared_edits.clear();
+    }
+
+    pub fn take_shared_edits(&mut self) -> Vec<(ReflectableUuid, Vec<HistoryCommand>)> {
+        std::mem::take(&mut self.shared_edits)
+    }
+
+    /// Applies edits made by someone else to the active tab. They aren't recorded as edits,
+    /// undoing only undoes the edits made here.
+    pub fn apply_remote(&mut self, commands: Vec<HistoryCommand>) {
+        if commands.is_empty() {
+            return;
+        }
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            commands.iter().for_each(|command| snapshot.apply(command));
+            self.settle_frames = SETTLE_FRAMES;
+        }
+        self.remote.push_back(commands);
+    }
+}
+
+fn position(node: &JsonNode) -> Vec2 {
+    Vec2::new(
+        convert_from_val_px(node.left),
+        convert_from_val_px(node.bottom),
+    )
+}
+
+fn size(node: &JsonNode) -> Vec2 {
+    Vec2::new(
+        convert_from_val_px(node.width),
+        convert_from_val_px(node.height),
+    )
+}
+
+/// Saved node without geometry and text, those are recorded as separate commands.
+pub fn properties(node: &JsonNode) -> Value {
+    let mut value = json!(node);
+    for key in ["left", "bottom", "width", "height"] {
+        value[key] = Value::Null;
+    }
+    value["text"]["text"] = Value::Null;
+    value
+}
+
+fn properties_changed(old: &HistoryNode, new: &HistoryNode) -> bool {
+    properties(&old.node) != properties(&new.node) || old.image != new.image
+}
+
+/// Json equality with numbers compared up to `VALUE_EPSILON`, waypoints lose precision as they
+/// round trip through `f32`.
+fn approx_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => (a - b).abs() < VALUE_EPSILON,
+            _ => a == b,
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| approx_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).map_or(false, |b| approx_eq(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Commands turning `old` into `new`. Nodes are created before and deleted after the arrows
+/// connecting them, so the commands can be applied in order and undone in reverse order.
+pub fn diff_snapshots(old: &HistorySnapshot, new: &HistorySnapshot) -> Vec<HistoryCommand> {
+    let mut created = vec![];
+    let mut changed = vec![];
+    let mut deleted = vec![];
+    for (id, new_node) in new.nodes.iter() {
+        let Some(old_node) = old.nodes.get(id) else {
+            created.push(HistoryCommand::NodeCreated(Box::new(new_node.clone())));
+            continue;
+        };
+        let (old_json, new_json) = (&old_node.node, &new_node.node);
+        if properties_changed(old_node, new_node) {
+            changed.push(HistoryCommand::NodeChanged {
+                from: Box::new(old_node.clone()),
+                to: Box::new(new_node.clone()),
+            });
+            continue;
+        }
+        if position(old_json).distance(position(new_json)) > POSITION_EPSILON {
+            changed.push(HistoryCommand::NodeMoved {
+                id: *id,
+                from: position(old_json),
+                to: position(new_json),
+            });
+        }
+        if size(old_json).distance(size(new_json)) > POSITION_EPSILON {
+            changed.push(HistoryCommand::NodeResized {
+                id: *id,
+                from: size(old_json),
+                to: size(new_json),
+            });
+        }
+        if old_json.text.text != new_json.text.text {
+            changed.push(HistoryCommand::TextEdited {
+                id: *id,
+                from: old_json.text.text.clone(),
+                to: new_json.text.text.clone(),
+            });
+        }
+    }
+    for (id, old_node) in old.nodes.iter() {
+        if !new.nodes.contains_key(id) {
+            deleted.push(HistoryCommand::NodeDeleted(Box::new(old_node.clone())));
+        }
+    }
+    changed.extend(diff_arrows(&old.arrows, &new.arrows));
+    created.into_iter().chain(changed).chain(deleted).collect()
+}
+
+/// Commands turning `old` arrows into `new` ones. Arrows have no ids, identical arrows are
+/// interchangeable.
+fn diff_arrows(old: &[Value], new: &[Value]) -> Vec<HistoryCommand> {
+    let mut commands = vec![];
+    let mut new_arrows: Vec<_> = new.iter().collect();
+    for arrow in old.iter() {
+        match new_arrows.iter().position(|new| approx_eq(arrow, new)) {
+            Some(index) => {
+                new_arrows.swap_remove(index);
+            }
+            None => commands.push(HistoryCommand::ArrowDeleted(arrow.clone())),
+        }
+    }
+    for arrow in new_arrows {
+        commands.push(HistoryCommand::ArrowCreated(arrow.clone()));
+    }
+    commands
+}
+
+/// Merges the moves, resizes and text edits of a node recorded in one entry into a single
+/// command each, and drops the ones ending where they started.
+fn coalesce(commands: Vec<HistoryCommand>) -> Vec<HistoryCommand> {
+    let mut merged: Vec<HistoryCommand> = vec![];
+    for command in commands {
+        let earlier = merged.iter_mut().rev().find(|earlier| {
+            command.node_id().is_some()
+                && earlier.node_id() == command.node_id()
+                && std::mem::discriminant(&**earlier) == std::mem::discriminant(&command)
+        });
+        let merged_into_earlier = match (earlier, &command) {
+            (
+                Some(HistoryCommand::NodeMoved { to, .. }),
+                HistoryCommand::NodeMoved { to: last, .. },
+            )
+            | (
+                Some(HistoryCommand::NodeResized { to, .. }),
+                HistoryCommand::NodeResized { to: last, .. },
+            ) => {
+                *to = *last;
+                true
+            }
+            (
+                Some(HistoryCommand::TextEdited { to, .. }),
+                HistoryCommand::TextEdited { to: last, .. },
+            ) => {
+                *to = last.clone();
+                true
+            }
+            _ => false,
+        };
+        if !merged_into_earlier {
+            merged.push(command);
+        }
+    }
+    merged.retain(|command| match command {
+        HistoryCommand::NodeMoved { from, to, .. }
+        | HistoryCommand::NodeResized { from, to, .. } => from.distance(*to) > POSITION_EPSILON,
+        HistoryCommand::TextEdited { from, to, .. } => from != to,
+        _ => true,
+    });
+    merged
+}
+
+fn active_tab(app_state: &AppState) -> Option<ReflectableUuid> {
+    let doc = app_state.docs.get(&app_state.current_document?)?;
+    doc.tabs.iter().find(|tab| tab.is_active).map(|tab| tab.id)
+}
+
+/// Turns the edits recorded by the editing systems into an entry of the active tab once none
+/// was recorded for a frame and no node is dragged or edited. Other changes of node properties
+/// and arrows are found by comparing only the changed ones with their last recorded state.
+pub fn record_history(
+    mut history: ResMut<History>,
+    load_request: Option<Res<LoadTabRequest>>,
+    app_state: Res<AppState>,
+    ui_state: Res<UiState>,
+    buttons: Res<Input<MouseButton>>,
+    nodes: NodeQuery,
+    containers: NodeContainerQuery,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    children: Query<&Children>,
+    arrows: ArrowQuery,
+    changed_nodes: Query<
+        Entity,
+        (
+            With<VeloNode>,
+            Or<(
+                Changed<Handle<Image>>,
+                Changed<TextPos>,
+                Changed<NodeBorder>,
+                Changed<NodeEffects>,
+                Changed<NodeTextStyle>,
+                Changed<NodeTags>,
+                Changed<NodeAttachments>,
+            )>,
+        ),
+    >,
+    changed_containers: Query<
+        &Children,
+        (
+            With<VeloNodeContainer>,
+            Or<(Changed<NodeZIndex>, Changed<NodeRotation>, Added<Locked>)>,
+        ),
+    >,
+    changed_arrows: Query<
+        (),
+        (
+            With<ArrowMeta>,
+            Or<(
+                Changed<ArrowMeta>,
+                Changed<ArrowStyle>,
+                Changed<ArrowLabel>,
+                Changed<ArrowRelation>,
+                Changed<ArrowWaypoints>,
+                Changed<ArrowPins>,
+                Changed<Visibility>,
+            )>,
+        ),
+    >,
+    mut unlocked: RemovedComponents<Locked>,
+    mut removed_arrows: RemovedComponents<ArrowMeta>,
+) {
+    let tab_id = active_tab(&app_state);
+    let mut loaded = load_request.is_some();
+    // the panned offset of the tab is restored by `tab_viewport`
+    if tab_id != history.tab_id {
+        loaded = true;
+        history.tab_id = tab_id;
+    }
+    let Some(tab_id) = tab_id else {
+        return;
+    };
+    if loaded {
+        history.snapshot = None;
+        history.pending = PendingEntry::default();
+        // remote edits of the canvas that is replaced are caught up with once the tab is loaded
+        history.remote.clear();
+    }
+    // the history is only changed when there is something to record, the history panel is
+    // rebuilt on changes
+    let recorded_this_frame = history.pending.recorded_this_frame;
+    if recorded_this_frame {
+        history.pending.recorded_this_frame = false;
+    }
+    let unlocked: Vec<Entity> = unlocked.iter().collect();
+    let removed_arrows = removed_arrows.iter().count() > 0;
+    if history.snapshot.is_some() {
+        let container_children = changed_containers
+            .iter()
+            .chain(
+                unlocked
+                    .iter()
+                    .filter_map(|entity| children.get(*entity).ok()),
+            )
+            .flat_map(|children| children.iter().copied())
+            .filter(|child| nodes.contains(*child));
+        let changed: Vec<Entity> = changed_nodes.iter().chain(container_children).collect();
+        if !changed.is_empty() {
+            history.pending.nodes.extend(changed);
+        }
+        if removed_arrows || !changed_arrows.is_empty() {
+            history.pending.arrows_changed = true;
+        }
+    }
+    if history.snapshot.is_none() && history.settle_frames == 0 {
+        history.settle_frames = SETTLE_FRAMES;
+    }
+    // nodes are compared once they are laid out and aren't being dragged or edited
+    let ready = !buttons.pressed(MouseButton::Left)
+        && !ui_state.editing_node
+        && history.to_apply.is_empty()
+        && history.remote.is_empty()
+        && history.apply_frames == 0
+        && history.pending_arrows.is_empty()
+        && history.pending_redraw.is_empty()
+        && containers.iter().all(|(rect, ..)| rect.size != Vec2::ZERO);
+    if !ready {
+        return;
+    }
+    // edits recorded in consecutive frames are one entry
+    if history.settle_frames == 0 && (recorded_this_frame || history.pending.is_empty()) {
+        return;
+    }
+    let history = &mut *history;
+    if history.settle_frames > 0 {
+        history.settle_frames -= 1;
+        if history.settle_frames > 0 {
+            return;
+        }
+        match history.snapshot.as_mut() {
+            Some(snapshot) => {
+                // nodes and arrows spawned by applied entries are read back from the canvas
+                for entity in history.pending.nodes.drain() {
+                    if let Some((node, image)) =
+                        json_node(entity, &nodes, &containers, &text_query, &children)
+                    {
+                        snapshot.nodes.insert(node.id, HistoryNode { node, image });
+                    }
+                }
+                if std::mem::take(&mut history.pending.arrows_changed) {
+                    snapshot.arrows = json_arrows(&arrows);
+                }
+            }
+            None => {
+                history.snapshot = Some(snapshot(&nodes, &containers, &text_query, &arrows));
+                history.pending = PendingEntry::default();
+            }
+        }
+        return;
+    }
+    let Some(snapshot) = history.snapshot.as_mut() else {
+        return;
+    };
+    let pending = &mut history.pending;
+    let recorded = coalesce(std::mem::take(&mut pending.commands));
+    let mut created = vec![];
+    let mut changed = vec![];
+    for entity in pending.nodes.drain() {
+        let Some((node, image)) = json_node(entity, &nodes, &containers, &text_query, &children)
+        else {
+            continue;
+        };
+        let node = HistoryNode { node, image };
+        let id = ReflectableUuid(node.node.id);
+        if let Some(index) = pending.created.iter().position(|created| *created == id) {
+            pending.created.swap_remove(index);
+            created.push(HistoryCommand::NodeCreated(Box::new(node)));
+        } else if let Some(old) = snapshot.nodes.get(&id.0) {
+            if properties_changed(old, &node) {
+                changed.push(HistoryCommand::NodeChanged {
+                    from: Box::new(old.clone()),
+                    to: Box::new(node),
+                });
+            }
+        }
+    }
+    // nodes are spawned by the time edits stop being recorded
+    pending.created.clear();
+    for command in created.iter().chain(recorded.iter()).chain(changed.iter()) {
+        snapshot.apply(command);
+    }
+    if std::mem::take(&mut pending.arrows_changed) {
+        let canvas_arrows = json_arrows(&arrows);
+        changed.extend(diff_arrows(&snapshot.arrows, &canvas_arrows));
+        snapshot.arrows = canvas_arrows;
+    }
+    // nodes are created before and deleted after the arrows connecting them
+    let (deleted, recorded): (Vec<_>, Vec<_>) = recorded
+        .into_iter()
+        .partition(|command| matches!(command, HistoryCommand::NodeDeleted(_)));
+    let entry: Vec<_> = created
+        .into_iter()
+        .chain(recorded)
+        .chain(changed)
+        .chain(deleted)
+        .collect();
+    if entry.is_empty() {
+        return;
+    }
+    if history.share_edits {
+        history.shared_edits.push((tab_id, entry.clone()));
+    }
+    let tab = history.tabs.entry(tab_id).or_default();
+    tab.undo.push(entry);
+    tab.redo.clear();
+}
+
+fn snapshot(
+    nodes: &NodeQuery,
+    containers: &NodeContainerQuery,
+    text_query: &Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    arrows: &ArrowQuery,
+) -> HistorySnapshot {
+    HistorySnapshot {
+        nodes: json_nodes(nodes, containers, text_query)
+            .into_iter()
+            .map(|(node, image)| (node.id, HistoryNode { node, image }))
+            .collect(),
+        arrows: json_arrows(arrows),
+    }
+}
+
+/// Undoes the last edit of the active tab with Cmd/Ctrl+Z, redoes it with Cmd/Ctrl+Shift+Z or
+/// Cmd/Ctrl+Y, unless other keys are bound to them.
+pub fn undo_redo(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    settings: Res<Settings>,
+    mut history: ResMut<History>,
+) {
+    if !ui_state.is_canvas_focused() {
+        return;
+    }
+    let redo = settings.keybindings.just_pressed(Action::Redo, &input);
+    let undo = settings.keybindings.just_pressed(Action::Undo, &input);
+    let Some(position) = history.active_tab().map(|tab| tab.undo.len()) else {
+        return;
+    };
+    if undo && position > 0 {
+        history.jump_to(position - 1);
+    } else if redo {
+        history.jump_to(position + 1);
+    }
+}
+
+/// Applies undone/redone entries to the canvas one by one, arrows are created once the markers of
+/// their nodes are spawned.
+pub fn apply_history(
+    mut commands: Commands,
+    mut history: ResMut<History>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    zoom: Res<CanvasZoom>,
+    asset_server: Res<AssetServer>,
+    mut font_system_state: ResMut<FontSystemState>,
+    mut containers: Query<
+        (
+            Entity,
+            &VeloNodeContainer,
+            &mut NodeRect,
+            Option<&NodeGroup>,
+            Option<&FrameMembers>,
+            Option<&FrameCollapsed>,
+            Option<&CollapsedBranch>,
+            Option<&PresentationStep>,
+        ),
+        With<VeloNodeContainer>,
+    >,
+    mut text_query: Query<(&RawText, &mut CosmicEditImage, &Parent), With<RawText>>,
+    text_styles: Query<&NodeTextStyle, With<VeloNode>>,
+    mut arrows: Query<(Entity, &ArrowMeta, Option<&ArrowLabel>, &mut Visibility)>,
+    markers: Query<&ArrowConnect>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut create_arrow: EventWriter<CreateArrowEvent>,
+    mut redraw_arrow: EventWriter<RedrawArrowEvent>,
+) {
+    let pending_arrows = std::mem::take(&mut history.pending_arrows);
+    for event in pending_arrows {
+        let spawned = |connect: &ArrowConnect| markers.iter().any(|marker| marker == connect);
+        if spawned(&event.start) && spawned(&event.end) {
+            create_arrow.send(event);
+        } else {
+            history.pending_arrows.push(event);
+        }
+    }
+    let pending_redraw = std::mem::take(&mut history.pending_redraw);
+    for id in pending_redraw {
+        if markers.iter().any(|marker| marker.id == id) {
+            redraw_arrow.send(RedrawArrowEvent { id });
+        } else {
+            history.pending_redraw.push(id);
+        }
+    }
+    if history.apply_frames > 0 {
+        history.apply_frames -= 1;
+        return;
+    }
+    if !history.pending_arrows.is_empty() {
+        return;
+    }
+    let (entry, remote) = match history.to_apply.pop_front() {
+        Some(entry) => (entry, false),
+        None => match history.remote.pop_front() {
+            Some(entry) => (entry, true),
+            None => return,
+        },
+    };
+    history.apply_frames = APPLY_FRAMES;
+    // remote edits leave the selection and the edited node alone unless they respawn them
+    let respawned: Vec<Uuid> = entry
+        .iter()
+        .filter_map(|command| match command {
+            HistoryCommand::NodeChanged { to: node, .. } | HistoryCommand::NodeDeleted(node) => {
+                Some(node.node.id)
+            }
+            _ => None,
+        })
+        .collect();
+    let edited = [
+        selected_nodes.edited(&ui_state),
+        ui_state.entity_to_resize.map(|(id, _)| id),
+    ];
+    if !remote || edited.iter().flatten().any(|id| respawned.contains(&id.0)) {
+        *ui_state = UiState::default();
+        commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+    }
+    if remote {
+        selected_nodes.0.retain(|id| !respawned.contains(&id.0));
+    } else {
+        selected_nodes.0.clear();
+    }
+    let scale_factor = windows.single().scale_factor() as f32;
+    for command in entry {
+        match command {
+            HistoryCommand::NodeMoved { id, to, .. } => {
+                for (_, container, mut rect, ..) in containers.iter_mut() {
+                    if container.id.0 == id {
+                        rect.position = to;
+                        redraw_arrow.send(RedrawArrowEvent { id: container.id });
+                    }
+                }
+            }
+            HistoryCommand::NodeResized { id, to, .. } => {
+                for (_, container, mut rect, ..) in containers.iter_mut() {
+                    if container.id.0 == id {
+                        rect.size = to;
+                        redraw_arrow.send(RedrawArrowEvent { id: container.id });
+                    }
+                }
+            }
+            HistoryCommand::TextEdited { id, to, .. } => {
+                for (raw_text, mut cosmic_edit, parent) in text_query.iter_mut() {
+                    if raw_text.id.0 == id {
+                        let text_style = text_styles.get(parent.get()).cloned().unwrap_or_default();
+                        let font_system = font_system_state.font_system.as_mut().unwrap();
+                        let buffer = cosmic_edit.editor.buffer_mut();
+                        buffer.set_text(font_system, to.as_str(), cosmic_attrs(&text_style));
+                        buffer.set_redraw(true);
+                    }
+                }
+            }
+            HistoryCommand::NodeChanged { to, .. } => {
+                let Some((entity, _, _, group, frame, frame_collapsed, collapsed, step)) =
+                    containers
+                        .iter()
+                        .find(|(_, container, ..)| container.id.0 == to.node.id)
+                else {
+                    continue;
+                };
+                // grouping, frames, branches and presentation aren't node properties
+                let group = group.copied();
+                let frame = frame.cloned();
+                let frame_collapsed = frame_collapsed.is_some();
+                let collapsed = collapsed.is_some();
+                let step = step.copied();
+                let id = ReflectableUuid(to.node.id);
+                commands.entity(entity).despawn_recursive();
+                let entity = spawn_node(
+                    &mut commands,
+                    &asset_server,
+                    &mut font_system_state,
+                    json_node_meta(to.node, Some(to.image), zoom.0, scale_factor),
+                );
+                if let Some(group) = group {
+                    commands.entity(entity).insert(group);
+                }
+                if let Some(frame) = frame {
+                    commands.entity(entity).insert(frame);
+                }
+                if frame_collapsed {
+                    commands.entity(entity).insert(FrameCollapsed);
+                }
+                if collapsed {
+                    commands.entity(entity).insert(CollapsedBranch);
+                }
+                if let Some(step) = step {
+                    commands.entity(entity).insert(step);
+                }
+                // arrows of the node are redrawn once its new markers are spawned
+                history.pending_redraw.push(id);
+            }
+            HistoryCommand::NodeCreated(node) => {
+                spawn_node(
+                    &mut commands,
+                    &asset_server,
+                    &mut font_system_state,
+                    json_node_meta(node.node, Some(node.image), zoom.0, scale_factor),
+                );
+            }
+            HistoryCommand::NodeDeleted(node) => {
+                for (entity, container, ..) in containers.iter() {
+                    if container.id.0 == node.node.id {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+            }
+            HistoryCommand::ArrowCreated(arrow) => match json_arrow_event(&arrow) {
+                Ok(event) => history.pending_arrows.push(event),
+                Err(e) => {
+                    let message = Localized::new("Couldn't read an arrow");
+                    notify(&mut commands, Notification::error(message).with_details(e));
+                }
+            },
+            HistoryCommand::ArrowDeleted(arrow) => {
+                let Ok(meta) = serde_json::from_value::<ArrowMeta>(arrow.clone()) else {
+                    continue;
+                };
+                let label = arrow["label"].as_str().unwrap_or_default();
+                if let Some((entity, _, _, mut visibility)) =
+                    arrows
+                        .iter_mut()
+                        .find(|(_, arrow, arrow_label, visibility)| {
+                            **arrow == meta
+                                && arrow_label.map_or("", |label| label.0.as_str()) == label
+                                && **visibility != Visibility::Hidden
+                        })
+                {
+                    remove_shape(&mut commands, entity, &mut visibility);
+                }
+            }
+        }
+    }
+}
+
+/// Lists the last edits of the active tab in the history panel, the list is rebuilt only when
+/// they change.
+pub fn update_history_list(
+    mut commands: Commands,
+    history: Res<History>,
+    list_query: Query<Entity, With<HistoryList>>,
+    mut shown: Local<Option<(Vec<String>, usize)>>,
+) {
+    if !history.is_changed() {
+        return;
+    }
+    let current = history
+        .active_tab()
+        .map_or((vec![], 0), |tab| (tab.entries(), tab.undo.len()));
+    if shown.as_ref() == Some(&current) {
+        return;
+    }
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+    let (entries, position) = &current;
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|builder| {
+        // the state the tab was opened in is the first entry
+        let first = entries.len().saturating_sub(SHOWN_ENTRIES - 1);
+        if first > 0 {
+            builder.spawn(TextBundle::from_section(
+                format!("{} earlier entries", first),
+                history_text_style(false),
+            ));
+        }
+        let labels = std::iter::once("Opened".to_string()).chain(entries.iter().cloned());
+        for (entry_position, label) in labels.enumerate().skip(first) {
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Px(22.)),
+                            align_items: AlignItems::Center,
+                            padding: UiRect::horizontal(Val::Px(4.)),
+                            ..default()
+                        },
+                        background_color: if entry_position == *position {
+                            CURRENT_ENTRY_COLOR.into()
+                        } else {
+                            Color::WHITE.into()
+                        },
+                        ..default()
+                    },
+                    HistoryEntryButton {
+                        position: entry_position,
+                    },
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    // undone edits are greyed out
+                    builder.spawn(TextBundle::from_section(
+                        label,
+                        history_text_style(entry_position <= *position),
+                    ));
+                });
+        }
+    });
+    *shown = Some(current);
+}
+
+fn history_text_style(done: bool) -> TextStyle {
+    TextStyle {
+        font_size: 14.,
+        color: if done { Color::BLACK } else { Color::GRAY },
+        ..default()
+    }
+}
+
+pub fn history_list_click(
+    interaction_query: Query<(&Interaction, &HistoryEntryButton), Changed<Interaction>>,
+    mut history: ResMut<History>,
+) {
+    for (interaction, entry) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            history.jump_to(entry.position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonNodeText, NodeType};
+
+    fn history_node(id: Uuid, left: f32, text: &str) -> HistoryNode {
+        HistoryNode {
+            node: JsonNode {
+                id,
+                node_type: NodeType::Rect,
+                left: Val::Px(left),
+                bottom: Val::Px(0.),
+                width: Val::Px(100.),
+                height: Val::Px(100.),
+                text: JsonNodeText {
+                    text: text.to_string(),
+                    pos: TextPos::Center,
+                    style: NodeTextStyle::default(),
+                },
+                bg_color: Color::WHITE,
+                z_index: 0,
+                locked: false,
+                border: NodeBorder::default(),
+                shadow: None,
+                opacity: 1.,
+                tags: vec![],
+                attachments: vec![],
+                rotation: 0.,
+            },
+            image: DEFAULT_IMAGE_HANDLE.typed(),
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let (moved, deleted, created) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let arrow = json!({"start": moved, "end": deleted});
+        let old = HistorySnapshot {
+            nodes: BTreeMap::from([
+                (moved, history_node(moved, 0., "a")),
+                (deleted, history_node(deleted, 0., "b")),
+            ]),
+            arrows: vec![arrow.clone()],
+        };
+        let mut changed = history_node(moved, 10.2, "c");
+        changed.node.width = Val::Px(100.3);
+        let new = HistorySnapshot {
+            nodes: BTreeMap::from([(moved, changed), (created, history_node(created, 0., ""))]),
+            arrows: vec![],
+        };
+        let commands = diff_snapshots(&old, &new);
+        assert!(
+            matches!(&commands[0], HistoryCommand::NodeCreated(node) if node.node.id == created)
+        );
+        assert!(matches!(
+            commands[1],
+            HistoryCommand::NodeMoved { id, from, to }
+                if id == moved && from == Vec2::ZERO && to == Vec2::new(10.2, 0.)
+        ));
+        assert!(matches!(
+            &commands[2],
+            HistoryCommand::TextEdited { id, from, to } if *id == moved && from == "a" && to == "c"
+        ));
+        assert!(matches!(&commands[3], HistoryCommand::ArrowDeleted(value) if *value == arrow));
+        assert!(
+            matches!(&commands[4], HistoryCommand::NodeDeleted(node) if node.node.id == deleted)
+        );
+        assert_eq!(commands.len(), 5);
+        assert_eq!(
+            describe_entry(&commands),
+            "Add node, Move node, Edit text, Delete arrow, Delete node"
+        );
+        assert_eq!(
+            describe_entry(&[commands[1].clone(), commands[1].inverse()]),
+            "Move node ×2"
+        );
+        assert!(diff_snapshots(&new, &new).is_empty());
+
+        assert!(matches!(
+            commands[1].inverse(),
+            HistoryCommand::NodeMoved { from, to, .. } if from == Vec2::new(10.2, 0.) && to == Vec2::ZERO
+        ));
+        assert!(matches!(
+            commands[3].inverse(),
+            HistoryCommand::ArrowCreated(_)
+        ));
+        assert!(matches!(
+            commands[4].inverse(),
+            HistoryCommand::NodeCreated(_)
+        ));
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let (dragged, resized) = (Uuid::new_v4(), Uuid::new_v4());
+        let moved = |id, from: f32, to: f32| HistoryCommand::NodeMoved {
+            id,
+            from: Vec2::splat(from),
+            to: Vec2::splat(to),
+        };
+        let commands = coalesce(vec![
+            moved(dragged, 0., 5.),
+            moved(resized, 0., 0.2),
+            HistoryCommand::NodeResized {
+                id: resized,
+                from: Vec2::splat(100.),
+                to: Vec2::splat(110.),
+            },
+            moved(dragged, 5., 12.),
+            moved(resized, 0.2, 0.3),
+            HistoryCommand::NodeResized {
+                id: resized,
+                from: Vec2::splat(110.),
+                to: Vec2::splat(120.),
+            },
+            HistoryCommand::TextEdited {
+                id: dragged,
+                from: "a".to_string(),
+                to: "a".to_string(),
+            },
+        ]);
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(
+            commands[0],
+            HistoryCommand::NodeMoved { id, from, to }
+                if id == dragged && from == Vec2::ZERO && to == Vec2::splat(12.)
+        ));
+        assert!(matches!(
+            commands[1],
+            HistoryCommand::NodeResized { id, from, to }
+                if id == resized && from == Vec2::splat(100.) && to == Vec2::splat(120.)
+        ));
+    }
+}
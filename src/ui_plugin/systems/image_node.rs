@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    window::PrimaryWindow,
+};
+use bevy_cosmic_edit::FontSystemState;
+use image::{imageops, RgbaImage};
+use uuid::Uuid;
+
+use crate::utils::ReflectableUuid;
+use crate::{
+    AddRectEvent, JsonNode, JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos, UiState,
+};
+
+use super::ui_helpers::{spawn_modal, InsertImage, MainPanel};
+use super::ModalAction;
+
+// Max size of image texture stored in the document
+const MAX_IMAGE_TEXTURE_SIZE: u32 = 1024;
+// Max size of newly created image node
+const MAX_IMAGE_NODE_SIZE: f32 = 400.;
+
+pub fn read_image_file(path: &Path) -> Option<Image> {
+    let bytes = std::fs::read(path).ok()?;
+    let img = image::load_from_memory(&bytes).ok()?.to_rgba8();
+    Some(image_from_rgba(img))
+}
+
+/// Creates texture from decoded image, downscaling it to fit `MAX_IMAGE_TEXTURE_SIZE`.
+pub fn image_from_rgba(mut img: RgbaImage) -> Image {
+    if img.width() > MAX_IMAGE_TEXTURE_SIZE || img.height() > MAX_IMAGE_TEXTURE_SIZE {
+        let scale = MAX_IMAGE_TEXTURE_SIZE as f32 / img.width().max(img.height()) as f32;
+        img = imageops::resize(
+            &img,
+            ((img.width() as f32 * scale) as u32).max(1),
+            ((img.height() as f32 * scale) as u32).max(1),
+            imageops::FilterType::Lanczos3,
+        );
+    }
+    let size = Extent3d {
+        width: img.width(),
+        height: img.height(),
+        ..Default::default()
+    };
+    Image::new(
+        size,
+        TextureDimension::D2,
+        img.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Sends event creating image node centered at `center` (world coordinates).
+pub fn add_image_node(
+    image: Image,
+    center: Vec2,
+    images: &mut ResMut<Assets<Image>>,
+    events: &mut EventWriter<AddRectEvent>,
+) {
+    let size = image.size();
+    let scale = (MAX_IMAGE_NODE_SIZE / size.x.max(size.y)).min(1.);
+    let (width, height) = (size.x * scale, size.y * scale);
+    let image = images.add(image);
+    events.send(AddRectEvent {
+        node: JsonNode {
+            id: Uuid::new_v4(),
+            node_type: NodeType::Image,
+            left: Val::Px(center.x - width / 2.),
+            bottom: Val::Px(center.y - height / 2.),
+            width: Val::Px(width),
+            height: Val::Px(height),
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::Center,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::WHITE,
+            z_index: 0,
+            locked: false,
+            border: NodeBorder::default(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: Some(image),
+    });
+}
+
+pub fn insert_image(
+    mut commands: Commands,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<InsertImage>)>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut font_system_state: ResMut<FontSystemState>,
+) {
+    let window = windows.single();
+    for interaction in &mut query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                let id = ReflectableUuid::generate();
+                *ui_state = UiState::default();
+                commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+                ui_state.modal_id = Some(id);
+                let entity = spawn_modal(
+                    &mut commands,
+                    &mut font_system_state,
+                    window,
+                    id,
+                    ModalAction::InsertImage,
+                );
+                commands.entity(main_panel_query.single()).add_child(entity);
+            }
+            Interaction::Hovered => {}
+            Interaction::None => {}
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::dot::dot_graph;
+use super::excalidraw::excalidraw_scene;
+use super::json_canvas::json_canvas;
+use super::mermaid::mermaid_flowchart;
+use super::ui_helpers::Localized;
+use super::{
+    compute_layout, is_encrypted_file, notify, FileIo, LayoutAlgorithm, Notification,
+    PassphraseRequest, LAYOUT_GAP,
+};
+use crate::canvas::arrow::components::{
+    ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowStyle, ArrowType,
+};
+use crate::components::Tab;
+use crate::resources::{AppState, LoadDocRequest, SaveTabRequest};
+use crate::utils::ReflectableUuid;
+use crate::{
+    BorderStyle, JsonFrame, JsonNode, JsonNodeText, NodeBorder, NodeTextStyle, NodeType, TextPos,
+};
+
+// Canvas position of the top left corner of imported graphs
+const IMPORT_ORIGIN: Vec2 = Vec2::new(50., 650.);
+const NODE_HEIGHT: f32 = 60.;
+const MIN_NODE_WIDTH: f32 = 120.;
+const MAX_NODE_WIDTH: f32 = 300.;
+// Width of a character of the default node font, imported nodes fit their first line
+const CHAR_WIDTH: f32 = 9.;
+
+/// Node read from an imported file.
+pub struct ImportedNode {
+    pub text: String,
+    pub node_type: NodeType,
+    pub bg_color: Color,
+    /// Position in the file, in canvas coordinates.
+    pub rect: Option<Rect>,
+    pub attachments: Vec<String>,
+}
+
+/// Arrow between nodes of an imported file, by their indices.
+pub struct ImportedEdge {
+    pub start: usize,
+    pub end: usize,
+    pub arrow_type: ArrowType,
+    pub style: ArrowStyle,
+    pub label: Option<String>,
+}
+
+/// Nodes and arrows read from another format.
+#[derive(Default)]
+pub struct ImportedGraph {
+    pub nodes: Vec<ImportedNode>,
+    pub edges: Vec<ImportedEdge>,
+    /// Frame nodes with the nodes inside of them.
+    pub frames: Vec<(usize, Vec<usize>)>,
+}
+
+impl ImportedGraph {
+    pub fn add_node(&mut self, text: String, node_type: NodeType) -> usize {
+        self.nodes.push(ImportedNode {
+            text,
+            node_type,
+            bg_color: Color::WHITE,
+            rect: None,
+            attachments: vec![],
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Tab json with the nodes connected with arrows. Nodes keep their positions from the file
+    /// if all of them have one, otherwise they are laid out with `algorithm`.
+    pub fn to_tab_json(&self, algorithm: LayoutAlgorithm) -> serde_json::Value {
+        let rects: Option<Vec<Rect>> = self.nodes.iter().map(|node| node.rect).collect();
+        let (sizes, centers): (Vec<_>, Vec<_>) = match rects {
+            Some(rects) => rects
+                .iter()
+                .map(|rect| (rect.size(), rect.center()))
+                .unzip(),
+            None => {
+                let sizes: Vec<_> = self
+                    .nodes
+                    .iter()
+                    .map(|node| {
+                        let chars = node.text.lines().next().unwrap_or_default().chars().count();
+                        Vec2::new(
+                            (chars as f32 * CHAR_WIDTH + 40.).clamp(MIN_NODE_WIDTH, MAX_NODE_WIDTH),
+                            NODE_HEIGHT,
+                        )
+                    })
+                    .collect();
+                let edges: Vec<_> = self
+                    .edges
+                    .iter()
+                    .map(|edge| (edge.start, edge.end))
+                    .collect();
+                let centers = compute_layout(
+                    algorithm,
+                    &sizes,
+                    &vec![Vec2::ZERO; sizes.len()],
+                    &edges,
+                    LAYOUT_GAP,
+                );
+                (sizes, centers)
+            }
+        };
+        let left_top = centers.iter().zip(sizes.iter()).fold(
+            Vec2::new(f32::MAX, f32::MIN),
+            |left_top, (center, size)| {
+                Vec2::new(
+                    left_top.x.min(center.x - size.x / 2.),
+                    left_top.y.max(center.y + size.y / 2.),
+                )
+            },
+        );
+        let ids: Vec<_> = self.nodes.iter().map(|_| Uuid::new_v4()).collect();
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let position = centers[i] - sizes[i] / 2. - left_top + IMPORT_ORIGIN;
+                let is_frame = node.node_type == NodeType::Frame;
+                json!(JsonNode {
+                    id: ids[i],
+                    node_type: node.node_type.clone(),
+                    left: Val::Px(position.x),
+                    bottom: Val::Px(position.y),
+                    width: Val::Px(sizes[i].x),
+                    height: Val::Px(sizes[i].y),
+                    text: JsonNodeText {
+                        text: node.text.clone(),
+                        pos: if is_frame {
+                            TextPos::TopLeft
+                        } else {
+                            TextPos::Center
+                        },
+                        style: NodeTextStyle::default(),
+                    },
+                    bg_color: node.bg_color,
+                    // frames are placed behind all other nodes
+                    z_index: if is_frame { -1 } else { 0 },
+                    locked: false,
+                    border: if is_frame {
+                        NodeBorder {
+                            color: Color::GRAY,
+                            style: BorderStyle::Dashed,
+                            ..default()
+                        }
+                    } else {
+                        NodeBorder::default()
+                    },
+                    shadow: None,
+                    opacity: 1.,
+                    tags: vec![],
+                    attachments: node.attachments.clone(),
+                    rotation: 0.,
+                })
+            })
+            .collect();
+        let arrows: Vec<_> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let mut arrow = json!(ArrowMeta {
+                    arrow_type: edge.arrow_type,
+                    start: ArrowConnect {
+                        id: ReflectableUuid(ids[edge.start]),
+                        pos: ArrowConnectPos::Bottom,
+                    },
+                    end: ArrowConnect {
+                        id: ReflectableUuid(ids[edge.end]),
+                        pos: ArrowConnectPos::Top,
+                    },
+                });
+                arrow["style"] = json!(edge.style);
+                if let Some(label) = &edge.label {
+                    arrow["label"] = json!(label);
+                }
+                arrow
+            })
+            .collect();
+        let frames: serde_json::Map<_, _> = self
+            .frames
+            .iter()
+            .map(|(frame, members)| {
+                (
+                    ids[*frame].to_string(),
+                    json!(JsonFrame {
+                        members: members.iter().map(|member| ids[*member]).collect(),
+                        collapsed: false,
+                    }),
+                )
+            })
+            .collect();
+        json!({
+            "images": {},
+            "nodes": nodes,
+            "arrows": arrows,
+            "frames": frames,
+        })
+    }
+}
+
+/// Mind map of a markdown outline: headings and list items become nodes connected to the heading
+/// or item they are nested in. Other lines are added to the text of the node above them.
+pub fn markdown_outline(markdown: &str) -> ImportedGraph {
+    let mut graph = ImportedGraph::default();
+    // levels and nodes of the headings and items the next line can be nested in
+    let mut parents: Vec<(usize, usize)> = vec![];
+    let mut heading_level = 0;
+    let mut lines = markdown.lines().peekable();
+    // front matter of exported tabs
+    if lines.peek().map(|line| line.trim()) == Some("---") {
+        lines.next();
+        for line in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+        }
+    }
+    let mut in_code = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+        }
+        let item = if in_code || trimmed.starts_with("```") {
+            None
+        } else if let Some(heading) = heading(trimmed) {
+            heading_level = heading.0;
+            Some(heading)
+        } else {
+            list_item(line).map(|(indent, text)| (heading_level + 1 + indent / 2, text))
+        };
+        match item {
+            Some((level, text)) => {
+                while parents.last().map_or(false, |(l, _)| *l >= level) {
+                    parents.pop();
+                }
+                let node = graph.add_node(text.to_string(), NodeType::Rect);
+                if let Some((_, parent)) = parents.last() {
+                    graph.edges.push(ImportedEdge {
+                        start: *parent,
+                        end: node,
+                        arrow_type: ArrowType::Arrow,
+                        style: ArrowStyle::for_type(ArrowType::Arrow),
+                        label: None,
+                    });
+                }
+                parents.push((level, node));
+            }
+            None if trimmed.is_empty() && !in_code => {}
+            None => match parents.last() {
+                Some((_, node)) => {
+                    let text = &mut graph.nodes[*node].text;
+                    text.push('\n');
+                    text.push_str(if in_code { line } else { trimmed });
+                }
+                None => {
+                    let node = graph.add_node(trimmed.to_string(), NodeType::Rect);
+                    parents.push((1, node));
+                }
+            },
+        }
+    }
+    graph
+}
+
+/// Level and text of a markdown heading.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (level > 0 && level <= 6).then_some((level, text.trim()))
+}
+
+/// Indentation (in spaces) and text of a bulleted or numbered list item.
+fn list_item(line: &str) -> Option<(usize, &str)> {
+    let text = line.trim_start();
+    let indent: usize = line[..line.len() - text.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let text = text
+        .strip_prefix("- ")
+        .or_else(|| text.strip_prefix("* "))
+        .or_else(|| text.strip_prefix("+ "))
+        .or_else(|| {
+            let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+            (digits > 0).then(|| text[digits..].strip_prefix(". "))?
+        })?;
+    Some((indent, text.trim()))
+}
+
+/// Adds a tab with the imported contents to the current document and switches to it.
+pub fn add_imported_tab(
+    commands: &mut Commands,
+    app_state: &mut AppState,
+    name: String,
+    json: serde_json::Value,
+) {
+    let Some(current_document) = app_state.current_document else {
+        return;
+    };
+    let tabs = &mut app_state.docs.get_mut(&current_document).unwrap().tabs;
+    for tab in tabs.iter_mut() {
+        if tab.is_active {
+            commands.insert_resource(SaveTabRequest {
+                tab_id: tab.id,
+                doc_id: current_document,
+            });
+        }
+        tab.is_active = false;
+    }
+    tabs.push(Tab {
+        id: ReflectableUuid::generate(),
+        name,
+        checkpoint: Some(json.to_string()),
+        zoom: 1.,
+        color: None,
+        icon: None,
+        pinned: false,
+        offset: [0., 0.],
+        selected: vec![],
+        is_active: true,
+    });
+    commands.insert_resource(LoadDocRequest {
+        doc_id: current_document,
+    });
+}
+
+/// Opens the file: formats velo can import are added as a new tab of the current document,
+/// anything else is loaded as a velo document.
+pub fn open_file(
+    path: &Path,
+    commands: &mut Commands,
+    app_state: &mut AppState,
+    file_io: &mut FileIo,
+) {
+    let read_text = || std::fs::read_to_string(path);
+    let name = path.file_stem().map_or("Imported".to_string(), |stem| {
+        stem.to_string_lossy().to_string()
+    });
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    let json = match extension.as_deref() {
+        Some("md") => {
+            read_text().map(|text| markdown_outline(&text).to_tab_json(LayoutAlgorithm::Tree))
+        }
+        Some("mmd") | Some("mermaid") => {
+            read_text().map(|text| mermaid_flowchart(&text).to_tab_json(LayoutAlgorithm::Layered))
+        }
+        Some("dot") | Some("gv") => {
+            read_text().map(|text| dot_graph(&text).to_tab_json(LayoutAlgorithm::Layered))
+        }
+        Some("excalidraw") => {
+            read_text().map(|text| excalidraw_scene(&text).to_tab_json(LayoutAlgorithm::Grid))
+        }
+        Some("canvas") => {
+            read_text().map(|text| json_canvas(&text).to_tab_json(LayoutAlgorithm::Grid))
+        }
+        _ if is_encrypted_file(path) => match std::fs::read(path) {
+            Ok(data) => {
+                commands.insert_resource(PassphraseRequest {
+                    data,
+                    wrong_passphrase: false,
+                });
+                return;
+            }
+            Err(e) => Err(e),
+        },
+        _ => {
+            file_io.load(path.to_path_buf());
+            return;
+        }
+    };
+    match json {
+        Ok(json) => add_imported_tab(commands, app_state, name, json),
+        Err(e) => {
+            let message =
+                Localized::new("Couldn't open {}").with_args([path.display().to_string()]);
+            notify(commands, Notification::error(message).with_details(e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_outline() {
+        let graph = markdown_outline(
+            "---\ntitle: \"Plan\"\n---\n# Plan\nintro\n## Build\n- code\n  - tests\n1. docs\n## Ship\n```\n# not a heading\n```\n",
+        );
+        let texts: Vec<_> = graph.nodes.iter().map(|node| node.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Plan\nintro",
+                "Build",
+                "code",
+                "tests",
+                "docs",
+                "Ship\n```\n# not a heading\n```"
+            ]
+        );
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.start, edge.end))
+            .collect();
+        assert_eq!(edges, vec![(0, 1), (1, 2), (2, 3), (1, 4), (0, 5)]);
+    }
+}
@@ -1,9 +1,9 @@
 use bevy::prelude::*;
 use bevy_ui_borders::BorderColor;
 
-use crate::ui_plugin::ui_helpers::GenericButton;
+use crate::ui_plugin::ui_helpers::{GenericButton, TooltipPosition};
 
-use super::ui_helpers::ChangeColor;
+use super::ui_helpers::{get_tooltip, ChangeColor, OpenColorPicker, Tooltip};
 
 pub fn add_color(commands: &mut Commands, color: Color) -> Entity {
     let top = commands
@@ -12,7 +12,7 @@ pub fn add_color(commands: &mut Commands, color: Color) -> Entity {
                 flex_direction: FlexDirection::Column,
                 align_self: AlignSelf::Stretch,
                 margin: UiRect::all(Val::Px(5.)),
-                size: Size::new(Val::Percent(20.), Val::Percent(100.)),
+                size: Size::new(Val::Percent(15.), Val::Percent(100.)),
                 ..default()
             },
             background_color: Color::BLACK.with_a(0.5).into(),
@@ -47,3 +47,61 @@ pub fn add_color(commands: &mut Commands, color: Color) -> Entity {
     commands.entity(top).add_child(button);
     top
 }
+
+pub fn add_custom_color(commands: &mut Commands, icon_font: &Handle<Font>) -> Entity {
+    let top = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_self: AlignSelf::Stretch,
+                margin: UiRect::all(Val::Px(5.)),
+                size: Size::new(Val::Percent(15.), Val::Percent(100.)),
+                ..default()
+            },
+            background_color: Color::BLACK.with_a(0.5).into(),
+            ..default()
+        })
+        .id();
+    let button = commands
+        .spawn((
+            ButtonBundle {
+                background_color: Color::rgb(207.0 / 255.0, 216.0 / 255.0, 220.0 / 255.0).into(),
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(-2.),
+                        right: Val::Px(0.),
+                        top: Val::Px(-2.),
+                        bottom: Val::Px(0.),
+                    },
+                    border: UiRect::all(Val::Px(1.)),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            BorderColor(Color::BLACK),
+            OpenColorPicker,
+            GenericButton,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                get_tooltip("Custom color".to_string(), 14., TooltipPosition::Bottom),
+                Tooltip,
+            ));
+            // palette icon
+            builder.spawn(TextBundle::from_section(
+                "\u{e40a}",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::BLACK,
+                    font: icon_font.clone(),
+                },
+            ));
+        })
+        .id();
+    commands.entity(top).add_child(button);
+    top
+}
@@ -8,12 +8,15 @@ use super::ui_helpers::{get_tooltip, ButtonAction, ButtonTypes, Tooltip};
 pub fn add_front_back(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    icon_font: &Handle<Font>,
     button_action: ButtonAction,
 ) -> Entity {
-    let (image, text) = if button_action.button_type == ButtonTypes::Front {
-        (asset_server.load("front.png"), "Move to front")
-    } else {
-        (asset_server.load("back.png"), "Move to back")
+    let (image, icon_code, text) = match button_action.button_type {
+        ButtonTypes::Front => (Some(asset_server.load("front.png")), None, "Bring forward"),
+        ButtonTypes::Back => (Some(asset_server.load("back.png")), None, "Send backward"),
+        ButtonTypes::ToFront => (None, Some("\u{e883}"), "Bring to front"),
+        ButtonTypes::ToBack => (None, Some("\u{e882}"), "Send to back"),
+        _ => panic!("Unsupported front/back button type"),
     };
     let top = commands
         .spawn(NodeBundle {
@@ -32,7 +35,7 @@ pub fn add_front_back(
         .spawn((
             ButtonBundle {
                 background_color: Color::rgb(207.0 / 255.0, 216.0 / 255.0, 220.0 / 255.0).into(),
-                image: image.into(),
+                image: image.map(|image| image.into()).unwrap_or_default(),
                 style: Style {
                     size: Size::new(Val::Percent(100.), Val::Percent(100.)),
                     align_items: AlignItems::Center,
@@ -58,6 +61,16 @@ pub fn add_front_back(
                 get_tooltip(text.to_string(), 14., TooltipPosition::Bottom),
                 Tooltip,
             ));
+            if let Some(icon_code) = icon_code {
+                builder.spawn(TextBundle::from_section(
+                    icon_code,
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::BLACK,
+                        font: icon_font.clone(),
+                    },
+                ));
+            }
         })
         .id();
     commands.entity(top).add_child(button);
@@ -0,0 +1,70 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ui_borders::BorderColor;
+
+use crate::ui_plugin::ui_helpers::{HistoryList, Localized};
+
+const ROW_HEIGHT: f32 = 24.;
+const FONT_SIZE: f32 = 14.;
+
+/// Recent edits of the active tab, clicking one undoes or redoes the tab to that point.
+pub fn add_history_panel(commands: &mut Commands) -> Entity {
+    let panel = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::width(Val::Px(220.)),
+                    flex_direction: FlexDirection::Column,
+                    margin: UiRect::top(Val::Px(10.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.with_a(0.95).into(),
+                // clicks on the panel don't reach the canvas
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+        ))
+        .id();
+    let header = commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                align_items: AlignItems::Center,
+                padding: UiRect::horizontal(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0).into(),
+            ..default()
+        })
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    "History",
+                    TextStyle {
+                        font_size: FONT_SIZE,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ),
+                Localized::new("History"),
+            ));
+        })
+        .id();
+    let list = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.)),
+                    ..default()
+                },
+                ..default()
+            },
+            HistoryList,
+        ))
+        .id();
+    commands.entity(panel).add_child(header);
+    commands.entity(panel).add_child(list);
+    panel
+}
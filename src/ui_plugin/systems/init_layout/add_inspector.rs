@@ -0,0 +1,181 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ui_borders::BorderColor;
+
+use crate::ui_plugin::ui_helpers::{
+    GenericButton, InspectorBody, InspectorStep, InspectorTagsField, InspectorToggle,
+    InspectorValue,
+};
+use crate::ui_plugin::NodeProperty;
+
+// Property, its label and the step of its -/+ buttons (no buttons for read-only properties)
+const PROPERTIES: [(NodeProperty, &str, f32); 10] = [
+    (NodeProperty::X, "x", 10.),
+    (NodeProperty::Y, "y", 10.),
+    (NodeProperty::Width, "width", 10.),
+    (NodeProperty::Height, "height", 10.),
+    (NodeProperty::ZIndex, "z-index", 1.),
+    (NodeProperty::Rotation, "rotation", 15.),
+    (NodeProperty::Color, "color", 1.),
+    (NodeProperty::TextPos, "text", 1.),
+    (NodeProperty::NodeType, "type", 0.),
+    (NodeProperty::Tags, "tags", 0.),
+];
+const ROW_HEIGHT: f32 = 24.;
+const FONT_SIZE: f32 = 14.;
+
+/// Properties panel, the first one of the right column of the canvas.
+pub fn add_inspector(commands: &mut Commands) -> Entity {
+    let text_style = TextStyle {
+        font_size: FONT_SIZE,
+        color: Color::BLACK,
+        ..default()
+    };
+    let inspector = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::width(Val::Px(220.)),
+                    flex_direction: FlexDirection::Column,
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.with_a(0.95).into(),
+                // clicks on the panel don't reach the canvas
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+        ))
+        .id();
+    let header = commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                    align_items: AlignItems::Center,
+                    padding: UiRect::horizontal(Val::Px(6.)),
+                    ..default()
+                },
+                background_color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0).into(),
+                ..default()
+            },
+            InspectorToggle,
+            GenericButton,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Properties [-]",
+                text_style.clone(),
+            ));
+        })
+        .id();
+    let body = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.)),
+                    ..default()
+                },
+                ..default()
+            },
+            InspectorBody,
+        ))
+        .id();
+    for (property, label, step) in PROPERTIES {
+        let row = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn(TextBundle {
+                    text: Text::from_section(label, text_style.clone()),
+                    style: Style {
+                        size: Size::width(Val::Px(70.)),
+                        ..default()
+                    },
+                    ..default()
+                });
+                if step != 0. {
+                    spawn_step_button(builder, property, -step, "-", &text_style);
+                }
+                let value = (
+                    TextBundle {
+                        text: Text::from_section("-", text_style.clone()),
+                        style: Style {
+                            flex_grow: 1.,
+                            margin: UiRect::horizontal(Val::Px(6.)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    InspectorValue { property },
+                );
+                if property == NodeProperty::Tags {
+                    // tags are edited by clicking the value
+                    builder
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    flex_grow: 1.,
+                                    size: Size::height(Val::Px(20.)),
+                                    align_items: AlignItems::Center,
+                                    border: UiRect::all(Val::Px(1.)),
+                                    overflow: Overflow::Hidden,
+                                    ..default()
+                                },
+                                background_color: Color::WHITE.into(),
+                                ..default()
+                            },
+                            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+                            InspectorTagsField,
+                        ))
+                        .with_children(|builder| {
+                            builder.spawn(value);
+                        });
+                } else {
+                    builder.spawn(value);
+                }
+                if step != 0. {
+                    spawn_step_button(builder, property, step, "+", &text_style);
+                }
+            })
+            .id();
+        commands.entity(body).add_child(row);
+    }
+    commands.entity(inspector).add_child(header);
+    commands.entity(inspector).add_child(body);
+    inspector
+}
+
+fn spawn_step_button(
+    builder: &mut ChildBuilder,
+    property: NodeProperty,
+    step: f32,
+    label: &str,
+    text_style: &TextStyle,
+) {
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(20.), Val::Px(20.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0).into(),
+                ..default()
+            },
+            InspectorStep { property, step },
+            GenericButton,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(label, text_style.clone()));
+        });
+}
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+
+use crate::ui_plugin::ui_helpers::{
+    GenericButton, LeftPanelChevron, LeftPanelEdge, ThemedBackground, ThemedText,
+};
+use crate::ui_plugin::{Theme, ThemeColor};
+
+const EDGE_WIDTH: f32 = 10.;
+
+/// Right edge of the left panel, dragged to resize the panel, with the chevron collapsing it.
+pub fn add_left_panel_edge(
+    commands: &mut Commands,
+    icon_font: &Handle<Font>,
+    theme: &Theme,
+) -> Entity {
+    commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(0.),
+                        top: Val::Px(0.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(EDGE_WIDTH), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: theme.border.into(),
+                z_index: ZIndex::Local(1),
+                ..default()
+            },
+            ThemedBackground(ThemeColor::Border),
+            LeftPanelEdge,
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(EDGE_WIDTH), Val::Px(32.)),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        background_color: theme.left_panel.into(),
+                        ..default()
+                    },
+                    ThemedBackground(ThemeColor::LeftPanel),
+                    LeftPanelChevron,
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    // the icon is set by `apply_panel_layout`
+                    builder.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: icon_font.clone(),
+                                font_size: 12.,
+                                color: theme.text,
+                            },
+                        ),
+                        ThemedText(ThemeColor::Text),
+                    ));
+                });
+        })
+        .id()
+}
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 
 use bevy::{
     a11y::{
@@ -9,6 +9,7 @@ use bevy::{
 };
 use bevy_pkv::PkvStore;
 
+use super::restore_session;
 use super::ui_helpers::ScrollingList;
 use crate::components::{Doc, Tab};
 use crate::resources::{AppState, LoadDocRequest};
@@ -20,7 +21,10 @@ pub fn add_list(
     app_state: &mut ResMut<AppState>,
     pkv: &mut ResMut<PkvStore>,
 ) -> Entity {
-    if let Ok(last_saved) = pkv.get::<ReflectableUuid>("last_saved") {
+    if let Some(doc_id) = restore_session(app_state, pkv) {
+        app_state.current_document = Some(doc_id);
+        commands.insert_resource(LoadDocRequest { doc_id });
+    } else if let Ok(last_saved) = pkv.get::<ReflectableUuid>("last_saved") {
         app_state.current_document = Some(last_saved);
         commands.insert_resource(LoadDocRequest { doc_id: last_saved });
     }
@@ -63,7 +67,13 @@ pub fn add_list(
         let tabs = vec![Tab {
             id: tab_id,
             name: tab_name,
-            checkpoints: VecDeque::new(),
+            checkpoint: None,
+            zoom: 1.,
+            color: None,
+            icon: None,
+            pinned: false,
+            offset: [0., 0.],
+            selected: vec![],
             is_active: true,
         }];
         let doc_id = ReflectableUuid::generate();
@@ -74,6 +84,8 @@ pub fn add_list(
                 name: "Untitled".to_string(),
                 tabs,
                 tags: vec![],
+                assets: HashMap::new(),
+                revision: 0,
             },
         );
         app_state.current_document = Some(doc_id);
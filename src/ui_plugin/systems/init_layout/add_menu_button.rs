@@ -1,7 +1,10 @@
 use bevy::{prelude::*, text::BreakLineOn};
 use bevy_ui_borders::BorderColor;
 
-use crate::ui_plugin::ui_helpers::{get_tooltip, GenericButton, Tooltip, TooltipPosition};
+use crate::ui_plugin::ui_helpers::{
+    get_tooltip, GenericButton, ThemedBackground, ThemedText, Tooltip, TooltipPosition,
+};
+use crate::ui_plugin::ThemeColor;
 
 pub fn add_menu_button(
     commands: &mut Commands,
@@ -14,11 +17,32 @@ pub fn add_menu_button(
         "New Document" => "\u{e89c}",
         "Save Document" => "\u{e161}",
         "Export To File" => "\u{e2c6}",
+        "Encrypt Document" => "\u{e897}",
         "Import From File" => "\u{e255}",
         "Import From URL" => "\u{e902}",
         "Save Document to window.velo object" => "\u{e866}",
         "Share Document (copy URL to clipboard)" => "\u{e80d}",
-        _ => panic!("Unknown menu button tooltip label: {}", label),
+        "Toggle Grid" => "\u{e3ec}",
+        "Toggle Dark Mode" => "\u{e51c}",
+        "Language" => "\u{e8e2}",
+        "Settings" => "\u{e8b8}",
+        "Keyboard Shortcuts" => "\u{e312}",
+        "Insert Image" => "\u{e3f4}",
+        "Layout" => "\u{e97a}",
+        "Pen" => "\u{e746}",
+        "Draw Rectangle" => "\u{e3c6}",
+        "Presentation" => "\u{e41b}",
+        "Compare With Last Save" => "\u{e3b9}",
+        "Open Recent" => "\u{e889}",
+        "Collaborate" => "\u{e7ef}",
+        "Sync Document" => "\u{e627}",
+        "Snippets" => "\u{e14f}",
+        "Import Document Into Current…" => "\u{e252}",
+        // buttons without an icon of their own get a generic one
+        _ => {
+            warn!("No icon for menu button \"{}\"", label);
+            "\u{e8fd}"
+        }
     };
     match label.as_str() {
         "New Tab" => {
@@ -58,6 +82,7 @@ pub fn add_menu_button(
                     },
                     component,
                     GenericButton,
+                    ThemedBackground(ThemeColor::BottomPanel),
                 ))
                 .with_children(|builder| {
                     let text_style = TextStyle {
@@ -74,7 +99,10 @@ pub fn add_menu_button(
                         linebreak_behaviour: BreakLineOn::WordBoundary,
                     };
 
-                    builder.spawn(TextBundle { text, ..default() });
+                    builder.spawn((
+                        TextBundle { text, ..default() },
+                        ThemedText(ThemeColor::Text),
+                    ));
                 })
                 .id();
             commands.entity(top).add_child(button);
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_ui_borders::BorderColor;
+
+use crate::ui_plugin::ui_helpers::{GenericButton, TooltipPosition};
+
+use super::ui_helpers::{get_tooltip, ChangeNodeEffect, NodeEffect, Tooltip};
+
+pub fn add_node_effect(
+    commands: &mut Commands,
+    icon_font: &Handle<Font>,
+    effect: NodeEffect,
+) -> Entity {
+    let (icon_code, text) = match effect {
+        NodeEffect::Shadow => ("\u{e3e0}", "Shadow"),
+        NodeEffect::Opacity => ("\u{e91c}", "Opacity"),
+    };
+    let top = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_self: AlignSelf::Stretch,
+                margin: UiRect::all(Val::Px(5.)),
+                size: Size::new(Val::Percent(15.), Val::Percent(100.)),
+                ..default()
+            },
+            background_color: Color::BLACK.with_a(0.5).into(),
+            ..default()
+        })
+        .id();
+    let button = commands
+        .spawn((
+            ButtonBundle {
+                background_color: Color::rgb(207.0 / 255.0, 216.0 / 255.0, 220.0 / 255.0).into(),
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    border: UiRect::all(Val::Px(1.)),
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(-2.),
+                        right: Val::Px(0.),
+                        top: Val::Px(-2.),
+                        bottom: Val::Px(0.),
+                    },
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            BorderColor(Color::BLACK),
+            ChangeNodeEffect { effect },
+            GenericButton,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                get_tooltip(text.to_string(), 14., TooltipPosition::Bottom),
+                Tooltip,
+            ));
+            builder.spawn(TextBundle::from_section(
+                icon_code,
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::BLACK,
+                    font: icon_font.clone(),
+                },
+            ));
+        })
+        .id();
+    commands.entity(top).add_child(button);
+    top
+}
@@ -1,7 +1,7 @@
 use bevy::{prelude::*, ui::FocusPolicy};
 use bevy_cosmic_edit::{spawn_cosmic_edit, CosmicEditMeta};
 use bevy_ui_borders::BorderColor;
-use cosmic_text::FontSystem;
+use cosmic_text::{Attrs, FontSystem};
 
 use crate::{
     ui_plugin::{
@@ -55,8 +55,11 @@ pub fn add_search_box(
         initial_background: None,
         font_size: 14.,
         line_height: 18.,
+        attrs: Attrs::new(),
         scale_factor,
         font_system,
+        paste: true,
+        mask_char: None,
         is_visible: true,
         initial_size: None,
     };
@@ -0,0 +1,77 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ui_borders::BorderColor;
+
+use crate::ui_plugin::ui_helpers::{GenericButton, TooltipPosition};
+use crate::ui_plugin::NodeType;
+
+use super::ui_helpers::{get_tooltip, shape_image, ButtonAction, ButtonTypes, Tooltip};
+
+pub fn add_shape(commands: &mut Commands, node_type: NodeType) -> Entity {
+    let text = match node_type {
+        NodeType::Ellipse => "New Ellipse",
+        NodeType::Diamond => "New Diamond",
+        NodeType::Hexagon => "New Hexagon",
+        NodeType::Parallelogram => "New Parallelogram",
+        NodeType::Cylinder => "New Cylinder",
+        _ => panic!("Unsupported shape node type: {:?}", node_type),
+    };
+    let top = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_self: AlignSelf::Stretch,
+                margin: UiRect::all(Val::Px(5.)),
+                size: Size::new(Val::Percent(15.), Val::Percent(100.)),
+                ..default()
+            },
+            background_color: Color::BLACK.with_a(0.5).into(),
+            ..default()
+        })
+        .id();
+    let button = commands
+        .spawn((
+            ButtonBundle {
+                background_color: Color::rgb(207.0 / 255.0, 216.0 / 255.0, 220.0 / 255.0).into(),
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    border: UiRect::all(Val::Px(1.)),
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(-2.),
+                        right: Val::Px(0.),
+                        top: Val::Px(-2.),
+                        bottom: Val::Px(0.),
+                    },
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            BorderColor(Color::BLACK),
+            ButtonAction {
+                button_type: ButtonTypes::AddShape(node_type.clone()),
+            },
+            GenericButton,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                get_tooltip(text.to_string(), 14., TooltipPosition::Bottom),
+                Tooltip,
+            ));
+            // preview of the shape drawn with the same texture as the node
+            builder.spawn(ImageBundle {
+                image: shape_image(&node_type).unwrap().into(),
+                background_color: Color::rgb(96.0 / 255.0, 125.0 / 255.0, 139.0 / 255.0).into(),
+                style: Style {
+                    size: Size::new(Val::Percent(70.), Val::Percent(70.)),
+                    ..default()
+                },
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            });
+        })
+        .id();
+    commands.entity(top).add_child(button);
+    top
+}
@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+
+use crate::ui_plugin::ui_helpers::{Localized, StatusBarItem, ThemedText};
+use crate::ui_plugin::{Theme, ThemeColor};
+
+const FONT_SIZE: f32 = 14.;
+
+/// Document stats at the right of the bottom panel, filled in by `update_status_bar`.
+pub fn add_status_bar(commands: &mut Commands, theme: &Theme) -> Entity {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::height(Val::Percent(100.)),
+                flex_grow: 1.,
+                flex_shrink: 0.,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::End,
+                padding: UiRect::right(Val::Px(10.)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|builder| {
+            for item in [
+                StatusBarItem::Selection,
+                StatusBarItem::WordCount,
+                StatusBarItem::NodeCount,
+                StatusBarItem::Zoom,
+                StatusBarItem::Autosave,
+            ] {
+                builder.spawn((
+                    TextBundle {
+                        text: Text::from_section(
+                            "",
+                            TextStyle {
+                                font_size: FONT_SIZE,
+                                color: theme.text,
+                                ..default()
+                            },
+                        ),
+                        style: Style {
+                            margin: UiRect::left(Val::Px(16.)),
+                            display: Display::None,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Localized::new(""),
+                    ThemedText(ThemeColor::Text),
+                    item,
+                ));
+            }
+        })
+        .id()
+}
@@ -0,0 +1,88 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ui_borders::BorderColor;
+
+use crate::ui_plugin::ui_helpers::{GenericButton, Localized, TagFilterModeButton, TagList};
+
+const ROW_HEIGHT: f32 = 24.;
+const FONT_SIZE: f32 = 14.;
+
+/// Tags of the current document with their counts, clicking a tag filters nodes by it.
+pub fn add_tags_panel(commands: &mut Commands) -> Entity {
+    let text_style = TextStyle {
+        font_size: FONT_SIZE,
+        color: Color::BLACK,
+        ..default()
+    };
+    let panel = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::width(Val::Px(220.)),
+                    flex_direction: FlexDirection::Column,
+                    margin: UiRect::top(Val::Px(10.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.with_a(0.95).into(),
+                // clicks on the panel don't reach the canvas
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+        ))
+        .id();
+    let header = commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(ROW_HEIGHT)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceBetween,
+                padding: UiRect::horizontal(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0).into(),
+            ..default()
+        })
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section("Tags", text_style.clone()),
+                Localized::new("Tags"),
+            ));
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::height(Val::Px(20.)),
+                            padding: UiRect::horizontal(Val::Px(6.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::WHITE.into(),
+                        ..default()
+                    },
+                    TagFilterModeButton,
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    builder.spawn(TextBundle::from_section("dim", text_style.clone()));
+                });
+        })
+        .id();
+    let list = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.)),
+                    ..default()
+                },
+                ..default()
+            },
+            TagList,
+        ))
+        .id();
+    commands.entity(panel).add_child(header);
+    commands.entity(panel).add_child(list);
+    panel
+}
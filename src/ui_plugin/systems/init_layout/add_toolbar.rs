@@ -0,0 +1,81 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ui_borders::BorderColor;
+
+use crate::ui_plugin::ui_helpers::{
+    get_tooltip, GenericButton, ThemedBackground, ThemedText, ToolButton, Tooltip, TooltipPosition,
+};
+use crate::ui_plugin::{Theme, ThemeColor, Tool};
+
+const BUTTON_SIZE: f32 = 36.;
+
+/// Column of tool buttons in the top left corner of the canvas, the active one is outlined by
+/// `highlight_tool_button`.
+pub fn add_toolbar(commands: &mut Commands, icon_font: &Handle<Font>, theme: &Theme) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                background_color: theme.surface.into(),
+                focus_policy: FocusPolicy::Block,
+                // stay above nodes even after they are brought to front
+                z_index: ZIndex::Local(i32::MAX),
+                ..default()
+            },
+            ThemedBackground(ThemeColor::Surface),
+        ))
+        .with_children(|builder| {
+            for tool in Tool::ALL {
+                builder
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(BUTTON_SIZE), Val::Px(BUTTON_SIZE)),
+                                margin: UiRect::vertical(Val::Px(2.)),
+                                border: UiRect::all(Val::Px(2.)),
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: theme.surface.into(),
+                            ..default()
+                        },
+                        BorderColor(Color::NONE),
+                        ThemedBackground(ThemeColor::Surface),
+                        ToolButton(tool),
+                        GenericButton,
+                    ))
+                    .with_children(|builder| {
+                        builder.spawn((
+                            get_tooltip(
+                                tool.action().label().to_string(),
+                                14.,
+                                TooltipPosition::Bottom,
+                            ),
+                            Tooltip,
+                        ));
+                        builder.spawn((
+                            TextBundle::from_section(
+                                tool.icon(),
+                                TextStyle {
+                                    font: icon_font.clone(),
+                                    font_size: 24.,
+                                    color: theme.text,
+                                },
+                            ),
+                            ThemedText(ThemeColor::Text),
+                        ));
+                    });
+            }
+        })
+        .id()
+}
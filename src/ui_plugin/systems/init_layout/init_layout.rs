@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, ui::FocusPolicy};
 
 use bevy::window::PrimaryWindow;
 use bevy_cosmic_edit::FontSystemState;
@@ -8,13 +8,22 @@ use std::time::Duration;
 use bevy_pkv::PkvStore;
 
 use super::ui_helpers::{
-    self, AddTab, BottomPanel, ButtonAction, LeftPanel, LeftPanelControls, LeftPanelExplorer,
-    MainPanel, Menu, NewDoc, ParticlesEffect, Root, SaveDoc, TextPosMode,
+    self, AddTab, ArrowStyleChange, BorderChange, BottomPanel, ButtonAction, CanvasPanels,
+    ChangeLanguage, CollabButton, CompareButton, DockablePanel, InsertImage, KeybindingsButton,
+    LayoutMenuButton, LeftPanel, LeftPanelControls, LeftPanelExplorer, MainBottom, MainPanel, Menu,
+    NewDoc, NodeEffect, ParticlesEffect, PenButton, PresentationButton, RecentDocsButton,
+    RectToolButton, RightPanel, Root, SaveDoc, SettingsButton, SnippetsButton, TabBar, TextPosMode,
+    TextStyleChange, ThemedBackground, ThemedBorder, ToggleGrid, ToggleTheme,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{ui_helpers::SyncDocButton, CloudSync};
+use super::{
+    CollabSettings, CommChannels, DockedPanel, EncryptDoc, ExportToFile, ImportFromFile,
+    ImportFromUrl, ImportIntoCurrent, ShareDoc, Theme, ThemeColor, NODE_COLORS,
 };
-use super::{CommChannels, ExportToFile, ImportFromFile, ImportFromUrl, ShareDoc};
 use crate::canvas::arrow::components::{ArrowMode, ArrowType};
 use crate::resources::AppState;
-use crate::{BlinkTimer, TextPos};
+use crate::{BlinkTimer, BorderStyle, TextPos};
 
 #[path = "add_arrow.rs"]
 mod add_arrow;
@@ -28,10 +37,30 @@ use add_color::*;
 mod add_front_back;
 use add_front_back::*;
 
+#[path = "add_shape.rs"]
+mod add_shape;
+use add_shape::*;
+
 #[path = "add_text_pos.rs"]
 mod add_text_pos;
 use add_text_pos::*;
 
+#[path = "add_arrow_style.rs"]
+mod add_arrow_style;
+use add_arrow_style::*;
+
+#[path = "add_border.rs"]
+mod add_border;
+use add_border::*;
+
+#[path = "add_node_effect.rs"]
+mod add_node_effect;
+use add_node_effect::*;
+
+#[path = "add_text_style.rs"]
+mod add_text_style;
+use add_text_style::*;
+
 #[path = "node_manipulation.rs"]
 mod node_manipulation;
 use node_manipulation::*;
@@ -48,6 +77,30 @@ use add_list::*;
 mod add_effect;
 use add_effect::*;
 
+#[path = "add_inspector.rs"]
+mod add_inspector;
+use add_inspector::*;
+
+#[path = "add_tags_panel.rs"]
+mod add_tags_panel;
+use add_tags_panel::*;
+
+#[path = "add_history_panel.rs"]
+mod add_history_panel;
+use add_history_panel::*;
+
+#[path = "add_status_bar.rs"]
+mod add_status_bar;
+use add_status_bar::*;
+
+#[path = "add_left_panel_edge.rs"]
+mod add_left_panel_edge;
+use add_left_panel_edge::*;
+
+#[path = "add_toolbar.rs"]
+mod add_toolbar;
+use add_toolbar::*;
+
 #[path = "add_search_box.rs"]
 mod add_search_box;
 use add_search_box::*;
@@ -59,6 +112,9 @@ pub fn init_layout(
     asset_server: Res<AssetServer>,
     mut pkv: ResMut<PkvStore>,
     mut font_system_state: ResMut<FontSystemState>,
+    collab_settings: Res<CollabSettings>,
+    #[cfg(not(target_arch = "wasm32"))] cloud_sync: Res<CloudSync>,
+    theme: Res<Theme>,
     windows: Query<&Window, With<PrimaryWindow>>,
 ) {
     let primary_window: &Window = windows.single();
@@ -75,7 +131,7 @@ pub fn init_layout(
     let bottom_panel = commands
         .spawn((
             NodeBundle {
-                background_color: Color::rgb(189.0 / 255.0, 189.0 / 255.0, 189.0 / 255.0).into(),
+                background_color: theme.bottom_panel.into(),
                 style: Style {
                     border: UiRect::all(Val::Px(1.0)),
                     position_type: PositionType::Absolute,
@@ -94,11 +150,31 @@ pub fn init_layout(
                 ..default()
             },
             BottomPanel,
-            BorderColor(Color::rgb(204.0 / 255.0, 204.0 / 255.0, 204.0 / 255.0)),
+            BorderColor(theme.border),
+            ThemedBackground(ThemeColor::BottomPanel),
+            ThemedBorder(ThemeColor::Border),
         ))
         .id();
+    let tab_bar = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Auto, Val::Percent(100.)),
+                    min_size: Size::new(Val::Px(0.), Val::Auto),
+                    align_items: AlignItems::Center,
+                    overflow: Overflow::Hidden,
+                    ..default()
+                },
+                ..default()
+            },
+            TabBar::default(),
+        ))
+        .id();
+    commands.entity(bottom_panel).add_child(tab_bar);
     let add_tab = add_menu_button(&mut commands, "New Tab".to_string(), &icon_font, AddTab);
     commands.entity(bottom_panel).add_child(add_tab);
+    let status_bar = add_status_bar(&mut commands, &theme);
+    commands.entity(bottom_panel).add_child(status_bar);
 
     let docs = add_list(&mut commands, &mut app_state, &mut pkv);
 
@@ -126,7 +202,7 @@ pub fn init_layout(
     let menu = commands
         .spawn((
             NodeBundle {
-                background_color: Color::rgb(245.0 / 255.0, 245.0 / 255.0, 245.0 / 255.0).into(),
+                background_color: theme.menu.into(),
                 style: Style {
                     border: UiRect::all(Val::Px(1.0)),
                     size: Size::new(Val::Percent(100.0), Val::Percent(5.)),
@@ -140,8 +216,10 @@ pub fn init_layout(
                 },
                 ..default()
             },
-            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+            BorderColor(theme.border),
             Menu,
+            ThemedBackground(ThemeColor::Menu),
+            ThemedBorder(ThemeColor::Border),
         ))
         .id();
     let new_doc = add_menu_button(
@@ -150,6 +228,12 @@ pub fn init_layout(
         &icon_font,
         NewDoc,
     );
+    let recent_docs = add_menu_button(
+        &mut commands,
+        "Open Recent".to_string(),
+        &icon_font,
+        RecentDocsButton,
+    );
     let save_doc = add_menu_button(
         &mut commands,
         "Save Document".to_string(),
@@ -164,12 +248,26 @@ pub fn init_layout(
         ExportToFile,
     );
     #[cfg(not(target_arch = "wasm32"))]
+    let encrypt_doc = add_menu_button(
+        &mut commands,
+        "Encrypt Document".to_string(),
+        &icon_font,
+        EncryptDoc,
+    );
+    #[cfg(not(target_arch = "wasm32"))]
     let import_file = add_menu_button(
         &mut commands,
         "Import From File".to_string(),
         &icon_font,
         ImportFromFile,
     );
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+    let import_into_current = add_menu_button(
+        &mut commands,
+        "Import Document Into Current…".to_string(),
+        &icon_font,
+        ImportIntoCurrent,
+    );
     #[cfg(not(target_arch = "wasm32"))]
     let import_url = add_menu_button(
         &mut commands,
@@ -177,6 +275,13 @@ pub fn init_layout(
         &icon_font,
         ImportFromUrl,
     );
+    #[cfg(not(target_arch = "wasm32"))]
+    let insert_image = add_menu_button(
+        &mut commands,
+        "Insert Image".to_string(),
+        &icon_font,
+        InsertImage,
+    );
     #[cfg(target_arch = "wasm32")]
     let set_window_prop = add_menu_button(
         &mut commands,
@@ -185,13 +290,20 @@ pub fn init_layout(
         super::SetWindowProperty,
     );
     commands.entity(menu).add_child(new_doc);
+    commands.entity(menu).add_child(recent_docs);
     commands.entity(menu).add_child(save_doc);
     #[cfg(not(target_arch = "wasm32"))]
     commands.entity(menu).add_child(export_file);
     #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(encrypt_doc);
+    #[cfg(not(target_arch = "wasm32"))]
     commands.entity(menu).add_child(import_file);
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+    commands.entity(menu).add_child(import_into_current);
     #[cfg(not(target_arch = "wasm32"))]
     commands.entity(menu).add_child(import_url);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(insert_image);
     if app_state.github_token.is_some() {
         let share_doc = add_menu_button(
             &mut commands,
@@ -201,24 +313,118 @@ pub fn init_layout(
         );
         commands.entity(menu).add_child(share_doc);
     }
+    if collab_settings.relay.is_some() {
+        let collaborate = add_menu_button(
+            &mut commands,
+            "Collaborate".to_string(),
+            &icon_font,
+            CollabButton,
+        );
+        commands.entity(menu).add_child(collaborate);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if cloud_sync.backend.is_some() {
+        let sync_doc = add_menu_button(
+            &mut commands,
+            "Sync Document".to_string(),
+            &icon_font,
+            SyncDocButton,
+        );
+        commands.entity(menu).add_child(sync_doc);
+    }
     #[cfg(target_arch = "wasm32")]
     commands.entity(menu).add_child(set_window_prop);
+    let toggle_grid = add_menu_button(
+        &mut commands,
+        "Toggle Grid".to_string(),
+        &icon_font,
+        ToggleGrid,
+    );
+    commands.entity(menu).add_child(toggle_grid);
+    let toggle_theme = add_menu_button(
+        &mut commands,
+        "Toggle Dark Mode".to_string(),
+        &icon_font,
+        ToggleTheme,
+    );
+    commands.entity(menu).add_child(toggle_theme);
+    let language = add_menu_button(
+        &mut commands,
+        "Language".to_string(),
+        &icon_font,
+        ChangeLanguage,
+    );
+    commands.entity(menu).add_child(language);
+    let settings = add_menu_button(
+        &mut commands,
+        "Settings".to_string(),
+        &icon_font,
+        SettingsButton,
+    );
+    commands.entity(menu).add_child(settings);
+    let keybindings = add_menu_button(
+        &mut commands,
+        "Keyboard Shortcuts".to_string(),
+        &icon_font,
+        KeybindingsButton,
+    );
+    commands.entity(menu).add_child(keybindings);
+    let layout = add_menu_button(
+        &mut commands,
+        "Layout".to_string(),
+        &icon_font,
+        LayoutMenuButton,
+    );
+    commands.entity(menu).add_child(layout);
+    let pen = add_menu_button(&mut commands, "Pen".to_string(), &icon_font, PenButton);
+    commands.entity(menu).add_child(pen);
+    let rect_tool = add_menu_button(
+        &mut commands,
+        "Draw Rectangle".to_string(),
+        &icon_font,
+        RectToolButton,
+    );
+    commands.entity(menu).add_child(rect_tool);
+    let presentation = add_menu_button(
+        &mut commands,
+        "Presentation".to_string(),
+        &icon_font,
+        PresentationButton,
+    );
+    commands.entity(menu).add_child(presentation);
+    let compare = add_menu_button(
+        &mut commands,
+        "Compare With Last Save".to_string(),
+        &icon_font,
+        CompareButton,
+    );
+    commands.entity(menu).add_child(compare);
+    let snippets = add_menu_button(
+        &mut commands,
+        "Snippets".to_string(),
+        &icon_font,
+        SnippetsButton,
+    );
+    commands.entity(menu).add_child(snippets);
 
     let main_bottom = commands
-        .spawn(NodeBundle {
-            style: Style {
-                size: Size::new(Val::Percent(100.0), Val::Percent(95.)),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Center,
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(95.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        })
+            MainBottom,
+        ))
         .id();
     let left_panel = commands
         .spawn((
             NodeBundle {
-                background_color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0).into(),
+                background_color: theme.left_panel.into(),
                 style: Style {
                     border: UiRect::all(Val::Px(1.0)),
                     size: Size::new(Val::Percent(15.), Val::Percent(100.)),
@@ -229,21 +435,26 @@ pub fn init_layout(
                 },
                 ..default()
             },
-            BorderColor(Color::rgb(192.0 / 255.0, 192.0 / 255.0, 192.0 / 255.0)),
+            BorderColor(theme.border),
             LeftPanel,
+            ThemedBackground(ThemeColor::LeftPanel),
+            ThemedBorder(ThemeColor::Border),
         ))
         .id();
     let right_panel = commands
-        .spawn((NodeBundle {
-            style: Style {
-                size: Size::new(Val::Percent(85.), Val::Percent(100.)),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Center,
-                flex_direction: FlexDirection::Column,
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(85.), Val::Percent(100.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        },))
+            RightPanel,
+        ))
         .id();
     let main_panel = commands
         .spawn((
@@ -262,9 +473,45 @@ pub fn init_layout(
         ))
         .id();
 
+    // properties, tags and history panels in the top right corner of the canvas
+    let canvas_panels = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                // stay above nodes even after they are brought to front
+                z_index: ZIndex::Local(i32::MAX),
+                ..default()
+            },
+            CanvasPanels,
+        ))
+        .id();
+    let inspector = add_inspector(&mut commands);
+    let tags_panel = add_tags_panel(&mut commands);
+    commands
+        .entity(tags_panel)
+        .insert(DockablePanel(DockedPanel::Tags));
+    let history_panel = add_history_panel(&mut commands);
+    commands.entity(canvas_panels).add_child(inspector);
+    commands.entity(canvas_panels).add_child(tags_panel);
+    commands.entity(canvas_panels).add_child(history_panel);
+    commands.entity(main_panel).add_child(canvas_panels);
+    let toolbar = add_toolbar(&mut commands, &icon_font, &theme);
+    commands.entity(main_panel).add_child(toolbar);
+
     commands.entity(right_panel).add_child(main_panel);
     commands.entity(right_panel).add_child(bottom_panel);
 
+    // the add and search panels are sized and docked by `apply_panel_layout`, they keep the
+    // background of the left panel when docked on the canvas
     let left_panel_controls = commands
         .spawn((
             NodeBundle {
@@ -276,9 +523,13 @@ pub fn init_layout(
                     justify_content: JustifyContent::Center,
                     ..default()
                 },
+                background_color: theme.left_panel.into(),
+                focus_policy: FocusPolicy::Block,
                 ..default()
             },
             LeftPanelControls,
+            DockablePanel(DockedPanel::Add),
+            ThemedBackground(ThemeColor::LeftPanel),
         ))
         .id();
     #[cfg(not(target_arch = "wasm32"))]
@@ -297,9 +548,13 @@ pub fn init_layout(
                     justify_content: JustifyContent::Center,
                     ..default()
                 },
+                background_color: theme.left_panel.into(),
+                focus_policy: FocusPolicy::Block,
                 ..default()
             },
             LeftPanelExplorer,
+            DockablePanel(DockedPanel::Search),
+            ThemedBackground(ThemeColor::LeftPanel),
         ))
         .id();
     #[cfg(not(target_arch = "wasm32"))]
@@ -308,6 +563,8 @@ pub fn init_layout(
 
     commands.entity(left_panel).add_child(left_panel_controls);
     commands.entity(left_panel).add_child(left_panel_explorer);
+    let left_panel_edge = add_left_panel_edge(&mut commands, &icon_font, &theme);
+    commands.entity(left_panel).add_child(left_panel_edge);
 
     let rectangle_creation = node_manipulation(
         &mut commands,
@@ -322,6 +579,23 @@ pub fn init_layout(
             button_type: ui_helpers::ButtonTypes::Del,
         },
     );
+    let shapes = commands
+        .spawn((NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                size: Size::new(Val::Percent(90.), Val::Percent(8.)),
+                margin: UiRect::all(Val::Px(5.)),
+                justify_content: JustifyContent::Start,
+                ..default()
+            },
+            ..default()
+        },))
+        .id();
+    for node_type in ui_helpers::SHAPE_NODE_TYPES.iter() {
+        let shape = add_shape(&mut commands, node_type.clone());
+        commands.entity(shapes).add_child(shape);
+    }
+
     let fron_back = commands
         .spawn((NodeBundle {
             style: Style {
@@ -337,6 +611,7 @@ pub fn init_layout(
     let front = add_front_back(
         &mut commands,
         &asset_server,
+        &icon_font,
         ButtonAction {
             button_type: ui_helpers::ButtonTypes::Front,
         },
@@ -344,12 +619,31 @@ pub fn init_layout(
     let back = add_front_back(
         &mut commands,
         &asset_server,
+        &icon_font,
         ButtonAction {
             button_type: ui_helpers::ButtonTypes::Back,
         },
     );
+    let to_front = add_front_back(
+        &mut commands,
+        &asset_server,
+        &icon_font,
+        ButtonAction {
+            button_type: ui_helpers::ButtonTypes::ToFront,
+        },
+    );
+    let to_back = add_front_back(
+        &mut commands,
+        &asset_server,
+        &icon_font,
+        ButtonAction {
+            button_type: ui_helpers::ButtonTypes::ToBack,
+        },
+    );
     commands.entity(fron_back).add_child(front);
     commands.entity(fron_back).add_child(back);
+    commands.entity(fron_back).add_child(to_front);
+    commands.entity(fron_back).add_child(to_back);
 
     let color_picker = commands
         .spawn((NodeBundle {
@@ -363,29 +657,12 @@ pub fn init_layout(
             ..default()
         },))
         .id();
-    let color1 = add_color(&mut commands, Color::rgb(1., 225.0 / 255.0, 130.0 / 255.0));
-    let color2 = add_color(
-        &mut commands,
-        Color::rgb(215.0 / 255.0, 204.0 / 255.0, 200.0 / 255.0),
-    );
-    let color3 = add_color(
-        &mut commands,
-        Color::rgb(173.0 / 255.0, 216.0 / 255.0, 230.0 / 255.0),
-    );
-    let color4 = add_color(
-        &mut commands,
-        Color::rgb(207.0 / 255.0, 226.0 / 255.0, 243.0 / 255.0),
-    );
-    let color5 = add_color(
-        &mut commands,
-        Color::rgb(245.0 / 255.0, 222.0 / 255.0, 179.0 / 255.0),
-    );
-
-    commands.entity(color_picker).add_child(color1);
-    commands.entity(color_picker).add_child(color2);
-    commands.entity(color_picker).add_child(color3);
-    commands.entity(color_picker).add_child(color4);
-    commands.entity(color_picker).add_child(color5);
+    for color in NODE_COLORS {
+        let color = add_color(&mut commands, color);
+        commands.entity(color_picker).add_child(color);
+    }
+    let custom_color = add_custom_color(&mut commands, &icon_font);
+    commands.entity(color_picker).add_child(custom_color);
 
     let arrow_modes = commands
         .spawn((NodeBundle {
@@ -448,6 +725,32 @@ pub fn init_layout(
     commands.entity(arrow_modes).add_child(arrow5);
     commands.entity(arrow_modes).add_child(arrow6);
 
+    let arrow_styles = commands
+        .spawn((NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                size: Size::new(Val::Percent(90.), Val::Percent(9.)),
+                margin: UiRect::all(Val::Px(5.)),
+                justify_content: JustifyContent::Start,
+                ..default()
+            },
+            ..default()
+        },))
+        .id();
+    for change in [
+        ArrowStyleChange::StartHead,
+        ArrowStyleChange::EndHead,
+        ArrowStyleChange::Thickness,
+        ArrowStyleChange::Dash,
+        ArrowStyleChange::Color,
+        ArrowStyleChange::Routing,
+        ArrowStyleChange::Animation,
+        ArrowStyleChange::Relation,
+    ] {
+        let arrow_style = add_arrow_style(&mut commands, &icon_font, change);
+        commands.entity(arrow_styles).add_child(arrow_style);
+    }
+
     let text_modes = commands
         .spawn((NodeBundle {
             style: Style {
@@ -477,6 +780,55 @@ pub fn init_layout(
     commands.entity(text_modes).add_child(text_pos1);
     commands.entity(text_modes).add_child(text_pos2);
 
+    let border_modes = commands
+        .spawn((NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                size: Size::new(Val::Percent(90.), Val::Percent(9.)),
+                margin: UiRect::all(Val::Px(5.)),
+                justify_content: JustifyContent::Start,
+                ..default()
+            },
+            ..default()
+        },))
+        .id();
+    for change in [
+        BorderChange::Style(BorderStyle::Solid),
+        BorderChange::Style(BorderStyle::Dashed),
+        BorderChange::Style(BorderStyle::Dotted),
+        BorderChange::Width,
+        BorderChange::Radius,
+        BorderChange::Color,
+    ] {
+        let border = add_border(&mut commands, &icon_font, change);
+        commands.entity(border_modes).add_child(border);
+    }
+
+    let node_styles = commands
+        .spawn((NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                size: Size::new(Val::Percent(90.), Val::Percent(9.)),
+                margin: UiRect::all(Val::Px(5.)),
+                justify_content: JustifyContent::Start,
+                ..default()
+            },
+            ..default()
+        },))
+        .id();
+    for effect in [NodeEffect::Shadow, NodeEffect::Opacity] {
+        let node_effect = add_node_effect(&mut commands, &icon_font, effect);
+        commands.entity(node_styles).add_child(node_effect);
+    }
+    for change in [
+        TextStyleChange::FontSize,
+        TextStyleChange::Font,
+        TextStyleChange::Color,
+    ] {
+        let text_style = add_text_style(&mut commands, &icon_font, change);
+        commands.entity(node_styles).add_child(text_style);
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     let effects = commands
         .spawn((NodeBundle {
@@ -499,9 +851,13 @@ pub fn init_layout(
     commands
         .entity(left_panel_controls)
         .add_child(rectangle_creation);
+    commands.entity(left_panel_controls).add_child(shapes);
     commands.entity(left_panel_controls).add_child(color_picker);
     commands.entity(left_panel_controls).add_child(arrow_modes);
+    commands.entity(left_panel_controls).add_child(arrow_styles);
     commands.entity(left_panel_controls).add_child(text_modes);
+    commands.entity(left_panel_controls).add_child(border_modes);
+    commands.entity(left_panel_controls).add_child(node_styles);
     commands.entity(left_panel_controls).add_child(fron_back);
     #[cfg(not(target_arch = "wasm32"))]
     commands.entity(left_panel_controls).add_child(effects);
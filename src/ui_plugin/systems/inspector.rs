@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+use bevy_cosmic_edit::CosmicEditImage;
+use cosmic_text::Edit;
+
+use crate::canvas::arrow::events::RedrawArrowEvent;
+use crate::utils::{next_in_cycle, to_cosmic_text_pos, ReflectableUuid};
+use crate::{NodeTextStyle, TextPos};
+
+use super::{
+    ui_helpers::{
+        InspectorBody, InspectorStep, InspectorToggle, InspectorValue, NodeEffects, NodeRect,
+        NodeZIndex,
+    },
+    ChangeNodePropertyEvent, History, HistoryCommand, Locked, NodeProperty, NodeRotation, NodeTags,
+    RawText, SelectedNodes, UiState, VeloNode, VeloNodeContainer, MIN_NODE_SIZE, NODE_COLORS,
+};
+
+/// Node shown in the inspector: the edited one or the first selected.
+pub fn inspected_node(
+    ui_state: &UiState,
+    selected_nodes: &SelectedNodes,
+) -> Option<ReflectableUuid> {
+    selected_nodes
+        .edited(ui_state)
+        .or_else(|| selected_nodes.primary())
+}
+
+pub fn toggle_inspector(
+    interaction_query: Query<
+        (&Interaction, &Children),
+        (Changed<Interaction>, With<InspectorToggle>),
+    >,
+    mut body_query: Query<&mut Style, With<InspectorBody>>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, children) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        for mut style in body_query.iter_mut() {
+            let collapse = style.display != Display::None;
+            style.display = if collapse {
+                Display::None
+            } else {
+                Display::Flex
+            };
+            for child in children.iter() {
+                if let Ok(mut text) = text_query.get_mut(*child) {
+                    text.sections[0].value =
+                        format!("Properties {}", if collapse { "[+]" } else { "[-]" });
+                }
+            }
+        }
+    }
+}
+
+pub fn inspector_buttons(
+    interaction_query: Query<(&Interaction, &InspectorStep), Changed<Interaction>>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut events: EventWriter<ChangeNodePropertyEvent>,
+) {
+    let Some(id) = inspected_node(&ui_state, &selected_nodes) else {
+        return;
+    };
+    for (interaction, step) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            events.send(ChangeNodePropertyEvent {
+                id,
+                property: step.property,
+                step: step.step,
+            });
+        }
+    }
+}
+
+/// Applies property changes of nodes, locked nodes keep their position and size.
+pub fn apply_node_property_change(
+    mut events: EventReader<ChangeNodePropertyEvent>,
+    mut containers: Query<
+        (
+            &mut NodeRect,
+            &mut NodeZIndex,
+            &VeloNodeContainer,
+            Option<&Locked>,
+            Option<&mut NodeRotation>,
+        ),
+        Without<VeloNode>,
+    >,
+    mut nodes: Query<
+        (
+            &VeloNode,
+            &Parent,
+            &mut TextPos,
+            &mut NodeEffects,
+            Option<&mut NodeTextStyle>,
+        ),
+        Without<VeloNodeContainer>,
+    >,
+    mut raw_text_query: Query<(&RawText, &mut CosmicEditImage), With<RawText>>,
+    mut arrow_events: EventWriter<RedrawArrowEvent>,
+    mut history: ResMut<History>,
+    mut moved: Local<Vec<ReflectableUuid>>,
+) {
+    // arrows are redrawn once the layout of changed nodes is computed
+    for id in moved.drain(..) {
+        arrow_events.send(RedrawArrowEvent { id });
+    }
+    for event in events.iter() {
+        let Some((node, parent, mut text_pos, mut effects, text_style)) = nodes
+            .iter_mut()
+            .find(|(node, _, _, _, _)| node.id == event.id)
+        else {
+            continue;
+        };
+        let Ok((mut rect, mut z_index, container, locked, rotation)) =
+            containers.get_mut(parent.get())
+        else {
+            continue;
+        };
+        let step = event.step;
+        let geometry = matches!(
+            event.property,
+            NodeProperty::X
+                | NodeProperty::Y
+                | NodeProperty::Width
+                | NodeProperty::Height
+                | NodeProperty::Rotation
+        );
+        if geometry && locked.is_some() {
+            continue;
+        }
+        let (position, size) = (rect.position, rect.size);
+        match event.property {
+            NodeProperty::X => rect.position.x += step,
+            NodeProperty::Y => rect.position.y += step,
+            NodeProperty::Width => rect.size.x = (rect.size.x + step).max(MIN_NODE_SIZE),
+            NodeProperty::Height => rect.size.y = (rect.size.y + step).max(MIN_NODE_SIZE),
+            NodeProperty::ZIndex => z_index.0 += event.step as i32,
+            NodeProperty::Rotation => {
+                if let Some(mut rotation) = rotation {
+                    rotation.0 = (rotation.0 + event.step).rem_euclid(360.);
+                }
+            }
+            NodeProperty::Color => {
+                let mut palette = NODE_COLORS.to_vec();
+                if event.step < 0. {
+                    palette.reverse();
+                }
+                effects.color = next_in_cycle(&palette, effects.color);
+            }
+            NodeProperty::TextPos => {
+                *text_pos = match *text_pos {
+                    TextPos::Center => TextPos::TopLeft,
+                    TextPos::TopLeft => TextPos::Center,
+                };
+                for (raw_text, mut cosmic_edit) in raw_text_query.iter_mut() {
+                    if raw_text.id == node.id {
+                        cosmic_edit.text_pos = to_cosmic_text_pos(text_pos.clone());
+                        cosmic_edit.editor.buffer_mut().set_redraw(true);
+                    }
+                }
+            }
+            NodeProperty::NodeType | NodeProperty::Tags => {}
+        }
+        if rect.position != position {
+            history.record(HistoryCommand::NodeMoved {
+                id: container.id.0,
+                from: position,
+                to: rect.position,
+            });
+        }
+        if rect.size != size {
+            history.record(HistoryCommand::NodeResized {
+                id: container.id.0,
+                from: size,
+                to: rect.size,
+            });
+        }
+        if geometry {
+            // text is laid out again in the new size
+            if let Some(mut text_style) = text_style {
+                text_style.set_changed();
+            }
+            moved.push(container.id);
+        }
+    }
+}
+
+/// Shows properties of the inspected node, values follow dragging and resizing.
+pub fn update_inspector(
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    containers: Query<(&NodeRect, &NodeZIndex, Option<&NodeRotation>), With<VeloNodeContainer>>,
+    nodes: Query<
+        (
+            &VeloNode,
+            &Parent,
+            &TextPos,
+            &NodeEffects,
+            Option<&NodeTags>,
+        ),
+        Without<VeloNodeContainer>,
+    >,
+    mut values: Query<(&mut Text, &InspectorValue)>,
+) {
+    let inspected = inspected_node(&ui_state, &selected_nodes).and_then(|id| {
+        let (node, parent, text_pos, effects, tags) =
+            nodes.iter().find(|(node, ..)| node.id == id)?;
+        let (rect, z_index, rotation) = containers.get(parent.get()).ok()?;
+        Some((node, text_pos, effects, tags, rect, z_index, rotation))
+    });
+    for (mut text, value) in values.iter_mut() {
+        // tags field shows the edited text while editing
+        if value.property == NodeProperty::Tags && ui_state.node_tags_to_edit.is_some() {
+            continue;
+        }
+        let new_value = match inspected {
+            // displayed geometry is in world coordinates, the same as in saved documents
+            Some((node, text_pos, effects, tags, rect, z_index, rotation)) => {
+                match value.property {
+                    NodeProperty::X => format!("{:.0}", rect.position.x),
+                    NodeProperty::Y => format!("{:.0}", rect.position.y),
+                    NodeProperty::Width => format!("{:.0}", rect.size.x),
+                    NodeProperty::Height => format!("{:.0}", rect.size.y),
+                    NodeProperty::ZIndex => z_index.0.to_string(),
+                    NodeProperty::Color => {
+                        let [r, g, b, _] = effects.color.as_rgba_u8();
+                        format!("#{:02X}{:02X}{:02X}", r, g, b)
+                    }
+                    NodeProperty::TextPos => match text_pos {
+                        TextPos::Center => "center".to_string(),
+                        TextPos::TopLeft => "top left".to_string(),
+                    },
+                    NodeProperty::NodeType => format!("{:?}", node.node_type),
+                    NodeProperty::Tags => match tags {
+                        Some(tags) if !tags.0.is_empty() => tags.0.join(", "),
+                        _ => "-".to_string(),
+                    },
+                    NodeProperty::Rotation => {
+                        format!("{:.0}°", rotation.map_or(0., |rotation| rotation.0))
+                    }
+                }
+            }
+            None => "-".to_string(),
+        };
+        // text is re-rendered only when the value changes
+        if text.sections[0].value != new_value {
+            text.sections[0].value = new_value;
+        }
+    }
+}
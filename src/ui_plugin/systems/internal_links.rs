@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use uuid::Uuid;
+
+use crate::components::Doc;
+use crate::resources::AppState;
+use crate::utils::ReflectableUuid;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::extract_asset;
+use super::ui_helpers::Localized;
+use super::{
+    checkpoint_texts, parse_asset_link, JumpToNodeEvent, Notification, OpenInternalLinkEvent,
+    VeloNodeContainer,
+};
+
+pub const NODE_LINK_PREFIX: &str = "velo://node/";
+const TAB_LINK_PREFIX: &str = "velo://tab/";
+
+/// Link to a tab of the current document (`[[Tab Name]]`), to a node of any of its tabs
+/// (`velo://node/<uuid>`, copied with "Copy link" of the node context menu) or to a file
+/// embedded in it (`velo://asset/<hash>/<name>`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum InternalLink {
+    Tab(String),
+    Node(ReflectableUuid),
+    Asset { hash: String, name: String },
+}
+
+pub fn parse_internal_link(link: &str) -> Option<InternalLink> {
+    if let Some(name) = link.strip_prefix(TAB_LINK_PREFIX) {
+        return Some(InternalLink::Tab(name.to_string()));
+    }
+    if let Some((hash, name)) = parse_asset_link(link) {
+        return Some(InternalLink::Asset {
+            hash: hash.to_string(),
+            name: name.to_string(),
+        });
+    }
+    let id = link.strip_prefix(NODE_LINK_PREFIX)?;
+    Uuid::parse_str(id.trim_end_matches('/'))
+        .ok()
+        .map(|id| InternalLink::Node(ReflectableUuid(id)))
+}
+
+/// Turns internal links of node text into markdown links, so they are rendered and clickable
+/// like other links: `[[Tab Name]]` becomes `[Tab Name](<velo://tab/Tab Name>)` and a bare
+/// `velo://node/<uuid>` becomes an autolink.
+pub fn internal_links_markdown(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        let tab_name = rest
+            .strip_prefix("[[")
+            .and_then(|rest| rest.split_once("]]"))
+            .map(|(name, _)| name)
+            .filter(|name| !name.trim().is_empty() && !name.contains(['[', ']', '<', '>', '\n']));
+        if let Some(name) = tab_name {
+            result.push_str(&format!("[{}](<{}{}>)", name, TAB_LINK_PREFIX, name));
+            i += name.len() + 4;
+            continue;
+        }
+        // links already written as markdown are left alone
+        if rest.starts_with(NODE_LINK_PREFIX) && !result.ends_with(['(', '<']) {
+            let id = &rest[NODE_LINK_PREFIX.len()..];
+            let id_len = id
+                .find(|c: char| !c.is_ascii_hexdigit() && c != '-')
+                .unwrap_or(id.len());
+            if Uuid::parse_str(&id[..id_len]).is_ok() {
+                let link = &rest[..NODE_LINK_PREFIX.len() + id_len];
+                result.push_str(&format!("<{}>", link));
+                i += link.len();
+                continue;
+            }
+        }
+        result.push(c);
+        i += c.len_utf8();
+    }
+    result
+}
+
+/// Resolves clicked internal links: a tab link switches to the tab with that name, a node link
+/// switches to the tab containing the node and pans the canvas to it, an asset link opens the
+/// embedded file with the default application.
+pub fn follow_internal_links(
+    mut events: EventReader<OpenInternalLinkEvent>,
+    app_state: Res<AppState>,
+    containers: Query<&VeloNodeContainer>,
+    mut jump_events: EventWriter<JumpToNodeEvent>,
+    mut notifications: EventWriter<Notification>,
+) {
+    for event in events.iter() {
+        let Some(doc) = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get(&doc_id))
+        else {
+            continue;
+        };
+        let target = match &event.link {
+            InternalLink::Tab(name) => doc
+                .tabs
+                .iter()
+                .find(|tab| tab.name.trim().eq_ignore_ascii_case(name.trim()))
+                .map(|tab| (tab.id, None)),
+            InternalLink::Node(node_id) => doc
+                .tabs
+                .iter()
+                .find(|tab| {
+                    if tab.is_active {
+                        containers.iter().any(|container| container.id == *node_id)
+                    } else {
+                        // other tabs are searched as of their last checkpoint
+                        tab.checkpoint.as_ref().map_or(false, |checkpoint| {
+                            checkpoint_texts(checkpoint)
+                                .unwrap_or_default()
+                                .iter()
+                                .any(|(id, _)| id == node_id)
+                        })
+                    }
+                })
+                .map(|tab| (tab.id, Some(*node_id))),
+            InternalLink::Asset { hash, name } => {
+                if let Err(notification) = open_asset(doc, hash, name) {
+                    notifications.send(notification);
+                }
+                continue;
+            }
+        };
+        match target {
+            Some((tab_id, node_id)) => jump_events.send(JumpToNodeEvent { tab_id, node_id }),
+            None => {
+                let message = Localized::new("The target of the link wasn't found");
+                notifications
+                    .send(Notification::warn(message).with_details(format!("{:?}", event.link)));
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn open_asset(doc: &Doc, hash: &str, name: &str) -> Result<(), Notification> {
+    let path = extract_asset(doc, hash, name).map_err(|err| {
+        let message = Localized::new("Couldn't extract the embedded file {}").with_args([name]);
+        Notification::warn(message).with_details(err)
+    })?;
+    open::that(&path).map_err(|err| {
+        let message = Localized::new("Couldn't open {}").with_args([path.display().to_string()]);
+        Notification::warn(message).with_details(err)
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn open_asset(_doc: &Doc, _hash: &str, name: &str) -> Result<(), Notification> {
+    let message = Localized::new("Embedded files can't be opened in the browser: {}");
+    Err(Notification::warn(message.with_args([name])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_internal_link() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            parse_internal_link(&format!("velo://node/{}", id)),
+            Some(InternalLink::Node(ReflectableUuid(id)))
+        );
+        assert_eq!(
+            parse_internal_link("velo://tab/Tab 2"),
+            Some(InternalLink::Tab("Tab 2".to_string()))
+        );
+        assert_eq!(
+            parse_internal_link("velo://asset/abc123/notes.txt"),
+            Some(InternalLink::Asset {
+                hash: "abc123".to_string(),
+                name: "notes.txt".to_string()
+            })
+        );
+        assert_eq!(parse_internal_link("velo://node/1234"), None);
+        assert_eq!(parse_internal_link("https://example.com"), None);
+    }
+
+    #[test]
+    fn test_internal_links_markdown() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            internal_links_markdown("see [[Tab 2]] and velo://node/x"),
+            "see [Tab 2](<velo://tab/Tab 2>) and velo://node/x"
+        );
+        assert_eq!(
+            internal_links_markdown(&format!("node velo://node/{}.", id)),
+            format!("node <velo://node/{}>.", id)
+        );
+        let markdown = format!("[node](velo://node/{})", id);
+        assert_eq!(internal_links_markdown(&markdown), markdown);
+        assert_eq!(internal_links_markdown("[[]] [[a]b]]"), "[[]] [[a]b]]");
+    }
+}
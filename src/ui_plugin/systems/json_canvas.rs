@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde_json::json;
+
+use super::color_to_hex;
+use super::export::{node_rect, ExportedTab};
+use super::import::{ImportedEdge, ImportedGraph};
+use crate::canvas::arrow::components::{ArrowConnectPos, ArrowStyle, ArrowType};
+use crate::NodeType;
+
+// Preset colors "1" to "6" of the JSON Canvas spec: red, orange, yellow, green, cyan and purple
+const PRESET_COLORS: [&str; 6] = ["FB464C", "E9973F", "E0DE71", "44CF6E", "53DFDD", "A882FF"];
+const GROUP_COLOR: Color = Color::rgb(245.0 / 255.0, 245.0 / 255.0, 245.0 / 255.0);
+
+fn canvas_color(color: &serde_json::Value) -> Option<Color> {
+    let color = color.as_str()?;
+    match color.strip_prefix('#') {
+        Some(hex) => Color::hex(hex).ok(),
+        None => {
+            let preset = PRESET_COLORS.get(color.parse::<usize>().ok()?.checked_sub(1)?)?;
+            Color::hex(preset).ok()
+        }
+    }
+}
+
+/// Nodes, groups (as frames of the nodes inside of them) and edges of a JSON Canvas file at
+/// their positions. File and link nodes show their path and have it attached.
+pub fn json_canvas(canvas: &str) -> ImportedGraph {
+    let canvas: serde_json::Value = serde_json::from_str(canvas).unwrap_or_default();
+    let mut graph = ImportedGraph::default();
+    let mut ids = HashMap::new();
+    let mut groups = vec![];
+    for node in canvas["nodes"].as_array().into_iter().flatten() {
+        let value = |key: &str| node[key].as_str().unwrap_or_default().to_string();
+        let (text, node_type, attachment) = match node["type"].as_str().unwrap_or_default() {
+            "text" => (value("text"), NodeType::Rect, None),
+            "file" => {
+                let file = value("file");
+                let name = file.rsplit('/').next().unwrap_or_default().to_string();
+                (name, NodeType::Rect, Some(file))
+            }
+            "link" => (value("url"), NodeType::Rect, Some(value("url"))),
+            "group" => (value("label"), NodeType::Frame, None),
+            _ => continue,
+        };
+        let index = graph.add_node(text, node_type.clone());
+        let imported = &mut graph.nodes[index];
+        imported.attachments.extend(attachment);
+        if node_type == NodeType::Frame {
+            imported.bg_color = GROUP_COLOR;
+            groups.push(index);
+        }
+        if let Some(color) = canvas_color(&node["color"]) {
+            imported.bg_color = color;
+        }
+        // json canvas has y axis pointing down
+        let number = |key: &str| node[key].as_f64().unwrap_or_default() as f32;
+        let (x, y) = (number("x"), number("y"));
+        imported.rect = Some(Rect::new(x, -y, x + number("width"), -y - number("height")));
+        ids.insert(value("id"), index);
+    }
+    for group in groups {
+        let Some(group_rect) = graph.nodes[group].rect else {
+            continue;
+        };
+        let members = (0..graph.nodes.len())
+            .filter(|index| {
+                *index != group
+                    && graph.nodes[*index].rect.map_or(false, |rect| {
+                        group_rect.contains(rect.min) && group_rect.contains(rect.max)
+                    })
+            })
+            .collect();
+        graph.frames.push((group, members));
+    }
+    for edge in canvas["edges"].as_array().into_iter().flatten() {
+        let (Some(from), Some(to)) = (
+            edge["fromNode"].as_str().and_then(|id| ids.get(id)),
+            edge["toNode"].as_str().and_then(|id| ids.get(id)),
+        ) else {
+            continue;
+        };
+        let from_arrow = edge["fromEnd"].as_str() == Some("arrow");
+        let to_arrow = edge["toEnd"].as_str() != Some("none");
+        let (start, end) = if from_arrow && !to_arrow {
+            (*to, *from)
+        } else {
+            (*from, *to)
+        };
+        let arrow_type = match (from_arrow, to_arrow) {
+            (true, true) => ArrowType::DoubleArrow,
+            (false, false) => ArrowType::Line,
+            _ => ArrowType::Arrow,
+        };
+        let mut style = ArrowStyle::for_type(arrow_type);
+        if let Some(color) = canvas_color(&edge["color"]) {
+            style.color = color;
+        }
+        graph.edges.push(ImportedEdge {
+            start,
+            end,
+            arrow_type,
+            style,
+            label: edge["label"]
+                .as_str()
+                .filter(|label| !label.trim().is_empty())
+                .map(|label| label.to_string()),
+        });
+    }
+    graph
+}
+
+fn side(pos: ArrowConnectPos) -> &'static str {
+    match pos {
+        ArrowConnectPos::Top => "top",
+        ArrowConnectPos::Bottom => "bottom",
+        ArrowConnectPos::Left => "left",
+        ArrowConnectPos::Right => "right",
+    }
+}
+
+/// JSON Canvas of the tab: frames become groups, other nodes text nodes (or file and link nodes
+/// for nodes showing just their attachment) and arrows edges with their label or relationship.
+/// Images are skipped.
+pub fn tab_to_json_canvas(tab: &ExportedTab) -> String {
+    let nodes: Vec<_> = tab
+        .nodes
+        .iter()
+        .filter(|node| node.node_type != NodeType::Image)
+        .map(|node| {
+            let rect = node_rect(node);
+            let mut canvas_node = json!({
+                "id": node.id.to_string(),
+                "x": rect.min.x.round() as i32,
+                // json canvas has y axis pointing down
+                "y": (-rect.max.y).round() as i32,
+                "width": rect.width().round() as i32,
+                "height": rect.height().round() as i32,
+            });
+            let text = node.text.text.trim();
+            let attachment = node.attachments.first().filter(|attachment| {
+                node.attachments.len() == 1
+                    && (text.is_empty()
+                        || text == attachment.as_str()
+                        || attachment.rsplit('/').next() == Some(text))
+            });
+            match (&node.node_type, attachment) {
+                (NodeType::Frame, _) => {
+                    canvas_node["type"] = json!("group");
+                    if !text.is_empty() {
+                        canvas_node["label"] = json!(text);
+                    }
+                }
+                (_, Some(url)) if url.contains("://") => {
+                    canvas_node["type"] = json!("link");
+                    canvas_node["url"] = json!(url);
+                }
+                (_, Some(file)) => {
+                    canvas_node["type"] = json!("file");
+                    canvas_node["file"] = json!(file);
+                }
+                (_, None) => {
+                    canvas_node["type"] = json!("text");
+                    canvas_node["text"] = json!(text);
+                }
+            }
+            if node.bg_color != Color::WHITE {
+                canvas_node["color"] = json!(color_to_hex(node.bg_color));
+            }
+            canvas_node
+        })
+        .collect();
+    let edges: Vec<_> = tab
+        .arrows
+        .iter()
+        .enumerate()
+        .filter(|(_, arrow)| {
+            [&arrow.meta.start, &arrow.meta.end].iter().all(|connect| {
+                tab.node(connect)
+                    .map_or(false, |node| node.node_type != NodeType::Image)
+            })
+        })
+        .map(|(i, arrow)| {
+            let (from_end, to_end) = match arrow.meta.arrow_type {
+                ArrowType::Line | ArrowType::ParallelLine => ("none", "none"),
+                ArrowType::Arrow | ArrowType::ParallelArrow => ("none", "arrow"),
+                ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => ("arrow", "arrow"),
+            };
+            let mut edge = json!({
+                "id": format!("edge-{}", i + 1),
+                "fromNode": arrow.meta.start.id.0.to_string(),
+                "fromSide": side(arrow.meta.start.pos),
+                "fromEnd": from_end,
+                "toNode": arrow.meta.end.id.0.to_string(),
+                "toSide": side(arrow.meta.end.pos),
+                "toEnd": to_end,
+                "color": color_to_hex(arrow.style.color),
+            });
+            let text = arrow
+                .label
+                .as_deref()
+                .filter(|label| !label.trim().is_empty())
+                .or(arrow.relation.as_deref());
+            if let Some(text) = text {
+                edge["label"] = json!(text);
+            }
+            edge
+        })
+        .collect();
+    serde_json::to_string_pretty(&json!({ "nodes": nodes, "edges": edges })).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_canvas() {
+        let graph = json_canvas(
+            r##"{
+                "nodes": [
+                    {"id": "g", "type": "group", "x": 0, "y": 0, "width": 500, "height": 300,
+                     "label": "Sprint"},
+                    {"id": "a", "type": "text", "x": 20, "y": 40, "width": 200, "height": 60,
+                     "text": "# Task", "color": "1"},
+                    {"id": "f", "type": "file", "x": 600, "y": 40, "width": 200, "height": 60,
+                     "file": "notes/spec.md"},
+                    {"id": "l", "type": "link", "x": 20, "y": 150, "width": 200, "height": 60,
+                     "url": "https://example.com"}
+                ],
+                "edges": [
+                    {"id": "e1", "fromNode": "a", "toNode": "f", "label": "see"},
+                    {"id": "e2", "fromNode": "l", "fromEnd": "arrow", "toNode": "a",
+                     "toEnd": "none", "color": "#00ff00"},
+                    {"id": "e3", "fromNode": "a", "toNode": "missing"}
+                ]
+            }"##,
+        );
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.text.as_str(),
+                    node.node_type.clone(),
+                    node.attachments.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("Sprint", NodeType::Frame, vec![]),
+                ("# Task", NodeType::Rect, vec![]),
+                ("spec.md", NodeType::Rect, vec!["notes/spec.md".to_string()]),
+                (
+                    "https://example.com",
+                    NodeType::Rect,
+                    vec!["https://example.com".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(
+            graph.nodes[1].bg_color,
+            Color::hex(PRESET_COLORS[0]).unwrap()
+        );
+        assert_eq!(graph.nodes[1].rect, Some(Rect::new(20., -40., 220., -100.)));
+        assert_eq!(graph.frames, vec![(0, vec![1, 3])]);
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.start, edge.end, edge.arrow_type, edge.label.as_deref()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (1, 2, ArrowType::Arrow, Some("see")),
+                (1, 3, ArrowType::Arrow, None),
+            ]
+        );
+        assert_eq!(graph.edges[1].style.color, Color::GREEN);
+    }
+}
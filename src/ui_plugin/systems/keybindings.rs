@@ -0,0 +1,740 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_ui_borders::BorderColor;
+use serde::{Deserialize, Serialize};
+
+use super::ui_helpers::{
+    KeybindingButton, KeybindingsButton, KeybindingsPanel, Localized, MainPanel, ResetKeybindings,
+    ThemedBackground, ThemedBorder, ThemedText,
+};
+use super::{Locale, Settings, Theme, ThemeColor};
+
+/// Commands of the canvas which can be bound to a key chord, in the order of the shortcuts panel.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Action {
+    NewNode,
+    NewTab,
+    SaveDocument,
+    SaveTab,
+    Undo,
+    Redo,
+    Copy,
+    Paste,
+    Duplicate,
+    Delete,
+    Find,
+    Lock,
+    Group,
+    Ungroup,
+    BringForward,
+    SendBackward,
+    BringToFront,
+    SendToBack,
+    StickyNote,
+    Frame,
+    SelectTool,
+    PanTool,
+    /// Kept the name of the rectangle tool it replaced, saved settings bind it by name
+    RectTool,
+    ArrowTool,
+    PenTool,
+    EraserTool,
+    CommandPalette,
+}
+
+impl Action {
+    pub const ALL: [Action; 27] = [
+        Action::NewNode,
+        Action::NewTab,
+        Action::SaveDocument,
+        Action::SaveTab,
+        Action::Undo,
+        Action::Redo,
+        Action::Copy,
+        Action::Paste,
+        Action::Duplicate,
+        Action::Delete,
+        Action::Find,
+        Action::Lock,
+        Action::Group,
+        Action::Ungroup,
+        Action::BringForward,
+        Action::SendBackward,
+        Action::BringToFront,
+        Action::SendToBack,
+        Action::StickyNote,
+        Action::Frame,
+        Action::SelectTool,
+        Action::PanTool,
+        Action::RectTool,
+        Action::ArrowTool,
+        Action::PenTool,
+        Action::EraserTool,
+        Action::CommandPalette,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::NewNode => "New node",
+            Action::NewTab => "New tab",
+            Action::SaveDocument => "Save document",
+            Action::SaveTab => "Save tab",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Copy => "Copy",
+            Action::Paste => "Paste",
+            Action::Duplicate => "Duplicate",
+            Action::Delete => "Delete",
+            Action::Find => "Find",
+            Action::Lock => "Lock/Unlock",
+            Action::Group => "Group",
+            Action::Ungroup => "Ungroup",
+            Action::BringForward => "Bring forward",
+            Action::SendBackward => "Send backward",
+            Action::BringToFront => "Bring to front",
+            Action::SendToBack => "Send to back",
+            Action::StickyNote => "Sticky note",
+            Action::Frame => "Frame",
+            Action::SelectTool => "Select tool",
+            Action::PanTool => "Pan tool",
+            Action::RectTool => "Node tool",
+            Action::ArrowTool => "Arrow tool",
+            Action::PenTool => "Pen tool",
+            Action::EraserTool => "Eraser tool",
+            Action::CommandPalette => "Command palette",
+        }
+    }
+
+    fn default_chords(self) -> &'static [&'static str] {
+        match self {
+            Action::NewNode => &["Alt+N"],
+            Action::NewTab => &["Alt+T"],
+            Action::SaveDocument => &["Ctrl+Shift+S"],
+            Action::SaveTab => &["Ctrl+S"],
+            Action::Undo => &["Ctrl+Z"],
+            Action::Redo => &["Ctrl+Shift+Z", "Ctrl+Y"],
+            Action::Copy => &["Ctrl+C"],
+            Action::Paste => &["Ctrl+V"],
+            Action::Duplicate => &["Ctrl+D"],
+            Action::Delete => &["Delete", "Backspace"],
+            Action::Find => &["Ctrl+F"],
+            Action::Lock => &["Ctrl+Shift+L"],
+            Action::Group => &["Ctrl+G"],
+            Action::Ungroup => &["Ctrl+Shift+G"],
+            Action::BringForward => &["Ctrl+]"],
+            Action::SendBackward => &["Ctrl+["],
+            Action::BringToFront => &["Ctrl+Shift+]"],
+            Action::SendToBack => &["Ctrl+Shift+["],
+            Action::StickyNote => &["Shift+N"],
+            Action::Frame => &["F"],
+            Action::SelectTool => &["V"],
+            Action::PanTool => &["H"],
+            Action::RectTool => &["N", "R"],
+            Action::ArrowTool => &["A"],
+            Action::PenTool => &["P"],
+            Action::EraserTool => &["E"],
+            Action::CommandPalette => &["Ctrl+Shift+P"],
+        }
+    }
+}
+
+/// Keys a chord can end with and their names in the settings.
+const KEY_NAMES: [(KeyCode, &str); 74] = [
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Back, "Backspace"),
+    (KeyCode::Return, "Enter"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::BracketLeft, "["),
+    (KeyCode::BracketRight, "]"),
+    (KeyCode::Minus, "-"),
+    (KeyCode::Equals, "="),
+    (KeyCode::Comma, ","),
+    (KeyCode::Period, "."),
+    (KeyCode::Slash, "/"),
+    (KeyCode::Backslash, "\\"),
+    (KeyCode::Semicolon, ";"),
+    (KeyCode::Apostrophe, "'"),
+    (KeyCode::Grave, "`"),
+];
+
+/// Key with the modifiers held down, written like `Ctrl+Shift+S`. Ctrl stands for Cmd as well,
+/// the shortcuts are the same on every platform.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub command: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        // `Ctrl++` isn't supported, there is no key named "+"
+        let name = parts.pop().unwrap_or_default();
+        let key = KEY_NAMES
+            .iter()
+            .find(|(_, key_name)| key_name.eq_ignore_ascii_case(name))
+            .map(|(key, _)| *key)
+            .ok_or_else(|| format!("unknown key {:?}", name))?;
+        let mut chord = Self {
+            key,
+            command: false,
+            shift: false,
+            alt: false,
+        };
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" => chord.command = true,
+                "shift" => chord.shift = true,
+                "alt" | "option" => chord.alt = true,
+                _ => return Err(format!("unknown modifier {:?}", modifier)),
+            }
+        }
+        Ok(chord)
+    }
+
+    /// Chord of a key pressed in this frame with the modifiers held down.
+    pub fn from_input(input: &Input<KeyCode>) -> Option<Self> {
+        let key = input
+            .get_just_pressed()
+            .find(|key| KEY_NAMES.iter().any(|(named, _)| named == *key))?;
+        let (command, shift, alt) = modifiers(input);
+        Some(Self {
+            key: *key,
+            command,
+            shift,
+            alt,
+        })
+    }
+
+    /// The key was pressed in this frame with exactly the modifiers of the chord.
+    pub fn just_pressed(&self, input: &Input<KeyCode>) -> bool {
+        modifiers(input) == (self.command, self.shift, self.alt) && input.just_pressed(self.key)
+    }
+}
+
+fn modifiers(input: &Input<KeyCode>) -> (bool, bool, bool) {
+    (
+        input.any_pressed([
+            KeyCode::RWin,
+            KeyCode::LWin,
+            KeyCode::RControl,
+            KeyCode::LControl,
+        ]),
+        input.any_pressed([KeyCode::RShift, KeyCode::LShift]),
+        input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]),
+    )
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        let name = KEY_NAMES
+            .iter()
+            .find(|(key, _)| *key == self.key)
+            .map_or("?", |(_, name)| name);
+        write!(f, "{}", name)
+    }
+}
+
+impl TryFrom<String> for KeyChord {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::parse(&text)
+    }
+}
+
+impl From<KeyChord> for String {
+    fn from(chord: KeyChord) -> Self {
+        chord.to_string()
+    }
+}
+
+/// Chords of every action, actions missing in saved settings get their default chords.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(
+    from = "BTreeMap<Action, Vec<KeyChord>>",
+    into = "BTreeMap<Action, Vec<KeyChord>>"
+)]
+pub struct Keybindings(BTreeMap<Action, Vec<KeyChord>>);
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        let chords = |action: Action| {
+            action
+                .default_chords()
+                .iter()
+                .map(|chord| KeyChord::parse(chord).unwrap())
+                .collect()
+        };
+        Self(
+            Action::ALL
+                .into_iter()
+                .map(|action| (action, chords(action)))
+                .collect(),
+        )
+    }
+}
+
+impl From<BTreeMap<Action, Vec<KeyChord>>> for Keybindings {
+    fn from(saved: BTreeMap<Action, Vec<KeyChord>>) -> Self {
+        let mut keybindings = Self::default();
+        keybindings.0.extend(saved);
+        keybindings
+    }
+}
+
+impl From<Keybindings> for BTreeMap<Action, Vec<KeyChord>> {
+    fn from(keybindings: Keybindings) -> Self {
+        keybindings.0
+    }
+}
+
+impl Keybindings {
+    pub fn chords(&self, action: Action) -> &[KeyChord] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces the chords of the action by `chord`.
+    pub fn set(&mut self, action: Action, chord: KeyChord) {
+        self.0.insert(action, vec![chord]);
+    }
+
+    /// Another action the chord is bound to.
+    pub fn conflict(&self, action: Action, chord: KeyChord) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(other, chords)| **other != action && chords.contains(&chord))
+            .map(|(other, _)| *other)
+    }
+
+    /// Chords bound to more than one action, only the first of them runs.
+    pub fn conflicts(&self) -> Vec<(KeyChord, Action, Action)> {
+        let mut conflicts = vec![];
+        for (action, chords) in self.0.iter() {
+            for chord in chords {
+                if let Some(other) = self.conflict(*action, *chord) {
+                    if other > *action {
+                        conflicts.push((*chord, *action, other));
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn just_pressed(&self, action: Action, input: &Input<KeyCode>) -> bool {
+        self.chords(action)
+            .iter()
+            .any(|chord| chord.just_pressed(input))
+    }
+
+    /// Chords of the action as shown in the shortcuts panel.
+    pub fn describe(&self, action: Action) -> String {
+        let chords: Vec<String> = self
+            .chords(action)
+            .iter()
+            .map(KeyChord::to_string)
+            .collect();
+        chords.join(", ")
+    }
+}
+
+/// Action of the shortcuts panel waiting for a chord, and the chord last rejected because
+/// another action has it.
+#[derive(Resource, Default)]
+pub struct KeybindingRecorder {
+    pub action: Option<Action>,
+    conflict: Option<(KeyChord, Action)>,
+}
+
+fn spawn_text(
+    builder: &mut ChildBuilder,
+    localized: Localized,
+    color: Option<Color>,
+    theme: &Theme,
+) {
+    let mut text = builder.spawn((
+        TextBundle::from_section(
+            localized.text.clone(),
+            TextStyle {
+                font_size: 14.,
+                color: color.unwrap_or(theme.text),
+                ..default()
+            },
+        ),
+        localized,
+    ));
+    // error messages keep their color
+    if color.is_none() {
+        text.insert(ThemedText(ThemeColor::Text));
+    }
+}
+
+/// Panel next to the settings panel with the chords of every action, a chord is changed by
+/// clicking it and pressing the new one.
+fn spawn_keybindings_panel(
+    commands: &mut Commands,
+    main_panel: Entity,
+    settings: &Settings,
+    recorder: &KeybindingRecorder,
+    theme: &Theme,
+    locale: &Locale,
+) {
+    let panel = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(320.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(320.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: theme.surface.into(),
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            BorderColor(theme.border),
+            ThemedBackground(ThemeColor::Surface),
+            ThemedBorder(ThemeColor::Border),
+            KeybindingsPanel,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    "Keyboard Shortcuts",
+                    TextStyle {
+                        font_size: 18.,
+                        color: theme.text,
+                        ..default()
+                    },
+                ),
+                ThemedText(ThemeColor::Text),
+                Localized::new("Keyboard Shortcuts"),
+            ));
+            for action in Action::ALL {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::top(Val::Px(2.)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|builder| {
+                        spawn_text(builder, Localized::new(action.label()), None, theme);
+                        let recording = recorder.action == Some(action);
+                        builder
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Auto, Val::Px(22.)),
+                                        align_items: AlignItems::Center,
+                                        padding: UiRect::horizontal(Val::Px(6.)),
+                                        border: UiRect::all(Val::Px(1.)),
+                                        ..default()
+                                    },
+                                    background_color: if recording {
+                                        theme.selection.with_a(0.3).into()
+                                    } else {
+                                        theme.surface.into()
+                                    },
+                                    ..default()
+                                },
+                                BorderColor(if recording {
+                                    theme.selection
+                                } else {
+                                    theme.border
+                                }),
+                                KeybindingButton(action),
+                            ))
+                            .with_children(|builder| {
+                                let label = if recording {
+                                    Localized::new("Press keys…")
+                                } else {
+                                    Localized::new("{}")
+                                        .with_args([settings.keybindings.describe(action)])
+                                };
+                                spawn_text(builder, label, None, theme);
+                            });
+                    });
+            }
+            if let Some((chord, other)) = recorder.conflict {
+                let message = Localized::new("{} is already used by \"{}\"").with_args([
+                    chord.to_string(),
+                    locale.translate(other.label()).to_string(),
+                ]);
+                spawn_text(builder, message, Some(Color::rgb(0.8, 0.1, 0.1)), theme);
+            }
+            builder
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Auto, Val::Px(24.)),
+                            align_self: AlignSelf::FlexEnd,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::horizontal(Val::Px(6.)),
+                            margin: UiRect::top(Val::Px(6.)),
+                            border: UiRect::all(Val::Px(1.)),
+                            ..default()
+                        },
+                        background_color: theme.surface.into(),
+                        ..default()
+                    },
+                    BorderColor(theme.border),
+                    ThemedBackground(ThemeColor::Surface),
+                    ThemedBorder(ThemeColor::Border),
+                    ResetKeybindings,
+                ))
+                .with_children(|builder| {
+                    spawn_text(builder, Localized::new("Reset"), None, theme);
+                });
+        })
+        .id();
+    commands.entity(main_panel).add_child(panel);
+}
+
+/// Opens or closes the shortcuts panel.
+pub fn toggle_keybindings_panel(
+    mut commands: Commands,
+    interactions: Query<&Interaction, (Changed<Interaction>, With<KeybindingsButton>)>,
+    panel: Query<Entity, With<KeybindingsPanel>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    mut recorder: ResMut<KeybindingRecorder>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    locale: Res<Locale>,
+) {
+    if !interactions
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    *recorder = KeybindingRecorder::default();
+    if let Ok(entity) = panel.get_single() {
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    spawn_keybindings_panel(
+        &mut commands,
+        main_panel,
+        &settings,
+        &recorder,
+        &theme,
+        &locale,
+    );
+}
+
+/// Starts recording the chord of the clicked action or resets all of them, the open panel is
+/// redrawn when the keybindings or the theme change.
+pub fn keybindings_panel_click(
+    mut commands: Commands,
+    action_buttons: Query<(&Interaction, &KeybindingButton), Changed<Interaction>>,
+    reset_buttons: Query<&Interaction, (Changed<Interaction>, With<ResetKeybindings>)>,
+    panel: Query<Entity, With<KeybindingsPanel>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    mut recorder: ResMut<KeybindingRecorder>,
+    mut settings: ResMut<Settings>,
+    theme: Res<Theme>,
+    locale: Res<Locale>,
+) {
+    for (interaction, button) in action_buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            recorder.action = Some(button.0);
+            recorder.conflict = None;
+        }
+    }
+    if reset_buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        *recorder = KeybindingRecorder::default();
+        settings.keybindings = Keybindings::default();
+    }
+    if !settings.is_changed() && !recorder.is_changed() && !theme.is_changed() {
+        return;
+    }
+    let (Ok(entity), Ok(main_panel)) = (panel.get_single(), main_panel_query.get_single()) else {
+        return;
+    };
+    commands.entity(entity).despawn_recursive();
+    spawn_keybindings_panel(
+        &mut commands,
+        main_panel,
+        &settings,
+        &recorder,
+        &theme,
+        &locale,
+    );
+}
+
+/// Binds the next chord pressed to the recorded action, Escape cancels. The key press is
+/// consumed so the shortcut it had doesn't run as well.
+pub fn record_keybinding(
+    mut input: ResMut<Input<KeyCode>>,
+    mut recorder: ResMut<KeybindingRecorder>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(action) = recorder.action else {
+        return;
+    };
+    let Some(chord) = KeyChord::from_input(&input) else {
+        return;
+    };
+    input.clear_just_pressed(chord.key);
+    if chord.key == KeyCode::Escape {
+        *recorder = KeybindingRecorder::default();
+    } else if let Some(other) = settings.keybindings.conflict(action, chord) {
+        recorder.conflict = Some((chord, other));
+    } else {
+        settings.keybindings.set(action, chord);
+        *recorder = KeybindingRecorder::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_chord() {
+        let chord = KeyChord::parse("ctrl+shift+s").unwrap();
+        assert_eq!(
+            chord,
+            KeyChord {
+                key: KeyCode::S,
+                command: true,
+                shift: true,
+                alt: false,
+            }
+        );
+        assert_eq!(chord.to_string(), "Ctrl+Shift+S");
+        assert_eq!(KeyChord::parse("Cmd+]").unwrap().key, KeyCode::BracketRight);
+        assert_eq!(
+            KeyChord::parse("Backspace").unwrap().to_string(),
+            "Backspace"
+        );
+        assert!(KeyChord::parse("Ctrl+Hyper").is_err());
+        assert!(KeyChord::parse("Meta+S").is_err());
+
+        let mut input = Input::<KeyCode>::default();
+        input.press(KeyCode::LControl);
+        input.press(KeyCode::S);
+        assert!(KeyChord::parse("Ctrl+S").unwrap().just_pressed(&input));
+        assert!(!KeyChord::parse("Ctrl+Shift+S")
+            .unwrap()
+            .just_pressed(&input));
+        assert_eq!(
+            KeyChord::from_input(&input),
+            Some(KeyChord::parse("Ctrl+S").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_keybindings() {
+        let mut keybindings = Keybindings::default();
+        assert!(keybindings.conflicts().is_empty());
+        assert_eq!(keybindings.describe(Action::Redo), "Ctrl+Shift+Z, Ctrl+Y");
+        let undo = KeyChord::parse("Ctrl+Z").unwrap();
+        assert_eq!(keybindings.conflict(Action::Redo, undo), Some(Action::Undo));
+        assert_eq!(keybindings.conflict(Action::Undo, undo), None);
+        keybindings.set(Action::Redo, undo);
+        assert_eq!(
+            keybindings.conflicts(),
+            vec![(undo, Action::Undo, Action::Redo)]
+        );
+
+        // saved bindings keep the defaults of the actions they miss
+        let saved: Keybindings = serde_json::from_str(r#"{ "Undo": ["Alt+U"] }"#).unwrap();
+        assert_eq!(saved.describe(Action::Undo), "Alt+U");
+        assert_eq!(saved.describe(Action::SaveTab), "Ctrl+S");
+        let json = serde_json::to_string(&saved).unwrap();
+        assert_eq!(serde_json::from_str::<Keybindings>(&json).unwrap(), saved);
+        assert!(serde_json::from_str::<Keybindings>(r#"{ "Undo": ["Hyper+U"] }"#).is_err());
+    }
+}
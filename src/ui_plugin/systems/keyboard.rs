@@ -1,21 +1,16 @@
-use bevy::{
-    prelude::*,
-    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    window::PrimaryWindow,
-};
+use bevy::{prelude::*, window::PrimaryWindow};
 
 #[cfg(not(target_arch = "wasm32"))]
 use image::*;
 
 use std::convert::TryInto;
-use uuid::Uuid;
 
-use crate::{
-    resources::{LoadTabRequest, SaveTabRequest},
-    AddRectEvent, BlinkTimer, UiState,
-};
+use crate::{resources::SaveTabRequest, AddRectEvent, BlinkTimer, UiState};
 
 use super::ui_helpers::{get_sections, EditableText};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{add_image_node, image_from_rgba, notify, ui_helpers::Localized, Notification};
+use super::{Action, SelectedNodes, Settings};
 use crate::resources::{AppState, SaveDocRequest};
 
 pub fn keyboard_input_system(
@@ -23,6 +18,7 @@ pub fn keyboard_input_system(
     mut images: ResMut<Assets<Image>>,
     mut app_state: ResMut<AppState>,
     mut ui_state: ResMut<UiState>,
+    selected_nodes: Res<SelectedNodes>,
     mut char_evr: EventReader<ReceivedCharacter>,
     mut events: EventWriter<AddRectEvent>,
     input: Res<Input<KeyCode>>,
@@ -31,27 +27,33 @@ pub fn keyboard_input_system(
     mut editable_text_query: Query<(&mut Text, &EditableText), With<EditableText>>,
     mut blink_timer: ResMut<BlinkTimer>,
     time: Res<Time>,
+    settings: Res<Settings>,
 ) {
     let primary_window = windows.single();
-    let scale_factor = primary_window.scale_factor();
-    let command = input.any_pressed([KeyCode::RWin, KeyCode::LWin]);
-    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    let paste = settings.keybindings.just_pressed(Action::Paste, &input);
     blink_timer.timer.tick(time.delta());
-    if command && input.just_pressed(KeyCode::V) {
+    if paste {
         #[cfg(not(target_arch = "wasm32"))]
-        insert_from_clipboard(
+        if let Err(e) = insert_from_clipboard(
             &mut images,
             &mut ui_state,
+            &selected_nodes,
             &mut editable_text_query,
             &mut events,
-            scale_factor,
-        );
-    } else if command && shift && input.just_pressed(KeyCode::S) {
+            primary_window,
+        ) {
+            let message = Localized::new("Couldn't paste from the clipboard");
+            notify(&mut commands, Notification::error(message).with_details(e));
+        }
+    } else if settings
+        .keybindings
+        .just_pressed(Action::SaveDocument, &input)
+    {
         commands.insert_resource(SaveDocRequest {
             doc_id: app_state.current_document.unwrap(),
             path: None,
         });
-    } else if command && input.just_pressed(KeyCode::S) {
+    } else if settings.keybindings.just_pressed(Action::SaveTab, &input) {
         if let Some(current_doc) = app_state.docs.get(&app_state.current_document.unwrap()) {
             if let Some(active_tab) = current_doc.tabs.iter().find(|t| t.is_active) {
                 commands.insert_resource(SaveTabRequest {
@@ -60,16 +62,6 @@ pub fn keyboard_input_system(
                 });
             }
         }
-    } else if command && input.just_pressed(KeyCode::L) {
-        if let Some(current_doc) = app_state.docs.get(&app_state.current_document.unwrap()) {
-            if let Some(active_tab) = current_doc.tabs.iter().find(|t| t.is_active) {
-                commands.insert_resource(LoadTabRequest {
-                    doc_id: app_state.current_document.unwrap(),
-                    tab_id: active_tab.id,
-                    drop_last_checkpoint: true,
-                });
-            }
-        }
     } else {
         if ui_state.doc_to_edit.is_some() || ui_state.tab_to_edit.is_some() {
             blink_timer.timer.unpause();
@@ -105,7 +97,13 @@ pub fn keyboard_input_system(
                 if str != current_str {
                     text.sections = get_sections(str.clone()).0;
                 }
-                if blink_timer.timer.finished() {
+                // the caret doesn't blink with reduced motion
+                if settings.reduced_motion {
+                    let caret = &mut text.sections.last_mut().unwrap().value;
+                    if caret != "|" {
+                        *caret = "|".to_string();
+                    }
+                } else if blink_timer.timer.finished() {
                     text.sections.last_mut().unwrap().value =
                         if text.sections.last().unwrap().value == "|" {
                             " ".to_string()
@@ -130,6 +128,7 @@ pub fn keyboard_input_system(
             }
         }
     }
+    Ok(())
 }
 
 fn get_text_val(
@@ -159,75 +158,30 @@ fn get_text_val(
 pub fn insert_from_clipboard(
     images: &mut ResMut<Assets<Image>>,
     state: &mut ResMut<UiState>,
+    selected_nodes: &SelectedNodes,
     query: &mut Query<(&mut Text, &EditableText), With<EditableText>>,
     events: &mut EventWriter<AddRectEvent>,
-    scale_factor: f64,
-) {
-    use crate::JsonNode;
-
-    let mut clipboard = arboard::Clipboard::new().unwrap();
-    if let Ok(image) = clipboard.get_image() {
-        let image: RgbaImage = ImageBuffer::from_raw(
-            image.width.try_into().unwrap(),
-            image.height.try_into().unwrap(),
-            image.bytes.into_owned(),
-        )
-        .unwrap();
-        let size: Extent3d = Extent3d {
-            width: image.width(),
-            height: image.height(),
-            ..Default::default()
-        };
-        let resize_width = image.width() / scale_factor as u32;
-        let resize_height = image.height() / scale_factor as u32;
-
-        let image = Image::new(
-            size,
-            TextureDimension::D2,
-            image.to_vec(),
-            TextureFormat::Rgba8UnormSrgb,
-        );
-        let image_buffer = imageops::resize(
-            &image.try_into_dynamic().unwrap(),
-            resize_width,
-            resize_height,
-            imageops::FilterType::Lanczos3,
-        );
-        let data = image_buffer.to_vec();
-        let resize_size: Extent3d = Extent3d {
-            width: resize_width,
-            height: resize_height,
-            ..Default::default()
-        };
-        let resized_image = Image::new(
-            resize_size,
-            TextureDimension::D2,
-            data,
-            TextureFormat::Rgba8UnormSrgb,
-        );
-        let image = images.add(resized_image);
-        events.send(AddRectEvent {
-            node: JsonNode {
-                id: Uuid::new_v4(),
-                node_type: crate::NodeType::Rect,
-                left: Val::Px(0.0),
-                bottom: Val::Px(0.0),
-                width: Val::Px(resize_width as f32),
-                height: Val::Px(resize_height as f32),
-                text: crate::JsonNodeText {
-                    text: "".to_string(),
-                    pos: crate::TextPos::Center,
-                },
-                bg_color: Color::WHITE,
-                z_index: 0,
-            },
-            image: Some(image.into()),
-        });
+    window: &Window,
+) -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    if state.is_canvas_focused() {
+        if let Ok(image) = clipboard.get_image() {
+            let (Ok(width), Ok(height)) = (image.width.try_into(), image.height.try_into()) else {
+                return Err(format!(
+                    "image too large ({}x{})",
+                    image.width, image.height
+                ));
+            };
+            let image: RgbaImage = ImageBuffer::from_raw(width, height, image.bytes.into_owned())
+                .ok_or("image data doesn't match its size")?;
+            let center = Vec2::new(window.width() / 2. - 200., window.height() / 2.);
+            add_image_node(image_from_rgba(image), center, images, events);
+        }
     }
 
     if let Ok(clipboard_text) = clipboard.get_text() {
         for (mut text, editable_text) in &mut query.iter_mut() {
-            if Some(editable_text.id) == state.entity_to_edit {
+            if Some(editable_text.id) == selected_nodes.edited(state) {
                 let mut str = "".to_string();
                 let mut text_copy = text.clone();
                 text_copy.sections.pop();
@@ -239,4 +193,5 @@ pub fn insert_from_clipboard(
             }
         }
     }
+    Ok(())
 }
@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+
+use crate::utils::ReflectableUuid;
+
+use super::{
+    ui_helpers::{NodeRect, NodeZIndex},
+    with_group_members, Locked, NodeGroup, SelectedNodes, UiState, VeloNodeContainer,
+};
+
+/// Node whose center is closest to `from` in `direction` (unit vector, y axis pointing up).
+/// Nodes off to the side count as farther away than nodes straight ahead.
+fn nearest_in_direction(
+    from: Vec2,
+    direction: Vec2,
+    centers: &[(ReflectableUuid, Vec2)],
+) -> Option<ReflectableUuid> {
+    centers
+        .iter()
+        .filter_map(|(id, center)| {
+            let offset = *center - from;
+            let ahead = offset.dot(direction);
+            if ahead <= 0. {
+                return None;
+            }
+            let aside = (offset - ahead * direction).length();
+            Some((ahead + 2. * aside, *id))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, id)| id)
+}
+
+/// Tab/Shift+Tab selects the next/previous node by z-order, Alt+arrow keys select the nearest
+/// node in that direction and Enter starts editing the selected node.
+pub fn keyboard_navigation(
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    containers: Query<(&NodeRect, &NodeZIndex, &VeloNodeContainer, Option<&Locked>)>,
+    groups_query: Query<(&VeloNodeContainer, &NodeGroup)>,
+    mut was_focused: Local<bool>,
+) {
+    // keys ending text input (e.g. Enter) aren't handled in the same frame
+    let focused = ui_state.is_canvas_focused();
+    if !std::mem::replace(&mut *was_focused, focused) || !focused {
+        return;
+    }
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    let alt = input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    let current = selected_nodes.0.first().copied();
+    if input.just_pressed(KeyCode::Return) {
+        let Some(id) = current else {
+            return;
+        };
+        // locked nodes can't be edited
+        if containers
+            .iter()
+            .any(|(_, _, container, locked)| container.id == id && locked.is_some())
+        {
+            return;
+        }
+        // the selection is kept, the node edited is its first one
+        *ui_state = UiState::default();
+        ui_state.editing_node = true;
+        return;
+    }
+    let direction = if !alt || shift {
+        // Alt+Shift+arrow keys align nodes
+        None
+    } else if input.just_pressed(KeyCode::Left) {
+        Some(Vec2::NEG_X)
+    } else if input.just_pressed(KeyCode::Right) {
+        Some(Vec2::X)
+    } else if input.just_pressed(KeyCode::Up) {
+        Some(Vec2::Y)
+    } else if input.just_pressed(KeyCode::Down) {
+        Some(Vec2::NEG_Y)
+    } else {
+        None
+    };
+    let tab = input.just_pressed(KeyCode::Tab);
+    if !tab && direction.is_none() {
+        return;
+    }
+    let mut nodes: Vec<_> = containers
+        .iter()
+        .map(|(rect, z_index, container, _)| (z_index.0, container.id, rect.center()))
+        .collect();
+    if nodes.is_empty() {
+        return;
+    }
+    // nodes with the same z-index keep a stable order
+    nodes.sort_by(|a, b| a.0.cmp(&b.0).then(a.1 .0.cmp(&b.1 .0)));
+    let current_index = current.and_then(|id| nodes.iter().position(|(_, node, _)| *node == id));
+    let next = match (direction, current_index) {
+        (Some(direction), Some(index)) => {
+            let from = nodes[index].2;
+            let centers: Vec<_> = nodes
+                .iter()
+                .filter(|(_, id, _)| Some(*id) != current)
+                .map(|(_, id, center)| (*id, *center))
+                .collect();
+            nearest_in_direction(from, direction, &centers)
+        }
+        (None, Some(index)) if shift => Some(nodes[(index + nodes.len() - 1) % nodes.len()].1),
+        (None, Some(index)) => Some(nodes[(index + 1) % nodes.len()].1),
+        (None, None) if shift => nodes.last().map(|(_, id, _)| *id),
+        (_, None) => nodes.first().map(|(_, id, _)| *id),
+    };
+    let Some(next) = next else {
+        return;
+    };
+    let groups: Vec<_> = groups_query
+        .iter()
+        .map(|(container, group)| (container.id, group.id))
+        .collect();
+    selected_nodes.0 = with_group_members(&[next], &groups);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_nearest_in_direction() {
+        let right = ReflectableUuid(Uuid::new_v4());
+        let far_right = ReflectableUuid(Uuid::new_v4());
+        let up = ReflectableUuid(Uuid::new_v4());
+        let centers = [
+            (right, Vec2::new(100., 40.)),
+            (far_right, Vec2::new(300., 0.)),
+            (up, Vec2::new(10., 200.)),
+        ];
+        assert_eq!(
+            nearest_in_direction(Vec2::ZERO, Vec2::X, &centers),
+            Some(right)
+        );
+        assert_eq!(
+            nearest_in_direction(Vec2::ZERO, Vec2::Y, &centers),
+            Some(up)
+        );
+        assert_eq!(
+            nearest_in_direction(Vec2::ZERO, Vec2::NEG_X, &centers),
+            None
+        );
+    }
+}
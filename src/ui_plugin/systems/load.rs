@@ -1,24 +1,29 @@
-use base64::{engine::general_purpose, Engine};
-use bevy::{
-    prelude::*,
-    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    window::PrimaryWindow,
-};
+use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_cosmic_edit::FontSystemState;
 
 use super::{
-    ui_helpers::{add_tab, spawn_node, BottomPanel, NodeMeta, TabContainer},
-    DeleteDoc, DeleteTab, MainPanel, VeloNodeContainer,
+    decrypt_requested_doc, notify, read_doc, spawn_stroke, stored_encrypted_docs,
+    ui_helpers::{
+        add_tabs, spawn_node, CollapsedBranch, FrameCollapsed, FrameMembers, Localized, NodeGroup,
+        NodeMeta, PenStroke, PresentationStep, TabBar, TabContainer,
+    },
+    CanvasZoom, CommChannels, DeleteDoc, DeleteTab, Notification, NotificationSender,
+    PassphraseRequest, SelectedStrokes, VeloNodeContainer,
+};
+use crate::{
+    canvas::arrow::components::{ArrowMeta, ArrowStyle},
+    resources::LoadTabRequest,
 };
-use crate::{canvas::arrow::components::ArrowMeta, resources::LoadTabRequest};
 use crate::{canvas::arrow::events::CreateArrowEvent, utils::load_doc_to_memory};
 
 use crate::resources::{AppState, LoadDocRequest};
-use crate::utils::ReflectableUuid;
-use crate::{JsonNode, UiState};
+use crate::utils::{convert_from_val_px, decode_image_base64, ReflectableUuid};
+use crate::{components::Doc, JsonFrame, JsonNode, JsonStroke, UiState};
+use async_channel::Sender;
 use bevy_pkv::PkvStore;
-use image::{load_from_memory_with_format, ImageFormat};
 use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 pub fn should_load_doc(request: Option<Res<LoadDocRequest>>) -> bool {
     request.is_some()
@@ -40,14 +45,36 @@ pub fn load_doc(
     request: Res<LoadDocRequest>,
     mut app_state: ResMut<AppState>,
     mut commands: Commands,
-    mut bottom_panel: Query<Entity, With<BottomPanel>>,
+    mut tab_bar: Query<Entity, With<TabBar>>,
     mut pkv: ResMut<PkvStore>,
     asset_server: Res<AssetServer>,
     mut tabs_query: Query<Entity, With<TabContainer>>,
     mut delete_doc: Query<(&mut Visibility, &DeleteDoc), With<DeleteDoc>>,
+    comm_channels: Res<CommChannels>,
 ) {
-    let bottom_panel = bottom_panel.single_mut();
+    let tab_bar = tab_bar.single_mut();
     let doc_id = request.doc_id;
+    // encrypted documents are loaded again once decrypted, with the passphrase they were
+    // opened with or the one asked for
+    if !app_state.docs.contains_key(&doc_id) {
+        if let Some(data) = stored_encrypted_docs(&pkv).remove(&doc_id) {
+            let request = PassphraseRequest {
+                data,
+                wrong_passphrase: false,
+            };
+            match app_state.passphrases.get(&doc_id).cloned() {
+                Some(passphrase) => decrypt_requested_doc(
+                    &request,
+                    &passphrase,
+                    &mut commands,
+                    &mut app_state,
+                    &comm_channels,
+                ),
+                None => commands.insert_resource(request),
+            }
+            return;
+        }
+    }
     for (mut visibility, doc) in delete_doc.iter_mut() {
         if doc.id == doc_id {
             *visibility = Visibility::Visible;
@@ -57,46 +84,124 @@ pub fn load_doc(
     }
     load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
 
-    let mut tabs = vec![];
     for entity in tabs_query.iter_mut() {
         commands.entity(entity).despawn_recursive();
     }
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter() {
-        let tab_view: Entity = add_tab(
-            &mut commands,
-            &asset_server,
-            tab.name.clone(),
-            tab.id,
-            tab.is_active,
-        );
-        tabs.push(tab_view);
-        if tab.is_active {
-            commands.insert_resource(LoadTabRequest {
-                doc_id,
-                tab_id: tab.id,
-                drop_last_checkpoint: false,
-            });
-        }
+    let tabs = &app_state.docs.get(&doc_id).unwrap().tabs;
+    add_tabs(&mut commands, &asset_server, tab_bar, tabs);
+    if let Some(tab) = tabs.iter().find(|tab| tab.is_active) {
+        commands.insert_resource(LoadTabRequest {
+            doc_id,
+            tab_id: tab.id,
+        });
+    }
+}
+
+/// Node to spawn from the saved one, its text is rendered for canvas zoom.
+pub fn json_node_meta(
+    json_node: JsonNode,
+    image: Option<Handle<Image>>,
+    zoom: f32,
+    scale_factor: f32,
+) -> NodeMeta {
+    NodeMeta {
+        size: Vec2::new(
+            convert_from_val_px(json_node.width),
+            convert_from_val_px(json_node.height),
+        ),
+        node_type: json_node.node_type,
+        id: ReflectableUuid(json_node.id),
+        image,
+        text: json_node.text.text,
+        bg_color: json_node.bg_color,
+        position: Vec2::new(
+            convert_from_val_px(json_node.left),
+            convert_from_val_px(json_node.bottom),
+        ),
+        text_pos: json_node.text.pos,
+        text_style: json_node.text.style,
+        z_index: json_node.z_index,
+        locked: json_node.locked,
+        border: json_node.border,
+        shadow: json_node.shadow,
+        opacity: json_node.opacity,
+        tags: json_node.tags,
+        attachments: json_node.attachments,
+        rotation: json_node.rotation,
+        is_active: false,
+        scale_factor,
+        zoom,
     }
-    commands.entity(bottom_panel).insert_children(0, &tabs);
+}
+
+/// Fetches the document at `url` and sends it to be loaded, failures are notified.
+pub fn load_doc_from_url(url: String, tx: Sender<Doc>, notification_sender: NotificationSender) {
+    ehttp::fetch(ehttp::Request::get(url), move |result| {
+        let result = result.and_then(|response| {
+            if response.ok {
+                read_doc(response.bytes.as_slice()).map_err(|e| e.to_string())
+            } else {
+                Err(format!("{} {}", response.status, response.status_text))
+            }
+        });
+        match result {
+            Ok(doc) => {
+                if let Err(e) = tx.try_send(doc) {
+                    let message = Localized::new("Couldn't open the document");
+                    notification_sender.send(Notification::error(message).with_details(e));
+                }
+            }
+            Err(e) => {
+                let message = Localized::new("Couldn't read the document");
+                notification_sender.send(Notification::error(message).with_details(e));
+            }
+        }
+    });
+}
+
+/// Arrow to create from the saved one, an error if it isn't an arrow.
+pub fn json_arrow_event(arrow: &Value) -> serde_json::Result<CreateArrowEvent> {
+    let arrow_meta: ArrowMeta = serde_json::from_value(arrow.clone())?;
+    Ok(CreateArrowEvent {
+        start: arrow_meta.start,
+        end: arrow_meta.end,
+        arrow_type: arrow_meta.arrow_type,
+        style: ArrowStyle::from_json(arrow, arrow_meta.arrow_type),
+        label: arrow["label"].as_str().map(|label| label.to_string()),
+        relation: arrow["relation"]
+            .as_str()
+            .map(|relation| relation.to_string()),
+        pins: serde_json::from_value(arrow["pins"].clone()).unwrap_or_default(),
+        waypoints: serde_json::from_value::<Vec<[f32; 2]>>(arrow["waypoints"].clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(Vec2::from)
+            .collect(),
+    })
 }
 
 pub fn load_tab(
     asset_server: Res<AssetServer>,
     old_nodes: Query<Entity, With<VeloNodeContainer>>,
     mut old_arrows: Query<(Entity, &mut Visibility), With<ArrowMeta>>,
+    mut old_strokes: Query<(Entity, &mut Visibility), (With<PenStroke>, Without<ArrowMeta>)>,
     request: Res<LoadTabRequest>,
     mut app_state: ResMut<AppState>,
     mut ui_state: ResMut<UiState>,
     mut commands: Commands,
     mut res_images: ResMut<Assets<Image>>,
     mut create_arrow: EventWriter<CreateArrowEvent>,
-    main_panel_query: Query<Entity, With<MainPanel>>,
-    mut delete_tab: Query<(&mut Visibility, &DeleteTab), (With<DeleteTab>, Without<ArrowMeta>)>,
+    mut delete_tab: Query<
+        (&mut Visibility, &DeleteTab),
+        (With<DeleteTab>, Without<ArrowMeta>, Without<PenStroke>),
+    >,
     mut font_system_state: ResMut<FontSystemState>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut canvas_zoom: ResMut<CanvasZoom>,
+    mut selected_strokes: ResMut<SelectedStrokes>,
 ) {
     *ui_state = UiState::default();
+    selected_strokes.0.clear();
     commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
     let window = windows.single_mut();
 
@@ -111,13 +216,29 @@ pub fn load_tab(
             *visibility = Visibility::Hidden;
         }
     }
+    #[allow(unused)]
+    for (entity, mut visibility) in &mut old_strokes.iter_mut() {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            *visibility = Visibility::Hidden;
+        }
+    }
     for entity in old_nodes.iter() {
         commands.entity(entity).despawn_recursive();
     }
 
     let doc_id = request.doc_id;
+    let pinned = app_state.docs.get(&doc_id).map_or(false, |doc| {
+        doc.tabs
+            .iter()
+            .any(|tab| tab.id == request.tab_id && tab.pinned)
+    });
     for (mut visibility, tab) in delete_tab.iter_mut() {
-        if tab.id == request.tab_id {
+        if tab.id == request.tab_id && !pinned {
             *visibility = Visibility::Visible;
         } else {
             *visibility = Visibility::Hidden;
@@ -125,73 +246,147 @@ pub fn load_tab(
     }
     for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter_mut() {
         if tab.id == request.tab_id {
-            if tab.checkpoints.is_empty() {
+            canvas_zoom.0 = tab.zoom;
+            let Some(checkpoint) = &tab.checkpoint else {
                 break;
-            }
+            };
 
-            let json = if request.drop_last_checkpoint && tab.checkpoints.len() > 1 {
-                tab.checkpoints.pop_back().unwrap()
-            } else {
-                tab.checkpoints.back().unwrap().clone()
+            let json = serde_json::from_str::<Value>(checkpoint);
+            let json = match json {
+                Ok(json) => json,
+                Err(e) => {
+                    let message = Localized::new("Couldn't read the tab {}, it's malformed")
+                        .with_args([tab.name.clone()]);
+                    notify(&mut commands, Notification::error(message).with_details(e));
+                    break;
+                }
             };
-            let mut json: Value = serde_json::from_str(&json).unwrap();
-            let images = json["images"].as_object().unwrap();
-            let nodes = json["nodes"].as_array().unwrap();
-            for node in nodes.iter() {
-                let json_node: JsonNode = serde_json::from_value(node.clone()).unwrap();
-                let image: Option<UiImage> = match images.get(&json_node.id.to_string()) {
-                    Some(image) => {
-                        let image_bytes = general_purpose::STANDARD
-                            .decode(image.as_str().unwrap().as_bytes())
-                            .unwrap();
-                        let img =
-                            load_from_memory_with_format(&image_bytes, ImageFormat::Png).unwrap();
-                        let size: Extent3d = Extent3d {
-                            width: img.width(),
-                            height: img.height(),
-                            ..Default::default()
-                        };
-                        let image = Image::new(
-                            size,
-                            TextureDimension::D2,
-                            img.into_bytes(),
-                            TextureFormat::Rgba8UnormSrgb,
-                        );
-                        let image_handle = res_images.add(image);
-                        Some(image_handle.into())
+            // malformed nodes, arrows and strokes are left out
+            let mut skipped = 0;
+            let mut node_groups = HashMap::new();
+            if let Some(groups) = json["groups"].as_object() {
+                for (group_id, members) in groups.iter() {
+                    let Ok(group_id) = Uuid::parse_str(group_id) else {
+                        skipped += 1;
+                        continue;
+                    };
+                    for member in members.as_array().map_or(&[][..], Vec::as_slice) {
+                        match serde_json::from_value::<Uuid>(member.clone()) {
+                            Ok(node_id) => {
+                                node_groups.insert(node_id, ReflectableUuid(group_id));
+                            }
+                            Err(_) => skipped += 1,
+                        }
+                    }
+                }
+            }
+            let mut frames: HashMap<Uuid, JsonFrame> = HashMap::new();
+            if let Some(json_frames) = json["frames"].as_object() {
+                for (frame_id, frame) in json_frames.iter() {
+                    let frame_id = Uuid::parse_str(frame_id).ok();
+                    let frame = serde_json::from_value::<JsonFrame>(frame.clone()).ok();
+                    match frame_id.zip(frame) {
+                        Some((frame_id, frame)) => {
+                            frames.insert(frame_id, frame);
+                        }
+                        None => skipped += 1,
                     }
-                    None => None,
+                }
+            }
+            let mut collapsed_branches: Vec<Uuid> = vec![];
+            if let Some(collapsed) = json["collapsed_branches"].as_array() {
+                collapsed_branches.extend(
+                    collapsed
+                        .iter()
+                        .filter_map(|id| serde_json::from_value(id.clone()).ok()),
+                );
+            }
+            let mut presentation: Vec<Uuid> = vec![];
+            if let Some(path) = json["presentation"].as_array() {
+                presentation.extend(
+                    path.iter()
+                        .filter_map(|id| serde_json::from_value(id.clone()).ok()),
+                );
+            }
+            let no_images = serde_json::Map::new();
+            let images = json["images"].as_object().unwrap_or(&no_images);
+            let nodes = json["nodes"].as_array().map_or(&[][..], Vec::as_slice);
+            for node in nodes.iter() {
+                let Ok(json_node) = serde_json::from_value::<JsonNode>(node.clone()) else {
+                    skipped += 1;
+                    continue;
                 };
+                let image = images
+                    .get(&json_node.id.to_string())
+                    .and_then(|image| image.as_str())
+                    .map(|image| res_images.add(decode_image_base64(image)));
+                let node_id = json_node.id;
                 // ideally AddRect event should be fired instead of calling spawn_node directly
                 let entity = spawn_node(
                     &mut commands,
                     &asset_server,
                     &mut font_system_state,
-                    NodeMeta {
-                        size: (json_node.width, json_node.height),
-                        node_type: json_node.node_type,
-                        id: ReflectableUuid(json_node.id),
-                        image: image.clone(),
-                        text: json_node.text.text.clone(),
-                        bg_color: json_node.bg_color,
-                        position: (json_node.left, json_node.bottom),
-                        text_pos: json_node.text.pos,
-                        z_index: json_node.z_index,
-                        is_active: false,
-                        scale_factor: window.scale_factor() as f32,
-                    },
+                    json_node_meta(json_node, image, tab.zoom, window.scale_factor() as f32),
                 );
-                commands.entity(main_panel_query.single()).add_child(entity);
+                if let Some(group_id) = node_groups.get(&node_id) {
+                    commands.entity(entity).insert(NodeGroup { id: *group_id });
+                }
+                if let Some(frame) = frames.get(&node_id) {
+                    commands.entity(entity).insert(FrameMembers(
+                        frame
+                            .members
+                            .iter()
+                            .map(|id| ReflectableUuid(*id))
+                            .collect(),
+                    ));
+                    if frame.collapsed {
+                        commands.entity(entity).insert(FrameCollapsed);
+                    }
+                }
+                if collapsed_branches.contains(&node_id) {
+                    commands.entity(entity).insert(CollapsedBranch);
+                }
+                if let Some(step) = presentation.iter().position(|id| *id == node_id) {
+                    commands
+                        .entity(entity)
+                        .insert(PresentationStep(step as u32));
+                }
             }
 
-            let arrows = json["arrows"].as_array_mut().unwrap();
+            let arrows = json["arrows"].as_array().map_or(&[][..], Vec::as_slice);
             for arrow in arrows.iter() {
-                let arrow_meta: ArrowMeta = serde_json::from_value(arrow.clone()).unwrap();
-                create_arrow.send(CreateArrowEvent {
-                    start: arrow_meta.start,
-                    end: arrow_meta.end,
-                    arrow_type: arrow_meta.arrow_type,
-                });
+                let Ok(event) = json_arrow_event(arrow) else {
+                    skipped += 1;
+                    continue;
+                };
+                create_arrow.send(event);
+            }
+
+            if let Some(strokes) = json["strokes"].as_array() {
+                for stroke in strokes.iter() {
+                    let Ok(stroke) = serde_json::from_value::<JsonStroke>(stroke.clone()) else {
+                        skipped += 1;
+                        continue;
+                    };
+                    spawn_stroke(
+                        &mut commands,
+                        PenStroke {
+                            id: ReflectableUuid(stroke.id),
+                            points: stroke
+                                .points
+                                .iter()
+                                .map(|point| Vec2::from(*point))
+                                .collect(),
+                            color: stroke.color,
+                            width: stroke.width,
+                        },
+                    );
+                }
+            }
+            if skipped > 0 {
+                let message = Localized::new("{} elements of the tab {} couldn't be read")
+                    .with_args([skipped.to_string(), tab.name.clone()]);
+                notify(&mut commands, Notification::warn(message));
             }
             break;
         }
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ui_helpers::{ChangeLanguage, Localized};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Default, Debug)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Spanish,
+    ];
+
+    /// Name of the language in the language itself.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+            Language::French => "Français",
+            Language::Spanish => "Español",
+        }
+    }
+
+    /// Translations of the English UI strings, English itself has none.
+    fn bundle(self) -> Option<&'static str> {
+        match self {
+            Language::English => None,
+            Language::German => Some(include_str!("../../../assets/locales/de.json")),
+            Language::French => Some(include_str!("../../../assets/locales/fr.json")),
+            Language::Spanish => Some(include_str!("../../../assets/locales/es.json")),
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|language| *language == self);
+        Self::ALL[index.map_or(0, |index| (index + 1) % Self::ALL.len())]
+    }
+}
+
+/// Language of the UI and the translations of its strings, keyed by the English string.
+#[derive(Resource, Default)]
+pub struct Locale {
+    pub language: Language,
+    bundles: HashMap<Language, HashMap<String, String>>,
+}
+
+impl Locale {
+    /// Parses the translation bundles of all languages.
+    pub fn load() -> Result<Self, serde_json::Error> {
+        let mut bundles = HashMap::new();
+        for language in Language::ALL {
+            if let Some(bundle) = language.bundle() {
+                bundles.insert(language, serde_json::from_str(bundle)?);
+            }
+        }
+        Ok(Self {
+            language: Language::default(),
+            bundles,
+        })
+    }
+
+    /// Translation of the English `text`, or `text` itself when it isn't translated.
+    pub fn translate<'a>(&'a self, text: &'a str) -> &'a str {
+        self.bundles
+            .get(&self.language)
+            .and_then(|bundle| bundle.get(text))
+            .map_or(text, String::as_str)
+    }
+
+    /// Translated text of `localized` with its `{}` placeholders replaced by its arguments.
+    pub fn format(&self, localized: &Localized) -> String {
+        let mut parts = self.translate(&localized.text).split("{}");
+        let mut result = parts.next().unwrap_or_default().to_string();
+        let mut args = localized.args.iter();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                result.push_str(arg);
+            }
+            result.push_str(part);
+        }
+        result
+    }
+}
+
+/// Loads the translations, the language is set by the settings.
+pub fn load_locale(mut locale: ResMut<Locale>) {
+    match Locale::load() {
+        Ok(loaded) => *locale = loaded,
+        Err(e) => error!("Error reading translations: {:?}", e),
+    }
+}
+
+/// "Language" switches the UI to the next language, the choice is kept in the settings.
+pub fn change_language(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ChangeLanguage>)>,
+    mut locale: ResMut<Locale>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    locale.language = locale.language.next();
+    info!("UI language: {}", locale.language.name());
+}
+
+/// Translates localized texts when they are spawned and all of them when the language changes.
+pub fn apply_locale(locale: Res<Locale>, mut texts: Query<(Ref<Localized>, &mut Text)>) {
+    let all = locale.is_changed();
+    for (localized, mut text) in texts.iter_mut() {
+        if all || localized.is_changed() {
+            if let Some(section) = text.sections.first_mut() {
+                section.value = locale.format(&localized);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let mut locale = Locale::load().unwrap();
+        let prompt = Localized::new(
+            "{} was changed by another program, reload it or keep the open version?",
+        )
+        .with_args(["plan.json"]);
+        assert_eq!(
+            locale.format(&prompt),
+            "plan.json was changed by another program, reload it or keep the open version?"
+        );
+        assert_eq!(locale.translate("not translated"), "not translated");
+
+        locale.language = Language::German;
+        assert_eq!(locale.translate("Cancel"), "Abbrechen");
+        assert_eq!(
+            locale.format(&prompt),
+            "plan.json wurde von einem anderen Programm geändert, neu laden oder die geöffnete \
+             Version behalten?"
+        );
+        // arguments aren't translated
+        let name = Localized::new("{}").with_args(["Cancel"]);
+        assert_eq!(locale.format(&name), "Cancel");
+    }
+
+    #[test]
+    fn test_bundles() {
+        let locale = Locale::load().unwrap();
+        let german = &locale.bundles[&Language::German];
+        for (language, bundle) in locale.bundles.iter() {
+            // every language translates the same strings with the same placeholders
+            assert_eq!(bundle.len(), german.len(), "{:?}", language);
+            for (text, translation) in bundle.iter() {
+                assert!(german.contains_key(text), "{:?}: {}", language, text);
+                assert_eq!(
+                    text.matches("{}").count(),
+                    translation.matches("{}").count(),
+                    "{:?}: {}",
+                    language,
+                    text
+                );
+            }
+        }
+    }
+}
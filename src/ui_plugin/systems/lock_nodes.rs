@@ -0,0 +1,85 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use super::{
+    ui_helpers::{LockBadge, Locked, NodePart},
+    Action, ContextMenuAction, ContextMenuEvent, SelectedNodes, Settings, UiState,
+    VeloNodeContainer,
+};
+
+// Badges are drawn over the node and its handles
+const BADGE_Z: f32 = 0.75;
+
+pub fn toggle_lock(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    containers: Query<(Entity, &VeloNodeContainer, Option<&Locked>)>,
+    mut context_menu_events: EventReader<ContextMenuEvent>,
+    settings: Res<Settings>,
+) {
+    let from_menu = context_menu_events
+        .iter()
+        .any(|event| event.action == ContextMenuAction::Lock);
+    if !from_menu
+        && (!settings.keybindings.just_pressed(Action::Lock, &input) || ui_state.editing_node)
+    {
+        return;
+    }
+    let selected: Vec<_> = containers
+        .iter()
+        .filter(|(_, container, _)| selected_nodes.0.contains(&container.id))
+        .collect();
+    // unlock only if every selected node is locked, otherwise lock them all
+    let lock = selected.iter().any(|(_, _, locked)| locked.is_none());
+    for (entity, _, _) in selected {
+        if lock {
+            commands.entity(entity).insert(Locked);
+        } else {
+            commands.entity(entity).remove::<Locked>();
+        }
+    }
+}
+
+pub fn update_lock_badges(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locked_query: Query<Entity, (Added<Locked>, With<VeloNodeContainer>)>,
+    mut unlocked: RemovedComponents<Locked>,
+    badges_query: Query<(Entity, &Parent), With<LockBadge>>,
+) {
+    for entity in unlocked.iter() {
+        for (badge, parent) in badges_query.iter() {
+            if parent.get() == entity {
+                commands.entity(badge).despawn_recursive();
+            }
+        }
+    }
+    let icon_font = asset_server.load("fonts/MaterialIcons-Regular.ttf");
+    for entity in locked_query.iter() {
+        let badge = commands
+            .spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "\u{e897}",
+                        TextStyle {
+                            font_size: 16.0,
+                            color: Color::BLACK.with_a(0.6),
+                            font: icon_font.clone(),
+                        },
+                    ),
+                    text_anchor: Anchor::TopRight,
+                    ..default()
+                },
+                NodePart::handle(
+                    Vec2::new(0.5, 0.5),
+                    Vec2::new(-2., -2.),
+                    Vec2::ZERO,
+                    BADGE_Z,
+                ),
+                LockBadge,
+            ))
+            .id();
+        commands.entity(entity).add_child(badge);
+    }
+}
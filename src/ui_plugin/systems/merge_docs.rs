@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde_json::Value;
+
+use super::ui_helpers::Localized;
+use super::{is_encrypted_file, notify, read_doc_file, remap_ids, Notification};
+use crate::components::Doc;
+use crate::resources::{AppState, LoadDocRequest, SaveTabRequest};
+use crate::utils::ReflectableUuid;
+
+/// Ids of the nodes, strokes and groups of a saved tab.
+fn checkpoint_ids(checkpoint: &str) -> HashSet<String> {
+    let Ok(json) = serde_json::from_str::<Value>(checkpoint) else {
+        return HashSet::new();
+    };
+    let mut ids = HashSet::new();
+    for key in ["nodes", "strokes"] {
+        for item in json[key].as_array().into_iter().flatten() {
+            if let Some(id) = item["id"].as_str() {
+                ids.insert(id.to_string());
+            }
+        }
+    }
+    if let Some(groups) = json["groups"].as_object() {
+        ids.extend(groups.keys().cloned());
+    }
+    ids
+}
+
+/// Appends the tabs of `other` to `doc`, the first of them becomes the active tab. Tab, node,
+/// group and stroke ids already used in `doc` get new ones, so merging a copy of the document
+/// into itself keeps both versions. Tabs named like one of `doc` get the name of `other`
+/// appended.
+pub fn merge_doc(doc: &mut Doc, other: Doc) {
+    let mut taken: HashSet<String> = doc
+        .tabs
+        .iter()
+        .flat_map(|tab| tab.checkpoint.as_deref())
+        .flat_map(checkpoint_ids)
+        .collect();
+    taken.extend(doc.tabs.iter().map(|tab| tab.id.0.to_string()));
+    let mut names: HashSet<String> = doc.tabs.iter().map(|tab| tab.name.clone()).collect();
+    for tab in doc.tabs.iter_mut() {
+        tab.is_active = false;
+    }
+    for (i, mut tab) in other.tabs.into_iter().enumerate() {
+        if taken.contains(&tab.id.0.to_string()) {
+            tab.id = ReflectableUuid::generate();
+        }
+        if names.contains(&tab.name) {
+            tab.name = format!("{} ({})", tab.name, other.name);
+        }
+        names.insert(tab.name.clone());
+        let mut ids = HashMap::new();
+        tab.checkpoint = tab
+            .checkpoint
+            .as_deref()
+            .map(|checkpoint| remap_ids(checkpoint, &mut ids, |id| taken.contains(id)));
+        tab.is_active = i == 0;
+        doc.tabs.push(tab);
+    }
+    doc.assets.extend(other.assets);
+}
+
+/// "Import Document Into Current…" adds the tabs of the velo document at `path` to the open one.
+pub fn import_into_current(commands: &mut Commands, app_state: &mut AppState, path: &Path) {
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    let file = path.display().to_string();
+    if is_encrypted_file(path) {
+        let message =
+            Localized::new("{} is encrypted, open it and save it without encryption to import it")
+                .with_args([file]);
+        notify(commands, Notification::error(message));
+        return;
+    }
+    let other = match read_doc_file(path) {
+        Ok(other) => other,
+        Err(e) => {
+            let message = Localized::new("Couldn't open {}").with_args([file]);
+            notify(commands, Notification::error(message).with_details(e));
+            return;
+        }
+    };
+    let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+        return;
+    };
+    if other.tabs.is_empty() {
+        return;
+    }
+    // the active tab is saved before the merged document is loaded
+    if let Some(tab) = doc.tabs.iter().find(|tab| tab.is_active) {
+        commands.insert_resource(SaveTabRequest {
+            doc_id,
+            tab_id: tab.id,
+        });
+    }
+    merge_doc(doc, other);
+    commands.insert_resource(LoadDocRequest { doc_id });
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::components::Tab;
+
+    fn tab(name: &str, checkpoint: &Value) -> Tab {
+        Tab {
+            id: ReflectableUuid::generate(),
+            name: name.to_string(),
+            checkpoint: Some(checkpoint.to_string()),
+            zoom: 1.,
+            color: None,
+            icon: None,
+            pinned: false,
+            offset: [0., 0.],
+            selected: vec![],
+            is_active: true,
+        }
+    }
+
+    fn doc(name: &str, tabs: Vec<Tab>) -> Doc {
+        Doc {
+            id: ReflectableUuid::generate(),
+            name: name.to_string(),
+            tabs,
+            tags: vec![],
+            assets: HashMap::from([(name.to_string(), String::new())]),
+            revision: 0,
+        }
+    }
+
+    #[test]
+    fn test_merge_doc() {
+        let shared = uuid::Uuid::new_v4().to_string();
+        let own = uuid::Uuid::new_v4().to_string();
+        let mut current = doc(
+            "Current",
+            vec![tab("Plan", &json!({ "nodes": [{ "id": shared }] }))],
+        );
+        let other_tab = tab(
+            "Plan",
+            &json!({
+                "nodes": [{ "id": shared }, { "id": own }],
+                "arrows": [{ "start": { "id": shared }, "end": { "id": own } }],
+            }),
+        );
+        let mut same_id_tab = tab("Notes", &json!({ "nodes": [] }));
+        same_id_tab.id = current.tabs[0].id;
+        let other = doc("Other", vec![other_tab, same_id_tab]);
+
+        merge_doc(&mut current, other);
+        let names: Vec<_> = current.tabs.iter().map(|tab| tab.name.as_str()).collect();
+        assert_eq!(names, vec!["Plan", "Plan (Other)", "Notes"]);
+        let active: Vec<_> = current.tabs.iter().map(|tab| tab.is_active).collect();
+        assert_eq!(active, vec![false, true, false]);
+        assert_ne!(current.tabs[2].id, current.tabs[0].id);
+        assert_eq!(current.assets.len(), 2);
+
+        let merged: Value =
+            serde_json::from_str(current.tabs[1].checkpoint.as_ref().unwrap()).unwrap();
+        let new_shared = merged["nodes"][0]["id"].as_str().unwrap();
+        assert_ne!(new_shared, shared);
+        assert_eq!(merged["nodes"][1]["id"], own);
+        assert_eq!(merged["arrows"][0]["start"]["id"], new_shared);
+        assert_eq!(merged["arrows"][0]["end"]["id"], own);
+    }
+}
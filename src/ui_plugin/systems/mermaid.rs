@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::export::ExportedTab;
+use super::import::{ImportedEdge, ImportedGraph};
+use crate::canvas::arrow::components::{ArrowDash, ArrowStyle, ArrowType};
+use crate::NodeType;
+
+// Node shapes by opening and closing delimiters, longer openings first
+const SHAPES: [(&str, &str, NodeType); 14] = [
+    ("(((", ")))", NodeType::Circle),
+    ("((", "))", NodeType::Circle),
+    ("([", "])", NodeType::Ellipse),
+    ("[[", "]]", NodeType::Rect),
+    ("[(", ")]", NodeType::Cylinder),
+    ("[/", "/]", NodeType::Parallelogram),
+    ("[/", "\\]", NodeType::Parallelogram),
+    ("[\\", "\\]", NodeType::Parallelogram),
+    ("[\\", "/]", NodeType::Parallelogram),
+    ("{{", "}}", NodeType::Hexagon),
+    ("(", ")", NodeType::Rect),
+    ("[", "]", NodeType::Rect),
+    ("{", "}", NodeType::Diamond),
+    (">", "]", NodeType::Rect),
+];
+// Statements that don't add nodes or arrows
+const IGNORED_STATEMENTS: [&str; 9] = [
+    "%%",
+    "classDef ",
+    "class ",
+    "style ",
+    "linkStyle ",
+    "click ",
+    "subgraph ",
+    "end ",
+    "direction ",
+];
+
+/// Nodes and arrows of a Mermaid flowchart (`flowchart` or `graph`). Subgraphs are flattened
+/// and styling statements are ignored.
+pub fn mermaid_flowchart(mermaid: &str) -> ImportedGraph {
+    let mut graph = ImportedGraph::default();
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    for statement in mermaid.lines().flat_map(|line| line.split(';')) {
+        let statement = statement.trim();
+        let keyword = statement.split_whitespace().next().unwrap_or_default();
+        if statement.is_empty()
+            || keyword == "flowchart"
+            || keyword == "graph"
+            || IGNORED_STATEMENTS
+                .iter()
+                .any(|ignored| statement.starts_with(ignored) || statement == ignored.trim())
+        {
+            continue;
+        }
+        let Some((mut previous, mut rest)) = node_list(statement, &mut graph, &mut ids) else {
+            continue;
+        };
+        while let Some((arrow_type, style, label, after_edge)) = edge(rest.trim_start()) {
+            let Some((nodes, after_nodes)) =
+                node_list(after_edge.trim_start(), &mut graph, &mut ids)
+            else {
+                break;
+            };
+            for start in previous.iter() {
+                for end in nodes.iter() {
+                    graph.edges.push(ImportedEdge {
+                        start: *start,
+                        end: *end,
+                        arrow_type,
+                        style,
+                        label: label.clone(),
+                    });
+                }
+            }
+            previous = nodes;
+            rest = after_nodes;
+        }
+    }
+    graph
+}
+
+/// Nodes of `A & B[text] & C` at the start of the statement and the rest of it.
+fn node_list<'a>(
+    statement: &'a str,
+    graph: &mut ImportedGraph,
+    ids: &mut HashMap<String, usize>,
+) -> Option<(Vec<usize>, &'a str)> {
+    let (first, mut rest) = node(statement, graph, ids)?;
+    let mut nodes = vec![first];
+    while let Some(after) = rest.trim_start().strip_prefix('&') {
+        let Some((next, after_node)) = node(after.trim_start(), graph, ids) else {
+            break;
+        };
+        nodes.push(next);
+        rest = after_node;
+    }
+    Some((nodes, rest))
+}
+
+/// Node with its optional shape and text at the start of the statement and the rest of it.
+/// Nodes are shared by id, a later shape and text replace the ones given before.
+fn node<'a>(
+    statement: &'a str,
+    graph: &mut ImportedGraph,
+    ids: &mut HashMap<String, usize>,
+) -> Option<(usize, &'a str)> {
+    let id_len = statement
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(statement.len());
+    if id_len == 0 {
+        return None;
+    }
+    let (id, mut rest) = statement.split_at(id_len);
+    let index = *ids
+        .entry(id.to_string())
+        .or_insert_with(|| graph.add_node(id.to_string(), NodeType::Rect));
+    for (open, close, node_type) in SHAPES.iter() {
+        let Some(after_open) = rest.strip_prefix(open) else {
+            continue;
+        };
+        // quoted text can contain the closing delimiter
+        let text_end = match after_open.strip_prefix('"') {
+            Some(quoted) => quoted.find('"').map(|end| end + 2),
+            None => Some(0),
+        };
+        let Some(end) =
+            text_end.and_then(|start| after_open[start..].find(close).map(|end| start + end))
+        else {
+            continue;
+        };
+        let node = &mut graph.nodes[index];
+        node.text = unescape(after_open[..end].trim().trim_matches('"'));
+        node.node_type = node_type.clone();
+        rest = &after_open[end + close.len()..];
+        break;
+    }
+    // css classes of the node
+    if let Some(class) = rest.strip_prefix(":::") {
+        rest = class.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '-');
+    }
+    Some((index, rest))
+}
+
+/// Link at the start of the statement (`-->`, `---`, `-.->`, `==>`, `<-->`, `-- text -->`,
+/// `-->|text|` ...) with its arrow type, style and text, and the rest of the statement.
+fn edge(statement: &str) -> Option<(ArrowType, ArrowStyle, Option<String>, &str)> {
+    let (link, mut rest) = link(statement)?;
+    let mut label = None;
+    // text between the two halves of the link, e.g. `-- text -->`
+    if !link.ends_with('>') && link.len() == 2 {
+        let closing_start = ["-->", "---", "==>", "===", ".->", ".-"]
+            .iter()
+            .filter_map(|closing| rest.find(closing))
+            .min();
+        if let Some(start) = closing_start {
+            let text = rest[..start].trim();
+            let (closing_link, after) = link_body(&rest[start..])?;
+            label = (!text.is_empty()).then(|| unescape(text.trim_matches('"')));
+            return Some(link_style(
+                &format!("{}{}", link, closing_link),
+                label,
+                after,
+            ));
+        }
+    }
+    if let Some(piped) = rest.trim_start().strip_prefix('|') {
+        let end = piped.find('|')?;
+        let text = piped[..end].trim().trim_matches('"');
+        label = (!text.is_empty()).then(|| unescape(text));
+        rest = &piped[end + 1..];
+    }
+    Some(link_style(link, label, rest))
+}
+
+/// Link with the optional start head at the start of the statement and the rest of it.
+fn link(statement: &str) -> Option<(&str, &str)> {
+    let body_start = usize::from(matches!(statement.chars().next(), Some('<' | 'o' | 'x')));
+    let (body, _) = link_body(&statement[body_start..])?;
+    let len = body_start + body.len();
+    Some((&statement[..len], &statement[len..]))
+}
+
+/// Dashes, dots and equal signs of a link with its optional end head.
+fn link_body(statement: &str) -> Option<(&str, &str)> {
+    let mut len = statement
+        .find(|c: char| !matches!(c, '-' | '=' | '.'))
+        .unwrap_or(statement.len());
+    if len < 2 {
+        return None;
+    }
+    let after = &statement[len..];
+    if after.starts_with('>')
+        || (after.starts_with(['o', 'x'])
+            && after[1..]
+                .chars()
+                .next()
+                .map_or(true, |c| c.is_whitespace()))
+    {
+        len += 1;
+    }
+    Some(statement.split_at(len))
+}
+
+fn link_style<'a>(
+    link: &str,
+    label: Option<String>,
+    rest: &'a str,
+) -> (ArrowType, ArrowStyle, Option<String>, &'a str) {
+    let arrow_type = match (link.starts_with('<'), link.ends_with('>')) {
+        (true, true) => ArrowType::DoubleArrow,
+        (_, true) => ArrowType::Arrow,
+        _ => ArrowType::Line,
+    };
+    let mut style = ArrowStyle::for_type(arrow_type);
+    if link.contains('.') {
+        style.dash = ArrowDash::Dashed;
+    }
+    if link.contains('=') {
+        style.thickness = 3.;
+    }
+    (arrow_type, style, label, rest)
+}
+
+/// Mermaid entities and line breaks used in node text.
+fn unescape(text: &str) -> String {
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .replace("#quot;", "\"")
+}
+
+fn escape(text: &str) -> String {
+    text.trim().replace('"', "#quot;").replace('\n', "<br>")
+}
+
+/// Mermaid flowchart of the tab, top to bottom. Nodes keep their shape where Mermaid has one,
+/// arrows keep their direction, dashing and label (or relationship).
+pub fn tab_to_mermaid(tab: &ExportedTab) -> String {
+    let order: Vec<usize> = tab
+        .reading_order()
+        .into_iter()
+        .filter(|index| tab.nodes[*index].node_type != NodeType::Frame)
+        .collect();
+    let mut ids = HashMap::new();
+    let mut mermaid = "flowchart TD\n".to_string();
+    for (i, index) in order.iter().enumerate() {
+        let node = &tab.nodes[*index];
+        let id = format!("n{}", i + 1);
+        let (open, close) = match node.node_type {
+            NodeType::Circle => ("((", "))"),
+            NodeType::Ellipse => ("([", "])"),
+            NodeType::Diamond => ("{", "}"),
+            NodeType::Hexagon => ("{{", "}}"),
+            NodeType::Parallelogram => ("[/", "/]"),
+            NodeType::Cylinder => ("[(", ")]"),
+            _ => ("[", "]"),
+        };
+        let _ = writeln!(
+            mermaid,
+            "    {}{}\"{}\"{}",
+            id,
+            open,
+            escape(&node.text.text),
+            close
+        );
+        ids.insert(node.id, id);
+    }
+    for arrow in tab.arrows.iter() {
+        let (Some(start), Some(end)) = (
+            ids.get(&arrow.meta.start.id.0),
+            ids.get(&arrow.meta.end.id.0),
+        ) else {
+            continue;
+        };
+        let dashed = arrow.style.dash != ArrowDash::Solid;
+        let link = match (arrow.meta.arrow_type, dashed) {
+            (ArrowType::Line | ArrowType::ParallelLine, false) => "---",
+            (ArrowType::Line | ArrowType::ParallelLine, true) => "-.-",
+            (ArrowType::Arrow | ArrowType::ParallelArrow, false) => "-->",
+            (ArrowType::Arrow | ArrowType::ParallelArrow, true) => "-.->",
+            (ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow, false) => "<-->",
+            (ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow, true) => "<-.->",
+        };
+        let text = arrow
+            .label
+            .as_deref()
+            .filter(|label| !label.trim().is_empty())
+            .or(arrow.relation.as_deref());
+        match text {
+            Some(text) => {
+                let _ = writeln!(
+                    mermaid,
+                    "    {} {}|\"{}\"| {}",
+                    start,
+                    link,
+                    escape(text),
+                    end
+                );
+            }
+            None => {
+                let _ = writeln!(mermaid, "    {} {} {}", start, link, end);
+            }
+        }
+    }
+    mermaid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mermaid_flowchart() {
+        let graph = mermaid_flowchart(
+            "flowchart TD\n    %% comment\n    A[Start] --> B{Is it?}\n    B -->|Yes| C((\"Done <br> [ok]\"))\n    B -- No --> D[(Store)] -.- A\n    C & D ==> E:::done\n    classDef done fill:#f96\n",
+        );
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|node| (node.text.as_str(), node.node_type.clone()))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("Start", NodeType::Rect),
+                ("Is it?", NodeType::Diamond),
+                ("Done \n [ok]", NodeType::Circle),
+                ("Store", NodeType::Cylinder),
+                ("E", NodeType::Rect),
+            ]
+        );
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    edge.start,
+                    edge.end,
+                    edge.arrow_type,
+                    edge.style.dash,
+                    edge.label.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, ArrowType::Arrow, ArrowDash::Solid, None),
+                (1, 2, ArrowType::Arrow, ArrowDash::Solid, Some("Yes")),
+                (1, 3, ArrowType::Arrow, ArrowDash::Solid, Some("No")),
+                (3, 0, ArrowType::Line, ArrowDash::Dashed, None),
+                (2, 4, ArrowType::Arrow, ArrowDash::Solid, None),
+                (3, 4, ArrowType::Arrow, ArrowDash::Solid, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::{
+    rasterize_minimap,
+    ui_helpers::{MainPanel, Minimap, NodeRect},
+    view_rect, world_to_canvas, CanvasOffset, CanvasZoom, PanCanvasEvent, VeloNode,
+    VeloNodeContainer,
+};
+
+const MINIMAP_SIZE: Vec2 = Vec2::new(200., 130.);
+// Space between the minimap border and its content
+const MINIMAP_PADDING: f32 = 6.;
+
+/// Redraws minimap when nodes, their colors, the view or the canvas size change, spawns it on
+/// first run.
+pub fn update_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
+    main_panel_query: Query<(Entity, &Node), With<MainPanel>>,
+    mut minimap_query: Query<&mut Minimap>,
+    containers: Query<(&NodeRect, &Children), With<VeloNodeContainer>>,
+    nodes: Query<&Sprite, With<VeloNode>>,
+    changed_containers: Query<(), (With<VeloNodeContainer>, Changed<NodeRect>)>,
+    changed_nodes: Query<(), (With<VeloNode>, Changed<Sprite>)>,
+    changed_panel: Query<(), (With<MainPanel>, Changed<Node>)>,
+    mut removed_containers: RemovedComponents<VeloNodeContainer>,
+) {
+    let Ok((main_panel, panel)) = main_panel_query.get_single() else {
+        return;
+    };
+    let removed = removed_containers.iter().count() > 0;
+    let Ok(mut minimap) = minimap_query.get_single_mut() else {
+        let image = images.add(rasterize_minimap(
+            MINIMAP_SIZE.as_uvec2(),
+            &[],
+            Rect::default(),
+        ));
+        let minimap = commands
+            .spawn((
+                ButtonBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            right: Val::Px(10.),
+                            bottom: Val::Px(10.),
+                            ..default()
+                        },
+                        size: Size::new(Val::Px(MINIMAP_SIZE.x), Val::Px(MINIMAP_SIZE.y)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1., 1., 1., 0.85).into(),
+                    // stay above nodes even after they are brought to front
+                    z_index: ZIndex::Local(i32::MAX),
+                    ..default()
+                },
+                Minimap {
+                    image: image.clone(),
+                    ..default()
+                },
+            ))
+            .with_children(|builder| {
+                builder.spawn(ImageBundle {
+                    image: image.into(),
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        ..default()
+                    },
+                    ..default()
+                });
+            })
+            .id();
+        commands.entity(main_panel).add_child(minimap);
+        return;
+    };
+    if changed_containers.is_empty()
+        && changed_nodes.is_empty()
+        && changed_panel.is_empty()
+        && !offset.is_changed()
+        && !zoom.is_changed()
+        && !removed
+        && minimap.scale > 0.
+    {
+        return;
+    }
+
+    // world coordinates, the viewport is the part of the world shown in the main panel
+    let viewport = view_rect(panel.size(), &offset, &zoom);
+    let mut bounds = viewport;
+    let mut rects = vec![];
+    for (node_rect, children) in containers.iter() {
+        let rect = node_rect.rect();
+        bounds = bounds.union(rect);
+        let color = children
+            .iter()
+            .find_map(|child| nodes.get(*child).ok())
+            .map_or(Color::GRAY, |sprite| sprite.color);
+        // darkened to keep white nodes visible on white background
+        let color = Color::rgb(color.r() * 0.8, color.g() * 0.8, color.b() * 0.8);
+        rects.push((rect, color));
+    }
+    let available = MINIMAP_SIZE - Vec2::splat(2. * MINIMAP_PADDING);
+    let scale = (available / bounds.size().max(Vec2::ONE)).min_element();
+    // content is centered in the minimap
+    let origin = bounds.center() - MINIMAP_SIZE / 2. / scale;
+    let to_minimap = |rect: Rect| Rect {
+        min: (rect.min - origin) * scale,
+        max: (rect.max - origin) * scale,
+    };
+    let rects: Vec<_> = rects
+        .into_iter()
+        .map(|(rect, color)| (to_minimap(rect), color))
+        .collect();
+    if let Some(image) = images.get_mut(&minimap.image) {
+        *image = rasterize_minimap(MINIMAP_SIZE.as_uvec2(), &rects, to_minimap(viewport));
+    }
+    minimap.origin = origin;
+    minimap.scale = scale;
+}
+
+/// Clicking in minimap centers the canvas on the clicked point, dragging moves the viewport.
+pub fn minimap_navigation(
+    minimap_query: Query<(&Interaction, &Minimap, &Node, &GlobalTransform)>,
+    main_panel_query: Query<&Node, With<MainPanel>>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut events: EventWriter<PanCanvasEvent>,
+    mut last_cursor: Local<Option<Vec2>>,
+) {
+    let Ok((interaction, minimap, node, transform)) = minimap_query.get_single() else {
+        return;
+    };
+    let primary_window = windows.single();
+    let (Interaction::Clicked, Some(cursor)) = (interaction, primary_window.cursor_position())
+    else {
+        *last_cursor = None;
+        return;
+    };
+    if minimap.scale <= 0. {
+        return;
+    }
+    // ui coordinates have y axis pointing down, minimap ones point up
+    let rect = node.logical_rect(transform);
+    let cursor = Vec2::new(
+        cursor.x - rect.min.x,
+        rect.max.y - (primary_window.height() - cursor.y),
+    );
+    let delta = match *last_cursor {
+        Some(last) => (last - cursor) / minimap.scale * zoom.0,
+        None => {
+            let panel_size = main_panel_query.single().size();
+            let point = cursor / minimap.scale + minimap.origin;
+            panel_size / 2. - world_to_canvas(point, &offset, &zoom)
+        }
+    };
+    if delta != Vec2::ZERO {
+        events.send(PanCanvasEvent { delta });
+    }
+    *last_cursor = Some(cursor);
+}
@@ -1,19 +1,23 @@
 use std::collections::HashMap;
 use std::fs::canonicalize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use bevy::prelude::*;
 use bevy::tasks::IoTaskPool;
+use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_cosmic_edit::{get_cosmic_text, ActiveEditor, CosmicEditImage};
 use bevy_pkv::PkvStore;
 use linkify::{LinkFinder, LinkKind};
 
 use super::ui_helpers::{ModalCancel, ModalConfirm, ModalTop};
-use super::{CommChannels, EditableText, ModalAction, TabContainer};
+use super::{
+    add_image_node, decrypt_requested_doc, load_doc_from_url, open_file, read_image_file,
+    set_passphrase, stored_encrypted_docs, CommChannels, EditableText, FileIo, ModalAction,
+    NotificationSender, PassphraseRequest, SetArrowRelationEvent, TabContainer,
+};
 use crate::components::Doc;
 use crate::resources::{AppState, LoadDocRequest, LoadTabRequest, SaveDocRequest};
 use crate::utils::ReflectableUuid;
-use crate::UiState;
+use crate::{AddRectEvent, UiState};
 
 pub fn cancel_modal(
     mut commands: Commands,
@@ -28,6 +32,9 @@ pub fn cancel_modal(
         if let Interaction::Clicked = interaction {
             for (entity, path_modal_top) in query.iter() {
                 if path_modal_cancel.id == path_modal_top.id {
+                    if let ModalAction::EnterPassphrase { .. } = path_modal_top.action {
+                        commands.remove_resource::<PassphraseRequest>();
+                    }
                     commands.entity(entity).despawn_recursive();
                     state.modal_id = None;
                 }
@@ -116,7 +123,6 @@ fn delete_tab(
     commands.insert_resource(LoadTabRequest {
         doc_id: current_document,
         tab_id: last_tab.id,
-        drop_last_checkpoint: false,
     });
 }
 
@@ -129,11 +135,10 @@ pub fn load_doc_handler(
     if comm_channels.rx.is_empty() {
         return;
     }
-    let r = comm_channels
+    let import_document = comm_channels
         .rx
         .try_recv()
-        .expect("Failed to receive document string");
-    let import_document: Doc = serde_json::from_str(&r).expect("Failed to deserialize document");
+        .expect("Failed to receive document");
     if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
         if docs.contains_key(&import_document.id) {
             return;
@@ -163,7 +168,17 @@ pub fn confirm_modal(
     input: Res<Input<KeyCode>>,
     mut query_path: Query<(&CosmicEditImage, &EditableText), With<EditableText>>,
     comm_channels: Res<CommChannels>,
+    mut file_io: ResMut<FileIo>,
+    mut images: ResMut<Assets<Image>>,
+    mut events: EventWriter<AddRectEvent>,
+    mut relation_events: EventWriter<SetArrowRelationEvent>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    passphrase_request: Option<Res<PassphraseRequest>>,
+    notification_sender: Res<NotificationSender>,
 ) {
+    let window = windows.single();
+    // same place as new rectangles
+    let center = Vec2::new(window.width() / 2. - 200., window.height() / 2.);
     for (interaction, path_modal_confirm) in interaction_query.iter_mut() {
         if let Interaction::Clicked = interaction {
             for (entity, path_modal_top) in query_top.iter() {
@@ -181,10 +196,12 @@ pub fn confirm_modal(
                                 }
                                 ModalAction::LoadFromFile => {
                                     if let Ok(path) = canonicalize(PathBuf::from(text.trim())) {
-                                        let json = std::fs::read_to_string(path)
-                                            .expect("Error reading document from file");
-                                        let cc = comm_channels.tx.clone();
-                                        cc.try_send(json).unwrap()
+                                        open_file(
+                                            &path,
+                                            &mut commands,
+                                            &mut app_state,
+                                            &mut file_io,
+                                        );
                                     }
                                 }
                                 ModalAction::LoadFromUrl => {
@@ -196,16 +213,37 @@ pub fn confirm_modal(
                                     if links.len() == 1 {
                                         let url = links.first().unwrap().as_str().to_owned();
                                         let cc = comm_channels.tx.clone();
+                                        let sender = notification_sender.clone();
                                         let task = pool.spawn(async move {
-                                            let request = ehttp::Request::get(url);
-                                            ehttp::fetch(request, move |result| {
-                                                let json_string = result.unwrap().text().unwrap();
-                                                cc.try_send(json_string).unwrap();
-                                            });
+                                            load_doc_from_url(url, cc, sender);
                                         });
                                         task.detach();
                                     }
                                 }
+                                ModalAction::InsertImage => {
+                                    if let Some(image) = read_image_file(Path::new(text.trim())) {
+                                        add_image_node(image, center, &mut images, &mut events);
+                                    }
+                                }
+                                ModalAction::ArrowRelation => {
+                                    relation_events.send(SetArrowRelationEvent {
+                                        relation: Some(text),
+                                    });
+                                }
+                                ModalAction::SetPassphrase => {
+                                    set_passphrase(&mut app_state, &text);
+                                }
+                                ModalAction::EnterPassphrase { .. } => {
+                                    if let Some(request) = &passphrase_request {
+                                        decrypt_requested_doc(
+                                            request,
+                                            &text,
+                                            &mut commands,
+                                            &mut app_state,
+                                            &comm_channels,
+                                        );
+                                    }
+                                }
                                 ModalAction::DeleteDocument => {}
                                 ModalAction::DeleteTab => {}
                             }
@@ -215,6 +253,10 @@ pub fn confirm_modal(
                         ModalAction::SaveToFile => {}
                         ModalAction::LoadFromFile => {}
                         ModalAction::LoadFromUrl => {}
+                        ModalAction::InsertImage => {}
+                        ModalAction::ArrowRelation => {}
+                        ModalAction::SetPassphrase => {}
+                        ModalAction::EnterPassphrase { .. } => {}
                         ModalAction::DeleteDocument => {
                             delete_doc(&mut app_state, &mut commands, &mut pkv);
                         }
@@ -245,10 +287,7 @@ pub fn confirm_modal(
                             }
                             ModalAction::LoadFromFile => {
                                 if let Ok(path) = canonicalize(PathBuf::from(text.trim())) {
-                                    let json = std::fs::read_to_string(path)
-                                        .expect("Error reading document from file");
-                                    let cc = comm_channels.tx.clone();
-                                    cc.try_send(json).unwrap()
+                                    open_file(&path, &mut commands, &mut app_state, &mut file_io);
                                 }
                             }
                             ModalAction::LoadFromUrl => {
@@ -260,16 +299,37 @@ pub fn confirm_modal(
                                 if links.len() == 1 {
                                     let url = links.first().unwrap().as_str().to_owned();
                                     let cc = comm_channels.tx.clone();
+                                    let sender = notification_sender.clone();
                                     let task = pool.spawn(async move {
-                                        let request = ehttp::Request::get(url);
-                                        ehttp::fetch(request, move |result| {
-                                            let json_string = result.unwrap().text().unwrap();
-                                            cc.try_send(json_string).unwrap();
-                                        });
+                                        load_doc_from_url(url, cc, sender);
                                     });
                                     task.detach();
                                 }
                             }
+                            ModalAction::InsertImage => {
+                                if let Some(image) = read_image_file(Path::new(text.trim())) {
+                                    add_image_node(image, center, &mut images, &mut events);
+                                }
+                            }
+                            ModalAction::ArrowRelation => {
+                                relation_events.send(SetArrowRelationEvent {
+                                    relation: Some(text),
+                                });
+                            }
+                            ModalAction::SetPassphrase => {
+                                set_passphrase(&mut app_state, &text);
+                            }
+                            ModalAction::EnterPassphrase { .. } => {
+                                if let Some(request) = &passphrase_request {
+                                    decrypt_requested_doc(
+                                        request,
+                                        &text,
+                                        &mut commands,
+                                        &mut app_state,
+                                        &comm_channels,
+                                    );
+                                }
+                            }
                             ModalAction::DeleteDocument => {}
                             ModalAction::DeleteTab => {}
                         }
@@ -279,6 +339,10 @@ pub fn confirm_modal(
                     ModalAction::SaveToFile => {}
                     ModalAction::LoadFromFile => {}
                     ModalAction::LoadFromUrl => {}
+                    ModalAction::InsertImage => {}
+                    ModalAction::ArrowRelation => {}
+                    ModalAction::SetPassphrase => {}
+                    ModalAction::EnterPassphrase { .. } => {}
                     ModalAction::DeleteDocument => {
                         delete_doc(&mut app_state, &mut commands, &mut pkv);
                     }
@@ -304,6 +368,10 @@ fn remove_from_storage(
             pkv.set("docs", &docs).unwrap();
         }
     }
+    let mut encrypted_docs = stored_encrypted_docs(pkv);
+    if encrypted_docs.remove(&id_to_remove).is_some() {
+        pkv.set("encrypted_docs", &encrypted_docs).unwrap();
+    }
     if let Ok(mut tags) = pkv.get::<HashMap<ReflectableUuid, Vec<String>>>("tags") {
         if tags.remove(&id_to_remove).is_some() {
             pkv.set("tags", &tags).unwrap();
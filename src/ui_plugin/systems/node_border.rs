@@ -0,0 +1,162 @@
+use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE, window::PrimaryWindow};
+
+use crate::utils::next_in_cycle;
+use crate::{BorderStyle, NodeBorder};
+
+use super::{
+    border_outline, has_image, rasterize_border,
+    ui_helpers::{
+        BorderChange, CanvasOutline, ChangeBorder, NodeBorderImage, NodeEffects, NodePart,
+    },
+    SelectedNodes, Theme, VeloNode, ROUNDED_FILL_IMAGE_HANDLE,
+};
+
+const BORDER_WIDTHS: [f32; 4] = [0., 1., 2., 4.];
+const BORDER_RADII: [f32; 3] = [0., 8., 16.];
+// Border images are drawn over the fill of the node, below its text
+const BORDER_IMAGE_Z: f32 = 0.1;
+const BORDER_COLORS: [Color; 5] = [
+    Color::rgb(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0),
+    Color::BLACK,
+    Color::GRAY,
+    Color::rgb(229.0 / 255.0, 57.0 / 255.0, 53.0 / 255.0),
+    Color::rgb(30.0 / 255.0, 136.0 / 255.0, 229.0 / 255.0),
+];
+
+pub fn change_border(
+    mut interaction_query: Query<
+        (&Interaction, &ChangeBorder),
+        (Changed<Interaction>, With<ChangeBorder>),
+    >,
+    mut nodes: Query<(&mut NodeBorder, &VeloNode), With<VeloNode>>,
+    selected_nodes: Res<SelectedNodes>,
+) {
+    for (interaction, change_border) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                let mut targets: Vec<_> = nodes
+                    .iter_mut()
+                    .filter(|(_, node)| selected_nodes.0.contains(&node.id))
+                    .map(|(border, _)| border)
+                    .collect();
+                let Some(first) = targets.first() else {
+                    continue;
+                };
+                // all nodes get the value following the one of the first node
+                let width = next_in_cycle(&BORDER_WIDTHS, first.width);
+                let radius = next_in_cycle(&BORDER_RADII, first.radius);
+                let color = next_in_cycle(&BORDER_COLORS, first.color);
+                for border in targets.iter_mut() {
+                    match change_border.change {
+                        BorderChange::Style(style) => {
+                            border.style = style;
+                            if border.width == 0. {
+                                border.width = 1.;
+                            }
+                        }
+                        BorderChange::Width => border.width = width,
+                        BorderChange::Radius => border.radius = radius,
+                        BorderChange::Color => border.color = color,
+                    }
+                }
+            }
+            Interaction::Hovered => {}
+            Interaction::None => {}
+        }
+    }
+}
+
+pub fn update_node_borders(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut nodes: Query<
+        (
+            Entity,
+            &VeloNode,
+            Ref<NodeBorder>,
+            Option<Ref<NodeEffects>>,
+            &mut CanvasOutline,
+            Ref<Sprite>,
+            &mut Handle<Image>,
+            Option<&Children>,
+        ),
+        With<VeloNode>,
+    >,
+    overlays: Query<&Handle<Image>, (With<NodeBorderImage>, Without<VeloNode>)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    selected_nodes: Res<SelectedNodes>,
+    theme: Res<Theme>,
+) {
+    let scale_factor = windows.single().scale_factor() as f32;
+    let fill_handle: Handle<Image> = ROUNDED_FILL_IMAGE_HANDLE.typed();
+    for (entity, velo_node, border, effects, mut outline, sprite, mut image, children) in
+        nodes.iter_mut()
+    {
+        let opacity = effects.as_ref().map_or(1., |e| e.opacity);
+        let effects_changed = effects.map_or(false, |e| e.is_changed());
+        // borders are drawn wider by high contrast themes
+        if !border.is_changed() && !effects_changed && !sprite.is_changed() && !theme.is_changed() {
+            continue;
+        }
+        // selected and edited nodes keep their highlight until deselected
+        let highlighted = selected_nodes.0.contains(&velo_node.id);
+        if (border.is_changed() || effects_changed) && !highlighted {
+            *outline = border_outline(&border, opacity);
+        }
+        let overlay = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| overlays.contains(**child))
+                .copied()
+        });
+        // nodes showing an image keep square corners of the image
+        let rounded_fill = border.radius > 0. && !has_image(&image);
+        if rounded_fill && *image != fill_handle {
+            *image = fill_handle.clone();
+        } else if !rounded_fill && *image == fill_handle {
+            *image = DEFAULT_IMAGE_HANDLE.typed();
+        }
+        let needs_overlay = rounded_fill
+            || (border.width > 0. && (border.style != BorderStyle::Solid || border.radius > 0.));
+        let size = sprite.custom_size.unwrap_or_default();
+        if !needs_overlay || size.x < 1. || size.y < 1. {
+            if let Some(overlay) = overlay {
+                commands.entity(overlay).despawn_recursive();
+            }
+            continue;
+        }
+        let drawn_border = NodeBorder {
+            width: theme.outline_width(border.width),
+            ..(*border).clone()
+        };
+        let texture = rasterize_border(
+            &drawn_border,
+            opacity,
+            rounded_fill.then_some(sprite.color),
+            size,
+            scale_factor,
+        );
+        match overlay {
+            Some(overlay) => {
+                let handle = overlays.get(overlay).unwrap();
+                if let Some(overlay_image) = images.get_mut(handle) {
+                    *overlay_image = texture;
+                }
+            }
+            None => {
+                let overlay = commands
+                    .spawn((
+                        SpriteBundle {
+                            texture: images.add(texture),
+                            ..default()
+                        },
+                        // below text and markers of the node
+                        NodePart::fill(BORDER_IMAGE_Z),
+                        NodeBorderImage,
+                    ))
+                    .id();
+                commands.entity(entity).add_child(overlay);
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+
+use crate::utils::next_in_cycle;
+use crate::{NodeBorder, NodeShadow};
+
+use super::{
+    has_border, node_shape_image, rasterize_shadow,
+    ui_helpers::{
+        ChangeNodeEffect, NodeEffect, NodeEffects, NodePart, NodeShadowImage, VeloNodeContainer,
+    },
+    SelectedNodes, VeloNode,
+};
+
+const NODE_SHADOWS: [Option<NodeShadow>; 3] = [
+    None,
+    Some(NodeShadow {
+        offset_x: 4.,
+        offset_y: 4.,
+        blur: 0.,
+        color: Color::rgba(0., 0., 0., 0.2),
+    }),
+    Some(NodeShadow {
+        offset_x: 0.,
+        offset_y: 6.,
+        blur: 12.,
+        color: Color::rgba(0., 0., 0., 0.35),
+    }),
+];
+const NODE_OPACITIES: [f32; 4] = [1., 0.75, 0.5, 0.25];
+// Shadows are drawn below the node
+const SHADOW_Z: f32 = -0.5;
+
+pub fn change_node_effect(
+    mut interaction_query: Query<
+        (&Interaction, &ChangeNodeEffect),
+        (Changed<Interaction>, With<ChangeNodeEffect>),
+    >,
+    mut nodes: Query<(&mut NodeEffects, &VeloNode), With<VeloNode>>,
+    selected_nodes: Res<SelectedNodes>,
+) {
+    for (interaction, change_effect) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                let mut targets: Vec<_> = nodes
+                    .iter_mut()
+                    .filter(|(_, node)| selected_nodes.0.contains(&node.id))
+                    .map(|(effects, _)| effects)
+                    .collect();
+                let Some(first) = targets.first() else {
+                    continue;
+                };
+                // all nodes get the value following the one of the first node
+                let shadow = next_in_cycle(&NODE_SHADOWS, first.shadow);
+                let opacity = next_in_cycle(&NODE_OPACITIES, first.opacity);
+                for effects in targets.iter_mut() {
+                    match change_effect.effect {
+                        NodeEffect::Shadow => effects.shadow = shadow,
+                        NodeEffect::Opacity => effects.opacity = opacity,
+                    }
+                }
+            }
+            Interaction::Hovered => {}
+            Interaction::None => {}
+        }
+    }
+}
+
+/// Draws node sprites in their color with their opacity applied, the text in them too.
+pub fn apply_node_opacity(
+    mut nodes: Query<
+        (&NodeEffects, &mut Sprite, Option<&Children>),
+        (
+            With<VeloNode>,
+            Or<(Changed<NodeEffects>, Changed<Children>)>,
+        ),
+    >,
+    children_query: Query<&Children, Without<VeloNode>>,
+    mut texts: Query<&mut Text>,
+) {
+    for (effects, mut sprite, children) in nodes.iter_mut() {
+        let color = effects.sprite_color();
+        if sprite.color != color {
+            sprite.color = color;
+        }
+        let opacity = effects.opacity;
+        let mut stack: Vec<Entity> = children.map_or(vec![], |c| c.to_vec());
+        while let Some(entity) = stack.pop() {
+            if let Ok(mut text) = texts.get_mut(entity) {
+                for section in text.sections.iter_mut() {
+                    section.style.color.set_a(opacity);
+                }
+            }
+            if let Ok(children) = children_query.get(entity) {
+                stack.extend(children.iter());
+            }
+        }
+    }
+}
+
+pub fn update_node_shadows(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    nodes: Query<
+        (
+            &VeloNode,
+            &NodeEffects,
+            Option<&NodeBorder>,
+            &Sprite,
+            &Parent,
+        ),
+        (
+            With<VeloNode>,
+            Or<(Changed<NodeEffects>, Changed<NodeBorder>, Changed<Sprite>)>,
+        ),
+    >,
+    containers: Query<&Children, With<VeloNodeContainer>>,
+    mut shadows: Query<
+        (&mut NodePart, &mut Sprite, &Handle<Image>),
+        (With<NodeShadowImage>, Without<VeloNode>),
+    >,
+) {
+    for (velo_node, effects, border, node_sprite, parent) in nodes.iter() {
+        let existing = containers.get(parent.get()).ok().and_then(|children| {
+            children
+                .iter()
+                .find(|child| shadows.contains(**child))
+                .copied()
+        });
+        let size = node_sprite.custom_size.unwrap_or_default();
+        let Some(shadow) = effects.shadow.filter(|_| size.x >= 1. && size.y >= 1.) else {
+            if let Some(existing) = existing {
+                commands.entity(existing).despawn_recursive();
+            }
+            continue;
+        };
+        // other shapes reuse their texture, which has no blurred version
+        let is_box = has_border(&velo_node.node_type);
+        let blur = if is_box { shadow.blur.max(0.) } else { 0. };
+        // the shadow is offset down by `offset_y`
+        let part = NodePart {
+            offset: Vec2::new(shadow.offset_x, -shadow.offset_y),
+            size: Vec2::splat(2. * blur),
+            grow: Vec2::ONE,
+            z: SHADOW_Z,
+            ..default()
+        };
+        let color = shadow.color.with_a(shadow.color.a() * effects.opacity);
+        let radius = border.map_or(0., |b| b.radius);
+        match existing {
+            Some(existing) => {
+                let (mut shadow_part, mut shadow_sprite, image) =
+                    shadows.get_mut(existing).unwrap();
+                if *shadow_part != part {
+                    *shadow_part = part;
+                }
+                shadow_sprite.color = color;
+                if is_box {
+                    if let Some(shadow_image) = images.get_mut(image) {
+                        *shadow_image = rasterize_shadow(size, blur, radius);
+                    }
+                }
+            }
+            None => {
+                let texture = if is_box {
+                    images.add(rasterize_shadow(size, blur, radius))
+                } else {
+                    node_shape_image(&velo_node.node_type, &asset_server).unwrap_or_default()
+                };
+                let shadow = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite { color, ..default() },
+                            texture,
+                            ..default()
+                        },
+                        part,
+                        NodeShadowImage,
+                    ))
+                    .id();
+                commands.entity(parent.get()).insert_children(0, &[shadow]);
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::utils::ReflectableUuid;
+
+use super::{
+    ui_helpers::{NodeRect, NodeRotation},
+    VeloNodeContainer,
+};
+
+// Side of the square cells of the grid, about the size of a node
+const CELL_SIZE: f32 = 256.;
+
+/// Bounds of the nodes in world coordinates in a grid of cells, so hit tests, rubber band selection and arrow
+/// routing look at the nodes nearby instead of all of them. Rotated nodes are kept with the
+/// bounds of their rotated rect. It follows moved, resized, rotated, spawned and despawned
+/// nodes, hidden ones are kept too.
+#[derive(Resource, Default)]
+pub struct NodeIndex {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+    nodes: HashMap<Entity, (ReflectableUuid, Rect)>,
+    entities: HashMap<ReflectableUuid, Entity>,
+}
+
+fn cells_of(rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+    let min = (rect.min / CELL_SIZE).floor();
+    let max = (rect.max / CELL_SIZE).floor();
+    (min.x as i32..=max.x as i32)
+        .flat_map(move |x| (min.y as i32..=max.y as i32).map(move |y| (x, y)))
+}
+
+impl NodeIndex {
+    pub fn insert(&mut self, entity: Entity, id: ReflectableUuid, rect: Rect) {
+        if self.nodes.get(&entity) == Some(&(id, rect)) {
+            return;
+        }
+        self.remove(entity);
+        for cell in cells_of(rect) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+        self.nodes.insert(entity, (id, rect));
+        self.entities.insert(id, entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some((id, rect)) = self.nodes.remove(&entity) else {
+            return;
+        };
+        for cell in cells_of(rect) {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|other| *other != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+        // a node respawned with the same id (e.g. on tab switch) keeps its new entity
+        if self.entities.get(&id) == Some(&entity) {
+            self.entities.remove(&id);
+        }
+    }
+
+    pub fn rect_of(&self, id: &ReflectableUuid) -> Option<Rect> {
+        let entity = self.entities.get(id)?;
+        self.nodes.get(entity).map(|(_, rect)| *rect)
+    }
+
+    /// Nodes overlapping `rect`, touching its edge isn't enough.
+    pub fn intersecting(&self, rect: Rect) -> Vec<(Entity, ReflectableUuid)> {
+        self.near(rect, |bounds| {
+            bounds.min.x < rect.max.x
+                && bounds.max.x > rect.min.x
+                && bounds.min.y < rect.max.y
+                && bounds.max.y > rect.min.y
+        })
+    }
+
+    /// Nodes containing `point`, edges included.
+    pub fn at(&self, point: Vec2) -> Vec<(Entity, ReflectableUuid)> {
+        self.near(Rect::from_corners(point, point), |bounds| {
+            bounds.contains(point)
+        })
+    }
+
+    fn near(&self, rect: Rect, hit: impl Fn(&Rect) -> bool) -> Vec<(Entity, ReflectableUuid)> {
+        let mut seen = HashSet::new();
+        cells_of(rect)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(|entity| seen.insert(**entity))
+            .filter_map(|entity| {
+                let (id, bounds) = self.nodes.get(entity)?;
+                hit(bounds).then_some((*entity, *id))
+            })
+            .collect()
+    }
+}
+
+/// Bounds of `rect` rotated clockwise by `degrees` around its center.
+fn rotated_bounds(rect: Rect, degrees: f32) -> Rect {
+    if degrees == 0. {
+        return rect;
+    }
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (sin, cos) = (sin.abs(), cos.abs());
+    let half = rect.half_size();
+    Rect::from_center_half_size(
+        rect.center(),
+        Vec2::new(half.x * cos + half.y * sin, half.x * sin + half.y * cos),
+    )
+}
+
+/// Keeps the bounds of moved, resized and rotated nodes up to date in the index.
+pub fn update_node_index(
+    mut node_index: ResMut<NodeIndex>,
+    containers: Query<
+        (Entity, &VeloNodeContainer, &NodeRect, Option<&NodeRotation>),
+        Or<(Changed<NodeRect>, Changed<NodeRotation>)>,
+    >,
+    mut removed: RemovedComponents<VeloNodeContainer>,
+) {
+    for entity in removed.iter() {
+        node_index.remove(entity);
+    }
+    for (entity, container, rect, rotation) in containers.iter() {
+        let degrees = rotation.map_or(0., |rotation| rotation.0);
+        node_index.insert(entity, container.id, rotated_bounds(rect.rect(), degrees));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_index() {
+        let (a, b) = (
+            ReflectableUuid(uuid::Uuid::from_u128(1)),
+            ReflectableUuid(uuid::Uuid::from_u128(2)),
+        );
+        let (node_a, node_b) = (Entity::from_raw(1), Entity::from_raw(2));
+        let mut index = NodeIndex::default();
+        index.insert(node_a, a, Rect::new(0., 0., 100., 50.));
+        // spans several cells
+        index.insert(node_b, b, Rect::new(200., -100., 600., 400.));
+        assert_eq!(index.at(Vec2::new(50., 50.)), vec![(node_a, a)]);
+        assert_eq!(index.at(Vec2::new(500., 300.)), vec![(node_b, b)]);
+        assert!(index.at(Vec2::new(150., 20.)).is_empty());
+        let mut hits = index.intersecting(Rect::new(50., 0., 250., 10.));
+        hits.sort_by_key(|(entity, _)| entity.index());
+        assert_eq!(hits, vec![(node_a, a), (node_b, b)]);
+        // touching edges don't overlap
+        assert!(index
+            .intersecting(Rect::new(100., 0., 150., 10.))
+            .is_empty());
+
+        // moved away, then despawned
+        index.insert(node_a, a, Rect::new(1000., 1000., 1100., 1050.));
+        assert!(index.at(Vec2::new(50., 25.)).is_empty());
+        assert_eq!(
+            index.rect_of(&a),
+            Some(Rect::new(1000., 1000., 1100., 1050.))
+        );
+        index.remove(node_a);
+        assert_eq!(index.rect_of(&a), None);
+        assert!(index.at(Vec2::new(1050., 1025.)).is_empty());
+    }
+
+    #[test]
+    fn test_rotated_bounds() {
+        let rect = Rect::new(0., 0., 100., 50.);
+        assert_eq!(rotated_bounds(rect, 0.), rect);
+        let quarter = rotated_bounds(rect, 90.);
+        assert!((quarter.min - Vec2::new(25., -25.)).length() < 1e-4);
+        assert!((quarter.max - Vec2::new(75., 75.)).length() < 1e-4);
+    }
+}
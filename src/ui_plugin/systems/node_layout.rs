@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+
+use bevy::{prelude::*, text::TextLayoutInfo, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicEditSprite;
+use bevy_markdown::BevyMarkdownRoot;
+use bevy_prototype_lyon::{
+    prelude::{GeometryBuilder, Path, ShapeBundle, Stroke},
+    shapes::{self, RectangleOrigin},
+};
+
+use super::ui_helpers::{
+    BevyMarkdownView, CanvasOutline, CanvasOutlineShape, Culled, CulledVisibility, FitToText,
+    NodePart, NodeRect, NodeZIndex, VeloNode, VeloNodeContainer,
+};
+use super::{CanvasZoom, TextPos, NODE_BASE_Z};
+use crate::canvas::arrow::components::ArrowConnect;
+
+// Outlines are drawn over the content of their sprite, below handles
+const OUTLINE_Z: f32 = 0.45;
+// Markdown views are drawn over the node background and its border image
+const MARKDOWN_Z: f32 = 0.3;
+// Text of chips and badges is drawn over their sprite
+const TEXT_Z: f32 = 0.01;
+
+/// Stacks nodes by their `NodeZIndex`, nodes with the same one in the order they were spawned.
+pub fn stack_nodes(
+    changed: Query<(), (Changed<NodeZIndex>, With<VeloNodeContainer>)>,
+    mut containers: Query<(Entity, &NodeZIndex, &mut Transform), With<VeloNodeContainer>>,
+) {
+    if changed.is_empty() {
+        return;
+    }
+    let mut order: Vec<(NodeZIndex, Entity)> = containers
+        .iter()
+        .map(|(entity, z_index, _)| (*z_index, entity))
+        .collect();
+    order.sort();
+    for (rank, (_, entity)) in order.into_iter().enumerate() {
+        let Ok((_, _, mut transform)) = containers.get_mut(entity) else {
+            continue;
+        };
+        let z = NODE_BASE_Z + rank as f32;
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
+
+/// Moves nodes to their `NodeRect` and lays out their parts for the node size and canvas zoom:
+/// sprites, the editor (rendered at the zoomed size, so text stays sharp) and the width the
+/// markdown view wraps at. Only the arrow connection markers of culled nodes are laid out, the
+/// rest once they're shown again.
+pub fn layout_nodes(
+    zoom: Res<CanvasZoom>,
+    mut containers: Query<
+        (Entity, Ref<NodeRect>, &mut Transform, Option<&Culled>),
+        With<VeloNodeContainer>,
+    >,
+    mut shown: RemovedComponents<Culled>,
+    changed_parts: Query<Entity, Changed<NodePart>>,
+    markers: Query<(), With<ArrowConnect>>,
+    parents: Query<&Parent>,
+    children_query: Query<&Children>,
+    mut parts: Query<
+        (
+            Option<&NodePart>,
+            &mut Transform,
+            Option<&mut Sprite>,
+            Option<&mut CosmicEditSprite>,
+            Option<&mut BevyMarkdownRoot>,
+        ),
+        Without<VeloNodeContainer>,
+    >,
+) {
+    // nodes whose parts were added or changed and nodes no longer culled
+    let mut dirty: HashSet<Entity> = changed_parts
+        .iter()
+        .filter_map(|part| {
+            parents
+                .iter_ancestors(part)
+                .find(|ancestor| containers.contains(*ancestor))
+        })
+        .chain(shown.iter())
+        .collect();
+    for (entity, rect, mut transform, _) in containers.iter_mut() {
+        if rect.is_changed() {
+            let center = rect.center();
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+        if rect.is_changed() || zoom.is_changed() {
+            dirty.insert(entity);
+        }
+    }
+    for node in dirty {
+        let Ok((_, rect, _, culled)) = containers.get(node) else {
+            continue;
+        };
+        let size = rect.size;
+        for descendant in children_query.iter_descendants(node) {
+            if culled.is_some() && !markers.contains(descendant) {
+                continue;
+            }
+            let Ok((part, mut transform, sprite, cosmic_edit, markdown)) =
+                parts.get_mut(descendant)
+            else {
+                continue;
+            };
+            if let Some(part) = part {
+                place_part(part, size, zoom.0, &mut transform, sprite);
+            }
+            if let Some(mut cosmic_edit) = cosmic_edit {
+                let render_size = size * zoom.0;
+                if cosmic_edit.size != render_size {
+                    cosmic_edit.size = render_size;
+                }
+            }
+            if let Some(mut markdown) = markdown {
+                let width = Some(size.x * zoom.0);
+                if markdown.width != width {
+                    markdown.width = width;
+                }
+            }
+        }
+    }
+}
+
+fn place_part(
+    part: &NodePart,
+    node_size: Vec2,
+    zoom: f32,
+    transform: &mut Transform,
+    sprite: Option<Mut<Sprite>>,
+) {
+    // screen sized parts are scaled down as much as the canvas is zoomed in
+    let scale = if part.screen_sized { 1. / zoom } else { 1. };
+    let placed =
+        Transform::from_translation((part.anchor * node_size + part.offset * scale).extend(part.z))
+            .with_rotation(transform.rotation)
+            .with_scale(Vec3::new(scale, scale, 1.));
+    if *transform != placed {
+        *transform = placed;
+    }
+    if let Some(mut sprite) = sprite {
+        let size = Some(part.size + part.grow * node_size / scale);
+        if sprite.custom_size != size {
+            sprite.custom_size = size;
+        }
+    }
+}
+
+/// Places the laid out markdown views of nodes, scaled down as much as their text is scaled up
+/// by canvas zoom: in the top left corner of the node or in its center.
+pub fn place_markdown_views(
+    zoom: Res<CanvasZoom>,
+    nodes: Query<(&TextPos, &Sprite), With<VeloNode>>,
+    mut views: Query<
+        (&BevyMarkdownRoot, &Parent, &mut Transform),
+        (With<BevyMarkdownView>, Without<CulledVisibility>),
+    >,
+) {
+    for (root, parent, mut transform) in views.iter_mut() {
+        let Ok((text_pos, sprite)) = nodes.get(parent.get()) else {
+            continue;
+        };
+        let node_size = sprite.custom_size.unwrap_or_default();
+        let view_size = root.size / zoom.0;
+        let top_left = match text_pos {
+            TextPos::TopLeft => Vec2::new(-node_size.x, node_size.y) / 2.,
+            TextPos::Center => Vec2::new(-view_size.x, view_size.y) / 2.,
+        };
+        let placed = Transform::from_translation(top_left.extend(MARKDOWN_Z))
+            .with_scale(Vec3::new(1. / zoom.0, 1. / zoom.0, 1.));
+        if *transform != placed {
+            *transform = placed;
+        }
+    }
+}
+
+/// Sizes `FitToText` sprites to their laid out text and centers the text in them. The size of
+/// sprites that are node parts is the size of the part. Sprites of culled nodes are left as they
+/// are.
+pub fn fit_to_text(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut fitted: Query<
+        (&FitToText, &Children, &mut Sprite, Option<&mut NodePart>),
+        Without<CulledVisibility>,
+    >,
+    mut texts: Query<(&TextLayoutInfo, &mut Transform)>,
+) {
+    let scale_factor = windows
+        .get_single()
+        .map_or(1., |window| window.scale_factor() as f32);
+    for (fit, children, mut sprite, part) in fitted.iter_mut() {
+        let Some((layout, mut text_transform)) =
+            children.iter().find_map(|child| texts.get_mut(*child).ok())
+        else {
+            continue;
+        };
+        // text is laid out in physical pixels
+        let size = layout.size / scale_factor + 2. * fit.padding;
+        // the anchor is the point of the sprite at its translation
+        let center = -sprite.anchor.as_vec() * size;
+        if text_transform.translation.truncate() != center {
+            text_transform.translation = center.extend(TEXT_Z);
+        }
+        match part {
+            Some(mut part) => {
+                if part.size != size {
+                    part.size = size;
+                }
+            }
+            None => {
+                if sprite.custom_size != Some(size) {
+                    sprite.custom_size = Some(size);
+                }
+            }
+        }
+    }
+}
+
+/// Draws `CanvasOutline`s as a stroke around the sprite they're on.
+pub fn draw_canvas_outlines(
+    mut commands: Commands,
+    zoom: Res<CanvasZoom>,
+    outlined: Query<(
+        Entity,
+        Ref<CanvasOutline>,
+        Ref<Sprite>,
+        Option<&NodePart>,
+        Option<&Children>,
+    )>,
+    mut shapes_query: Query<(&mut Path, &mut Stroke), With<CanvasOutlineShape>>,
+) {
+    for (entity, outline, sprite, part, children) in outlined.iter() {
+        if !outline.is_changed() && !sprite.is_changed() && !zoom.is_changed() {
+            continue;
+        }
+        // screen sized sprites are already scaled down
+        let scale = match part {
+            Some(part) if part.screen_sized => 1.,
+            _ => zoom.0,
+        };
+        let thickness = outline.thickness / scale;
+        let size = sprite.custom_size.unwrap_or_default() + Vec2::splat(thickness);
+        let path = GeometryBuilder::build_as(&shapes::Rectangle {
+            extents: size,
+            origin: RectangleOrigin::Center,
+        });
+        let stroke = Stroke::new(outline.color, thickness);
+        let shape = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| shapes_query.contains(**child))
+                .copied()
+        });
+        match shape {
+            Some(shape) => {
+                let Ok((mut shape_path, mut shape_stroke)) = shapes_query.get_mut(shape) else {
+                    continue;
+                };
+                *shape_path = path;
+                *shape_stroke = stroke;
+            }
+            None => {
+                let shape = commands
+                    .spawn((
+                        ShapeBundle {
+                            path,
+                            transform: Transform::from_xyz(0., 0., OUTLINE_Z),
+                            ..default()
+                        },
+                        stroke,
+                        CanvasOutlineShape,
+                    ))
+                    .id();
+                commands.entity(entity).add_child(shape);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_part() {
+        let node_size = Vec2::new(200., 100.);
+        let mut transform = Transform::default();
+        // marker in the middle of the top edge, a quarter of its size when zoomed in 4 times
+        let marker = NodePart::handle(Vec2::new(0., 0.5), Vec2::ZERO, Vec2::splat(18.), 1.);
+        place_part(&marker, node_size, 4., &mut transform, None);
+        assert_eq!(transform.translation, Vec3::new(0., 50., 1.));
+        assert_eq!(transform.scale, Vec3::new(0.25, 0.25, 1.));
+
+        let mut world = World::new();
+        let entity = world.spawn(Sprite::default()).id();
+        let mut query = world.query::<&mut Sprite>();
+        let sprite = query.get_mut(&mut world, entity).unwrap();
+        // shadow bigger than the node by its blur, offset to the right and down
+        let shadow = NodePart {
+            offset: Vec2::new(4., -4.),
+            size: Vec2::splat(16.),
+            grow: Vec2::ONE,
+            z: -0.5,
+            ..default()
+        };
+        place_part(&shadow, node_size, 4., &mut transform, Some(sprite));
+        assert_eq!(transform.translation, Vec3::new(4., -4., -0.5));
+        assert_eq!(transform.scale, Vec3::ONE);
+        assert_eq!(
+            world.get::<Sprite>(entity).unwrap().custom_size,
+            Some(Vec2::new(216., 116.))
+        );
+    }
+}
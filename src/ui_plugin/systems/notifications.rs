@@ -0,0 +1,320 @@
+use async_channel::{Receiver, Sender};
+use bevy::{asset::LoadState, prelude::*};
+use bevy_ui_borders::BorderColor;
+
+use super::ui_helpers::{
+    Localized, MainPanel, ThemedBackground, ThemedText, Toast, ToastDetails, ToastList,
+};
+use super::{Locale, Notification, NotificationLevel, Theme, ThemeColor};
+
+const TOAST_WIDTH: f32 = 320.;
+const FONT_SIZE: f32 = 14.;
+// The oldest toasts are dismissed when there are more
+const MAX_TOASTS: usize = 5;
+// Fonts loaded from the assets folder, the regular face of nodes is embedded
+const FONTS: [&str; 5] = [
+    "fonts/MaterialIcons-Regular.ttf",
+    "fonts/SourceCodePro-Bold.ttf",
+    "fonts/SourceCodePro-Italic.ttf",
+    "fonts/SourceCodePro-ExtraBold.ttf",
+    "fonts/SourceCodePro-SemiBoldItalic.ttf",
+];
+
+impl NotificationLevel {
+    /// Seconds the toast stays, errors stay longer.
+    fn duration(self) -> f32 {
+        match self {
+            NotificationLevel::Info => 4.,
+            NotificationLevel::Warn => 6.,
+            NotificationLevel::Error => 10.,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            NotificationLevel::Info => Color::rgb(33.0 / 255.0, 150.0 / 255.0, 243.0 / 255.0),
+            NotificationLevel::Warn => Color::rgb(245.0 / 255.0, 158.0 / 255.0, 11.0 / 255.0),
+            NotificationLevel::Error => Color::rgb(0.8, 0.1, 0.1),
+        }
+    }
+}
+
+/// Sends notifications from tasks and callbacks running outside of the systems, they are shown
+/// in the next frame.
+#[derive(Resource, Clone)]
+pub struct NotificationSender {
+    tx: Sender<Notification>,
+    rx: Receiver<Notification>,
+}
+
+impl Default for NotificationSender {
+    fn default() -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        Self { tx, rx }
+    }
+}
+
+impl NotificationSender {
+    pub fn send(&self, notification: Notification) {
+        let _ = self.tx.try_send(notification);
+    }
+}
+
+pub fn forward_notifications(
+    sender: Res<NotificationSender>,
+    mut notifications: EventWriter<Notification>,
+) {
+    while let Ok(notification) = sender.rx.try_recv() {
+        notifications.send(notification);
+    }
+}
+
+/// Sends the notification from systems which can't take an `EventWriter<Notification>`.
+pub fn notify(commands: &mut Commands, notification: Notification) {
+    commands.add(move |world: &mut World| {
+        world
+            .resource_mut::<Events<Notification>>()
+            .send(notification);
+    });
+}
+
+fn log_notification(notification: &Notification) {
+    // logged in English whatever the language of the UI
+    let mut message = Locale::default().format(&notification.message);
+    if let Some(details) = &notification.details {
+        message = format!("{}: {}", message, details);
+    }
+    match notification.level {
+        NotificationLevel::Info => info!("{}", message),
+        NotificationLevel::Warn => warn!("{}", message),
+        NotificationLevel::Error => error!("{}", message),
+    }
+}
+
+fn spawn_toast(builder: &mut ChildBuilder, notification: &Notification, theme: &Theme) {
+    let text_style = |color: Color| TextStyle {
+        font_size: FONT_SIZE,
+        color,
+        ..default()
+    };
+    let text_width = Size::width(Val::Px(TOAST_WIDTH - 20.));
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::width(Val::Px(TOAST_WIDTH)),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.)),
+                    margin: UiRect::top(Val::Px(6.)),
+                    border: UiRect::left(Val::Px(4.)),
+                    ..default()
+                },
+                background_color: theme.surface.into(),
+                ..default()
+            },
+            BorderColor(notification.level.color()),
+            ThemedBackground(ThemeColor::Surface),
+            Toast {
+                remaining: notification.level.duration(),
+                expanded: false,
+            },
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle {
+                    text: Text::from_section("", text_style(theme.text)),
+                    style: Style {
+                        max_size: text_width,
+                        ..default()
+                    },
+                    ..default()
+                },
+                notification.message.clone(),
+                ThemedText(ThemeColor::Text),
+            ));
+            if let Some(details) = &notification.details {
+                builder.spawn((
+                    TextBundle {
+                        text: Text::from_section(details.clone(), text_style(Color::GRAY)),
+                        style: Style {
+                            max_size: text_width,
+                            margin: UiRect::top(Val::Px(4.)),
+                            display: Display::None,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ToastDetails,
+                ));
+            }
+        });
+}
+
+/// Logs the notifications and shows a toast for each of them in the bottom left corner.
+pub fn show_notifications(
+    mut commands: Commands,
+    mut events: EventReader<Notification>,
+    theme: Res<Theme>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    list_query: Query<(Entity, Option<&Children>), With<ToastList>>,
+) {
+    let notifications: Vec<Notification> = events.iter().cloned().collect();
+    if notifications.is_empty() {
+        return;
+    }
+    for notification in notifications.iter() {
+        log_notification(notification);
+    }
+    let (list, shown) = match list_query.get_single() {
+        Ok((list, children)) => (list, children.map_or(vec![], |children| children.to_vec())),
+        Err(_) => {
+            let Ok(main_panel) = main_panel_query.get_single() else {
+                return;
+            };
+            let list = commands
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Px(10.),
+                                // above the status line of file saves and loads
+                                bottom: Val::Px(40.),
+                                ..default()
+                            },
+                            flex_direction: FlexDirection::Column,
+                            ..default()
+                        },
+                        z_index: ZIndex::Global(10),
+                        ..default()
+                    },
+                    ToastList,
+                ))
+                .id();
+            commands.entity(main_panel).add_child(list);
+            (list, vec![])
+        }
+    };
+    let excess = (shown.len() + notifications.len()).saturating_sub(MAX_TOASTS);
+    for toast in shown.iter().take(excess) {
+        commands.entity(*toast).despawn_recursive();
+    }
+    let skipped = excess.saturating_sub(shown.len());
+    commands.entity(list).with_children(|builder| {
+        for notification in notifications.iter().skip(skipped) {
+            spawn_toast(builder, notification, &theme);
+        }
+    });
+}
+
+/// A click shows or hides the details of a toast, toasts without details are dismissed.
+pub fn toast_click(
+    mut commands: Commands,
+    mut toasts: Query<(Entity, &Interaction, &mut Toast, &Children), Changed<Interaction>>,
+    mut details_query: Query<&mut Style, With<ToastDetails>>,
+) {
+    for (entity, interaction, mut toast, children) in toasts.iter_mut() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let details = children
+            .iter()
+            .find(|child| details_query.contains(**child));
+        let Some(details) = details else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        toast.expanded = !toast.expanded;
+        if let Ok(mut style) = details_query.get_mut(*details) {
+            style.display = if toast.expanded {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+    }
+}
+
+/// Notifies the fonts which couldn't be loaded, text using them isn't shown.
+pub fn check_fonts(
+    asset_server: Res<AssetServer>,
+    mut pending: Local<Option<Vec<(&'static str, Handle<Font>)>>>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let pending = pending.get_or_insert_with(|| {
+        FONTS
+            .into_iter()
+            .map(|path| (path, asset_server.load(path)))
+            .collect()
+    });
+    pending.retain(|(path, handle)| match asset_server.get_load_state(handle) {
+        LoadState::Failed => {
+            let message = Localized::new("Couldn't load the font {}").with_args([*path]);
+            notifications.send(Notification::error(message));
+            false
+        }
+        LoadState::Loaded => false,
+        _ => true,
+    });
+}
+
+/// Dismisses the toasts whose time is up, the ones showing their details stay.
+pub fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.expanded {
+            continue;
+        }
+        toast.remaining -= time.delta_seconds();
+        if toast.remaining <= 0. {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_notifications() {
+        let mut app = App::new();
+        app.add_event::<Notification>();
+        app.init_resource::<Theme>();
+        app.add_system(show_notifications);
+        app.world.spawn((NodeBundle::default(), MainPanel));
+
+        app.world.resource_mut::<Events<Notification>>().send(
+            Notification::error(Localized::new("Couldn't save the document"))
+                .with_details("Permission denied"),
+        );
+        app.update();
+        let mut toasts = app.world.query::<&Toast>();
+        assert_eq!(toasts.iter(&app.world).count(), 1);
+        assert!(!toasts.single(&app.world).expanded);
+        let mut details = app.world.query_filtered::<&Style, With<ToastDetails>>();
+        assert_eq!(details.single(&app.world).display, Display::None);
+
+        for i in 0..MAX_TOASTS + 2 {
+            let message = Localized::new("{}").with_args([i.to_string()]);
+            app.world
+                .resource_mut::<Events<Notification>>()
+                .send(Notification::info(message));
+        }
+        app.update();
+        assert_eq!(toasts.iter(&app.world).count(), MAX_TOASTS);
+        let mut messages = app
+            .world
+            .query_filtered::<&Localized, Without<ToastDetails>>();
+        let mut args: Vec<String> = messages
+            .iter(&app.world)
+            .flat_map(|localized| localized.args.clone())
+            .collect();
+        args.sort();
+        // the newest ones are kept
+        assert_eq!(args, vec!["2", "3", "4", "5", "6"]);
+    }
+}
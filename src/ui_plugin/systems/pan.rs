@@ -0,0 +1,66 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::{CanvasOffset, MainPanel, PanCanvasEvent, Tool, UiState, VeloNode};
+
+/// Pans canvas by dragging it with middle mouse button, with left button while Space is held or
+/// with the pan tool (over nodes as well), other systems (e.g. minimap) pan it with
+/// `PanCanvasEvent`.
+///
+/// Only `CanvasOffset` changes, the main camera follows it. The canvas has no bounds.
+pub fn pan_canvas(
+    buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    tool: Res<Tool>,
+    interaction_query: Query<
+        (&Interaction, Option<&VeloNode>),
+        (Changed<Interaction>, Or<(With<MainPanel>, With<VeloNode>)>),
+    >,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut pan_events: EventReader<PanCanvasEvent>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut offset: ResMut<CanvasOffset>,
+    mut last_cursor: Local<Option<Vec2>>,
+) {
+    let mut delta: Vec2 = pan_events.iter().map(|event| event.delta).sum();
+    let mut primary_window = windows.single_mut();
+    let space = input.pressed(KeyCode::Space) && ui_state.is_canvas_focused();
+    if last_cursor.is_none() {
+        let space_drag = space
+            && interaction_query
+                .iter()
+                .any(|(interaction, node)| *interaction == Interaction::Clicked && node.is_none());
+        let tool_drag = *tool == Tool::Pan
+            && interaction_query
+                .iter()
+                .any(|(interaction, _)| *interaction == Interaction::Clicked);
+        let middle_drag = buttons.just_pressed(MouseButton::Middle)
+            && primary_window.cursor_position().map_or(false, |cursor| {
+                main_panel_query.iter().any(|(node, transform)| {
+                    // ui coordinates have y axis pointing down
+                    let cursor = Vec2::new(cursor.x, primary_window.height() - cursor.y);
+                    node.logical_rect(transform).contains(cursor)
+                })
+            });
+        if (space_drag || tool_drag || middle_drag) && !ui_state.holding_node {
+            *last_cursor = primary_window.cursor_position();
+            primary_window.cursor.icon = CursorIcon::Grabbing;
+        }
+        cursor_moved_events.clear();
+    } else if !buttons.any_pressed([MouseButton::Left, MouseButton::Middle]) {
+        *last_cursor = None;
+        primary_window.cursor.icon = tool.cursor();
+    } else {
+        for event in cursor_moved_events.iter() {
+            if let Some(last) = *last_cursor {
+                delta += event.position - last;
+            }
+            *last_cursor = Some(event.position);
+        }
+    }
+    if delta == Vec2::ZERO {
+        return;
+    }
+    offset.0 += delta;
+}
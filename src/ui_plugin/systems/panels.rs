@@ -0,0 +1,281 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use super::ui_helpers::{
+    CanvasPanels, DockablePanel, LeftPanel, LeftPanelChevron, LeftPanelEdge, RightPanel,
+};
+use super::Settings;
+
+// Width of the left panel in percent of the window
+const MIN_LEFT_WIDTH: f32 = 8.;
+const MAX_LEFT_WIDTH: f32 = 40.;
+// Only the edge with the chevron is left of a collapsed panel
+const COLLAPSED_WIDTH: f32 = 1.;
+const DOCKED_RIGHT_WIDTH: f32 = 220.;
+const CHEVRON_LEFT: &str = "\u{e5cb}";
+const CHEVRON_RIGHT: &str = "\u{e5cc}";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub enum DockSide {
+    /// In the left panel
+    #[default]
+    Left,
+    /// In the top right corner of the canvas, with the properties and history panels
+    Right,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DockedPanel {
+    /// Shapes, colors, arrows and the other tools adding and styling nodes
+    Add,
+    /// Search box and document list
+    Search,
+    Tags,
+}
+
+impl DockedPanel {
+    pub const ALL: [DockedPanel; 3] = [DockedPanel::Add, DockedPanel::Search, DockedPanel::Tags];
+
+    /// Share of the height of the left panel among the panels docked in it.
+    fn weight(self) -> f32 {
+        match self {
+            DockedPanel::Add => 4.,
+            DockedPanel::Search => 6.,
+            DockedPanel::Tags => 3.,
+        }
+    }
+
+    /// Height in the corner of the canvas, the tags panel grows with its list.
+    fn docked_right_height(self) -> Val {
+        match self {
+            DockedPanel::Add => Val::Px(320.),
+            DockedPanel::Search => Val::Px(360.),
+            DockedPanel::Tags => Val::Auto,
+        }
+    }
+}
+
+/// Width of the left panel and the side each dockable panel is on, kept in the settings.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct PanelLayout {
+    /// Percent of the window
+    pub left_width: f32,
+    pub left_collapsed: bool,
+    pub add: DockSide,
+    pub search: DockSide,
+    pub tags: DockSide,
+}
+
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self {
+            left_width: 15.,
+            left_collapsed: false,
+            add: DockSide::Left,
+            search: DockSide::Left,
+            tags: DockSide::Right,
+        }
+    }
+}
+
+impl PanelLayout {
+    pub fn side(&self, panel: DockedPanel) -> DockSide {
+        match panel {
+            DockedPanel::Add => self.add,
+            DockedPanel::Search => self.search,
+            DockedPanel::Tags => self.tags,
+        }
+    }
+
+    pub fn set_side(&mut self, panel: DockedPanel, side: DockSide) {
+        match panel {
+            DockedPanel::Add => self.add = side,
+            DockedPanel::Search => self.search = side,
+            DockedPanel::Tags => self.tags = side,
+        }
+    }
+
+    /// Width of the left panel in percent of the window, it's hidden when no panel is docked in
+    /// it.
+    pub fn left_panel_width(&self) -> f32 {
+        if !DockedPanel::ALL
+            .iter()
+            .any(|panel| self.side(*panel) == DockSide::Left)
+        {
+            0.
+        } else if self.left_collapsed {
+            COLLAPSED_WIDTH
+        } else {
+            self.left_width
+        }
+    }
+}
+
+/// Dragging the edge of the left panel resizes it, the width is kept in the settings when the
+/// edge is released.
+pub fn resize_left_panel(
+    edges: Query<&Interaction, With<LeftPanelEdge>>,
+    hovered_edges: Query<&Interaction, (Changed<Interaction>, With<LeftPanelEdge>)>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut panels: Query<(&mut Style, Option<&LeftPanel>), Or<(With<LeftPanel>, With<RightPanel>)>>,
+    mut settings: ResMut<Settings>,
+    mut dragged_width: Local<Option<f32>>,
+) {
+    let mut primary_window = windows.single_mut();
+    for interaction in hovered_edges.iter() {
+        primary_window.cursor.icon = match interaction {
+            Interaction::None => CursorIcon::Default,
+            _ => CursorIcon::EwResize,
+        };
+    }
+    if !edges
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        if let Some(width) = dragged_width.take() {
+            settings.panels.left_width = width;
+            settings.panels.left_collapsed = false;
+        }
+        return;
+    }
+    let Some(cursor) = primary_window.cursor_position() else {
+        return;
+    };
+    let width = (cursor.x / primary_window.width() * 100.).clamp(MIN_LEFT_WIDTH, MAX_LEFT_WIDTH);
+    if *dragged_width == Some(width) {
+        return;
+    }
+    *dragged_width = Some(width);
+    for (mut style, left_panel) in panels.iter_mut() {
+        style.size.width = Val::Percent(if left_panel.is_some() {
+            width
+        } else {
+            100. - width
+        });
+    }
+}
+
+/// The chevron on the edge of the left panel collapses or expands it.
+pub fn toggle_left_panel(
+    interactions: Query<&Interaction, (Changed<Interaction>, With<LeftPanelChevron>)>,
+    mut settings: ResMut<Settings>,
+) {
+    if interactions
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        settings.panels.left_collapsed = !settings.panels.left_collapsed;
+    }
+}
+
+/// Moves the dockable panels to the side the settings put them on and sizes the left panel,
+/// when the settings change.
+pub fn apply_panel_layout(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    new_panels: Query<(), Added<DockablePanel>>,
+    mut panels: Query<
+        (Entity, &mut Style, Option<&LeftPanel>),
+        Or<(With<LeftPanel>, With<RightPanel>)>,
+    >,
+    mut docked: Query<
+        (Entity, &DockablePanel, &mut Style),
+        (Without<LeftPanel>, Without<RightPanel>),
+    >,
+    canvas_panels_query: Query<Entity, With<CanvasPanels>>,
+    chevrons: Query<&Children, With<LeftPanelChevron>>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() && new_panels.is_empty() {
+        return;
+    }
+    let layout = &settings.panels;
+    let left_width = layout.left_panel_width();
+    let mut left_panel = None;
+    for (entity, mut style, is_left) in panels.iter_mut() {
+        if is_left.is_some() {
+            left_panel = Some(entity);
+            style.size.width = Val::Percent(left_width);
+            style.display = if left_width > 0. {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        } else {
+            style.size.width = Val::Percent(100. - left_width);
+        }
+    }
+    let (Some(left_panel), Ok(canvas_panels)) = (left_panel, canvas_panels_query.get_single())
+    else {
+        return;
+    };
+
+    let total_weight: f32 = DockedPanel::ALL
+        .iter()
+        .filter(|panel| layout.side(**panel) == DockSide::Left)
+        .map(|panel| panel.weight())
+        .sum();
+    let mut left = vec![];
+    let mut right = vec![];
+    for panel in DockedPanel::ALL {
+        let Some((entity, _, mut style)) = docked.iter_mut().find(|(_, p, _)| p.0 == panel) else {
+            continue;
+        };
+        match layout.side(panel) {
+            DockSide::Left => {
+                let height = panel.weight() / total_weight * 100.;
+                style.size = Size::new(Val::Percent(100.), Val::Percent(height));
+                style.margin = UiRect::default();
+                style.display = if layout.left_collapsed {
+                    Display::None
+                } else {
+                    Display::Flex
+                };
+                left.push(entity);
+            }
+            DockSide::Right => {
+                style.size = Size::new(Val::Px(DOCKED_RIGHT_WIDTH), panel.docked_right_height());
+                style.margin = UiRect::top(Val::Px(10.));
+                style.display = Display::Flex;
+                right.push(entity);
+            }
+        }
+    }
+    commands.entity(left_panel).push_children(&left);
+    // below the properties panel, above the history panel
+    commands.entity(canvas_panels).insert_children(1, &right);
+
+    let icon = if layout.left_collapsed {
+        CHEVRON_RIGHT
+    } else {
+        CHEVRON_LEFT
+    };
+    for children in chevrons.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = icon.to_string();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_left_panel_width() {
+        let mut layout = PanelLayout::default();
+        assert_eq!(layout.left_panel_width(), 15.);
+        layout.left_collapsed = true;
+        assert_eq!(layout.left_panel_width(), COLLAPSED_WIDTH);
+        layout.set_side(DockedPanel::Add, DockSide::Right);
+        layout.set_side(DockedPanel::Search, DockSide::Right);
+        assert_eq!(layout.side(DockedPanel::Search), DockSide::Right);
+        // nothing is docked in the left panel
+        assert_eq!(layout.left_panel_width(), 0.);
+        layout.set_side(DockedPanel::Tags, DockSide::Left);
+        assert_eq!(layout.left_panel_width(), COLLAPSED_WIDTH);
+    }
+}
@@ -0,0 +1,425 @@
+use bevy::{
+    input::touch::{ForceTouch, Touches},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_prototype_lyon::{
+    prelude::{GeometryBuilder, LineCap, LineJoin, Path, ShapeBundle, Stroke, StrokeOptions},
+    shapes,
+};
+
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
+
+use super::{
+    ui_helpers::{ChangeColor, MainPanel, PenStroke},
+    CanvasZoom, PenTool, SelectedStrokes, Theme, Tool, UiState,
+};
+
+const MIN_PEN_WIDTH: f32 = 0.5;
+const MAX_PEN_WIDTH: f32 = 32.;
+// Factor of one `[`/`]` press
+const PEN_WIDTH_STEP: f32 = 1.5;
+// Points closer than that to the previous one are skipped while drawing
+const MIN_POINT_DISTANCE: f32 = 1.;
+// Max deviation of the simplified stroke from the drawn one
+const SIMPLIFY_EPSILON: f32 = 1.;
+// Clicks this far from a stroke still hit it
+const HIT_TOLERANCE: f32 = 4.;
+const SELECTED_STROKE_COLOR: Color = Color::rgb(33.0 / 255.0, 150.0 / 255.0, 243.0 / 255.0);
+
+/// Stroke or erasing in progress.
+pub enum PenGesture {
+    Drawing { entity: Entity, pressures: Vec<f32> },
+    Erasing,
+}
+
+/// Drops points of the polyline deviating less than `epsilon` from the line between their
+/// neighbours (Ramer–Douglas–Peucker).
+pub fn simplify_polyline(points: &[Vec2], epsilon: f32) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let first = points[0];
+    let last = points[points.len() - 1];
+    let (index, distance) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, point)| (i + 1, distance_to_segment(*point, first, last)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    if distance <= epsilon {
+        return vec![first, last];
+    }
+    let mut result = simplify_polyline(&points[..=index], epsilon);
+    result.pop();
+    result.extend(simplify_polyline(&points[index..], epsilon));
+    result
+}
+
+pub fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared == 0. {
+        return point.distance(start);
+    }
+    let t = ((point - start).dot(segment) / length_squared).clamp(0., 1.);
+    point.distance(start + t * segment)
+}
+
+pub fn distance_to_polyline(point: Vec2, points: &[Vec2]) -> f32 {
+    match points {
+        [] => f32::INFINITY,
+        [single] => point.distance(*single),
+        _ => points
+            .windows(2)
+            .map(|segment| distance_to_segment(point, segment[0], segment[1]))
+            .fold(f32::INFINITY, f32::min),
+    }
+}
+
+fn pressure(force: ForceTouch) -> f32 {
+    match force {
+        ForceTouch::Calibrated {
+            force,
+            max_possible_force,
+            ..
+        } => (force / max_possible_force) as f32,
+        ForceTouch::Normalized(force) => force as f32,
+    }
+}
+
+/// Window position (y axis pointing up) of the pointer held down, with the touch pressure if
+/// the device reports it.
+fn pointer(
+    window: &Window,
+    buttons: &Input<MouseButton>,
+    touches: &Touches,
+) -> Option<(Vec2, Option<f32>)> {
+    // touch positions are from the top left corner of the window
+    if let Some(touch) = touches.iter().next() {
+        let position = Vec2::new(touch.position().x, window.height() - touch.position().y);
+        return Some((position, touch.force().map(pressure)));
+    }
+    if buttons.pressed(MouseButton::Left) {
+        return window.cursor_position().map(|position| (position, None));
+    }
+    None
+}
+
+/// Stroke closest to the world `point`, within `tolerance` of its edge.
+fn stroke_at(
+    point: Vec2,
+    tolerance: f32,
+    strokes: impl Iterator<Item = (Entity, ReflectableUuid, f32, Vec<Vec2>)>,
+) -> Option<(Entity, ReflectableUuid)> {
+    strokes
+        .map(|(entity, id, width, points)| {
+            let distance = distance_to_polyline(point, &points) - width / 2.;
+            (distance, entity, id)
+        })
+        .filter(|(distance, _, _)| *distance <= tolerance)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, entity, id)| (entity, id))
+}
+
+#[allow(unused)]
+pub fn remove_shape(commands: &mut Commands, entity: Entity, visibility: &mut Visibility) {
+    // deleted shapes are hidden on wasm like arrows
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(entity).despawn_recursive();
+    #[cfg(target_arch = "wasm32")]
+    {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+pub fn spawn_stroke(commands: &mut Commands, stroke: PenStroke) -> Entity {
+    commands
+        .spawn((
+            ShapeBundle::default(),
+            Stroke::new(stroke.color, stroke.width),
+            stroke,
+        ))
+        .id()
+}
+
+/// `[`/`]` change the pen width while the pen is the active tool. Palette colors set the pen
+/// color and recolor the selected strokes.
+pub fn adjust_pen(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    tool: Res<Tool>,
+    color_buttons: Query<(&Interaction, &ChangeColor), Changed<Interaction>>,
+    selected_strokes: Res<SelectedStrokes>,
+    mut strokes: Query<&mut PenStroke>,
+    mut pen: ResMut<PenTool>,
+) {
+    for (interaction, change_color) in color_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        pen.color = change_color.color;
+        for mut stroke in strokes.iter_mut() {
+            if selected_strokes.0.contains(&stroke.id) {
+                stroke.color = change_color.color;
+            }
+        }
+    }
+    let modifier = input.any_pressed([
+        KeyCode::RWin,
+        KeyCode::LWin,
+        KeyCode::RControl,
+        KeyCode::LControl,
+        KeyCode::LAlt,
+        KeyCode::RAlt,
+    ]);
+    if !modifier && ui_state.is_canvas_focused() && *tool == Tool::Pen {
+        if input.just_pressed(KeyCode::BracketLeft) {
+            pen.width = (pen.width / PEN_WIDTH_STEP).max(MIN_PEN_WIDTH);
+        } else if input.just_pressed(KeyCode::BracketRight) {
+            pen.width = (pen.width * PEN_WIDTH_STEP).min(MAX_PEN_WIDTH);
+        }
+    }
+}
+
+/// With the pen tool dragging on the canvas (mouse or touch) draws a stroke, with the eraser
+/// tool it erases strokes under the pointer. Finished strokes are simplified and their width
+/// follows the average touch pressure when the device reports it. Points are in world
+/// coordinates, skipped and simplified by their distance on screen.
+pub fn draw_pen_strokes(
+    mut commands: Commands,
+    pen: Res<PenTool>,
+    theme: Res<Theme>,
+    tool: Res<Tool>,
+    zoom: Res<CanvasZoom>,
+    buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    touches: Res<Touches>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut strokes: Query<(Entity, &mut PenStroke, &mut Visibility)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut gesture: Local<Option<PenGesture>>,
+) {
+    let window = windows.single();
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
+    let pointer = pointer(window, &buttons, &touches).and_then(|(position, pressure)| {
+        let point = camera.viewport_to_world_2d(camera_transform, position)?;
+        Some((point, pressure))
+    });
+
+    if gesture.is_none() {
+        let pressed = interaction_query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Clicked);
+        // Space+drag pans the canvas instead
+        let drawing = *tool == Tool::Pen || *tool == Tool::Eraser;
+        if !drawing || !pressed || input.pressed(KeyCode::Space) {
+            return;
+        }
+        let Some((point, _)) = pointer else {
+            return;
+        };
+        *gesture = Some(if *tool == Tool::Eraser {
+            PenGesture::Erasing
+        } else {
+            let entity = spawn_stroke(
+                &mut commands,
+                PenStroke {
+                    id: ReflectableUuid::generate(),
+                    points: vec![point],
+                    color: pen.color,
+                    width: pen.width,
+                },
+            );
+            PenGesture::Drawing {
+                entity,
+                pressures: vec![],
+            }
+        });
+    }
+
+    match (gesture.as_mut(), pointer) {
+        (Some(PenGesture::Erasing), Some((point, _))) => {
+            let hit = stroke_at(
+                point,
+                HIT_TOLERANCE * theme.hit_target_scale / zoom.0,
+                strokes
+                    .iter()
+                    .filter(|(_, _, visibility)| **visibility != Visibility::Hidden)
+                    .map(|(entity, stroke, _)| {
+                        (entity, stroke.id, stroke.width, stroke.points.clone())
+                    }),
+            );
+            if let Some((entity, _)) = hit {
+                if let Ok((_, _, mut visibility)) = strokes.get_mut(entity) {
+                    remove_shape(&mut commands, entity, &mut visibility);
+                }
+            }
+        }
+        (Some(PenGesture::Drawing { entity, pressures }), Some((point, pressure))) => {
+            // the stroke is spawned with commands, it's there from the next frame
+            let Ok((_, mut stroke, _)) = strokes.get_mut(*entity) else {
+                return;
+            };
+            if let Some(pressure) = pressure {
+                pressures.push(pressure);
+            }
+            if stroke.points.last().map_or(true, |last| {
+                last.distance(point) >= MIN_POINT_DISTANCE / zoom.0
+            }) {
+                stroke.points.push(point);
+            }
+        }
+        (Some(PenGesture::Drawing { entity, pressures }), None) => {
+            if let Ok((entity, mut stroke, mut visibility)) = strokes.get_mut(*entity) {
+                if stroke.points.len() < 2 {
+                    remove_shape(&mut commands, entity, &mut visibility);
+                } else {
+                    stroke.points = simplify_polyline(&stroke.points, SIMPLIFY_EPSILON / zoom.0);
+                    if !pressures.is_empty() {
+                        let average = pressures.iter().sum::<f32>() / pressures.len() as f32;
+                        stroke.width = pen.width * (average * 2.).max(0.25);
+                    }
+                }
+            }
+            *gesture = None;
+        }
+        (_, None) => {
+            *gesture = None;
+        }
+        (None, Some(_)) => {}
+    }
+}
+
+/// Clicking a stroke with the select tool selects it, Shift adds it to the selection and
+/// Delete/Backspace removes the selected strokes.
+pub fn select_pen_strokes(
+    mut commands: Commands,
+    tool: Res<Tool>,
+    theme: Res<Theme>,
+    zoom: Res<CanvasZoom>,
+    ui_state: Res<UiState>,
+    input: Res<Input<KeyCode>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut strokes: Query<(Entity, &PenStroke, &mut Visibility)>,
+    mut selected_strokes: ResMut<SelectedStrokes>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    if ui_state.is_canvas_focused()
+        && !selected_strokes.0.is_empty()
+        && input.any_just_pressed([KeyCode::Delete, KeyCode::Back])
+    {
+        for (entity, stroke, mut visibility) in strokes.iter_mut() {
+            if selected_strokes.0.contains(&stroke.id) {
+                remove_shape(&mut commands, entity, &mut visibility);
+            }
+        }
+        selected_strokes.0.clear();
+        return;
+    }
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked || *tool != Tool::Select || input.pressed(KeyCode::Space) {
+        return;
+    }
+    let (camera, camera_transform) = camera_q.single();
+    let Some(point) = windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let hit = stroke_at(
+        point,
+        HIT_TOLERANCE * theme.hit_target_scale / zoom.0,
+        strokes
+            .iter()
+            .filter(|(_, _, visibility)| **visibility != Visibility::Hidden)
+            .map(|(entity, stroke, _)| (entity, stroke.id, stroke.width, stroke.points.clone())),
+    );
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    match hit {
+        Some((_, id)) if shift => {
+            if selected_strokes.0.contains(&id) {
+                selected_strokes.0.retain(|selected| *selected != id);
+            } else {
+                selected_strokes.0.push(id);
+            }
+        }
+        Some((_, id)) => selected_strokes.0 = vec![id],
+        None if shift => {}
+        None => {
+            if !selected_strokes.0.is_empty() {
+                selected_strokes.0.clear();
+            }
+        }
+    }
+}
+
+/// Rebuilds stroke shapes when strokes or their selection change.
+pub fn update_pen_paths(
+    selected_strokes: Res<SelectedStrokes>,
+    mut strokes: Query<(Ref<PenStroke>, &mut Path, &mut Stroke)>,
+) {
+    for (pen_stroke, mut path, mut stroke) in strokes.iter_mut() {
+        if !pen_stroke.is_changed() && !selected_strokes.is_changed() {
+            continue;
+        }
+        *path = GeometryBuilder::new()
+            .add(&shapes::Polygon {
+                points: pen_stroke.points.clone(),
+                closed: false,
+            })
+            .build();
+        let color = if selected_strokes.0.contains(&pen_stroke.id) {
+            SELECTED_STROKE_COLOR
+        } else {
+            pen_stroke.color
+        };
+        *stroke = Stroke {
+            color,
+            options: StrokeOptions::default()
+                .with_line_width(pen_stroke.width)
+                .with_line_cap(LineCap::Round)
+                .with_line_join(LineJoin::Round),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplify_polyline() {
+        let points = [
+            Vec2::new(0., 0.),
+            Vec2::new(1., 0.2),
+            Vec2::new(2., -0.1),
+            Vec2::new(3., 0.),
+            Vec2::new(3.1, 2.),
+            Vec2::new(3., 4.),
+        ];
+        assert_eq!(
+            simplify_polyline(&points, 0.5),
+            vec![Vec2::new(0., 0.), Vec2::new(3., 0.), Vec2::new(3., 4.)]
+        );
+        assert_eq!(simplify_polyline(&points[..2], 0.5), points[..2].to_vec());
+    }
+
+    #[test]
+    fn test_distance_to_polyline() {
+        let points = [Vec2::new(0., 0.), Vec2::new(10., 0.), Vec2::new(10., 10.)];
+        assert_eq!(distance_to_polyline(Vec2::new(5., 3.), &points), 3.);
+        assert_eq!(distance_to_polyline(Vec2::new(12., 5.), &points), 2.);
+        assert_eq!(distance_to_polyline(Vec2::new(-4., 3.), &points), 5.);
+        assert_eq!(distance_to_polyline(Vec2::ZERO, &[]), f32::INFINITY);
+    }
+}
@@ -0,0 +1,408 @@
+use bevy::{
+    prelude::*,
+    sprite::Anchor,
+    window::{PrimaryWindow, WindowMode},
+};
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::utils::ReflectableUuid;
+
+use super::{
+    ui_helpers::{
+        BottomPanel, CanvasPanels, FitToText, LeftPanel, MainBottom, MainPanel, Menu, Minimap,
+        NodePart, NodeRect, PresentationBadge, PresentationButton, PresentationStep, RightPanel,
+        VeloNode,
+    },
+    world_to_canvas, CanvasOffset, CanvasZoom, ContextMenuAction, ContextMenuEvent, MotionSettings,
+    Presentation, SelectedNodes, Tool, UiState, VeloNodeContainer, ZoomCanvasEvent, MAX_ZOOM,
+    MIN_ZOOM,
+};
+
+pub const ADD_TO_PRESENTATION_ACTION: &str = "add_to_presentation";
+pub const REMOVE_FROM_PRESENTATION_ACTION: &str = "remove_from_presentation";
+// Badges are drawn over the node and its handles
+const BADGE_Z: f32 = 0.75;
+// Share of the canvas taken by the presented node
+const PRESENTATION_FILL: f32 = 0.6;
+// How fast transitions between nodes ease out, higher is faster
+const TRANSITION_SPEED: f32 = 6.;
+
+/// Nodes in reading order of the arrows: starting from nodes without incoming arrows (top to
+/// bottom, left to right), each node is followed by the nodes its arrows lead to. Nodes only
+/// reachable through cycles come last.
+///
+/// `nodes` holds node ids with their centers, `edges` holds `(start node id, end node id)`.
+pub fn arrow_order(
+    nodes: &[(ReflectableUuid, Vec2)],
+    edges: &[(ReflectableUuid, ReflectableUuid)],
+) -> Vec<ReflectableUuid> {
+    let mut sorted = nodes.to_vec();
+    // canvas positions have y axis pointing up
+    sorted.sort_by(|a, b| b.1.y.total_cmp(&a.1.y).then(a.1.x.total_cmp(&b.1.x)));
+    let mut roots: Vec<ReflectableUuid> = sorted
+        .iter()
+        .filter(|(id, _)| !edges.iter().any(|(start, end)| end == id && start != id))
+        .map(|(id, _)| *id)
+        .collect();
+    roots.extend(sorted.iter().map(|(id, _)| *id));
+    let mut result: Vec<ReflectableUuid> = vec![];
+    for root in roots {
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if result.contains(&id) {
+                continue;
+            }
+            result.push(id);
+            // the first child in reading order is visited first
+            for (child, _) in sorted.iter().rev() {
+                if edges.contains(&(id, *child)) && !result.contains(child) {
+                    stack.push(*child);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Adds the selected nodes to the end of the presentation path or removes them from it.
+pub fn edit_presentation_path(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    selected_nodes: Res<SelectedNodes>,
+    containers: Query<(Entity, &VeloNodeContainer, Option<&PresentationStep>)>,
+) {
+    for event in events.iter() {
+        let add = event.action == ContextMenuAction::Custom(ADD_TO_PRESENTATION_ACTION);
+        let remove = event.action == ContextMenuAction::Custom(REMOVE_FROM_PRESENTATION_ACTION);
+        if !add && !remove {
+            continue;
+        }
+        let mut steps: Vec<_> = containers
+            .iter()
+            .filter_map(|(entity, container, step)| step.map(|step| (*step, entity, container.id)))
+            .collect();
+        steps.sort_by_key(|(step, ..)| *step);
+        let mut path: Vec<(Entity, ReflectableUuid)> = steps
+            .into_iter()
+            .map(|(_, entity, id)| (entity, id))
+            .collect();
+        if add {
+            for id in selected_nodes.0.iter() {
+                if path.iter().any(|(_, node)| node == id) {
+                    continue;
+                }
+                if let Some((entity, _, _)) = containers
+                    .iter()
+                    .find(|(_, container, _)| container.id == *id)
+                {
+                    path.push((entity, *id));
+                }
+            }
+        } else {
+            for (entity, id) in path.iter() {
+                if selected_nodes.0.contains(id) {
+                    commands.entity(*entity).remove::<PresentationStep>();
+                }
+            }
+            path.retain(|(_, id)| !selected_nodes.0.contains(id));
+        }
+        for (step, (entity, _)) in path.iter().enumerate() {
+            commands
+                .entity(*entity)
+                .insert(PresentationStep(step as u32));
+        }
+    }
+}
+
+/// Presentation button or `F5` starts the presentation of the visible nodes, following their
+/// presentation path or, if there is none, the arrows. Arrow keys, Space and Page Up/Down move
+/// between nodes, `Escape` ends it.
+pub fn toggle_presentation(
+    input: Res<Input<KeyCode>>,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<PresentationButton>)>,
+    mut presentation: ResMut<Presentation>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut tool: ResMut<Tool>,
+    containers: Query<(
+        &NodeRect,
+        &VeloNodeContainer,
+        &Visibility,
+        Option<&PresentationStep>,
+    )>,
+    arrows: Query<(&ArrowMeta, &Visibility)>,
+) {
+    let clicked = buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if presentation.active {
+        if clicked || input.any_just_pressed([KeyCode::Escape, KeyCode::F5]) {
+            presentation.active = false;
+            return;
+        }
+        let last = presentation.path.len().saturating_sub(1);
+        if input.any_just_pressed([
+            KeyCode::Right,
+            KeyCode::Down,
+            KeyCode::Space,
+            KeyCode::PageDown,
+        ]) {
+            presentation.current = (presentation.current + 1).min(last);
+        } else if input.any_just_pressed([KeyCode::Left, KeyCode::Up, KeyCode::PageUp]) {
+            presentation.current = presentation.current.saturating_sub(1);
+        }
+        return;
+    }
+    if !clicked && !(input.just_pressed(KeyCode::F5) && ui_state.is_canvas_focused()) {
+        return;
+    }
+    let visible: Vec<_> = containers
+        .iter()
+        .filter(|(_, _, visibility, _)| **visibility != Visibility::Hidden)
+        .collect();
+    let mut steps: Vec<_> = visible
+        .iter()
+        .filter_map(|(_, container, _, step)| step.map(|step| (*step, container.id)))
+        .collect();
+    steps.sort_by_key(|(step, ..)| *step);
+    let path: Vec<ReflectableUuid> = if steps.is_empty() {
+        let nodes: Vec<_> = visible
+            .iter()
+            .map(|(rect, container, _, _)| (container.id, rect.center()))
+            .collect();
+        let edges: Vec<_> = arrows
+            .iter()
+            .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+            .map(|(arrow, _)| (arrow.start.id, arrow.end.id))
+            .collect();
+        arrow_order(&nodes, &edges)
+    } else {
+        steps.into_iter().map(|(_, id)| id).collect()
+    };
+    if path.is_empty() {
+        return;
+    }
+    *presentation = Presentation {
+        active: true,
+        path,
+        current: 0,
+    };
+    ui_state.editing_node = false;
+    selected_nodes.0.clear();
+    if *tool != Tool::Select {
+        *tool = Tool::Select;
+    }
+}
+
+/// Hides menu, panels and minimap and makes the window fullscreen while presenting.
+pub fn apply_presentation_chrome(
+    presentation: Res<Presentation>,
+    mut panels: Query<
+        (Entity, &mut Style, Option<&RightPanel>, Option<&MainBottom>),
+        Or<(
+            With<Menu>,
+            With<LeftPanel>,
+            With<BottomPanel>,
+            With<CanvasPanels>,
+            With<Minimap>,
+            With<RightPanel>,
+            With<MainBottom>,
+        )>,
+    >,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut sizes: Local<Vec<(Entity, Size)>>,
+    mut presenting: Local<bool>,
+) {
+    if presentation.active == *presenting {
+        return;
+    }
+    *presenting = presentation.active;
+    if presentation.active {
+        *sizes = panels
+            .iter()
+            .map(|(entity, style, _, _)| (entity, style.size))
+            .collect();
+    }
+    for (entity, mut style, right_panel, main_bottom) in panels.iter_mut() {
+        // canvas takes the whole window
+        if right_panel.is_some() || main_bottom.is_some() {
+            if presentation.active {
+                style.size = Size::new(Val::Percent(100.), Val::Percent(100.));
+            } else if let Some((_, size)) = sizes.iter().find(|(e, _)| *e == entity) {
+                style.size = *size;
+            }
+            continue;
+        }
+        if presentation.active {
+            style.display = Display::None;
+            // other systems offset the canvas by the left panel width
+            style.size.width = Val::Percent(0.);
+        } else {
+            style.display = Display::Flex;
+            if let Some((_, size)) = sizes.iter().find(|(e, _)| *e == entity) {
+                style.size = *size;
+            }
+        }
+    }
+    windows.single_mut().mode = if presentation.active {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+}
+
+/// Pans and zooms the canvas towards the current node of the presentation, the transition
+/// eases out over a few frames (it's instant with reduced motion).
+pub fn animate_presentation(
+    presentation: Res<Presentation>,
+    zoom: Res<CanvasZoom>,
+    offset: Res<CanvasOffset>,
+    time: Res<Time>,
+    motion_settings: Res<MotionSettings>,
+    containers: Query<(&NodeRect, &VeloNodeContainer)>,
+    main_panel_query: Query<&Node, With<MainPanel>>,
+    mut events: EventWriter<ZoomCanvasEvent>,
+) {
+    if !presentation.active {
+        return;
+    }
+    let Some(id) = presentation.path.get(presentation.current) else {
+        return;
+    };
+    let Some((rect, _)) = containers.iter().find(|(_, container)| container.id == *id) else {
+        return;
+    };
+    let Ok(panel) = main_panel_query.get_single() else {
+        return;
+    };
+    let panel_size = panel.size();
+    if panel_size.min_element() <= 0. {
+        return;
+    }
+    let size = rect.size.max(Vec2::ONE);
+    let center = world_to_canvas(rect.center(), &offset, &zoom);
+    let target_zoom = (panel_size * PRESENTATION_FILL / size)
+        .min_element()
+        .clamp(MIN_ZOOM, MAX_ZOOM);
+    let offset = panel_size / 2. - center;
+    let ratio = target_zoom / zoom.0;
+    if offset.length() < 0.5 && (ratio - 1.).abs() < 0.001 {
+        return;
+    }
+    let t = if motion_settings.reduced_motion {
+        1.
+    } else {
+        1. - (-TRANSITION_SPEED * time.delta_seconds()).exp()
+    };
+    events.send(ZoomCanvasEvent {
+        zoom: zoom.0 * ratio.powf(t),
+        from: center,
+        to: center + offset * t,
+    });
+}
+
+/// Keeps numbers of the presentation path in the top left corner of the nodes, they are
+/// hidden while presenting.
+pub fn update_presentation_badges(
+    mut commands: Commands,
+    presentation: Res<Presentation>,
+    steps: Query<(&VeloNodeContainer, &PresentationStep)>,
+    nodes: Query<(Entity, &VeloNode)>,
+    mut badges: Query<(Entity, &PresentationBadge, &Children, &mut Visibility)>,
+    mut text_query: Query<&mut Text>,
+) {
+    let badge_visibility = if presentation.active {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for (badge_entity, badge, children, mut visibility) in badges.iter_mut() {
+        let Some((_, step)) = steps.iter().find(|(container, _)| container.id == badge.id) else {
+            commands.entity(badge_entity).despawn_recursive();
+            continue;
+        };
+        if *visibility != badge_visibility {
+            *visibility = badge_visibility;
+        }
+        let label = (step.0 + 1).to_string();
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                if text.sections[0].value != label {
+                    text.sections[0].value = label.clone();
+                }
+            }
+        }
+    }
+    for (container, step) in steps.iter() {
+        if badges
+            .iter()
+            .any(|(_, badge, _, _)| badge.id == container.id)
+        {
+            continue;
+        }
+        let Some((node_entity, _)) = nodes.iter().find(|(_, node)| node.id == container.id) else {
+            continue;
+        };
+        let badge = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(97.0 / 255.0, 97.0 / 255.0, 97.0 / 255.0),
+                        anchor: Anchor::TopLeft,
+                        ..default()
+                    },
+                    visibility: badge_visibility,
+                    ..default()
+                },
+                NodePart::handle(
+                    Vec2::new(-0.5, 0.5),
+                    Vec2::new(-10., 10.),
+                    Vec2::ZERO,
+                    BADGE_Z,
+                ),
+                FitToText {
+                    padding: Vec2::new(6., 2.),
+                },
+                PresentationBadge { id: container.id },
+            ))
+            .with_children(|builder| {
+                builder.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        (step.0 + 1).to_string(),
+                        TextStyle {
+                            font_size: 14.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    ..default()
+                });
+            })
+            .id();
+        commands.entity(node_entity).add_child(badge);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_arrow_order() {
+        let ids: Vec<_> = (0..6).map(|_| ReflectableUuid(Uuid::new_v4())).collect();
+        let (a, b, c, d, e, f) = (ids[0], ids[1], ids[2], ids[3], ids[4], ids[5]);
+        let nodes = [
+            (c, Vec2::new(50., 0.)),
+            (f, Vec2::new(10., -100.)),
+            (a, Vec2::new(0., 100.)),
+            (e, Vec2::new(0., -100.)),
+            (b, Vec2::new(-50., 0.)),
+            (d, Vec2::new(200., 100.)),
+        ];
+        // e and f only point at each other
+        let edges = [(a, c), (a, b), (e, f), (f, e)];
+        assert_eq!(arrow_order(&nodes, &edges), vec![a, b, c, d, e, f]);
+        assert!(arrow_order(&[], &edges).is_empty());
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use bevy_ui_borders::BorderColor;
+use serde::{Deserialize, Serialize};
+
+use super::ui_helpers::{
+    ContextMenu, Localized, MainPanel, RecentDocsButton, WelcomeClose, WelcomeDocButton,
+    WelcomeScreen, WelcomeTemplateButton,
+};
+use super::{
+    open_template, spawn_context_menu, ContextMenuAction, ContextMenuEvent, Notification, Template,
+    UiState,
+};
+use crate::resources::{AppState, LoadDocRequest, SaveDocRequest};
+use crate::utils::{load_doc_to_memory, ReflectableUuid};
+
+const RECENT_DOCS_KEY: &str = "recent_docs";
+const SESSION_KEY: &str = "session";
+pub const MAX_RECENT_DOCS: usize = 10;
+
+/// Document and tab open when the app was last used.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Session {
+    pub doc_id: ReflectableUuid,
+    pub tab_id: ReflectableUuid,
+}
+
+/// Moves the document to the front of the recent documents.
+pub fn push_recent_doc(recent: &mut Vec<ReflectableUuid>, doc_id: ReflectableUuid) {
+    recent.retain(|id| *id != doc_id);
+    recent.insert(0, doc_id);
+    recent.truncate(MAX_RECENT_DOCS);
+}
+
+/// Names of the recent documents that are still saved, most recent first.
+pub fn recent_docs(pkv: &PkvStore) -> Vec<(ReflectableUuid, String)> {
+    let recent = pkv
+        .get::<Vec<ReflectableUuid>>(RECENT_DOCS_KEY)
+        .unwrap_or_default();
+    let names = pkv
+        .get::<HashMap<ReflectableUuid, String>>("names")
+        .unwrap_or_default();
+    recent
+        .into_iter()
+        .filter_map(|id| names.get(&id).map(|name| (id, name.clone())))
+        .collect()
+}
+
+/// Loads the document of the last session with its active tab, `None` if it was never saved.
+pub fn restore_session(
+    app_state: &mut ResMut<AppState>,
+    pkv: &mut ResMut<PkvStore>,
+) -> Option<ReflectableUuid> {
+    let session = pkv.get::<Session>(SESSION_KEY).ok()?;
+    let names = pkv.get::<HashMap<ReflectableUuid, String>>("names").ok()?;
+    if !names.contains_key(&session.doc_id) {
+        return None;
+    }
+    load_doc_to_memory(session.doc_id, app_state, pkv);
+    let doc = app_state.docs.get_mut(&session.doc_id)?;
+    if doc.tabs.iter().any(|tab| tab.id == session.tab_id) {
+        for tab in doc.tabs.iter_mut() {
+            tab.is_active = tab.id == session.tab_id;
+        }
+    }
+    Some(session.doc_id)
+}
+
+fn open_doc(commands: &mut Commands, app_state: &mut AppState, doc_id: ReflectableUuid) {
+    if Some(doc_id) == app_state.current_document {
+        return;
+    }
+    if let Some(current) = app_state.current_document {
+        commands.insert_resource(SaveDocRequest {
+            doc_id: current,
+            path: None,
+        });
+    }
+    app_state.current_document = Some(doc_id);
+    commands.insert_resource(LoadDocRequest { doc_id });
+}
+
+/// Persists the open document and tab whenever one of them changes, the document is moved to
+/// the front of the recent documents. A failed write isn't retried until they change again.
+pub fn remember_session(
+    app_state: Res<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    mut notifications: EventWriter<Notification>,
+    mut last: Local<Option<Session>>,
+) {
+    let Some(session) = app_state.current_document.and_then(|doc_id| {
+        let doc = app_state.docs.get(&doc_id)?;
+        let tab = doc.tabs.iter().find(|tab| tab.is_active)?;
+        Some(Session {
+            doc_id,
+            tab_id: tab.id,
+        })
+    }) else {
+        return;
+    };
+    if *last == Some(session) {
+        return;
+    }
+    if last.map(|last| last.doc_id) != Some(session.doc_id) {
+        let mut recent = pkv
+            .get::<Vec<ReflectableUuid>>(RECENT_DOCS_KEY)
+            .unwrap_or_default();
+        push_recent_doc(&mut recent, session.doc_id);
+        if let Err(e) = pkv.set(RECENT_DOCS_KEY, &recent) {
+            let message = Localized::new("Couldn't save the recent documents");
+            notifications.send(Notification::error(message).with_details(e));
+        }
+    }
+    if let Err(e) = pkv.set(SESSION_KEY, &session) {
+        let message = Localized::new("Couldn't save the open document");
+        notifications.send(Notification::error(message).with_details(e));
+    }
+    *last = Some(session);
+}
+
+/// Opens the recent documents menu below the menu bar.
+pub fn open_recent_docs_menu(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<RecentDocsButton>)>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    menus: Query<Entity, With<ContextMenu>>,
+    pkv: Res<PkvStore>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    // opened menu is closed by the click
+    if !menus.is_empty() {
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    let items: Vec<_> = recent_docs(&pkv)
+        .into_iter()
+        .map(|(id, name)| (name, ContextMenuAction::OpenDoc(id)))
+        .collect();
+    if items.is_empty() {
+        return;
+    }
+    spawn_context_menu(
+        &mut commands,
+        main_panel,
+        &items,
+        Vec2::new(8., 8.),
+        None,
+        Vec2::ZERO,
+    );
+}
+
+pub fn recent_docs_menu_actions(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    mut app_state: ResMut<AppState>,
+) {
+    for event in events.iter() {
+        if let ContextMenuAction::OpenDoc(doc_id) = event.action {
+            open_doc(&mut commands, &mut app_state, doc_id);
+        }
+    }
+}
+
+fn spawn_welcome_button(
+    builder: &mut ChildBuilder,
+    label: Localized,
+    bundle: impl Bundle,
+    font_size: f32,
+) {
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Px(30.)),
+                    align_items: AlignItems::Center,
+                    padding: UiRect::left(Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            },
+            bundle,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    label.text.clone(),
+                    TextStyle {
+                        font_size,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ),
+                label,
+            ));
+        });
+}
+
+fn spawn_welcome_title(builder: &mut ChildBuilder, title: &str) {
+    builder.spawn((
+        TextBundle::from_section(
+            title,
+            TextStyle {
+                font_size: 20.,
+                color: Color::BLACK,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            margin: UiRect::vertical(Val::Px(10.)),
+            ..default()
+        }),
+        Localized::new(title),
+    ));
+}
+
+/// Welcome screen shown once on startup with the template gallery, and the recent documents
+/// when there is more than one. The document of the last session is already open behind it.
+pub fn show_welcome_screen(
+    mut commands: Commands,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    pkv: Res<PkvStore>,
+    mut shown: Local<bool>,
+) {
+    if *shown {
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    *shown = true;
+    let recent = recent_docs(&pkv);
+    let welcome = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(35.),
+                        top: Val::Percent(15.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(30.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(10.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+            WelcomeScreen,
+        ))
+        .with_children(|builder| {
+            if recent.len() > 1 {
+                spawn_welcome_title(builder, "Recent documents");
+                for (id, name) in recent {
+                    // document names aren't translated
+                    let label = Localized::new("{}").with_args([name]);
+                    spawn_welcome_button(builder, label, WelcomeDocButton { id }, 16.);
+                }
+            }
+            spawn_welcome_title(builder, "New from template");
+            for template in Template::ALL {
+                spawn_welcome_button(
+                    builder,
+                    Localized::new(template.name()),
+                    WelcomeTemplateButton { template },
+                    16.,
+                );
+            }
+            spawn_welcome_button(builder, Localized::new("Continue"), WelcomeClose, 14.);
+        })
+        .id();
+    commands.entity(main_panel).add_child(welcome);
+}
+
+/// Opens the document or template clicked on the welcome screen, any choice or Escape closes it.
+pub fn welcome_screen_click(
+    mut commands: Commands,
+    mut app_state: ResMut<AppState>,
+    ui_state: Res<UiState>,
+    input: Res<Input<KeyCode>>,
+    docs: Query<(&Interaction, &WelcomeDocButton), Changed<Interaction>>,
+    templates: Query<(&Interaction, &WelcomeTemplateButton), Changed<Interaction>>,
+    close: Query<&Interaction, (Changed<Interaction>, With<WelcomeClose>)>,
+    welcome: Query<Entity, With<WelcomeScreen>>,
+) {
+    let Ok(welcome) = welcome.get_single() else {
+        return;
+    };
+    let mut closed = close
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+        || (ui_state.is_canvas_focused() && input.just_pressed(KeyCode::Escape));
+    for (interaction, button) in docs.iter() {
+        if *interaction == Interaction::Clicked {
+            open_doc(&mut commands, &mut app_state, button.id);
+            closed = true;
+        }
+    }
+    for (interaction, button) in templates.iter() {
+        if *interaction == Interaction::Clicked {
+            open_template(&mut commands, &mut app_state, button.template);
+            closed = true;
+        }
+    }
+    if closed {
+        commands.entity(welcome).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_recent_doc() {
+        let ids: Vec<_> = (0..=MAX_RECENT_DOCS)
+            .map(|_| ReflectableUuid::generate())
+            .collect();
+        let mut recent = vec![];
+        for id in ids.iter() {
+            push_recent_doc(&mut recent, *id);
+        }
+        assert_eq!(recent.len(), MAX_RECENT_DOCS);
+        assert_eq!(recent[0], ids[MAX_RECENT_DOCS]);
+        assert!(!recent.contains(&ids[0]));
+
+        push_recent_doc(&mut recent, ids[5]);
+        assert_eq!(recent.len(), MAX_RECENT_DOCS);
+        assert_eq!(recent[0], ids[5]);
+        assert_eq!(recent.iter().filter(|id| **id == ids[5]).count(), 1);
+    }
+}
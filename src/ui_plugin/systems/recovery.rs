@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowCloseRequested};
+use bevy_cosmic_edit::CosmicEditImage;
+
+use super::ui_helpers::{
+    spawn_prompt, CollapsedBranch, FrameCollapsed, FrameMembers, Localized, MainPanel, PenStroke,
+    PresentationStep, RecoveryDiscard, RecoveryPrompt, RecoveryRestore,
+};
+use super::{
+    json_nodes, tab_json, ArrowQuery, NodeContainerQuery, NodeGroup, NodeQuery, RawText,
+    VeloNodeContainer,
+};
+use crate::components::Doc;
+use crate::resources::{AppState, LoadDocRequest};
+use crate::{APP_NAME, ORG_NAME};
+
+/// Seconds between two recovery snapshots of the open document.
+const RECOVERY_INTERVAL: f32 = 30.;
+/// Backups kept next to a document saved to a file.
+pub const MAX_BACKUPS: usize = 5;
+const RUNNING_MARKER: &str = "running";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// Snapshot of the document left by a session that didn't exit cleanly.
+#[derive(Resource)]
+pub struct PendingRecovery {
+    pub doc: Doc,
+}
+
+fn recovery_dir() -> PathBuf {
+    let dirs = directories::ProjectDirs::from("", ORG_NAME, APP_NAME);
+    match dirs.as_ref() {
+        Some(dirs) => dirs.data_dir(),
+        None => Path::new("."),
+    }
+    .join("recovery")
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Backups of `file_name` among `names` that are over the `keep` most recent ones.
+pub fn backups_to_remove(file_name: &str, names: &[String], keep: usize) -> Vec<String> {
+    let prefix = format!("{}.", file_name);
+    let mut backups: Vec<(u64, &String)> = names
+        .iter()
+        .filter_map(|name| {
+            let timestamp = name.strip_prefix(&prefix)?.strip_suffix(".bak")?;
+            Some((timestamp.parse().ok()?, name))
+        })
+        .collect();
+    backups.sort_by(|a, b| b.0.cmp(&a.0));
+    backups
+        .into_iter()
+        .skip(keep)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Copies the file about to be overwritten to `<file name>.<timestamp>.bak` next to it and
+/// removes the oldest backups.
+pub fn rotate_backups(path: &Path, keep: usize) -> std::io::Result<()> {
+    let (Some(file_name), Some(dir)) = (path.file_name().and_then(|n| n.to_str()), path.parent())
+    else {
+        return Ok(());
+    };
+    if !path.is_file() {
+        return Ok(());
+    }
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    fs::copy(path, dir.join(format!("{}.{}.bak", file_name, timestamp())))?;
+    let names: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+    for name in backups_to_remove(file_name, &names, keep) {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+/// Offers the snapshot of the last session if it didn't exit cleanly and marks this one as
+/// running.
+pub fn check_recovery(mut commands: Commands) {
+    let dir = recovery_dir();
+    let snapshot = dir.join(SNAPSHOT_FILE);
+    if dir.join(RUNNING_MARKER).exists() {
+        if let Some(doc) = fs::read_to_string(&snapshot)
+            .ok()
+            .and_then(|json| serde_json::from_str::<Doc>(&json).ok())
+        {
+            commands.insert_resource(PendingRecovery { doc });
+        }
+    } else {
+        let _ = fs::remove_file(&snapshot);
+    }
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(RUNNING_MARKER), "")) {
+        info!("Error marking session as running: {:?}", e);
+    }
+}
+
+/// Writes the open document with the current state of its active tab to the recovery directory
+/// every `RECOVERY_INTERVAL` seconds. The document itself isn't changed, so it isn't marked as
+/// edited. Documents with a passphrase are never written in plain text, they get no snapshot.
+pub fn write_recovery_snapshot(
+    time: Res<Time>,
+    app_state: Res<AppState>,
+    pending_recovery: Option<Res<PendingRecovery>>,
+    images: Res<Assets<Image>>,
+    node_container_query: NodeContainerQuery,
+    node_query: NodeQuery,
+    arrows: ArrowQuery,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    groups_query: Query<(&VeloNodeContainer, &NodeGroup)>,
+    frames_query: Query<(&VeloNodeContainer, &FrameMembers, Option<&FrameCollapsed>)>,
+    collapsed_query: Query<&VeloNodeContainer, With<CollapsedBranch>>,
+    strokes: Query<(&PenStroke, &Visibility)>,
+    presentation_query: Query<(&VeloNodeContainer, &PresentationStep)>,
+    mut elapsed: Local<f32>,
+) {
+    // the snapshot of the last session is kept until it's restored or discarded
+    if pending_recovery.is_some() {
+        return;
+    }
+    *elapsed += time.delta_seconds();
+    if *elapsed < RECOVERY_INTERVAL {
+        return;
+    }
+    *elapsed = 0.;
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    if app_state.passphrases.contains_key(&doc_id) {
+        return;
+    }
+    let Some(mut doc) = app_state.docs.get(&doc_id).cloned() else {
+        return;
+    };
+    let Some(tab) = doc.tabs.iter_mut().find(|tab| tab.is_active) else {
+        return;
+    };
+    let nodes = json_nodes(&node_query, &node_container_query, &text_query);
+    let json = tab_json(
+        &images,
+        &nodes,
+        &arrows,
+        &groups_query,
+        &frames_query,
+        &collapsed_query,
+        &strokes,
+        &presentation_query,
+    );
+    tab.checkpoint = Some(json.to_string());
+    let dir = recovery_dir();
+    if let Err(e) = serde_json::to_string(&doc)
+        .map_err(std::io::Error::from)
+        .and_then(|json| fs::write(dir.join(SNAPSHOT_FILE), json))
+    {
+        info!("Error writing recovery snapshot: {:?}", e);
+    }
+}
+
+/// Asks whether to restore the snapshot left by a session that didn't exit cleanly.
+pub fn show_recovery_prompt(
+    mut commands: Commands,
+    pending_recovery: Option<Res<PendingRecovery>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    mut shown: Local<bool>,
+) {
+    let Some(pending_recovery) = pending_recovery else {
+        return;
+    };
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    if *shown {
+        return;
+    }
+    *shown = true;
+    spawn_prompt(
+        &mut commands,
+        main_panel,
+        Localized::new("velo didn't exit properly, restore unsaved changes of \"{}\"?")
+            .with_args([pending_recovery.doc.name.clone()]),
+        RecoveryPrompt,
+        ("Restore", RecoveryRestore),
+        ("Discard", RecoveryDiscard),
+    );
+}
+
+/// Restores the snapshot as the open document or discards it.
+pub fn recovery_prompt_click(
+    mut commands: Commands,
+    mut app_state: ResMut<AppState>,
+    pending_recovery: Option<Res<PendingRecovery>>,
+    restore: Query<&Interaction, (Changed<Interaction>, With<RecoveryRestore>)>,
+    discard: Query<&Interaction, (Changed<Interaction>, With<RecoveryDiscard>)>,
+    prompts: Query<Entity, With<RecoveryPrompt>>,
+) {
+    let Some(pending_recovery) = pending_recovery else {
+        return;
+    };
+    let restored = restore
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let discarded = discard
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !restored && !discarded {
+        return;
+    }
+    if restored {
+        let doc = pending_recovery.doc.clone();
+        let doc_id = doc.id;
+        app_state.docs.insert(doc_id, doc);
+        app_state.doc_list_ui.insert(doc_id);
+        app_state.current_document = Some(doc_id);
+        commands.insert_resource(LoadDocRequest { doc_id });
+    }
+    let _ = fs::remove_file(recovery_dir().join(SNAPSHOT_FILE));
+    commands.remove_resource::<PendingRecovery>();
+    for entity in prompts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Removes the running marker and the snapshot when the app is closed.
+pub fn clear_recovery_on_exit(
+    mut exit: EventReader<AppExit>,
+    mut close_requested: EventReader<WindowCloseRequested>,
+    primary_window: Query<(), With<PrimaryWindow>>,
+) {
+    // closing a tab preview doesn't close the app
+    let closed = close_requested
+        .iter()
+        .filter(|event| primary_window.contains(event.window))
+        .count();
+    if exit.iter().count() + closed == 0 {
+        return;
+    }
+    let dir = recovery_dir();
+    let _ = fs::remove_file(dir.join(SNAPSHOT_FILE));
+    let _ = fs::remove_file(dir.join(RUNNING_MARKER));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_backups_to_remove() {
+        let names: Vec<String> = [
+            "velo.json",
+            "velo.json.100.bak",
+            "velo.json.300.bak",
+            "velo.json.200.bak",
+            "other.json.50.bak",
+            "velo.json.old.bak",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        assert_eq!(
+            backups_to_remove("velo.json", &names, 2),
+            vec!["velo.json.100.bak".to_string()]
+        );
+        assert!(backups_to_remove("velo.json", &names, 3).is_empty());
+    }
+
+    #[test]
+    fn test_rotate_backups() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("velo.json");
+        // nothing to back up yet
+        rotate_backups(&path, 2).unwrap();
+        fs::write(&path, "{}").unwrap();
+        for timestamp in [1, 2, 3] {
+            fs::write(dir.path().join(format!("velo.json.{}.bak", timestamp)), "").unwrap();
+        }
+        rotate_backups(&path, 2).unwrap();
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&"velo.json".to_string()));
+        assert!(names.contains(&"velo.json.3.bak".to_string()));
+        assert!(!names.contains(&"velo.json.2.bak".to_string()));
+        let new_backup = names
+            .iter()
+            .find(|name| {
+                !["velo.json", "velo.json.3.bak", "velo.json.1.bak"].contains(&name.as_str())
+            })
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(new_backup)).unwrap(),
+            "{}"
+        );
+    }
+}
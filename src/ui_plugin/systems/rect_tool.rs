@@ -0,0 +1,108 @@
+use bevy::{prelude::*, sprite::Anchor, window::PrimaryWindow};
+use uuid::Uuid;
+
+use crate::components::MainCamera;
+use crate::{AddRectEvent, JsonNode, JsonNodeText, NodeType, TextPos};
+
+use super::{
+    ui_helpers::{CanvasOutline, MainPanel, RectToolPreview},
+    GridSettings, Theme, Tool, MIN_NODE_SIZE, OVERLAY_Z,
+};
+
+/// With the node tool dragging on the canvas outlines a node, on release the node is created
+/// with the dragged size and edited right away. The select tool is back after that.
+pub fn draw_rect_node(
+    mut commands: Commands,
+    mut tool: ResMut<Tool>,
+    grid_settings: Res<GridSettings>,
+    buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut preview_query: Query<(Entity, &mut Transform, &mut Sprite), With<RectToolPreview>>,
+    mut events: EventWriter<AddRectEvent>,
+    theme: Res<Theme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut start: Local<Option<Vec2>>,
+) {
+    let (camera, camera_transform) = camera_q.single();
+    let Some(cursor) = windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let cursor = Vec2::new(grid_settings.snap(cursor.x), grid_settings.snap(cursor.y));
+
+    let Some(start_pos) = *start else {
+        let pressed = interaction_query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Clicked);
+        // Space+drag pans the canvas instead
+        if *tool != Tool::Node || !pressed || input.pressed(KeyCode::Space) {
+            return;
+        }
+        *start = Some(cursor);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::NONE,
+                    custom_size: Some(Vec2::ZERO),
+                    anchor: Anchor::BottomLeft,
+                    ..default()
+                },
+                transform: Transform::from_translation(cursor.extend(OVERLAY_Z)),
+                ..default()
+            },
+            CanvasOutline::new(Color::DARK_GRAY, 1.),
+            RectToolPreview,
+        ));
+        return;
+    };
+
+    let min = start_pos.min(cursor);
+    let max = start_pos.max(cursor);
+    for (_, mut transform, mut sprite) in preview_query.iter_mut() {
+        transform.translation = min.extend(OVERLAY_Z);
+        sprite.custom_size = Some(max - min);
+    }
+    if buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    for (entity, ..) in preview_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *start = None;
+    // a plain click doesn't create a node, the tool stays active
+    let size = max - min;
+    if size.x < MIN_NODE_SIZE || size.y < MIN_NODE_SIZE {
+        return;
+    }
+    *tool = Tool::Select;
+    events.send(AddRectEvent {
+        node: JsonNode {
+            id: Uuid::new_v4(),
+            node_type: NodeType::Rect,
+            left: Val::Px(min.x),
+            bottom: Val::Px(min.y),
+            width: Val::Px(size.x),
+            height: Val::Px(size.y),
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::Center,
+                style: theme.node_text_style(),
+            },
+            bg_color: theme.node_background,
+            z_index: 0,
+            locked: false,
+            border: theme.node_border(),
+            shadow: None,
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: None,
+    });
+}
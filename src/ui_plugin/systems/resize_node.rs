@@ -1,12 +1,10 @@
 use super::{
-    ui_helpers::ResizeMarker, BevyMarkdownView, RawText, RedrawArrowEvent, VeloNode,
-    VeloNodeContainer,
+    ui_helpers::{KeepAspectRatio, NodeRect, ResizeMarker},
+    CanvasZoom, GridSettings, History, HistoryCommand, Locked, RedrawArrowEvent, VeloNode,
+    VeloNodeContainer, MIN_NODE_SIZE,
 };
-use crate::{utils::convert_from_val_px, UiState};
+use crate::UiState;
 use bevy::{input::mouse::MouseMotion, prelude::*, window::PrimaryWindow};
-use bevy_cosmic_edit::{CosmicEditImage, FontSystemState};
-use bevy_markdown::BevyMarkdownNode;
-use cosmic_text::Edit;
 
 pub fn resize_entity_start(
     mut interaction_query: Query<
@@ -14,12 +12,19 @@ pub fn resize_entity_start(
         (Changed<Interaction>, With<ResizeMarker>),
     >,
     mut button_query: Query<&VeloNode, With<VeloNode>>,
+    locked_query: Query<&VeloNodeContainer, With<Locked>>,
     mut state: ResMut<UiState>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     let mut primary_window = windows.single_mut();
     for (interaction, parent, resize_marker) in &mut interaction_query {
         let rectangle = button_query.get_mut(parent.get()).unwrap();
+        if locked_query
+            .iter()
+            .any(|container| container.id == rectangle.id)
+        {
+            continue;
+        }
         match *interaction {
             Interaction::Clicked => {
                 state.entity_to_resize = Some((rectangle.id, *resize_marker));
@@ -37,6 +42,12 @@ pub fn resize_entity_start(
                 ResizeMarker::BottomRight => {
                     primary_window.cursor.icon = CursorIcon::NwseResize;
                 }
+                ResizeMarker::Top | ResizeMarker::Bottom => {
+                    primary_window.cursor.icon = CursorIcon::NsResize;
+                }
+                ResizeMarker::Left | ResizeMarker::Right => {
+                    primary_window.cursor.icon = CursorIcon::EwResize;
+                }
             },
             Interaction::None => {
                 primary_window.cursor.icon = CursorIcon::Default;
@@ -45,131 +56,128 @@ pub fn resize_entity_start(
     }
 }
 
+/// Horizontal (-1 left, 1 right) and vertical (1 top, -1 bottom) edges moved by the marker.
+fn marker_edges(marker: ResizeMarker) -> Vec2 {
+    match marker {
+        ResizeMarker::TopLeft => Vec2::new(-1., 1.),
+        ResizeMarker::TopRight => Vec2::new(1., 1.),
+        ResizeMarker::BottomLeft => Vec2::new(-1., -1.),
+        ResizeMarker::BottomRight => Vec2::new(1., -1.),
+        ResizeMarker::Top => Vec2::new(0., 1.),
+        ResizeMarker::Bottom => Vec2::new(0., -1.),
+        ResizeMarker::Left => Vec2::new(-1., 0.),
+        ResizeMarker::Right => Vec2::new(1., 0.),
+    }
+}
+
+/// Resizes `rect` (left, bottom, width, height) by mouse `delta` (y axis pointing down).
+/// Edges opposite to the marker stay in place, or the center does with `from_center`.
+/// With `keep_aspect_ratio` height follows width, except for top/bottom handles.
+fn resize_rect(
+    rect: Vec4,
+    marker: ResizeMarker,
+    delta: Vec2,
+    keep_aspect_ratio: bool,
+    from_center: bool,
+) -> Vec4 {
+    let [left, bottom, old_width, old_height] = rect.to_array();
+    let edges = marker_edges(marker);
+    let factor = if from_center { 2. } else { 1. };
+    let mut width = old_width + factor * edges.x * delta.x;
+    let mut height = old_height - factor * edges.y * delta.y;
+    if keep_aspect_ratio && old_width > 0. && old_height > 0. {
+        if edges.x != 0. {
+            height = width * old_height / old_width;
+        } else {
+            width = height * old_width / old_height;
+        }
+        let scale = (MIN_NODE_SIZE / width).max(MIN_NODE_SIZE / height).max(1.);
+        width *= scale;
+        height *= scale;
+    } else {
+        width = width.max(MIN_NODE_SIZE);
+        height = height.max(MIN_NODE_SIZE);
+    }
+    let left = if from_center || edges.x == 0. {
+        left + (old_width - width) / 2.
+    } else if edges.x < 0. {
+        left + old_width - width
+    } else {
+        left
+    };
+    let bottom = if from_center || edges.y == 0. {
+        bottom + (old_height - height) / 2.
+    } else if edges.y < 0. {
+        bottom + old_height - height
+    } else {
+        bottom
+    };
+    Vec4::new(left, bottom, width, height)
+}
+
+/// Resizes the node held by a resize marker by the mouse motion, in world units. The editor
+/// and markdown view follow the new size in `layout_nodes`.
 pub fn resize_entity_end(
     mut mouse_motion_events: EventReader<MouseMotion>,
     state: Res<UiState>,
+    input: Res<Input<KeyCode>>,
+    zoom: Res<CanvasZoom>,
     mut node_query: Query<
-        (&VeloNodeContainer, &mut Style),
-        (
-            With<VeloNodeContainer>,
-            Without<BevyMarkdownNode>,
-            Without<RawText>,
-        ),
-    >,
-    mut raw_text_query: Query<
-        (&RawText, &mut CosmicEditImage),
-        (
-            Without<VeloNodeContainer>,
-            Without<BevyMarkdownNode>,
-            With<RawText>,
-        ),
+        (&VeloNodeContainer, &mut NodeRect, Option<&KeepAspectRatio>),
+        With<VeloNodeContainer>,
     >,
-    mut markdown_text_input_query: Query<
-        (&Parent, &mut Style),
-        (
-            With<BevyMarkdownNode>,
-            Without<VeloNodeContainer>,
-            Without<RawText>,
-        ),
-    >,
-    markdown_view_query: Query<(&BevyMarkdownView, Entity), With<BevyMarkdownView>>,
     mut events: EventWriter<RedrawArrowEvent>,
-    windows: Query<&mut Window, With<PrimaryWindow>>,
-    mut font_system_state: ResMut<FontSystemState>,
+    grid_settings: Res<GridSettings>,
+    mut history: ResMut<History>,
+    mut pending_delta: Local<Vec2>,
 ) {
-    let primary_window = windows.single();
-    let font_system = font_system_state.font_system.as_mut().unwrap();
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    let alt = input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if state.entity_to_resize.is_none() {
+        *pending_delta = Vec2::ZERO;
+    }
     for event in mouse_motion_events.iter() {
         if let Some((id, resize_marker)) = state.entity_to_resize {
-            for (rectangle, mut button_style) in &mut node_query {
+            for (rectangle, mut rect, keep_aspect_ratio) in &mut node_query {
                 if id == rectangle.id {
                     events.send(RedrawArrowEvent { id });
-                    #[allow(unused)]
-                    let mut delta = event.delta;
+                    let mut delta = event.delta / zoom.0;
                     #[cfg(target_arch = "wasm32")]
                     {
                         // MouseMotion returns different values depending on platform
                         delta = Vec2::new(delta.x / 2., delta.y / 2.);
                     }
-                    match resize_marker {
-                        ResizeMarker::TopLeft => {
-                            if let Val::Px(width) = button_style.size.width {
-                                button_style.size.width = Val::Px(width - delta.x);
-                            }
-
-                            if let Val::Px(height) = button_style.size.height {
-                                button_style.size.height = Val::Px(height - delta.y);
-                            }
-
-                            if let Val::Px(x) = button_style.position.left {
-                                button_style.position.left = Val::Px(x + delta.x);
-                            }
-                        }
-                        ResizeMarker::TopRight => {
-                            if let Val::Px(width) = button_style.size.width {
-                                button_style.size.width = Val::Px(width + delta.x);
-                            }
-
-                            if let Val::Px(height) = button_style.size.height {
-                                button_style.size.height = Val::Px(height - delta.y);
-                            }
-                        }
-                        ResizeMarker::BottomLeft => {
-                            if let Val::Px(width) = button_style.size.width {
-                                button_style.size.width = Val::Px(width - delta.x);
-                            }
-
-                            if let Val::Px(height) = button_style.size.height {
-                                button_style.size.height = Val::Px(height + delta.y);
-                            }
-
-                            if let Val::Px(x) = button_style.position.left {
-                                button_style.position.left = Val::Px(x + delta.x);
-                            }
-
-                            if let Val::Px(y) = button_style.position.bottom {
-                                button_style.position.bottom = Val::Px(y - delta.y);
-                            }
-                        }
-                        ResizeMarker::BottomRight => {
-                            if let Val::Px(width) = button_style.size.width {
-                                button_style.size.width = Val::Px(width + delta.x);
-                            }
-
-                            if let Val::Px(height) = button_style.size.height {
-                                button_style.size.height = Val::Px(height + delta.y);
-                            }
-
-                            if let Val::Px(y) = button_style.position.bottom {
-                                button_style.position.bottom = Val::Px(y - delta.y);
-                            }
-                        }
-                    };
-                    for (text, mut cosmic_edit) in &mut raw_text_query.iter_mut() {
-                        if text.id == id {
-                            let scale_factor = primary_window.scale_factor() as f32;
-                            let width = convert_from_val_px(button_style.size.width);
-                            let height = convert_from_val_px(button_style.size.height);
-                            cosmic_edit.editor.buffer_mut().set_size(
-                                font_system,
-                                width * scale_factor,
-                                height * scale_factor,
-                            );
-                            cosmic_edit.editor.buffer_mut().set_redraw(true);
-                            break;
-                        }
+                    if grid_settings.enabled && grid_settings.size > 0. {
+                        // resize in whole grid steps, keeping the remainder for next events
+                        *pending_delta += delta;
+                        delta = (*pending_delta / grid_settings.size).trunc() * grid_settings.size;
+                        *pending_delta -= delta;
                     }
-                    for (node, entity) in markdown_view_query.iter() {
-                        if node.id == id {
-                            for (parent, mut text_style) in &mut markdown_text_input_query {
-                                if parent.get() == entity {
-                                    text_style.max_size.width = button_style.size.width;
-                                    text_style.max_size.height = button_style.size.height;
-                                    break;
-                                }
-                            }
-                        }
+                    let old = Vec4::new(rect.position.x, rect.position.y, rect.size.x, rect.size.y);
+                    let [left, bottom, width, height] = resize_rect(
+                        old,
+                        resize_marker,
+                        delta,
+                        keep_aspect_ratio.is_some() || shift,
+                        alt,
+                    )
+                    .to_array();
+                    let (position, size) = (Vec2::new(left, bottom), Vec2::new(width, height));
+                    if position != rect.position {
+                        history.record(HistoryCommand::NodeMoved {
+                            id: id.0,
+                            from: rect.position,
+                            to: position,
+                        });
                     }
+                    if size != rect.size {
+                        history.record(HistoryCommand::NodeResized {
+                            id: id.0,
+                            from: rect.size,
+                            to: size,
+                        });
+                    }
+                    *rect = NodeRect::new(position, size);
                 }
             }
         }
@@ -178,17 +186,67 @@ pub fn resize_entity_end(
 
 #[cfg(test)]
 mod tests {
-    use super::{resize_entity_end, RedrawArrowEvent, VeloNodeContainer};
-    use crate::{ui_plugin::ui_helpers::ResizeMarker, UiState};
+    use super::{
+        resize_entity_end, resize_rect, CanvasZoom, GridSettings, History, RedrawArrowEvent,
+        VeloNodeContainer,
+    };
+    use crate::{
+        ui_plugin::ui_helpers::{NodeRect, ResizeMarker},
+        UiState,
+    };
     use bevy::{input::mouse::MouseMotion, prelude::*};
-    use bevy_cosmic_edit::FontSystemState;
-    use cosmic_text::FontSystem;
+
+    #[test]
+    fn test_resize_rect() {
+        let rect = Vec4::new(0., 0., 100., 50.);
+        // edges resize one axis
+        assert_eq!(
+            resize_rect(rect, ResizeMarker::Right, Vec2::new(10., 7.), false, false),
+            Vec4::new(0., 0., 110., 50.)
+        );
+        assert_eq!(
+            resize_rect(
+                rect,
+                ResizeMarker::Bottom,
+                Vec2::new(10., 10.),
+                false,
+                false
+            ),
+            Vec4::new(0., -10., 100., 60.)
+        );
+        // aspect ratio is kept, top edge stays in place
+        assert_eq!(
+            resize_rect(
+                rect,
+                ResizeMarker::BottomRight,
+                Vec2::new(20., 0.),
+                true,
+                false
+            ),
+            Vec4::new(0., -10., 120., 60.)
+        );
+        // center stays in place
+        assert_eq!(
+            resize_rect(rect, ResizeMarker::Left, Vec2::new(-10., 0.), false, true),
+            Vec4::new(-10., 0., 120., 50.)
+        );
+        // nodes don't collapse
+        assert_eq!(
+            resize_rect(
+                rect,
+                ResizeMarker::TopLeft,
+                Vec2::new(200., 0.),
+                false,
+                false
+            ),
+            Vec4::new(90., 0., 10., 50.)
+        );
+    }
 
     #[test]
     fn test_resize_entity_end() {
         // Set up a test app with the necessary resources and entities
         let mut app = App::new();
-        app.add_plugin(WindowPlugin::default());
         let entity_id = crate::utils::ReflectableUuid::generate();
 
         // Test all ResizeMarkers
@@ -202,66 +260,52 @@ mod tests {
                 entity_to_resize: Some((entity_id, marker)),
                 ..default()
             });
-            app.insert_resource(FontSystemState {
-                font_system: Some(FontSystem::new()),
-            });
-
+            // mouse motion is in screen pixels, nodes are resized in world units
+            app.insert_resource(CanvasZoom(0.5));
+            app.init_resource::<GridSettings>();
+            app.init_resource::<History>();
+            app.init_resource::<Input<KeyCode>>();
             app.add_event::<MouseMotion>();
             app.add_event::<RedrawArrowEvent>();
             app.world
                 .resource_mut::<Events<MouseMotion>>()
                 .send(MouseMotion {
-                    delta: Vec2::new(10.0, 5.0),
+                    delta: Vec2::new(5.0, 2.5),
                 });
 
             app.add_system(resize_entity_end);
 
-            app.world
-                .spawn(NodeBundle {
-                    style: Style {
-                        size: Size::new(Val::Px(100.0), Val::Px(100.0)),
-                        position: UiRect {
-                            left: Val::Px(0.0),
-                            bottom: Val::Px(0.0),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(VeloNodeContainer { id: entity_id });
+            app.world.spawn((
+                NodeRect::new(Vec2::ZERO, Vec2::splat(100.0)),
+                VeloNodeContainer { id: entity_id },
+            ));
 
             // Run the app
             app.update();
 
             // Check that the size and position of the rectangle have been updated correctly
-            let (_, style) = app
+            let (_, rect) = app
                 .world
-                .query::<(&VeloNodeContainer, &mut Style)>()
-                .iter_mut(&mut app.world)
+                .query::<(&VeloNodeContainer, &NodeRect)>()
+                .iter(&app.world)
                 .last()
                 .unwrap();
 
             match marker {
                 ResizeMarker::TopLeft => {
-                    assert_eq!(style.size.width, Val::Px(90.0));
-                    assert_eq!(style.size.height, Val::Px(95.0));
-                    assert_eq!(style.position.left, Val::Px(10.0));
+                    assert_eq!(rect.size, Vec2::new(90.0, 95.0));
+                    assert_eq!(rect.position.x, 10.0);
                 }
                 ResizeMarker::TopRight => {
-                    assert_eq!(style.size.width, Val::Px(120.0));
-                    assert_eq!(style.size.height, Val::Px(90.0));
+                    assert_eq!(rect.size, Vec2::new(120.0, 90.0));
                 }
                 ResizeMarker::BottomLeft => {
-                    assert_eq!(style.size.width, Val::Px(70.0));
-                    assert_eq!(style.size.height, Val::Px(115.0));
-                    assert_eq!(style.position.left, Val::Px(30.0));
-                    assert_eq!(style.position.bottom, Val::Px(-15.0));
+                    assert_eq!(rect.size, Vec2::new(70.0, 115.0));
+                    assert_eq!(rect.position, Vec2::new(30.0, -15.0));
                 }
                 ResizeMarker::BottomRight => {
-                    assert_eq!(style.size.width, Val::Px(140.0));
-                    assert_eq!(style.size.height, Val::Px(120.0));
-                    assert_eq!(style.position.bottom, Val::Px(-20.0));
+                    assert_eq!(rect.size, Vec2::new(140.0, 120.0));
+                    assert_eq!(rect.position.y, -20.0);
                 }
             }
         }
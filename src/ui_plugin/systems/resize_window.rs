@@ -16,7 +16,6 @@ pub fn resize_notificator(
                 commands.insert_resource(LoadTabRequest {
                     doc_id: current_doc.id,
                     tab_id: active_tab.id,
-                    drop_last_checkpoint: false,
                 });
             }
         }
@@ -0,0 +1,141 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::canvas::arrow::events::RedrawArrowEvent;
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
+
+use super::{
+    ui_helpers::RotationHandle, Locked, NodeRotation, SelectedNodes, UiState, VeloNodeContainer,
+};
+
+const ROTATION_SNAP: f32 = 15.;
+
+/// Clockwise angle in degrees (0..360) of `cursor` around `center`, 0 pointing up.
+/// Both are in world coordinates (y axis pointing up).
+fn rotation_angle(center: Vec2, cursor: Vec2, snap: bool) -> f32 {
+    let offset = cursor - center;
+    let mut angle = offset.x.atan2(offset.y).to_degrees();
+    if snap {
+        angle = (angle / ROTATION_SNAP).round() * ROTATION_SNAP;
+    }
+    angle.rem_euclid(360.)
+}
+
+pub fn rotate_node_start(
+    interaction_query: Query<(&Interaction, &RotationHandle), Changed<Interaction>>,
+    locked_query: Query<&VeloNodeContainer, With<Locked>>,
+    mut ui_state: ResMut<UiState>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let mut primary_window = windows.single_mut();
+    for (interaction, handle) in interaction_query.iter() {
+        if locked_query
+            .iter()
+            .any(|container| container.id == handle.id)
+        {
+            continue;
+        }
+        match *interaction {
+            Interaction::Clicked => {
+                ui_state.entity_to_rotate = Some(handle.id);
+            }
+            Interaction::Hovered => {
+                primary_window.cursor.icon = CursorIcon::Grab;
+            }
+            Interaction::None => {
+                primary_window.cursor.icon = CursorIcon::Default;
+            }
+        }
+    }
+}
+
+/// Rotates the node around its center following the cursor in 15° steps, Alt rotates freely.
+pub fn rotate_node(
+    mut ui_state: ResMut<UiState>,
+    buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    mut containers: Query<(&VeloNodeContainer, &GlobalTransform, &mut NodeRotation)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    let Some(id) = ui_state.entity_to_rotate else {
+        return;
+    };
+    if !buttons.pressed(MouseButton::Left) {
+        ui_state.entity_to_rotate = None;
+        return;
+    }
+    let (camera, camera_transform) = camera_q.single();
+    let Some(cursor) = windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let snap = !input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    for (container, global_transform, mut rotation) in containers.iter_mut() {
+        if container.id != id {
+            continue;
+        }
+        let center = global_transform.translation().truncate();
+        let angle = rotation_angle(center, cursor, snap);
+        if rotation.0 != angle {
+            rotation.0 = angle;
+        }
+    }
+}
+
+pub fn apply_node_rotation(
+    mut containers: Query<
+        (&VeloNodeContainer, &NodeRotation, &mut Transform),
+        Changed<NodeRotation>,
+    >,
+    mut events: EventWriter<RedrawArrowEvent>,
+    mut rotated: Local<Vec<ReflectableUuid>>,
+) {
+    // arrows are redrawn once transforms of the connection points are propagated
+    for id in rotated.drain(..) {
+        events.send(RedrawArrowEvent { id });
+    }
+    for (container, rotation, mut transform) in containers.iter_mut() {
+        // node rotation is clockwise, the world y axis points up
+        transform.rotation = Quat::from_rotation_z(-rotation.0.to_radians());
+        rotated.push(container.id);
+    }
+}
+
+pub fn update_rotation_handles(
+    ui_state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut handles: Query<(&RotationHandle, &mut Visibility)>,
+) {
+    for (handle, mut visibility) in handles.iter_mut() {
+        let shown =
+            ui_state.entity_to_rotate == Some(handle.id) || selected_nodes.0.contains(&handle.id);
+        let new_visibility = if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_angle() {
+        let center = Vec2::new(100., 100.);
+        assert_eq!(rotation_angle(center, Vec2::new(100., 150.), true), 0.);
+        assert_eq!(rotation_angle(center, Vec2::new(150., 100.), true), 90.);
+        assert_eq!(rotation_angle(center, Vec2::new(50., 100.), true), 270.);
+        assert_eq!(rotation_angle(center, Vec2::new(110., 150.), true), 15.);
+        let free = rotation_angle(center, Vec2::new(110., 150.), false);
+        assert!(free > 11. && free < 12.);
+    }
+}
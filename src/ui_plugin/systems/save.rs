@@ -1,21 +1,30 @@
-use base64::{engine::general_purpose, Engine};
 use bevy::prelude::*;
 
 use bevy_cosmic_edit::{get_cosmic_text, CosmicEditImage};
 use bevy_pkv::PkvStore;
-use image::*;
 
 use serde_json::json;
-use std::{collections::HashMap, io::Cursor};
+use std::collections::HashMap;
 
-use super::ui_helpers::VeloNode;
-use super::{RawText, SaveStoreEvent, VeloNodeContainer};
-use crate::canvas::arrow::components::ArrowMeta;
+use super::ui_helpers::{
+    CollapsedBranch, FrameCollapsed, FrameMembers, HiddenByBranch, Localized, NodeRect, NodeZIndex,
+    PenStroke, PresentationStep, VeloNode,
+};
+use super::{
+    autosave_document, encrypt_doc, has_image, normalize_checkpoint, prune_assets,
+    stored_encrypted_docs, Locked, NodeAttachments, NodeEffects, NodeGroup, NodeRotation, NodeTags,
+    Notification, RawText, SaveStoreEvent, VeloNodeContainer,
+};
+use crate::canvas::arrow::components::{
+    ArrowLabel, ArrowMeta, ArrowPins, ArrowRelation, ArrowStyle, ArrowWaypoints,
+};
 use crate::components::Doc;
 use crate::resources::SaveDocRequest;
 use crate::resources::{AppState, SaveTabRequest};
-use crate::utils::{load_doc_to_memory, ReflectableUuid};
-use crate::{ui_plugin::ui_helpers::style_to_pos, JsonNode, JsonNodeText, MAX_CHECKPOINTS};
+use crate::utils::{encode_image_base64, load_doc_to_memory, ReflectableUuid};
+use crate::{
+    JsonFrame, JsonNode, JsonNodeText, JsonStroke, NodeBorder, NodeTextStyle, NodeType, TextPos,
+};
 
 pub fn should_save_doc(request: Option<Res<SaveDocRequest>>) -> bool {
     request.is_some()
@@ -59,51 +68,95 @@ pub fn save_doc(
     });
 }
 
+/// Writes the document, its tags and its name to the store. Documents with a passphrase are
+/// stored encrypted and without their tags, only their name is left readable for the document
+/// list.
+fn store_doc(pkv: &mut PkvStore, doc: &Doc, passphrase: Option<&str>) -> Result<(), String> {
+    let mut docs = pkv
+        .get::<HashMap<ReflectableUuid, Doc>>("docs")
+        .unwrap_or_default();
+    let mut encrypted_docs = stored_encrypted_docs(pkv);
+    let mut tags = pkv
+        .get::<HashMap<ReflectableUuid, Vec<String>>>("tags")
+        .unwrap_or_default();
+    match passphrase {
+        Some(passphrase) => {
+            let data = encrypt_doc(doc, passphrase).map_err(|e| e.to_string())?;
+            encrypted_docs.insert(doc.id, data);
+            docs.remove(&doc.id);
+            tags.remove(&doc.id);
+        }
+        None => {
+            encrypted_docs.remove(&doc.id);
+            docs.insert(doc.id, doc.clone());
+            if let Some(tags) = tags.get_mut(&doc.id) {
+                tags.append(&mut doc.tags.clone());
+            } else {
+                tags.insert(doc.id, doc.tags.clone());
+            }
+        }
+    }
+    pkv.set("docs", &docs).map_err(|e| e.to_string())?;
+    pkv.set("encrypted_docs", &encrypted_docs)
+        .map_err(|e| e.to_string())?;
+    pkv.set("tags", &tags).map_err(|e| e.to_string())?;
+    let mut names = pkv
+        .get::<HashMap<ReflectableUuid, String>>("names")
+        .unwrap_or_default();
+    names.insert(doc.id, doc.name.clone());
+    pkv.set("names", &names).map_err(|e| e.to_string())?;
+    pkv.set("last_saved", &doc.id).map_err(|e| e.to_string())
+}
+
 pub fn save_to_store(
     mut pkv: ResMut<PkvStore>,
     mut app_state: ResMut<AppState>,
     mut events: EventReader<SaveStoreEvent>,
+    mut notifications: EventWriter<Notification>,
+    #[cfg(not(target_arch = "wasm32"))] mut file_io: ResMut<super::FileIo>,
 ) {
     for event in events.iter() {
         let doc_id = event.doc_id;
-        if let Ok(mut docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-            docs.insert(doc_id, app_state.docs.get(&doc_id).unwrap().clone());
-            pkv.set("docs", &docs).unwrap();
-        } else {
-            let mut docs = HashMap::new();
-            docs.insert(doc_id, app_state.docs.get(&doc_id).unwrap().clone());
-            pkv.set("docs", &docs).unwrap();
-        }
-        if let Ok(mut tags) = pkv.get::<HashMap<ReflectableUuid, Vec<String>>>("tags") {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            if let Some(tags) = tags.get_mut(&doc_id) {
-                tags.append(&mut doc.tags.clone());
-            } else {
-                tags.insert(doc.id, doc.tags.clone());
-            }
-            pkv.set("tags", &tags).unwrap();
-        } else {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            let mut tags = HashMap::new();
-            tags.insert(doc.id, doc.tags.clone());
-            pkv.set("tags", &tags).unwrap();
-        }
-        if let Ok(mut names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            names.insert(doc.id, doc.name.clone());
-            pkv.set("names", &names).unwrap();
-        } else {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            let mut names = HashMap::new();
-            names.insert(doc.id, doc.name.clone());
-            pkv.set("names", &names).unwrap();
+        let passphrase = app_state.passphrases.get(&doc_id).cloned();
+        let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+            continue;
+        };
+        prune_assets(doc);
+        if let Err(e) = store_doc(&mut pkv, doc, passphrase.as_deref()) {
+            notifications.send(
+                Notification::error(
+                    Localized::new("Couldn't save {}").with_args([doc.name.clone()]),
+                )
+                .with_details(e),
+            );
+            continue;
         }
-        pkv.set("last_saved", &doc_id).unwrap();
+        autosave_document(doc, passphrase.as_deref());
 
         if let Some(path) = event.path.clone() {
             let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
-            std::fs::write(path, serde_json::to_string_pretty(&current_doc).unwrap())
-                .expect("Error saving current document to file")
+            // written in the background, the file is remembered once it's saved
+            #[cfg(not(target_arch = "wasm32"))]
+            file_io.save(doc_id, current_doc, path, passphrase.clone());
+            // the browser only lets a click offer a file to save
+            #[cfg(target_arch = "wasm32")]
+            {
+                match super::save_document_file(&path, &current_doc, passphrase.as_deref()) {
+                    Ok(()) if super::is_document_path(&path) => {
+                        app_state
+                            .files
+                            .insert(doc_id, crate::resources::DocFile::new(path));
+                    }
+                    Ok(()) => {}
+                    Err(e) => notifications.send(
+                        Notification::error(
+                            Localized::new("Couldn't save {}")
+                                .with_args([path.display().to_string()]),
+                        )
+                        .with_details(e),
+                    ),
+                }
+            }
         }
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -124,113 +177,322 @@ pub fn save_to_store(
     }
 }
 
-pub fn save_tab(
-    images: Res<Assets<Image>>,
-    node_container_query: Query<(&Style, &Node), With<VeloNodeContainer>>,
-    node_query: Query<
-        (
-            &VeloNode,
-            &UiImage,
-            &BackgroundColor,
-            &ZIndex,
-            &Parent,
-            &Style,
-        ),
-        (With<VeloNode>, Without<VeloNodeContainer>),
-    >,
-    arrows: Query<(&ArrowMeta, &Visibility), With<ArrowMeta>>,
-    request: Res<SaveTabRequest>,
-    mut app_state: ResMut<AppState>,
-    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
-) {
-    #[cfg(not(target_arch = "wasm32"))]
-    if let Some(index) = &mut app_state.search_index {
-        index.tabs_to_delete.insert(request.tab_id.0);
+pub type NodeContainerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static NodeRect,
+        &'static NodeZIndex,
+        Option<&'static Locked>,
+        Option<&'static NodeRotation>,
+    ),
+    With<VeloNodeContainer>,
+>;
+
+pub type NodeQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static VeloNode,
+        &'static Handle<Image>,
+        &'static Sprite,
+        &'static Parent,
+        &'static TextPos,
+        Option<&'static NodeBorder>,
+        Option<&'static NodeEffects>,
+        Option<&'static NodeTextStyle>,
+        Option<&'static NodeTags>,
+        Option<&'static NodeAttachments>,
+    ),
+    (With<VeloNode>, Without<VeloNodeContainer>),
+>;
+
+pub type ArrowQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static ArrowMeta,
+        &'static ArrowStyle,
+        Option<&'static ArrowLabel>,
+        Option<&'static ArrowRelation>,
+        &'static ArrowWaypoints,
+        &'static ArrowPins,
+        &'static Visibility,
+        Option<&'static HiddenByBranch>,
+    ),
+    With<ArrowMeta>,
+>;
+
+type NodeItem<'a> = (
+    &'a VeloNode,
+    &'a Handle<Image>,
+    &'a Sprite,
+    &'a Parent,
+    &'a TextPos,
+    Option<&'a NodeBorder>,
+    Option<&'a NodeEffects>,
+    Option<&'a NodeTextStyle>,
+    Option<&'a NodeTags>,
+    Option<&'a NodeAttachments>,
+);
+
+/// Nodes of the active tab as saved, with their images. Positions and sizes are in world
+/// coordinates, they don't depend on the view.
+pub fn json_nodes(
+    nodes: &NodeQuery,
+    containers: &NodeContainerQuery,
+    text_query: &Query<(&RawText, &CosmicEditImage), With<RawText>>,
+) -> Vec<(JsonNode, Handle<Image>)> {
+    let mut texts: HashMap<ReflectableUuid, String> = text_query
+        .iter()
+        .map(|(raw_text, cosmic_edit)| (raw_text.id, get_cosmic_text(&cosmic_edit.editor)))
+        .collect();
+    nodes
+        .iter()
+        .filter_map(|item| {
+            let text = texts.remove(&item.0.id)?;
+            to_json_node(item, text, containers)
+        })
+        .collect()
+}
+
+/// Saved node of a `VeloNode` entity, its text is read from the editor among its children.
+pub fn json_node(
+    entity: Entity,
+    nodes: &NodeQuery,
+    containers: &NodeContainerQuery,
+    text_query: &Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    children: &Query<&Children>,
+) -> Option<(JsonNode, Handle<Image>)> {
+    let item = nodes.get(entity).ok()?;
+    let text = children
+        .get(entity)
+        .ok()?
+        .iter()
+        .find_map(|child| text_query.get(*child).ok())
+        .map(|(_, cosmic_edit)| get_cosmic_text(&cosmic_edit.editor))?;
+    to_json_node(item, text, containers)
+}
+
+fn to_json_node(
+    item: NodeItem,
+    text: String,
+    containers: &NodeContainerQuery,
+) -> Option<(JsonNode, Handle<Image>)> {
+    let (node, image, sprite, parent, text_pos, border, effects, text_style, tags, attachments) =
+        item;
+    let (rect, z_index, locked, rotation) = containers.get(parent.get()).ok()?;
+    let bg_color = effects.map_or(sprite.color, |e| e.color);
+    let json_node = JsonNode {
+        node_type: node.node_type.clone(),
+        id: node.id.0,
+        left: Val::Px(rect.position.x),
+        bottom: Val::Px(rect.position.y),
+        width: Val::Px(rect.size.x),
+        height: Val::Px(rect.size.y),
+        bg_color,
+        text: JsonNodeText {
+            text,
+            pos: text_pos.clone(),
+            style: text_style.cloned().unwrap_or_default(),
+        },
+        z_index: z_index.0,
+        locked: locked.is_some(),
+        border: border.cloned().unwrap_or_default(),
+        shadow: effects.and_then(|e| e.shadow),
+        opacity: effects.map_or(1., |e| e.opacity),
+        tags: tags.map_or(vec![], |tags| tags.0.clone()),
+        attachments: attachments.map_or(vec![], |attachments| attachments.0.clone()),
+        rotation: rotation.map_or(0., |rotation| rotation.0),
+    };
+    Some((json_node, image.clone()))
+}
+
+/// Arrows of the active tab as saved, waypoints are in world coordinates.
+pub fn json_arrows(arrows: &ArrowQuery) -> Vec<serde_json::Value> {
+    let mut json_arrows = vec![];
+    for (arrow_meta, style, label, relation, waypoints, pins, visibility, hidden_by_branch) in
+        arrows.iter()
+    {
+        // arrows of collapsed branches are hidden, not deleted
+        if visibility != Visibility::Hidden || hidden_by_branch.is_some() {
+            let mut json_arrow = json!(arrow_meta);
+            json_arrow["style"] = json!(style);
+            if let Some(label) = label.filter(|label| !label.0.is_empty()) {
+                json_arrow["label"] = json!(label.0);
+            }
+            if let Some(relation) = relation {
+                json_arrow["relation"] = json!(relation.0);
+            }
+            if *pins != ArrowPins::default() {
+                json_arrow["pins"] = json!(pins);
+            }
+            if !waypoints.0.is_empty() {
+                json_arrow["waypoints"] = json!(waypoints
+                    .0
+                    .iter()
+                    .map(|point| [point.x, point.y])
+                    .collect::<Vec<_>>());
+            }
+            json_arrows.push(json_arrow);
+        }
     }
-    let mut json = json!({
+    json_arrows
+}
+
+/// Saved tab without any content.
+pub fn empty_tab_json() -> serde_json::Value {
+    json!({
         "images": {},
         "nodes": [],
         "arrows": [],
-    });
-    let json_images = json["images"].as_object_mut().unwrap();
-    for (rect, image, _, _, _, _) in node_query.iter() {
-        if let Some(image) = images.get(&image.texture) {
-            if let Ok(img) = image.clone().try_into_dynamic() {
-                let mut image_data: Vec<u8> = Vec::new();
-                img.write_to(&mut Cursor::new(&mut image_data), ImageOutputFormat::Png)
-                    .unwrap();
-                let res_base64 = general_purpose::STANDARD.encode(image_data);
-                json_images.insert(rect.id.0.to_string(), json!(res_base64));
+        "groups": {},
+        "frames": {},
+        "collapsed_branches": [],
+        "strokes": [],
+        "presentation": [],
+    })
+}
+
+/// Images of the nodes encoded for saving, by node id.
+pub fn json_images(
+    nodes: &[(JsonNode, Handle<Image>)],
+    images: &Assets<Image>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut json_images = serde_json::Map::new();
+    for (node, image) in nodes.iter() {
+        // other node types and rounded nodes are drawn with built-in textures
+        if !matches!(node.node_type, NodeType::Rect | NodeType::Image) || !has_image(image) {
+            continue;
+        }
+        if let Some(image) = images.get(image) {
+            if let Some(res_base64) = encode_image_base64(image) {
+                json_images.insert(node.id.to_string(), json!(res_base64));
             }
         }
     }
+    json_images
+}
+
+/// Saved state of the active tab from its nodes (see `json_nodes`), arrows, groups, frames,
+/// collapsed branches, strokes and presentation.
+pub fn tab_json(
+    images: &Assets<Image>,
+    nodes: &[(JsonNode, Handle<Image>)],
+    arrows: &ArrowQuery,
+    groups_query: &Query<(&VeloNodeContainer, &NodeGroup)>,
+    frames_query: &Query<(&VeloNodeContainer, &FrameMembers, Option<&FrameCollapsed>)>,
+    collapsed_query: &Query<&VeloNodeContainer, With<CollapsedBranch>>,
+    strokes: &Query<(&PenStroke, &Visibility)>,
+    presentation_query: &Query<(&VeloNodeContainer, &PresentationStep)>,
+) -> serde_json::Value {
+    let mut json = empty_tab_json();
+    json["images"] = json!(json_images(nodes, images));
 
     let json_nodes = json["nodes"].as_array_mut().unwrap();
-    for (node, _, bg_color, z_index, parent, test_pos_style) in node_query.iter() {
-        for (editable_text, cosmic_edit) in text_query.iter() {
-            if node.id == editable_text.id {
-                let str = get_cosmic_text(&cosmic_edit.editor);
-                let (style, node_container): (&Style, &Node) =
-                    node_container_query.get(parent.get()).unwrap();
-                let left = style.position.left;
-                let bottom = style.position.bottom;
-                let bg_color = bg_color.0;
-                let z_index = match *z_index {
-                    ZIndex::Local(v) => v,
-                    _ => -1,
-                };
-                json_nodes.push(json!(JsonNode {
-                    node_type: node.node_type.clone(),
-                    id: node.id.0,
-                    left,
-                    bottom,
-                    width: Val::Px(node_container.size().x),
-                    height: Val::Px(node_container.size().y),
-                    bg_color,
-                    text: JsonNodeText {
-                        text: str.clone(),
-                        pos: style_to_pos((
-                            test_pos_style.justify_content,
-                            test_pos_style.align_items
-                        )),
-                    },
-                    z_index,
-                }));
-                #[cfg(not(target_arch = "wasm32"))]
-                if let Some(index) = &mut app_state.search_index {
-                    index.node_updates.insert(
-                        super::NodeSearchLocation {
-                            doc_id: request.doc_id.0,
-                            tab_id: request.tab_id.0,
-                            node_id: node.id.0,
-                        },
-                        str.clone(),
-                    );
-                }
-            }
-        }
+    for (node, _) in nodes {
+        json_nodes.push(json!(node));
+    }
+
+    json["arrows"] = json!(json_arrows(arrows));
+
+    let json_groups = json["groups"].as_object_mut().unwrap();
+    for (container, group) in groups_query.iter() {
+        let members = json_groups
+            .entry(group.id.0.to_string())
+            .or_insert(json!([]));
+        members.as_array_mut().unwrap().push(json!(container.id.0));
     }
 
-    let json_arrows = json["arrows"].as_array_mut().unwrap();
-    for (arrow_meta, visibility) in arrows.iter() {
-        if visibility != Visibility::Hidden {
-            json_arrows.push(json!(arrow_meta));
+    let json_frames = json["frames"].as_object_mut().unwrap();
+    for (container, members, collapsed) in frames_query.iter() {
+        json_frames.insert(
+            container.id.0.to_string(),
+            json!(JsonFrame {
+                members: members.0.iter().map(|id| id.0).collect(),
+                collapsed: collapsed.is_some(),
+            }),
+        );
+    }
+
+    let json_collapsed = json["collapsed_branches"].as_array_mut().unwrap();
+    for container in collapsed_query.iter() {
+        json_collapsed.push(json!(container.id.0));
+    }
+
+    let json_strokes = json["strokes"].as_array_mut().unwrap();
+    for (stroke, visibility) in strokes.iter() {
+        // erased strokes are hidden on wasm
+        if visibility == Visibility::Hidden {
+            continue;
         }
+        json_strokes.push(json!(JsonStroke {
+            id: stroke.id.0,
+            points: stroke
+                .points
+                .iter()
+                .map(|point| [point.x, point.y])
+                .collect(),
+            color: stroke.color,
+            width: stroke.width,
+        }));
+    }
+
+    let mut presentation: Vec<_> = presentation_query.iter().collect();
+    presentation.sort_by_key(|(_, step)| **step);
+    let json_presentation = json["presentation"].as_array_mut().unwrap();
+    for (container, _) in presentation {
+        json_presentation.push(json!(container.id.0));
     }
 
+    normalize_checkpoint(&mut json);
+    json
+}
+
+pub fn save_tab(
+    images: Res<Assets<Image>>,
+    node_container_query: NodeContainerQuery,
+    node_query: NodeQuery,
+    arrows: ArrowQuery,
+    request: Res<SaveTabRequest>,
+    mut app_state: ResMut<AppState>,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    groups_query: Query<(&VeloNodeContainer, &NodeGroup)>,
+    frames_query: Query<(&VeloNodeContainer, &FrameMembers, Option<&FrameCollapsed>)>,
+    collapsed_query: Query<&VeloNodeContainer, With<CollapsedBranch>>,
+    strokes: Query<(&PenStroke, &Visibility)>,
+    presentation_query: Query<(&VeloNodeContainer, &PresentationStep)>,
+) {
+    let nodes = json_nodes(&node_query, &node_container_query, &text_query);
+    let json = tab_json(
+        &images,
+        &nodes,
+        &arrows,
+        &groups_query,
+        &frames_query,
+        &collapsed_query,
+        &strokes,
+        &presentation_query,
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(index) = &mut app_state.search_index {
+        index.tabs_to_delete.insert(request.tab_id.0);
+        for (node, _) in nodes {
+            index.node_updates.insert(
+                super::NodeSearchLocation {
+                    doc_id: request.doc_id.0,
+                    tab_id: request.tab_id.0,
+                    node_id: node.id,
+                },
+                node.text.text,
+            );
+        }
+    }
     let doc_id = request.doc_id;
 
     for tab in &mut app_state.docs.get_mut(&doc_id).unwrap().tabs {
         if request.tab_id == tab.id {
-            if (tab.checkpoints.len() as i32) > MAX_CHECKPOINTS {
-                tab.checkpoints.pop_front();
-            }
-            if let Some(last) = tab.checkpoints.back() {
-                if last == &json.to_string() {
-                    break;
-                }
-            }
-            tab.checkpoints.push_back(json.to_string());
+            tab.checkpoint = Some(json.to_string());
             break;
         }
     }
@@ -239,14 +501,50 @@ pub fn save_tab(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ui_plugin::{file_io_results, CommChannels, FileIo, FileIoEvent};
+    use bevy::tasks::{IoTaskPool, TaskPool};
     use tempfile::tempdir;
 
+    /// Document files are written in `IoTaskPool` tasks by `FileIo`.
+    fn add_file_io(app: &mut App) {
+        IoTaskPool::init(TaskPool::default);
+        let (tx, rx) = async_channel::bounded(1);
+        app.insert_resource(CommChannels { tx, rx });
+        app.init_resource::<FileIo>();
+        app.add_event::<FileIoEvent>();
+        app.add_system(file_io_results);
+    }
+
+    /// Saves the requested document and waits for its file to be written.
+    fn update_until_saved(app: &mut App) {
+        let mut reader = app.world.resource::<Events<FileIoEvent>>().get_reader();
+        app.update();
+        app.world.remove_resource::<SaveDocRequest>();
+        for _ in 0..500 {
+            let events = app.world.resource::<Events<FileIoEvent>>();
+            for event in reader.iter(events) {
+                match event {
+                    FileIoEvent::Saved { .. } => return,
+                    FileIoEvent::Failed { error, .. } => panic!("{}", error),
+                    FileIoEvent::Loaded { .. } => {}
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            app.update();
+        }
+        panic!("document file wasn't saved");
+    }
+
     #[test]
     /// No PKV with tags
     fn test_save_doc1() {
         // Setup
         let mut app = App::new();
-        app.add_systems((save_doc, save_to_store.after(save_doc)));
+        app.add_systems((
+            save_doc.run_if(should_save_doc),
+            save_to_store.after(save_doc),
+        ));
+        add_file_io(&mut app);
         let temp_dir = tempdir().unwrap();
         let temp_file_path = temp_dir.path().join("test_doc.json");
         let doc_id = ReflectableUuid::generate();
@@ -258,11 +556,19 @@ mod tests {
                 id: doc_id,
                 name: "test_doc".to_string(),
                 tags: vec!["test_tag".to_string()],
+                assets: HashMap::new(),
+                revision: 0,
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     name: "Test tab".to_string(),
-                    checkpoints: std::collections::VecDeque::new(),
+                    checkpoint: None,
+                    zoom: 1.,
+                    color: None,
+                    icon: None,
+                    pinned: false,
+                    offset: [0., 0.],
+                    selected: vec![],
                 }],
             },
         );
@@ -272,12 +578,13 @@ mod tests {
         };
         app.insert_resource(request);
         app.add_event::<SaveStoreEvent>();
+        app.add_event::<Notification>();
         PkvStore::new("test", "test").clear().unwrap();
         app.insert_resource(PkvStore::new("test", "test"));
         app.insert_resource(app_state);
 
         // Run systems
-        app.update();
+        update_until_saved(&mut app);
 
         // Assertions
         let pkv = app.world.resource::<PkvStore>();
@@ -303,7 +610,11 @@ mod tests {
     fn test_save_doc2() {
         // Setup
         let mut app = App::new();
-        app.add_systems((save_doc, save_to_store.after(save_doc)));
+        app.add_systems((
+            save_doc.run_if(should_save_doc),
+            save_to_store.after(save_doc),
+        ));
+        add_file_io(&mut app);
         let temp_dir = tempdir().unwrap();
         let temp_file_path = temp_dir.path().join("test_doc.json");
         let doc_id = ReflectableUuid::generate();
@@ -315,11 +626,19 @@ mod tests {
                 id: doc_id,
                 name: "test_doc".to_string(),
                 tags: vec!["test_tag_1".to_string()],
+                assets: HashMap::new(),
+                revision: 0,
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     name: "Test tab".to_string(),
-                    checkpoints: std::collections::VecDeque::new(),
+                    checkpoint: None,
+                    zoom: 1.,
+                    color: None,
+                    icon: None,
+                    pinned: false,
+                    offset: [0., 0.],
+                    selected: vec![],
                 }],
             },
         );
@@ -334,11 +653,12 @@ mod tests {
         tags.insert(ReflectableUuid::generate(), vec!["test_tag_2".to_string()]);
         pkv.set("tags", &tags).unwrap();
         app.add_event::<SaveStoreEvent>();
+        app.add_event::<Notification>();
         app.insert_resource(pkv);
         app.insert_resource(app_state);
 
         // Run systems
-        app.update();
+        update_until_saved(&mut app);
 
         // Assertions
         let pkv = app.world.resource::<PkvStore>();
@@ -364,7 +684,11 @@ mod tests {
     fn test_save_doc3() {
         // Setup
         let mut app = App::new();
-        app.add_systems((save_doc, save_to_store.after(save_doc)));
+        app.add_systems((
+            save_doc.run_if(should_save_doc),
+            save_to_store.after(save_doc),
+        ));
+        add_file_io(&mut app);
         let temp_dir = tempdir().unwrap();
         let temp_file_path = temp_dir.path().join("test_doc.json");
         let doc_id = ReflectableUuid::generate();
@@ -377,11 +701,19 @@ mod tests {
                 id: doc_id,
                 name: "test_doc".to_string(),
                 tags: vec!["test_tag_1".to_string()],
+                assets: HashMap::new(),
+                revision: 0,
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     name: "Test tab".to_string(),
-                    checkpoints: std::collections::VecDeque::new(),
+                    checkpoint: None,
+                    zoom: 1.,
+                    color: None,
+                    icon: None,
+                    pinned: false,
+                    offset: [0., 0.],
+                    selected: vec![],
                 }],
             },
         );
@@ -396,11 +728,12 @@ mod tests {
         tags.insert(doc_id, vec!["test_tag_2".to_string()]);
         pkv.set("tags", &tags).unwrap();
         app.add_event::<SaveStoreEvent>();
+        app.add_event::<Notification>();
         app.insert_resource(pkv);
         app.insert_resource(app_state);
 
         // Run systems
-        app.update();
+        update_until_saved(&mut app);
 
         // Assertions
         // Check that the document was saved to the PKV store
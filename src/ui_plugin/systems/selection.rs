@@ -0,0 +1,148 @@
+use bevy::{prelude::*, sprite::Anchor, window::PrimaryWindow};
+
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
+
+use super::{
+    node_outline,
+    ui_helpers::{CanvasOutline, MainPanel, NodeGroup, RubberBand},
+    CanvasZoom, NodeBorder, NodeEffects, NodeIndex, SelectedArrow, SelectedNodes, Theme, Tool,
+    VeloNode, VeloNodeContainer, OVERLAY_Z,
+};
+
+// Drags shorter than that (in screen pixels) are plain clicks
+const CLICK_DISTANCE: f32 = 3.;
+
+/// Extends `ids` with all members of the groups they belong to.
+///
+/// `groups` holds `(node id, group id)` pairs of grouped nodes.
+pub fn with_group_members(
+    ids: &[ReflectableUuid],
+    groups: &[(ReflectableUuid, ReflectableUuid)],
+) -> Vec<ReflectableUuid> {
+    let mut result = ids.to_vec();
+    for (node_id, group_id) in groups.iter() {
+        if result.contains(node_id) {
+            continue;
+        }
+        let in_group = groups
+            .iter()
+            .any(|(id, group)| group == group_id && ids.contains(id));
+        if in_group {
+            result.push(*node_id);
+        }
+    }
+    result
+}
+
+pub fn rubber_band_selection(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut rubber_band_query: Query<(Entity, &mut Transform, &mut Sprite), With<RubberBand>>,
+    nodes: Query<(&VeloNodeContainer, Option<&NodeGroup>)>,
+    node_index: Res<NodeIndex>,
+    zoom: Res<CanvasZoom>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    tool: Res<Tool>,
+    selected_arrow: Res<SelectedArrow>,
+    theme: Res<Theme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut start: Local<Option<Vec2>>,
+) {
+    let (camera, camera_transform) = camera_q.single();
+    let Some(cursor) = windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    // Space+drag pans the canvas, the other tools use the drag instead and a dragged arrow end
+    // or waypoint is moved
+    let space = input.pressed(KeyCode::Space);
+    let tool = *tool != Tool::Select || selected_arrow.is_dragging();
+
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Clicked && !space && !tool {
+            *start = Some(cursor);
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: theme.selection.with_a(0.2),
+                        custom_size: Some(Vec2::ZERO),
+                        anchor: Anchor::BottomLeft,
+                        ..default()
+                    },
+                    transform: Transform::from_translation(cursor.extend(OVERLAY_Z)),
+                    ..default()
+                },
+                CanvasOutline::new(theme.selection, 1.),
+                RubberBand,
+            ));
+        }
+    }
+
+    if let Some(start_pos) = *start {
+        let min = start_pos.min(cursor);
+        let max = start_pos.max(cursor);
+        for (_, mut transform, mut sprite) in rubber_band_query.iter_mut() {
+            transform.translation = min.extend(OVERLAY_Z);
+            sprite.custom_size = Some(max - min);
+        }
+
+        if buttons.just_released(MouseButton::Left) {
+            if !shift {
+                selected_nodes.0.clear();
+            }
+            // a plain click on the canvas only clears the selection
+            if (max - min).max_element() > CLICK_DISTANCE / zoom.0 {
+                for (_, id) in node_index.intersecting(Rect::from_corners(min, max)) {
+                    if !selected_nodes.0.contains(&id) {
+                        selected_nodes.0.push(id);
+                    }
+                }
+            }
+            let groups: Vec<_> = nodes
+                .iter()
+                .filter_map(|(container, group)| group.map(|g| (container.id, g.id)))
+                .collect();
+            selected_nodes.0 = with_group_members(&selected_nodes.0, &groups);
+            for (entity, ..) in rubber_band_query.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            *start = None;
+        }
+    }
+}
+
+pub fn selected_nodes_changed(
+    selected_nodes: Res<SelectedNodes>,
+    theme: Res<Theme>,
+    mut velo_node_query: Query<
+        (
+            &mut CanvasOutline,
+            &VeloNode,
+            Option<&NodeBorder>,
+            Option<&NodeEffects>,
+        ),
+        With<VeloNode>,
+    >,
+    added_nodes: Query<(), Added<VeloNode>>,
+) {
+    // newly spawned nodes (e.g. duplicates) may already be selected
+    if !selected_nodes.is_changed() && !theme.is_changed() && added_nodes.is_empty() {
+        return;
+    }
+    for (mut outline, node, border, effects) in velo_node_query.iter_mut() {
+        if selected_nodes.0.contains(&node.id) {
+            outline.color = theme.selection;
+            outline.thickness = 2.;
+        } else {
+            *outline = node_outline(&node.node_type, border, effects.map_or(1., |e| e.opacity));
+        }
+    }
+}
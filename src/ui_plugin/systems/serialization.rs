@@ -0,0 +1,155 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+/// Decimals kept for the numbers of saved documents, finer differences are float noise.
+const DECIMALS: i32 = 3;
+
+fn round_numbers(value: &mut Value) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let factor = 10f64.powi(DECIMALS);
+            if let Some(rounded) = number
+                .as_f64()
+                .and_then(|x| serde_json::Number::from_f64((x * factor).round() / factor))
+            {
+                *number = rounded;
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(round_numbers),
+        Value::Object(map) => map.values_mut().for_each(round_numbers),
+        _ => {}
+    }
+}
+
+fn sort_by_id(values: &mut Value) {
+    if let Some(values) = values.as_array_mut() {
+        values.sort_by(|a, b| a["id"].to_string().cmp(&b["id"].to_string()));
+    }
+}
+
+/// Puts the saved tab in a stable form: nodes and strokes sorted by id, arrows by the ids of the
+/// nodes they connect and numbers rounded. Object keys are always sorted by `serde_json`.
+pub fn normalize_checkpoint(checkpoint: &mut Value) {
+    sort_by_id(&mut checkpoint["nodes"]);
+    sort_by_id(&mut checkpoint["strokes"]);
+    if let Some(arrows) = checkpoint["arrows"].as_array_mut() {
+        arrows.sort_by_key(|arrow| {
+            (
+                arrow["start"]["id"].to_string(),
+                arrow["end"]["id"].to_string(),
+                arrow.to_string(),
+            )
+        });
+    }
+    if let Some(ids) = checkpoint["collapsed_branches"].as_array_mut() {
+        ids.sort_by_key(|id| id.to_string());
+    }
+    round_numbers(checkpoint);
+}
+
+/// Writes the saved tab embedded as JSON in human readable formats so that document files diff
+/// line by line, in the stable form of `normalize_checkpoint`. Other formats keep the string.
+pub fn serialize_checkpoint<S: Serializer>(
+    checkpoint: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let embedded = checkpoint
+        .as_deref()
+        .filter(|_| serializer.is_human_readable())
+        .and_then(|checkpoint| serde_json::from_str::<Value>(checkpoint).ok());
+    match embedded {
+        Some(mut embedded) => {
+            normalize_checkpoint(&mut embedded);
+            serializer.serialize_some(&embedded)
+        }
+        None => checkpoint.serialize(serializer),
+    }
+}
+
+/// Reads the saved tab written by `serialize_checkpoint` or as a string. Documents written
+/// before it was kept alone have a list of checkpoints of which the last one is the current
+/// state.
+pub fn deserialize_checkpoint<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    fn to_string(checkpoint: Value) -> Option<String> {
+        match checkpoint {
+            Value::Null => None,
+            Value::String(checkpoint) => Some(checkpoint),
+            embedded => Some(embedded.to_string()),
+        }
+    }
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Array(mut checkpoints) => checkpoints.pop().and_then(to_string),
+        checkpoint => to_string(checkpoint),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::components::Doc;
+
+    #[test]
+    fn test_normalize_checkpoint() {
+        let mut checkpoint = json!({
+            "nodes": [
+                { "id": "b", "left": { "Px": 10.000000123 } },
+                { "id": "a", "left": { "Px": 0.1234567 } },
+            ],
+            "arrows": [
+                { "start": { "id": "b" }, "end": { "id": "a" } },
+                { "start": { "id": "a" }, "end": { "id": "b" } },
+            ],
+            "collapsed_branches": ["b", "a"],
+            "presentation": ["b", "a"],
+        });
+        normalize_checkpoint(&mut checkpoint);
+        assert_eq!(
+            checkpoint,
+            json!({
+                "nodes": [
+                    { "id": "a", "left": { "Px": 0.123 } },
+                    { "id": "b", "left": { "Px": 10.0 } },
+                ],
+                "arrows": [
+                    { "start": { "id": "a" }, "end": { "id": "b" } },
+                    { "start": { "id": "b" }, "end": { "id": "a" } },
+                ],
+                "collapsed_branches": ["a", "b"],
+                // order of the presentation matters
+                "presentation": ["b", "a"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_doc_json_roundtrip() {
+        let json = json!({
+            "id": "6f9619ff-8b86-d011-b42d-00cf4fc964ff",
+            "name": "Doc",
+            "tags": [],
+            "tabs": [{
+                "id": "6f9619ff-8b86-d011-b42d-00cf4fc964fe",
+                "name": "Tab 1",
+                "is_active": true,
+                "zoom": 1.1,
+                "checkpoints": ["{\"nodes\":[{\"id\":\"b\"},{\"id\":\"a\"}]}"],
+            }],
+        });
+        let doc: Doc = serde_json::from_value(json).unwrap();
+        let written: Value = serde_json::from_str(&serde_json::to_string(&doc).unwrap()).unwrap();
+        assert_eq!(
+            written["tabs"][0]["checkpoint"],
+            json!({ "nodes": [{ "id": "a" }, { "id": "b" }] })
+        );
+        assert_eq!(written["tabs"][0]["zoom"], json!(1.1));
+        let read: Doc = serde_json::from_value(written).unwrap();
+        assert_eq!(
+            read.tabs[0].checkpoint.as_deref(),
+            Some("{\"nodes\":[{\"id\":\"a\"},{\"id\":\"b\"}]}")
+        );
+    }
+}
@@ -4,20 +4,69 @@ use crate::utils::{get_timestamp, ReflectableUuid};
 
 use std::time::Duration;
 
-use super::{UiState, VeloNode};
+use super::{
+    with_group_members, Locked, NodeGroup, SelectedNodes, Tool, UiState, VeloNode,
+    VeloNodeContainer,
+};
 
 pub fn set_focused_entity(
     mut interaction_query: Query<(&Interaction, &VeloNode), (Changed<Interaction>, With<VeloNode>)>,
     mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    tool: Res<Tool>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
     buttons: Res<Input<MouseButton>>,
+    input: Res<Input<KeyCode>>,
+    groups_query: Query<(&VeloNodeContainer, &NodeGroup)>,
+    locked_query: Query<&VeloNodeContainer, With<Locked>>,
     mut holding_time: Local<(Duration, Option<ReflectableUuid>)>,
     mut double_click: Local<(Duration, Option<ReflectableUuid>)>,
 ) {
     let mut primary_window = windows.single_mut();
+    let groups: Vec<_> = groups_query
+        .iter()
+        .map(|(container, group)| (container.id, group.id))
+        .collect();
+    let locked: Vec<_> = locked_query.iter().map(|container| container.id).collect();
     for (interaction, node) in &mut interaction_query {
+        // the other tools use presses on nodes for panning, drawing and arrows
+        if *tool != Tool::Select {
+            if *interaction != Interaction::Clicked {
+                primary_window.cursor.icon = tool.cursor();
+            }
+            continue;
+        }
         match *interaction {
             Interaction::Clicked => {
+                let members = with_group_members(&[node.id], &groups);
+                if input.any_pressed([KeyCode::RShift, KeyCode::LShift]) {
+                    if selected_nodes.0.contains(&node.id) {
+                        // deselecting the edited node ends editing
+                        if selected_nodes
+                            .edited(&ui_state)
+                            .map_or(false, |id| members.contains(&id))
+                        {
+                            ui_state.editing_node = false;
+                        }
+                        selected_nodes.0.retain(|id| !members.contains(id));
+                    } else {
+                        selected_nodes.0.extend(members);
+                    }
+                    continue;
+                }
+                // the clicked node becomes the primary one, dragging it moves the selection
+                if selected_nodes.primary() != Some(node.id) {
+                    ui_state.editing_node = false;
+                }
+                if selected_nodes.0.contains(&node.id) {
+                    selected_nodes.set_primary(node.id);
+                } else {
+                    selected_nodes.0 = members.clone();
+                }
+                // locked nodes can be selected but not moved or edited
+                if locked.contains(&node.id) {
+                    continue;
+                }
                 primary_window.cursor.icon = CursorIcon::Text;
                 let now_ms = get_timestamp();
                 if double_click.1 == Some(node.id)
@@ -25,17 +74,22 @@ pub fn set_focused_entity(
                         < Duration::from_millis(500)
                 {
                     *ui_state = UiState::default();
-                    ui_state.entity_to_edit = Some(node.id);
+                    selected_nodes.edit(&mut ui_state, node.id);
+                    selected_nodes.0 = members;
                 } else {
                     *double_click = (Duration::from_millis(now_ms as u64), Some(node.id));
                 }
                 *holding_time = (Duration::from_millis(now_ms as u64), Some(node.id));
             }
             Interaction::Hovered => {
-                if ui_state.hold_entity.is_none() && ui_state.entity_to_edit.is_none() {
+                if locked.contains(&node.id) {
+                    primary_window.cursor.icon = CursorIcon::NotAllowed;
+                    continue;
+                }
+                if !ui_state.holding_node && !ui_state.editing_node {
                     primary_window.cursor.icon = CursorIcon::Hand;
                 }
-                if ui_state.entity_to_edit.is_some() {
+                if ui_state.editing_node {
                     primary_window.cursor.icon = CursorIcon::Text;
                 }
             }
@@ -45,22 +99,22 @@ pub fn set_focused_entity(
         }
     }
 
-    if ui_state.hold_entity.is_some() {
+    if ui_state.holding_node {
         primary_window.cursor.icon = CursorIcon::Move;
     }
 
     let now_ms = get_timestamp();
     // 150ms delay before re-positioning the rectangle
-    if ui_state.hold_entity.is_none()
+    if !ui_state.holding_node
         && Duration::from_millis(now_ms as u64) - holding_time.0 > Duration::from_millis(150)
         && holding_time.1.is_some()
     {
-        ui_state.hold_entity = holding_time.1;
+        ui_state.holding_node = selected_nodes.primary() == holding_time.1;
     }
 
     if buttons.just_released(MouseButton::Left) {
         *holding_time = (Duration::new(0, 0), None);
-        ui_state.hold_entity = None;
+        ui_state.holding_node = false;
         ui_state.entity_to_resize = None;
     }
 }
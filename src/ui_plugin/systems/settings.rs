@@ -0,0 +1,535 @@
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use bevy_ui_borders::BorderColor;
+use serde::{Deserialize, Serialize};
+
+use super::ui_helpers::{
+    Localized, MainPanel, SettingsButton, SettingsOption, SettingsPanel, ThemedBackground,
+    ThemedBorder, ThemedText,
+};
+use super::{
+    DockSide, DockedPanel, GridSettings, Keybindings, Language, Locale, MotionSettings,
+    Notification, PanelLayout, Theme, ThemeColor, ThemeKind, NODE_COLORS,
+};
+use crate::resources::{AppState, SaveDocRequest};
+
+const SETTINGS_KEY: &str = "settings";
+const AUTOSAVE_MINUTES: [u32; 3] = [1, 5, 10];
+const GRID_SIZES: [f32; 3] = [10., 20., 40.];
+
+/// Preferences kept between starts, in the local storage of the browser on the web.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    /// Light or dark, a custom theme of the theme file is applied over it
+    pub theme: ThemeKind,
+    pub language: Language,
+    /// Minutes between saves of the open document, 0 turns autosave off
+    pub autosave_minutes: u32,
+    pub grid_enabled: bool,
+    pub grid_size: f32,
+    /// Stops animations and eases of the canvas, arrows and carets
+    pub reduced_motion: bool,
+    /// Replaces the theme with the high contrast one while on
+    pub high_contrast: bool,
+    /// Background of new nodes instead of the one of the theme
+    pub node_background: Option<Color>,
+    pub keybindings: Keybindings,
+    pub panels: PanelLayout,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let grid = GridSettings::default();
+        Self {
+            theme: ThemeKind::default(),
+            language: Language::default(),
+            autosave_minutes: 0,
+            grid_enabled: grid.enabled,
+            grid_size: grid.size,
+            reduced_motion: false,
+            high_contrast: false,
+            node_background: None,
+            keybindings: Keybindings::default(),
+            panels: PanelLayout::default(),
+        }
+    }
+}
+
+/// Option of the settings panel.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingChange {
+    Theme(ThemeKind),
+    Language(Language),
+    AutosaveMinutes(u32),
+    Grid(bool),
+    GridSize(f32),
+    ReducedMotion(bool),
+    HighContrast(bool),
+    NodeBackground(Option<Color>),
+    Dock(DockedPanel, DockSide),
+}
+
+impl Settings {
+    /// Theme the settings ask for, a custom theme of the theme file is applied over it.
+    pub fn theme_kind(&self) -> ThemeKind {
+        if self.high_contrast {
+            ThemeKind::HighContrast
+        } else {
+            self.theme
+        }
+    }
+}
+
+impl SettingChange {
+    pub fn apply(self, settings: &mut Settings) {
+        match self {
+            SettingChange::Theme(theme) => settings.theme = theme,
+            SettingChange::Language(language) => settings.language = language,
+            SettingChange::AutosaveMinutes(minutes) => settings.autosave_minutes = minutes,
+            SettingChange::Grid(enabled) => settings.grid_enabled = enabled,
+            SettingChange::GridSize(size) => settings.grid_size = size,
+            SettingChange::ReducedMotion(reduced) => settings.reduced_motion = reduced,
+            SettingChange::HighContrast(high_contrast) => settings.high_contrast = high_contrast,
+            SettingChange::NodeBackground(color) => settings.node_background = color,
+            SettingChange::Dock(panel, side) => settings.panels.set_side(panel, side),
+        }
+    }
+
+    pub fn is_selected(self, settings: &Settings) -> bool {
+        let mut changed = settings.clone();
+        self.apply(&mut changed);
+        changed == *settings
+    }
+}
+
+/// Loads the settings saved last time, the language and the theme are applied before the
+/// layout is spawned.
+pub fn load_settings(
+    pkv: Res<PkvStore>,
+    mut settings: ResMut<Settings>,
+    mut theme: ResMut<Theme>,
+    mut locale: ResMut<Locale>,
+) {
+    if let Ok(saved) = pkv.get::<Settings>(SETTINGS_KEY) {
+        *settings = saved;
+    }
+    for (chord, action, other) in settings.keybindings.conflicts() {
+        warn!("{} is bound to both {:?} and {:?}", chord, action, other);
+    }
+    *theme = Theme::for_kind(settings.theme_kind());
+    locale.language = settings.language;
+}
+
+/// Keeps the settings and the resources they control in sync: changes of the settings panel
+/// are applied and changes made elsewhere, like with the "Toggle Grid" button, are kept in the
+/// settings. The settings are saved whenever they change.
+pub fn sync_settings(
+    mut settings: ResMut<Settings>,
+    mut grid_settings: ResMut<GridSettings>,
+    mut motion_settings: ResMut<MotionSettings>,
+    mut theme: ResMut<Theme>,
+    mut locale: ResMut<Locale>,
+    mut pkv: ResMut<PkvStore>,
+    mut notifications: EventWriter<Notification>,
+    mut applied_theme: Local<Option<ThemeKind>>,
+) {
+    // on the first run the resources were just added and the loaded settings are applied
+    let first_run = applied_theme.is_none();
+    let mut tracked = settings.clone();
+    if grid_settings.is_changed() && !first_run {
+        tracked.grid_enabled = grid_settings.enabled;
+        tracked.grid_size = grid_settings.size;
+    }
+    if motion_settings.is_changed() && !first_run {
+        tracked.reduced_motion = motion_settings.reduced_motion;
+    }
+    // e.g. "Toggle Dark Mode" also turns high contrast off
+    if theme.is_changed() && !first_run {
+        if let ThemeKind::Light | ThemeKind::Dark = theme.kind {
+            tracked.theme = theme.kind;
+            tracked.high_contrast = false;
+        }
+    }
+    if locale.is_changed() && !first_run {
+        tracked.language = locale.language;
+    }
+    if tracked != *settings {
+        *settings = tracked;
+    }
+
+    if settings.is_changed() {
+        if grid_settings.enabled != settings.grid_enabled
+            || grid_settings.size != settings.grid_size
+        {
+            grid_settings.enabled = settings.grid_enabled;
+            grid_settings.size = settings.grid_size;
+        }
+        if motion_settings.reduced_motion != settings.reduced_motion {
+            motion_settings.reduced_motion = settings.reduced_motion;
+        }
+        if locale.language != settings.language {
+            locale.language = settings.language;
+        }
+        // a custom theme is only replaced when another theme is chosen
+        let kind = settings.theme_kind();
+        if *applied_theme != Some(kind) {
+            *applied_theme = Some(kind);
+            if theme.kind != kind {
+                *theme = Theme::for_kind(kind);
+            }
+        }
+        if let Err(e) = pkv.set(SETTINGS_KEY, &*settings) {
+            let message = Localized::new("Couldn't save the settings");
+            notifications.send(Notification::error(message).with_details(e));
+        }
+    }
+
+    if settings.is_changed() || theme.is_changed() {
+        // custom themes keep their own color when the setting is removed
+        let node_background = match (settings.node_background, theme.kind) {
+            (Some(color), _) => Some(color),
+            (None, ThemeKind::Custom) => None,
+            (None, kind) => Some(Theme::for_kind(kind).node_background),
+        };
+        if let Some(color) = node_background {
+            if theme.node_background != color {
+                theme.node_background = color;
+            }
+        }
+    }
+}
+
+/// Saves the open document every `autosave_minutes` of the settings.
+pub fn autosave(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    app_state: Res<AppState>,
+    save_request: Option<Res<SaveDocRequest>>,
+    mut elapsed: Local<f32>,
+) {
+    if settings.autosave_minutes == 0 {
+        *elapsed = 0.;
+        return;
+    }
+    *elapsed += time.delta_seconds();
+    if *elapsed < settings.autosave_minutes as f32 * 60. || save_request.is_some() {
+        return;
+    }
+    *elapsed = 0.;
+    if let Some(doc_id) = app_state.current_document {
+        commands.insert_resource(SaveDocRequest { doc_id, path: None });
+    }
+}
+
+fn spawn_option(
+    builder: &mut ChildBuilder,
+    label: Option<Localized>,
+    change: SettingChange,
+    settings: &Settings,
+    theme: &Theme,
+) {
+    let selected = change.is_selected(settings);
+    let background = match change {
+        SettingChange::NodeBackground(Some(color)) => color,
+        _ if selected => theme.selection.with_a(0.3),
+        _ => theme.surface,
+    };
+    let border = if selected {
+        theme.selection
+    } else {
+        theme.border
+    };
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Auto, Val::Px(24.)),
+                    min_size: Size::width(Val::Px(24.)),
+                    align_items: AlignItems::Center,
+                    padding: UiRect::horizontal(Val::Px(6.)),
+                    margin: UiRect::all(Val::Px(2.)),
+                    border: UiRect::all(Val::Px(if selected { 2. } else { 1. })),
+                    ..default()
+                },
+                background_color: background.into(),
+                ..default()
+            },
+            BorderColor(border),
+            SettingsOption(change),
+        ))
+        .with_children(|builder| {
+            if let Some(label) = label {
+                builder.spawn((
+                    TextBundle::from_section(
+                        label.text.clone(),
+                        TextStyle {
+                            font_size: 14.,
+                            color: theme.text,
+                            ..default()
+                        },
+                    ),
+                    ThemedText(ThemeColor::Text),
+                    label,
+                ));
+            }
+        });
+}
+
+fn spawn_setting(
+    builder: &mut ChildBuilder,
+    title: &str,
+    options: Vec<(Option<Localized>, SettingChange)>,
+    settings: &Settings,
+    theme: &Theme,
+) {
+    builder.spawn((
+        TextBundle::from_section(
+            title,
+            TextStyle {
+                font_size: 14.,
+                color: theme.text,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            margin: UiRect::top(Val::Px(6.)),
+            ..default()
+        }),
+        ThemedText(ThemeColor::Text),
+        Localized::new(title),
+    ));
+    builder
+        .spawn(NodeBundle {
+            style: Style {
+                flex_wrap: FlexWrap::Wrap,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|builder| {
+            for (label, change) in options {
+                spawn_option(builder, label, change, settings, theme);
+            }
+        });
+}
+
+fn on_off(change: fn(bool) -> SettingChange) -> Vec<(Option<Localized>, SettingChange)> {
+    vec![
+        (Some(Localized::new("On")), change(true)),
+        (Some(Localized::new("Off")), change(false)),
+    ]
+}
+
+/// Panel in the right top corner of the canvas with the options of each setting.
+fn spawn_settings_panel(
+    commands: &mut Commands,
+    main_panel: Entity,
+    settings: &Settings,
+    theme: &Theme,
+) {
+    let panel = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(300.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: theme.surface.into(),
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            BorderColor(theme.border),
+            ThemedBackground(ThemeColor::Surface),
+            ThemedBorder(ThemeColor::Border),
+            SettingsPanel,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    "Settings",
+                    TextStyle {
+                        font_size: 18.,
+                        color: theme.text,
+                        ..default()
+                    },
+                ),
+                ThemedText(ThemeColor::Text),
+                Localized::new("Settings"),
+            ));
+            let themes = [("Light", ThemeKind::Light), ("Dark", ThemeKind::Dark)]
+                .into_iter()
+                .map(|(label, kind)| (Some(Localized::new(label)), SettingChange::Theme(kind)))
+                .collect();
+            spawn_setting(builder, "Theme", themes, settings, theme);
+            // language names aren't translated
+            let languages = Language::ALL
+                .into_iter()
+                .map(|language| {
+                    let label = Localized::new("{}").with_args([language.name()]);
+                    (Some(label), SettingChange::Language(language))
+                })
+                .collect();
+            spawn_setting(builder, "Language", languages, settings, theme);
+            let mut autosave = vec![(
+                Some(Localized::new("Off")),
+                SettingChange::AutosaveMinutes(0),
+            )];
+            autosave.extend(AUTOSAVE_MINUTES.into_iter().map(|minutes| {
+                let label = Localized::new("{} min").with_args([minutes.to_string()]);
+                (Some(label), SettingChange::AutosaveMinutes(minutes))
+            }));
+            spawn_setting(builder, "Autosave", autosave, settings, theme);
+            spawn_setting(
+                builder,
+                "Grid",
+                on_off(SettingChange::Grid),
+                settings,
+                theme,
+            );
+            let grid_sizes = GRID_SIZES
+                .into_iter()
+                .map(|size| {
+                    let label = Localized::new("{} px").with_args([size.to_string()]);
+                    (Some(label), SettingChange::GridSize(size))
+                })
+                .collect();
+            spawn_setting(builder, "Grid size", grid_sizes, settings, theme);
+            spawn_setting(
+                builder,
+                "Reduced motion",
+                on_off(SettingChange::ReducedMotion),
+                settings,
+                theme,
+            );
+            spawn_setting(
+                builder,
+                "High contrast",
+                on_off(SettingChange::HighContrast),
+                settings,
+                theme,
+            );
+            let mut node_backgrounds = vec![(
+                Some(Localized::new("Theme")),
+                SettingChange::NodeBackground(None),
+            )];
+            node_backgrounds.extend(
+                [Color::WHITE]
+                    .into_iter()
+                    .chain(NODE_COLORS)
+                    .map(|color| (None, SettingChange::NodeBackground(Some(color)))),
+            );
+            spawn_setting(builder, "New nodes", node_backgrounds, settings, theme);
+            for (title, panel) in [
+                ("Add panel", DockedPanel::Add),
+                ("Search panel", DockedPanel::Search),
+                ("Tags panel", DockedPanel::Tags),
+            ] {
+                let sides = [("Left", DockSide::Left), ("Right", DockSide::Right)]
+                    .into_iter()
+                    .map(|(label, side)| {
+                        (
+                            Some(Localized::new(label)),
+                            SettingChange::Dock(panel, side),
+                        )
+                    })
+                    .collect();
+                spawn_setting(builder, title, sides, settings, theme);
+            }
+        })
+        .id();
+    commands.entity(main_panel).add_child(panel);
+}
+
+/// Opens or closes the settings panel.
+pub fn toggle_settings_panel(
+    mut commands: Commands,
+    interactions: Query<&Interaction, (Changed<Interaction>, With<SettingsButton>)>,
+    panel: Query<Entity, With<SettingsPanel>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+) {
+    if !interactions
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    if let Ok(entity) = panel.get_single() {
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    spawn_settings_panel(&mut commands, main_panel, &settings, &theme);
+}
+
+/// Applies the clicked option, the open panel is redrawn when the settings or the theme change.
+pub fn settings_panel_click(
+    mut commands: Commands,
+    interactions: Query<(&Interaction, &SettingsOption), Changed<Interaction>>,
+    panel: Query<Entity, With<SettingsPanel>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    mut settings: ResMut<Settings>,
+    theme: Res<Theme>,
+) {
+    for (interaction, option) in interactions.iter() {
+        if *interaction == Interaction::Clicked {
+            option.0.apply(&mut settings);
+        }
+    }
+    if !settings.is_changed() && !theme.is_changed() {
+        return;
+    }
+    let (Ok(entity), Ok(main_panel)) = (panel.get_single(), main_panel_query.get_single()) else {
+        return;
+    };
+    commands.entity(entity).despawn_recursive();
+    spawn_settings_panel(&mut commands, main_panel, &settings, &theme);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setting_change() {
+        let mut settings = Settings::default();
+        assert!(SettingChange::AutosaveMinutes(0).is_selected(&settings));
+        assert!(!SettingChange::Theme(ThemeKind::Dark).is_selected(&settings));
+        SettingChange::Theme(ThemeKind::Dark).apply(&mut settings);
+        SettingChange::NodeBackground(Some(NODE_COLORS[0])).apply(&mut settings);
+        assert_eq!(settings.theme, ThemeKind::Dark);
+        assert!(SettingChange::NodeBackground(Some(NODE_COLORS[0])).is_selected(&settings));
+        assert!(!SettingChange::NodeBackground(None).is_selected(&settings));
+        SettingChange::Dock(DockedPanel::Tags, DockSide::Left).apply(&mut settings);
+        assert_eq!(settings.panels.tags, DockSide::Left);
+        SettingChange::HighContrast(true).apply(&mut settings);
+        assert_eq!(settings.theme_kind(), ThemeKind::HighContrast);
+        // the theme chosen before comes back
+        SettingChange::HighContrast(false).apply(&mut settings);
+        assert_eq!(settings.theme_kind(), ThemeKind::Dark);
+    }
+
+    #[test]
+    fn test_settings_defaults() {
+        // settings saved by an older version miss the newer fields
+        let settings: Settings = serde_json::from_str(r#"{ "grid_size": 40.0 }"#).unwrap();
+        assert_eq!(settings.grid_size, 40.);
+        assert_eq!(settings.autosave_minutes, 0);
+        assert_eq!(settings.theme, ThemeKind::Light);
+        assert_eq!(settings.panels, PanelLayout::default());
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::HashSet;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::{CosmicEditImage, FontSystemState};
+use bevy_pkv::PkvStore;
+use bevy_ui_borders::BorderColor;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::ui_helpers::{
+    spawn_node, DeleteSnippetButton, Localized, MainPanel, SnippetButton, SnippetLibrary,
+    SnippetsButton,
+};
+use super::{
+    canvas_to_world, json_arrow_event, json_arrows, json_images, json_node_meta, json_nodes,
+    notify, with_new_ids, ArrowQuery, CanvasOffset, CanvasZoom, ContextMenuAction,
+    ContextMenuEvent, History, NodeContainerQuery, NodeQuery, Notification, RawText, SelectedNodes,
+};
+use crate::utils::{decode_image_base64, ReflectableUuid};
+use crate::JsonNode;
+
+pub const SAVE_SNIPPET_ACTION: &str = "save_snippet";
+const SNIPPETS_KEY: &str = "snippets";
+const MAX_NAME_LENGTH: usize = 30;
+
+/// Nodes saved from a selection to be stamped onto any canvas later.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snippet {
+    pub id: ReflectableUuid,
+    pub name: String,
+    /// Nodes, images and arrows in the format of saved tabs, positioned from the left bottom
+    /// corner of the snippet.
+    pub content: String,
+    pub size: [f32; 2],
+}
+
+/// Moves the nodes and arrow waypoints of the saved selection so its left bottom corner is at
+/// the origin, returns the size of the selection.
+pub fn normalize_snippet(content: &mut Value) -> Vec2 {
+    let rects: Vec<Rect> = content["nodes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|node| serde_json::from_value::<JsonNode>(node.clone()).ok())
+        .filter_map(
+            |node| match (node.left, node.bottom, node.width, node.height) {
+                (Val::Px(left), Val::Px(bottom), Val::Px(width), Val::Px(height)) => {
+                    Some(Rect::new(left, bottom, left + width, bottom + height))
+                }
+                _ => None,
+            },
+        )
+        .collect();
+    let Some(bounds) = rects.iter().copied().reduce(|bounds, rect| Rect {
+        min: bounds.min.min(rect.min),
+        max: bounds.max.max(rect.max),
+    }) else {
+        return Vec2::ZERO;
+    };
+    move_snippet(content, -bounds.min);
+    bounds.size()
+}
+
+/// Moves the nodes and arrow waypoints of the snippet content by `delta`.
+pub fn move_snippet(content: &mut Value, delta: Vec2) {
+    for node in content["nodes"].as_array_mut().into_iter().flatten() {
+        for (key, delta) in [("left", delta.x), ("bottom", delta.y)] {
+            if let Some(value) = node[key]["Px"].as_f64() {
+                node[key]["Px"] = json!(value as f32 + delta);
+            }
+        }
+    }
+    for arrow in content["arrows"].as_array_mut().into_iter().flatten() {
+        for waypoint in arrow["waypoints"].as_array_mut().into_iter().flatten() {
+            if let (Some(x), Some(y)) = (waypoint[0].as_f64(), waypoint[1].as_f64()) {
+                *waypoint = json!([x as f32 + delta.x, y as f32 + delta.y]);
+            }
+        }
+    }
+}
+
+/// First line of the text of the top left node, for the name of a new snippet.
+pub fn snippet_name(content: &Value, count: usize) -> String {
+    let mut nodes: Vec<JsonNode> = content["nodes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|node| serde_json::from_value(node.clone()).ok())
+        .collect();
+    let top_left = |node: &JsonNode| match (node.left, node.bottom, node.height) {
+        (Val::Px(left), Val::Px(bottom), Val::Px(height)) => (-(bottom + height), left),
+        _ => (0., 0.),
+    };
+    nodes.sort_by(|a, b| {
+        let (a, b) = (top_left(a), top_left(b));
+        a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+    });
+    nodes
+        .iter()
+        .find_map(|node| {
+            let line = node.text.text.lines().next()?.trim();
+            (!line.is_empty()).then(|| line.chars().take(MAX_NAME_LENGTH).collect())
+        })
+        .unwrap_or_else(|| format!("Snippet {}", count + 1))
+}
+
+fn load_snippets(pkv: &PkvStore) -> Vec<Snippet> {
+    pkv.get::<Vec<Snippet>>(SNIPPETS_KEY).unwrap_or_default()
+}
+
+fn store_snippets(commands: &mut Commands, pkv: &mut PkvStore, snippets: &[Snippet]) {
+    if let Err(e) = pkv.set(SNIPPETS_KEY, &snippets) {
+        let message = Localized::new("Couldn't save the snippets");
+        notify(commands, Notification::error(message).with_details(e));
+    }
+}
+
+fn spawn_library_button(builder: &mut ChildBuilder, label: &str, bundle: impl Bundle, width: Val) {
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(width, Val::Px(26.)),
+                    align_items: AlignItems::Center,
+                    padding: UiRect::horizontal(Val::Px(6.)),
+                    overflow: Overflow::Hidden,
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            },
+            bundle,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 14.,
+                    color: Color::BLACK,
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Panel listing the snippets in the left top corner of the canvas, clicking one stamps it.
+fn spawn_snippet_library(commands: &mut Commands, main_panel: Entity, snippets: &[Snippet]) {
+    let library = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(220.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+            SnippetLibrary,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    "Snippets",
+                    TextStyle {
+                        font_size: 16.,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(6.)),
+                    ..default()
+                }),
+                Localized::new("Snippets"),
+            ));
+            if snippets.is_empty() {
+                let hint = "Save a selection with \"Save as snippet\" of its context menu";
+                builder.spawn((
+                    TextBundle::from_section(
+                        hint,
+                        TextStyle {
+                            font_size: 12.,
+                            color: Color::DARK_GRAY,
+                            ..default()
+                        },
+                    ),
+                    Localized::new(hint),
+                ));
+            }
+            for snippet in snippets {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            size: Size::width(Val::Percent(100.)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|builder| {
+                        spawn_library_button(
+                            builder,
+                            &snippet.name,
+                            SnippetButton { id: snippet.id },
+                            Val::Percent(85.),
+                        );
+                        spawn_library_button(
+                            builder,
+                            "×",
+                            DeleteSnippetButton { id: snippet.id },
+                            Val::Percent(15.),
+                        );
+                    });
+            }
+        })
+        .id();
+    commands.entity(main_panel).add_child(library);
+}
+
+fn refresh_snippet_library(
+    commands: &mut Commands,
+    library: &Query<Entity, With<SnippetLibrary>>,
+    main_panel: Entity,
+    snippets: &[Snippet],
+) {
+    if let Ok(entity) = library.get_single() {
+        commands.entity(entity).despawn_recursive();
+        spawn_snippet_library(commands, main_panel, snippets);
+    }
+}
+
+/// Opens or closes the snippet library.
+pub fn toggle_snippet_library(
+    mut commands: Commands,
+    interactions: Query<&Interaction, (Changed<Interaction>, With<SnippetsButton>)>,
+    library: Query<Entity, With<SnippetLibrary>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    pkv: Res<PkvStore>,
+) {
+    if !interactions
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    if let Ok(entity) = library.get_single() {
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+    let Ok(main_panel) = main_panel_query.get_single() else {
+        return;
+    };
+    spawn_snippet_library(&mut commands, main_panel, &load_snippets(&pkv));
+}
+
+/// "Save as snippet" of the node context menu saves the selected nodes with the arrows between
+/// them to the snippet library.
+pub fn save_snippet(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    mut pkv: ResMut<PkvStore>,
+    selected_nodes: Res<SelectedNodes>,
+    images: Res<Assets<Image>>,
+    nodes: NodeQuery,
+    containers: NodeContainerQuery,
+    arrows: ArrowQuery,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+    library: Query<Entity, With<SnippetLibrary>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+) {
+    for event in events.iter() {
+        if event.action != ContextMenuAction::Custom(SAVE_SNIPPET_ACTION) {
+            continue;
+        }
+        let mut ids: HashSet<Uuid> = selected_nodes.0.iter().map(|id| id.0).collect();
+        if let Some(id) = event.node {
+            ids.insert(id.0);
+        }
+        let saved_nodes: Vec<_> = json_nodes(&nodes, &containers, &text_query)
+            .into_iter()
+            .filter(|(node, _)| ids.contains(&node.id))
+            .collect();
+        if saved_nodes.is_empty() {
+            continue;
+        }
+        let saved_arrows: Vec<_> = json_arrows(&arrows)
+            .into_iter()
+            .filter(|arrow| {
+                ["start", "end"].iter().all(|end| {
+                    serde_json::from_value::<Uuid>(arrow[end]["id"].clone())
+                        .map_or(false, |id| ids.contains(&id))
+                })
+            })
+            .collect();
+        let mut content = json!({
+            "images": json_images(&saved_nodes, &images),
+            "nodes": saved_nodes.iter().map(|(node, _)| json!(node)).collect::<Vec<_>>(),
+            "arrows": saved_arrows,
+        });
+        let size = normalize_snippet(&mut content);
+        let mut snippets = load_snippets(&pkv);
+        snippets.push(Snippet {
+            id: ReflectableUuid::generate(),
+            name: snippet_name(&content, snippets.len()),
+            content: content.to_string(),
+            size: size.into(),
+        });
+        store_snippets(&mut commands, &mut pkv, &snippets);
+        if let Ok(main_panel) = main_panel_query.get_single() {
+            refresh_snippet_library(&mut commands, &library, main_panel, &snippets);
+        }
+    }
+}
+
+/// Stamps the clicked snippet in the middle of the canvas with new ids and selects it, or
+/// deletes it from the library.
+pub fn snippet_library_click(
+    mut commands: Commands,
+    mut pkv: ResMut<PkvStore>,
+    mut history: ResMut<History>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut res_images: ResMut<Assets<Image>>,
+    mut font_system_state: ResMut<FontSystemState>,
+    asset_server: Res<AssetServer>,
+    offset: Res<CanvasOffset>,
+    zoom: Res<CanvasZoom>,
+    stamp: Query<(&Interaction, &SnippetButton), Changed<Interaction>>,
+    delete: Query<(&Interaction, &DeleteSnippetButton), Changed<Interaction>>,
+    library: Query<Entity, With<SnippetLibrary>>,
+    main_panel_query: Query<(Entity, &Node), With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok((main_panel, panel)) = main_panel_query.get_single() else {
+        return;
+    };
+    let mut snippets = load_snippets(&pkv);
+    for (interaction, button) in delete.iter() {
+        if *interaction == Interaction::Clicked {
+            snippets.retain(|snippet| snippet.id != button.id);
+            store_snippets(&mut commands, &mut pkv, &snippets);
+            refresh_snippet_library(&mut commands, &library, main_panel, &snippets);
+            return;
+        }
+    }
+    let Some(snippet) = stamp
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .and_then(|(_, button)| snippets.iter().find(|snippet| snippet.id == button.id))
+    else {
+        return;
+    };
+    let Ok(mut content) = serde_json::from_str::<Value>(&with_new_ids(&snippet.content)) else {
+        return;
+    };
+    // centered in the visible part of the canvas
+    let size = Vec2::from(snippet.size);
+    let center = canvas_to_world(panel.size() / 2., &offset, &zoom);
+    move_snippet(&mut content, center - size / 2.);
+    let scale_factor = windows.single().scale_factor() as f32;
+    let mut stamped = vec![];
+    for node in content["nodes"].as_array().into_iter().flatten() {
+        let Ok(json_node) = serde_json::from_value::<JsonNode>(node.clone()) else {
+            continue;
+        };
+        let image = content["images"][json_node.id.to_string()]
+            .as_str()
+            .map(|image| res_images.add(decode_image_base64(image)));
+        stamped.push(ReflectableUuid(json_node.id));
+        history.record_created(ReflectableUuid(json_node.id));
+        spawn_node(
+            &mut commands,
+            &asset_server,
+            &mut font_system_state,
+            json_node_meta(json_node, image, zoom.0, scale_factor),
+        );
+    }
+    let mut arrows = vec![];
+    for arrow in content["arrows"].as_array().into_iter().flatten() {
+        match json_arrow_event(arrow) {
+            Ok(event) => arrows.push(event),
+            Err(e) => {
+                let message = Localized::new("Couldn't read an arrow");
+                notify(&mut commands, Notification::error(message).with_details(e));
+            }
+        }
+    }
+    history.create_arrows(arrows);
+    selected_nodes.0 = stamped;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(text: &str, left: f32, bottom: f32) -> Value {
+        json!({
+            "id": Uuid::new_v4(),
+            "node_type": "Rect",
+            "left": { "Px": left },
+            "bottom": { "Px": bottom },
+            "width": { "Px": 100. },
+            "height": { "Px": 50. },
+            "text": { "text": text, "pos": "Center" },
+            "bg_color": { "Rgba": { "red": 1., "green": 1., "blue": 1., "alpha": 1. } },
+            "z_index": 0,
+        })
+    }
+
+    #[test]
+    fn test_normalize_snippet() {
+        let mut content = json!({
+            "images": {},
+            "nodes": [node("a", 200., 300.), node("b", 400., 250.)],
+            "arrows": [{ "waypoints": [[350., 320.]] }],
+        });
+        let size = normalize_snippet(&mut content);
+        assert_eq!(size, Vec2::new(300., 100.));
+        assert_eq!(content["nodes"][0]["left"]["Px"], json!(0.));
+        assert_eq!(content["nodes"][0]["bottom"]["Px"], json!(50.));
+        assert_eq!(content["nodes"][1]["left"]["Px"], json!(200.));
+        assert_eq!(content["nodes"][1]["bottom"]["Px"], json!(0.));
+        assert_eq!(content["arrows"][0]["waypoints"][0], json!([150., 70.]));
+    }
+
+    #[test]
+    fn test_snippet_name() {
+        let content = json!({
+            "nodes": [node("", 0., 200.), node("Bottom", 0., 0.), node("Top\nmore", 150., 200.)],
+        });
+        assert_eq!(snippet_name(&content, 0), "Top");
+        assert_eq!(
+            snippet_name(&json!({ "nodes": [node(" ", 0., 0.)] }), 2),
+            "Snippet 3"
+        );
+    }
+}
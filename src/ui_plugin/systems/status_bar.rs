@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use bevy_cosmic_edit::{get_cosmic_text, CosmicEditImage};
+
+use super::ui_helpers::{Localized, NodeRect, RawText, StatusBarItem, VeloNodeContainer};
+use super::{CanvasZoom, SelectedNodes, Settings, UiState};
+use crate::resources::SaveDocRequest;
+use crate::utils::ReflectableUuid;
+
+/// Node the selection item describes: the one resized, else the one dragged or edited, else
+/// the only selected one.
+fn status_node(ui_state: &UiState, selected: &SelectedNodes) -> Option<ReflectableUuid> {
+    ui_state
+        .entity_to_resize
+        .map(|(id, _)| id)
+        .or(selected.held(ui_state))
+        .or(selected.edited(ui_state))
+        .or(match selected.0.as_slice() {
+            [id] => Some(*id),
+            _ => None,
+        })
+}
+
+fn autosave_text(settings: &Settings, saving: bool) -> Localized {
+    if saving {
+        Localized::new("Saving…")
+    } else if settings.autosave_minutes == 0 {
+        Localized::new("Autosave off")
+    } else {
+        Localized::new("Autosave every {} min").with_args([settings.autosave_minutes.to_string()])
+    }
+}
+
+/// Fills in the status bar, each item is only recomputed when what it shows changed.
+pub fn update_status_bar(
+    ui_state: Res<UiState>,
+    selected: Res<SelectedNodes>,
+    zoom: Res<CanvasZoom>,
+    settings: Res<Settings>,
+    save_request: Option<Res<SaveDocRequest>>,
+    mut was_saving: Local<bool>,
+    added_nodes: Query<(), Added<VeloNodeContainer>>,
+    mut removed_nodes: RemovedComponents<VeloNodeContainer>,
+    containers: Query<(&VeloNodeContainer, Ref<NodeRect>)>,
+    texts: Query<(&RawText, Ref<CosmicEditImage>)>,
+    new_items: Query<(), Added<StatusBarItem>>,
+    mut items: Query<(&StatusBarItem, &mut Localized, &mut Style), Without<VeloNodeContainer>>,
+) {
+    let all = !new_items.is_empty();
+    let mut updates: Vec<(StatusBarItem, Option<Localized>)> = vec![];
+
+    let nodes_removed = removed_nodes.iter().count() > 0;
+    if all || nodes_removed || !added_nodes.is_empty() {
+        let count = containers.iter().count();
+        let text = Localized::new("Nodes: {}").with_args([count.to_string()]);
+        updates.push((StatusBarItem::NodeCount, Some(text)));
+    }
+
+    let node = status_node(&ui_state, &selected);
+    let container = node.and_then(|id| containers.iter().find(|(c, _)| c.id == id));
+    let container_changed = container
+        .as_ref()
+        .map_or(false, |(_, rect)| rect.is_changed());
+    if all || ui_state.is_changed() || selected.is_changed() || container_changed {
+        // world coordinates, the same as in the saved document
+        let text = container.map(|(_, rect)| {
+            Localized::new("{} × {} at {}, {}").with_args([
+                rect.size.x.round().to_string(),
+                rect.size.y.round().to_string(),
+                rect.position.x.round().to_string(),
+                rect.position.y.round().to_string(),
+            ])
+        });
+        updates.push((StatusBarItem::Selection, text));
+    }
+
+    if all || zoom.is_changed() {
+        let percent = (zoom.0 * 100.).round().to_string();
+        let text = Localized::new("Zoom: {}%").with_args([percent]);
+        updates.push((StatusBarItem::Zoom, Some(text)));
+    }
+
+    let saving = save_request.is_some();
+    if all || settings.is_changed() || saving != *was_saving {
+        *was_saving = saving;
+        updates.push((
+            StatusBarItem::Autosave,
+            Some(autosave_text(&settings, saving)),
+        ));
+    }
+
+    let edited = selected
+        .edited(&ui_state)
+        .and_then(|id| texts.iter().find(|(raw_text, _)| raw_text.id == id));
+    let text_changed = edited
+        .as_ref()
+        .map_or(false, |(_, editor)| editor.is_changed());
+    if all || ui_state.is_changed() || selected.is_changed() || text_changed {
+        let text = edited.map(|(_, editor)| {
+            let words = get_cosmic_text(&editor.editor).split_whitespace().count();
+            Localized::new("Words: {}").with_args([words.to_string()])
+        });
+        updates.push((StatusBarItem::WordCount, text));
+    }
+
+    for (item, mut localized, mut style) in items.iter_mut() {
+        let Some((_, text)) = updates.iter().find(|(kind, _)| kind == item) else {
+            continue;
+        };
+        let display = if text.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+        // texts are only replaced when they differ, see `apply_locale`
+        if let Some(text) = text {
+            if localized.text != text.text || localized.args != text.args {
+                *localized = text.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item_text(app: &mut App, item: StatusBarItem) -> Option<(String, Vec<String>)> {
+        let mut items = app.world.query::<(&StatusBarItem, &Localized, &Style)>();
+        items
+            .iter(&app.world)
+            .find(|(kind, _, _)| **kind == item)
+            .filter(|(_, _, style)| style.display != Display::None)
+            .map(|(_, localized, _)| (localized.text.clone(), localized.args.clone()))
+    }
+
+    #[test]
+    fn test_update_status_bar() {
+        let mut app = App::new();
+        app.init_resource::<UiState>();
+        app.init_resource::<SelectedNodes>();
+        app.init_resource::<CanvasZoom>();
+        app.init_resource::<Settings>();
+        app.add_system(update_status_bar);
+        for item in [
+            StatusBarItem::NodeCount,
+            StatusBarItem::Selection,
+            StatusBarItem::Zoom,
+            StatusBarItem::Autosave,
+            StatusBarItem::WordCount,
+        ] {
+            app.world
+                .spawn((item, Localized::new(""), Style::default()));
+        }
+        let id = ReflectableUuid::generate();
+        app.world.spawn((
+            VeloNodeContainer { id },
+            NodeRect::new(Vec2::new(40., 20.), Vec2::new(100., 50.)),
+        ));
+        app.update();
+        assert_eq!(
+            item_text(&mut app, StatusBarItem::NodeCount),
+            Some(("Nodes: {}".to_string(), vec!["1".to_string()]))
+        );
+        assert_eq!(
+            item_text(&mut app, StatusBarItem::Zoom),
+            Some(("Zoom: {}%".to_string(), vec!["100".to_string()]))
+        );
+        assert_eq!(
+            item_text(&mut app, StatusBarItem::Autosave),
+            Some(("Autosave off".to_string(), vec![]))
+        );
+        assert_eq!(item_text(&mut app, StatusBarItem::Selection), None);
+        assert_eq!(item_text(&mut app, StatusBarItem::WordCount), None);
+
+        app.world.resource_mut::<SelectedNodes>().0 = vec![id];
+        app.world.resource_mut::<CanvasZoom>().0 = 2.;
+        app.world.resource_mut::<Settings>().autosave_minutes = 5;
+        app.update();
+        // the position doesn't depend on canvas zoom
+        let position = vec!["100", "50", "40", "20"];
+        assert_eq!(
+            item_text(&mut app, StatusBarItem::Selection),
+            Some((
+                "{} × {} at {}, {}".to_string(),
+                position.into_iter().map(String::from).collect()
+            ))
+        );
+        assert_eq!(
+            item_text(&mut app, StatusBarItem::Zoom),
+            Some(("Zoom: {}%".to_string(), vec!["200".to_string()]))
+        );
+        assert_eq!(
+            item_text(&mut app, StatusBarItem::Autosave),
+            Some(("Autosave every {} min".to_string(), vec!["5".to_string()]))
+        );
+    }
+}
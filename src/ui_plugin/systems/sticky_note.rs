@@ -0,0 +1,77 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use uuid::Uuid;
+
+use crate::components::MainCamera;
+use crate::{
+    AddRectEvent, JsonNode, JsonNodeText, NodeBorder, NodeShadow, NodeTextStyle, NodeType, TextPos,
+};
+
+use super::{ui_helpers::LeftPanel, Action, Settings, UiState};
+
+const STICKY_SIZE: f32 = 160.;
+
+pub fn add_sticky_note(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    mut events: EventWriter<AddRectEvent>,
+    left_panel_query: Query<&Style, With<LeftPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    settings: Res<Settings>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(Action::StickyNote, &input)
+        || !ui_state.is_canvas_focused()
+    {
+        return;
+    }
+    let primary_window = windows.single();
+    let mut left_panel_width = 0.;
+    if let Val::Percent(x) = left_panel_query.single().size.width {
+        left_panel_width = (primary_window.width() * x) / 100.;
+    }
+    // drop the sticky under the cursor, or in the middle of the canvas if cursor is outside
+    let cursor = primary_window.cursor_position().unwrap_or(Vec2::new(
+        (primary_window.width() + left_panel_width) / 2.,
+        primary_window.height() / 2.,
+    ));
+    let (camera, camera_transform) = camera_q.single();
+    let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+    events.send(AddRectEvent {
+        node: JsonNode {
+            id: Uuid::new_v4(),
+            node_type: NodeType::Sticky,
+            left: Val::Px(point.x - STICKY_SIZE / 2.),
+            bottom: Val::Px(point.y - STICKY_SIZE / 2.),
+            width: Val::Px(STICKY_SIZE),
+            height: Val::Px(STICKY_SIZE),
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::TopLeft,
+                style: NodeTextStyle::default(),
+            },
+            bg_color: Color::rgb(1., 225.0 / 255.0, 130.0 / 255.0),
+            z_index: 0,
+            locked: false,
+            // sticky notes are drawn with a shadow instead of border by default
+            border: NodeBorder {
+                width: 0.,
+                ..default()
+            },
+            shadow: Some(NodeShadow {
+                offset_x: 4.,
+                offset_y: 4.,
+                blur: 0.,
+                color: Color::BLACK.with_a(0.2),
+            }),
+            opacity: 1.,
+            tags: vec![],
+            attachments: vec![],
+            rotation: 0.,
+        },
+        image: None,
+    });
+}
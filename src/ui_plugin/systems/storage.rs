@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::components::Doc;
+use crate::utils::ReflectableUuid;
+
+/// Documents of the store encrypted with their passphrase (see `encrypt_doc`), they're kept
+/// under "encrypted_docs" instead of "docs".
+pub fn stored_encrypted_docs(pkv: &PkvStore) -> HashMap<ReflectableUuid, Vec<u8>> {
+    pkv.get("encrypted_docs").unwrap_or_default()
+}
+
+/// Saves the document to the file of `path`. Natively it's written to disk after backing up the
+/// previous version, in the browser the file name is offered to the File System Access API or
+/// used for a download.
+pub fn save_document_file(path: &Path, doc: &Doc, passphrase: Option<&str>) -> std::io::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Err(e) = super::rotate_backups(path, super::MAX_BACKUPS) {
+            info!("Error backing up {:?}: {:?}", path, e);
+        }
+        super::write_document(path, doc, passphrase)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let file_name = path.file_name().map_or("velo.json".to_string(), |name| {
+            name.to_string_lossy().to_string()
+        });
+        super::save_file_in_browser(file_name, super::document_bytes(path, doc, passphrase)?);
+        Ok(())
+    }
+}
+
+/// Keeps a copy of the document saved to `PkvStore`. The browser's local storage is small and
+/// can be cleared with the site data, so documents are also put in IndexedDB there (encrypted
+/// when they have a passphrase).
+pub fn autosave_document(doc: &Doc, passphrase: Option<&str>) {
+    #[cfg(target_arch = "wasm32")]
+    super::autosave_to_indexed_db(doc, passphrase);
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = (doc, passphrase);
+}
@@ -0,0 +1,314 @@
+use async_channel::{Receiver, Sender};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::RenderLayers,
+    },
+    window::WindowRef,
+};
+use image::RgbaImage;
+
+use super::export::ExportedTab;
+use super::export_png::tab_to_png;
+use super::ui_helpers::{TabPreview, TabPreviewView};
+use super::{window_title, ContextMenuAction, ContextMenuEvent, UiState, MAX_ZOOM, MIN_ZOOM};
+use crate::resources::AppState;
+
+// Layers below are used by the main window, the canvas and the particle effects
+const FIRST_LAYER: u8 = 3;
+// Zoom factor of one line of mouse wheel scroll
+const WHEEL_ZOOM_STEP: f32 = 1.1;
+// Pixels of touchpad scroll counted as one line
+const PIXELS_PER_LINE: f32 = 20.;
+
+struct TabPreviewRender {
+    window: Entity,
+    generation: u64,
+    image: RgbaImage,
+}
+
+/// Previewed tabs are rendered like the PNG export in `AsyncComputeTaskPool` tasks,
+/// each time their last checkpoint changes.
+#[derive(Resource)]
+pub struct TabPreviewRenders {
+    tx: Sender<TabPreviewRender>,
+    rx: Receiver<TabPreviewRender>,
+}
+
+impl Default for TabPreviewRenders {
+    fn default() -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        Self { tx, rx }
+    }
+}
+
+/// Projection scale showing the whole image in the window.
+fn fit_scale(image: Vec2, window: Vec2) -> f32 {
+    (image / window.max(Vec2::ONE))
+        .max_element()
+        .clamp(1. / MAX_ZOOM, 1. / MIN_ZOOM)
+}
+
+/// "Open preview window" of the tab menu opens an OS window with a read-only preview of the
+/// tab, with a camera and a sprite of its own on a render layer no other window uses. The
+/// preview is the saved state of the tab, nodes can only be edited in the main window which
+/// holds the entities of the current tab.
+pub fn open_tab_preview(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    app_state: Res<AppState>,
+    views: Query<&RenderLayers, With<TabPreviewView>>,
+) {
+    for event in events.iter() {
+        let ContextMenuAction::OpenTabPreview(tab_id) = event.action else {
+            continue;
+        };
+        let Some((doc_id, doc)) = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get(&doc_id).map(|doc| (doc_id, doc)))
+        else {
+            continue;
+        };
+        let Some(tab) = doc.tabs.iter().find(|tab| tab.id == tab_id) else {
+            continue;
+        };
+        let Some(layer) = (FIRST_LAYER..RenderLayers::TOTAL_LAYERS as u8).find(|layer| {
+            !views
+                .iter()
+                .any(|layers| layers.intersects(&RenderLayers::layer(*layer)))
+        }) else {
+            warn!("No render layer is left for another tab preview");
+            continue;
+        };
+        let window = commands
+            .spawn(Window {
+                title: window_title(&doc.name, Some(&tab.name), false),
+                ..default()
+            })
+            .id();
+        let camera = commands
+            .spawn((
+                Camera2dBundle {
+                    camera: Camera {
+                        target: RenderTarget::Window(WindowRef::Entity(window)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                // the ui is only laid out for the primary window
+                UiCameraConfig { show_ui: false },
+                RenderLayers::layer(layer),
+                TabPreviewView { window },
+            ))
+            .id();
+        let sprite = commands
+            .spawn((
+                SpriteBundle {
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                RenderLayers::layer(layer),
+                TabPreviewView { window },
+            ))
+            .id();
+        commands.entity(window).insert(TabPreview {
+            doc_id,
+            tab_id,
+            camera,
+            sprite,
+            rendered: None,
+            generation: 0,
+            fitted: false,
+            drag_cursor: None,
+        });
+    }
+}
+
+/// Keeps tab previews in sync with the document: renders their tab again when its checkpoint
+/// changed, follows renames in the title and closes windows whose tab or document is gone.
+///
+/// The tab shown in the main window too is updated when it's saved, like its checkpoint.
+pub fn render_tab_previews(
+    mut commands: Commands,
+    app_state: Res<AppState>,
+    renders: Res<TabPreviewRenders>,
+    mut tab_previews: Query<(Entity, &mut TabPreview, &mut Window)>,
+) {
+    for (entity, mut preview, mut window) in tab_previews.iter_mut() {
+        if !app_state.is_changed() && !preview.is_added() {
+            continue;
+        }
+        let doc = app_state
+            .docs
+            .get(&preview.doc_id)
+            .filter(|_| app_state.current_document == Some(preview.doc_id));
+        let Some((doc, tab)) = doc.and_then(|doc| {
+            let tab = doc.tabs.iter().find(|tab| tab.id == preview.tab_id)?;
+            Some((doc, tab))
+        }) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let title = window_title(&doc.name, Some(&tab.name), false);
+        if window.title != title {
+            window.title = title;
+        }
+        let checkpoint = tab.checkpoint.as_ref();
+        if preview.rendered.as_ref() == checkpoint {
+            continue;
+        }
+        preview.rendered = checkpoint.cloned();
+        preview.generation += 1;
+        let generation = preview.generation;
+        let exported = ExportedTab::from_tab(tab);
+        let tx = renders.tx.clone();
+        bevy::tasks::AsyncComputeTaskPool::get()
+            .spawn(async move {
+                let Ok(image) = image::load_from_memory(&tab_to_png(&exported)) else {
+                    return;
+                };
+                let _ = tx
+                    .send(TabPreviewRender {
+                        window: entity,
+                        generation,
+                        image: image.to_rgba8(),
+                    })
+                    .await;
+            })
+            .detach();
+    }
+}
+
+/// Shows finished renders in their window, the first one is fitted to the window.
+pub fn show_tab_preview_renders(
+    renders: Res<TabPreviewRenders>,
+    mut images: ResMut<Assets<Image>>,
+    mut tab_previews: Query<(&mut TabPreview, &Window)>,
+    mut sprites: Query<(&mut Handle<Image>, &mut Visibility), With<TabPreviewView>>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<TabPreviewView>>,
+) {
+    while let Ok(render) = renders.rx.try_recv() {
+        // renders of closed windows and outdated ones are dropped
+        let Ok((mut preview, window)) = tab_previews.get_mut(render.window) else {
+            continue;
+        };
+        if preview.generation != render.generation {
+            continue;
+        }
+        let size = Vec2::new(render.image.width() as f32, render.image.height() as f32);
+        let image = Image::new(
+            Extent3d {
+                width: render.image.width(),
+                height: render.image.height(),
+                ..default()
+            },
+            TextureDimension::D2,
+            render.image.into_raw(),
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        if let Ok((mut texture, mut visibility)) = sprites.get_mut(preview.sprite) {
+            *texture = images.add(image);
+            *visibility = Visibility::Inherited;
+        }
+        if preview.fitted {
+            continue;
+        }
+        preview.fitted = true;
+        if let Ok((mut transform, mut projection)) = cameras.get_mut(preview.camera) {
+            transform.translation = Vec3::new(0., 0., transform.translation.z);
+            projection.scale = fit_scale(size, Vec2::new(window.width(), window.height()));
+        }
+    }
+}
+
+/// Scrolling over a tab preview zooms it around the cursor and dragging it with the left or
+/// middle button pans it, independently of the main canvas and the other windows.
+pub fn navigate_tab_previews(
+    buttons: Res<Input<MouseButton>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut tab_previews: Query<(Entity, &mut TabPreview, &Window)>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<TabPreviewView>>,
+) {
+    let wheel_lines: f32 = mouse_wheel_events
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    let cursor_moves: Vec<&CursorMoved> = cursor_moved_events.iter().collect();
+    let drag_buttons = [MouseButton::Left, MouseButton::Middle];
+    for (entity, mut preview, window) in tab_previews.iter_mut() {
+        let Ok((mut transform, mut projection)) = cameras.get_mut(preview.camera) else {
+            continue;
+        };
+        let cursor = window.cursor_position();
+        if !buttons.any_pressed(drag_buttons) {
+            preview.drag_cursor = None;
+        } else if buttons.any_just_pressed(drag_buttons) && cursor.is_some() {
+            preview.drag_cursor = cursor;
+        }
+        if preview.drag_cursor.is_some() {
+            for event in cursor_moves.iter().filter(|event| event.window == entity) {
+                if let Some(last) = preview.drag_cursor {
+                    let delta = (event.position - last) * projection.scale;
+                    transform.translation -= delta.extend(0.);
+                }
+                preview.drag_cursor = Some(event.position);
+            }
+        }
+        let Some(cursor) = cursor.filter(|_| wheel_lines != 0.) else {
+            continue;
+        };
+        let scale = (projection.scale / WHEEL_ZOOM_STEP.powf(wheel_lines))
+            .clamp(1. / MAX_ZOOM, 1. / MIN_ZOOM);
+        // the point under the cursor stays there
+        let from_center = cursor - Vec2::new(window.width(), window.height()) / 2.;
+        transform.translation += (from_center * (projection.scale - scale)).extend(0.);
+        projection.scale = scale;
+    }
+}
+
+/// Despawns the camera and the sprite of tab previews which were closed.
+pub fn close_tab_preview_views(
+    mut commands: Commands,
+    views: Query<(Entity, &TabPreviewView)>,
+    windows: Query<(), With<Window>>,
+) {
+    for (entity, view) in views.iter() {
+        if !windows.contains(view.window) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Keys typed in a tab preview don't reach the canvas of the main window.
+pub fn track_tab_preview_focus(
+    windows: Query<&Window, With<TabPreview>>,
+    mut ui_state: ResMut<UiState>,
+) {
+    let focused = windows.iter().any(|window| window.focused);
+    if ui_state.tab_preview_focused != focused {
+        ui_state.tab_preview_focused = focused;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_scale() {
+        assert_eq!(fit_scale(Vec2::new(1600., 600.), Vec2::new(800., 600.)), 2.);
+        assert_eq!(fit_scale(Vec2::new(400., 450.), Vec2::new(800., 900.)), 0.5);
+        // tiny charts aren't blown up past the max zoom of the canvas
+        assert_eq!(
+            fit_scale(Vec2::new(10., 10.), Vec2::new(800., 600.)),
+            1. / MAX_ZOOM
+        );
+    }
+}
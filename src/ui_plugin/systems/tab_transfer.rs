@@ -0,0 +1,351 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_cosmic_edit::CosmicEditImage;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::ui_helpers::{MainPanel, NodeZIndex, Tooltip};
+use super::{
+    context_menu_height, delete_nodes, empty_tab_json, json_arrows, json_images, json_nodes,
+    spawn_context_menu, ArrowQuery, ContextMenuAction, ContextMenuEvent, History,
+    NodeContainerQuery, NodeQuery, RawText, SelectedNodes, UiState, VeloNodeContainer,
+};
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::components::Tab;
+use crate::resources::{AppState, LoadDocRequest, SaveTabRequest};
+use crate::utils::ReflectableUuid;
+
+pub const DUPLICATE_TAB_ACTION: &str = "duplicate_tab";
+pub const MOVE_TO_TAB_ACTION: &str = "move_to_tab";
+
+fn remap_id(id: &mut Value, ids: &mut HashMap<String, String>, remap: &impl Fn(&str) -> bool) {
+    if let Some(old) = id.as_str() {
+        if !ids.contains_key(old) && !remap(old) {
+            return;
+        }
+        let new = ids
+            .entry(old.to_string())
+            .or_insert_with(|| Uuid::new_v4().to_string());
+        *id = json!(new);
+    }
+}
+
+/// Copy of the saved tab with new ids for its nodes, groups and strokes. Arrows, images, frames,
+/// collapsed branches and the presentation path refer to the new node ids.
+pub fn with_new_ids(checkpoint: &str) -> String {
+    remap_ids(checkpoint, &mut HashMap::new(), |_| true)
+}
+
+/// Copy of the saved tab where the ids for which `remap` is true, or which are already in `ids`,
+/// are replaced by new ones. `ids` keeps the replacements so several checkpoints of a tab can be
+/// remapped the same way.
+pub fn remap_ids(
+    checkpoint: &str,
+    ids: &mut HashMap<String, String>,
+    remap: impl Fn(&str) -> bool,
+) -> String {
+    let Ok(mut json) = serde_json::from_str::<Value>(checkpoint) else {
+        return checkpoint.to_string();
+    };
+    for node in json["nodes"].as_array_mut().into_iter().flatten() {
+        remap_id(&mut node["id"], ids, &remap);
+    }
+    for arrow in json["arrows"].as_array_mut().into_iter().flatten() {
+        remap_id(&mut arrow["start"]["id"], ids, &remap);
+        remap_id(&mut arrow["end"]["id"], ids, &remap);
+    }
+    for key in ["collapsed_branches", "presentation"] {
+        for id in json[key].as_array_mut().into_iter().flatten() {
+            remap_id(id, ids, &remap);
+        }
+    }
+    for stroke in json["strokes"].as_array_mut().into_iter().flatten() {
+        remap_id(&mut stroke["id"], ids, &remap);
+    }
+    // keyed by ids
+    for key in ["images", "groups", "frames"] {
+        let Some(entries) = json[key].as_object().cloned() else {
+            continue;
+        };
+        let mut remapped = serde_json::Map::new();
+        for (id, mut value) in entries {
+            let mut id = json!(id);
+            remap_id(&mut id, ids, &remap);
+            match key {
+                "groups" => {
+                    for member in value.as_array_mut().into_iter().flatten() {
+                        remap_id(member, ids, &remap);
+                    }
+                }
+                "frames" => {
+                    for member in value["members"].as_array_mut().into_iter().flatten() {
+                        remap_id(member, ids, &remap);
+                    }
+                }
+                _ => {}
+            }
+            remapped.insert(id.as_str().unwrap().to_string(), value);
+        }
+        json[key] = Value::Object(remapped);
+    }
+    json.to_string()
+}
+
+/// Saved tab with the nodes, images and arrows of `moved` added to it.
+pub fn merge_into_checkpoint(checkpoint: Option<&String>, moved: &Value) -> String {
+    let mut json: Value = checkpoint
+        .and_then(|checkpoint| serde_json::from_str(checkpoint).ok())
+        .unwrap_or_else(empty_tab_json);
+    for key in ["nodes", "arrows"] {
+        if let (Some(items), Some(moved_items)) = (json[key].as_array_mut(), moved[key].as_array())
+        {
+            items.extend(moved_items.iter().cloned());
+        }
+    }
+    if let (Some(images), Some(moved_images)) =
+        (json["images"].as_object_mut(), moved["images"].as_object())
+    {
+        images.extend(moved_images.clone());
+    }
+    json.to_string()
+}
+
+/// "Duplicate tab" of the canvas context menu adds a copy of the active tab after it, the copy
+/// gets new ids so both tabs can be edited (and linked to) independently.
+pub fn duplicate_tab(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    mut app_state: ResMut<AppState>,
+    mut pending: Local<Option<(ReflectableUuid, ReflectableUuid)>>,
+) {
+    let requested = events
+        .iter()
+        .any(|event| event.action == ContextMenuAction::Custom(DUPLICATE_TAB_ACTION));
+    // the tab is saved first and copied the frame after
+    if let Some((doc_id, tab_id)) = pending.take() {
+        let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+            return;
+        };
+        let Some(index) = doc.tabs.iter().position(|tab| tab.id == tab_id) else {
+            return;
+        };
+        let source = &doc.tabs[index];
+        let copy = Tab {
+            id: ReflectableUuid::generate(),
+            name: format!("{} copy", source.name),
+            checkpoint: source.checkpoint.as_deref().map(with_new_ids),
+            zoom: source.zoom,
+            color: source.color,
+            icon: source.icon.clone(),
+            pinned: false,
+            // nodes of the copy are where they are on the tab
+            offset: source.offset,
+            selected: vec![],
+            is_active: true,
+        };
+        for tab in doc.tabs.iter_mut() {
+            tab.is_active = false;
+        }
+        doc.tabs.insert(index + 1, copy);
+        commands.insert_resource(LoadDocRequest { doc_id });
+        return;
+    }
+    if !requested {
+        return;
+    }
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    let Some(tab) = app_state
+        .docs
+        .get(&doc_id)
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active))
+    else {
+        return;
+    };
+    commands.insert_resource(SaveTabRequest {
+        doc_id,
+        tab_id: tab.id,
+    });
+    *pending = Some((doc_id, tab.id));
+}
+
+/// "Move to tab…" of the node context menu opens a menu of the other tabs of the document.
+pub fn open_move_to_tab_menu(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    app_state: Res<AppState>,
+    main_panel_query: Query<(Entity, &Node), With<MainPanel>>,
+) {
+    for event in events.iter() {
+        if event.action != ContextMenuAction::Custom(MOVE_TO_TAB_ACTION) {
+            continue;
+        }
+        let Some(doc) = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get(&doc_id))
+        else {
+            continue;
+        };
+        let items: Vec<_> = doc
+            .tabs
+            .iter()
+            .filter(|tab| !tab.is_active)
+            .map(|tab| (tab.name.clone(), ContextMenuAction::MoveToTab(tab.id)))
+            .collect();
+        let Ok((main_panel, panel)) = main_panel_query.get_single() else {
+            continue;
+        };
+        if items.is_empty() {
+            continue;
+        }
+        // opened where the context menu was, y axis pointing down
+        let top = (panel.size().y - event.position.y)
+            .min(panel.size().y - context_menu_height(items.len()))
+            .max(0.);
+        spawn_context_menu(
+            &mut commands,
+            main_panel,
+            &items,
+            Vec2::new(event.position.x, top),
+            event.node,
+            event.position,
+        );
+    }
+}
+
+/// Moves the selected nodes with the arrows between them to the tab chosen in the "Move to tab…"
+/// menu, arrows to nodes staying on the tab are deleted.
+pub fn move_selection_to_tab(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    mut app_state: ResMut<AppState>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    mut history: ResMut<History>,
+    images: Res<Assets<Image>>,
+    nodes: NodeQuery,
+    mut containers: ParamSet<(
+        NodeContainerQuery,
+        Query<(Entity, &VeloNodeContainer, &mut NodeZIndex), With<VeloNodeContainer>>,
+    )>,
+    mut arrows: ParamSet<(
+        ArrowQuery,
+        Query<(Entity, &ArrowMeta, &mut Visibility), (With<ArrowMeta>, Without<Tooltip>)>,
+    )>,
+    text_query: Query<(&RawText, &CosmicEditImage), With<RawText>>,
+) {
+    for event in events.iter() {
+        let ContextMenuAction::MoveToTab(tab_id) = event.action else {
+            continue;
+        };
+        let mut ids = selected_nodes.0.clone();
+        if let Some(id) = event.node {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        let moved_ids: HashSet<Uuid> = ids.iter().map(|id| id.0).collect();
+        let moved_nodes: Vec<_> = json_nodes(&nodes, &containers.p0(), &text_query)
+            .into_iter()
+            .filter(|(node, _)| moved_ids.contains(&node.id))
+            .collect();
+        if moved_nodes.is_empty() {
+            continue;
+        }
+        let moved_arrows: Vec<_> = json_arrows(&arrows.p0())
+            .into_iter()
+            .filter(|arrow| {
+                ["start", "end"].iter().all(|end| {
+                    serde_json::from_value::<Uuid>(arrow[end]["id"].clone())
+                        .map_or(false, |id| moved_ids.contains(&id))
+                })
+            })
+            .collect();
+        let moved = json!({
+            "images": json_images(&moved_nodes, &images),
+            "nodes": moved_nodes.iter().map(|(node, _)| json!(node)).collect::<Vec<_>>(),
+            "arrows": moved_arrows,
+        });
+        let Some(tab) = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get_mut(&doc_id))
+            .and_then(|doc| doc.tabs.iter_mut().find(|tab| tab.id == tab_id))
+        else {
+            continue;
+        };
+        tab.checkpoint = Some(merge_into_checkpoint(tab.checkpoint.as_ref(), &moved));
+        delete_nodes(
+            &ids,
+            &mut commands,
+            &mut ui_state,
+            &mut selected_nodes,
+            &mut history,
+            &containers.p1(),
+            &mut arrows.p1(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_new_ids() {
+        let node = Uuid::new_v4().to_string();
+        let other = Uuid::new_v4().to_string();
+        let group = Uuid::new_v4().to_string();
+        let checkpoint = json!({
+            "images": { node.clone(): "png" },
+            "nodes": [{ "id": node }, { "id": other }],
+            "arrows": [{ "start": { "id": node }, "end": { "id": other } }],
+            "groups": { group.clone(): [node, other] },
+            "frames": { other.clone(): { "members": [node], "collapsed": false } },
+            "collapsed_branches": [node],
+            "strokes": [],
+            "presentation": [other, node],
+        });
+        let copy: Value = serde_json::from_str(&with_new_ids(&checkpoint.to_string())).unwrap();
+        let new_node = copy["nodes"][0]["id"].as_str().unwrap();
+        let new_other = copy["nodes"][1]["id"].as_str().unwrap();
+        assert_ne!(new_node, node);
+        assert_ne!(new_other, other);
+        assert_eq!(copy["images"][new_node], "png");
+        assert_eq!(copy["arrows"][0]["start"]["id"], new_node);
+        assert_eq!(copy["arrows"][0]["end"]["id"], new_other);
+        assert!(copy["groups"].get(&group).is_none());
+        assert_eq!(
+            copy["groups"].as_object().unwrap().values().next().unwrap(),
+            &json!([new_node, new_other])
+        );
+        assert_eq!(copy["frames"][new_other]["members"], json!([new_node]));
+        assert_eq!(copy["collapsed_branches"], json!([new_node]));
+        assert_eq!(copy["presentation"], json!([new_other, new_node]));
+    }
+
+    #[test]
+    fn test_merge_into_checkpoint() {
+        let moved = json!({
+            "images": { "a": "png" },
+            "nodes": [{ "id": "a" }, { "id": "b" }],
+            "arrows": [{ "start": { "id": "a" }, "end": { "id": "b" } }],
+        });
+        let empty: Value = serde_json::from_str(&merge_into_checkpoint(None, &moved)).unwrap();
+        assert_eq!(empty["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(empty["frames"], json!({}));
+
+        let checkpoint = json!({
+            "images": { "c": "jpg" },
+            "nodes": [{ "id": "c" }],
+            "arrows": [],
+        })
+        .to_string();
+        let merged: Value =
+            serde_json::from_str(&merge_into_checkpoint(Some(&checkpoint), &moved)).unwrap();
+        assert_eq!(merged["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(merged["arrows"].as_array().unwrap().len(), 1);
+        assert_eq!(merged["images"], json!({ "a": "png", "c": "jpg" }));
+    }
+}
@@ -1,15 +1,22 @@
-use std::{collections::VecDeque, time::Duration};
+use std::time::Duration;
 
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
 
 use bevy::window::PrimaryWindow;
 use bevy_cosmic_edit::FontSystemState;
 
-use super::ui_helpers::{spawn_modal, AddTab, DeleteTab, TabButton};
-use super::MainPanel;
+use super::ui_helpers::{
+    add_tabs, spawn_modal, AddTab, DeleteTab, TabBar, TabButton, TabContainer, TAB_ICONS,
+    TAB_MARGIN, TAB_WIDTH,
+};
+use super::{
+    context_menu_height, spawn_context_menu, Action, CanvasOffset, ContextMenuAction,
+    ContextMenuEvent, MainPanel, SelectedNodes, Settings, MENU_WIDTH, NODE_COLORS,
+};
 use crate::components::Tab;
-use crate::resources::{AppState, LoadDocRequest, LoadTabRequest, SaveTabRequest};
-use crate::utils::{get_timestamp, ReflectableUuid};
+use crate::resources::{AppState, LoadDocRequest, LoadTabRequest, SaveDocRequest, SaveTabRequest};
+use crate::utils::{get_timestamp, next_in_cycle, ReflectableUuid};
 use crate::UiState;
 
 pub fn select_tab_handler(
@@ -54,7 +61,6 @@ pub fn select_tab_handler(
                 commands.insert_resource(LoadTabRequest {
                     doc_id: current_document,
                     tab_id: selected_tab.id,
-                    drop_last_checkpoint: false,
                 });
             }
             Interaction::Hovered => {}
@@ -65,39 +71,48 @@ pub fn select_tab_handler(
 
 pub fn add_tab_handler(
     mut commands: Commands,
-    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<AddTab>)>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<AddTab>)>,
     mut app_state: ResMut<AppState>,
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    settings: Res<Settings>,
 ) {
-    for interaction in &mut interaction_query {
-        match *interaction {
-            Interaction::Clicked => {
-                let tab_id = ReflectableUuid::generate();
-                let current_document = app_state.current_document.unwrap();
-                let tabs = &mut app_state.docs.get_mut(&current_document).unwrap().tabs;
-                for tab in tabs.iter_mut() {
-                    if tab.is_active {
-                        commands.insert_resource(SaveTabRequest {
-                            tab_id: tab.id,
-                            doc_id: current_document,
-                        });
-                    }
-                    tab.is_active = false;
-                }
-                let tabs_len = tabs.len();
-                tabs.push(Tab {
-                    id: tab_id,
-                    name: "Tab ".to_string() + &(tabs_len + 1).to_string(),
-                    checkpoints: VecDeque::new(),
-                    is_active: true,
-                });
-                commands.insert_resource(LoadDocRequest {
-                    doc_id: app_state.current_document.unwrap(),
-                });
-            }
-            Interaction::Hovered => {}
-            Interaction::None => {}
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let shortcut =
+        ui_state.is_canvas_focused() && settings.keybindings.just_pressed(Action::NewTab, &input);
+    if !clicked && !shortcut {
+        return;
+    }
+    let tab_id = ReflectableUuid::generate();
+    let current_document = app_state.current_document.unwrap();
+    let tabs = &mut app_state.docs.get_mut(&current_document).unwrap().tabs;
+    for tab in tabs.iter_mut() {
+        if tab.is_active {
+            commands.insert_resource(SaveTabRequest {
+                tab_id: tab.id,
+                doc_id: current_document,
+            });
         }
+        tab.is_active = false;
     }
+    let tabs_len = tabs.len();
+    tabs.push(Tab {
+        id: tab_id,
+        name: "Tab ".to_string() + &(tabs_len + 1).to_string(),
+        checkpoint: None,
+        zoom: 1.,
+        color: None,
+        icon: None,
+        pinned: false,
+        offset: [0., 0.],
+        selected: vec![],
+        is_active: true,
+    });
+    commands.insert_resource(LoadDocRequest {
+        doc_id: app_state.current_document.unwrap(),
+    });
 }
 
 pub fn rename_tab_handler(
@@ -107,7 +122,6 @@ pub fn rename_tab_handler(
         (Changed<Interaction>, With<TabButton>),
     >,
     mut ui_state: ResMut<UiState>,
-    mut app_state: ResMut<AppState>,
     mut double_click: Local<(Duration, Option<ReflectableUuid>)>,
 ) {
     for (interaction, item) in &mut interaction_query {
@@ -120,16 +134,7 @@ pub fn rename_tab_handler(
                 {
                     *ui_state = UiState::default();
                     commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
-                    let current_document = app_state.current_document.unwrap();
-                    let tab = app_state
-                        .docs
-                        .get_mut(&current_document)
-                        .unwrap()
-                        .tabs
-                        .iter()
-                        .find(|x| x.is_active)
-                        .unwrap();
-                    ui_state.tab_to_edit = Some(tab.id);
+                    ui_state.tab_to_edit = Some(item.id);
                     *double_click = (Duration::from_secs(0), None);
                 } else {
                     *double_click = (Duration::from_millis(now_ms as u64), Some(item.id));
@@ -144,7 +149,7 @@ pub fn rename_tab_handler(
 pub fn delete_tab_handler(
     mut commands: Commands,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<DeleteTab>)>,
-    mut app_state: ResMut<AppState>,
+    app_state: Res<AppState>,
     mut ui_state: ResMut<UiState>,
     main_panel_query: Query<Entity, With<MainPanel>>,
     windows: Query<&Window, With<PrimaryWindow>>,
@@ -158,13 +163,9 @@ pub fn delete_tab_handler(
                 *ui_state = UiState::default();
                 commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
                 let current_document = app_state.current_document.unwrap();
-                let tabs_len = app_state
-                    .docs
-                    .get_mut(&current_document)
-                    .unwrap()
-                    .tabs
-                    .len();
-                if tabs_len < 2 {
+                let tabs = &app_state.docs.get(&current_document).unwrap().tabs;
+                // pinned tabs are protected from deletion
+                if tabs.len() < 2 || tabs.iter().any(|tab| tab.is_active && tab.pinned) {
                     return;
                 }
                 ui_state.modal_id = Some(id);
@@ -182,3 +183,323 @@ pub fn delete_tab_handler(
         }
     }
 }
+
+// cursor distance a pressed tab has to move before it is dragged
+const DRAG_THRESHOLD: f32 = 5.;
+
+pub struct TabDrag {
+    id: ReflectableUuid,
+    start: f32,
+    dragging: bool,
+}
+
+/// Pressed tabs follow the cursor along the tab bar, dropping one moves it to the place it was
+/// dropped at in the tabs of the document.
+pub fn drag_tab(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &TabButton), Changed<Interaction>>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut app_state: ResMut<AppState>,
+    asset_server: Res<AssetServer>,
+    tab_bar_query: Query<(Entity, &TabBar)>,
+    mut containers: Query<(Entity, &TabContainer, &mut Style)>,
+    mut drag: Local<Option<TabDrag>>,
+) {
+    let Some(cursor) = windows.single().cursor_position() else {
+        return;
+    };
+    for (interaction, tab) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            *drag = Some(TabDrag {
+                id: tab.id,
+                start: cursor.x,
+                dragging: false,
+            });
+        }
+    }
+    let Some(tab_drag) = drag.as_mut() else {
+        return;
+    };
+    let Ok((tab_bar_entity, tab_bar)) = tab_bar_query.get_single() else {
+        return;
+    };
+    let offset = cursor.x - tab_drag.start;
+    if mouse.pressed(MouseButton::Left) {
+        tab_drag.dragging |= offset.abs() > DRAG_THRESHOLD;
+        if tab_drag.dragging {
+            for (_, container, mut style) in containers.iter_mut() {
+                if container.id == tab_drag.id {
+                    style.position.left = Val::Px(offset - tab_bar.position);
+                }
+            }
+        }
+        return;
+    }
+    let Some(tab_drag) = drag.take() else {
+        return;
+    };
+    if !tab_drag.dragging {
+        return;
+    }
+    let Some(doc) = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get_mut(&doc_id))
+    else {
+        return;
+    };
+    let Some(index) = doc.tabs.iter().position(|tab| tab.id == tab_drag.id) else {
+        return;
+    };
+    let slot = TAB_WIDTH + 2. * TAB_MARGIN;
+    // pinned tabs stay before the other ones
+    let pinned = doc.tabs.iter().filter(|tab| tab.pinned).count();
+    let (first, last) = if doc.tabs[index].pinned {
+        (0, pinned - 1)
+    } else {
+        (pinned, doc.tabs.len() - 1)
+    };
+    let new_index = (index as f32 + offset / slot)
+        .round()
+        .clamp(first as f32, last as f32) as usize;
+    if new_index == index {
+        for (_, container, mut style) in containers.iter_mut() {
+            if container.id == tab_drag.id {
+                style.position.left = Val::Px(-tab_bar.position);
+            }
+        }
+        return;
+    }
+    let tab = doc.tabs.remove(index);
+    doc.tabs.insert(new_index, tab);
+    for (entity, _, _) in containers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    add_tabs(&mut commands, &asset_server, tab_bar_entity, &doc.tabs);
+}
+
+/// Scrolls the tab bar with the mouse wheel over it when there are more tabs than fit in it, the
+/// active tab is scrolled into view when the tabs are spawned again (e.g. a new tab is added).
+pub fn scroll_tab_bar(
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    app_state: Res<AppState>,
+    mut tab_bar_query: Query<(&mut TabBar, &Node, &GlobalTransform)>,
+    added_tabs: Query<(), Added<TabContainer>>,
+    mut containers: Query<&mut Style, With<TabContainer>>,
+) {
+    let Ok((mut tab_bar, node, transform)) = tab_bar_query.get_single_mut() else {
+        return;
+    };
+    let Some(tabs) = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get(&doc_id))
+        .map(|doc| &doc.tabs)
+    else {
+        return;
+    };
+    let window = windows.single();
+    let slot = TAB_WIDTH + 2. * TAB_MARGIN;
+    let width = node.size().x;
+    let mut position = tab_bar.position;
+    let respawned = !added_tabs.is_empty();
+    if respawned {
+        if let Some(index) = tabs.iter().position(|tab| tab.is_active) {
+            let left = index as f32 * slot;
+            position = position.min(left).max(left + slot - width);
+        }
+    }
+    // ui is y-down, the cursor position is from the bottom of the window
+    let hovered = window.cursor_position().map_or(false, |cursor| {
+        let center = Vec2::new(
+            transform.translation().x,
+            window.height() - transform.translation().y,
+        );
+        (cursor - center).abs().cmple(node.size() / 2.).all()
+    });
+    for event in mouse_wheel_events.iter() {
+        if !hovered {
+            continue;
+        }
+        // vertical mouse wheels scroll the bar too
+        let delta = if event.x != 0. { event.x } else { event.y };
+        position -= match event.unit {
+            MouseScrollUnit::Line => delta * 20.,
+            MouseScrollUnit::Pixel => delta,
+        };
+    }
+    let position = position.clamp(0., (tabs.len() as f32 * slot - width).max(0.));
+    if position != tab_bar.position || respawned {
+        tab_bar.position = position;
+        for mut style in containers.iter_mut() {
+            style.position.left = Val::Px(-position);
+        }
+    }
+}
+
+/// Right-clicking a tab opens its menu to pin it or to change its color or icon.
+pub fn open_tab_menu(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tab_buttons: Query<(&Interaction, &TabButton)>,
+    app_state: Res<AppState>,
+    main_panel_query: Query<(Entity, &Node, &GlobalTransform), With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    if !buttons.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some((_, tab_button)) = tab_buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Hovered)
+    else {
+        return;
+    };
+    let Some(tab) = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get(&doc_id))
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.id == tab_button.id))
+    else {
+        return;
+    };
+    let Ok((main_panel, panel, panel_transform)) = main_panel_query.get_single() else {
+        return;
+    };
+    let Some(cursor) = windows.single().cursor_position() else {
+        return;
+    };
+    #[allow(unused_mut)]
+    let mut items = vec![
+        (
+            if tab.pinned { "Unpin tab" } else { "Pin tab" }.to_string(),
+            ContextMenuAction::PinTab(tab.id),
+        ),
+        (
+            "Change tab color".to_string(),
+            ContextMenuAction::CycleTabColor(tab.id),
+        ),
+        (
+            "Change tab icon".to_string(),
+            ContextMenuAction::CycleTabIcon(tab.id),
+        ),
+    ];
+    #[cfg(not(target_arch = "wasm32"))]
+    items.push((
+        "Open preview window".to_string(),
+        ContextMenuAction::OpenTabPreview(tab.id),
+    ));
+    // opened at the bottom of the canvas, right above the tab bar
+    let rect = panel.logical_rect(panel_transform);
+    let left = (cursor.x - rect.min.x)
+        .min(rect.width() - MENU_WIDTH)
+        .max(0.);
+    let top = rect.height() - context_menu_height(items.len());
+    spawn_context_menu(
+        &mut commands,
+        main_panel,
+        &items,
+        Vec2::new(left, top),
+        None,
+        Vec2::ZERO,
+    );
+}
+
+/// Pins the tab chosen in its menu (pinned tabs are kept leftmost in their order) or changes
+/// its color or icon to the next one, no color or icon follows the last one.
+pub fn tab_menu_actions(
+    mut commands: Commands,
+    mut events: EventReader<ContextMenuEvent>,
+    mut app_state: ResMut<AppState>,
+    asset_server: Res<AssetServer>,
+    tab_bar_query: Query<Entity, With<TabBar>>,
+    containers: Query<Entity, With<TabContainer>>,
+) {
+    for event in events.iter() {
+        let (ContextMenuAction::PinTab(tab_id)
+        | ContextMenuAction::CycleTabColor(tab_id)
+        | ContextMenuAction::CycleTabIcon(tab_id)) = event.action
+        else {
+            continue;
+        };
+        let Some(doc) = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get_mut(&doc_id))
+        else {
+            continue;
+        };
+        let Some(tab) = doc.tabs.iter_mut().find(|tab| tab.id == tab_id) else {
+            continue;
+        };
+        match event.action {
+            ContextMenuAction::PinTab(_) => {
+                tab.pinned = !tab.pinned;
+                doc.tabs.sort_by_key(|tab| !tab.pinned);
+            }
+            ContextMenuAction::CycleTabColor(_) => {
+                let colors: Vec<_> = std::iter::once(None)
+                    .chain(NODE_COLORS.iter().copied().map(Some))
+                    .collect();
+                tab.color = next_in_cycle(&colors, tab.color);
+            }
+            _ => {
+                let icons: Vec<_> = std::iter::once(None)
+                    .chain(TAB_ICONS.iter().map(|(name, _)| Some(*name)))
+                    .collect();
+                tab.icon = next_in_cycle(&icons, tab.icon.as_deref()).map(str::to_string);
+            }
+        }
+        for entity in containers.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if let Ok(tab_bar) = tab_bar_query.get_single() {
+            add_tabs(&mut commands, &asset_server, tab_bar, &doc.tabs);
+        }
+    }
+}
+
+fn find_tab_mut(app_state: &mut AppState, tab_id: ReflectableUuid) -> Option<&mut Tab> {
+    app_state
+        .docs
+        .values_mut()
+        .flat_map(|doc| doc.tabs.iter_mut())
+        .find(|tab| tab.id == tab_id)
+}
+
+/// Keeps the viewport of each tab: the panned offset and the selected nodes of the tab that is
+/// left (or saved) are stored on it and restored when it's shown again, also after the document
+/// is reopened. Zoom of the tab is kept by `canvas_zoom` and restored by `load_tab`.
+pub fn tab_viewport(
+    mut app_state: ResMut<AppState>,
+    mut offset: ResMut<CanvasOffset>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    save_tab_request: Option<Res<SaveTabRequest>>,
+    save_doc_request: Option<Res<SaveDocRequest>>,
+    mut shown_tab: Local<Option<ReflectableUuid>>,
+) {
+    let active_tab = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get(&doc_id))
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active))
+        .map(|tab| tab.id);
+    let saving = save_tab_request.is_some() || save_doc_request.is_some();
+    if active_tab == *shown_tab && !saving {
+        return;
+    }
+    if let Some(tab) = shown_tab.and_then(|tab_id| find_tab_mut(&mut app_state, tab_id)) {
+        tab.offset = offset.0.to_array();
+        tab.selected = selected_nodes.0.clone();
+    }
+    if active_tab == *shown_tab {
+        return;
+    }
+    *shown_tab = active_tab;
+    let tab = active_tab.and_then(|tab_id| find_tab_mut(&mut app_state, tab_id));
+    offset.0 = tab
+        .as_ref()
+        .map_or(Vec2::ZERO, |tab| Vec2::from(tab.offset));
+    // nodes of the tab that is left are no longer edited or dragged
+    ui_state.editing_node = false;
+    ui_state.holding_node = false;
+    selected_nodes.0 = tab.map_or(vec![], |tab| tab.selected.clone());
+}
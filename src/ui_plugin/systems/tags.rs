@@ -0,0 +1,418 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{Fill, Stroke};
+
+use crate::canvas::arrow::components::{ArrowMeta, ArrowRelation, ArrowStyle};
+
+use super::{
+    inspected_node,
+    ui_helpers::{
+        GenericButton, HiddenByBranch, HiddenByFrame, InspectorTagsField, InspectorValue,
+        Localized, NodePart, RelationFilterButton, TagFilterButton, TagFilterDim,
+        TagFilterModeButton, TagList,
+    },
+    NodeProperty, NodeTags, SelectedNodes, SetNodeTagsEvent, TagFilter, TagFilterMode, UiState,
+    VeloNode, VeloNodeContainer,
+};
+
+const SELECTED_TAG_COLOR: Color = Color::rgb(191.0 / 255.0, 219.0 / 255.0, 254.0 / 255.0);
+// Alpha of arrows dimmed by the relationship filter
+const DIMMED_ARROW_ALPHA: f32 = 0.15;
+// Dims are drawn over the content of the node, below its outline and handles
+const DIM_Z: f32 = 0.42;
+
+/// Comma separated tags, a leading `#` is optional. Duplicates and empty tags are dropped.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in text.split(',') {
+        let tag = tag.trim().trim_start_matches('#').trim();
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Edits tags of the inspected node in the inspector, Enter applies and Escape cancels.
+pub fn edit_node_tags(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<InspectorTagsField>)>,
+    mut char_events: EventReader<ReceivedCharacter>,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    mut selected_nodes: ResMut<SelectedNodes>,
+    nodes: Query<(&VeloNode, Option<&NodeTags>)>,
+    mut values: Query<(&mut Text, &InspectorValue)>,
+    mut events: EventWriter<SetNodeTagsEvent>,
+    mut buffer: Local<String>,
+) {
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    let Some(id) = ui_state.node_tags_to_edit else {
+        char_events.clear();
+        if !clicked {
+            return;
+        }
+        let Some(id) = inspected_node(&ui_state, &selected_nodes) else {
+            return;
+        };
+        *buffer = nodes
+            .iter()
+            .find(|(node, _)| node.id == id)
+            .and_then(|(_, tags)| tags.map(|tags| tags.0.join(", ")))
+            .unwrap_or_default();
+        // typed text would go to the edited node otherwise, the node stays inspected
+        // as a selected one
+        ui_state.editing_node = false;
+        if !selected_nodes.0.contains(&id) {
+            selected_nodes.0 = vec![id];
+        }
+        ui_state.node_tags_to_edit = Some(id);
+        set_tags_text(&mut values, format!("{}|", *buffer));
+        return;
+    };
+    // e.g. another node got selected
+    if inspected_node(&ui_state, &selected_nodes) != Some(id) {
+        ui_state.node_tags_to_edit = None;
+        return;
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        char_events.clear();
+        ui_state.node_tags_to_edit = None;
+        return;
+    }
+    if input.just_pressed(KeyCode::Return) {
+        char_events.clear();
+        events.send(SetNodeTagsEvent {
+            id,
+            tags: parse_tags(&buffer),
+        });
+        ui_state.node_tags_to_edit = None;
+        return;
+    }
+    let mut changed = false;
+    for event in char_events.iter() {
+        if !event.char.is_control() {
+            buffer.push(event.char);
+            changed = true;
+        }
+    }
+    if input.just_pressed(KeyCode::Back) {
+        buffer.pop();
+        changed = true;
+    }
+    if changed {
+        set_tags_text(&mut values, format!("{}|", *buffer));
+    }
+}
+
+fn set_tags_text(values: &mut Query<(&mut Text, &InspectorValue)>, value: String) {
+    for (mut text, inspector_value) in values.iter_mut() {
+        if inspector_value.property == NodeProperty::Tags {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub fn set_node_tags(
+    mut commands: Commands,
+    mut events: EventReader<SetNodeTagsEvent>,
+    nodes: Query<(Entity, &VeloNode)>,
+) {
+    for event in events.iter() {
+        if let Some((entity, _)) = nodes.iter().find(|(_, node)| node.id == event.id) {
+            commands.entity(entity).insert(NodeTags(event.tags.clone()));
+        }
+    }
+}
+
+pub fn tag_filter_buttons(
+    tag_buttons: Query<(&Interaction, &TagFilterButton), Changed<Interaction>>,
+    relation_buttons: Query<(&Interaction, &RelationFilterButton), Changed<Interaction>>,
+    mode_buttons: Query<
+        (&Interaction, &Children),
+        (Changed<Interaction>, With<TagFilterModeButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut tag_filter: ResMut<TagFilter>,
+) {
+    for (interaction, button) in tag_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if tag_filter.tags.contains(&button.tag) {
+            tag_filter.tags.retain(|tag| *tag != button.tag);
+        } else {
+            tag_filter.tags.push(button.tag.clone());
+        }
+    }
+    for (interaction, button) in relation_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if tag_filter.relations.contains(&button.relation) {
+            tag_filter
+                .relations
+                .retain(|relation| *relation != button.relation);
+        } else {
+            tag_filter.relations.push(button.relation.clone());
+        }
+    }
+    for (interaction, children) in mode_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        tag_filter.mode = match tag_filter.mode {
+            TagFilterMode::Dim => TagFilterMode::Hide,
+            TagFilterMode::Hide => TagFilterMode::Dim,
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value = match tag_filter.mode {
+                    TagFilterMode::Dim => "dim".to_string(),
+                    TagFilterMode::Hide => "hide".to_string(),
+                };
+            }
+        }
+    }
+}
+
+/// Counts of values, sorted by value.
+fn count_values<'a>(values: impl Iterator<Item = &'a String>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = vec![];
+    for value in values {
+        match counts.iter_mut().find(|(v, _)| v == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value.clone(), 1)),
+        }
+    }
+    counts.sort();
+    counts
+}
+
+type ShownTagList = (
+    Vec<(String, usize)>,
+    Vec<String>,
+    Vec<(String, usize)>,
+    Vec<String>,
+);
+
+/// Lists tags of the nodes and relationship types of the arrows on the canvas with their counts,
+/// the list is rebuilt only when they or the filter change.
+pub fn update_tag_list(
+    mut commands: Commands,
+    nodes: Query<&NodeTags, With<VeloNode>>,
+    arrows: Query<(&ArrowRelation, &Visibility), With<ArrowMeta>>,
+    list_query: Query<Entity, With<TagList>>,
+    mut tag_filter: ResMut<TagFilter>,
+    mut shown: Local<Option<ShownTagList>>,
+) {
+    let counts = count_values(nodes.iter().flat_map(|tags| tags.0.iter()));
+    // deleted arrows are only hidden on wasm
+    let relation_counts = count_values(
+        arrows
+            .iter()
+            .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+            .map(|(relation, _)| &relation.0),
+    );
+    // tags removed from all nodes don't filter anymore
+    if tag_filter
+        .tags
+        .iter()
+        .any(|tag| !counts.iter().any(|(t, _)| t == tag))
+    {
+        tag_filter
+            .tags
+            .retain(|tag| counts.iter().any(|(t, _)| t == tag));
+    }
+    if tag_filter
+        .relations
+        .iter()
+        .any(|relation| !relation_counts.iter().any(|(r, _)| r == relation))
+    {
+        tag_filter
+            .relations
+            .retain(|relation| relation_counts.iter().any(|(r, _)| r == relation));
+    }
+    let current = (
+        counts,
+        tag_filter.tags.clone(),
+        relation_counts,
+        tag_filter.relations.clone(),
+    );
+    if shown.as_ref() == Some(&current) {
+        return;
+    }
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+    let text_style = TextStyle {
+        font_size: 14.,
+        color: Color::BLACK,
+        ..default()
+    };
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|builder| {
+        let (counts, _, relation_counts, _) = &current;
+        if counts.is_empty() && relation_counts.is_empty() {
+            builder.spawn((
+                TextBundle::from_section("no tags", text_style.clone()),
+                Localized::new("no tags"),
+            ));
+        }
+        for (tag, count) in counts.iter() {
+            builder
+                .spawn((
+                    tag_list_entry(tag_filter.tags.contains(tag)),
+                    TagFilterButton { tag: tag.clone() },
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    builder.spawn(TextBundle::from_section(
+                        format!("#{} ({})", tag, count),
+                        text_style.clone(),
+                    ));
+                });
+        }
+        for (relation, count) in relation_counts.iter() {
+            builder
+                .spawn((
+                    tag_list_entry(tag_filter.relations.contains(relation)),
+                    RelationFilterButton {
+                        relation: relation.clone(),
+                    },
+                    GenericButton,
+                ))
+                .with_children(|builder| {
+                    builder.spawn(TextBundle::from_section(
+                        format!("→ {} ({})", relation, count),
+                        text_style.clone(),
+                    ));
+                });
+        }
+    });
+    *shown = Some(current);
+}
+
+fn tag_list_entry(selected: bool) -> ButtonBundle {
+    ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Px(22.)),
+            align_items: AlignItems::Center,
+            padding: UiRect::horizontal(Val::Px(4.)),
+            ..default()
+        },
+        background_color: if selected {
+            SELECTED_TAG_COLOR.into()
+        } else {
+            Color::WHITE.into()
+        },
+        ..default()
+    }
+}
+
+/// Dims arrows of none of the selected relationship types. Only the alpha is changed so the
+/// hover and selection highlights stay visible.
+pub fn apply_relation_filter(
+    tag_filter: Res<TagFilter>,
+    mut arrows: Query<
+        (Option<&ArrowRelation>, &ArrowStyle, &mut Stroke, &mut Fill),
+        With<ArrowMeta>,
+    >,
+) {
+    for (relation, style, mut stroke, mut fill) in arrows.iter_mut() {
+        let matches = tag_filter.relations.is_empty()
+            || relation.map_or(false, |relation| tag_filter.relations.contains(&relation.0));
+        let alpha = if matches {
+            style.color.a()
+        } else {
+            DIMMED_ARROW_ALPHA
+        };
+        if stroke.color.a() != alpha {
+            stroke.color.set_a(alpha);
+        }
+        if fill.color.a() != alpha {
+            fill.color.set_a(alpha);
+        }
+    }
+}
+
+/// Dims or hides nodes that have none of the selected tags.
+pub fn apply_tag_filter(
+    mut commands: Commands,
+    tag_filter: Res<TagFilter>,
+    nodes: Query<(&Parent, Option<&NodeTags>), With<VeloNode>>,
+    mut containers: Query<
+        (
+            &mut Visibility,
+            Option<&HiddenByFrame>,
+            Option<&HiddenByBranch>,
+        ),
+        With<VeloNodeContainer>,
+    >,
+    dims: Query<(Entity, &Parent), With<TagFilterDim>>,
+) {
+    for (parent, tags) in nodes.iter() {
+        let container = parent.get();
+        let matches = tag_filter.tags.is_empty()
+            || tags.map_or(false, |tags| {
+                tags.0.iter().any(|tag| tag_filter.tags.contains(tag))
+            });
+        let hidden = !matches && tag_filter.mode == TagFilterMode::Hide;
+        let dimmed = !matches && tag_filter.mode == TagFilterMode::Dim;
+        if let Ok((mut visibility, hidden_by_frame, hidden_by_branch)) =
+            containers.get_mut(container)
+        {
+            // contents of collapsed frames and branches stay hidden
+            let new_visibility =
+                if hidden || hidden_by_frame.is_some() || hidden_by_branch.is_some() {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Inherited
+                };
+            if *visibility != new_visibility {
+                *visibility = new_visibility;
+            }
+        }
+        let dim = dims
+            .iter()
+            .find(|(_, dim_parent)| dim_parent.get() == container)
+            .map(|(entity, _)| entity);
+        match (dimmed, dim) {
+            (true, None) => {
+                let dim = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::WHITE.with_a(0.7),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        // dimmed nodes can still be selected and edited, the dim isn't picked
+                        NodePart::fill(DIM_Z),
+                        TagFilterDim,
+                    ))
+                    .id();
+                commands.entity(container).add_child(dim);
+            }
+            (false, Some(dim)) => {
+                commands.entity(dim).despawn_recursive();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" #todo, idea,, todo ,#  "),
+            vec!["todo".to_string(), "idea".to_string()]
+        );
+        assert!(parse_tags("").is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::{prelude::*, sprite::Anchor, text::TextLayoutInfo, window::PrimaryWindow};
+use bevy_cosmic_edit::{get_cosmic_text, CosmicEditImage, FontSystemState};
+use bevy_markdown::{get_task_checkbox, BevyMarkdownNode};
+use cosmic_text::Edit;
+
+use crate::components::MainCamera;
+use crate::NodeTextStyle;
+
+use super::{
+    cosmic_attrs, section_at, BevyMarkdownView, History, HistoryCommand, RawText, UiState, VeloNode,
+};
+
+pub fn toggle_task_items(
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut markdown_text_query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &mut Text,
+            &TextLayoutInfo,
+            &Anchor,
+            &mut BevyMarkdownNode,
+        ),
+        With<BevyMarkdownNode>,
+    >,
+    parents: Query<&Parent>,
+    markdown_view_query: Query<&BevyMarkdownView>,
+    mut raw_text_query: Query<(&RawText, &mut CosmicEditImage, &Parent), With<RawText>>,
+    text_style_query: Query<&NodeTextStyle, With<VeloNode>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<VeloNode>)>,
+    mut font_system_state: ResMut<FontSystemState>,
+    ui_state: Res<UiState>,
+    mut history: ResMut<History>,
+) {
+    if ui_state.holding_node {
+        return;
+    }
+    let mut primary_window = primary_window.single_mut();
+    let scale_factor = primary_window.scale_factor() as f32;
+    let (camera, camera_transform) = camera_q.single();
+    let Some(point) = primary_window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    for (entity, transform, mut text, text_layout_info, anchor, mut markdown_text) in
+        markdown_text_query.iter_mut()
+    {
+        let hovered = section_at(point, transform, text_layout_info, anchor, scale_factor)
+            .and_then(|section_index| {
+                markdown_text
+                    .task_items
+                    .iter()
+                    .position(|task| task.section_index == section_index)
+            });
+        let Some(task_index) = hovered else {
+            continue;
+        };
+        primary_window.cursor.icon = CursorIcon::Hand;
+        if !clicked {
+            continue;
+        }
+        // text blocks are nested in the markdown view
+        let Some(markdown_view) = parents
+            .iter_ancestors(entity)
+            .find_map(|ancestor| markdown_view_query.get(ancestor).ok())
+        else {
+            continue;
+        };
+        let Some((_, mut cosmic_edit, node_parent)) = raw_text_query
+            .iter_mut()
+            .find(|(raw_text, _, _)| raw_text.id == markdown_view.id)
+        else {
+            continue;
+        };
+        let task = &mut markdown_text.task_items[task_index];
+        let mut source = get_cosmic_text(&cosmic_edit.editor);
+        let mark = task.offset + 1;
+        // markdown view is stale if the source was edited in the meantime
+        if source.get(task.offset..task.offset + 1) != Some("[")
+            || !matches!(source.get(mark..mark + 1), Some(" " | "x" | "X"))
+        {
+            continue;
+        }
+        task.checked = !task.checked;
+        let old_source = source.clone();
+        source.replace_range(mark..mark + 1, if task.checked { "x" } else { " " });
+        text.sections[task.section_index].value = get_task_checkbox(task.checked).to_string();
+        let text_style = text_style_query
+            .get(node_parent.get())
+            .cloned()
+            .unwrap_or_default();
+        let font_system = font_system_state.font_system.as_mut().unwrap();
+        let buffer = cosmic_edit.editor.buffer_mut();
+        buffer.set_text(font_system, source.as_str(), cosmic_attrs(&text_style));
+        buffer.set_redraw(true);
+        history.record(HistoryCommand::TextEdited {
+            id: markdown_view.id.0,
+            from: old_source,
+            to: source,
+        });
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use super::{ImportedEdge, ImportedGraph, ImportedNode, LayoutAlgorithm};
+use crate::canvas::arrow::components::{ArrowStyle, ArrowType};
+use crate::components::{Doc, Tab};
+use crate::resources::{AppState, LoadDocRequest, SaveDocRequest};
+use crate::utils::ReflectableUuid;
+use crate::NodeType;
+
+const STICKY_COLOR: Color = Color::rgb(1., 225.0 / 255.0, 130.0 / 255.0);
+
+/// Documents of the template gallery on the welcome screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Template {
+    Swot,
+    Retro,
+    Flowchart,
+    WeeklyPlanner,
+}
+
+impl Template {
+    pub const ALL: [Template; 4] = [
+        Template::Swot,
+        Template::Retro,
+        Template::Flowchart,
+        Template::WeeklyPlanner,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::Swot => "SWOT analysis",
+            Template::Retro => "Retro board",
+            Template::Flowchart => "Flowchart",
+            Template::WeeklyPlanner => "Weekly planner",
+        }
+    }
+
+    pub fn graph(self) -> ImportedGraph {
+        let mut graph = ImportedGraph::default();
+        match self {
+            Template::Swot => {
+                let quadrants = [
+                    (
+                        "Strengths",
+                        "What do we do well?",
+                        Color::rgb(0.9, 0.97, 0.9),
+                    ),
+                    (
+                        "Weaknesses",
+                        "Where can we improve?",
+                        Color::rgb(0.99, 0.92, 0.9),
+                    ),
+                    (
+                        "Opportunities",
+                        "What could we take advantage of?",
+                        Color::rgb(0.9, 0.94, 0.99),
+                    ),
+                    (
+                        "Threats",
+                        "What could get in our way?",
+                        Color::rgb(0.97, 0.93, 0.86),
+                    ),
+                ];
+                for (i, (title, question, color)) in quadrants.into_iter().enumerate() {
+                    let left = (i % 2) as f32 * 340.;
+                    let top = (i / 2) as f32 * 280.;
+                    add_frame(
+                        &mut graph,
+                        title,
+                        color,
+                        rect(left, top, 320., 260.),
+                        &[question],
+                    );
+                }
+            }
+            Template::Retro => {
+                let columns = [
+                    ("Went well", "What should we keep doing?"),
+                    ("To improve", "What slowed us down?"),
+                    ("Action items", "What will we try next?"),
+                ];
+                for (i, (title, question)) in columns.into_iter().enumerate() {
+                    add_frame(
+                        &mut graph,
+                        title,
+                        Color::rgb(0.96, 0.96, 0.96),
+                        rect(i as f32 * 300., 0., 280., 420.),
+                        &[question],
+                    );
+                }
+            }
+            Template::Flowchart => {
+                let start = add_node(
+                    &mut graph,
+                    "Start",
+                    NodeType::Ellipse,
+                    Color::WHITE,
+                    rect(90., 0., 140., 60.),
+                );
+                let step = add_node(
+                    &mut graph,
+                    "Do something",
+                    NodeType::Rect,
+                    Color::WHITE,
+                    rect(80., 120., 160., 70.),
+                );
+                let decision = add_node(
+                    &mut graph,
+                    "Done?",
+                    NodeType::Diamond,
+                    Color::WHITE,
+                    rect(80., 250., 160., 100.),
+                );
+                let end = add_node(
+                    &mut graph,
+                    "End",
+                    NodeType::Ellipse,
+                    Color::WHITE,
+                    rect(0., 420., 140., 60.),
+                );
+                let retry = add_node(
+                    &mut graph,
+                    "Try again",
+                    NodeType::Rect,
+                    Color::WHITE,
+                    rect(180., 415., 160., 70.),
+                );
+                add_edge(&mut graph, start, step, None);
+                add_edge(&mut graph, step, decision, None);
+                add_edge(&mut graph, decision, end, Some("yes"));
+                add_edge(&mut graph, decision, retry, Some("no"));
+            }
+            Template::WeeklyPlanner => {
+                add_node(
+                    &mut graph,
+                    "Goals of the week",
+                    NodeType::Sticky,
+                    STICKY_COLOR,
+                    rect(0., 0., 380., 100.),
+                );
+                let days = [
+                    "Monday",
+                    "Tuesday",
+                    "Wednesday",
+                    "Thursday",
+                    "Friday",
+                    "Saturday",
+                    "Sunday",
+                ];
+                for (i, day) in days.into_iter().enumerate() {
+                    add_frame(
+                        &mut graph,
+                        day,
+                        Color::rgb(0.96, 0.96, 0.96),
+                        rect(i as f32 * 196., 130., 180., 360.),
+                        &[],
+                    );
+                }
+            }
+        }
+        graph
+    }
+
+    /// New document with the template as its only tab.
+    pub fn doc(self) -> Doc {
+        let json = self.graph().to_tab_json(LayoutAlgorithm::Grid);
+        Doc {
+            id: ReflectableUuid::generate(),
+            name: self.name().to_string(),
+            tabs: vec![Tab {
+                id: ReflectableUuid::generate(),
+                name: "Tab 1".to_string(),
+                checkpoint: Some(json.to_string()),
+                zoom: 1.,
+                color: None,
+                icon: None,
+                pinned: false,
+                offset: [0., 0.],
+                selected: vec![],
+                is_active: true,
+            }],
+            tags: vec![],
+            assets: HashMap::new(),
+            revision: 0,
+        }
+    }
+}
+
+/// Canvas rectangle from its left top corner, `top` growing downwards.
+fn rect(left: f32, top: f32, width: f32, height: f32) -> Rect {
+    Rect::new(left, -top - height, left + width, -top)
+}
+
+fn add_node(
+    graph: &mut ImportedGraph,
+    text: &str,
+    node_type: NodeType,
+    bg_color: Color,
+    rect: Rect,
+) -> usize {
+    graph.nodes.push(ImportedNode {
+        text: text.to_string(),
+        node_type,
+        bg_color,
+        rect: Some(rect),
+        attachments: vec![],
+    });
+    graph.nodes.len() - 1
+}
+
+/// Frame with a sticky note for each of `notes` stacked inside of it.
+fn add_frame(graph: &mut ImportedGraph, title: &str, color: Color, frame: Rect, notes: &[&str]) {
+    let frame_node = add_node(graph, title, NodeType::Frame, color, frame);
+    let members = notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| {
+            let top = -frame.max.y + 50. + i as f32 * 120.;
+            add_node(
+                graph,
+                note,
+                NodeType::Sticky,
+                STICKY_COLOR,
+                rect(frame.min.x + 20., top, 160., 100.),
+            )
+        })
+        .collect();
+    graph.frames.push((frame_node, members));
+}
+
+fn add_edge(graph: &mut ImportedGraph, start: usize, end: usize, label: Option<&str>) {
+    graph.edges.push(ImportedEdge {
+        start,
+        end,
+        arrow_type: ArrowType::Arrow,
+        style: ArrowStyle::for_type(ArrowType::Arrow),
+        label: label.map(str::to_string),
+    });
+}
+
+/// Opens a new document made from the template, the open one is saved.
+pub fn open_template(commands: &mut Commands, app_state: &mut AppState, template: Template) {
+    let doc = template.doc();
+    let doc_id = doc.id;
+    if let Some(current) = app_state.current_document {
+        commands.insert_resource(SaveDocRequest {
+            doc_id: current,
+            path: None,
+        });
+    }
+    app_state.docs.insert(doc_id, doc);
+    app_state.doc_list_ui.insert(doc_id);
+    app_state.current_document = Some(doc_id);
+    commands.insert_resource(LoadDocRequest { doc_id });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_docs() {
+        for template in Template::ALL {
+            let doc = template.doc();
+            assert_eq!(doc.name, template.name());
+            assert_eq!(doc.tabs.len(), 1);
+            let json: serde_json::Value =
+                serde_json::from_str(doc.tabs[0].checkpoint.as_ref().unwrap()).unwrap();
+            assert!(!json["nodes"].as_array().unwrap().is_empty());
+        }
+        let swot: serde_json::Value =
+            serde_json::from_str(Template::Swot.doc().tabs[0].checkpoint.as_ref().unwrap())
+                .unwrap();
+        assert_eq!(swot["nodes"].as_array().unwrap().len(), 8);
+        let frames = swot["frames"].as_object().unwrap();
+        assert_eq!(frames.len(), 4);
+        assert!(frames
+            .values()
+            .all(|frame| frame["members"].as_array().unwrap().len() == 1));
+        let flowchart: serde_json::Value = serde_json::from_str(
+            Template::Flowchart.doc().tabs[0]
+                .checkpoint
+                .as_ref()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(flowchart["arrows"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_frame_notes_inside_frame() {
+        let graph = Template::Retro.graph();
+        for (frame, members) in graph.frames.iter() {
+            let frame = graph.nodes[*frame].rect.unwrap();
+            for member in members {
+                let note = graph.nodes[*member].rect.unwrap();
+                assert!(frame.min.cmple(note.min).all() && frame.max.cmpge(note.max).all());
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::{get_cosmic_text, set_cosmic_text_style, CosmicEditImage, FontSystemState};
+use bevy_markdown::spawn_bevy_markdown;
+use cosmic_text::Edit;
+
+use crate::utils::next_in_cycle;
+use crate::{NodeFont, NodeTextStyle};
+
+use super::{
+    cosmic_attrs, editor_line_height, markdown_view,
+    ui_helpers::{ChangeTextStyle, NodeRect, TextStyleChange},
+    BevyMarkdownView, CanvasZoom, RawText, SelectedNodes, VeloNode, VeloNodeContainer,
+};
+
+const FONT_SIZES: [f32; 4] = [14., 18., 24., 32.];
+const FONTS: [NodeFont; 3] = [NodeFont::Regular, NodeFont::Bold, NodeFont::Italic];
+const TEXT_COLORS: [Color; 5] = [
+    Color::BLACK,
+    Color::GRAY,
+    Color::rgb(229.0 / 255.0, 57.0 / 255.0, 53.0 / 255.0),
+    Color::rgb(30.0 / 255.0, 136.0 / 255.0, 229.0 / 255.0),
+    Color::rgb(67.0 / 255.0, 160.0 / 255.0, 71.0 / 255.0),
+];
+
+pub fn change_text_style(
+    mut interaction_query: Query<
+        (&Interaction, &ChangeTextStyle),
+        (Changed<Interaction>, With<ChangeTextStyle>),
+    >,
+    mut nodes: Query<(&mut NodeTextStyle, &VeloNode), With<VeloNode>>,
+    selected_nodes: Res<SelectedNodes>,
+) {
+    for (interaction, change_style) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                let mut targets: Vec<_> = nodes
+                    .iter_mut()
+                    .filter(|(_, node)| selected_nodes.0.contains(&node.id))
+                    .map(|(style, _)| style)
+                    .collect();
+                let Some(first) = targets.first() else {
+                    continue;
+                };
+                // all nodes get the value following the one of the first node
+                let font_size = next_in_cycle(&FONT_SIZES, first.font_size);
+                let font = next_in_cycle(&FONTS, first.font);
+                let color = next_in_cycle(&TEXT_COLORS, first.color);
+                for style in targets.iter_mut() {
+                    match change_style.change {
+                        TextStyleChange::FontSize => style.font_size = font_size,
+                        TextStyleChange::Font => style.font = font,
+                        TextStyleChange::Color => style.color = color,
+                    }
+                }
+            }
+            Interaction::Hovered => {}
+            Interaction::None => {}
+        }
+    }
+}
+
+/// Re-applies text style to the editor and markdown view of nodes, also used after zooming.
+pub fn update_text_style(
+    mut commands: Commands,
+    nodes: Query<
+        (Entity, &VeloNode, Ref<NodeTextStyle>, &Children, &Parent),
+        (With<VeloNode>, Changed<NodeTextStyle>),
+    >,
+    containers: Query<&NodeRect, With<VeloNodeContainer>>,
+    mut raw_text_query: Query<&mut CosmicEditImage, With<RawText>>,
+    markdown_view_query: Query<Entity, With<BevyMarkdownView>>,
+    mut font_system_state: ResMut<FontSystemState>,
+    asset_server: Res<AssetServer>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    zoom: Res<CanvasZoom>,
+) {
+    let scale_factor = windows.single().scale_factor() as f32;
+    let font_system = font_system_state.font_system.as_mut().unwrap();
+    for (entity, velo_node, style, children, parent) in nodes.iter() {
+        // spawned nodes already have their style applied
+        if style.is_added() {
+            continue;
+        }
+        let Ok(rect) = containers.get(parent.get()) else {
+            continue;
+        };
+        // the editor is rendered at the zoomed size
+        let render_size = rect.size * zoom.0 * scale_factor;
+        let mut text = String::new();
+        for child in children.iter() {
+            if let Ok(mut cosmic_edit) = raw_text_query.get_mut(*child) {
+                set_cosmic_text_style(
+                    &mut cosmic_edit,
+                    font_system,
+                    style.font_size * zoom.0,
+                    editor_line_height(&style) * zoom.0,
+                    scale_factor,
+                    cosmic_attrs(&style),
+                );
+                cosmic_edit
+                    .editor
+                    .buffer_mut()
+                    .set_size(font_system, render_size.x, render_size.y);
+                text = get_cosmic_text(&cosmic_edit.editor);
+            }
+        }
+        // markdown view (shown when node is not edited) is rebuilt with the new style
+        for child in children.iter() {
+            if !markdown_view_query.contains(*child) {
+                continue;
+            }
+            commands.entity(*child).despawn_recursive();
+            let bevy_markdown =
+                markdown_view(text.clone(), &style, rect.size.x, zoom.0, &asset_server);
+            let markdown_text = spawn_bevy_markdown(&mut commands, bevy_markdown)
+                .expect("should handle markdown conversion");
+            commands
+                .entity(markdown_text)
+                .insert(BevyMarkdownView { id: velo_node.id });
+            commands.entity(entity).add_child(markdown_text);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use bevy::prelude::*;
+use bevy_ui_borders::BorderColor;
+use serde::{Deserialize, Serialize};
+
+use super::ui_helpers::{ThemedBackground, ThemedBorder, ThemedText, ToggleTheme};
+use crate::canvas::arrow::components::ArrowStyle;
+use crate::components::CanvasBackground;
+use crate::{NodeBorder, NodeTextStyle};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub enum ThemeKind {
+    #[default]
+    Light,
+    Dark,
+    /// Set by the high contrast setting, see `Settings::high_contrast`
+    HighContrast,
+    /// Loaded from the theme file of the config
+    Custom,
+}
+
+/// Colors of the UI chrome a themed entity takes, see `ThemedBackground`, `ThemedBorder` and
+/// `ThemedText`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ThemeColor {
+    Menu,
+    LeftPanel,
+    BottomPanel,
+    Border,
+    /// Modals, prompts and other popups
+    Surface,
+    Text,
+}
+
+/// Colors of the UI, of the canvas and the defaults of new nodes and arrows. Nodes and arrows
+/// keep the colors they are saved with when the theme changes.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Theme {
+    pub kind: ThemeKind,
+    /// Tint of the canvas background image
+    pub canvas: Color,
+    pub grid_line: Color,
+    pub menu: Color,
+    pub left_panel: Color,
+    pub bottom_panel: Color,
+    pub border: Color,
+    pub surface: Color,
+    pub text: Color,
+    pub node_background: Color,
+    pub node_text: Color,
+    pub node_border: Color,
+    pub arrow: Color,
+    /// Outline of selected nodes and the rubber band
+    pub selection: Color,
+    /// Node outlines and arrow lines are drawn at least this wide
+    pub min_outline_width: f32,
+    /// Hit zones of node handles, connection points, arrows and pen strokes are scaled by it
+    pub hit_target_scale: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        Self {
+            kind: ThemeKind::Light,
+            canvas: Color::WHITE,
+            grid_line: Color::rgba(0., 0., 0., 0.08),
+            menu: Color::rgb(245.0 / 255.0, 245.0 / 255.0, 245.0 / 255.0),
+            left_panel: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0),
+            bottom_panel: Color::rgb(189.0 / 255.0, 189.0 / 255.0, 189.0 / 255.0),
+            border: Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0),
+            surface: Color::WHITE,
+            text: Color::BLACK,
+            node_background: Color::WHITE,
+            node_text: Color::BLACK,
+            node_border: NodeBorder::default().color,
+            arrow: ArrowStyle::default().color,
+            selection: Color::rgb(33.0 / 255.0, 150.0 / 255.0, 243.0 / 255.0),
+            min_outline_width: 0.,
+            hit_target_scale: 1.,
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            kind: ThemeKind::Dark,
+            canvas: Color::rgb(0.16, 0.16, 0.18),
+            grid_line: Color::rgba(1., 1., 1., 0.08),
+            menu: Color::rgb(38.0 / 255.0, 38.0 / 255.0, 42.0 / 255.0),
+            left_panel: Color::rgb(32.0 / 255.0, 32.0 / 255.0, 36.0 / 255.0),
+            bottom_panel: Color::rgb(45.0 / 255.0, 45.0 / 255.0, 50.0 / 255.0),
+            border: Color::rgb(70.0 / 255.0, 70.0 / 255.0, 76.0 / 255.0),
+            surface: Color::rgb(48.0 / 255.0, 48.0 / 255.0, 53.0 / 255.0),
+            text: Color::rgb(230.0 / 255.0, 230.0 / 255.0, 230.0 / 255.0),
+            node_background: Color::rgb(52.0 / 255.0, 52.0 / 255.0, 58.0 / 255.0),
+            node_text: Color::rgb(235.0 / 255.0, 235.0 / 255.0, 235.0 / 255.0),
+            node_border: Color::rgb(205.0 / 255.0, 204.0 / 255.0, 90.0 / 255.0),
+            arrow: Color::rgb(140.0 / 255.0, 158.0 / 255.0, 1.),
+            selection: Color::rgb(100.0 / 255.0, 181.0 / 255.0, 246.0 / 255.0),
+            min_outline_width: 0.,
+            hit_target_scale: 1.,
+        }
+    }
+
+    /// White on black with yellow borders, thicker outlines and larger hit targets.
+    pub fn high_contrast() -> Self {
+        Self {
+            kind: ThemeKind::HighContrast,
+            canvas: Color::BLACK,
+            grid_line: Color::rgba(1., 1., 1., 0.3),
+            menu: Color::BLACK,
+            left_panel: Color::BLACK,
+            bottom_panel: Color::BLACK,
+            border: Color::WHITE,
+            surface: Color::BLACK,
+            text: Color::WHITE,
+            node_background: Color::BLACK,
+            node_text: Color::WHITE,
+            node_border: Color::YELLOW,
+            arrow: Color::WHITE,
+            selection: Color::CYAN,
+            min_outline_width: 3.,
+            hit_target_scale: 1.5,
+        }
+    }
+
+    /// Built-in theme of the kind, custom themes start from the light one.
+    pub fn for_kind(kind: ThemeKind) -> Self {
+        match kind {
+            ThemeKind::Light | ThemeKind::Custom => Self::light(),
+            ThemeKind::Dark => Self::dark(),
+            ThemeKind::HighContrast => Self::high_contrast(),
+        }
+    }
+
+    /// Width an outline or a line of `width` is drawn with.
+    pub fn outline_width(&self, width: f32) -> f32 {
+        if width > 0. {
+            width.max(self.min_outline_width)
+        } else {
+            0.
+        }
+    }
+
+    pub fn color(&self, color: ThemeColor) -> Color {
+        match color {
+            ThemeColor::Menu => self.menu,
+            ThemeColor::LeftPanel => self.left_panel,
+            ThemeColor::BottomPanel => self.bottom_panel,
+            ThemeColor::Border => self.border,
+            ThemeColor::Surface => self.surface,
+            ThemeColor::Text => self.text,
+        }
+    }
+
+    /// Text style of new nodes.
+    pub fn node_text_style(&self) -> NodeTextStyle {
+        NodeTextStyle {
+            color: self.node_text,
+            ..default()
+        }
+    }
+
+    /// Border of new nodes.
+    pub fn node_border(&self) -> NodeBorder {
+        NodeBorder {
+            color: self.node_border,
+            ..default()
+        }
+    }
+
+    /// Style of a new arrow, the default color is replaced by the arrow color of the theme.
+    pub fn arrow_style(&self, style: ArrowStyle) -> ArrowStyle {
+        if style.color == ArrowStyle::default().color {
+            ArrowStyle {
+                color: self.arrow,
+                ..style
+            }
+        } else {
+            style
+        }
+    }
+}
+
+/// "Toggle Dark Mode" switches between the light and the dark theme, the choice is kept in the
+/// settings.
+pub fn toggle_theme(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ToggleTheme>)>,
+    mut theme: ResMut<Theme>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        return;
+    }
+    let kind = match theme.kind {
+        ThemeKind::Light | ThemeKind::Custom => ThemeKind::Dark,
+        ThemeKind::Dark | ThemeKind::HighContrast => ThemeKind::Light,
+    };
+    *theme = Theme::for_kind(kind);
+}
+
+/// Colors themed entities when they are spawned and all of them when the theme changes.
+pub fn apply_theme(
+    theme: Res<Theme>,
+    mut backgrounds: Query<(Ref<ThemedBackground>, &mut BackgroundColor)>,
+    mut sprites: Query<(Ref<ThemedBackground>, &mut Sprite), Without<CanvasBackground>>,
+    mut borders: Query<(Ref<ThemedBorder>, &mut BorderColor)>,
+    mut texts: Query<(Ref<ThemedText>, &mut Text)>,
+    mut canvas: Query<&mut Sprite, With<CanvasBackground>>,
+) {
+    let all = theme.is_changed();
+    for (themed, mut background) in backgrounds.iter_mut() {
+        if all || themed.is_added() {
+            background.0 = theme.color(themed.0);
+        }
+    }
+    // canvas parts of nodes are sprites
+    for (themed, mut sprite) in sprites.iter_mut() {
+        if all || themed.is_added() {
+            sprite.color = theme.color(themed.0);
+        }
+    }
+    for (themed, mut border) in borders.iter_mut() {
+        if all || themed.is_added() {
+            border.0 = theme.color(themed.0);
+        }
+    }
+    for (themed, mut text) in texts.iter_mut() {
+        if all || themed.is_added() {
+            for section in text.sections.iter_mut() {
+                section.style.color = theme.color(themed.0);
+            }
+        }
+    }
+    if all {
+        for mut sprite in canvas.iter_mut() {
+            sprite.color = theme.canvas;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_defaults() {
+        let light = Theme::light();
+        assert_eq!(Theme::default(), light);
+        assert_eq!(light.node_text_style(), NodeTextStyle::default());
+        assert_eq!(light.node_border(), NodeBorder::default());
+        assert_eq!(
+            light.arrow_style(ArrowStyle::default()),
+            ArrowStyle::default()
+        );
+
+        let dark = Theme::for_kind(ThemeKind::Dark);
+        assert_eq!(dark.kind, ThemeKind::Dark);
+        assert_eq!(dark.node_text_style().color, dark.node_text);
+        assert_eq!(dark.arrow_style(ArrowStyle::default()).color, dark.arrow);
+        // arrows styled with another color keep it
+        let red = ArrowStyle {
+            color: Color::RED,
+            ..default()
+        };
+        assert_eq!(dark.arrow_style(red).color, Color::RED);
+
+        let high_contrast = Theme::for_kind(ThemeKind::HighContrast);
+        assert_eq!(high_contrast.outline_width(1.), 3.);
+        assert_eq!(high_contrast.outline_width(4.), 4.);
+        // nodes without border stay without it
+        assert_eq!(high_contrast.outline_width(0.), 0.);
+        assert_eq!(dark.outline_width(1.), 1.);
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use serde_json::Value;
+
+use super::ui_helpers::Localized;
+use super::{Notification, Theme, ThemeKind};
+
+/// Seconds between two checks of the theme file.
+const WATCH_INTERVAL: f32 = 1.;
+
+/// Custom theme set by `theme_file` of the config, reloaded when it changes.
+#[derive(Resource, Default)]
+pub struct ThemeFile {
+    pub path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+fn theme_color_mut<'a>(theme: &'a mut Theme, name: &str) -> Option<&'a mut Color> {
+    let color = match name {
+        "canvas" => &mut theme.canvas,
+        "grid_line" => &mut theme.grid_line,
+        "menu" => &mut theme.menu,
+        "left_panel" => &mut theme.left_panel,
+        "bottom_panel" => &mut theme.bottom_panel,
+        "border" => &mut theme.border,
+        "surface" => &mut theme.surface,
+        "text" => &mut theme.text,
+        "node_background" => &mut theme.node_background,
+        "node_text" => &mut theme.node_text,
+        "node_border" => &mut theme.node_border,
+        "arrow" => &mut theme.arrow,
+        "selection" => &mut theme.selection,
+        _ => return None,
+    };
+    Some(color)
+}
+
+/// Reads a theme of hex colors (`"#1e1e2e"`, the `#` being optional) named like the fields of
+/// `Theme`, the colors not set are the ones of the `base` theme, `"light"` or `"dark"`.
+pub fn parse_theme(text: &str, is_json: bool) -> Result<Theme, String> {
+    let value: Value = if is_json {
+        serde_json::from_str(text).map_err(|e| e.to_string())?
+    } else {
+        toml::from_str(text).map_err(|e| e.to_string())?
+    };
+    let Value::Object(entries) = value else {
+        return Err("expected a table of colors".to_string());
+    };
+    let mut theme = match entries.get("base").map(|base| base.as_str()) {
+        None | Some(Some("light")) => Theme::light(),
+        Some(Some("dark")) => Theme::dark(),
+        Some(_) => return Err("base should be \"light\" or \"dark\"".to_string()),
+    };
+    theme.kind = ThemeKind::Custom;
+    for (name, value) in entries.iter().filter(|(name, _)| *name != "base") {
+        let hex = value
+            .as_str()
+            .ok_or_else(|| format!("{} should be a hex color", name))?;
+        let color = theme_color_mut(&mut theme, name)
+            .ok_or_else(|| format!("unknown theme color {}", name))?;
+        *color = Color::hex(hex.trim_start_matches('#'))
+            .map_err(|_| format!("invalid color {:?} of {}", hex, name))?;
+    }
+    Ok(theme)
+}
+
+fn read_theme_file(path: &Path) -> Result<Theme, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let is_json = path
+        .extension()
+        .map_or(false, |extension| extension == "json");
+    parse_theme(&text, is_json)
+}
+
+/// Applies the theme file when velo starts and again whenever it's saved, so a theme can be
+/// tweaked while velo is open.
+pub fn watch_theme_file(
+    time: Res<Time>,
+    mut theme_file: ResMut<ThemeFile>,
+    mut theme: ResMut<Theme>,
+    mut notifications: EventWriter<Notification>,
+    mut elapsed: Local<Option<f32>>,
+) {
+    let Some(path) = theme_file.path.clone() else {
+        return;
+    };
+    // checked right away the first time
+    if let Some(elapsed) = elapsed.as_mut() {
+        *elapsed += time.delta_seconds();
+        if *elapsed < WATCH_INTERVAL {
+            return;
+        }
+    }
+    *elapsed = Some(0.);
+    let modified = std::fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    if modified.is_none() || modified == theme_file.modified {
+        return;
+    }
+    theme_file.modified = modified;
+    match read_theme_file(&path) {
+        Ok(custom) => *theme = custom,
+        Err(e) => {
+            let message = Localized::new("Couldn't read the theme {}")
+                .with_args([path.display().to_string()]);
+            notifications.send(Notification::error(message).with_details(e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = parse_theme(
+            "base = \"dark\"\ncanvas = \"#102030\"\nselection = \"ff0000\"\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(theme.kind, ThemeKind::Custom);
+        assert_eq!(theme.canvas, Color::hex("102030").unwrap());
+        assert_eq!(theme.selection, Color::RED);
+        assert_eq!(theme.menu, Theme::dark().menu);
+
+        let theme = parse_theme(r##"{ "arrow": "#00ff00" }"##, true).unwrap();
+        assert_eq!(theme.arrow, Color::GREEN);
+        assert_eq!(theme.text, Theme::light().text);
+
+        assert!(parse_theme("canvas = \"not a color\"", false).is_err());
+        assert!(parse_theme("shadow = \"#000000\"", false).is_err());
+        assert!(parse_theme("base = \"sepia\"", false).is_err());
+        assert!(parse_theme("[1, 2]", true).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ui_borders::BorderColor;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowPins};
+use crate::canvas::arrow::events::CreateArrowEvent;
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
+
+use super::ui_helpers::{MainPanel, NodeRect, PenButton, RectToolButton, ToolButton};
+use super::{Action, Settings, Theme, Tool, UiState, VeloNode, VeloNodeContainer};
+
+impl Tool {
+    /// In the order of the toolbar.
+    pub const ALL: [Tool; 6] = [
+        Tool::Select,
+        Tool::Pan,
+        Tool::Node,
+        Tool::Arrow,
+        Tool::Pen,
+        Tool::Eraser,
+    ];
+
+    pub fn action(self) -> Action {
+        match self {
+            Tool::Select => Action::SelectTool,
+            Tool::Pan => Action::PanTool,
+            Tool::Node => Action::RectTool,
+            Tool::Arrow => Action::ArrowTool,
+            Tool::Pen => Action::PenTool,
+            Tool::Eraser => Action::EraserTool,
+        }
+    }
+
+    /// Material icon of the toolbar button.
+    pub fn icon(self) -> &'static str {
+        match self {
+            Tool::Select => "\u{e569}",
+            Tool::Pan => "\u{e925}",
+            Tool::Node => "\u{e3c6}",
+            Tool::Arrow => "\u{e941}",
+            Tool::Pen => "\u{e3c9}",
+            Tool::Eraser => "\u{e664}",
+        }
+    }
+
+    /// Cursor over the canvas while the tool is active.
+    pub fn cursor(self) -> CursorIcon {
+        match self {
+            Tool::Select => CursorIcon::Default,
+            Tool::Pan => CursorIcon::Grab,
+            Tool::Node | Tool::Pen => CursorIcon::Crosshair,
+            Tool::Arrow => CursorIcon::Alias,
+            Tool::Eraser => CursorIcon::Cell,
+        }
+    }
+}
+
+/// Sides of the nodes facing each other, their rects are in world coordinates (y axis pointing
+/// up).
+fn facing_sides(start: Rect, end: Rect) -> (ArrowConnectPos, ArrowConnectPos) {
+    let delta = end.center() - start.center();
+    if delta.x.abs() > delta.y.abs() {
+        if delta.x > 0. {
+            (ArrowConnectPos::Right, ArrowConnectPos::Left)
+        } else {
+            (ArrowConnectPos::Left, ArrowConnectPos::Right)
+        }
+    } else if delta.y > 0. {
+        (ArrowConnectPos::Top, ArrowConnectPos::Bottom)
+    } else {
+        (ArrowConnectPos::Bottom, ArrowConnectPos::Top)
+    }
+}
+
+/// Toolbar buttons and the chords bound to the tools choose the tool, `Escape` goes back to the
+/// select tool. The pen and rectangle buttons of the menu toggle their tool.
+pub fn select_tool(
+    input: Res<Input<KeyCode>>,
+    ui_state: Res<UiState>,
+    settings: Res<Settings>,
+    tool_buttons: Query<(&Interaction, &ToolButton), Changed<Interaction>>,
+    pen_buttons: Query<&Interaction, (Changed<Interaction>, With<PenButton>)>,
+    rect_buttons: Query<&Interaction, (Changed<Interaction>, With<RectToolButton>)>,
+    mut tool: ResMut<Tool>,
+) {
+    let clicked = |interaction: &Interaction| *interaction == Interaction::Clicked;
+    let toggle = |other: Tool| {
+        if *tool == other {
+            Tool::Select
+        } else {
+            other
+        }
+    };
+    let mut chosen = tool_buttons
+        .iter()
+        .find(|(interaction, _)| clicked(*interaction))
+        .map(|(_, button)| button.0);
+    if pen_buttons.iter().any(clicked) {
+        chosen = Some(toggle(Tool::Pen));
+    } else if rect_buttons.iter().any(clicked) {
+        chosen = Some(toggle(Tool::Node));
+    } else if ui_state.is_canvas_focused() {
+        let shortcut = Tool::ALL
+            .into_iter()
+            .find(|shortcut| settings.keybindings.just_pressed(shortcut.action(), &input));
+        if shortcut.is_some() {
+            chosen = shortcut;
+        } else if input.just_pressed(KeyCode::Escape) {
+            chosen = Some(Tool::Select);
+        }
+    }
+    if let Some(chosen) = chosen {
+        if *tool != chosen {
+            *tool = chosen;
+        }
+    }
+}
+
+/// Outlines the toolbar button of the active tool.
+pub fn highlight_tool_button(
+    tool: Res<Tool>,
+    theme: Res<Theme>,
+    new_buttons: Query<(), Added<ToolButton>>,
+    mut buttons: Query<(&ToolButton, &mut BorderColor)>,
+) {
+    if !tool.is_changed() && !theme.is_changed() && new_buttons.is_empty() {
+        return;
+    }
+    for (button, mut border) in buttons.iter_mut() {
+        border.0 = if button.0 == *tool {
+            theme.selection
+        } else {
+            Color::NONE
+        };
+    }
+}
+
+/// Shows the cursor of the tool when it's chosen and when the pointer comes back to the canvas
+/// from a panel or a button.
+pub fn update_tool_cursor(
+    tool: Res<Tool>,
+    interactions: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    // handles of the select tool set their own cursors
+    let hovered = *tool != Tool::Select
+        && interactions
+            .iter()
+            .any(|interaction| *interaction == Interaction::Hovered);
+    if tool.is_changed() || hovered {
+        windows.single_mut().cursor.icon = tool.cursor();
+    }
+}
+
+/// With the arrow tool dragging from a node and releasing over another one connects them with an
+/// arrow between the sides facing each other.
+pub fn draw_arrow_tool(
+    tool: Res<Tool>,
+    ui_state: Res<UiState>,
+    theme: Res<Theme>,
+    buttons: Res<Input<MouseButton>>,
+    interactions: Query<(&Interaction, &VeloNode), Changed<Interaction>>,
+    containers: Query<(&VeloNodeContainer, &NodeRect, &Visibility)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut create_arrow: EventWriter<CreateArrowEvent>,
+    mut start: Local<Option<ReflectableUuid>>,
+) {
+    if *tool != Tool::Arrow {
+        *start = None;
+        return;
+    }
+    for (interaction, node) in interactions.iter() {
+        if *interaction == Interaction::Clicked {
+            *start = Some(node.id);
+        }
+    }
+    let Some(start_id) = *start else {
+        return;
+    };
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    *start = None;
+    let (camera, camera_transform) = camera_q.single();
+    let Some(cursor) = windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let nodes: Vec<(ReflectableUuid, Rect)> = containers
+        .iter()
+        .filter(|(_, _, visibility)| **visibility != Visibility::Hidden)
+        .map(|(container, rect, _)| (container.id, rect.rect()))
+        .collect();
+    let start_rect = nodes.iter().find(|(id, _)| *id == start_id);
+    let end_rect = nodes
+        .iter()
+        .find(|(id, rect)| *id != start_id && rect.contains(cursor));
+    let (Some((_, start_rect)), Some((end_id, end_rect))) = (start_rect, end_rect) else {
+        return;
+    };
+    let (start_pos, end_pos) = facing_sides(*start_rect, *end_rect);
+    create_arrow.send(CreateArrowEvent {
+        start: ArrowConnect {
+            id: start_id,
+            pos: start_pos,
+        },
+        end: ArrowConnect {
+            id: *end_id,
+            pos: end_pos,
+        },
+        arrow_type: ui_state.arrow_type,
+        style: theme.arrow_style(ui_state.arrow_style),
+        label: None,
+        relation: ui_state.arrow_relation.clone(),
+        pins: ArrowPins::default(),
+        waypoints: vec![],
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_facing_sides() {
+        let start = Rect::new(0., 0., 100., 50.);
+        assert_eq!(
+            facing_sides(start, Rect::new(200., 0., 300., 50.)),
+            (ArrowConnectPos::Right, ArrowConnectPos::Left)
+        );
+        // above in world coordinates
+        assert_eq!(
+            facing_sides(start, Rect::new(0., 150., 100., 200.)),
+            (ArrowConnectPos::Top, ArrowConnectPos::Bottom)
+        );
+        assert_eq!(
+            facing_sides(start, Rect::new(-40., -200., 60., -150.)),
+            (ArrowConnectPos::Bottom, ArrowConnectPos::Top)
+        );
+    }
+
+    #[test]
+    fn test_select_tool() {
+        let mut app = App::new();
+        app.init_resource::<Input<KeyCode>>();
+        app.init_resource::<UiState>();
+        app.init_resource::<Settings>();
+        app.init_resource::<Tool>();
+        app.add_system(select_tool);
+
+        let press = |app: &mut App, key: KeyCode| {
+            let mut input = app.world.resource_mut::<Input<KeyCode>>();
+            input.reset_all();
+            input.press(key);
+            app.update();
+            *app.world.resource::<Tool>()
+        };
+        assert_eq!(press(&mut app, KeyCode::P), Tool::Pen);
+        assert_eq!(press(&mut app, KeyCode::E), Tool::Eraser);
+        assert_eq!(press(&mut app, KeyCode::Escape), Tool::Select);
+        assert_eq!(press(&mut app, KeyCode::N), Tool::Node);
+        assert_eq!(press(&mut app, KeyCode::H), Tool::Pan);
+
+        // tool shortcuts are typed into the edited node instead
+        app.world.resource_mut::<UiState>().editing_node = true;
+        assert_eq!(press(&mut app, KeyCode::V), Tool::Pan);
+    }
+}
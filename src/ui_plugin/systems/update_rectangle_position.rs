@@ -1,31 +1,147 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::prelude::*;
 
 use crate::canvas::arrow::events::RedrawArrowEvent;
+use crate::components::MainCamera;
+use crate::utils::ReflectableUuid;
 
-use super::{LeftPanel, UiState, VeloNodeContainer};
+use super::{
+    ui_helpers::{FrameMembers, NodeRect},
+    with_frame_members, GridSettings, History, HistoryCommand, Locked, SelectedNodes, UiState,
+    VeloNodeContainer,
+};
 
+/// The held node follows the cursor, its left bottom corner snapped to the grid in world
+/// coordinates.
 pub fn update_rectangle_position(
     mut cursor_moved_events: EventReader<CursorMoved>,
-    mut node_position: Query<(&mut Style, &VeloNodeContainer), With<VeloNodeContainer>>,
+    mut node_position: Query<
+        (&mut NodeRect, &VeloNodeContainer, Option<&Locked>),
+        With<VeloNodeContainer>,
+    >,
     state: Res<UiState>,
-    mut query: Query<(&Style, &LeftPanel), Without<VeloNodeContainer>>,
+    selected_nodes: Res<SelectedNodes>,
+    grid_settings: Res<GridSettings>,
+    frames_query: Query<(&VeloNodeContainer, &FrameMembers)>,
     mut events: EventWriter<RedrawArrowEvent>,
-    windows: Query<&Window, With<PrimaryWindow>>,
+    mut history: ResMut<History>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
 ) {
-    let primary_window = windows.single();
-    for event in cursor_moved_events.iter() {
-        for (mut style, top) in &mut node_position.iter_mut() {
-            if Some(top.id) == state.hold_entity && state.entity_to_edit.is_none() {
-                let size = query.single_mut().0.size;
-                if let (Val::Percent(x), Val::Px(element_width)) = (size.width, style.size.width) {
-                    let width = (primary_window.width() * x) / 100.;
-                    style.position.left = Val::Px(event.position.x - width - element_width / 2.);
-                }
-                if let Val::Px(element_height) = style.size.height {
-                    style.position.bottom = Val::Px(event.position.y - element_height / 2.);
-                }
+    // only the last position of the frame matters, the held node follows it and the rest of
+    // the selection moves by the whole distance at once
+    let Some(event) = cursor_moved_events.iter().last() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+    let Some(cursor) = camera.viewport_to_world_2d(camera_transform, event.position) else {
+        return;
+    };
+    let held = selected_nodes.held(&state).filter(|_| !state.editing_node);
+    let mut delta = None;
+    for (mut rect, top, _) in &mut node_position.iter_mut() {
+        if Some(top.id) == held {
+            let old = rect.position;
+            let position = cursor - rect.size / 2.;
+            rect.position = Vec2::new(
+                grid_settings.snap(position.x),
+                grid_settings.snap(position.y),
+            );
+            delta = Some(rect.position - old);
+            history.record(HistoryCommand::NodeMoved {
+                id: top.id.0,
+                from: old,
+                to: rect.position,
+            });
+            events.send(RedrawArrowEvent { id: top.id });
+        }
+    }
+    // move the rest of the selection and contents of moved frames along with the held node
+    if let (Some(delta), Some(hold_entity)) = (delta, held) {
+        let frames: Vec<_> = frames_query
+            .iter()
+            .map(|(frame, members)| (frame.id, members.0.clone()))
+            .collect();
+        let moved = with_frame_members(&selected_nodes.0, &frames);
+        for (mut rect, top, locked) in &mut node_position.iter_mut() {
+            if top.id != hold_entity && moved.contains(&top.id) && locked.is_none() {
+                history.record(HistoryCommand::NodeMoved {
+                    id: top.id.0,
+                    from: rect.position,
+                    to: rect.position + delta,
+                });
+                rect.position += delta;
                 events.send(RedrawArrowEvent { id: top.id });
             }
         }
     }
 }
+
+/// Moves selected nodes with arrow keys by 1px, or by 10px with Shift held.
+pub fn nudge_selected_nodes(
+    input: Res<Input<KeyCode>>,
+    state: Res<UiState>,
+    selected_nodes: Res<SelectedNodes>,
+    mut node_position: Query<
+        (&mut NodeRect, &VeloNodeContainer, Option<&Locked>),
+        With<VeloNodeContainer>,
+    >,
+    frames_query: Query<(&VeloNodeContainer, &FrameMembers)>,
+    mut events: EventWriter<RedrawArrowEvent>,
+    mut history: ResMut<History>,
+    mut moved: Local<Vec<ReflectableUuid>>,
+) {
+    // arrows are redrawn once the layout of moved nodes is computed
+    for id in moved.drain(..) {
+        events.send(RedrawArrowEvent { id });
+    }
+    // Alt and Cmd/Ctrl with arrow keys select and align nodes
+    if !state.is_canvas_focused()
+        || selected_nodes.0.is_empty()
+        || input.any_pressed([
+            KeyCode::LAlt,
+            KeyCode::RAlt,
+            KeyCode::RWin,
+            KeyCode::LWin,
+            KeyCode::RControl,
+            KeyCode::LControl,
+        ])
+    {
+        return;
+    }
+    let step = if input.any_pressed([KeyCode::RShift, KeyCode::LShift]) {
+        10.
+    } else {
+        1.
+    };
+    let mut delta = Vec2::ZERO;
+    if input.just_pressed(KeyCode::Left) {
+        delta.x -= step;
+    }
+    if input.just_pressed(KeyCode::Right) {
+        delta.x += step;
+    }
+    if input.just_pressed(KeyCode::Up) {
+        delta.y += step;
+    }
+    if input.just_pressed(KeyCode::Down) {
+        delta.y -= step;
+    }
+    if delta == Vec2::ZERO {
+        return;
+    }
+    let frames: Vec<_> = frames_query
+        .iter()
+        .map(|(frame, members)| (frame.id, members.0.clone()))
+        .collect();
+    let nudged = with_frame_members(&selected_nodes.0, &frames);
+    for (mut rect, top, locked) in &mut node_position.iter_mut() {
+        if nudged.contains(&top.id) && locked.is_none() {
+            history.record(HistoryCommand::NodeMoved {
+                id: top.id.0,
+                from: rect.position,
+                to: rect.position + delta,
+            });
+            rect.position += delta;
+            moved.push(top.id);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use async_channel::Sender;
+use bevy::prelude::*;
+use js_sys::{Array, Function, Object, Promise, Reflect, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    Blob, BlobPropertyBag, HtmlAnchorElement, HtmlInputElement, IdbDatabase, IdbOpenDbRequest,
+    IdbRequest, IdbTransactionMode, Url,
+};
+
+use super::ui_helpers::{ImportFromFile, Localized};
+use super::{encrypt_doc, read_doc, CommChannels, Notification, NotificationSender};
+use crate::components::Doc;
+
+const DB_NAME: &str = "velo";
+const DOCS_STORE: &str = "docs";
+const LAST_SAVED_KEY: &str = "last_saved";
+
+/// Resolves with the result of the IndexedDB request.
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    JsFuture::from(promise).await?;
+    request.result()
+}
+
+async fn open_db() -> Result<IdbDatabase, JsValue> {
+    let factory = web_sys::window()
+        .and_then(|window| window.indexed_db().ok().flatten())
+        .ok_or("IndexedDB isn't available")?;
+    let request = factory.open_with_u32(DB_NAME, 1)?;
+    let upgrade = Closure::once_into_js(move |event: web_sys::Event| {
+        let db = event
+            .target()
+            .and_then(|target| target.dyn_into::<IdbOpenDbRequest>().ok())
+            .and_then(|request| request.result().ok())
+            .and_then(|db| db.dyn_into::<IdbDatabase>().ok());
+        if let Some(db) = db {
+            let _ = db.create_object_store(DOCS_STORE);
+        }
+    });
+    request.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+    request_result(&request).await?.dyn_into()
+}
+
+async fn put_doc(value: JsValue, doc_id: String) -> Result<(), JsValue> {
+    let db = open_db().await?;
+    let store = db
+        .transaction_with_str_and_mode(DOCS_STORE, IdbTransactionMode::Readwrite)?
+        .object_store(DOCS_STORE)?;
+    let doc_id = JsValue::from(doc_id);
+    request_result(&store.put_with_key(&value, &doc_id)?).await?;
+    request_result(&store.put_with_key(&doc_id, &JsValue::from(LAST_SAVED_KEY))?).await?;
+    Ok(())
+}
+
+async fn last_saved_doc() -> Result<Option<String>, JsValue> {
+    let db = open_db().await?;
+    let store = db
+        .transaction_with_str(DOCS_STORE)?
+        .object_store(DOCS_STORE)?;
+    let Some(doc_id) = request_result(&store.get(&LAST_SAVED_KEY.into())?)
+        .await?
+        .as_string()
+    else {
+        return Ok(None);
+    };
+    // encrypted documents are put as bytes, they can't be opened without their passphrase
+    Ok(request_result(&store.get(&doc_id.into())?)
+        .await?
+        .as_string())
+}
+
+/// Keeps a copy of the saved document in IndexedDB, which holds much more than the local
+/// storage used by `PkvStore` in the browser. Documents with a passphrase are put encrypted.
+pub fn autosave_to_indexed_db(doc: &Doc, passphrase: Option<&str>) {
+    let value = match passphrase {
+        Some(passphrase) => match encrypt_doc(doc, passphrase) {
+            Ok(data) => JsValue::from(Uint8Array::from(data.as_slice())),
+            Err(e) => {
+                error!("Error encrypting document for IndexedDB: {:?}", e);
+                return;
+            }
+        },
+        None => match serde_json::to_string(doc) {
+            Ok(json) => JsValue::from(json),
+            Err(_) => return,
+        },
+    };
+    let doc_id = doc.id.0.to_string();
+    spawn_local(async move {
+        if let Err(e) = put_doc(value, doc_id).await {
+            error!("Error saving document to IndexedDB: {:?}", e);
+        }
+    });
+}
+
+/// Opens the last document saved to IndexedDB, used when the local storage lost it.
+pub fn restore_from_indexed_db(tx: Sender<Doc>) {
+    spawn_local(async move {
+        match last_saved_doc().await {
+            Ok(Some(json)) => match serde_json::from_str::<Doc>(&json) {
+                Ok(doc) => {
+                    let _ = tx.try_send(doc);
+                }
+                Err(e) => error!("Error reading document from IndexedDB: {:?}", e),
+            },
+            Ok(None) => {}
+            Err(e) => error!("Error opening IndexedDB: {:?}", e),
+        }
+    });
+}
+
+async fn save_with_file_picker(
+    picker: Function,
+    file_name: String,
+    data: Vec<u8>,
+) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let options = Object::new();
+    Reflect::set(&options, &"suggestedName".into(), &file_name.into())?;
+    let handle = JsFuture::from(picker.call1(&window, &options)?.dyn_into::<Promise>()?).await?;
+    let create_writable: Function = Reflect::get(&handle, &"createWritable".into())?.dyn_into()?;
+    let writable = JsFuture::from(create_writable.call0(&handle)?.dyn_into::<Promise>()?).await?;
+    let write: Function = Reflect::get(&writable, &"write".into())?.dyn_into()?;
+    JsFuture::from(
+        write
+            .call1(&writable, &Uint8Array::from(data.as_slice()))?
+            .dyn_into::<Promise>()?,
+    )
+    .await?;
+    let close: Function = Reflect::get(&writable, &"close".into())?.dyn_into()?;
+    JsFuture::from(close.call0(&writable)?.dyn_into::<Promise>()?).await?;
+    Ok(())
+}
+
+fn download(file_name: &str, data: &[u8]) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let parts = Array::of1(&Uint8Array::from(data));
+    let mut options = BlobPropertyBag::new();
+    options.type_("application/octet-stream");
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let anchor: HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    Url::revoke_object_url(&url)
+}
+
+/// Saves the file with the File System Access API where the browser has it, as a download
+/// otherwise.
+pub fn save_file_in_browser(file_name: String, data: Vec<u8>) {
+    let picker = web_sys::window()
+        .and_then(|window| Reflect::get(&window, &"showSaveFilePicker".into()).ok())
+        .and_then(|picker| picker.dyn_into::<Function>().ok());
+    match picker {
+        Some(picker) => spawn_local(async move {
+            // cancelling the picker rejects it too
+            if let Err(e) = save_with_file_picker(picker, file_name, data).await {
+                info!("File not saved: {:?}", e);
+            }
+        }),
+        None => {
+            if let Err(e) = download(&file_name, &data) {
+                error!("Error downloading file: {:?}", e);
+            }
+        }
+    }
+}
+
+async fn read_file(input: HtmlInputElement) -> Result<Vec<u8>, JsValue> {
+    let file = input
+        .files()
+        .and_then(|files| files.get(0))
+        .ok_or("no file chosen")?;
+    let buffer = JsFuture::from(file.array_buffer()).await?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// Lets the user choose a document file with the browser file picker and opens it.
+pub fn pick_file_in_browser(tx: Sender<Doc>, notification_sender: NotificationSender) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    let Ok(input) = document.create_element("input").and_then(|element| {
+        element
+            .dyn_into::<HtmlInputElement>()
+            .map_err(JsValue::from)
+    }) else {
+        return;
+    };
+    input.set_type("file");
+    input.set_accept(".json,.gz");
+    let chosen = input.clone();
+    let on_change = Closure::once_into_js(move || {
+        spawn_local(async move {
+            match read_file(chosen).await {
+                Ok(data) => match read_doc(data.as_slice()) {
+                    Ok(doc) => {
+                        let _ = tx.try_send(doc);
+                    }
+                    Err(e) => {
+                        let message = Localized::new("Couldn't read the document");
+                        notification_sender.send(Notification::error(message).with_details(e));
+                    }
+                },
+                Err(e) => info!("No document opened: {:?}", e),
+            }
+        });
+    });
+    input.set_onchange(Some(on_change.unchecked_ref()));
+    input.click();
+}
+
+/// "Import From File" opens the browser file picker, there is no path to type in the browser.
+pub fn import_from_browser_file(
+    query: Query<&Interaction, (Changed<Interaction>, With<ImportFromFile>)>,
+    comm_channels: Res<CommChannels>,
+    notification_sender: Res<NotificationSender>,
+) {
+    if query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked)
+    {
+        pick_file_in_browser(comm_channels.tx.clone(), notification_sender.clone());
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::{History, UnsavedChanges};
+use crate::resources::{AppState, LoadDocRequest, SaveDocRequest};
+use crate::utils::ReflectableUuid;
+
+const APP_TITLE: &str = "Velo";
+
+/// `<document name> — <active tab>`, followed by a dot when there are unsaved changes.
+pub fn window_title(doc_name: &str, tab_name: Option<&str>, unsaved: bool) -> String {
+    let mut title = match tab_name {
+        Some(tab_name) => format!("{} — {}", doc_name, tab_name),
+        None => doc_name.to_string(),
+    };
+    if unsaved {
+        title.push_str(" ●");
+    }
+    title
+}
+
+/// Edits recorded, undone or redone in the history make the document unsaved, saving or
+/// loading it makes it saved again. Switching tabs doesn't change it.
+pub fn track_unsaved_changes(
+    history: Res<History>,
+    app_state: Res<AppState>,
+    save_request: Option<Res<SaveDocRequest>>,
+    load_request: Option<Res<LoadDocRequest>>,
+    mut unsaved: ResMut<UnsavedChanges>,
+    mut last: Local<Option<(ReflectableUuid, usize, usize)>>,
+    mut last_doc: Local<Option<ReflectableUuid>>,
+) {
+    let edits = history
+        .tab_id()
+        .zip(history.active_tab())
+        .map(|(tab_id, tab)| (tab_id, tab.undo.len(), tab.redo.len()));
+    let edited = match (*last, edits) {
+        (Some((last_tab, ..)), Some((tab_id, ..))) => last_tab == tab_id && *last != edits,
+        _ => false,
+    };
+    *last = edits;
+    let saved = save_request.map_or(false, |request| {
+        Some(request.doc_id) == app_state.current_document
+    });
+    let loaded = load_request.is_some() || *last_doc != app_state.current_document;
+    *last_doc = app_state.current_document;
+    let value = if saved || loaded {
+        false
+    } else {
+        unsaved.0 || edited
+    };
+    if unsaved.0 != value {
+        unsaved.0 = value;
+    }
+}
+
+/// Shows the open document, its active tab and whether it has unsaved changes in the window
+/// title, for the task switcher of the OS.
+pub fn update_window_title(
+    app_state: Res<AppState>,
+    unsaved: Res<UnsavedChanges>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !app_state.is_changed() && !unsaved.is_changed() {
+        return;
+    }
+    let doc = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get(&doc_id));
+    let title = match doc {
+        Some(doc) => {
+            let tab = doc.tabs.iter().find(|tab| tab.is_active);
+            window_title(&doc.name, tab.map(|tab| tab.name.as_str()), unsaved.0)
+        }
+        None => APP_TITLE.to_string(),
+    };
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    // the window is only touched when the title differs
+    if window.title != title {
+        window.title = title;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Doc, Tab};
+
+    #[test]
+    fn test_update_window_title() {
+        assert_eq!(window_title("Notes", None, true), "Notes ●");
+
+        let mut app = App::new();
+        app.init_resource::<AppState>();
+        app.init_resource::<UnsavedChanges>();
+        app.add_system(update_window_title);
+        app.world.spawn((Window::default(), PrimaryWindow));
+        let title = |app: &mut App| {
+            let mut windows = app.world.query::<&Window>();
+            windows.single(&app.world).title.clone()
+        };
+        app.update();
+        assert_eq!(title(&mut app), APP_TITLE);
+
+        let doc_id = ReflectableUuid::generate();
+        let tab = Tab {
+            is_active: true,
+            id: ReflectableUuid::generate(),
+            name: "Plan".to_string(),
+            checkpoint: None,
+            zoom: 1.,
+            color: None,
+            icon: None,
+            pinned: false,
+            offset: [0., 0.],
+            selected: vec![],
+        };
+        let mut app_state = app.world.resource_mut::<AppState>();
+        app_state.current_document = Some(doc_id);
+        app_state.docs.insert(
+            doc_id,
+            Doc {
+                id: doc_id,
+                name: "Roadmap".to_string(),
+                tabs: vec![tab],
+                ..default()
+            },
+        );
+        app.update();
+        assert_eq!(title(&mut app), "Roadmap — Plan");
+
+        app.world.resource_mut::<UnsavedChanges>().0 = true;
+        app.update();
+        assert_eq!(title(&mut app), "Roadmap — Plan ●");
+    }
+}
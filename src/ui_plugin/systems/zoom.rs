@@ -0,0 +1,134 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::resources::AppState;
+use crate::NodeTextStyle;
+
+use super::{
+    panel_rect, ui_helpers::NodeRect, world_to_canvas, CanvasOffset, CanvasZoom, MainPanel,
+    UiState, VeloNode, ZoomCanvasEvent,
+};
+
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 4.;
+// Zoom factor of Cmd/Ctrl+plus/minus
+const KEY_ZOOM_STEP: f32 = 1.25;
+// Zoom factor of one line of mouse wheel scroll
+const WHEEL_ZOOM_STEP: f32 = 1.1;
+// Pixels of touchpad scroll counted as one line
+const PIXELS_PER_LINE: f32 = 20.;
+// Free space around nodes after fit to content, as share of canvas size
+const FIT_MARGIN: f32 = 0.05;
+
+/// Zooms canvas with Cmd/Ctrl+scroll (around cursor), Cmd/Ctrl+plus/minus, Cmd/Ctrl+0 (reset)
+/// and Shift+1 (fit to content), other systems (e.g. presentation) zoom it with `ZoomCanvasEvent`.
+///
+/// Only `CanvasZoom` and `CanvasOffset` change, the main camera follows them. Text is re-rendered
+/// in the new size by `update_text_style`.
+pub fn canvas_zoom(
+    input: Res<Input<KeyCode>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut zoom_events: EventReader<ZoomCanvasEvent>,
+    ui_state: Res<UiState>,
+    mut zoom: ResMut<CanvasZoom>,
+    mut offset: ResMut<CanvasOffset>,
+    mut app_state: ResMut<AppState>,
+    main_panel_query: Query<(&Node, &GlobalTransform), With<MainPanel>>,
+    containers: Query<&NodeRect>,
+    mut text_styles: Query<&mut NodeTextStyle, With<VeloNode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let command = input.any_pressed([
+        KeyCode::RWin,
+        KeyCode::LWin,
+        KeyCode::RControl,
+        KeyCode::LControl,
+    ]);
+    let shift = input.any_pressed([KeyCode::RShift, KeyCode::LShift]);
+    let Ok((panel, panel_transform)) = main_panel_query.get_single() else {
+        return;
+    };
+    let window = windows.single();
+    let panel_size = panel.size();
+    let panel_center = panel_size / 2.;
+
+    let mut wheel_lines = 0.;
+    for event in mouse_wheel_events.iter() {
+        wheel_lines += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        };
+    }
+
+    // new zoom, point of the canvas that is scaled and where it ends up (from left bottom corner)
+    let (new_zoom, from, to) = if let Some(event) = zoom_events.iter().last().copied() {
+        (event.zoom, event.from, event.to)
+    } else if command && wheel_lines != 0. {
+        let Some(cursor) = window.cursor_position() else {
+            return;
+        };
+        let pivot = cursor - panel_rect(panel, panel_transform, window).min;
+        (zoom.0 * WHEEL_ZOOM_STEP.powf(wheel_lines), pivot, pivot)
+    } else if command && ui_state.is_canvas_focused() {
+        if input.any_just_pressed([KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]) {
+            (zoom.0 * KEY_ZOOM_STEP, panel_center, panel_center)
+        } else if input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+            (zoom.0 / KEY_ZOOM_STEP, panel_center, panel_center)
+        } else if input.any_just_pressed([KeyCode::Key0, KeyCode::Numpad0]) {
+            (1., panel_center, panel_center)
+        } else {
+            return;
+        }
+    } else if shift && ui_state.is_canvas_focused() && input.just_pressed(KeyCode::Key1) {
+        let Some((min, max)) = content_bounds(&containers) else {
+            return;
+        };
+        let (min, max) = (
+            world_to_canvas(min, &offset, &zoom),
+            world_to_canvas(max, &offset, &zoom),
+        );
+        let content_size = (max - min).max(Vec2::ONE);
+        let available = panel_size * (1. - 2. * FIT_MARGIN);
+        let fit = (available / content_size).min_element();
+        (zoom.0 * fit, (min + max) / 2., panel_center)
+    } else {
+        return;
+    };
+
+    let new_zoom = new_zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    let factor = new_zoom / zoom.0;
+    if factor == 1. && from == to {
+        return;
+    }
+    // the world point shown at `from` is shown at `to`
+    offset.0 = to + (offset.0 - from) * factor;
+    zoom.0 = new_zoom;
+    // re-render text of all nodes in the new size
+    for mut text_style in text_styles.iter_mut() {
+        text_style.set_changed();
+    }
+
+    // zoom is remembered per tab
+    if let Some(doc) = app_state
+        .current_document
+        .and_then(|doc_id| app_state.docs.get_mut(&doc_id))
+    {
+        if let Some(tab) = doc.tabs.iter_mut().find(|tab| tab.is_active) {
+            tab.zoom = new_zoom;
+        }
+    }
+}
+
+/// Left bottom and right top corners (world) of the box around all nodes.
+fn content_bounds(containers: &Query<&NodeRect>) -> Option<(Vec2, Vec2)> {
+    containers.iter().fold(None, |bounds, rect| {
+        let (min, max) = (rect.position, rect.position + rect.size);
+        Some(match bounds {
+            Some((bounds_min, bounds_max)) => (min.min(bounds_min), max.max(bounds_max)),
+            None => (min, max),
+        })
+    })
+}
@@ -1,28 +1,57 @@
 use bevy::prelude::*;
 
-use crate::utils::ReflectableUuid;
+use crate::components::Tab;
 
 use super::{DeleteTab, EditableText, GenericButton, TabButton, TabContainer};
 
-pub fn add_tab(
+pub const TAB_WIDTH: f32 = 140.;
+pub const TAB_MARGIN: f32 = 10.;
+pub const TAB_COLOR: Color = Color::rgb(1., 193.0 / 255.0, 7.0 / 255.0);
+const PIN_ICON: &str = "\u{f10d}";
+
+/// Icons of tabs by the name they are saved with.
+pub const TAB_ICONS: [(&str, &str); 8] = [
+    ("star", "\u{e838}"),
+    ("favorite", "\u{e87d}"),
+    ("bookmark", "\u{e866}"),
+    ("flag", "\u{e153}"),
+    ("home", "\u{e88a}"),
+    ("work", "\u{e8f9}"),
+    ("code", "\u{e86f}"),
+    ("idea", "\u{e0f0}"),
+];
+
+/// Spawns the tabs of the document in the tab bar, in order.
+pub fn add_tabs(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    name: String,
-    id: ReflectableUuid,
-    is_active: bool,
-) -> Entity {
+    tab_bar: Entity,
+    tabs: &[Tab],
+) {
+    let tabs: Vec<Entity> = tabs
+        .iter()
+        .map(|tab| add_tab(commands, asset_server, tab))
+        .collect();
+    commands.entity(tab_bar).push_children(&tabs);
+}
+
+pub fn add_tab(commands: &mut Commands, asset_server: &Res<AssetServer>, tab: &Tab) -> Entity {
+    let id = tab.id;
     let icon_font = asset_server.load("fonts/MaterialIcons-Regular.ttf");
+    let color = tab.color.unwrap_or(TAB_COLOR);
     let root = commands
         .spawn((
             NodeBundle {
-                background_color: Color::rgb(1., 193.0 / 255.0, 7.0 / 255.0).into(),
+                background_color: color.into(),
                 style: Style {
-                    size: Size::new(Val::Percent(8.), Val::Percent(90.)),
+                    size: Size::new(Val::Px(TAB_WIDTH), Val::Percent(90.)),
+                    // tabs that don't fit are scrolled to instead of being squeezed
+                    flex_shrink: 0.,
                     justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
                     margin: UiRect {
-                        left: Val::Px(10.),
-                        right: Val::Px(10.),
+                        left: Val::Px(TAB_MARGIN),
+                        right: Val::Px(TAB_MARGIN),
                         top: Val::Px(0.),
                         bottom: Val::Px(0.),
                     },
@@ -36,7 +65,7 @@ pub fn add_tab(
     let tab_button = commands
         .spawn((
             ButtonBundle {
-                background_color: Color::rgb(1., 193.0 / 255.0, 7.0 / 255.0).into(),
+                background_color: color.into(),
                 style: Style {
                     size: Size::new(Val::Percent(90.), Val::Percent(100.)),
                     align_items: AlignItems::Center,
@@ -56,7 +85,7 @@ pub fn add_tab(
                 text: Text {
                     sections: vec![
                         TextSection {
-                            value: name,
+                            value: tab.name.clone(),
                             style: TextStyle {
                                 font_size: 18.,
                                 color: Color::BLACK,
@@ -86,8 +115,9 @@ pub fn add_tab(
     let del_button = commands
         .spawn((
             ButtonBundle {
-                background_color: Color::rgb(1., 193.0 / 255.0, 7.0 / 255.0).into(),
-                visibility: if is_active {
+                background_color: color.into(),
+                // pinned tabs can't be deleted
+                visibility: if tab.is_active && !tab.pinned {
                     Visibility::Visible
                 } else {
                     Visibility::Hidden
@@ -120,7 +150,7 @@ pub fn add_tab(
                         style: TextStyle {
                             font_size: 18.,
                             color: Color::BLACK,
-                            font: icon_font,
+                            font: icon_font.clone(),
                         },
                     }],
                     ..default()
@@ -130,6 +160,34 @@ pub fn add_tab(
             Label,
         ))
         .id();
+    let icons: String = [
+        tab.pinned.then_some(PIN_ICON),
+        tab.icon.as_ref().and_then(|icon| {
+            TAB_ICONS
+                .iter()
+                .find(|(name, _)| name == icon)
+                .map(|(_, glyph)| *glyph)
+        }),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !icons.is_empty() {
+        let icons_label = commands
+            .spawn((
+                TextBundle::from_section(
+                    icons,
+                    TextStyle {
+                        font_size: 18.,
+                        color: Color::BLACK,
+                        font: icon_font,
+                    },
+                ),
+                Label,
+            ))
+            .id();
+        commands.entity(tab_button).add_child(icons_label);
+    }
     commands.entity(tab_button).add_child(tab_label);
     commands.entity(del_button).add_child(del_label);
     commands.entity(root).add_child(tab_button);
@@ -0,0 +1,161 @@
+use crate::ui_plugin::{BorderStyle, NodeBorder, NodeType};
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::DEFAULT_IMAGE_HANDLE,
+    },
+};
+
+use super::CanvasOutline;
+
+// Transparent texture of nodes which fill is drawn by the border image (rounded corners)
+pub const ROUNDED_FILL_IMAGE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x7e10_5ba9_0000_0101);
+
+/// Whether the sprite of a node shows an image, not its plain color or a built-in texture.
+pub fn has_image(texture: &Handle<Image>) -> bool {
+    *texture != DEFAULT_IMAGE_HANDLE.typed() && *texture != ROUNDED_FILL_IMAGE_HANDLE.typed()
+}
+
+/// Only box-shaped nodes have configurable border, other shapes are drawn with textures.
+pub fn has_border(node_type: &NodeType) -> bool {
+    matches!(
+        node_type,
+        NodeType::Rect | NodeType::Sticky | NodeType::Image | NodeType::Frame
+    )
+}
+
+/// Plain borders are drawn with an outline, the rest with a generated texture.
+pub fn border_outline(border: &NodeBorder, opacity: f32) -> CanvasOutline {
+    if border.style == BorderStyle::Solid && border.radius <= 0. {
+        let color = border.color.with_a(border.color.a() * opacity);
+        CanvasOutline::new(color, border.width)
+    } else {
+        CanvasOutline::new(Color::rgba(0., 0., 0., 0.), 0.)
+    }
+}
+
+/// Outline of a node which is neither selected nor edited.
+pub fn node_outline(
+    node_type: &NodeType,
+    border: Option<&NodeBorder>,
+    opacity: f32,
+) -> CanvasOutline {
+    match border {
+        Some(border) if has_border(node_type) => border_outline(border, opacity),
+        _ => CanvasOutline::new(
+            Color::rgba(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0, 0.),
+            1.,
+        ),
+    }
+}
+
+// Returns (dash, gap) lengths in logical pixels
+fn dash_pattern(border: &NodeBorder) -> Option<(f32, f32)> {
+    match border.style {
+        BorderStyle::Solid => None,
+        BorderStyle::Dashed => Some(((3. * border.width).max(4.), (2. * border.width).max(3.))),
+        BorderStyle::Dotted => Some((border.width.max(1.), (1.5 * border.width).max(2.))),
+    }
+}
+
+/// Signed distance from `p` (relative to the center) to rounded rectangle, negative inside.
+pub fn rounded_rect_distance(p: Vec2, half: Vec2, radius: f32) -> f32 {
+    let q = p.abs() - (half - Vec2::splat(radius));
+    q.max(Vec2::ZERO).length() + q.x.max(q.y).min(0.) - radius
+}
+
+/// Draws border of node of `size` (logical pixels), `fill` is drawn inside of rounded border.
+pub fn rasterize_border(
+    border: &NodeBorder,
+    opacity: f32,
+    fill: Option<Color>,
+    size: Vec2,
+    scale_factor: f32,
+) -> Image {
+    let width = (size.x * scale_factor).ceil().max(1.) as u32;
+    let height = (size.y * scale_factor).ceil().max(1.) as u32;
+    let half = size / 2.;
+    let radius = border.radius.min(half.x).min(half.y).max(0.);
+    let pattern = dash_pattern(border);
+    // coverage of pixel by area where signed distance is negative
+    let coverage = |distance: f32| (0.5 - distance * scale_factor).clamp(0., 1.);
+    let [br, bg, bb, ba] = border.color.as_rgba_f32();
+    let [fr, fg, fb, fa] = fill.unwrap_or(Color::rgba(0., 0., 0., 0.)).as_rgba_f32();
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let p = Vec2::new(
+                (x as f32 + 0.5) / scale_factor,
+                (y as f32 + 0.5) / scale_factor,
+            );
+            let distance = rounded_rect_distance(p - half, half, radius);
+            let outer = coverage(distance);
+            let mut stroke = if border.width > 0. {
+                outer - coverage(distance + border.width)
+            } else {
+                0.
+            };
+            if let Some((dash, gap)) = pattern {
+                // position along the closest straight edge, corners are always drawn
+                let q = (p - half).abs() - (half - Vec2::splat(radius));
+                let along = if q.x > 0. && q.y > 0. {
+                    None
+                } else if q.x > q.y {
+                    Some(p.y)
+                } else {
+                    Some(p.x)
+                };
+                if let Some(along) = along {
+                    if along % (dash + gap) >= dash {
+                        stroke = 0.;
+                    }
+                }
+            }
+            let stroke_alpha = ba * opacity * stroke;
+            let fill_alpha = fa * outer * (1. - stroke_alpha);
+            let alpha = stroke_alpha + fill_alpha;
+            let blend = |b: f32, f: f32| {
+                if alpha > 0. {
+                    (b * stroke_alpha + f * fill_alpha) / alpha
+                } else {
+                    0.
+                }
+            };
+            data.extend_from_slice(&[
+                (255. * blend(br, fr)) as u8,
+                (255. * blend(bg, fg)) as u8,
+                (255. * blend(bb, fb)) as u8,
+                (255. * alpha) as u8,
+            ]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn init_border_images(mut images: ResMut<Assets<Image>>) {
+    images.set_untracked(
+        ROUNDED_FILL_IMAGE_HANDLE,
+        Image::new(
+            Extent3d {
+                width: 1,
+                height: 1,
+                ..Default::default()
+            },
+            TextureDimension::D2,
+            vec![0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+        ),
+    );
+}
@@ -0,0 +1,348 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    ui::FocusPolicy,
+};
+use bevy_ui_borders::BorderColor;
+
+use super::{
+    ColorPicker, ColorPickerArea, ColorPickerButton, ColorPickerHex, ColorPickerPreview,
+    GenericButton, RecentColorSwatch,
+};
+
+pub const SATURATION_VALUE_SIZE: UVec2 = UVec2::new(220, 140);
+pub const HUE_SIZE: UVec2 = UVec2::new(220, 14);
+
+/// Converts hue (degrees), saturation and value (0-1) to color.
+pub fn hsv_to_color(hue: f32, saturation: f32, value: f32) -> Color {
+    let hue = hue.rem_euclid(360.) / 60.;
+    let chroma = value * saturation;
+    let x = chroma * (1. - (hue % 2. - 1.).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    let m = value - chroma;
+    Color::rgb(r + m, g + m, b + m)
+}
+
+/// Hue (degrees), saturation and value (0-1) of the color, alpha is ignored.
+pub fn color_to_hsv(color: Color) -> (f32, f32, f32) {
+    let (r, g, b) = (color.r(), color.g(), color.b());
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let hue = if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+    let saturation = if max == 0. { 0. } else { delta / max };
+    (hue, saturation, max)
+}
+
+pub fn color_to_hex(color: Color) -> String {
+    let [r, g, b, _] = color.as_rgba_u8();
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+fn new_image(size: UVec2, data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Saturation (left to right) and value (bottom to top) square of the hue with a marker
+/// of the current saturation and value.
+pub fn saturation_value_image(hue: f32, saturation: f32, value: f32) -> Image {
+    let UVec2 {
+        x: width,
+        y: height,
+    } = SATURATION_VALUE_SIZE;
+    let marker = UVec2::new(
+        (saturation * (width - 1) as f32).round() as u32,
+        ((1. - value) * (height - 1) as f32).round() as u32,
+    );
+    // dark marker on light colors, light one on dark
+    let marker_color = if value > 0.5 {
+        [0, 0, 0, 255]
+    } else {
+        [255; 4]
+    };
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x.abs_diff(marker.x);
+            let dy = y.abs_diff(marker.y);
+            if dx.max(dy) == 4 {
+                data.extend_from_slice(&marker_color);
+                continue;
+            }
+            let color = hsv_to_color(
+                hue,
+                x as f32 / (width - 1) as f32,
+                1. - y as f32 / (height - 1) as f32,
+            );
+            data.extend_from_slice(&color.as_rgba_u8());
+        }
+    }
+    new_image(SATURATION_VALUE_SIZE, data)
+}
+
+/// Bar of all hues with a marker of the current one.
+pub fn hue_image(hue: f32) -> Image {
+    let UVec2 {
+        x: width,
+        y: height,
+    } = HUE_SIZE;
+    let marker = (hue / 360. * (width - 1) as f32).round() as u32;
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for _ in 0..height {
+        for x in 0..width {
+            if x.abs_diff(marker) <= 1 {
+                data.extend_from_slice(&[0, 0, 0, 255]);
+                continue;
+            }
+            let color = hsv_to_color(x as f32 / (width - 1) as f32 * 360., 1., 1.);
+            data.extend_from_slice(&color.as_rgba_u8());
+        }
+    }
+    new_image(HUE_SIZE, data)
+}
+
+fn text_style(font_size: f32) -> TextStyle {
+    TextStyle {
+        font_size,
+        color: Color::BLACK,
+        ..default()
+    }
+}
+
+/// Color picker popup in the top left corner of the canvas.
+pub fn spawn_color_picker(
+    commands: &mut Commands,
+    icon_font: &Handle<Font>,
+    picker: ColorPicker,
+    recent_colors: &[Color],
+) -> Entity {
+    let color = hsv_to_color(picker.hue, picker.saturation, picker.value);
+    let saturation_value = picker.saturation_value_image.clone();
+    let hue = picker.hue_image.clone();
+    let row_style = Style {
+        align_items: AlignItems::Center,
+        margin: UiRect::top(Val::Px(6.)),
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                // clicks on the popup don't reach the canvas
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Local(i32::MAX),
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+            picker,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                ButtonBundle {
+                    style: Style {
+                        size: Size::new(
+                            Val::Px(SATURATION_VALUE_SIZE.x as f32),
+                            Val::Px(SATURATION_VALUE_SIZE.y as f32),
+                        ),
+                        ..default()
+                    },
+                    image: saturation_value.into(),
+                    ..default()
+                },
+                ColorPickerArea::SaturationValue,
+            ));
+            builder.spawn((
+                ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(HUE_SIZE.x as f32), Val::Px(HUE_SIZE.y as f32)),
+                        margin: UiRect::top(Val::Px(6.)),
+                        ..default()
+                    },
+                    image: hue.into(),
+                    ..default()
+                },
+                ColorPickerArea::Hue,
+            ));
+            builder
+                .spawn(NodeBundle {
+                    style: row_style.clone(),
+                    ..default()
+                })
+                .with_children(|builder| {
+                    builder.spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(40.), Val::Px(24.)),
+                                border: UiRect::all(Val::Px(1.)),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        },
+                        BorderColor(Color::BLACK),
+                        ColorPickerPreview,
+                    ));
+                    builder
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(90.), Val::Px(24.)),
+                                    margin: UiRect::left(Val::Px(6.)),
+                                    padding: UiRect::horizontal(Val::Px(4.)),
+                                    align_items: AlignItems::Center,
+                                    border: UiRect::all(Val::Px(1.)),
+                                    ..default()
+                                },
+                                background_color: Color::WHITE.into(),
+                                ..default()
+                            },
+                            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+                            ColorPickerButton::Hex,
+                        ))
+                        .with_children(|builder| {
+                            builder.spawn((
+                                TextBundle::from_section(color_to_hex(color), text_style(16.)),
+                                ColorPickerHex,
+                            ));
+                        });
+                    builder
+                        .spawn((
+                            picker_button_bundle(),
+                            ColorPickerButton::Eyedropper,
+                            GenericButton,
+                        ))
+                        .with_children(|builder| {
+                            builder.spawn(TextBundle::from_section(
+                                // colorize icon
+                                "\u{e3b8}",
+                                TextStyle {
+                                    font_size: 18.,
+                                    color: Color::BLACK,
+                                    font: icon_font.clone(),
+                                },
+                            ));
+                        });
+                });
+            if !recent_colors.is_empty() {
+                builder
+                    .spawn(NodeBundle {
+                        style: row_style.clone(),
+                        ..default()
+                    })
+                    .with_children(|builder| {
+                        for color in recent_colors {
+                            builder.spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(22.), Val::Px(22.)),
+                                        margin: UiRect::right(Val::Px(4.)),
+                                        border: UiRect::all(Val::Px(1.)),
+                                        ..default()
+                                    },
+                                    background_color: (*color).into(),
+                                    ..default()
+                                },
+                                BorderColor(Color::BLACK),
+                                RecentColorSwatch { color: *color },
+                            ));
+                        }
+                    });
+            }
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        justify_content: JustifyContent::FlexEnd,
+                        ..row_style
+                    },
+                    ..default()
+                })
+                .with_children(|builder| {
+                    for (label, button) in [
+                        ("Apply", ColorPickerButton::Apply),
+                        ("Close", ColorPickerButton::Close),
+                    ] {
+                        builder
+                            .spawn((picker_button_bundle(), button, GenericButton))
+                            .with_children(|builder| {
+                                builder.spawn(TextBundle::from_section(label, text_style(16.)));
+                            });
+                    }
+                });
+        })
+        .id()
+}
+
+fn picker_button_bundle() -> ButtonBundle {
+    ButtonBundle {
+        style: Style {
+            size: Size::height(Val::Px(24.)),
+            padding: UiRect::horizontal(Val::Px(6.)),
+            margin: UiRect::left(Val::Px(6.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::rgb(224.0 / 255.0, 224.0 / 255.0, 224.0 / 255.0).into(),
+        ..default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hsv_round_trip() {
+        for color in [
+            Color::rgb(1., 0., 0.),
+            Color::rgb(0., 0.5, 1.),
+            Color::rgb(173.0 / 255.0, 216.0 / 255.0, 230.0 / 255.0),
+            Color::rgb(0.2, 0.2, 0.2),
+            Color::BLACK,
+            Color::WHITE,
+        ] {
+            let (hue, saturation, value) = color_to_hsv(color);
+            assert_eq!(
+                color_to_hex(hsv_to_color(hue, saturation, value)),
+                color_to_hex(color)
+            );
+        }
+        assert_eq!(color_to_hsv(Color::rgb(0., 1., 0.)), (120., 1., 1.));
+        assert_eq!(color_to_hex(hsv_to_color(240., 1., 1.)), "#0000FF");
+    }
+}
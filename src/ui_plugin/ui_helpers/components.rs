@@ -1,4 +1,10 @@
-use crate::{ui_plugin::NodeType, utils::ReflectableUuid};
+use crate::{
+    ui_plugin::{
+        Action, BorderStyle, ContextMenuAction, DockedPanel, NodeProperty, NodeShadow, NodeType,
+        PaletteEntry, SettingChange, Template, ThemeColor, Tool,
+    },
+    utils::ReflectableUuid,
+};
 use bevy::prelude::*;
 
 use crate::TextPos;
@@ -41,15 +47,31 @@ pub struct ExportToFile;
 #[derive(Component)]
 pub struct SetWindowProperty;
 
+#[derive(Component)]
+pub struct EncryptDoc;
+
 #[derive(Component)]
 pub struct ImportFromFile;
 
+#[derive(Component)]
+pub struct ImportIntoCurrent;
+
+#[derive(Component)]
+pub struct FileIoStatus;
+
 #[derive(Component)]
 pub struct ImportFromUrl;
 
+#[derive(Component)]
+pub struct InsertImage;
+
 #[derive(Component)]
 pub struct ShareDoc;
 
+/// Starts or stops collaborating on the open document through the configured relay.
+#[derive(Component)]
+pub struct CollabButton;
+
 #[derive(Component)]
 pub struct DeleteDoc {
     pub id: ReflectableUuid,
@@ -64,6 +86,12 @@ pub struct TabContainer {
     pub id: ReflectableUuid,
 }
 
+/// Tabs of the document, scrolled horizontally when there are more than fit in the bottom panel.
+#[derive(Component, Default)]
+pub struct TabBar {
+    pub position: f32,
+}
+
 #[derive(Component)]
 pub struct SearchButton {
     pub id: ReflectableUuid,
@@ -108,12 +136,764 @@ pub struct BottomPanel;
 #[derive(Component)]
 pub struct LeftPanel;
 
+#[derive(Component)]
+pub struct RubberBand;
+
+#[derive(Component)]
+pub struct ToggleGrid;
+
+#[derive(Component)]
+pub struct ToggleTheme;
+
+/// Background color of the entity follows the theme.
+#[derive(Component)]
+pub struct ThemedBackground(pub ThemeColor);
+
+/// Border color of the entity follows the theme.
+#[derive(Component)]
+pub struct ThemedBorder(pub ThemeColor);
+
+/// Text color of the entity follows the theme.
+#[derive(Component)]
+pub struct ThemedText(pub ThemeColor);
+
+#[derive(Component)]
+pub struct ChangeLanguage;
+
+/// Opens or closes the settings panel.
+#[derive(Component)]
+pub struct SettingsButton;
+
+#[derive(Component)]
+pub struct SettingsPanel;
+
+/// Option of the settings panel, applied when clicked.
+#[derive(Component)]
+pub struct SettingsOption(pub SettingChange);
+
+/// Opens or closes the keyboard shortcuts panel.
+#[derive(Component)]
+pub struct KeybindingsButton;
+
+#[derive(Component)]
+pub struct KeybindingsPanel;
+
+/// Chords of the action in the shortcuts panel, a new chord is recorded when clicked.
+#[derive(Component)]
+pub struct KeybindingButton(pub Action);
+
+#[derive(Component)]
+pub struct ResetKeybindings;
+
+/// Text of the entity is `text` translated to the language of the UI, see `Locale`.
+#[derive(Component, Clone, Debug)]
+pub struct Localized {
+    /// English text, `{}` placeholders are replaced by `args`
+    pub text: String,
+    pub args: Vec<String>,
+}
+
+impl Localized {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            args: vec![],
+        }
+    }
+
+    pub fn with_args<T: Into<String>>(mut self, args: impl IntoIterator<Item = T>) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+#[derive(Component)]
+pub struct LayoutMenuButton;
+
+/// Node moving from `from` to `to` (left, bottom) after automatic layout.
+#[derive(Component)]
+pub struct LayoutAnimation {
+    pub from: Vec2,
+    pub to: Vec2,
+    pub elapsed: f32,
+}
+
+#[derive(Component)]
+pub struct Grid;
+
+#[derive(Component)]
+pub struct PenButton;
+
+#[derive(Component)]
+pub struct RectToolButton;
+
+#[derive(Component)]
+pub struct PresentationButton;
+
+#[derive(Component)]
+pub struct CompareButton;
+
+#[derive(Component)]
+pub struct RecentDocsButton;
+
+/// Panel listing the recent documents, shown over the canvas on startup.
+#[derive(Component)]
+pub struct WelcomeScreen;
+
+#[derive(Component)]
+pub struct WelcomeDocButton {
+    pub id: ReflectableUuid,
+}
+
+#[derive(Component)]
+pub struct WelcomeClose;
+
+#[derive(Component)]
+pub struct WelcomeTemplateButton {
+    pub template: Template,
+}
+
+/// Opens or closes the snippet library.
+#[derive(Component)]
+pub struct SnippetsButton;
+
+/// Panel listing the saved snippets.
+#[derive(Component)]
+pub struct SnippetLibrary;
+
+/// Stamps the snippet onto the canvas.
+#[derive(Component)]
+pub struct SnippetButton {
+    pub id: ReflectableUuid,
+}
+
+#[derive(Component)]
+pub struct DeleteSnippetButton {
+    pub id: ReflectableUuid,
+}
+
+/// Prompt to restore the snapshot of a session that didn't exit cleanly.
+#[derive(Component)]
+pub struct RecoveryPrompt;
+
+#[derive(Component)]
+pub struct RecoveryRestore;
+
+#[derive(Component)]
+pub struct RecoveryDiscard;
+
+/// Prompt shown when the file of the document was changed by another program.
+#[derive(Component)]
+pub struct FileChangedPrompt {
+    pub doc_id: ReflectableUuid,
+}
+
+#[derive(Component)]
+pub struct FileReload;
+
+#[derive(Component)]
+pub struct FileKeep;
+
+/// Pushes the open document to the sync remote or pulls it from there.
+#[derive(Component)]
+pub struct SyncDocButton;
+
+/// Prompt shown when the document was changed both locally and on the sync remote.
+#[derive(Component)]
+pub struct SyncConflictPrompt;
+
+#[derive(Component)]
+pub struct SyncKeepMine;
+
+#[derive(Component)]
+pub struct SyncKeepTheirs;
+
+/// Keeps the remote version and the local one as a new document.
+#[derive(Component)]
+pub struct SyncDuplicate;
+
+/// Outline of a node added, modified or deleted since the last save, shown in compare mode.
+#[derive(Component)]
+pub struct CompareHighlight;
+
+/// Node deleted since the last save, shown in compare mode where it was.
+#[derive(Component)]
+pub struct CompareGhost;
+
+/// Position of the node in the presentation path of the tab, starting from 0.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct PresentationStep(pub u32);
+
+/// Number of the node in the presentation path shown in its top left corner.
+#[derive(Component)]
+pub struct PresentationBadge {
+    pub id: ReflectableUuid,
+}
+
+/// Panel with the canvas and the tabs next to the left panel.
+#[derive(Component)]
+pub struct RightPanel;
+
+/// Part of the window below the menu.
+#[derive(Component)]
+pub struct MainBottom;
+
+/// Properties and tags panels in the top right corner of the canvas.
+#[derive(Component)]
+pub struct CanvasPanels;
+
+/// Outline of the node being drawn with the rectangle tool.
+#[derive(Component)]
+pub struct RectToolPreview;
+
+/// Cursor with name tag or selection outline of another collaborator.
+#[derive(Component)]
+pub struct CollaboratorMarker;
+
+/// Freehand stroke drawn with the pen tool, points and width are in world coordinates like node
+/// positions.
+#[derive(Component, Clone)]
+pub struct PenStroke {
+    pub id: ReflectableUuid,
+    pub points: Vec<Vec2>,
+    pub color: Color,
+    pub width: f32,
+}
+
+/// Overview of all nodes in the corner of the canvas.
+///
+/// World point `p` is drawn at `(p - origin) * scale` of the minimap.
+#[derive(Component, Default)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    pub origin: Vec2,
+    pub scale: f32,
+}
+
+#[derive(Component, Clone, Copy)]
+pub struct NodeGroup {
+    pub id: ReflectableUuid,
+}
+
+#[derive(Component)]
+pub struct Locked;
+
+/// Clockwise rotation of the node container in degrees.
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct NodeRotation(pub f32);
+
+/// Handle above the node rotating it while dragged.
+#[derive(Component)]
+pub struct RotationHandle {
+    pub id: ReflectableUuid,
+}
+
+#[derive(Component)]
+pub struct LockBadge;
+
+/// Nodes lying inside of the frame node, they move along with the frame.
+#[derive(Component, Clone, Default, Debug)]
+pub struct FrameMembers(pub Vec<ReflectableUuid>);
+
+/// Frame which contents are hidden.
+#[derive(Component)]
+pub struct FrameCollapsed;
+
+/// Member of a collapsed frame.
+#[derive(Component)]
+pub struct HiddenByFrame;
+
+#[derive(Component)]
+pub struct FrameCollapseButton {
+    pub id: ReflectableUuid,
+}
+
+/// Node which outgoing subtree is hidden.
+#[derive(Component)]
+pub struct CollapsedBranch;
+
+/// Node or arrow of a collapsed subtree.
+#[derive(Component)]
+pub struct HiddenByBranch;
+
+/// Badge of the collapsed node with the hidden nodes count, expands the subtree when clicked.
+#[derive(Component)]
+pub struct BranchBadge {
+    pub id: ReflectableUuid,
+}
+
+/// Right-click menu, `position` is the right-clicked point of the canvas.
+#[derive(Component)]
+pub struct ContextMenu {
+    pub node: Option<ReflectableUuid>,
+    pub position: Vec2,
+}
+
+#[derive(Component)]
+pub struct ContextMenuItem {
+    pub action: ContextMenuAction,
+}
+
+/// Tags of the node, used to filter nodes.
+#[derive(Component, Clone, Default, Debug)]
+pub struct NodeTags(pub Vec<String>);
+
+/// Inspector field editing tags of the inspected node.
+#[derive(Component)]
+pub struct InspectorTagsField;
+
+#[derive(Component)]
+pub struct TagList;
+
+/// Tag list entry, toggles the tag in the tag filter.
+#[derive(Component)]
+pub struct TagFilterButton {
+    pub tag: String,
+}
+
+/// Relationship entry of the tag list, toggles the relationship type in the tag filter.
+#[derive(Component)]
+pub struct RelationFilterButton {
+    pub relation: String,
+}
+
+#[derive(Component)]
+pub struct TagFilterModeButton;
+
+#[derive(Component)]
+pub struct HistoryList;
+
+/// History panel entry, undoes or redoes the active tab until `position` edits are done.
+#[derive(Component)]
+pub struct HistoryEntryButton {
+    pub position: usize,
+}
+
+/// File paths or URLs attached to the node, shown as chips at the bottom of the node.
+#[derive(Component, Clone, Default, Debug)]
+pub struct NodeAttachments(pub Vec<String>);
+
+/// Row of attachment chips of the node.
+#[derive(Component)]
+pub struct AttachmentChips {
+    pub id: ReflectableUuid,
+}
+
+/// Chip opening the attachment, Alt+Click removes it from the node.
+#[derive(Component)]
+pub struct AttachmentChip {
+    pub id: ReflectableUuid,
+    pub attachment: String,
+}
+
+/// Overlay dimming a node that doesn't match the tag filter.
+#[derive(Component)]
+pub struct TagFilterDim;
+
+/// Node of any tab of the current document matching the canvas search.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CanvasSearchResult {
+    pub tab_id: ReflectableUuid,
+    pub node_id: ReflectableUuid,
+    pub tab_name: String,
+    pub preview: String,
+}
+
+/// Search bar opened with Cmd/Ctrl+F, `selected` is the index of the highlighted result.
+#[derive(Component, Default)]
+pub struct CanvasSearch {
+    pub query: String,
+    pub fuzzy: bool,
+    pub results: Vec<CanvasSearchResult>,
+    pub selected: usize,
+}
+
+#[derive(Component)]
+pub struct CanvasSearchInput;
+
+#[derive(Component)]
+pub struct CanvasSearchText;
+
+#[derive(Component)]
+pub struct CanvasSearchModeButton;
+
+#[derive(Component)]
+pub struct CanvasSearchClose;
+
+#[derive(Component)]
+pub struct CanvasSearchResultList;
+
+#[derive(Component)]
+pub struct CanvasSearchResultButton {
+    pub index: usize,
+}
+
+/// Palette opened with Cmd/Ctrl+Shift+P, `results` are the indices of the entries matching the
+/// query and `selected` the index of the highlighted one among them.
+#[derive(Component, Default)]
+pub struct CommandPalette {
+    pub query: String,
+    pub entries: Vec<PaletteEntry>,
+    pub results: Vec<usize>,
+    pub selected: usize,
+}
+
+#[derive(Component)]
+pub struct CommandPaletteInput;
+
+#[derive(Component)]
+pub struct CommandPaletteText;
+
+#[derive(Component)]
+pub struct CommandPaletteResultList;
+
+#[derive(Component)]
+pub struct CommandPaletteResultButton {
+    pub index: usize,
+}
+
+/// Toasts of the notifications, the newest at the bottom.
+#[derive(Component)]
+pub struct ToastList;
+
+/// Toast of a `Notification`, dismissed after `remaining` seconds unless its details are shown.
+#[derive(Component)]
+pub struct Toast {
+    pub remaining: f32,
+    pub expanded: bool,
+}
+
+/// Details of a toast, hidden until the toast is clicked.
+#[derive(Component)]
+pub struct ToastDetails;
+
+/// Part of the status bar at the right of the bottom panel, hidden while it has nothing to show.
+#[derive(Component, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum StatusBarItem {
+    NodeCount,
+    /// Size and position of the dragged, resized, edited or only selected node
+    Selection,
+    Zoom,
+    Autosave,
+    /// Words of the node being edited
+    WordCount,
+}
+
+/// Overlay highlighting a node matching the canvas search.
+#[derive(Component)]
+pub struct SearchHighlight;
+
+/// Left panel button opening the color picker.
+#[derive(Component)]
+pub struct OpenColorPicker;
+
+/// Color picker popup, the picked color is applied to `targets` (nodes selected when it was
+/// opened, so the eyedropper can click other nodes).
+#[derive(Component)]
+pub struct ColorPicker {
+    pub hue: f32,
+    pub saturation: f32,
+    pub value: f32,
+    pub targets: Vec<ReflectableUuid>,
+    pub saturation_value_image: Handle<Image>,
+    pub hue_image: Handle<Image>,
+    pub eyedropper: bool,
+    /// Hex digits typed so far while the hex field is edited.
+    pub hex_input: Option<String>,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum ColorPickerArea {
+    SaturationValue,
+    Hue,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum ColorPickerButton {
+    Hex,
+    Eyedropper,
+    Apply,
+    Close,
+}
+
+#[derive(Component)]
+pub struct ColorPickerPreview;
+
+#[derive(Component)]
+pub struct ColorPickerHex;
+
+#[derive(Component)]
+pub struct RecentColorSwatch {
+    pub color: Color,
+}
+
+/// Header of the inspector panel, collapses/expands it.
+#[derive(Component)]
+pub struct InspectorToggle;
+
+#[derive(Component)]
+pub struct InspectorBody;
+
+#[derive(Component)]
+pub struct InspectorValue {
+    pub property: NodeProperty,
+}
+
+/// Inspector button changing `property` of the inspected node by `step`.
+#[derive(Component)]
+pub struct InspectorStep {
+    pub property: NodeProperty,
+    pub step: f32,
+}
+
+/// Node keeps its width/height ratio while being resized.
+#[derive(Component)]
+pub struct KeepAspectRatio;
+
+/// Texture drawing dashed, dotted or rounded border (and rounded fill) of a node.
+#[derive(Component)]
+pub struct NodeBorderImage;
+
+#[derive(Clone, Copy)]
+pub enum BorderChange {
+    Style(BorderStyle),
+    Width,
+    Radius,
+    Color,
+}
+
+#[derive(Component)]
+pub struct ChangeBorder {
+    pub change: BorderChange,
+}
+
+/// Dot drawn in the middle of an arrow connection point, it grows when the point is hovered.
+#[derive(Component)]
+pub struct ArrowMarkerDot;
+
+/// Label of an arrow shown in the middle of the arrow line.
+#[derive(Component)]
+pub struct ArrowLabelView {
+    pub arrow: Entity,
+}
+
+#[derive(Component)]
+pub struct ArrowLabelEditor {
+    pub arrow: Entity,
+}
+
+#[derive(Clone, Copy)]
+pub enum ArrowStyleChange {
+    StartHead,
+    EndHead,
+    Thickness,
+    Dash,
+    Color,
+    Routing,
+    Animation,
+    Relation,
+}
+
+#[derive(Component)]
+pub struct ChangeArrowStyle {
+    pub change: ArrowStyleChange,
+}
+
+/// Drop shadow, opacity and color of a node. The node sprite is drawn in `color` with `opacity`
+/// applied, so the color is changed here rather than on the sprite.
+#[derive(Component, Clone)]
+pub struct NodeEffects {
+    pub shadow: Option<NodeShadow>,
+    pub opacity: f32,
+    pub color: Color,
+}
+
+impl NodeEffects {
+    pub fn new(shadow: Option<NodeShadow>, opacity: f32, color: Color) -> Self {
+        Self {
+            shadow,
+            opacity: opacity.clamp(0.1, 1.),
+            color,
+        }
+    }
+
+    /// Color the node sprite is drawn in.
+    pub fn sprite_color(&self) -> Color {
+        self.color.with_a(self.color.a() * self.opacity)
+    }
+}
+
+#[derive(Component)]
+pub struct NodeShadowImage;
+
+#[derive(Clone, Copy)]
+pub enum NodeEffect {
+    Shadow,
+    Opacity,
+}
+
+#[derive(Component)]
+pub struct ChangeNodeEffect {
+    pub effect: NodeEffect,
+}
+
+#[derive(Clone, Copy)]
+pub enum TextStyleChange {
+    FontSize,
+    Font,
+    Color,
+}
+
+#[derive(Component)]
+pub struct ChangeTextStyle {
+    pub change: TextStyleChange,
+}
+
 #[derive(Component)]
 pub struct LeftPanelControls;
 
 #[derive(Component)]
 pub struct LeftPanelExplorer;
 
+/// Panel docked in the left panel or in the top right corner of the canvas, see `PanelLayout`.
+#[derive(Component)]
+pub struct DockablePanel(pub DockedPanel);
+
+/// Right edge of the left panel, dragging it resizes the panel.
+#[derive(Component)]
+pub struct LeftPanelEdge;
+
+/// Collapses or expands the left panel.
+#[derive(Component)]
+pub struct LeftPanelChevron;
+
+/// Button of the toolbar on the left of the canvas choosing the tool.
+#[derive(Component)]
+pub struct ToolButton(pub Tool);
+
+/// OS window showing a read-only preview of a tab of the open document next to the main
+/// window.
+#[derive(Component)]
+pub struct TabPreview {
+    pub doc_id: ReflectableUuid,
+    pub tab_id: ReflectableUuid,
+    /// Camera and sprite of the window, on a render layer of their own
+    pub camera: Entity,
+    pub sprite: Entity,
+    /// Checkpoint of the tab the last render was started for
+    pub rendered: Option<String>,
+    /// Increased with each render, results of older renders are dropped
+    pub generation: u64,
+    /// Fits the first render to the window, later ones keep the panned view
+    pub fitted: bool,
+    /// Where a drag panning the view was last, in window coordinates
+    pub drag_cursor: Option<Vec2>,
+}
+
+/// Camera or sprite of a `TabPreview`, despawned once its window is closed.
+#[derive(Component)]
+pub struct TabPreviewView {
+    pub window: Entity,
+}
+
+/// Content of a node culled outside of the view, with the visibility it had before.
+#[derive(Component)]
+pub struct CulledVisibility(pub Visibility);
+
+/// Container of a node culled outside of the view, its parts but the arrow connection markers
+/// aren't laid out until it's shown again.
+#[derive(Component)]
+pub struct Culled;
+
+/// Bounds of a node in world coordinates (y axis pointing up), `position` is its left bottom
+/// corner. Nodes are drawn by the main camera, which pans and zooms over them.
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug)]
+pub struct NodeRect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl NodeRect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position, size }
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::from_corners(self.position, self.position + self.size)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.position + self.size / 2.
+    }
+}
+
+/// Stacking order of a node, nodes with a higher one are drawn over the others.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct NodeZIndex(pub i32);
+
+/// Sprite drawn in or around a node, placed relative to the center of the node at `anchor`
+/// (fractions of the node size) moved by `offset`. Its size is `size` plus `grow` times the
+/// node size, `z` is relative to the node.
+///
+/// `offset` and `size` of screen sized parts (handles, badges) are in logical pixels of the
+/// screen: these parts are scaled so they don't grow or shrink when the canvas is zoomed.
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug)]
+pub struct NodePart {
+    pub anchor: Vec2,
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub grow: Vec2,
+    pub z: f32,
+    pub screen_sized: bool,
+}
+
+impl NodePart {
+    /// Part covering the whole node.
+    pub fn fill(z: f32) -> Self {
+        Self {
+            grow: Vec2::ONE,
+            z,
+            ..default()
+        }
+    }
+
+    /// Handle or badge of `size` (screen pixels) at `anchor` of the node.
+    pub fn handle(anchor: Vec2, offset: Vec2, size: Vec2, z: f32) -> Self {
+        Self {
+            anchor,
+            offset,
+            size,
+            z,
+            screen_sized: true,
+            ..default()
+        }
+    }
+}
+
+/// Outline drawn around the sprite of a canvas entity (nodes, rubber band, collaborators),
+/// `thickness` is in logical pixels of the screen whatever the canvas zoom.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct CanvasOutline {
+    pub color: Color,
+    pub thickness: f32,
+}
+
+impl CanvasOutline {
+    pub fn new(color: Color, thickness: f32) -> Self {
+        Self { color, thickness }
+    }
+}
+
+/// Shape drawing the `CanvasOutline` of its parent.
+#[derive(Component)]
+pub struct CanvasOutlineShape;
+
+/// Canvas sprite (chip, badge) sized to fit its `Text2d` child with `padding` on each side.
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug)]
+pub struct FitToText {
+    pub padding: Vec2,
+}
+
 #[derive(Component, Default, Reflect, Debug)]
 #[reflect(Component)]
 pub struct VeloNodeContainer {
@@ -131,9 +911,12 @@ pub struct VeloNode {
 pub enum ButtonTypes {
     AddRec,
     AddCircle,
+    AddShape(NodeType),
     Del,
     Front,
     Back,
+    ToFront,
+    ToBack,
 }
 #[derive(Component)]
 pub struct ButtonAction {
@@ -166,6 +949,10 @@ pub enum ResizeMarker {
     TopRight,
     BottomLeft,
     BottomRight,
+    Top,
+    Bottom,
+    Left,
+    Right,
 }
 
 #[derive(Component)]
@@ -181,6 +968,10 @@ pub enum ModalAction {
     LoadFromUrl,
     DeleteDocument,
     DeleteTab,
+    InsertImage,
+    ArrowRelation,
+    SetPassphrase,
+    EnterPassphrase { wrong_passphrase: bool },
 }
 
 impl std::fmt::Display for ModalAction {
@@ -191,6 +982,15 @@ impl std::fmt::Display for ModalAction {
             ModalAction::LoadFromFile => write!(f, "Load from file:"),
             ModalAction::LoadFromUrl => write!(f, "Load from URL:"),
             ModalAction::SaveToFile => write!(f, "Save to file:"),
+            ModalAction::InsertImage => write!(f, "Insert image from file:"),
+            ModalAction::ArrowRelation => write!(f, "Relationship of new arrows:"),
+            ModalAction::SetPassphrase => write!(f, "Encrypt with passphrase (empty to decrypt):"),
+            ModalAction::EnterPassphrase {
+                wrong_passphrase: false,
+            } => write!(f, "Passphrase of the document:"),
+            ModalAction::EnterPassphrase {
+                wrong_passphrase: true,
+            } => write!(f, "Wrong passphrase, try again:"),
         }
     }
 }
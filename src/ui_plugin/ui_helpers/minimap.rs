@@ -0,0 +1,46 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+/// Draws minimap of `size` pixels: filled `nodes` rectangles and outline of the `viewport`.
+///
+/// Rectangles are in minimap pixels with origin in the left bottom corner.
+pub fn rasterize_minimap(size: UVec2, nodes: &[(Rect, Color)], viewport: Rect) -> Image {
+    let (width, height) = (size.x.max(1), size.y.max(1));
+    let mut data = vec![0; (width * height * 4) as usize];
+    let mut fill = |rect: Rect, color: Color| {
+        let [r, g, b, a] = color.as_rgba_u8();
+        let min = rect.min.max(Vec2::ZERO).floor().as_uvec2();
+        let max = rect.max.ceil().as_uvec2().min(UVec2::new(width, height));
+        for y in min.y..max.y {
+            // image rows go from top to bottom
+            let row = height - 1 - y;
+            for x in min.x..max.x {
+                let i = ((row * width + x) * 4) as usize;
+                data[i..i + 4].copy_from_slice(&[r, g, b, a]);
+            }
+        }
+    };
+    for (rect, color) in nodes {
+        // tiny nodes are still visible
+        let rect = Rect::from_center_size(rect.center(), rect.size().max(Vec2::ONE));
+        fill(rect, *color);
+    }
+    let outline = Color::rgb(33.0 / 255.0, 150.0 / 255.0, 243.0 / 255.0);
+    let Rect { min, max } = viewport;
+    fill(Rect::new(min.x, min.y, max.x, min.y + 1.), outline);
+    fill(Rect::new(min.x, max.y - 1., max.x, max.y), outline);
+    fill(Rect::new(min.x, min.y, min.x + 1., max.y), outline);
+    fill(Rect::new(max.x - 1., min.y, max.x, max.y), outline);
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
@@ -0,0 +1,37 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use super::rounded_rect_distance;
+
+/// White shadow of rounded box of `size` with soft edges `blur` pixels wide, tinted by node color.
+///
+/// The texture is `blur` pixels larger than the box on each side.
+pub fn rasterize_shadow(size: Vec2, blur: f32, radius: f32) -> Image {
+    let extent = size + Vec2::splat(2. * blur);
+    let width = extent.x.ceil().max(1.) as u32;
+    let height = extent.y.ceil().max(1.) as u32;
+    let half = size / 2.;
+    let radius = radius.min(half.x).min(half.y).max(0.);
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - extent / 2.;
+            let distance = rounded_rect_distance(p, half, radius);
+            let t = (0.5 - distance / blur.max(1.)).clamp(0., 1.);
+            let alpha = t * t * (3. - 2. * t);
+            data.extend_from_slice(&[255, 255, 255, (255. * alpha) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
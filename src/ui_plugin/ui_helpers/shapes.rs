@@ -0,0 +1,159 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::ui_plugin::NodeType;
+
+const SHAPE_IMAGE_SIZE: u32 = 128;
+// Samples per pixel side used for anti-aliasing
+const SHAPE_IMAGE_SAMPLES: u32 = 4;
+// Height of cylinder caps relative to node height
+const CYLINDER_CAP: f32 = 0.12;
+// Horizontal skew of parallelogram relative to node width
+const PARALLELOGRAM_SKEW: f32 = 0.2;
+
+pub const ELLIPSE_IMAGE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x7e10_5ba9_0000_0001);
+pub const DIAMOND_IMAGE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x7e10_5ba9_0000_0002);
+pub const HEXAGON_IMAGE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x7e10_5ba9_0000_0003);
+pub const PARALLELOGRAM_IMAGE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x7e10_5ba9_0000_0004);
+pub const CYLINDER_IMAGE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x7e10_5ba9_0000_0005);
+
+pub const SHAPE_NODE_TYPES: [NodeType; 5] = [
+    NodeType::Ellipse,
+    NodeType::Diamond,
+    NodeType::Hexagon,
+    NodeType::Parallelogram,
+    NodeType::Cylinder,
+];
+
+/// Returns the generated texture used to draw a shape node, `None` for other node types.
+pub fn shape_image(node_type: &NodeType) -> Option<Handle<Image>> {
+    let handle = match node_type {
+        NodeType::Ellipse => ELLIPSE_IMAGE_HANDLE,
+        NodeType::Diamond => DIAMOND_IMAGE_HANDLE,
+        NodeType::Hexagon => HEXAGON_IMAGE_HANDLE,
+        NodeType::Parallelogram => PARALLELOGRAM_IMAGE_HANDLE,
+        NodeType::Cylinder => CYLINDER_IMAGE_HANDLE,
+        NodeType::Rect
+        | NodeType::Circle
+        | NodeType::Sticky
+        | NodeType::Image
+        | NodeType::Frame => return None,
+    };
+    Some(handle.typed::<Image>())
+}
+
+/// Texture of circle and shape nodes, `None` for nodes drawn as plain boxes.
+pub fn node_shape_image(node_type: &NodeType, asset_server: &AssetServer) -> Option<Handle<Image>> {
+    match node_type {
+        NodeType::Circle => {
+            #[cfg(not(target_arch = "wasm32"))]
+            let image = Some(asset_server.load("circle-node.basis"));
+            #[cfg(target_arch = "wasm32")]
+            let image = Some(asset_server.load("circle-node.png"));
+            image
+        }
+        _ => shape_image(node_type),
+    }
+}
+
+/// Arrow connection markers (top, left, bottom, right) relative to the node center, as
+/// fractions of node size (y axis pointing up).
+pub fn arrow_marker_anchors(node_type: &NodeType) -> [Vec2; 4] {
+    let skew = match node_type {
+        NodeType::Parallelogram => PARALLELOGRAM_SKEW / 2.,
+        _ => 0.,
+    };
+    [
+        Vec2::new(skew, 0.5),
+        Vec2::new(-0.5 + skew, 0.),
+        Vec2::new(-skew, -0.5),
+        Vec2::new(0.5 - skew, 0.),
+    ]
+}
+
+// u, v are normalized coordinates with origin in the top left corner
+fn is_inside(node_type: &NodeType, u: f32, v: f32) -> bool {
+    let (dx, dy) = ((u - 0.5).abs(), (v - 0.5).abs());
+    match node_type {
+        NodeType::Ellipse => dx * dx + dy * dy <= 0.25,
+        NodeType::Diamond => dx + dy <= 0.5,
+        NodeType::Hexagon => dx <= 0.25 + 0.5 * (0.5 - dy),
+        NodeType::Parallelogram => {
+            u >= PARALLELOGRAM_SKEW * (1. - v) && u <= 1. - PARALLELOGRAM_SKEW * v
+        }
+        NodeType::Cylinder => {
+            let cap = |center: f32| {
+                let (x, y) = ((u - 0.5) / 0.5, (v - center) / CYLINDER_CAP);
+                x * x + y * y <= 1.
+            };
+            (v >= CYLINDER_CAP && v <= 1. - CYLINDER_CAP)
+                || cap(CYLINDER_CAP)
+                || cap(1. - CYLINDER_CAP)
+        }
+        NodeType::Rect
+        | NodeType::Circle
+        | NodeType::Sticky
+        | NodeType::Image
+        | NodeType::Frame => true,
+    }
+}
+
+// visible rim of the cylinder top cap
+fn is_rim(node_type: &NodeType, u: f32, v: f32) -> bool {
+    if *node_type != NodeType::Cylinder || v <= CYLINDER_CAP {
+        return false;
+    }
+    let (x, y) = ((u - 0.5) / 0.5, (v - CYLINDER_CAP) / CYLINDER_CAP);
+    ((x * x + y * y).sqrt() - 1.).abs() < 0.1
+}
+
+fn rasterize_shape(node_type: &NodeType) -> Image {
+    let size = SHAPE_IMAGE_SIZE;
+    let samples = SHAPE_IMAGE_SAMPLES;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (mut inside, mut rim) = (0, 0);
+            for sy in 0..samples {
+                for sx in 0..samples {
+                    let u = (x as f32 + (sx as f32 + 0.5) / samples as f32) / size as f32;
+                    let v = (y as f32 + (sy as f32 + 0.5) / samples as f32) / size as f32;
+                    if is_inside(node_type, u, v) {
+                        inside += 1;
+                        if is_rim(node_type, u, v) {
+                            rim += 1;
+                        }
+                    }
+                }
+            }
+            let total = (samples * samples) as f32;
+            let shade = (255. * (1. - 0.4 * rim as f32 / total)) as u8;
+            data.extend_from_slice(&[shade, shade, shade, (255. * inside as f32 / total) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn init_shape_images(mut images: ResMut<Assets<Image>>) {
+    for node_type in SHAPE_NODE_TYPES.iter() {
+        let handle = shape_image(node_type).unwrap();
+        images.set_untracked(handle, rasterize_shape(node_type));
+    }
+}
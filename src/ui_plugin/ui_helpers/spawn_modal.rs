@@ -1,14 +1,15 @@
 use bevy_cosmic_edit::{spawn_cosmic_edit, ActiveEditor, CosmicEditMeta, FontSystemState};
 use bevy_ui_borders::BorderColor;
+use cosmic_text::Attrs;
 
 use bevy::prelude::*;
 
 use super::{
-    add_rectangle_txt, EditableText, GenericButton, ModalAction, ModalCancel, ModalConfirm,
-    ModalTop,
+    add_rectangle_txt, EditableText, GenericButton, Localized, ModalAction, ModalCancel,
+    ModalConfirm, ModalTop, ThemedBackground, ThemedText,
 };
 use crate::{
-    ui_plugin::TextPos,
+    ui_plugin::{TextPos, ThemeColor},
     utils::{to_cosmic_text_pos, ReflectableUuid},
 };
 
@@ -25,6 +26,7 @@ pub fn spawn_modal(
         ModalAction::SaveToFile => "./velo.json".to_string(),
         ModalAction::LoadFromFile => "./velo.json".to_string(),
         ModalAction::LoadFromUrl => "https://gist..".to_string(),
+        ModalAction::InsertImage => "./image.png".to_string(),
         _ => "".to_string(),
     };
     let top = commands
@@ -95,12 +97,14 @@ pub fn spawn_modal(
                 ..default()
             };
 
-            builder.spawn(
-                TextBundle::from_section(" Ok ", text_style).with_style(Style {
+            builder.spawn((
+                TextBundle::from_section("Ok", text_style).with_style(Style {
                     position_type: PositionType::Relative,
+                    margin: UiRect::horizontal(Val::Px(5.)),
                     ..default()
                 }),
-            );
+                Localized::new("Ok"),
+            ));
         })
         .id();
     let cancel_button = commands
@@ -127,19 +131,26 @@ pub fn spawn_modal(
                 ..default()
             };
 
-            builder.spawn(
+            builder.spawn((
                 TextBundle::from_section("Cancel", text_style).with_style(Style {
                     position_type: PositionType::Relative,
                     ..default()
                 }),
-            );
+                Localized::new("Cancel"),
+            ));
         })
         .id();
     commands.entity(modal_static).add_child(ok_button);
     commands.entity(modal_static).add_child(cancel_button);
 
     let modal_dynamic = match modal_action {
-        ModalAction::SaveToFile | ModalAction::LoadFromFile | ModalAction::LoadFromUrl => {
+        ModalAction::SaveToFile
+        | ModalAction::LoadFromFile
+        | ModalAction::LoadFromUrl
+        | ModalAction::InsertImage
+        | ModalAction::ArrowRelation
+        | ModalAction::SetPassphrase
+        | ModalAction::EnterPassphrase { .. } => {
             let top = commands
                 .spawn(NodeBundle {
                     style: Style {
@@ -169,7 +180,11 @@ pub fn spawn_modal(
                     ..default()
                 })
                 .with_children(|builder| {
-                    builder.spawn(add_rectangle_txt(modal_action.to_string()));
+                    builder.spawn((
+                        add_rectangle_txt(modal_action.to_string()),
+                        ThemedText(ThemeColor::Text),
+                        Localized::new(modal_action.to_string()),
+                    ));
                 })
                 .id();
             let width = 180.;
@@ -204,8 +219,14 @@ pub fn spawn_modal(
                 initial_size: Some((width, height)),
                 font_size: 14.,
                 line_height: 18.,
+                attrs: Attrs::new(),
                 scale_factor: window.scale_factor() as f32,
                 font_system: font_system.font_system.as_mut().unwrap(),
+                paste: true,
+                mask_char: match modal_action {
+                    ModalAction::SetPassphrase | ModalAction::EnterPassphrase { .. } => Some('*'),
+                    _ => None,
+                },
                 is_visible: true,
             };
             let cosmic_edit = spawn_cosmic_edit(commands, cosmic_edit_meta);
@@ -244,10 +265,11 @@ pub fn spawn_modal(
                 })
                 .id();
             let node_label = commands
-                .spawn(add_rectangle_txt(format!(
-                    "Are you sure you want to {}?",
-                    modal_action
-                )))
+                .spawn((
+                    add_rectangle_txt(format!("Are you sure you want to {}?", modal_action)),
+                    ThemedText(ThemeColor::Text),
+                    Localized::new(format!("Are you sure you want to {}?", modal_action)),
+                ))
                 .id();
             commands.entity(node).add_child(node_label);
             commands.entity(top).add_child(node);
@@ -276,6 +298,7 @@ pub fn spawn_modal(
                 ..default()
             },
             BorderColor(Color::BLACK),
+            ThemedBackground(ThemeColor::Surface),
         ))
         .id();
     commands.entity(modal).add_child(modal_dynamic);
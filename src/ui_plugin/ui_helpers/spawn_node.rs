@@ -1,32 +1,50 @@
-use bevy_cosmic_edit::{spawn_cosmic_edit, ActiveEditor, CosmicEditMeta, FontSystemState};
-use bevy_markdown::{spawn_bevy_markdown, BevyMarkdown};
-use bevy_ui_borders::{BorderColor, Outline};
+use bevy_cosmic_edit::{spawn_cosmic_edit_sprite, ActiveEditor, CosmicEditMeta, FontSystemState};
+use bevy_markdown::spawn_bevy_markdown;
 
 use bevy::prelude::*;
 
-use crate::ui_plugin::NodeType;
+use crate::ui_plugin::{NodeBorder, NodeShadow, NodeTextStyle, NodeType, ThemeColor};
 use crate::TextPos;
 
 use super::{
-    create_arrow_marker, create_rectangle_btn, create_resize_marker, BevyMarkdownView, RawText,
-    ResizeMarker, VeloNode, VeloNodeContainer,
+    arrow_marker_anchors, arrow_marker_dot, cosmic_attrs, create_arrow_marker,
+    create_edge_resize_marker, create_rectangle_btn, create_resize_marker, editor_line_height,
+    has_border, markdown_view, node_outline, node_shape_image, BevyMarkdownView, CanvasOutline,
+    FrameCollapseButton, FrameMembers, KeepAspectRatio, Locked, NodeAttachments, NodeEffects,
+    NodePart, NodeRect, NodeRotation, NodeTags, NodeZIndex, RawText, ResizeMarker, RotationHandle,
+    ThemedBackground, ThemedText, VeloNode, VeloNodeContainer, ROTATION_HANDLE_SIZE,
 };
 use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos};
-use crate::utils::{convert_from_val_px, to_cosmic_text_pos, ReflectableUuid};
+use crate::utils::{to_cosmic_text_pos, ReflectableUuid};
 
+// The editor is drawn over the node like its markdown view
+const EDITOR_Z: f32 = 0.3;
+// Buttons on the node are drawn over its handles
+const NODE_BUTTON_Z: f32 = 0.7;
+
+/// Node to spawn, `position` (left bottom corner) and `size` are in world coordinates.
 #[derive(Clone)]
 pub struct NodeMeta {
     pub id: ReflectableUuid,
     pub node_type: NodeType,
-    pub size: (Val, Val),
-    pub position: (Val, Val),
+    pub size: Vec2,
+    pub position: Vec2,
     pub text: String,
     pub bg_color: Color,
-    pub image: Option<UiImage>,
+    pub image: Option<Handle<Image>>,
     pub text_pos: TextPos,
+    pub text_style: NodeTextStyle,
     pub z_index: i32,
+    pub locked: bool,
+    pub border: NodeBorder,
+    pub shadow: Option<NodeShadow>,
+    pub opacity: f32,
+    pub tags: Vec<String>,
+    pub attachments: Vec<String>,
+    pub rotation: f32,
     pub is_active: bool,
     pub scale_factor: f32,
+    pub zoom: f32,
 }
 
 pub fn spawn_node(
@@ -35,146 +53,177 @@ pub fn spawn_node(
     font_system: &mut ResMut<FontSystemState>,
     item_meta: NodeMeta,
 ) -> Entity {
+    let rect = NodeRect::new(item_meta.position, item_meta.size);
     let top = commands
         .spawn((
-            NodeBundle {
-                style: Style {
-                    flex_direction: FlexDirection::Column,
-                    align_self: AlignSelf::Stretch,
-                    position_type: PositionType::Absolute,
-                    position: UiRect {
-                        left: item_meta.position.0,
-                        bottom: item_meta.position.1,
-                        ..Default::default()
-                    },
-                    size: Size::new(item_meta.size.0, item_meta.size.1),
-                    ..default()
-                },
-                // background_color: Color::BLACK.with_a(0.5).into(),
-                ..default()
-            },
+            SpatialBundle::from_transform(
+                Transform::from_translation(rect.center().extend(0.))
+                    .with_rotation(Quat::from_rotation_z(-item_meta.rotation.to_radians())),
+            ),
+            rect,
+            NodeZIndex(item_meta.z_index),
             VeloNodeContainer { id: item_meta.id },
         ))
         .id();
     let image = match item_meta.node_type {
-        NodeType::Rect => item_meta.image,
-        NodeType::Circle => {
-            #[cfg(not(target_arch = "wasm32"))]
-            let image = Some(asset_server.load("circle-node.basis").into());
-            #[cfg(target_arch = "wasm32")]
-            let image = Some(asset_server.load("circle-node.png").into());
-            image
-        }
+        NodeType::Rect | NodeType::Image => item_meta.image,
+        _ => node_shape_image(&item_meta.node_type, asset_server),
     };
     let button = commands
         .spawn((
-            create_rectangle_btn(
-                item_meta.bg_color,
-                image.clone(),
-                item_meta.z_index,
-                item_meta.text_pos.clone(),
-            ),
+            create_rectangle_btn(item_meta.bg_color, image, item_meta.text_pos.clone()),
             VeloNode {
                 id: item_meta.id,
                 node_type: item_meta.node_type.clone(),
             },
         ))
         .id();
-    let outline_color = match item_meta.node_type {
-        NodeType::Rect => Color::rgb(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0),
-        NodeType::Circle => Color::rgba(158.0 / 255.0, 157.0 / 255.0, 36.0 / 255.0, 0.),
-    };
-    commands
-        .entity(button)
-        .insert(Outline::all(outline_color, Val::Px(1.)));
-    let arrow_marker1 = commands
-        .spawn((
-            create_arrow_marker(50.0, 0., 0., 0.),
-            BorderColor(Color::BLUE.with_a(0.5)),
-            ArrowConnect {
-                pos: ArrowConnectPos::Top,
-                id: item_meta.id,
-            },
-        ))
-        .id();
-    let arrow_marker2 = commands
-        .spawn((
-            create_arrow_marker(0., 0., 50., 0.),
-            BorderColor(Color::BLUE.with_a(0.5)),
-            ArrowConnect {
-                pos: ArrowConnectPos::Left,
-                id: item_meta.id,
-            },
-        ))
-        .id();
-    let arrow_marker3 = commands
-        .spawn((
-            create_arrow_marker(50., 0., 100., 0.),
-            BorderColor(Color::BLUE.with_a(0.5)),
-            ArrowConnect {
-                pos: ArrowConnectPos::Bottom,
-                id: item_meta.id,
-            },
-        ))
-        .id();
-    let arrow_marker4 = commands
+    commands.entity(button).insert((
+        node_outline(
+            &item_meta.node_type,
+            Some(&item_meta.border),
+            item_meta.opacity,
+        ),
+        NodeEffects::new(item_meta.shadow, item_meta.opacity, item_meta.bg_color),
+        item_meta.text_style.clone(),
+        NodeTags(item_meta.tags.clone()),
+        NodeAttachments(item_meta.attachments.clone()),
+    ));
+    if has_border(&item_meta.node_type) {
+        commands.entity(button).insert(item_meta.border.clone());
+    }
+    let connect_positions = [
+        ArrowConnectPos::Top,
+        ArrowConnectPos::Left,
+        ArrowConnectPos::Bottom,
+        ArrowConnectPos::Right,
+    ];
+    for (pos, anchor) in connect_positions
+        .into_iter()
+        .zip(arrow_marker_anchors(&item_meta.node_type))
+    {
+        let arrow_marker = commands
+            .spawn((
+                create_arrow_marker(anchor),
+                ArrowConnect {
+                    pos,
+                    id: item_meta.id,
+                },
+            ))
+            .with_children(arrow_marker_dot)
+            .id();
+        commands.entity(button).add_child(arrow_marker);
+    }
+    for (marker, anchor) in [
+        (ResizeMarker::TopLeft, Vec2::new(-0.5, 0.5)),
+        (ResizeMarker::TopRight, Vec2::new(0.5, 0.5)),
+        (ResizeMarker::BottomRight, Vec2::new(0.5, -0.5)),
+        (ResizeMarker::BottomLeft, Vec2::new(-0.5, -0.5)),
+    ] {
+        let resize_marker = commands.spawn((create_resize_marker(anchor), marker)).id();
+        commands.entity(button).add_child(resize_marker);
+    }
+    for (marker, anchor) in [
+        (ResizeMarker::Top, Vec2::new(0., 0.5)),
+        (ResizeMarker::Bottom, Vec2::new(0., -0.5)),
+        (ResizeMarker::Left, Vec2::new(-0.5, 0.)),
+        (ResizeMarker::Right, Vec2::new(0.5, 0.)),
+    ] {
+        let edge_marker = commands
+            .spawn((create_edge_resize_marker(anchor), marker))
+            .id();
+        commands.entity(button).add_child(edge_marker);
+    }
+    let rotation_handle = commands
         .spawn((
-            create_arrow_marker(100., 0., 50., 0.),
-            BorderColor(Color::BLUE.with_a(0.5)),
-            ArrowConnect {
-                pos: ArrowConnectPos::Right,
-                id: item_meta.id,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE,
+                    ..default()
+                },
+                // shown for selected nodes only
+                visibility: Visibility::Hidden,
+                ..default()
             },
+            NodePart::handle(
+                Vec2::new(0., 0.5),
+                Vec2::new(0., 17.),
+                Vec2::splat(ROTATION_HANDLE_SIZE),
+                NODE_BUTTON_Z,
+            ),
+            Interaction::default(),
+            CanvasOutline::new(Color::BLUE.with_a(0.5), 1.),
+            ThemedBackground(ThemeColor::Surface),
+            RotationHandle { id: item_meta.id },
         ))
         .id();
-    let resize_marker1 = commands
-        .spawn((create_resize_marker(0., 0., 0., 0.), ResizeMarker::TopLeft))
-        .id();
-    let resize_marker2 = commands
-        .spawn((
-            create_resize_marker(100., 0., 0., 0.),
-            ResizeMarker::TopRight,
-        ))
-        .id();
-    let resize_marker3 = commands
-        .spawn((
-            create_resize_marker(100., 0., 100., 0.),
-            ResizeMarker::BottomRight,
-        ))
-        .id();
-    let resize_marker4 = commands
-        .spawn((
-            create_resize_marker(0., 0., 100., 0.),
-            ResizeMarker::BottomLeft,
-        ))
-        .id();
-    commands.entity(button).add_child(arrow_marker1);
-    commands.entity(button).add_child(arrow_marker2);
-    commands.entity(button).add_child(arrow_marker3);
-    commands.entity(button).add_child(arrow_marker4);
-    commands.entity(button).add_child(resize_marker1);
-    commands.entity(button).add_child(resize_marker2);
-    commands.entity(button).add_child(resize_marker3);
-    commands.entity(button).add_child(resize_marker4);
+    commands.entity(button).add_child(rotation_handle);
+    if item_meta.node_type == NodeType::Frame {
+        let collapse_button = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                    ..default()
+                },
+                NodePart::handle(
+                    Vec2::new(0.5, 0.5),
+                    Vec2::new(-15., -15.),
+                    Vec2::splat(18.),
+                    NODE_BUTTON_Z,
+                ),
+                Interaction::default(),
+                CanvasOutline::new(Color::GRAY, 1.),
+                ThemedBackground(ThemeColor::Surface),
+                FrameCollapseButton { id: item_meta.id },
+            ))
+            .with_children(|builder| {
+                builder.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            "-",
+                            TextStyle {
+                                font_size: 14.,
+                                color: Color::BLACK,
+                                ..default()
+                            },
+                        ),
+                        transform: Transform::from_xyz(0., 0., 0.01),
+                        ..default()
+                    },
+                    ThemedText(ThemeColor::Text),
+                ));
+            })
+            .id();
+        commands.entity(button).add_child(collapse_button);
+    }
 
     let cosmic_edit_meta = CosmicEditMeta {
         text: item_meta.text.clone(),
-        font_size: 14.,
-        line_height: 18.,
+        font_size: item_meta.text_style.font_size * item_meta.zoom,
+        line_height: editor_line_height(&item_meta.text_style) * item_meta.zoom,
+        attrs: cosmic_attrs(&item_meta.text_style),
         scale_factor: item_meta.scale_factor,
         font_system: font_system.font_system.as_mut().unwrap(),
+        paste: true,
+        mask_char: None,
         is_visible: false,
-        initial_background: image,
-        initial_size: Some((
-            convert_from_val_px(item_meta.size.0),
-            convert_from_val_px(item_meta.size.1),
-        )),
+        // the editor is drawn over the node sprite
+        initial_background: None,
+        initial_size: None,
         text_pos: to_cosmic_text_pos(item_meta.text_pos),
     };
-    let cosmic_edit = spawn_cosmic_edit(commands, cosmic_edit_meta);
+    let cosmic_edit = spawn_cosmic_edit_sprite(
+        commands,
+        cosmic_edit_meta,
+        item_meta.size * item_meta.zoom,
+        item_meta.size,
+    );
     commands
         .entity(cosmic_edit)
-        .insert(RawText { id: item_meta.id });
+        .insert((RawText { id: item_meta.id }, NodePart::fill(EDITOR_Z)));
     commands.entity(button).add_child(cosmic_edit);
 
     match item_meta.is_active {
@@ -184,18 +233,13 @@ pub fn spawn_node(
             });
         }
         false => {
-            let bevy_markdown = BevyMarkdown {
-                text: item_meta.text.clone(),
-                regular_font: Some(TextStyle::default().font),
-                code_font: Some(TextStyle::default().font),
-                bold_font: Some(asset_server.load("fonts/SourceCodePro-Bold.ttf")),
-                italic_font: Some(asset_server.load("fonts/SourceCodePro-Italic.ttf")),
-                extra_bold_font: Some(asset_server.load("fonts/SourceCodePro-ExtraBold.ttf")),
-                semi_bold_italic_font: Some(
-                    asset_server.load("fonts/SourceCodePro-SemiBoldItalic.ttf"),
-                ),
-                size: Some(item_meta.size),
-            };
+            let bevy_markdown = markdown_view(
+                item_meta.text.clone(),
+                &item_meta.text_style,
+                item_meta.size.x,
+                item_meta.zoom,
+                asset_server,
+            );
             let markdown_text = spawn_bevy_markdown(commands, bevy_markdown)
                 .expect("should handle markdown convertion");
             commands
@@ -206,5 +250,17 @@ pub fn spawn_node(
         }
     }
     commands.entity(top).add_child(button);
+    if item_meta.node_type == NodeType::Image {
+        commands.entity(top).insert(KeepAspectRatio);
+    }
+    if item_meta.node_type == NodeType::Frame {
+        commands.entity(top).insert(FrameMembers::default());
+    }
+    if item_meta.locked {
+        commands.entity(top).insert(Locked);
+    }
+    commands
+        .entity(top)
+        .insert(NodeRotation(item_meta.rotation));
     top
 }
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy_ui_borders::BorderColor;
+
+use super::{Localized, ThemedBackground, ThemedBorder, ThemedText};
+use crate::ui_plugin::ThemeColor;
+
+fn spawn_prompt_button(builder: &mut ChildBuilder, label: &str, bundle: impl Bundle, width: f32) {
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(width), Val::Px(30.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            },
+            ThemedBackground(ThemeColor::Surface),
+            bundle,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 16.,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ),
+                ThemedText(ThemeColor::Text),
+                Localized::new(label),
+            ));
+        });
+}
+
+/// Non-modal question shown at the top of the canvas with two answer buttons, the message and
+/// the answers are translated.
+pub fn spawn_prompt(
+    commands: &mut Commands,
+    main_panel: Entity,
+    message: Localized,
+    prompt: impl Component,
+    confirm: (&str, impl Bundle),
+    cancel: (&str, impl Bundle),
+) -> Entity {
+    spawn_prompt_node(commands, main_panel, message, prompt, |builder| {
+        spawn_prompt_button(builder, confirm.0, confirm.1, 50.);
+        spawn_prompt_button(builder, cancel.0, cancel.1, 50.);
+    })
+}
+
+/// Prompt with a third answer between the two of `spawn_prompt`.
+pub fn spawn_prompt_with_alternative(
+    commands: &mut Commands,
+    main_panel: Entity,
+    message: Localized,
+    prompt: impl Component,
+    confirm: (&str, impl Bundle),
+    alternative: (&str, impl Bundle),
+    cancel: (&str, impl Bundle),
+) -> Entity {
+    spawn_prompt_node(commands, main_panel, message, prompt, |builder| {
+        let width = 100. / 3.;
+        spawn_prompt_button(builder, confirm.0, confirm.1, width);
+        spawn_prompt_button(builder, alternative.0, alternative.1, width);
+        spawn_prompt_button(builder, cancel.0, cancel.1, width);
+    })
+}
+
+fn spawn_prompt_node(
+    commands: &mut Commands,
+    main_panel: Entity,
+    message: Localized,
+    prompt: impl Component,
+    buttons: impl FnOnce(&mut ChildBuilder),
+) -> Entity {
+    let prompt = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(35.),
+                        top: Val::Percent(5.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(30.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(10.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                // above the welcome screen
+                z_index: ZIndex::Global(3),
+                ..default()
+            },
+            BorderColor(Color::rgb(200.0 / 255.0, 200.0 / 255.0, 200.0 / 255.0)),
+            ThemedBackground(ThemeColor::Surface),
+            ThemedBorder(ThemeColor::Border),
+            prompt,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    message.text.clone(),
+                    TextStyle {
+                        font_size: 16.,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(10.)),
+                    max_size: Size::new(Val::Percent(100.), Val::Undefined),
+                    ..default()
+                }),
+                ThemedText(ThemeColor::Text),
+                message,
+            ));
+            builder.spawn(NodeBundle::default()).with_children(buttons);
+        })
+        .id();
+    commands.entity(main_panel).add_child(prompt);
+    prompt
+}
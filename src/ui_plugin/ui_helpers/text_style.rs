@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use bevy_markdown::BevyMarkdown;
+use cosmic_text::{Attrs, Weight};
+
+use crate::ui_plugin::{internal_links_markdown, NodeFont, NodeTextStyle};
+
+// Line height of the editor relative to font size
+const LINE_HEIGHT_FACTOR: f32 = 1.3;
+
+/// Regular face of markdown text, bold and italic markup keep their own fonts.
+pub fn node_font(font: NodeFont, asset_server: &AssetServer) -> Handle<Font> {
+    match font {
+        // default bevy font is replaced with Source Code Pro Regular
+        NodeFont::Regular => TextStyle::default().font,
+        NodeFont::Bold => asset_server.load("fonts/SourceCodePro-Bold.ttf"),
+        NodeFont::Italic => asset_server.load("fonts/SourceCodePro-Italic.ttf"),
+    }
+}
+
+pub fn editor_line_height(style: &NodeTextStyle) -> f32 {
+    style.font_size * LINE_HEIGHT_FACTOR
+}
+
+/// Attributes of editor text, faces are picked from fonts loaded into cosmic text.
+pub fn cosmic_attrs(style: &NodeTextStyle) -> Attrs<'static> {
+    let [r, g, b, a] = style.color.as_rgba_u8();
+    let attrs = Attrs::new().color(cosmic_text::Color::rgba(r, g, b, a));
+    match style.font {
+        NodeFont::Regular => attrs,
+        NodeFont::Bold => attrs.weight(Weight::BOLD),
+        NodeFont::Italic => attrs.style(cosmic_text::Style::Italic),
+    }
+}
+
+/// Markdown view of node text of `width` (world), font size and width are scaled by canvas zoom
+/// (the view is scaled back down) and internal links are turned into markdown links.
+pub fn markdown_view(
+    text: String,
+    style: &NodeTextStyle,
+    width: f32,
+    zoom: f32,
+    asset_server: &AssetServer,
+) -> BevyMarkdown {
+    BevyMarkdown {
+        text: internal_links_markdown(&text),
+        regular_font: Some(node_font(style.font, asset_server)),
+        code_font: Some(TextStyle::default().font),
+        bold_font: Some(asset_server.load("fonts/SourceCodePro-Bold.ttf")),
+        italic_font: Some(asset_server.load("fonts/SourceCodePro-Italic.ttf")),
+        extra_bold_font: Some(asset_server.load("fonts/SourceCodePro-ExtraBold.ttf")),
+        semi_bold_italic_font: Some(asset_server.load("fonts/SourceCodePro-SemiBoldItalic.ttf")),
+        width: Some(width * zoom),
+        font_size: Some(style.font_size * zoom),
+        color: Some(style.color),
+    }
+}
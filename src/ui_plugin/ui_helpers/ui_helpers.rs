@@ -10,10 +10,38 @@ pub use components::*;
 mod spawn_node;
 pub use spawn_node::*;
 
+#[path = "shapes.rs"]
+mod shapes;
+pub use shapes::*;
+
+#[path = "borders.rs"]
+mod borders;
+pub use borders::*;
+
+#[path = "shadows.rs"]
+mod shadows;
+pub use shadows::*;
+
+#[path = "minimap.rs"]
+mod minimap;
+pub use minimap::*;
+
+#[path = "color_picker.rs"]
+mod color_picker;
+pub use color_picker::*;
+
+#[path = "text_style.rs"]
+mod text_style;
+pub use text_style::*;
+
 #[path = "spawn_modal.rs"]
 mod spawn_modal;
 pub use spawn_modal::*;
 
+#[path = "spawn_prompt.rs"]
+mod spawn_prompt;
+pub use spawn_prompt::*;
+
 #[path = "add_tab.rs"]
 mod add_tab;
 pub use add_tab::*;
@@ -22,17 +50,17 @@ pub use add_tab::*;
 mod add_list_item;
 pub use add_list_item::*;
 
-fn get_marker_style(position: UiRect, size: f32) -> Style {
-    Style {
-        position_type: PositionType::Absolute,
-        position,
-        border: UiRect::all(Val::Px(1.)),
-        size: Size::new(Val::Px(size), Val::Px(size)),
-        justify_content: JustifyContent::Center,
-        align_items: AlignItems::Center,
-        ..default()
-    }
-}
+pub const ARROW_MARKER_SIZE: f32 = 6.;
+pub const ARROW_MARKER_HOVER_SIZE: f32 = 10.;
+pub const ARROW_MARKER_COLOR: Color = Color::rgba(0., 0., 1., 0.5);
+// Clicks this close to a connection point hit it
+pub const ARROW_MARKER_HIT_SIZE: f32 = 18.;
+pub const RESIZE_MARKER_SIZE: f32 = 10.;
+pub const EDGE_MARKER_THICKNESS: f32 = 6.;
+pub const ROTATION_HANDLE_SIZE: f32 = 10.;
+// Handles are drawn over the content of the node, connection points over resize handles
+const RESIZE_MARKER_Z: f32 = 0.6;
+const ARROW_MARKER_Z: f32 = 0.65;
 
 pub fn add_rectangle_txt(text: String) -> TextBundle {
     let text_style = TextStyle {
@@ -46,75 +74,108 @@ pub fn add_rectangle_txt(text: String) -> TextBundle {
     })
 }
 
-pub fn pos_to_style(text_pos: TextPos) -> (JustifyContent, AlignItems) {
-    match text_pos {
-        TextPos::TopLeft => (JustifyContent::FlexStart, AlignItems::FlexStart),
-        TextPos::Center => (JustifyContent::Center, AlignItems::Center),
-    }
-}
-
-pub fn style_to_pos(style: (JustifyContent, AlignItems)) -> TextPos {
-    match style {
-        (JustifyContent::FlexStart, AlignItems::FlexStart) => TextPos::TopLeft,
-        (JustifyContent::Center, AlignItems::Center) => TextPos::Center,
-        _ => panic!("Invalid style! {:?}", style),
-    }
-}
-
+/// Sprite of the node, the texture (if any) is drawn tinted by `bg_color`.
 fn create_rectangle_btn(
     bg_color: Color,
-    image: Option<UiImage>,
-    z_index: i32,
+    image: Option<Handle<Image>>,
     text_pos: TextPos,
-) -> ButtonBundle {
-    let (justify_content, align_items) = pos_to_style(text_pos);
-    let mut button = ButtonBundle {
-        background_color: bg_color.into(),
-        z_index: ZIndex::Local(z_index),
-        style: Style {
-            position_type: PositionType::Absolute,
-            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
-            justify_content,
-            align_items,
+) -> (SpriteBundle, Interaction, NodePart, TextPos) {
+    let mut sprite = SpriteBundle {
+        sprite: Sprite {
+            color: bg_color,
             ..default()
         },
         ..default()
     };
     if let Some(image) = image {
-        button.image = image;
+        sprite.texture = image;
     }
-    button
+    (sprite, Interaction::default(), NodePart::fill(0.), text_pos)
 }
 
-fn create_arrow_marker(left: f32, right: f32, top: f32, bottom: f32) -> ButtonBundle {
-    ButtonBundle {
-        style: get_marker_style(
-            UiRect {
-                left: Val::Percent(left),
-                right: Val::Percent(right),
-                top: Val::Percent(top),
-                bottom: Val::Percent(bottom),
+/// Transparent sprite picked like a button, e.g. a handle of a node. It's sized once the node
+/// is laid out.
+fn hit_zone(part: NodePart) -> (SpriteBundle, Interaction, NodePart) {
+    (
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0., 0., 0., 0.),
+                ..default()
             },
-            4.,
-        ),
-        ..default()
-    }
+            ..default()
+        },
+        Interaction::default(),
+        part,
+    )
+}
+
+/// Invisible hit zone centered on the connection point at `anchor` of the node, bigger than
+/// the dot drawn in it.
+fn create_arrow_marker(anchor: Vec2) -> (SpriteBundle, Interaction, NodePart) {
+    hit_zone(NodePart::handle(
+        anchor,
+        Vec2::ZERO,
+        Vec2::splat(ARROW_MARKER_HIT_SIZE),
+        ARROW_MARKER_Z,
+    ))
 }
 
-fn create_resize_marker(left: f32, right: f32, top: f32, bottom: f32) -> ButtonBundle {
-    ButtonBundle {
-        style: get_marker_style(
-            UiRect {
-                left: Val::Percent(left),
-                right: Val::Percent(right),
-                top: Val::Percent(top),
-                bottom: Val::Percent(bottom),
+/// Dot of a connection point: a white square framed by a square of `ARROW_MARKER_COLOR`.
+fn arrow_marker_dot(builder: &mut ChildBuilder) {
+    builder
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: ARROW_MARKER_COLOR,
+                    custom_size: Some(Vec2::splat(ARROW_MARKER_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., 0.01),
+                ..default()
             },
-            10.,
-        ),
-        background_color: Color::rgba(0., 0., 0., 0.).into(),
-        ..default()
-    }
+            ArrowMarkerDot,
+        ))
+        .with_children(|builder| {
+            builder.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE,
+                    custom_size: Some(Vec2::splat(ARROW_MARKER_SIZE - 2.)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., 0.01),
+                ..default()
+            });
+        });
+}
+
+/// Corner handle, it lies right of and below the corner at `anchor`.
+fn create_resize_marker(anchor: Vec2) -> (SpriteBundle, Interaction, NodePart) {
+    hit_zone(NodePart::handle(
+        anchor,
+        Vec2::new(RESIZE_MARKER_SIZE, -RESIZE_MARKER_SIZE) / 2.,
+        Vec2::splat(RESIZE_MARKER_SIZE),
+        RESIZE_MARKER_Z,
+    ))
+}
+
+/// Thin handle along the node edge at `anchor`, 10 pixels shorter than the edge at both ends.
+fn create_edge_resize_marker(anchor: Vec2) -> (SpriteBundle, Interaction, NodePart) {
+    let horizontal = anchor.x == 0.;
+    let (size, grow) = if horizontal {
+        (Vec2::new(-20., EDGE_MARKER_THICKNESS), Vec2::X)
+    } else {
+        (Vec2::new(EDGE_MARKER_THICKNESS, -20.), Vec2::Y)
+    };
+    hit_zone(NodePart {
+        anchor,
+        // inside of the node
+        offset: -anchor * EDGE_MARKER_THICKNESS,
+        size,
+        grow,
+        // below corner and arrow markers
+        z: RESIZE_MARKER_Z - 0.05,
+        screen_sized: true,
+    })
 }
 
 pub fn get_sections(text: String) -> (Vec<TextSection>, Vec<bool>) {
@@ -186,7 +247,13 @@ pub enum TooltipPosition {
     Bottom,
 }
 
-pub fn get_tooltip(text: String, size: f32, tooltip_position: TooltipPosition) -> TextBundle {
+/// Hidden tooltip text, translated to the language of the UI.
+pub fn get_tooltip(
+    text: String,
+    size: f32,
+    tooltip_position: TooltipPosition,
+) -> (TextBundle, Localized) {
+    let localized = Localized::new(text.clone());
     let text = Text {
         sections: vec![TextSection {
             value: text,
@@ -217,12 +284,15 @@ pub fn get_tooltip(text: String, size: f32, tooltip_position: TooltipPosition) -
         position,
         ..default()
     };
-    TextBundle {
-        z_index: ZIndex::Global(1),
-        visibility: Visibility::Hidden,
-        background_color: Color::WHITE.into(),
-        text,
-        style: text_bundle_style,
-        ..default()
-    }
+    (
+        TextBundle {
+            z_index: ZIndex::Global(1),
+            visibility: Visibility::Hidden,
+            background_color: Color::WHITE.into(),
+            text,
+            style: text_bundle_style,
+            ..default()
+        },
+        localized,
+    )
 }
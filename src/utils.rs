@@ -1,4 +1,8 @@
-use bevy::prelude::*;
+use base64::{engine::general_purpose, Engine};
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
 
 use bevy_cosmic_edit::CosmicTextPos;
 use serde::{Deserialize, Serialize};
@@ -46,6 +50,10 @@ pub fn load_doc_to_memory(
     if app_state.docs.contains_key(&doc_id) {
         return;
     }
+    // encrypted documents aren't in "docs", they're loaded once they're decrypted
+    let encrypted = pkv
+        .get::<HashMap<ReflectableUuid, Vec<u8>>>("encrypted_docs")
+        .map_or(false, |docs| docs.contains_key(&doc_id));
     if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
         if docs.contains_key(&doc_id) {
             let keys = app_state.docs.keys().cloned().collect::<Vec<_>>();
@@ -55,7 +63,7 @@ pub fn load_doc_to_memory(
             app_state
                 .docs
                 .insert(doc_id, docs.get(&doc_id).unwrap().clone());
-        } else {
+        } else if !encrypted {
             panic!("Document not found in pkv");
         }
     }
@@ -64,6 +72,14 @@ pub fn load_doc_to_memory(
 #[derive(Debug, Default)]
 pub struct Config {
     pub github_access_token: Option<String>,
+    pub grid_size: Option<f32>,
+    pub reduced_motion: Option<bool>,
+    pub collab_relay: Option<String>,
+    pub collab_name: Option<String>,
+    /// Settings of the `[sync]` table, the remote documents are synced with.
+    pub sync: Option<HashMap<String, String>>,
+    /// TOML or JSON file of a custom theme, relative to the home directory unless absolute.
+    pub theme_file: Option<PathBuf>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -78,9 +94,41 @@ pub fn read_config_file() -> Option<Config> {
             config.github_access_token = Some(token_str.to_owned());
         }
     }
+    if let Some(grid_size) = config_value.get("grid_size") {
+        if let Some(grid_size) = grid_size.as_float() {
+            config.grid_size = Some(grid_size as f32);
+        } else if let Some(grid_size) = grid_size.as_integer() {
+            config.grid_size = Some(grid_size as f32);
+        }
+    }
+    if let Some(reduced_motion) = config_value.get("reduced_motion") {
+        config.reduced_motion = reduced_motion.as_bool();
+    }
+    if let Some(relay) = config_value.get("collab_relay") {
+        config.collab_relay = relay.as_str().map(str::to_owned);
+    }
+    if let Some(name) = config_value.get("collab_name") {
+        config.collab_name = name.as_str().map(str::to_owned);
+    }
+    if let Some(theme_file) = config_value.get("theme_file").and_then(toml::Value::as_str) {
+        config.theme_file = Some(PathBuf::from(&home_dir).join(theme_file));
+    }
+    if let Some(sync) = config_value.get("sync").and_then(toml::Value::as_table) {
+        config.sync = Some(
+            sync.iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_owned())))
+                .collect(),
+        );
+    }
     Some(config)
 }
 
+/// Value following `current` in `values`, wrapping around (first value if `current` is not found).
+pub fn next_in_cycle<T: PartialEq + Copy>(values: &[T], current: T) -> T {
+    let index = values.iter().position(|v| *v == current);
+    values[index.map_or(0, |i| (i + 1) % values.len())]
+}
+
 pub fn convert_from_val_px(x: Val) -> f32 {
     match x {
         Val::Px(x) => x,
@@ -88,6 +136,33 @@ pub fn convert_from_val_px(x: Val) -> f32 {
     }
 }
 
+pub fn encode_image_base64(image: &Image) -> Option<String> {
+    let img = image.clone().try_into_dynamic().ok()?;
+    let mut image_data: Vec<u8> = Vec::new();
+    img.write_to(
+        &mut std::io::Cursor::new(&mut image_data),
+        image::ImageOutputFormat::Png,
+    )
+    .unwrap();
+    Some(general_purpose::STANDARD.encode(image_data))
+}
+
+pub fn decode_image_base64(data: &str) -> Image {
+    let image_bytes = general_purpose::STANDARD.decode(data.as_bytes()).unwrap();
+    let img = image::load_from_memory_with_format(&image_bytes, image::ImageFormat::Png).unwrap();
+    let size: Extent3d = Extent3d {
+        width: img.width(),
+        height: img.height(),
+        ..Default::default()
+    };
+    Image::new(
+        size,
+        TextureDimension::D2,
+        img.into_bytes(),
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
 pub fn to_cosmic_text_pos(pos: TextPos) -> CosmicTextPos {
     match pos {
         TextPos::Center => CosmicTextPos::Center,